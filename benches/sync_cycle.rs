@@ -0,0 +1,46 @@
+//! `cargo bench`: one-note sync cycles and `git status` in generated vaults
+//! of growing size. `obsyncgit bench` breaks a cycle down into its phases.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use obsyncgit::bench;
+use obsyncgit::git::GitFacade;
+use obsyncgit::testsupport::Sandbox;
+
+const SIZES: [usize; 2] = [1_000, 10_000];
+
+fn sync_cycle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sync_cycle");
+    group.sample_size(10);
+    for files in SIZES {
+        let sandbox = Sandbox::new().expect("failed to create sandbox");
+        let (mut device, _) = bench::vault(&sandbox, files).expect("failed to generate vault");
+        let mut revision = 0;
+        group.bench_with_input(BenchmarkId::from_parameter(files), &files, |b, &files| {
+            b.iter(|| {
+                revision += 1;
+                let index = (revision * 7919) % files;
+                device
+                    .write(&bench::note_path(index), &bench::note(index, revision))
+                    .expect("failed to edit note");
+                device.sync().expect("sync failed");
+            })
+        });
+    }
+    group.finish();
+}
+
+fn status(c: &mut Criterion) {
+    let mut group = c.benchmark_group("status");
+    for files in SIZES {
+        let sandbox = Sandbox::new().expect("failed to create sandbox");
+        let (device, _) = bench::vault(&sandbox, files).expect("failed to generate vault");
+        let git = GitFacade::new(device.config()).expect("failed to open vault");
+        group.bench_with_input(BenchmarkId::from_parameter(files), &files, |b, _| {
+            b.iter(|| git.list_pending_paths().expect("status failed"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sync_cycle, status);
+criterion_main!(benches);
@@ -0,0 +1,331 @@
+# English messages. Every message needs an entry here: it is the fallback
+# for languages that lack one.
+
+## Desktop helper: labels
+
+gui-title = ObsyncGit Control Center
+gui-tab-settings = Settings
+gui-tab-history = History
+gui-repo-url = Repository URL
+gui-branch = Branch
+gui-remote = Remote
+gui-workdir = Working Directory
+gui-author-name = Author Name
+gui-author-email = Author Email
+gui-ssh-key = SSH Key
+gui-auto-update = Automatic updates
+gui-update-interval = Interval (hours)
+gui-autostart = Launch ObsyncGit at login
+gui-autostart-unsupported = Autostart control is unavailable on this platform.
+gui-save = Save
+gui-manual-update = Manual Update
+gui-exit = Exit
+gui-history-intro = Recent changes. Restoring a note puts that version back in the vault; the daemon then syncs it like any other edit.
+gui-refresh = Refresh
+gui-history-empty = No history yet.
+gui-restore = Restore
+gui-conflicts-title = Notes changed on two devices
+gui-conflicts-intro = Pick the version to keep for each note, then apply. Keeping both saves the other device's version next to yours as "<name> (other device)".
+gui-this-device = This device
+gui-other-device = Other device
+gui-keep-mine = Keep mine
+gui-keep-theirs = Keep theirs
+gui-keep-both = Keep both
+gui-back = Back
+gui-apply = Apply
+gui-review = Review
+gui-theme = Appearance
+gui-theme-system = Same as system
+gui-theme-light = Light
+gui-theme-dark = Dark
+gui-high-contrast = High contrast
+gui-conflict-list = Conflicting notes
+gui-line-changed = Differs
+gui-line-missing = missing
+gui-conflicts-banner = { $count ->
+    [one] A note was changed on two devices and cannot sync until you choose which version to keep.
+   *[other] { $count } notes were changed on two devices and cannot sync until you choose which version to keep.
+}
+gui-conflict-choice = { $choice ->
+    [mine] keep mine
+    [theirs] keep theirs
+   *[both] keep both
+}
+gui-change = { $change ->
+    [added] added
+    [deleted] deleted
+   *[modified] modified
+}
+
+## Desktop helper: status messages
+
+gui-host-key = SSH host key
+gui-check-host-key = Check
+gui-trust-host-key = Trust these keys
+gui-host-key-not-ssh = The repository is not reached over SSH, so there is no host key to check.
+gui-host-key-pinned = The keys of { $host } are pinned in known_hosts.
+gui-host-key-unknown = { $host } is not trusted yet, so syncing cannot connect to it. Check its key to see the fingerprints.
+gui-host-key-checking = Fetching the keys of { $host }…
+gui-host-key-offered = { $host } offers these keys. Compare them with the fingerprints the host publishes before trusting them:
+gui-host-key-offered-pinned = Keys of { $host } are pinned already. If syncing reports that they changed, compare these with the fingerprints the host publishes before replacing the pinned ones:
+gui-host-key-failed = Could not check the host key: { $error }
+gui-host-key-trusted = { $count ->
+    [one] Trusted one key of { $host }
+   *[other] Trusted { $count } keys of { $host }
+}
+gui-saved = Saved at { $time }
+gui-save-failed = Save failed: { $error }
+gui-update-checking = Checking for updates…
+gui-update-installed = Installed a new version; the daemon picks it up after its next restart
+gui-update-current = ObsyncGit is up to date
+gui-update-failed = Manual update failed: { $error }
+gui-autostart-unavailable = Autostart status unavailable: { $error }
+gui-autostart-failed = Autostart update failed: { $error }
+gui-autostart-enabled = Autostart enabled
+gui-autostart-disabled = Autostart disabled
+gui-choice-failed = Could not record choice: { $error }
+gui-conflicts-applied = { $count ->
+    [one] Kept your choice for one note; it syncs on the next cycle
+   *[other] Kept your choice for { $count } notes; they sync on the next cycle
+}
+gui-conflicts-failed = Resolving conflicts failed: { $error }
+gui-history-failed = Could not load history: { $error }
+gui-restored = Restored { $path } from { $commit }; it syncs on the next cycle
+gui-restore-failed = Restore failed: { $error }
+gui-clone-progress = Cloning the vault: { $phase } { $percent }%
+gui-clone-progress-rate = Cloning the vault: { $phase } { $percent }% at { $rate }
+gui-clone-done = The vault is cloned and syncing
+
+## Tray menu
+
+tray-sync-now = Sync now
+tray-pause = Pause for 1 hour
+tray-open-vault = Open vault folder
+tray-open-repo = Open repository page
+tray-show = Show
+tray-quit = Quit
+tray-sync-requested = Sync requested
+tray-paused = Sync paused for an hour; choose Sync now to resume earlier
+tray-opened = Opened { $target }
+
+## Status bars
+
+bar-synced = synced
+bar-pending = { $count } pending
+bar-unpushed = not pushed
+bar-paused = paused
+bar-stopped = stopped
+bar-conflict = { $count ->
+    [one] conflict
+   *[other] { $count } conflicts
+}
+bar-error = error
+bar-vault = Vault: { $path }
+bar-branch = Branch: { $branch }
+bar-daemon-running = Daemon: running (PID { $pid })
+bar-daemon-stopped = Daemon: not running
+bar-paused-for = Paused for { $minutes } more min
+bar-pending-count = { $count ->
+    [one] { $count } changed path
+   *[other] { $count } changed paths
+}
+bar-conflict-hint = { $count ->
+    [one] A note needs a choice: run `obsyncgit conflicts` or open obsyncgit-gui
+   *[other] { $count } notes need a choice: run `obsyncgit conflicts` or open obsyncgit-gui
+}
+bar-damaged = { $count ->
+    [one] git fsck found the repository damaged: run `obsyncgit repair`
+   *[other] git fsck found { $count } problems in the repository: run `obsyncgit repair`
+}
+bar-last-error = Last sync failed: { $cause }
+bar-held-secrets = { $count ->
+    [one] A file that looks like a secret is not synced: run `obsyncgit status`
+   *[other] { $count } files that look like secrets are not synced: run `obsyncgit status`
+}
+bar-refresh = Refresh
+
+## Git failures
+
+git-error-auth = the remote refused the credentials
+git-error-unknown-host = the SSH host key of the remote is not trusted yet; run obsyncgit trust-host
+git-error-host-key-changed = the SSH host key of the remote has changed; check it before trusting it again
+git-error-network = the remote could not be reached
+git-error-conflict = notes were changed here and on another device
+git-error-non-fast-forward = the remote has commits this device has not pulled yet
+git-error-lock-held = another git process holds the vault's lock
+git-error-disk-full = the disk is full
+git-error-hook = a git hook of the vault refused the commit
+git-error-unknown = git failed; see the daemon log
+
+## Conflicts
+
+conflict-deleted-here = deleted here, edited on another device
+conflict-deleted-there = edited here, deleted on another device
+conflict-edited-both = edited here and on another device
+conflict-copy = conflict copy made by { $tool }: { $copy }
+
+## Command line
+
+cli-config-created = Created configuration at { $path }. Edit this file before running `obsyncgit run`.
+cli-bundle-written = Wrote setup bundle to { $path }. On the new device run `obsyncgit import-setup { $path }`.
+cli-bundle-imported = Created configuration at { $path } for a vault in { $workdir }.
+cli-bundle-start = Start syncing with `obsyncgit run`; the vault is cloned on first start.
+cli-update-disabled = Auto-updates are disabled in the configuration. Re-run with --force or enable them via "obsyncgit settings set self-update.enabled true".
+cli-update-installed = Installed a new version. Running daemons pick it up after their next restart.
+cli-update-done = Self-update check completed.
+cli-update-still-disabled = Auto-updates are currently disabled. Enable them with `obsyncgit settings set self-update.enabled true` if desired.
+cli-rescan-done = Vault reconciled with the remote.
+cli-repair-healthy = git fsck found no damage; nothing to repair. Use --force to replace the repository with a fresh clone anyway.
+cli-repair-damaged = { $count ->
+    [one] git fsck found one problem:
+   *[other] git fsck found { $count } problems:
+}
+cli-repair-copy = Kept your version of { $path } as { $copy }; compare the two and delete one.
+cli-repair-done = Replaced the repository with a fresh clone. The damaged vault is kept in { $path }.
+cli-repair-kept = { $count ->
+    [one] Carried one local change over into the fresh clone.
+   *[other] Carried { $count } local changes over into the fresh clone.
+}
+cli-backup-written = Wrote a backup bundle to { $path }.
+cli-backup-restored = Restored the vault from { $bundle } into { $path }.
+cli-backup-restored-vault = Start syncing with `obsyncgit run`. If the remote lost the repository, point repo_url at a new empty one first; the restored history is pushed to it.
+cli-backup-restored-beside = The vault itself was left alone. Compare the two, or stop the daemon and move the restored folder into the vault's place.
+cli-trust-host-known = { $host } already has keys in known_hosts. If ssh reports that the key changed, confirm the new fingerprint with the host, then run `obsyncgit trust-host --replace`.
+cli-trust-host-keys = { $host } offers these SSH host keys:
+cli-trust-host-compare = Compare them with the fingerprints the host publishes (GitHub, GitLab and others list them in their documentation), or ask the host's administrator.
+cli-trust-host-prompt = Trust these keys?
+cli-trust-host-declined = Nothing was pinned.
+cli-trust-host-pinned = { $count ->
+    [one] Pinned one key of { $host } in { $path }.
+   *[other] Pinned { $count } keys of { $host } in { $path }.
+}
+cli-launch-agent-installed = Installed the LaunchAgent at { $path }; the daemon starts now and at every login.
+cli-service-installed = Installed the { $name } service; it starts at boot. Start it now with `obsyncgit service start`. It logs to obsyncgit-service.log next to the configuration.
+cli-service-uninstalled = Removed the { $name } service.
+cli-service-started = Started the { $name } service.
+cli-service-stopped = Stopped the { $name } service.
+cli-setup-cancelled = Setup cancelled; nothing was written.
+cli-conflicts-resolved = { $count ->
+    [one] Resolved one conflict; the daemon pushes the result on its next sync.
+   *[other] Resolved { $count } conflicts; the daemon pushes the result on its next sync.
+}
+cli-conflicts-none = No conflicts.
+cli-conflicts-hint = Choose a version for each with `obsyncgit conflicts --mine PATH --theirs PATH --both PATH`, or use obsyncgit-gui.
+cli-conflict-copies-hint = For a conflict copy, --mine keeps the note and deletes the copy, --theirs replaces the note with it, and --both keeps it as "<name> (other device)". Pass the note's path or the copy's.
+cli-case-none = No paths differ only in letter case.
+cli-case-hint = macOS and Windows keep only one path of each group. Rename all but one with `obsyncgit case-collisions --rename`, or set sync.case_collisions to rename.
+cli-case-renamed = { $count ->
+    [one] Renamed one file; the daemon pushes the result on its next sync.
+   *[other] Renamed { $count } files; the daemon pushes the result on its next sync.
+}
+cli-file-names-none = Every file name can be checked out on Windows.
+cli-file-names-hint = Windows devices cannot check these out. Rename them with `obsyncgit file-names --rename`, or set sync.file_names to rename.
+cli-file-names-renamed = { $count ->
+    [one] Renamed one file and listed it in { $record }; the daemon commits the rename on its next sync.
+   *[other] Renamed { $count } files and listed them in { $record }; the daemon commits the renames on its next sync.
+}
+cli-unicode-none = Every file name is in the composed Unicode form.
+cli-unicode-hint = These names are decomposed, as macOS can write them; other devices may see them as different files from the same names typed there. Rename them with `obsyncgit unicode-names --rename`, or set compat.unicode_normalization to nfc.
+cli-unicode-renamed = { $count ->
+    [one] Renamed one file; the daemon syncs the result on its next sync.
+   *[other] Renamed { $count } files; the daemon syncs the result on its next sync.
+}
+cli-ignore-watcher-rule = Watcher: { $pattern } ({ $source })
+cli-ignore-git-rule = git: { $pattern } ({ $file }:{ $line })
+cli-ignore-blocked-extension = Blocked: .{ $extension } is in ignore.extensions_blocklist
+cli-ignore-blocked-size = Blocked: { $size } MB is over ignore.max_file_size_mb ({ $limit } MB)
+cli-ignore-no-rules = No ignore rule matches.
+cli-ignore-ignored = { $path } is ignored and never committed.
+cli-ignore-synced = { $path } is synced.
+cli-ignore-synced-quietly = { $path } is synced, but editing it alone does not start a sync; the edits go out with the next other change.
+cli-ignore-tracked-hint = git keeps committing a file it already tracks, whatever .gitignore says. Stop tracking it with `git rm --cached "{ $path }"`; the file stays on disk.
+cli-ignore-globs-hint = ignore.globs only keeps edits from starting a sync. Add the pattern to the vault's .gitignore to keep the file out of commits.
+cli-log-empty = No saved versions of { $path }.
+cli-log-hint = Restore a version with `obsyncgit restore { $path } --commit <id>`.
+cli-restored = Restored { $path } from { $commit }. A running daemon commits and syncs it like any other edit.
+cli-exported = Exported the vault as of { $commit } to { $path }.
+cli-import-summary = { $count ->
+    [one] Imported one file
+   *[other] Imported { $count } files
+}; { $unchanged } already in the vault, { $skipped } older than the vault's version.
+cli-import-vault-newer = the vault's version is newer
+cli-import-dry-run = Dry run: nothing was written.
+cli-import-committed = Committed as { $commit } and tagged { $tag }; the daemon pushes it on its next sync.
+cli-import-held = Not committed, as the daemon holds these back: { $paths }
+cli-digest-sent = Sent the digest; a new period starts now.
+cli-digest-next = The next digest goes out in { $time }.
+cli-digest-off = The daemon sends no digests; turn them on with `obsyncgit settings set digest.enabled true`.
+digest-title-ok = ObsyncGit: syncing is healthy
+digest-title-attention = ObsyncGit: syncing needs attention
+digest-body = Last { $hours } h: syncs { $syncs }, notes changed { $notes }, conflicts { $conflicts }, errors { $errors }.
+digest-last-error = Last error: { $cause }
+cli-alerts-off = No alerts are sent; set alerts.email, alerts.telegram or alerts.discord to hear when syncing keeps failing.
+cli-alerts-email = Alerts go by mail to { $to } through { $server }.
+cli-alerts-telegram = Alerts go to Telegram chat { $chat }.
+cli-alerts-discord = Alerts go to a Discord webhook on { $host }.
+cli-alerts-failures = { $count ->
+    [one] An alert goes out when a sync fails.
+   *[other] An alert goes out once { $count } syncs in a row failed.
+}
+cli-alerts-failures-off = Failed syncs send no alerts.
+cli-alerts-conflicts = Notes in a conflict send an alert.
+cli-alerts-sent = Sent a test alert by { $channel }.
+cli-alerts-send-failed = Could not send a test alert by { $channel }: { $error }
+alert-test-subject = ObsyncGit: test alert
+alert-test-body = Alerts about { $vault } on { $device } arrive here.
+alert-failing-subject = ObsyncGit: syncing { $vault } keeps failing
+alert-failing-body = { $failures ->
+    [one] The last sync of { $vault } on { $device } failed.
+   *[other] The last { $failures } syncs of { $vault } on { $device } failed.
+}
+alert-cause = Cause: { $cause }
+alert-failing-hint = The daemon keeps trying; `obsyncgit status` and `obsyncgit doctor` tell more. Another alert follows once syncing works again.
+alert-recovered-subject = ObsyncGit: { $vault } syncs again
+alert-recovered-body = { $failures ->
+    [one] { $vault } on { $device } reached the remote again after a failed sync.
+   *[other] { $vault } on { $device } reached the remote again after { $failures } failed syncs.
+}
+alert-conflict-subject = ObsyncGit: notes in a conflict in { $vault }
+alert-conflict-body = { $count ->
+    [one] This note was changed on { $device } and on another device:
+   *[other] These notes were changed on { $device } and on another device:
+}
+alert-conflict-hint = They do not sync until you choose which version to keep, in obsyncgit-gui or with `obsyncgit conflicts`.
+cli-snapshots-none = No daily snapshots yet. Turn them on with `obsyncgit settings set snapshots.tag-daily true`; each day is tagged once it has ended.
+cli-prune-none = No old attachment versions to drop; every version under retention.paths is still in a recent commit.
+cli-prune-plan = { $commits } commit(s) from before { $cutoff } hold { $versions } attachment version(s) that no later commit has, up to { $size } MB.
+cli-prune-warning = Pruning rewrites the history of every commit and force-pushes it. Other devices pick the new history up on their next sync; one with unpushed merges will ask for `obsyncgit repair --force`.
+cli-prune-prompt = Drop these versions from the history?
+cli-prune-declined = The history was left alone.
+cli-prune-done = Pruned the history and pushed it. The old history is saved in { $path }.
+cli-prune-tags = { $count ->
+    [one] Moved one tag onto the rewritten commits.
+   *[other] Moved { $count } tags onto the rewritten commits.
+}
+cli-preflight-empty = The vault's branch does not exist on the remote yet, so the first start downloads nothing.
+cli-preflight-needed = The first clone needs about { $needed } MB of disk space.
+cli-preflight-needed-lfs = The first clone needs about { $needed } MB of disk space, { $lfs } MB of it for Git LFS files.
+cli-preflight-free = { $available } MB are free at { $path }.
+cli-preflight-short = That leaves less than disk.min_free_mb ({ $min } MB), so the daemon will not clone the vault; free some space or choose a workdir on another disk.
+cli-preflight-unknown = Could not tell how large the first clone will be: { $reason }
+cli-history-empty = No commits yet.
+cli-history-hint = No commit has sync notes; set commit.notes to record the device, file count and delay of each commit.
+cli-events-empty = No events yet; the daemon records them while it runs.
+cli-events-change = Change detected: { $paths }
+cli-events-commit = Committed { $commit } ({ $count ->
+    [one] { $count } file
+   *[other] { $count } files
+}): { $files }
+cli-events-amend = Amended { $commit } ({ $count ->
+    [one] { $count } file
+   *[other] { $count } files
+}): { $files }
+cli-events-clone = Cloning the vault: { $phase } { $percent }%
+cli-events-clone-rate = Cloning the vault: { $phase } { $percent }% at { $rate }
+cli-events-push = Pushed to { $branch }
+cli-events-pull = Pulled { $commit }, { $count ->
+    [one] { $count } file changed
+   *[other] { $count } files changed
+}: { $files }
+cli-events-error = Sync failed ({ $category }): { $message }
+cli-man-written = Wrote manual pages to { $dir }.
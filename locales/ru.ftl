@@ -0,0 +1,346 @@
+# Русские сообщения. Чего здесь нет, берётся из en.ftl.
+
+## Графическое приложение: подписи
+
+gui-title = Центр управления ObsyncGit
+gui-tab-settings = Настройки
+gui-tab-history = История
+gui-repo-url = URL репозитория
+gui-branch = Ветка
+gui-remote = Remote
+gui-workdir = Папка хранилища
+gui-author-name = Имя автора
+gui-author-email = Email автора
+gui-ssh-key = SSH-ключ
+gui-auto-update = Автоматические обновления
+gui-update-interval = Интервал (часы)
+gui-autostart = Запускать ObsyncGit при входе в систему
+gui-autostart-unsupported = Автозапуск на этой платформе недоступен.
+gui-save = Сохранить
+gui-manual-update = Обновить сейчас
+gui-exit = Выход
+gui-history-intro = Последние изменения. Восстановленная версия заметки возвращается в хранилище, и демон синхронизирует её как обычную правку.
+gui-refresh = Обновить
+gui-history-empty = Истории пока нет.
+gui-restore = Восстановить
+gui-conflicts-title = Заметки изменены на двух устройствах
+gui-conflicts-intro = Выберите, какую версию каждой заметки оставить, и примените. При выборе «Обе» версия с другого устройства сохраняется рядом с вашей как «<имя> (other device)».
+gui-this-device = Это устройство
+gui-other-device = Другое устройство
+gui-keep-mine = Мою
+gui-keep-theirs = Другую
+gui-keep-both = Обе
+gui-back = Назад
+gui-apply = Применить
+gui-review = Разобрать
+gui-theme = Оформление
+gui-theme-system = Как в системе
+gui-theme-light = Светлое
+gui-theme-dark = Тёмное
+gui-high-contrast = Высокая контрастность
+gui-conflict-list = Заметки с конфликтами
+gui-line-changed = Отличается
+gui-line-missing = нет строки
+gui-conflicts-banner = { $count ->
+    [one] { $count } заметка изменена на двух устройствах и не синхронизируется, пока вы не выберете, какую версию оставить.
+    [few] { $count } заметки изменены на двух устройствах и не синхронизируются, пока вы не выберете, какую версию оставить.
+   *[other] { $count } заметок изменены на двух устройствах и не синхронизируются, пока вы не выберете, какую версию оставить.
+}
+gui-conflict-choice = { $choice ->
+    [mine] оставить мою
+    [theirs] оставить другую
+   *[both] оставить обе
+}
+gui-change = { $change ->
+    [added] добавлена
+    [deleted] удалена
+   *[modified] изменена
+}
+
+## Графическое приложение: сообщения
+
+gui-host-key = SSH-ключ сервера
+gui-check-host-key = Проверить
+gui-trust-host-key = Доверять этим ключам
+gui-host-key-not-ssh = Репозиторий доступен не по SSH, проверять ключ сервера не нужно.
+gui-host-key-pinned = Ключи { $host } закреплены в known_hosts.
+gui-host-key-unknown = { $host } ещё не признан доверенным, поэтому синхронизация не может к нему подключиться. Проверьте ключ, чтобы увидеть отпечатки.
+gui-host-key-checking = Получение ключей { $host }…
+gui-host-key-offered = { $host } предлагает такие ключи. Сравните их с отпечатками, которые публикует сервер, прежде чем доверять им:
+gui-host-key-offered-pinned = Ключи { $host } уже закреплены. Если синхронизация сообщает, что они изменились, сравните эти отпечатки с опубликованными, прежде чем заменить закреплённые:
+gui-host-key-failed = Не удалось проверить ключ сервера: { $error }
+gui-host-key-trusted = { $count ->
+    [one] Добавлен { $count } ключ { $host }
+    [few] Добавлено { $count } ключа { $host }
+   *[other] Добавлено { $count } ключей { $host }
+}
+gui-saved = Сохранено { $time }
+gui-save-failed = Не удалось сохранить: { $error }
+gui-update-checking = Проверка обновлений…
+gui-update-installed = Установлена новая версия; демон начнёт её использовать после перезапуска
+gui-update-current = Установлена последняя версия ObsyncGit
+gui-update-failed = Не удалось обновить: { $error }
+gui-autostart-unavailable = Состояние автозапуска недоступно: { $error }
+gui-autostart-failed = Не удалось изменить автозапуск: { $error }
+gui-autostart-enabled = Автозапуск включён
+gui-autostart-disabled = Автозапуск выключен
+gui-choice-failed = Не удалось запомнить выбор: { $error }
+gui-conflicts-applied = { $count ->
+    [one] Выбор сохранён для { $count } заметки; она синхронизируется в следующем цикле
+   *[other] Выбор сохранён для { $count } заметок; они синхронизируются в следующем цикле
+}
+gui-conflicts-failed = Не удалось разрешить конфликты: { $error }
+gui-history-failed = Не удалось загрузить историю: { $error }
+gui-restored = { $path } восстановлена из { $commit }; она синхронизируется в следующем цикле
+gui-restore-failed = Не удалось восстановить: { $error }
+gui-clone-progress = Клонирование хранилища: { $phase } { $percent }%
+gui-clone-progress-rate = Клонирование хранилища: { $phase } { $percent }%, { $rate }
+gui-clone-done = Хранилище склонировано и синхронизируется
+
+## Меню в трее
+
+tray-sync-now = Синхронизировать
+tray-pause = Пауза на 1 час
+tray-open-vault = Открыть папку хранилища
+tray-open-repo = Открыть страницу репозитория
+tray-show = Показать
+tray-quit = Выйти
+tray-sync-requested = Синхронизация запрошена
+tray-paused = Синхронизация приостановлена на час; «Синхронизировать» возобновит её раньше
+tray-opened = Открыто: { $target }
+
+## Панели состояния
+
+bar-synced = синхронизировано
+bar-pending = { $count } в очереди
+bar-unpushed = не отправлено
+bar-paused = на паузе
+bar-stopped = остановлено
+bar-conflict = { $count ->
+    [one] { $count } конфликт
+    [few] { $count } конфликта
+   *[other] { $count } конфликтов
+}
+bar-error = ошибка
+bar-vault = Хранилище: { $path }
+bar-branch = Ветка: { $branch }
+bar-daemon-running = Демон: работает (PID { $pid })
+bar-daemon-stopped = Демон: не запущен
+bar-paused-for = Пауза ещё { $minutes } мин
+bar-pending-count = { $count ->
+    [one] { $count } изменённый путь
+    [few] { $count } изменённых пути
+   *[other] { $count } изменённых путей
+}
+bar-conflict-hint = { $count ->
+    [one] { $count } заметка ждёт выбора: `obsyncgit conflicts` или obsyncgit-gui
+    [few] { $count } заметки ждут выбора: `obsyncgit conflicts` или obsyncgit-gui
+   *[other] { $count } заметок ждут выбора: `obsyncgit conflicts` или obsyncgit-gui
+}
+bar-damaged = { $count ->
+    [one] git fsck нашёл { $count } повреждение в репозитории: выполните `obsyncgit repair`
+    [few] git fsck нашёл { $count } повреждения в репозитории: выполните `obsyncgit repair`
+   *[other] git fsck нашёл { $count } повреждений в репозитории: выполните `obsyncgit repair`
+}
+bar-last-error = Последняя синхронизация не удалась: { $cause }
+bar-held-secrets = { $count ->
+    [one] { $count } файл похож на секрет и не синхронизируется: `obsyncgit status`
+    [few] { $count } файла похожи на секреты и не синхронизируются: `obsyncgit status`
+   *[other] { $count } файлов похожи на секреты и не синхронизируются: `obsyncgit status`
+}
+bar-refresh = Обновить
+
+## Ошибки git
+
+git-error-auth = удалённый репозиторий отклонил учётные данные
+git-error-unknown-host = SSH-ключ сервера ещё не признан доверенным; выполните obsyncgit trust-host
+git-error-host-key-changed = SSH-ключ сервера изменился; проверьте его, прежде чем снова доверять
+git-error-network = удалённый репозиторий недоступен
+git-error-conflict = заметки изменены здесь и на другом устройстве
+git-error-non-fast-forward = в удалённом репозитории есть коммиты, ещё не полученные этим устройством
+git-error-lock-held = другой процесс git держит блокировку хранилища
+git-error-disk-full = диск заполнен
+git-error-hook = git-хук хранилища отклонил коммит
+git-error-unknown = ошибка git; подробности в журнале демона
+
+## Конфликты
+
+conflict-deleted-here = удалена здесь, изменена на другом устройстве
+conflict-deleted-there = изменена здесь, удалена на другом устройстве
+conflict-edited-both = изменена здесь и на другом устройстве
+conflict-copy = конфликтная копия от { $tool }: { $copy }
+
+## Командная строка
+
+cli-config-created = Конфигурация создана: { $path }. Отредактируйте этот файл перед запуском `obsyncgit run`.
+cli-bundle-written = Пакет настройки записан в { $path }. На новом устройстве выполните `obsyncgit import-setup { $path }`.
+cli-bundle-imported = Конфигурация создана: { $path }, хранилище в { $workdir }.
+cli-bundle-start = Запустите синхронизацию командой `obsyncgit run`; хранилище будет клонировано при первом запуске.
+cli-update-disabled = Автообновления выключены в конфигурации. Повторите с --force или включите их: "obsyncgit settings set self-update.enabled true".
+cli-update-installed = Установлена новая версия. Запущенные демоны перейдут на неё после перезапуска.
+cli-update-done = Проверка обновлений завершена.
+cli-update-still-disabled = Автообновления сейчас выключены. При желании включите их: `obsyncgit settings set self-update.enabled true`.
+cli-rescan-done = Хранилище сверено с удалённым репозиторием.
+cli-repair-healthy = git fsck не нашёл повреждений; чинить нечего. Чтобы всё равно заменить репозиторий свежим клоном, добавьте --force.
+cli-repair-damaged = { $count ->
+    [one] git fsck нашёл { $count } проблему:
+    [few] git fsck нашёл { $count } проблемы:
+   *[other] git fsck нашёл { $count } проблем:
+}
+cli-repair-copy = Ваша версия { $path } сохранена как { $copy }; сравните их и удалите лишнюю.
+cli-repair-done = Репозиторий заменён свежим клоном. Повреждённое хранилище сохранено в { $path }.
+cli-repair-kept = { $count ->
+    [one] В свежий клон перенесено { $count } локальное изменение.
+    [few] В свежий клон перенесено { $count } локальных изменения.
+   *[other] В свежий клон перенесено { $count } локальных изменений.
+}
+cli-backup-written = Резервная копия записана в { $path }.
+cli-backup-restored = Хранилище восстановлено из { $bundle } в { $path }.
+cli-backup-restored-vault = Запустите синхронизацию командой `obsyncgit run`. Если удалённый репозиторий потерян, сначала укажите в repo_url новый пустой — восстановленная история будет отправлена в него.
+cli-backup-restored-beside = Само хранилище не тронуто. Сравните их или остановите демон и переместите восстановленную папку на место хранилища.
+cli-trust-host-known = Ключи { $host } уже есть в known_hosts. Если ssh сообщает, что ключ изменился, сверьте новый отпечаток с владельцем сервера и выполните `obsyncgit trust-host --replace`.
+cli-trust-host-keys = { $host } предлагает такие SSH-ключи:
+cli-trust-host-compare = Сравните их с отпечатками, которые публикует сервер (GitHub, GitLab и другие приводят их в документации), или уточните у администратора.
+cli-trust-host-prompt = Доверять этим ключам?
+cli-trust-host-declined = Ничего не добавлено.
+cli-trust-host-pinned = { $count ->
+    [one] Добавлен { $count } ключ { $host } в { $path }.
+    [few] Добавлено { $count } ключа { $host } в { $path }.
+   *[other] Добавлено { $count } ключей { $host } в { $path }.
+}
+cli-launch-agent-installed = LaunchAgent установлен в { $path }; демон запускается сейчас и при каждом входе в систему.
+cli-service-installed = Служба { $name } установлена и запускается при загрузке. Чтобы запустить её сейчас, выполните `obsyncgit service start`. Журнал службы — obsyncgit-service.log рядом с конфигурацией.
+cli-service-uninstalled = Служба { $name } удалена.
+cli-service-started = Служба { $name } запущена.
+cli-service-stopped = Служба { $name } остановлена.
+cli-setup-cancelled = Настройка отменена; ничего не записано.
+cli-conflicts-resolved = { $count ->
+    [one] Разрешён { $count } конфликт; демон отправит результат при следующей синхронизации.
+    [few] Разрешено { $count } конфликта; демон отправит результат при следующей синхронизации.
+   *[other] Разрешено { $count } конфликтов; демон отправит результат при следующей синхронизации.
+}
+cli-conflicts-none = Конфликтов нет.
+cli-conflicts-hint = Выберите версию для каждого файла: `obsyncgit conflicts --mine ПУТЬ --theirs ПУТЬ --both ПУТЬ`, или откройте obsyncgit-gui.
+cli-conflict-copies-hint = Для конфликтной копии --mine оставляет заметку и удаляет копию, --theirs заменяет заметку копией, а --both сохраняет копию как «<имя> (other device)». Укажите путь заметки или копии.
+cli-case-none = Путей, отличающихся только регистром букв, нет.
+cli-case-hint = macOS и Windows сохраняют только один путь из каждой группы. Переименуйте остальные: `obsyncgit case-collisions --rename`, или задайте sync.case_collisions: rename.
+cli-case-renamed = { $count ->
+    [one] Переименован { $count } файл; демон отправит результат при следующей синхронизации.
+    [few] Переименовано { $count } файла; демон отправит результат при следующей синхронизации.
+   *[other] Переименовано { $count } файлов; демон отправит результат при следующей синхронизации.
+}
+cli-file-names-none = Все имена файлов допустимы в Windows.
+cli-file-names-hint = Устройства с Windows не смогут получить эти файлы. Переименуйте их: `obsyncgit file-names --rename`, или задайте sync.file_names: rename.
+cli-file-names-renamed = { $count ->
+    [one] Переименован { $count } файл, запись в { $record }; демон закоммитит переименование при следующей синхронизации.
+    [few] Переименовано { $count } файла, запись в { $record }; демон закоммитит переименования при следующей синхронизации.
+   *[other] Переименовано { $count } файлов, запись в { $record }; демон закоммитит переименования при следующей синхронизации.
+}
+cli-unicode-none = Все имена файлов в составной форме Unicode.
+cli-unicode-hint = Эти имена в разложенной форме, как их может записать macOS; другие устройства могут считать их отличными от тех же имён, набранных у себя. Переименуйте их: `obsyncgit unicode-names --rename`, или задайте compat.unicode_normalization: nfc.
+cli-unicode-renamed = { $count ->
+    [one] Переименован { $count } файл; демон синхронизирует результат при следующей синхронизации.
+    [few] Переименовано { $count } файла; демон синхронизирует результат при следующей синхронизации.
+   *[other] Переименовано { $count } файлов; демон синхронизирует результат при следующей синхронизации.
+}
+cli-ignore-watcher-rule = Наблюдатель: { $pattern } ({ $source })
+cli-ignore-git-rule = git: { $pattern } ({ $file }:{ $line })
+cli-ignore-blocked-extension = Заблокирован: .{ $extension } есть в ignore.extensions_blocklist
+cli-ignore-blocked-size = Заблокирован: { $size } МБ больше ignore.max_file_size_mb ({ $limit } МБ)
+cli-ignore-no-rules = Ни одно правило игнорирования не подходит.
+cli-ignore-ignored = { $path } игнорируется и никогда не коммитится.
+cli-ignore-synced = { $path } синхронизируется.
+cli-ignore-synced-quietly = { $path } синхронизируется, но его правка сама по себе не запускает синхронизацию; она уйдёт вместе со следующим другим изменением.
+cli-ignore-tracked-hint = git продолжает коммитить файл, который уже отслеживает, что бы ни было в .gitignore. Перестаньте его отслеживать: `git rm --cached "{ $path }"`; файл останется на диске.
+cli-ignore-globs-hint = ignore.globs лишь не даёт правкам запускать синхронизацию. Добавьте шаблон в .gitignore хранилища, чтобы файл не попадал в коммиты.
+cli-log-empty = Сохранённых версий { $path } нет.
+cli-log-hint = Восстановить версию: `obsyncgit restore { $path } --commit <id>`.
+cli-restored = { $path } восстановлен из { $commit }. Запущенный демон закоммитит и синхронизирует его как обычную правку.
+cli-exported = Хранилище на момент { $commit } экспортировано в { $path }.
+cli-import-summary = { $count ->
+    [one] Импортирован { $count } файл
+    [few] Импортировано { $count } файла
+   *[other] Импортировано { $count } файлов
+}; уже в хранилище: { $unchanged }, старше версии в хранилище: { $skipped }.
+cli-import-vault-newer = версия в хранилище новее
+cli-import-dry-run = Пробный запуск: ничего не записано.
+cli-import-committed = Закоммичено как { $commit } с тегом { $tag }; демон отправит это при следующей синхронизации.
+cli-import-held = Не закоммичены, так как демон их придерживает: { $paths }
+cli-digest-sent = Сводка отправлена; новый период начинается сейчас.
+cli-digest-next = Следующая сводка будет отправлена через { $time }.
+cli-digest-off = Демон не отправляет сводки; включите их командой `obsyncgit settings set digest.enabled true`.
+digest-title-ok = ObsyncGit: синхронизация в порядке
+digest-title-attention = ObsyncGit: синхронизация требует внимания
+digest-body = За { $hours } ч: синхронизаций { $syncs }, изменено заметок { $notes }, конфликтов { $conflicts }, ошибок { $errors }.
+digest-last-error = Последняя ошибка: { $cause }
+cli-alerts-off = Оповещения не отправляются; задайте alerts.email, alerts.telegram или alerts.discord, чтобы узнавать, когда синхронизация раз за разом не удаётся.
+cli-alerts-email = Оповещения уходят письмом на { $to } через { $server }.
+cli-alerts-telegram = Оповещения уходят в чат Telegram { $chat }.
+cli-alerts-discord = Оповещения уходят в вебхук Discord на { $host }.
+cli-alerts-failures = { $count ->
+    [one] Оповещение уходит после { $count } неудачной синхронизации подряд.
+   *[other] Оповещение уходит после { $count } неудачных синхронизаций подряд.
+}
+cli-alerts-failures-off = Неудачные синхронизации не вызывают оповещений.
+cli-alerts-conflicts = Заметки в конфликте вызывают оповещение.
+cli-alerts-sent = Тестовое оповещение отправлено через { $channel }.
+cli-alerts-send-failed = Не удалось отправить тестовое оповещение через { $channel }: { $error }
+alert-test-subject = ObsyncGit: тестовое оповещение
+alert-test-body = Оповещения о { $vault } на { $device } приходят сюда.
+alert-failing-subject = ObsyncGit: синхронизация { $vault } не удаётся
+alert-failing-body = Неудачных синхронизаций { $vault } на { $device } подряд: { $failures }.
+alert-cause = Причина: { $cause }
+alert-failing-hint = Демон продолжает попытки; подробнее покажут `obsyncgit status` и `obsyncgit doctor`. Когда синхронизация снова заработает, придёт ещё одно оповещение.
+alert-recovered-subject = ObsyncGit: { $vault } снова синхронизируется
+alert-recovered-body = { $failures ->
+    [one] { $vault } на { $device } снова достучался до удалённого репозитория после { $failures } неудачной синхронизации.
+   *[other] { $vault } на { $device } снова достучался до удалённого репозитория после { $failures } неудачных синхронизаций.
+}
+alert-conflict-subject = ObsyncGit: заметки в конфликте в { $vault }
+alert-conflict-body = { $count ->
+    [one] Эта заметка изменена на { $device } и на другом устройстве:
+   *[other] Эти заметки изменены на { $device } и на другом устройстве:
+}
+alert-conflict-hint = Они не синхронизируются, пока вы не выберете, какую версию оставить, в obsyncgit-gui или командой `obsyncgit conflicts`.
+cli-snapshots-none = Ежедневных снимков пока нет. Включите их командой `obsyncgit settings set snapshots.tag-daily true`; каждый день помечается тегом после его окончания.
+cli-prune-none = Удалять нечего: каждая версия вложений из retention.paths есть в одном из недавних коммитов.
+cli-prune-plan = В { $commits } коммит(ах) до { $cutoff } есть { $versions } верси(я/и) вложений, которых нет ни в одном более позднем коммите, — до { $size } МБ.
+cli-prune-warning = Очистка переписывает историю всех коммитов и отправляет её с принудительной перезаписью. Другие устройства получат новую историю при следующей синхронизации; устройство с неотправленными слияниями попросит выполнить `obsyncgit repair --force`.
+cli-prune-prompt = Удалить эти версии из истории?
+cli-prune-declined = История не изменена.
+cli-prune-done = История очищена и отправлена. Прежняя история сохранена в { $path }.
+cli-prune-tags = { $count ->
+    [one] { $count } тег перенесён на переписанные коммиты.
+    [few] { $count } тега перенесены на переписанные коммиты.
+   *[other] { $count } тегов перенесено на переписанные коммиты.
+}
+cli-preflight-empty = Ветки хранилища на сервере ещё нет, поэтому при первом запуске скачивать нечего.
+cli-preflight-needed = Для первого клонирования нужно около { $needed } МБ на диске.
+cli-preflight-needed-lfs = Для первого клонирования нужно около { $needed } МБ на диске, из них { $lfs } МБ — файлы Git LFS.
+cli-preflight-free = Свободно { $available } МБ в { $path }.
+cli-preflight-short = После этого останется меньше disk.min_free_mb ({ $min } МБ), поэтому демон не станет клонировать хранилище; освободите место или выберите workdir на другом диске.
+cli-preflight-unknown = Не удалось узнать, сколько займёт первое клонирование: { $reason }
+cli-history-empty = Коммитов пока нет.
+cli-history-hint = Ни у одного коммита нет заметок синхронизации; включите commit.notes, чтобы записывать устройство, число файлов и задержку каждого коммита.
+cli-events-empty = Событий пока нет; демон записывает их, пока работает.
+cli-events-change = Обнаружено изменение: { $paths }
+cli-events-commit = Коммит { $commit } ({ $count ->
+    [one] { $count } файл
+    [few] { $count } файла
+   *[other] { $count } файлов
+}): { $files }
+cli-events-amend = Коммит { $commit } дополнен ({ $count ->
+    [one] { $count } файл
+    [few] { $count } файла
+   *[other] { $count } файлов
+}): { $files }
+cli-events-clone = Клонирование хранилища: { $phase } { $percent }%
+cli-events-clone-rate = Клонирование хранилища: { $phase } { $percent }%, { $rate }
+cli-events-push = Отправлено в { $branch }
+cli-events-pull = Получен { $commit }, { $count ->
+    [one] изменён { $count } файл
+    [few] изменено { $count } файла
+   *[other] изменено { $count } файлов
+}: { $files }
+cli-events-error = Ошибка синхронизации ({ $category }): { $message }
+cli-man-written = Страницы руководства записаны в { $dir }.
@@ -308,37 +308,11 @@ UNIT
 }
 
 create_launch_agent() {
-  plist_dir="$HOME/Library/LaunchAgents"
-  mkdir -p "$plist_dir"
-  plist_path="$plist_dir/dev.obsyncgit.daemon.plist"
-  cat >"$plist_path" <<PLIST
-<?xml version="1.0" encoding="UTF-8"?>
-<!DOCTYPE plist PUBLIC "-//Apple Computer//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
-<plist version="1.0">
-  <dict>
-    <key>Label</key>
-    <string>dev.obsyncgit.daemon</string>
-    <key>ProgramArguments</key>
-    <array>
-      <string>$OBSYNCHGIT_BIN</string>
-      <string>run</string>
-    </array>
-    <key>RunAtLoad</key>
-    <true/>
-    <key>StandardOutPath</key>
-    <string>$HOME/Library/Logs/obsyncgit.log</string>
-    <key>StandardErrorPath</key>
-    <string>$HOME/Library/Logs/obsyncgit.err.log</string>
-  </dict>
-</plist>
-PLIST
-
-  if command -v launchctl >/dev/null 2>&1; then
-    launchctl unload "$plist_path" >/dev/null 2>&1 || true
-    launchctl load "$plist_path" || true
+  # The daemon writes the same agent as the desktop helper's autostart switch.
+  if "$OBSYNCHGIT_BIN" service install --launch-agent --force; then
     echo "Configured LaunchAgent dev.obsyncgit.daemon"
   else
-    echo "launchctl not available; created LaunchAgent at $plist_path"
+    echo "Could not configure the LaunchAgent; run '$OBSYNCHGIT_BIN service install --launch-agent' later"
   fi
 }
 
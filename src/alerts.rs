@@ -0,0 +1,553 @@
+//! `alerts`: mail, Telegram or Discord messages for a daemon nobody
+//! watches, such as one on a headless server where no desktop notification
+//! reaches anyone. An alert goes out once `after_failures` sync cycles in a
+//! row failed, and another once syncing works again; with `on_conflict`,
+//! also when notes end up in a conflict. Each run of failures and each
+//! conflicted note is alerted about once, on every channel that is set up.
+
+use std::collections::HashSet;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use crate::config::{
+    AlertsConfig, Config, DiscordAlertConfig, EmailAlertConfig, SmtpTls, TelegramAlertConfig,
+};
+use crate::git_error::GitError;
+use crate::tr;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+const USER_AGENT: &str = concat!("obsyncgit/", env!("CARGO_PKG_VERSION"));
+/// The longest message the Bot API takes, in characters.
+const TELEGRAM_MAX_CHARS: usize = 4096;
+/// The longest `content` a Discord webhook takes, in characters.
+const DISCORD_MAX_CHARS: usize = 2000;
+
+/// What an alert is about; `{event}` in a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertKind {
+    Failing,
+    Recovered,
+    Conflict,
+    Test,
+}
+
+impl AlertKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Failing => "failing",
+            Self::Recovered => "recovered",
+            Self::Conflict => "conflict",
+            Self::Test => "test",
+        }
+    }
+}
+
+/// One message, in the user's language.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub subject: String,
+    pub body: String,
+    pub vault: String,
+    pub device: String,
+}
+
+impl Alert {
+    /// Sent by `obsyncgit alerts --test`.
+    pub fn test(config: &Config) -> Self {
+        let vault = config.workdir.to_string();
+        let device = config.sync.device_name();
+        Self {
+            kind: AlertKind::Test,
+            subject: tr!("alert-test-subject"),
+            body: tr!(
+                "alert-test-body",
+                vault = vault.clone(),
+                device = device.clone()
+            ),
+            vault,
+            device,
+        }
+    }
+
+    /// Fills in a chat message template: `{subject}`, `{body}`, `{event}`
+    /// (`failing`, `recovered`, `conflict` or `test`), `{vault}` and
+    /// `{device}`. Anything else in braces is kept as written.
+    pub fn render(&self, template: &str) -> String {
+        let mut text = String::with_capacity(template.len() + self.body.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let value = after.find('}').and_then(|end| {
+                let value = match &after[..end] {
+                    "subject" => &self.subject,
+                    "body" => &self.body,
+                    "event" => self.kind.as_str(),
+                    "vault" => &self.vault,
+                    "device" => &self.device,
+                    _ => return None,
+                };
+                Some((value, end))
+            });
+            match value {
+                Some((value, end)) => {
+                    text.push_str(value);
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    text.push('{');
+                    rest = after;
+                }
+            }
+        }
+        text.push_str(rest);
+        text
+    }
+}
+
+/// Somewhere alerts go.
+#[derive(Debug, Clone)]
+pub enum Channel {
+    Email(EmailAlertConfig),
+    Telegram(TelegramAlertConfig),
+    Discord(DiscordAlertConfig),
+}
+
+impl Channel {
+    /// The channels set up in `alerts`, in a fixed order.
+    pub fn all(alerts: &AlertsConfig) -> Vec<Self> {
+        let mut channels = Vec::new();
+        if alerts.email.is_set() {
+            channels.push(Self::Email(alerts.email.clone()));
+        }
+        if alerts.telegram.is_set() {
+            channels.push(Self::Telegram(alerts.telegram.clone()));
+        }
+        if alerts.discord.is_set() {
+            channels.push(Self::Discord(alerts.discord.clone()));
+        }
+        channels
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Email(_) => "email",
+            Self::Telegram(_) => "telegram",
+            Self::Discord(_) => "discord",
+        }
+    }
+
+    /// Where the channel's alerts go, without any secret in it.
+    pub fn target(&self) -> String {
+        match self {
+            Self::Email(email) => {
+                format!("mail to {} through {}", email.to.join(", "), server(email))
+            }
+            Self::Telegram(telegram) => format!("Telegram chat {}", chat(telegram)),
+            Self::Discord(discord) => format!("Discord webhook on {}", webhook_host(discord)),
+        }
+    }
+
+    pub fn send(&self, alert: &Alert) -> Result<()> {
+        match self {
+            Self::Email(email) => send(email, alert),
+            Self::Telegram(telegram) => send_telegram(telegram, alert),
+            Self::Discord(discord) => send_discord(discord, alert),
+        }
+    }
+
+    /// Checks the channel would take an alert without sending one, for
+    /// `obsyncgit doctor`.
+    pub fn check(&self) -> Result<()> {
+        match self {
+            Self::Email(email) => check(email),
+            Self::Telegram(telegram) => check_telegram(telegram),
+            Self::Discord(discord) => check_discord(discord),
+        }
+    }
+}
+
+/// The daemon's side: counts failed cycles and remembers what it already
+/// alerted about.
+#[derive(Debug)]
+pub struct Alerter {
+    channels: Vec<Channel>,
+    after_failures: u32,
+    on_conflict: bool,
+    vault: String,
+    device: String,
+    /// Failed cycles since the last one that reached the remote.
+    failures: u32,
+    /// An alert about the current run of failures went out.
+    failing: bool,
+    /// Conflicted notes already alerted about.
+    conflicts: HashSet<String>,
+}
+
+impl Alerter {
+    /// `None` unless an alert channel is set up.
+    pub fn new(config: &Config) -> Option<Self> {
+        let alerts = &config.alerts;
+        let channels = Channel::all(alerts);
+        if channels.is_empty() {
+            return None;
+        }
+        Some(Self {
+            channels,
+            after_failures: alerts.after_failures,
+            on_conflict: alerts.on_conflict,
+            vault: config.workdir.to_string(),
+            device: config.sync.device_name(),
+            failures: 0,
+            failing: false,
+            conflicts: HashSet::new(),
+        })
+    }
+
+    /// A sync cycle failed with `failure`.
+    pub fn failed(&mut self, failure: &GitError) {
+        self.failures = self.failures.saturating_add(1);
+        if self.after_failures == 0 || self.failing || self.failures < self.after_failures {
+            return;
+        }
+        self.failing = true;
+        let body = [
+            tr!(
+                "alert-failing-body",
+                vault = self.vault.clone(),
+                device = self.device.clone(),
+                failures = self.failures
+            ),
+            tr!("alert-cause", cause = failure.cause()),
+            String::new(),
+            failure.message().trim().to_string(),
+            String::new(),
+            tr!("alert-failing-hint"),
+        ];
+        self.dispatch(
+            AlertKind::Failing,
+            tr!("alert-failing-subject", vault = self.vault.clone()),
+            body.join("\n"),
+        );
+    }
+
+    /// A sync cycle reached the remote, which also means any conflict was
+    /// settled.
+    pub fn synced(&mut self) {
+        let failures = std::mem::take(&mut self.failures);
+        self.conflicts.clear();
+        if std::mem::take(&mut self.failing) {
+            self.dispatch(
+                AlertKind::Recovered,
+                tr!("alert-recovered-subject", vault = self.vault.clone()),
+                tr!(
+                    "alert-recovered-body",
+                    vault = self.vault.clone(),
+                    device = self.device.clone(),
+                    failures = failures
+                ),
+            );
+        }
+    }
+
+    /// `files` are in a conflict; alerts about those not alerted yet.
+    pub fn conflicts(&mut self, files: &[&str]) {
+        if !self.on_conflict {
+            return;
+        }
+        let new: Vec<&str> = files
+            .iter()
+            .copied()
+            .filter(|path| self.conflicts.insert(path.to_string()))
+            .collect();
+        if new.is_empty() {
+            return;
+        }
+        let mut body = vec![tr!(
+            "alert-conflict-body",
+            device = self.device.clone(),
+            count = new.len()
+        )];
+        body.extend(new.iter().map(|path| format!("  {path}")));
+        body.push(String::new());
+        body.push(tr!("alert-conflict-hint"));
+        self.dispatch(
+            AlertKind::Conflict,
+            tr!("alert-conflict-subject", vault = self.vault.clone()),
+            body.join("\n"),
+        );
+    }
+
+    /// Sends an alert to every channel on a thread of its own, so a slow
+    /// mail server or chat API never holds up syncing. A channel that fails
+    /// does not keep the alert from the others.
+    fn dispatch(&self, kind: AlertKind, subject: String, body: String) {
+        let alert = Alert {
+            kind,
+            subject,
+            body,
+            vault: self.vault.clone(),
+            device: self.device.clone(),
+        };
+        let channels = self.channels.clone();
+        let spawned = thread::Builder::new()
+            .name("obsyncgit-alert".to_string())
+            .spawn(move || {
+                for channel in &channels {
+                    match channel.send(&alert) {
+                        Ok(()) => {
+                            info!(channel = channel.name(), subject = %alert.subject, "sent an alert")
+                        }
+                        Err(err) => warn!(
+                            ?err,
+                            channel = channel.name(),
+                            subject = %alert.subject,
+                            "failed to send an alert"
+                        ),
+                    }
+                }
+            });
+        if let Err(err) = spawned {
+            warn!(?err, "failed to start sending an alert");
+        }
+    }
+}
+
+/// Mails `alert` to `alerts.email.to`.
+pub fn send(email: &EmailAlertConfig, alert: &Alert) -> Result<()> {
+    let message = message(email, alert)?;
+    transport(email)?
+        .send(&message)
+        .context("the mail server did not take the alert")?;
+    Ok(())
+}
+
+/// Connects to the mail server and logs in, for `obsyncgit doctor`.
+pub fn check(email: &EmailAlertConfig) -> Result<()> {
+    message(
+        email,
+        &Alert {
+            kind: AlertKind::Test,
+            subject: String::new(),
+            body: String::new(),
+            vault: String::new(),
+            device: String::new(),
+        },
+    )?;
+    if !transport(email)?
+        .test_connection()
+        .context("cannot reach the mail server")?
+    {
+        bail!("the mail server did not answer");
+    }
+    Ok(())
+}
+
+/// The server and port alerts go through, as `host:port`.
+pub fn server(email: &EmailAlertConfig) -> String {
+    format!(
+        "{}:{}",
+        email.server.as_deref().unwrap_or_default().trim(),
+        port(email)
+    )
+}
+
+fn port(email: &EmailAlertConfig) -> u16 {
+    email.port.unwrap_or(match email.tls {
+        SmtpTls::Starttls => 587,
+        SmtpTls::Tls => 465,
+        SmtpTls::None => 25,
+    })
+}
+
+fn message(email: &EmailAlertConfig, alert: &Alert) -> Result<Message> {
+    let mailbox = |address: &str, key: &str| {
+        address
+            .trim()
+            .parse::<Mailbox>()
+            .with_context(|| format!("invalid address '{address}' in alerts.email.{key}"))
+    };
+    let Some(first) = email.to.first() else {
+        bail!("alerts.email.to lists nobody to alert");
+    };
+    let from = mailbox(email.from.as_deref().unwrap_or(first), "from")?;
+    let mut builder = Message::builder()
+        .from(from)
+        .subject(alert.subject.clone())
+        .header(ContentType::TEXT_PLAIN);
+    for address in &email.to {
+        builder = builder.to(mailbox(address, "to")?);
+    }
+    builder
+        .body(alert.body.clone())
+        .context("failed to compose the alert")
+}
+
+fn transport(email: &EmailAlertConfig) -> Result<SmtpTransport> {
+    let Some(server) = email
+        .server
+        .as_deref()
+        .map(str::trim)
+        .filter(|server| !server.is_empty())
+    else {
+        bail!("alerts.email.server is not set");
+    };
+    let builder = match email.tls {
+        SmtpTls::Starttls => SmtpTransport::starttls_relay(server)
+            .with_context(|| format!("cannot use {server} as a mail server"))?,
+        SmtpTls::Tls => SmtpTransport::relay(server)
+            .with_context(|| format!("cannot use {server} as a mail server"))?,
+        SmtpTls::None => SmtpTransport::builder_dangerous(server),
+    };
+    let mut builder = builder.port(port(email)).timeout(Some(SMTP_TIMEOUT));
+    if let Some(username) = &email.username {
+        builder = builder.credentials(Credentials::new(
+            username.clone(),
+            email.password.clone().unwrap_or_default(),
+        ));
+    }
+    Ok(builder.build())
+}
+
+/// Posts `alert` to `alerts.telegram.chat_id` as plain text, so nothing in
+/// a git error can break Markdown parsing.
+pub fn send_telegram(telegram: &TelegramAlertConfig, alert: &Alert) -> Result<()> {
+    let text = clip(&alert.render(&telegram.template), TELEGRAM_MAX_CHARS);
+    let response = request(
+        http()?
+            .post(telegram_url(telegram, "sendMessage")?)
+            .json(&json!({
+                "chat_id": chat(telegram),
+                "text": text,
+                "disable_web_page_preview": true,
+            })),
+    )
+    .context("cannot reach the Telegram Bot API")?;
+    telegram_result(response)
+}
+
+/// Asks the Bot API who the bot is, which fails for a wrong token.
+fn check_telegram(telegram: &TelegramAlertConfig) -> Result<()> {
+    let response = request(http()?.get(telegram_url(telegram, "getMe")?))
+        .context("cannot reach the Telegram Bot API")?;
+    telegram_result(response)
+}
+
+/// The chat Telegram alerts go to.
+pub fn chat(telegram: &TelegramAlertConfig) -> &str {
+    telegram.chat_id.as_deref().unwrap_or_default().trim()
+}
+
+fn telegram_url(telegram: &TelegramAlertConfig, method: &str) -> Result<String> {
+    let Some(token) = telegram
+        .bot_token
+        .as_deref()
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+    else {
+        bail!("alerts.telegram.bot_token is not set");
+    };
+    Ok(format!(
+        "{}/bot{token}/{method}",
+        telegram.api_url.trim().trim_end_matches('/')
+    ))
+}
+
+/// The Bot API answers `{"ok": false, "description": ...}` when it turns
+/// a request down.
+fn telegram_result(response: Response) -> Result<()> {
+    let status = response.status();
+    let reply: Value = response.json().unwrap_or_default();
+    if status.is_success() && reply["ok"].as_bool() == Some(true) {
+        return Ok(());
+    }
+    match reply["description"].as_str() {
+        Some(description) => bail!("Telegram turned the alert down: {description}"),
+        None => bail!("Telegram turned the alert down with HTTP {status}"),
+    }
+}
+
+/// Posts `alert` to `alerts.discord.webhook_url`, without pinging anyone
+/// named in it.
+pub fn send_discord(discord: &DiscordAlertConfig, alert: &Alert) -> Result<()> {
+    let content = clip(&alert.render(&discord.template), DISCORD_MAX_CHARS);
+    let response = request(http()?.post(discord_url(discord)?).json(&json!({
+        "content": content,
+        "allowed_mentions": { "parse": [] },
+    })))
+    .context("cannot reach the Discord webhook")?;
+    if !response.status().is_success() {
+        bail!(
+            "Discord turned the alert down with HTTP {}",
+            response.status()
+        );
+    }
+    Ok(())
+}
+
+/// Fetches the webhook, which fails once it was deleted.
+fn check_discord(discord: &DiscordAlertConfig) -> Result<()> {
+    let response =
+        request(http()?.get(discord_url(discord)?)).context("cannot reach the Discord webhook")?;
+    if !response.status().is_success() {
+        bail!("the Discord webhook answered HTTP {}", response.status());
+    }
+    Ok(())
+}
+
+/// The host of the Discord webhook, leaving out its token.
+pub fn webhook_host(discord: &DiscordAlertConfig) -> String {
+    discord
+        .webhook_url
+        .as_deref()
+        .and_then(|url| reqwest::Url::parse(url.trim()).ok())
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn discord_url(discord: &DiscordAlertConfig) -> Result<&str> {
+    match discord
+        .webhook_url
+        .as_deref()
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+    {
+        Some(url) => Ok(url),
+        None => bail!("alerts.discord.webhook_url is not set"),
+    }
+}
+
+/// Sends `builder`, keeping its URL out of any error: the URL carries the
+/// bot or webhook token, and the error ends up in logs and terminals.
+fn request(builder: RequestBuilder) -> reqwest::Result<Response> {
+    builder.send().map_err(reqwest::Error::without_url)
+}
+
+fn http() -> Result<Client> {
+    Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(HTTP_TIMEOUT)
+        .build()
+        .context("failed to build HTTP client")
+}
+
+/// Cuts `text` to `max` characters, ending in `…` when it had to.
+fn clip(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut clipped: String = text.chars().take(max - 1).collect();
+    clipped.push('…');
+    clipped
+}
@@ -0,0 +1,204 @@
+//! `git bundle` backups of the vault's repository in a second place, such
+//! as an external drive or a NAS, for when the remote host loses it, and
+//! `obsyncgit backup restore` to clone a vault back out of one.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use camino::Utf8PathBuf;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::config::Config;
+use crate::git::{self, GitFacade};
+use crate::instance;
+
+/// The last bundle written, kept in the state directory for `status` and
+/// the daemon's schedule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    /// RFC 3339.
+    pub written_at: String,
+    pub bundle: PathBuf,
+}
+
+impl Record {
+    /// Time since the bundle was written; zero when the timestamp is
+    /// unreadable.
+    pub fn age(&self) -> Duration {
+        DateTime::parse_from_rfc3339(&self.written_at)
+            .ok()
+            .and_then(|written| (Utc::now() - written.with_timezone(&Utc)).to_std().ok())
+            .unwrap_or_default()
+    }
+}
+
+fn record_path(config: &Config) -> Result<Utf8PathBuf> {
+    Ok(config
+        .state_dir()?
+        .join("backup")
+        .join(format!("vault-{}.json", instance::vault_key(config))))
+}
+
+/// The record of the last bundle written, if any.
+pub fn load(config: &Config) -> Option<Record> {
+    let contents = fs::read_to_string(record_path(config).ok()?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn save(config: &Config, record: &Record) -> Result<()> {
+    let path = record_path(config)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("failed to create {parent}"))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(record)?)
+        .with_context(|| format!("failed to write {tmp}"))?;
+    fs::rename(&tmp, &path).with_context(|| format!("failed to replace {path}"))
+}
+
+fn backup_dir(config: &Config) -> Result<PathBuf> {
+    match &config.backup.dir {
+        Some(dir) => Ok(git::expand_home(dir)),
+        None => bail!("backup.dir is not set; set it to a folder on another disk first"),
+    }
+}
+
+/// Start of this vault's bundle names: the vault folder's name and a short
+/// key of its path, so vaults of the same name can share a folder.
+fn name_prefix(config: &Config) -> String {
+    let folder = config.workdir.file_name().unwrap_or("vault");
+    let key = instance::vault_key(config);
+    format!("{folder}-{}-", &key[..8])
+}
+
+/// How long until the next bundle is due; zero when it is overdue or none
+/// was written yet, `None` when the daemon writes none.
+pub fn next_backup_in(config: &Config) -> Option<Duration> {
+    let interval = config.backup.interval()?;
+    Some(
+        load(config)
+            .map(|record| interval.saturating_sub(record.age()))
+            .unwrap_or_default(),
+    )
+}
+
+/// Writes a bundle of every branch and tag into `backup.dir` and deletes
+/// bundles beyond `backup.keep`. Returns the new bundle's path.
+pub fn write(git: &GitFacade, config: &Config) -> Result<PathBuf> {
+    let dir = backup_dir(config)?;
+    if !dir.is_dir() {
+        // A missing parent usually means a drive that is not mounted;
+        // creating the folder there would fill the system disk instead.
+        match dir.parent() {
+            Some(parent) if parent.is_dir() => fs::create_dir(&dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?,
+            _ => bail!(
+                "{} is not available; is the drive holding it mounted?",
+                dir.display()
+            ),
+        }
+    }
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    let bundle = dir.join(format!("{}{stamp}.bundle", name_prefix(config)));
+    let tmp = bundle.with_extension("bundle.tmp");
+    if let Err(err) = git.bundle(&tmp) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    fs::rename(&tmp, &bundle).with_context(|| format!("failed to replace {}", bundle.display()))?;
+    info!(path = %bundle.display(), "wrote a backup bundle");
+
+    for old in list(config)?.iter().skip(config.backup.keep.max(1)) {
+        match fs::remove_file(old) {
+            Ok(()) => debug!(path = %old.display(), "deleted an old backup bundle"),
+            Err(err) => {
+                debug!(?err, path = %old.display(), "failed to delete an old backup bundle")
+            }
+        }
+    }
+    save(
+        config,
+        &Record {
+            written_at: Utc::now().to_rfc3339(),
+            bundle: bundle.clone(),
+        },
+    )?;
+    Ok(bundle)
+}
+
+/// This vault's bundles in `backup.dir`, newest first.
+pub fn list(config: &Config) -> Result<Vec<PathBuf>> {
+    let dir = backup_dir(config)?;
+    let prefix = name_prefix(config);
+    let entries =
+        fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    let mut bundles = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name();
+        if let Some(name) = name.to_str()
+            && name.starts_with(&prefix)
+            && name.ends_with(".bundle")
+        {
+            bundles.push(entry.path());
+        }
+    }
+    // The timestamp in the names sorts by age.
+    bundles.sort();
+    bundles.reverse();
+    Ok(bundles)
+}
+
+/// Clones the vault out of `bundle`, or the newest one in `backup.dir`,
+/// into `target`. Without a target the vault itself is restored when it is
+/// gone, and a folder beside it, `<vault>.restored-<time>`, is used when it
+/// is not. The clone's remote points at `repo_url` again, so syncing can
+/// carry on from it. Returns the bundle and the folder restored into.
+pub fn restore(
+    config: &Config,
+    bundle: Option<&Path>,
+    target: Option<&Path>,
+) -> Result<(PathBuf, PathBuf)> {
+    let bundle = match bundle {
+        Some(bundle) => bundle.to_path_buf(),
+        None => list(config)?
+            .into_iter()
+            .next()
+            .context("no backup bundle of this vault in backup.dir")?,
+    };
+    let workdir = config.workdir.as_std_path();
+    let vault_gone = !workdir.exists()
+        || fs::read_dir(workdir).is_ok_and(|mut entries| entries.next().is_none());
+    let target = match target {
+        Some(target) => target.to_path_buf(),
+        None if vault_gone => workdir.to_path_buf(),
+        None => {
+            let mut name = workdir.as_os_str().to_owned();
+            name.push(format!(
+                ".restored-{}",
+                Local::now().format("%Y%m%d-%H%M%S")
+            ));
+            PathBuf::from(name)
+        }
+    };
+
+    let mut restored_config = config.clone();
+    if target != workdir {
+        restored_config.workdir = Utf8PathBuf::from_path_buf(target.clone())
+            .map_err(|path| anyhow::anyhow!("{} is not valid UTF-8", path.display()))?;
+        restored_config.git.separate_git_dir = None;
+    }
+    restored_config.bootstrap.enabled = false;
+    let restored = GitFacade::new(&restored_config)?;
+    let source = bundle.to_str().context("bundle path is not valid UTF-8")?;
+    restored
+        .ensure_repo(source)
+        .with_context(|| format!("failed to clone {}", bundle.display()))?;
+    restored.set_remote(&config.repo_url)?;
+    info!(bundle = %bundle.display(), path = %target.display(), "restored the vault from a backup bundle");
+    Ok((bundle, target))
+}
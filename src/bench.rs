@@ -0,0 +1,228 @@
+//! Sync cycle timings against a generated vault, for `obsyncgit bench` and
+//! the criterion benchmarks in `benches/`. Like the selftest, everything
+//! happens in a throwaway sandbox.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use tokio::sync::mpsc;
+
+use crate::config::WatcherBackend;
+use crate::ignore::IgnoreMatcher;
+use crate::shutdown::Shutdown;
+use crate::testsupport::{Device, Sandbox};
+use crate::trace;
+use crate::watcher::{WatchEvent, WatcherSupervisor};
+
+/// Notes per generated folder.
+const FILES_PER_FOLDER: usize = 100;
+/// Longest wait for the watcher to report an edit.
+const WATCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Writes `files` notes of a few hundred bytes into `root`, in folders of
+/// [`FILES_PER_FOLDER`].
+pub fn generate(root: &Path, files: usize) -> Result<()> {
+    for index in 0..files {
+        let folder = root.join(format!("folder-{:04}", index / FILES_PER_FOLDER));
+        if index % FILES_PER_FOLDER == 0 {
+            fs::create_dir_all(&folder)
+                .with_context(|| format!("failed to create {}", folder.display()))?;
+        }
+        let path = folder.join(format!("note-{index:06}.md"));
+        fs::write(&path, note(index, 0))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Vault-relative path of the `index`th generated note.
+pub fn note_path(index: usize) -> String {
+    format!("folder-{:04}/note-{index:06}.md", index / FILES_PER_FOLDER)
+}
+
+/// Contents of the `index`th note after `revision` edits.
+pub fn note(index: usize, revision: usize) -> String {
+    format!(
+        "# Note {index}\n\nRevision {revision}.\n\n- [[note-{:06}]]\n- [[note-{:06}]]\n\n{}\n",
+        index.wrapping_sub(1),
+        index + 1,
+        "Lorem ipsum dolor sit amet, consectetur adipiscing elit. ".repeat(4)
+    )
+}
+
+/// A device whose vault holds `files` generated notes, committed and pushed,
+/// with the sync trace turned on.
+pub fn vault(sandbox: &Sandbox, files: usize) -> Result<(Device, Duration)> {
+    let mut device = sandbox.device_with("bench", |config| config.trace.enabled = true)?;
+    generate(device.workdir(), files)?;
+    let started = Instant::now();
+    device.sync()?;
+    Ok((device, started.elapsed()))
+}
+
+/// Timings of one edit travelling from disk to the remote.
+#[derive(Debug, Clone)]
+pub struct Cycle {
+    /// From writing the note until the watcher reported it.
+    pub watcher: Duration,
+    /// The commit-and-push cycle the daemon runs after its debounce.
+    pub sync: Duration,
+    /// Time spent per git subcommand during `sync`.
+    pub phases: BTreeMap<String, Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub files: usize,
+    /// Committing and pushing the generated vault.
+    pub initial: Duration,
+    pub cycles: Vec<Cycle>,
+    /// Added by the daemon between the watcher event and the sync cycle.
+    pub debounce: Duration,
+}
+
+/// Generates a vault of `files` notes and times `cycles` single-note edits.
+pub fn run(files: usize, cycles: usize) -> Result<Report> {
+    if files == 0 || cycles == 0 {
+        bail!("the benchmark needs at least one file and one cycle");
+    }
+    let sandbox = Sandbox::new()?;
+    let (mut device, initial) = vault(&sandbox, files)?;
+    let root = device.workdir().to_path_buf();
+    let config = device.config().clone();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let ignore = Arc::new(RwLock::new(IgnoreMatcher::new(
+        &root,
+        &config.ignore.globs,
+    )?));
+    let mut watcher = WatcherSupervisor::new(
+        root.clone(),
+        ignore,
+        tx,
+        Shutdown::new(),
+        config.debounce_duration(),
+        WatcherBackend::Auto,
+    );
+    watcher.start()?;
+
+    let state_dir = config.state_dir()?;
+    let mut timings = Vec::with_capacity(cycles);
+    for revision in 1..=cycles {
+        // Spread the edits so each cycle touches a different folder.
+        let index = (revision * 7919) % files;
+        while rx.try_recv().is_ok() {}
+        let started = Instant::now();
+        fs::write(root.join(note_path(index)), note(index, revision))?;
+        let watched = loop {
+            match rx.try_recv() {
+                Ok(WatchEvent::Changed(_)) => break started.elapsed(),
+                Ok(_) => {}
+                Err(mpsc::error::TryRecvError::Empty) if started.elapsed() < WATCH_TIMEOUT => {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                Err(_) => bail!("the watcher did not report an edit within {WATCH_TIMEOUT:?}"),
+            }
+        };
+        let started = Instant::now();
+        device.traced_sync()?;
+        let sync = started.elapsed();
+        let mut phases = BTreeMap::new();
+        if let Some(cycle) = trace::load(&state_dir)?.pop() {
+            for command in cycle.commands {
+                let name = command.args.split(' ').next().unwrap_or_default();
+                *phases.entry(format!("git {name}")).or_default() +=
+                    Duration::from_millis(command.millis);
+            }
+        }
+        timings.push(Cycle {
+            watcher: watched,
+            sync,
+            phases,
+        });
+    }
+    Ok(Report {
+        files,
+        initial,
+        cycles: timings,
+        debounce: config.debounce_duration(),
+    })
+}
+
+impl Report {
+    /// A table of median and slowest timings per phase.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let folders = self.files.div_ceil(FILES_PER_FOLDER);
+        let _ = writeln!(
+            out,
+            "vault: {} files in {folders} folders; initial commit and push took {}",
+            self.files,
+            millis(self.initial)
+        );
+        let _ = writeln!(
+            out,
+            "\n{:<28}{:>10}{:>10}",
+            format!("{} edits", self.cycles.len()),
+            "median",
+            "max"
+        );
+        let mut row = |label: &str, values: Vec<Duration>| {
+            let (median, max) = summary(values);
+            let _ = writeln!(out, "{label:<28}{:>10}{:>10}", millis(median), millis(max));
+        };
+        row(
+            "watcher event",
+            self.cycles.iter().map(|cycle| cycle.watcher).collect(),
+        );
+        row(
+            "sync cycle",
+            self.cycles.iter().map(|cycle| cycle.sync).collect(),
+        );
+        let names: Vec<&String> = self
+            .cycles
+            .iter()
+            .flat_map(|cycle| cycle.phases.keys())
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        for name in names {
+            row(
+                &format!("  {name}"),
+                self.cycles
+                    .iter()
+                    .map(|cycle| cycle.phases.get(name).copied().unwrap_or_default())
+                    .collect(),
+            );
+        }
+        row(
+            "watcher to push",
+            self.cycles
+                .iter()
+                .map(|cycle| cycle.watcher + cycle.sync)
+                .collect(),
+        );
+        let _ = writeln!(
+            out,
+            "\nThe daemon waits debounce_seconds ({}) between the watcher event and the sync cycle.",
+            humantime::format_duration(self.debounce)
+        );
+        out
+    }
+}
+
+fn summary(mut values: Vec<Duration>) -> (Duration, Duration) {
+    values.sort();
+    let median = values.get(values.len() / 2).copied().unwrap_or_default();
+    let max = values.last().copied().unwrap_or_default();
+    (median, max)
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{} ms", duration.as_millis())
+}
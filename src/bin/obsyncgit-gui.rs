@@ -1,42 +1,172 @@
 #![cfg(feature = "gui")]
 
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
-use std::time::SystemTime;
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-use anyhow::{Context, Result, anyhow, bail};
-use camino::Utf8PathBuf;
-use obsyncgit::config::Config;
+use anyhow::{Context, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use obsyncgit::config::{Config, GuiTheme};
+use obsyncgit::conflicts::{self, Conflict, Resolution};
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use obsyncgit::control::ControlInbox;
+use obsyncgit::events::{Event, EventLog};
+use obsyncgit::git::{CommitSummary, GitFacade};
+use obsyncgit::host_keys::{self, HostKey, SshHost};
+use obsyncgit::network;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use obsyncgit::remote;
+use obsyncgit::tr;
+use obsyncgit::updater::SelfUpdateManager;
 #[cfg(any(target_os = "macos", target_os = "windows"))]
 use slint::CloseRequestResponse;
-use slint::ComponentHandle;
+use slint::{ComponentHandle, ModelRc, Timer, TimerMode, VecModel};
 
 slint::include_modules!();
 
+#[path = "obsyncgit-gui/accessibility.rs"]
+mod accessibility;
 #[path = "obsyncgit-gui/autostart.rs"]
 mod autostart;
 
 use autostart::AutostartState;
 
+/// How often the conflict banner is refreshed from the repository.
+const CONFLICT_REFRESH: Duration = Duration::from_secs(30);
+/// Commits listed on the History tab.
+const HISTORY_LIMIT: usize = 50;
+/// How often the event stream is checked for clone progress.
+const CLONE_PROGRESS_REFRESH: Duration = Duration::from_secs(1);
+/// Clone progress older than this is from a clone that no longer runs.
+const CLONE_PROGRESS_STALE: chrono::TimeDelta = chrono::TimeDelta::minutes(10);
+
 struct AppState {
     config_path: Utf8PathBuf,
     config: Config,
     autostart_supported: bool,
     autostart_enabled: bool,
+    /// The desktop's high-contrast setting; the config only records a
+    /// choice that differs from it.
+    system_high_contrast: bool,
+    conflicts: Vec<Conflict>,
+    choices: BTreeMap<String, Resolution>,
+    history: Vec<CommitSummary>,
+    /// Keys fetched by the last host key check, waiting for the user to
+    /// trust them.
+    host_scan: Option<HostScan>,
+}
+
+struct HostScan {
+    host: SshHost,
+    keys: Vec<HostKey>,
+    /// Whether keys were pinned for the host already and get replaced.
+    known: bool,
+}
+
+impl AppState {
+    /// The configuration as the daemon sees it, with secrets resolved.
+    /// `config` keeps the references so saving never writes a secret out.
+    fn resolved_config(&self) -> Result<Config> {
+        let mut config = self.config.clone();
+        config.resolve_secrets()?;
+        Ok(config)
+    }
 }
 
 fn main() -> Result<()> {
     let (config, config_path) =
         Config::detect_and_load(None).context("failed to load configuration")?;
+    let mut resolved = config.clone();
+    match resolved.resolve_secrets() {
+        // SAFETY: no other threads exist yet.
+        Ok(()) => unsafe { network::export_proxy_env(&resolved.network) },
+        Err(err) => tracing::warn!(?err, "failed to resolve secrets in configuration"),
+    }
 
     let state = Arc::new(Mutex::new(AppState {
         config_path,
         config,
         autostart_supported: false,
         autostart_enabled: false,
+        system_high_contrast: accessibility::system_high_contrast(),
+        conflicts: Vec::new(),
+        choices: BTreeMap::new(),
+        history: Vec::new(),
+        host_scan: None,
     }));
 
     let ui = ConfiguratorWindow::new().context("failed to initialize UI")?;
+    translate_ui(&ui);
     populate_ui(&ui, &state)?;
+    refresh_conflicts(&ui, &state);
+
+    // The daemon fetches in the background; pick up conflicts it runs into
+    // without disturbing a review in progress.
+    let conflict_timer = Timer::default();
+    {
+        let ui_weak = ui.as_weak();
+        let state = state.clone();
+        conflict_timer.start(TimerMode::Repeated, CONFLICT_REFRESH, move || {
+            if let Some(ui) = ui_weak.upgrade()
+                && !ui.get_show_conflicts()
+            {
+                refresh_conflicts(&ui, &state);
+            }
+        });
+    }
+
+    // The first clone of a large vault takes a while; show how far the
+    // daemon got.
+    let clone_timer = Timer::default();
+    {
+        let ui_weak = ui.as_weak();
+        let state = state.clone();
+        let mut watch = CloneWatch::default();
+        clone_timer.start(TimerMode::Repeated, CLONE_PROGRESS_REFRESH, move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                watch.refresh(&ui, &state);
+            }
+        });
+    }
+
+    let ui_weak_conflict = ui.as_weak();
+    {
+        let state = state.clone();
+        ui.on_conflict_selected(move |index| {
+            if let Some(ui) = ui_weak_conflict.upgrade() {
+                ui.set_selected_conflict(index);
+                update_conflict_view(&ui, &state.lock().unwrap());
+            }
+        });
+    }
+
+    let ui_weak_choice = ui.as_weak();
+    {
+        let state = state.clone();
+        ui.on_conflict_choice_requested(move |index, choice| {
+            if let Some(ui) = ui_weak_choice.upgrade()
+                && let Err(err) = handle_conflict_choice(&ui, &state, index, &choice)
+            {
+                set_status(&ui, tr!("gui-choice-failed", error = err.to_string()));
+            }
+        });
+    }
+
+    let ui_weak_apply = ui.as_weak();
+    {
+        let state = state.clone();
+        ui.on_apply_conflicts_requested(move || {
+            if let Some(ui) = ui_weak_apply.upgrade() {
+                match handle_apply_conflicts(&ui, &state) {
+                    Ok(count) => set_status(&ui, tr!("gui-conflicts-applied", count = count)),
+                    Err(err) => {
+                        set_status(&ui, tr!("gui-conflicts-failed", error = err.to_string()))
+                    }
+                }
+            }
+        });
+    }
 
     let ui_weak_save = ui.as_weak();
     {
@@ -45,20 +175,86 @@ fn main() -> Result<()> {
             if let Some(ui) = ui_weak_save.upgrade()
                 && let Err(err) = handle_save(&ui, state.clone())
             {
-                set_status(&ui, format!("Save failed: {err}"));
+                set_status(&ui, tr!("gui-save-failed", error = err.to_string()));
+            }
+        });
+    }
+
+    let ui_weak_history = ui.as_weak();
+    {
+        let state = state.clone();
+        ui.on_history_requested(move || {
+            if let Some(ui) = ui_weak_history.upgrade()
+                && let Err(err) = refresh_history(&ui, &state)
+            {
+                set_status(&ui, tr!("gui-history-failed", error = err.to_string()));
+            }
+        });
+    }
+
+    let ui_weak_restore = ui.as_weak();
+    {
+        let state = state.clone();
+        ui.on_restore_requested(move |commit, file| {
+            if let Some(ui) = ui_weak_restore.upgrade() {
+                match handle_restore(&ui, &state, commit, file) {
+                    Ok(message) => set_status(&ui, message),
+                    Err(err) => set_status(&ui, tr!("gui-restore-failed", error = err.to_string())),
+                }
             }
         });
     }
 
     let ui_weak_manual = ui.as_weak();
-    ui.on_manual_update_requested(move || {
-        if let Some(ui) = ui_weak_manual.upgrade() {
-            match run_manual_update() {
-                Ok(_) => set_status(&ui, "Manual update triggered"),
-                Err(err) => set_status(&ui, format!("Manual update failed: {err}")),
+    {
+        let state = state.clone();
+        ui.on_manual_update_requested(move || {
+            let Some(ui) = ui_weak_manual.upgrade() else {
+                return;
+            };
+            let (config, config_path) = {
+                let guard = state.lock().unwrap();
+                (guard.resolved_config(), guard.config_path.clone())
+            };
+            set_status(&ui, tr!("gui-update-checking"));
+            // Downloading takes a while; keep the window responsive.
+            let ui_weak = ui.as_weak();
+            thread::spawn(move || {
+                let message =
+                    match config.and_then(|config| run_manual_update(&config, &config_path)) {
+                        Ok(true) => tr!("gui-update-installed"),
+                        Ok(false) => tr!("gui-update-current"),
+                        Err(err) => tr!("gui-update-failed", error = format!("{err:#}")),
+                    };
+                let _ = ui_weak.upgrade_in_event_loop(move |ui| set_status(&ui, message));
+            });
+        });
+    }
+
+    let ui_weak_host_check = ui.as_weak();
+    {
+        let state = state.clone();
+        ui.on_host_key_check_requested(move || {
+            if let Some(ui) = ui_weak_host_check.upgrade() {
+                start_host_key_check(&ui, &state);
             }
-        }
-    });
+        });
+    }
+
+    let ui_weak_host_trust = ui.as_weak();
+    {
+        let state = state.clone();
+        ui.on_host_key_trust_requested(move || {
+            if let Some(ui) = ui_weak_host_trust.upgrade() {
+                match handle_host_key_trust(&ui, &state) {
+                    Ok(message) => set_status(&ui, message),
+                    Err(err) => {
+                        set_status(&ui, tr!("gui-host-key-failed", error = format!("{err:#}")))
+                    }
+                }
+            }
+        });
+    }
 
     ui.on_exit_requested(|| {
         std::process::exit(0);
@@ -76,31 +272,82 @@ fn main() -> Result<()> {
                         }
                     }
                     Err(err) => {
-                        set_status(&ui, format!("Autostart update failed: {err}"));
+                        set_status(&ui, tr!("gui-autostart-failed", error = err.to_string()));
                     }
                 }
             }
         });
     }
 
-    setup_tray(&ui)?;
+    setup_tray(&ui, &state)?;
 
     ui.run()?;
     Ok(())
 }
 
+/// Replaces the English labels built into the window with the current
+/// language's.
+fn translate_ui(ui: &ConfiguratorWindow) {
+    let strings = ui.global::<Strings>();
+    strings.set_title(tr!("gui-title").into());
+    strings.set_tab_settings(tr!("gui-tab-settings").into());
+    strings.set_tab_history(tr!("gui-tab-history").into());
+    strings.set_repo_url(tr!("gui-repo-url").into());
+    strings.set_branch(tr!("gui-branch").into());
+    strings.set_remote(tr!("gui-remote").into());
+    strings.set_workdir(tr!("gui-workdir").into());
+    strings.set_author_name(tr!("gui-author-name").into());
+    strings.set_author_email(tr!("gui-author-email").into());
+    strings.set_ssh_key(tr!("gui-ssh-key").into());
+    strings.set_host_key(tr!("gui-host-key").into());
+    strings.set_check_host_key(tr!("gui-check-host-key").into());
+    strings.set_trust_host_key(tr!("gui-trust-host-key").into());
+    strings.set_auto_update(tr!("gui-auto-update").into());
+    strings.set_update_interval(tr!("gui-update-interval").into());
+    strings.set_autostart(tr!("gui-autostart").into());
+    strings.set_autostart_unsupported(tr!("gui-autostart-unsupported").into());
+    strings.set_save(tr!("gui-save").into());
+    strings.set_manual_update(tr!("gui-manual-update").into());
+    strings.set_exit(tr!("gui-exit").into());
+    strings.set_history_intro(tr!("gui-history-intro").into());
+    strings.set_refresh(tr!("gui-refresh").into());
+    strings.set_history_empty(tr!("gui-history-empty").into());
+    strings.set_restore(tr!("gui-restore").into());
+    strings.set_conflicts_title(tr!("gui-conflicts-title").into());
+    strings.set_conflicts_intro(tr!("gui-conflicts-intro").into());
+    strings.set_this_device(tr!("gui-this-device").into());
+    strings.set_other_device(tr!("gui-other-device").into());
+    strings.set_keep_mine(tr!("gui-keep-mine").into());
+    strings.set_keep_theirs(tr!("gui-keep-theirs").into());
+    strings.set_keep_both(tr!("gui-keep-both").into());
+    strings.set_back(tr!("gui-back").into());
+    strings.set_apply(tr!("gui-apply").into());
+    strings.set_review(tr!("gui-review").into());
+    strings.set_theme(tr!("gui-theme").into());
+    strings.set_theme_system(tr!("gui-theme-system").into());
+    strings.set_theme_light(tr!("gui-theme-light").into());
+    strings.set_theme_dark(tr!("gui-theme-dark").into());
+    strings.set_high_contrast(tr!("gui-high-contrast").into());
+    strings.set_conflict_list(tr!("gui-conflict-list").into());
+    strings.set_line_changed(tr!("gui-line-changed").into());
+    strings.set_line_missing(tr!("gui-line-missing").into());
+}
+
 fn populate_ui(ui: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) -> Result<()> {
     let autostart_state = match autostart::status() {
         Ok(state) => state,
         Err(err) => {
-            set_status(ui, format!("Autostart status unavailable: {err}"));
+            set_status(
+                ui,
+                tr!("gui-autostart-unavailable", error = err.to_string()),
+            );
             AutostartState::Unsupported
         }
     };
     let mut guard = state.lock().unwrap();
     ui.set_repo_url(guard.config.repo_url.clone().into());
     ui.set_branch(guard.config.branch.clone().into());
-    ui.set_remote(guard.config.remote.clone().into());
+    ui.set_remote(guard.config.remote.name.clone().into());
     ui.set_workdir(guard.config.workdir.to_string().into());
     ui.set_author_name(
         guard
@@ -129,6 +376,7 @@ fn populate_ui(ui: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) -> Result<
             .unwrap_or_default()
             .into(),
     );
+    ui.set_host_key_text(host_key_summary(&guard.config.repo_url).into());
     ui.set_auto_update_enabled(guard.config.self_update.enabled);
     ui.set_auto_update_interval_text(
         guard
@@ -145,6 +393,19 @@ fn populate_ui(ui: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) -> Result<
     ui.set_autostart_supported(guard.autostart_supported);
     ui.set_autostart_enabled(guard.autostart_enabled);
 
+    ui.set_theme_index(match guard.config.gui.theme {
+        GuiTheme::System => 0,
+        GuiTheme::Light => 1,
+        GuiTheme::Dark => 2,
+    });
+    ui.global::<Theme>().set_high_contrast(
+        guard
+            .config
+            .gui
+            .high_contrast
+            .unwrap_or(guard.system_high_contrast),
+    );
+
     ui.set_status_text("".into());
     Ok(())
 }
@@ -153,7 +414,7 @@ fn handle_save(ui: &ConfiguratorWindow, state: Arc<Mutex<AppState>>) -> Result<(
     let mut guard = state.lock().unwrap();
     guard.config.repo_url = ui.get_repo_url().into();
     guard.config.branch = ui.get_branch().into();
-    guard.config.remote = ui.get_remote().into();
+    guard.config.remote.name = ui.get_remote().into();
     guard.config.workdir = ui.get_workdir().to_string().into();
 
     let author_name = ui.get_author_name();
@@ -185,6 +446,15 @@ fn handle_save(ui: &ConfiguratorWindow, state: Arc<Mutex<AppState>>) -> Result<(
     let normalized_interval = parsed.max(1);
     guard.config.self_update.interval_hours = Some(normalized_interval);
 
+    guard.config.gui.theme = match ui.get_theme_index() {
+        1 => GuiTheme::Light,
+        2 => GuiTheme::Dark,
+        _ => GuiTheme::System,
+    };
+    let high_contrast = ui.global::<Theme>().get_high_contrast();
+    guard.config.gui.high_contrast =
+        (high_contrast != guard.system_high_contrast).then_some(high_contrast);
+
     guard
         .config
         .save_to_path(&guard.config_path)
@@ -194,21 +464,332 @@ fn handle_save(ui: &ConfiguratorWindow, state: Arc<Mutex<AppState>>) -> Result<(
     ui.set_auto_update_interval_text(normalized_interval.to_string().into());
     set_status(
         ui,
-        format!("Saved at {}", humantime::format_rfc3339(SystemTime::now())),
+        tr!(
+            "gui-saved",
+            time = humantime::format_rfc3339(SystemTime::now()).to_string()
+        ),
     );
     Ok(())
 }
 
-fn run_manual_update() -> Result<()> {
-    let status = std::process::Command::new("obsyncgit")
-        .arg("update")
-        .arg("--force")
-        .status()
-        .context("failed to spawn obsyncgit for update")?;
-    if status.success() {
-        Ok(())
+fn refresh_conflicts(ui: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) {
+    let mut guard = state.lock().unwrap();
+    let open = match guard.resolved_config().and_then(|config| {
+        let git = GitFacade::new(&config)?;
+        conflicts::list(&git, &config)
+    }) {
+        Ok(open) => open,
+        // Not cloned yet or git missing: the settings screen is the place
+        // to sort that out, so there is nothing to show here.
+        Err(err) => {
+            tracing::debug!(?err, "failed to list conflicts");
+            return;
+        }
+    };
+    guard
+        .choices
+        .retain(|path, _| open.iter().any(|conflict| &conflict.path == path));
+    guard.conflicts = open;
+    update_conflict_view(ui, &guard);
+}
+
+fn update_conflict_view(ui: &ConfiguratorWindow, state: &AppState) {
+    let entries: Vec<ConflictEntry> = state
+        .conflicts
+        .iter()
+        .map(|conflict| ConflictEntry {
+            path: conflict.path.clone().into(),
+            status: match state.choices.get(&conflict.path) {
+                Some(choice) => tr!("gui-conflict-choice", choice = choice.to_string()),
+                None => conflict.summary(),
+            }
+            .into(),
+            choice: state
+                .choices
+                .get(&conflict.path)
+                .map(ToString::to_string)
+                .unwrap_or_default()
+                .into(),
+        })
+        .collect();
+    ui.set_conflicts(ModelRc::new(VecModel::from(entries)));
+    ui.set_conflict_banner(tr!("gui-conflicts-banner", count = state.conflicts.len()).into());
+    ui.set_conflicts_ready(
+        !state.conflicts.is_empty()
+            && state
+                .conflicts
+                .iter()
+                .all(|conflict| state.choices.contains_key(&conflict.path)),
+    );
+    if state.conflicts.is_empty() {
+        ui.set_show_conflicts(false);
+    }
+
+    let selected =
+        (ui.get_selected_conflict().max(0) as usize).min(state.conflicts.len().saturating_sub(1));
+    ui.set_selected_conflict(selected as i32);
+    let lines: Vec<DiffLine> = match state.conflicts.get(selected) {
+        Some(conflict) => conflicts::side_by_side(
+            conflict.mine.as_deref().unwrap_or_default(),
+            conflict.theirs.as_deref().unwrap_or_default(),
+        )
+        .into_iter()
+        .map(|row| DiffLine {
+            changed: row.is_change(),
+            mine_present: row.mine.is_some(),
+            theirs_present: row.theirs.is_some(),
+            mine: row.mine.unwrap_or_default().into(),
+            theirs: row.theirs.unwrap_or_default().into(),
+        })
+        .collect(),
+        None => Vec::new(),
+    };
+    ui.set_conflict_lines(ModelRc::new(VecModel::from(lines)));
+}
+
+fn handle_conflict_choice(
+    ui: &ConfiguratorWindow,
+    state: &Arc<Mutex<AppState>>,
+    index: i32,
+    choice: &str,
+) -> Result<()> {
+    let resolution: Resolution = choice.parse()?;
+    let mut guard = state.lock().unwrap();
+    let path = usize::try_from(index)
+        .ok()
+        .and_then(|index| guard.conflicts.get(index))
+        .map(|conflict| conflict.path.clone())
+        .context("no conflict selected")?;
+    guard.choices.insert(path, resolution);
+    // Move on to the next note that still needs a decision.
+    if let Some(next) = guard
+        .conflicts
+        .iter()
+        .position(|conflict| !guard.choices.contains_key(&conflict.path))
+    {
+        ui.set_selected_conflict(next as i32);
+    }
+    update_conflict_view(ui, &guard);
+    Ok(())
+}
+
+fn handle_apply_conflicts(ui: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) -> Result<usize> {
+    let mut guard = state.lock().unwrap();
+    let config = guard.resolved_config()?;
+    let git = GitFacade::new(&config)?;
+    conflicts::resolve(&git, &config, &guard.choices)?;
+    let count = guard.choices.len();
+    guard.choices.clear();
+    drop(guard);
+    refresh_conflicts(ui, state);
+    Ok(count)
+}
+
+fn refresh_history(ui: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) -> Result<()> {
+    let mut guard = state.lock().unwrap();
+    let git = GitFacade::new(&guard.resolved_config()?)?;
+    guard.history = git.recent_commits(HISTORY_LIMIT)?;
+    let commits: Vec<HistoryCommit> = guard
+        .history
+        .iter()
+        .map(|commit| {
+            let files: Vec<HistoryFile> = commit
+                .files
+                .iter()
+                .map(|(change, path)| HistoryFile {
+                    path: path.clone().into(),
+                    change: tr!(
+                        "gui-change",
+                        change = match change {
+                            'A' => "added",
+                            'D' => "deleted",
+                            _ => "modified",
+                        }
+                    )
+                    .into(),
+                })
+                .collect();
+            HistoryCommit {
+                id: commit.commit.clone().into(),
+                date: commit
+                    .date
+                    .get(..16)
+                    .unwrap_or(&commit.date)
+                    .replace('T', " ")
+                    .into(),
+                summary: commit.summary.clone().into(),
+                files: ModelRc::new(VecModel::from(files)),
+            }
+        })
+        .collect();
+    ui.set_history(ModelRc::new(VecModel::from(commits)));
+    Ok(())
+}
+
+/// Puts back the version of a note a History entry refers to: the one the
+/// commit wrote, or for a deletion the one it deleted.
+fn handle_restore(
+    ui: &ConfiguratorWindow,
+    state: &Arc<Mutex<AppState>>,
+    commit: i32,
+    file: i32,
+) -> Result<String> {
+    let guard = state.lock().unwrap();
+    let entry = usize::try_from(commit)
+        .ok()
+        .and_then(|index| guard.history.get(index))
+        .context("no such commit")?;
+    let (change, path) = usize::try_from(file)
+        .ok()
+        .and_then(|index| entry.files.get(index))
+        .context("no such file")?;
+    let source = if *change == 'D' {
+        format!("{}^", entry.commit)
     } else {
-        Err(anyhow!("obsyncgit update exited with status {status}"))
+        entry.commit.clone()
+    };
+    let git = GitFacade::new(&guard.resolved_config()?)?;
+    git.restore_file(path, &source)?;
+    let message = tr!(
+        "gui-restored",
+        path = path.as_str(),
+        commit = entry.commit.get(..10).unwrap_or(&entry.commit)
+    );
+    drop(guard);
+    refresh_history(ui, state)?;
+    Ok(message)
+}
+
+/// Whether the remote's host key is pinned, before any check was run.
+fn host_key_summary(repo_url: &str) -> String {
+    let Some(host) = SshHost::parse(repo_url) else {
+        return tr!("gui-host-key-not-ssh");
+    };
+    let host = host.resolve();
+    match host_keys::is_known(&host) {
+        Ok(true) => tr!("gui-host-key-pinned", host = host.to_string()),
+        Ok(false) => tr!("gui-host-key-unknown", host = host.to_string()),
+        Err(err) => tr!("gui-host-key-failed", error = format!("{err:#}")),
+    }
+}
+
+/// Fetches the host keys of the repository URL in the form, which may not
+/// be saved yet, and lists their fingerprints for the user to compare.
+fn start_host_key_check(ui: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) {
+    let Some(host) = SshHost::parse(&ui.get_repo_url()) else {
+        ui.set_host_key_text(tr!("gui-host-key-not-ssh").into());
+        return;
+    };
+    state.lock().unwrap().host_scan = None;
+    ui.set_host_key_pending(false);
+    ui.set_host_key_text(tr!("gui-host-key-checking", host = host.to_string()).into());
+    // ssh-keyscan waits for the host; keep the window responsive.
+    let ui_weak = ui.as_weak();
+    let state = state.clone();
+    thread::spawn(move || {
+        let host = host.resolve();
+        let result =
+            host_keys::is_known(&host).and_then(|known| Ok((known, host_keys::scan(&host)?)));
+        let _ = ui_weak.upgrade_in_event_loop(move |ui| match result {
+            Ok((known, keys)) => {
+                let mut text = if known {
+                    tr!("gui-host-key-offered-pinned", host = host.to_string())
+                } else {
+                    tr!("gui-host-key-offered", host = host.to_string())
+                };
+                for key in &keys {
+                    text.push_str(&format!("\n{}  {}", key.fingerprint(), key.kind));
+                }
+                ui.set_host_key_text(text.into());
+                ui.set_host_key_pending(true);
+                state.lock().unwrap().host_scan = Some(HostScan { host, keys, known });
+            }
+            Err(err) => {
+                ui.set_host_key_text(tr!("gui-host-key-failed", error = format!("{err:#}")).into())
+            }
+        });
+    });
+}
+
+/// Pins the keys the last check fetched, replacing ones pinned before.
+fn handle_host_key_trust(ui: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) -> Result<String> {
+    let scan = state
+        .lock()
+        .unwrap()
+        .host_scan
+        .take()
+        .context("check the host key first")?;
+    host_keys::pin(&scan.host, &scan.keys, scan.known)?;
+    ui.set_host_key_pending(false);
+    ui.set_host_key_text(tr!("gui-host-key-pinned", host = scan.host.to_string()).into());
+    Ok(tr!(
+        "gui-host-key-trusted",
+        count = scan.keys.len(),
+        host = scan.host.to_string()
+    ))
+}
+
+/// Updates the daemon binary in place, with the same settings `obsyncgit
+/// update --force` would use. Returns whether a new version was installed.
+fn run_manual_update(config: &Config, config_path: &Utf8Path) -> Result<bool> {
+    let daemon = autostart::find_daemon_binary()?;
+    SelfUpdateManager::new(&config.self_update, config_path)
+        .with_network(&config.network)
+        .installing_to(daemon)
+        .check_now(true)
+}
+
+/// Follows the daemon's event stream for the progress of its first clone.
+#[derive(Default)]
+struct CloneWatch {
+    /// When the stream was last read; it is only read again once it changed.
+    modified: Option<SystemTime>,
+    /// Whether the status line shows clone progress.
+    showing: bool,
+}
+
+impl CloneWatch {
+    fn refresh(&mut self, ui: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) {
+        let Ok(events) = EventLog::new(&state.lock().unwrap().config) else {
+            return;
+        };
+        let modified = std::fs::metadata(events.path())
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if modified.is_none() || modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+        let Some(record) = events.recent(1).pop() else {
+            return;
+        };
+        let fresh = record
+            .time()
+            .is_some_and(|time| chrono::Utc::now() - time < CLONE_PROGRESS_STALE);
+        match record.event {
+            Event::CloneProgress {
+                phase,
+                percent,
+                rate,
+            } if fresh => {
+                let message = match rate {
+                    Some(rate) => tr!(
+                        "gui-clone-progress-rate",
+                        phase = phase,
+                        percent = percent,
+                        rate = rate
+                    ),
+                    None => tr!("gui-clone-progress", phase = phase, percent = percent),
+                };
+                set_status(ui, message);
+                self.showing = true;
+            }
+            _ if self.showing => {
+                set_status(ui, tr!("gui-clone-done"));
+                self.showing = false;
+            }
+            _ => {}
+        }
     }
 }
 
@@ -249,15 +830,15 @@ fn handle_autostart_toggle(
     ui.set_autostart_enabled(guard.autostart_enabled);
 
     if guard.autostart_enabled {
-        Ok(Some("Autostart has been enabled.".to_string()))
+        Ok(Some(tr!("gui-autostart-enabled")))
     } else {
-        Ok(Some("Autostart has been disabled.".to_string()))
+        Ok(Some(tr!("gui-autostart-disabled")))
     }
 }
 
 #[cfg(any(target_os = "macos", target_os = "windows"))]
-fn setup_tray(window: &ConfiguratorWindow) -> Result<()> {
-    use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+fn setup_tray(window: &ConfiguratorWindow, state: &Arc<Mutex<AppState>>) -> Result<()> {
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
     use tray_icon::{TrayIconBuilder, TrayIconEvent};
 
     let window_handle = window.window();
@@ -272,10 +853,23 @@ fn setup_tray(window: &ConfiguratorWindow) -> Result<()> {
     let tray_icon = load_tray_icon()?;
 
     let menu = Menu::new();
-    let show_item = Box::leak(Box::new(MenuItem::new("Show", true, None)));
-    let quit_item = Box::leak(Box::new(MenuItem::new("Quit", true, None)));
-    menu.append_items(&[show_item, quit_item])?;
-
+    let sync_item = Box::leak(Box::new(MenuItem::new(tr!("tray-sync-now"), true, None)));
+    let pause_item = Box::leak(Box::new(MenuItem::new(tr!("tray-pause"), true, None)));
+    let vault_item = Box::leak(Box::new(MenuItem::new(tr!("tray-open-vault"), true, None)));
+    let repo_item = Box::leak(Box::new(MenuItem::new(tr!("tray-open-repo"), true, None)));
+    let separator = Box::leak(Box::new(PredefinedMenuItem::separator()));
+    let show_item = Box::leak(Box::new(MenuItem::new(tr!("tray-show"), true, None)));
+    let quit_item = Box::leak(Box::new(MenuItem::new(tr!("tray-quit"), true, None)));
+    menu.append_items(&[
+        sync_item, pause_item, vault_item, repo_item, separator, show_item, quit_item,
+    ])?;
+
+    let actions = [
+        (sync_item.id().clone(), TrayAction::SyncNow),
+        (pause_item.id().clone(), TrayAction::Pause),
+        (vault_item.id().clone(), TrayAction::OpenVault),
+        (repo_item.id().clone(), TrayAction::OpenRepoPage),
+    ];
     let show_id = show_item.id().clone();
     let quit_id = quit_item.id().clone();
 
@@ -287,10 +881,17 @@ fn setup_tray(window: &ConfiguratorWindow) -> Result<()> {
     let tray_id = tray.id().clone();
 
     let window_for_menu = window.as_weak();
+    let state = state.clone();
     std::thread::spawn(move || {
         let receiver = MenuEvent::receiver().clone();
         for event in receiver.iter() {
-            if event.id == show_id {
+            if let Some((_, action)) = actions.iter().find(|(id, _)| *id == event.id) {
+                let message = match run_tray_action(&state, *action) {
+                    Ok(message) => message,
+                    Err(err) => format!("{err:#}"),
+                };
+                let _ = window_for_menu.upgrade_in_event_loop(move |ui| set_status(&ui, message));
+            } else if event.id == show_id {
                 let weak = window_for_menu.clone();
                 let _ = slint::invoke_from_event_loop(move || {
                     if let Some(ui) = weak.upgrade() {
@@ -326,8 +927,64 @@ fn setup_tray(window: &ConfiguratorWindow) -> Result<()> {
     Ok(())
 }
 
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+#[derive(Debug, Clone, Copy)]
+enum TrayAction {
+    SyncNow,
+    Pause,
+    OpenVault,
+    OpenRepoPage,
+}
+
+/// How long "Pause for 1 hour" pauses the daemon.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+const TRAY_PAUSE: Duration = Duration::from_secs(3600);
+
+/// Carries out a tray menu entry and returns the status line to show.
+/// Daemon requests go through its control inbox, so they also work when
+/// the daemon was started separately, and wait for it when it is not running.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn run_tray_action(state: &Arc<Mutex<AppState>>, action: TrayAction) -> Result<String> {
+    let config = state.lock().unwrap().resolved_config()?;
+    match action {
+        TrayAction::SyncNow => {
+            ControlInbox::new(&config)?.request_sync()?;
+            Ok(tr!("tray-sync-requested"))
+        }
+        TrayAction::Pause => {
+            ControlInbox::new(&config)?.pause_for(TRAY_PAUSE)?;
+            Ok(tr!("tray-paused"))
+        }
+        TrayAction::OpenVault => {
+            open_in_desktop(config.workdir.as_str())?;
+            Ok(tr!("tray-opened", target = config.workdir.to_string()))
+        }
+        TrayAction::OpenRepoPage => {
+            let url = remote::web_url(&config.repo_url)
+                .with_context(|| format!("cannot tell the web page of {}", config.repo_url))?;
+            open_in_desktop(&url)?;
+            Ok(tr!("tray-opened", target = url))
+        }
+    }
+}
+
+/// Opens a folder in the file manager or a URL in the browser.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn open_in_desktop(target: &str) -> Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "explorer"
+    };
+    std::process::Command::new(opener)
+        .arg(target)
+        .spawn()
+        .with_context(|| format!("failed to open {target}"))?;
+    Ok(())
+}
+
 #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-fn setup_tray(_window: &ConfiguratorWindow) -> Result<()> {
+fn setup_tray(_window: &ConfiguratorWindow, _state: &Arc<Mutex<AppState>>) -> Result<()> {
     tracing::warn!("Tray icon support is currently unavailable on this platform");
     Ok(())
 }
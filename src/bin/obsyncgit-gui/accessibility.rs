@@ -0,0 +1,80 @@
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+use std::process::{Command, Stdio};
+
+/// Whether the desktop asks applications for a high-contrast look. Anything
+/// that cannot be queried counts as "no".
+pub fn system_high_contrast() -> bool {
+    platform::high_contrast().unwrap_or(false)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+fn query(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::query;
+
+    pub(super) fn high_contrast() -> Option<bool> {
+        // GNOME and other GTK desktops. KDE has no such switch, only
+        // colour schemes, so Plasma users turn the option on in the window.
+        let value = query(
+            "gsettings",
+            &["get", "org.gnome.desktop.a11y.interface", "high-contrast"],
+        )?;
+        Some(value == "true")
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::query;
+
+    pub(super) fn high_contrast() -> Option<bool> {
+        let value = query(
+            "defaults",
+            &["read", "com.apple.universalaccess", "increaseContrast"],
+        )?;
+        Some(value == "1")
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::query;
+
+    /// `HCF_HIGHCONTRASTON` in the `HIGHCONTRAST` flags.
+    const HIGH_CONTRAST_ON: u32 = 0x1;
+
+    pub(super) fn high_contrast() -> Option<bool> {
+        let output = query(
+            "reg",
+            &[
+                "query",
+                r"HKCU\Control Panel\Accessibility\HighContrast",
+                "/v",
+                "Flags",
+            ],
+        )?;
+        // `    Flags    REG_SZ    126`
+        let flags: u32 = output.split_whitespace().last()?.parse().ok()?;
+        Some(flags & HIGH_CONTRAST_ON != 0)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+mod platform {
+    pub(super) fn high_contrast() -> Option<bool> {
+        None
+    }
+}
@@ -1,9 +1,8 @@
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::path::PathBuf;
+use std::process::Command;
 
 use anyhow::{Context, Result, anyhow};
 use camino::Utf8Path;
-use directories::BaseDirs;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AutostartState {
@@ -28,7 +27,8 @@ pub fn set_enabled(config_path: &Utf8Path, enabled: bool) -> Result<()> {
     platform::set_enabled(config_path, enabled)
 }
 
-fn find_daemon_binary() -> Result<PathBuf> {
+/// Path of the `obsyncgit` binary the desktop helper manages.
+pub fn find_daemon_binary() -> Result<PathBuf> {
     // Prefer a binary that lives alongside the GUI executable.
     let current_exe =
         std::env::current_exe().context("failed to determine current executable path")?;
@@ -55,7 +55,10 @@ fn find_daemon_binary() -> Result<PathBuf> {
 #[cfg(target_os = "linux")]
 mod platform {
     use super::*;
+    use directories::BaseDirs;
     use std::fs;
+    use std::path::Path;
+    use std::process::Stdio;
 
     const SERVICE_NAME: &str = "obsyncgit.service";
 
@@ -177,9 +180,8 @@ mod platform {
 #[cfg(target_os = "macos")]
 mod platform {
     use super::*;
-    use std::fs;
-
-    const LABEL: &str = "dev.obsyncgit.daemon";
+    use obsyncgit::config::Config;
+    use obsyncgit::launchd::{self, LABEL, LaunchAgent};
 
     pub(super) fn status() -> Result<AutostartState> {
         let output = Command::new("launchctl").args(["list", LABEL]).output();
@@ -193,69 +195,18 @@ mod platform {
         }
     }
 
+    /// Writes the same agent as `obsyncgit service install --launch-agent`.
     pub(super) fn set_enabled(config_path: &Utf8Path, enabled: bool) -> Result<()> {
-        let daemon = find_daemon_binary()?;
-        let plist_path = plist_path()?;
         if enabled {
-            write_plist(&plist_path, &daemon, config_path)?;
-            run_launchctl(["unload", &plist_path])?;
-            run_launchctl(["load", "-w", &plist_path])?;
+            let options = Config::load_from_path(config_path)?.launchd;
+            let agent =
+                LaunchAgent::new(find_daemon_binary()?, config_path.to_path_buf(), options)?;
+            launchd::install(&agent)?;
         } else {
-            run_launchctl(["unload", "-w", &plist_path])?;
+            launchd::uninstall()?;
         }
         Ok(())
     }
-
-    fn plist_path() -> Result<String> {
-        let dirs = BaseDirs::new().context("failed to determine home directory")?;
-        let path = dirs.home_dir().join("Library/LaunchAgents");
-        fs::create_dir_all(&path)
-            .with_context(|| format!("failed to create {}", path.display()))?;
-        Ok(path
-            .join("dev.obsyncgit.daemon.plist")
-            .to_string_lossy()
-            .into_owned())
-    }
-
-    fn write_plist(plist_path: &str, daemon: &Path, config_path: &Utf8Path) -> Result<()> {
-        let logs_dir = Path::new(&plist_path)
-            .parent()
-            .context("launch agent path missing parent")?
-            .parent()
-            .context("failed to resolve Library directory")?
-            .join("Logs");
-        fs::create_dir_all(&logs_dir)
-            .with_context(|| format!("failed to create {}", logs_dir.display()))?;
-
-        let stdout_path = logs_dir.join("obsyncgit.log");
-        let stderr_path = logs_dir.join("obsyncgit.err.log");
-
-        let contents = format!(
-            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE plist PUBLIC \"-//Apple Computer//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n<plist version=\"1.0\">\n  <dict>\n    <key>Label</key>\n    <string>{label}</string>\n    <key>ProgramArguments</key>\n    <array>\n      <string>{daemon}</string>\n      <string>run</string>\n      <string>--config</string>\n      <string>{config}</string>\n    </array>\n    <key>RunAtLoad</key>\n    <true/>\n    <key>StandardOutPath</key>\n    <string>{stdout}</string>\n    <key>StandardErrorPath</key>\n    <string>{stderr}</string>\n    <key>EnvironmentVariables</key>\n    <dict>\n      <key>OBSYNCGIT_CONFIG</key>\n      <string>{config}</string>\n    </dict>\n  </dict>\n</plist>\n",
-            label = LABEL,
-            daemon = daemon.to_string_lossy(),
-            config = config_path.as_str(),
-            stdout = stdout_path.to_string_lossy(),
-            stderr = stderr_path.to_string_lossy(),
-        );
-        fs::write(plist_path, contents).with_context(|| format!("failed to write {plist_path}"))
-    }
-
-    fn run_launchctl<I, S>(args: I) -> Result<()>
-    where
-        I: IntoIterator<Item = S>,
-        S: AsRef<std::ffi::OsStr>,
-    {
-        let status = Command::new("launchctl")
-            .args(args)
-            .status()
-            .context("failed to invoke launchctl")?;
-        if status.success() {
-            Ok(())
-        } else {
-            Err(anyhow!("launchctl command failed with status {status}"))
-        }
-    }
 }
 
 #[cfg(target_os = "windows")]
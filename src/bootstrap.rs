@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::config::BootstrapConfig;
+
+const GITATTRIBUTES: &str = "\
+# Notes keep LF line endings on every device, so Windows and macOS edits
+# do not show up as whole-file changes.
+* text=auto eol=lf
+*.md text diff=markdown
+*.canvas text
+
+# Attachments are never diffed or merged line by line.
+*.png binary
+*.jpg binary
+*.jpeg binary
+*.gif binary
+*.webp binary
+*.pdf binary
+*.mp3 binary
+*.mp4 binary
+*.zip binary
+";
+
+const GITIGNORE: &str = "\
+# Obsidian state that differs per device and changes on every click.
+.obsidian/workspace.json
+.obsidian/workspace-mobile.json
+.obsidian/cache
+
+# Operating system files.
+.DS_Store
+Thumbs.db
+desktop.ini
+";
+
+const DAILY_NOTE: &str = "\
+# {{date}}
+
+## Notes
+
+## Tasks
+- [ ] 
+";
+
+const MEETING_NOTE: &str = "\
+# {{title}}
+
+Date: {{date}}
+Attendees:
+
+## Agenda
+
+## Decisions
+
+## Follow-up
+- [ ] 
+";
+
+/// Starter files for a vault created on an empty remote, relative to the
+/// vault root.
+pub fn starter_files(config: &BootstrapConfig) -> Vec<(&'static str, &'static str)> {
+    let mut files = Vec::new();
+    if config.gitattributes {
+        files.push((".gitattributes", GITATTRIBUTES));
+    }
+    if config.gitignore {
+        files.push((".gitignore", GITIGNORE));
+    }
+    if config.templates {
+        files.push(("templates/Daily note.md", DAILY_NOTE));
+        files.push(("templates/Meeting.md", MEETING_NOTE));
+    }
+    files
+}
+
+/// Writes the starter files into `root`, leaving any that already exist
+/// alone, and returns the paths written. Nothing is written unless
+/// `bootstrap.enabled` is set.
+pub fn write_starter(root: &Path, config: &BootstrapConfig) -> Result<Vec<&'static str>> {
+    if !config.enabled {
+        return Ok(Vec::new());
+    }
+    let mut written = Vec::new();
+    for (path, contents) in starter_files(config) {
+        let target = root.join(path);
+        if target.exists() {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs::write(&target, contents)
+            .with_context(|| format!("failed to write {}", target.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
@@ -0,0 +1,147 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use anyhow::{Result, bail};
+
+use crate::git::GitFacade;
+
+/// Tracked paths that differ only in letter case, such as `Note.md` and
+/// `note.md`. macOS and Windows keep one of them on disk, so the others'
+/// content shows up as edits of the first and gets committed over it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collision {
+    /// Spellings, sorted. [`resolve`] keeps the first, so every device
+    /// that renames picks the same names.
+    pub paths: Vec<String>,
+    /// Whether the paths are directories rather than files.
+    pub directory: bool,
+}
+
+impl Collision {
+    /// Whether `path` is one of the spellings or lies under one.
+    pub fn covers(&self, path: &str) -> bool {
+        let key = self.paths[0].to_lowercase();
+        let path = path.to_lowercase();
+        path == key
+            || path
+                .strip_prefix(key.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+}
+
+/// Collisions among `tracked` files. A directory spelled two ways is one
+/// collision, not one for every file in it.
+pub fn find(tracked: &[String]) -> Vec<Collision> {
+    let files: HashSet<&str> = tracked.iter().map(String::as_str).collect();
+    let mut spellings: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+    for path in tracked {
+        for end in path
+            .match_indices('/')
+            .map(|(index, _)| index)
+            .chain([path.len()])
+        {
+            let prefix = &path[..end];
+            spellings
+                .entry(prefix.to_lowercase())
+                .or_default()
+                .insert(prefix);
+        }
+    }
+    let colliding: HashSet<&str> = spellings
+        .iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(key, _)| key.as_str())
+        .collect();
+    spellings
+        .iter()
+        .filter(|(key, paths)| {
+            paths.len() > 1
+                && !key
+                    .match_indices('/')
+                    .any(|(index, _)| colliding.contains(&key[..index]))
+        })
+        .map(|(_, paths)| Collision {
+            directory: paths.iter().any(|path| !files.contains(path)),
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+        })
+        .collect()
+}
+
+/// New names for all but the first spelling of each collision: `note.md`
+/// becomes `note (2).md` and a directory `notes` becomes `notes (2)`,
+/// counting up past names already taken in any case.
+pub fn renames(collisions: &[Collision], tracked: &[String]) -> Vec<(String, String)> {
+    let mut taken: HashSet<String> = tracked
+        .iter()
+        .flat_map(|path| {
+            path.match_indices('/')
+                .map(|(index, _)| index)
+                .chain([path.len()])
+                .map(|end| path[..end].to_lowercase())
+        })
+        .collect();
+    let mut renames = Vec::new();
+    for collision in collisions {
+        for path in collision.paths.iter().skip(1) {
+            let mut number = 2;
+            let target = loop {
+                let candidate = numbered(path, number, collision.directory);
+                if taken.insert(candidate.to_lowercase()) {
+                    break candidate;
+                }
+                number += 1;
+            };
+            renames.push((path.clone(), target));
+        }
+    }
+    renames
+}
+
+/// `path` with ` (number)` added before the extension, or after the name
+/// for directories.
+pub fn numbered(path: &str, number: usize, directory: bool) -> String {
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (format!("{dir}/"), name),
+        None => (String::new(), path),
+    };
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !directory && !stem.is_empty() => {
+            format!("{dir}{stem} ({number}).{ext}")
+        }
+        _ => format!("{dir}{name} ({number})"),
+    }
+}
+
+/// Renames all but the first spelling of each collision and commits the
+/// result, returning the files moved. Other local changes must be committed
+/// first.
+pub fn resolve(git: &GitFacade, collisions: &[Collision]) -> Result<Vec<(String, String)>> {
+    // On a case-insensitive file system the colliding paths always look
+    // modified, so only the rest of the vault has to be clean.
+    let dirty: Vec<String> = git
+        .list_pending_paths()?
+        .into_iter()
+        .filter(|path| !collisions.iter().any(|collision| collision.covers(path)))
+        .collect();
+    if !dirty.is_empty() {
+        bail!(
+            "the vault has uncommitted changes ({}); let the daemon commit them first",
+            dirty.join(", ")
+        );
+    }
+    let tracked = git.tracked_files()?;
+    let renames = renames(collisions, &tracked);
+    let keep: Vec<String> = collisions
+        .iter()
+        .map(|collision| collision.paths[0].clone())
+        .collect();
+    let moved = git.rename_tracked(&renames, &keep)?;
+    let summary: Vec<String> = renames
+        .iter()
+        .map(|(from, to)| format!("{from} -> {to}"))
+        .collect();
+    git.commit(&format!(
+        "Rename paths that differ only in case: {}",
+        summary.join(", ")
+    ))?;
+    Ok(moved)
+}
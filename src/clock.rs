@@ -0,0 +1,145 @@
+//! Timers that survive sleep and clock changes. The monotonic clock stands
+//! still while Linux and macOS are suspended, so a deadline kept as an
+//! `Instant` slips by however long the machine slept; the wall clock keeps
+//! counting but jumps when the user or NTP sets it. A [`Moment`] reads both
+//! and counts the larger of the two elapsed times: time asleep is counted,
+//! a clock set back is not. A clock set forward only brings a deadline
+//! forward, which costs an early poll or update check at most.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Where the time comes from; [`MockClock`] in `obsyncgit selftest`.
+pub trait Clock {
+    fn monotonic(&self) -> Instant;
+    fn wall(&self) -> SystemTime;
+}
+
+/// The system's clocks.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn monotonic(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn wall(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clocks that only move when told to. Clones share their time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<(Instant, SystemTime)>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            now: Arc::new(Mutex::new((Instant::now(), SystemTime::now()))),
+        }
+    }
+
+    /// Lets `duration` pass on both clocks, as while the machine is awake.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        now.0 += duration;
+        now.1 += duration;
+    }
+
+    /// Lets `duration` pass on the wall clock only, as while suspended.
+    pub fn suspend(&self, duration: Duration) {
+        self.now.lock().unwrap().1 += duration;
+    }
+
+    /// Sets the wall clock back by `duration`.
+    pub fn set_back(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        now.1 = now.1.checked_sub(duration).unwrap_or(now.1);
+    }
+}
+
+impl Clock for MockClock {
+    fn monotonic(&self) -> Instant {
+        self.now.lock().unwrap().0
+    }
+
+    fn wall(&self) -> SystemTime {
+        self.now.lock().unwrap().1
+    }
+}
+
+/// A point in time, on both clocks.
+#[derive(Debug, Clone, Copy)]
+pub struct Moment {
+    monotonic: Instant,
+    wall: SystemTime,
+}
+
+impl Moment {
+    pub fn now(clock: &impl Clock) -> Self {
+        Self {
+            monotonic: clock.monotonic(),
+            wall: clock.wall(),
+        }
+    }
+
+    /// Time since this moment, including time the machine slept.
+    pub fn elapsed(&self, clock: &impl Clock) -> Duration {
+        let monotonic = clock.monotonic().saturating_duration_since(self.monotonic);
+        let wall = clock.wall().duration_since(self.wall).unwrap_or_default();
+        monotonic.max(wall)
+    }
+
+    /// The moment `duration` before this one, or this one if the clocks do
+    /// not reach back that far.
+    pub fn earlier(self, duration: Duration) -> Self {
+        Self {
+            monotonic: self
+                .monotonic
+                .checked_sub(duration)
+                .unwrap_or(self.monotonic),
+            wall: self.wall.checked_sub(duration).unwrap_or(self.wall),
+        }
+    }
+}
+
+/// A time that is due some while after a [`Moment`], such as the next poll.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    from: Moment,
+    after: Duration,
+}
+
+impl Deadline {
+    pub fn new(from: Moment, after: Duration) -> Self {
+        Self { from, after }
+    }
+
+    /// Due `after` from now.
+    pub fn after(clock: &impl Clock, after: Duration) -> Self {
+        Self::new(Moment::now(clock), after)
+    }
+
+    pub fn remaining(&self, clock: &impl Clock) -> Duration {
+        self.after.saturating_sub(self.from.elapsed(clock))
+    }
+
+    pub fn is_due(&self, clock: &impl Clock) -> bool {
+        self.remaining(clock).is_zero()
+    }
+
+    /// The monotonic instant to wait for, as of now. Recompute it after a
+    /// wake-up: one taken before the machine slept is late by the sleep.
+    pub fn instant(&self, clock: &impl Clock) -> Instant {
+        clock.monotonic() + self.remaining(clock)
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+use std::path::{Component, Path};
+
+/// File sync service whose folder contains the vault. Such services copy
+/// files behind git's back, replace notes with online-only placeholders and
+/// create duplicates on conflicts, so the daemon has to tread carefully.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudProvider {
+    ICloud,
+    OneDrive,
+    Dropbox,
+    GoogleDrive,
+}
+
+impl fmt::Display for CloudProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ICloud => "iCloud Drive",
+            Self::OneDrive => "OneDrive",
+            Self::Dropbox => "Dropbox",
+            Self::GoogleDrive => "Google Drive",
+        })
+    }
+}
+
+impl CloudProvider {
+    /// Temporary, placeholder and metadata files the provider writes into
+    /// synced folders. They are never committed or treated as edits.
+    pub fn ignore_globs(self) -> &'static [&'static str] {
+        match self {
+            Self::ICloud => &["**/.*.icloud", "**/*.icloud"],
+            Self::OneDrive => &["**/~$*", "**/*.tmp", "**/desktop.ini"],
+            Self::Dropbox => &[
+                "**/.dropbox",
+                "**/.dropbox.attr",
+                "**/.dropbox.cache",
+                "**/.dropbox.cache/**",
+            ],
+            Self::GoogleDrive => &[
+                "**/.tmp.drivedownload",
+                "**/.tmp.drivedownload/**",
+                "**/.tmp.driveupload",
+                "**/.tmp.driveupload/**",
+                "**/desktop.ini",
+            ],
+        }
+    }
+
+    /// Ways the provider is known to clash with git, for `obsyncgit doctor`.
+    pub fn known_conflicts(self) -> &'static [&'static str] {
+        match self {
+            Self::ICloud => &[
+                "\"Optimise Mac Storage\" replaces notes with .icloud placeholders; the daemon never commits those as deletions, but turn it off for the vault folder",
+                "conflicting edits become duplicates named \"note 2.md\", which get committed like any other note",
+            ],
+            Self::OneDrive => &[
+                "Files On-Demand leaves online-only files that git cannot read; mark the vault \"Always keep on this device\"",
+                "conflicts become duplicates suffixed with the computer name, e.g. \"note-LAPTOP.md\"",
+            ],
+            Self::Dropbox => &[
+                "conflicts become \"note (conflicted copy).md\" duplicates",
+                "online-only files (Smart Sync) cannot be read by git; make the vault folder available offline",
+            ],
+            Self::GoogleDrive => &[
+                "streamed files are fetched on first read, which can stall git; mirror the vault folder instead",
+                "conflicts become duplicate files with the same name",
+            ],
+        }
+    }
+
+    /// Marker in the names of duplicates the provider creates on conflicts.
+    pub fn conflict_marker(self) -> Option<&'static str> {
+        match self {
+            Self::Dropbox => Some("conflicted copy"),
+            _ => None,
+        }
+    }
+}
+
+/// The cloud sync service `path` lives in, if any.
+pub fn detect(path: &Path) -> Option<CloudProvider> {
+    let names: Vec<String> = path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().to_lowercase()),
+            _ => None,
+        })
+        .collect();
+    for (index, name) in names.iter().enumerate() {
+        // macOS: ~/Library/Mobile Documents and ~/Library/CloudStorage/<Provider>-<account>
+        if name == "mobile documents" || name == "icloud drive" || name == "iclouddrive" {
+            return Some(CloudProvider::ICloud);
+        }
+        let in_cloud_storage = index > 0 && names[index - 1] == "cloudstorage";
+        if name.starts_with("onedrive") {
+            return Some(CloudProvider::OneDrive);
+        }
+        if name == "dropbox" || (in_cloud_storage && name.starts_with("dropbox")) {
+            return Some(CloudProvider::Dropbox);
+        }
+        if name == "google drive" || name.starts_with("googledrive") || name == "my drive" {
+            return Some(CloudProvider::GoogleDrive);
+        }
+    }
+    // Dropbox folders can be renamed; its root keeps a `.dropbox` marker.
+    if path
+        .ancestors()
+        .any(|ancestor| ancestor.join(".dropbox").is_file())
+    {
+        return Some(CloudProvider::Dropbox);
+    }
+    None
+}
+
+/// The subset of `files` (relative to `root`) whose content is not on this
+/// machine: online-only stubs, and notes iCloud evicted into a `.icloud`
+/// placeholder, which git sees as deleted. Staging them would either pull
+/// the whole file down or delete the note on every device.
+pub fn online_only(root: &Path, files: &[String]) -> Vec<String> {
+    files
+        .iter()
+        .filter(|file| {
+            let path = root.join(file);
+            has_icloud_placeholder(&path) || is_stub(&path)
+        })
+        .cloned()
+        .collect()
+}
+
+fn has_icloud_placeholder(path: &Path) -> bool {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    parent
+        .join(format!(".{}.icloud", name.to_string_lossy()))
+        .exists()
+}
+
+/// Whether the file system reports the file as a placeholder whose data
+/// lives in the cloud.
+#[cfg(windows)]
+fn is_stub(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| {
+        metadata.file_attributes()
+            & (FILE_ATTRIBUTE_OFFLINE
+                | FILE_ATTRIBUTE_RECALL_ON_OPEN
+                | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS)
+            != 0
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn is_stub(path: &Path) -> bool {
+    use std::os::macos::fs::MetadataExt;
+
+    const SF_DATALESS: u32 = 0x4000_0000;
+
+    std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.st_flags() & SF_DATALESS != 0)
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn is_stub(_path: &Path) -> bool {
+    false
+}
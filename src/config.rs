@@ -1,10 +1,15 @@
+use std::collections::BTreeMap;
+use std::env::VarError;
 use std::{fs, time::Duration};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
 use camino::{Utf8Path, Utf8PathBuf};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 
+use crate::provider::Provider;
+use crate::secrets;
+
 fn default_branch() -> String {
     "main".to_string()
 }
@@ -29,14 +34,188 @@ fn default_max_files_in_summary() -> usize {
     5
 }
 
+fn default_snapshot_interval_minutes() -> u64 {
+    30
+}
+
+fn default_session_idle_minutes() -> u64 {
+    10
+}
+
+fn default_update_command_timeout_seconds() -> u64 {
+    600
+}
+
+fn default_git_timeout_seconds() -> u64 {
+    300
+}
+
+fn default_merge_command_timeout_seconds() -> u64 {
+    300
+}
+
+fn default_git_repo_config() -> BTreeMap<String, String> {
+    [("core.autocrlf", "false"), ("pull.rebase", "true")]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn default_metered_poll_interval_seconds() -> u64 {
+    1800
+}
+
+fn default_metered_max_push_kb() -> u64 {
+    1024
+}
+
+fn default_integrity_check_interval_hours() -> u64 {
+    24
+}
+
+fn default_backup_interval_hours() -> u64 {
+    24
+}
+
+fn default_backup_keep() -> usize {
+    7
+}
+
+fn default_digest_interval_hours() -> u64 {
+    24
+}
+
+fn default_alert_after_failures() -> u32 {
+    3
+}
+
+fn default_alert_template() -> String {
+    "{subject}\n\n{body}".to_string()
+}
+
+fn default_discord_template() -> String {
+    "**{subject}**\n{body}".to_string()
+}
+
+fn default_telegram_api_url() -> String {
+    "https://api.telegram.org".to_string()
+}
+
+fn default_mqtt_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+fn default_retention_keep_months() -> u32 {
+    12
+}
+
+fn default_shared_vault_lock_minutes() -> u64 {
+    15
+}
+
+fn default_low_battery_percent() -> u8 {
+    20
+}
+
+fn default_battery_poll_interval_seconds() -> u64 {
+    1800
+}
+
+fn default_battery_max_push_kb() -> u64 {
+    1024
+}
+
+fn default_launchd_throttle_interval_seconds() -> u64 {
+    60
+}
+
+fn default_secret_file_globs() -> Vec<String> {
+    [
+        "**/.env",
+        "**/.env.*",
+        "**/*.pem",
+        "**/*.key",
+        "**/*.p12",
+        "**/*.pfx",
+        "**/id_rsa",
+        "**/id_dsa",
+        "**/id_ecdsa",
+        "**/id_ed25519",
+    ]
+    .map(str::to_string)
+    .to_vec()
+}
+
+fn default_secret_patterns() -> Vec<String> {
+    [
+        "-----BEGIN [A-Z ]*PRIVATE KEY-----",
+        "AKIA[0-9A-Z]{16}",
+        "gh[pousr]_[A-Za-z0-9]{36,}",
+        "github_pat_[A-Za-z0-9_]{40,}",
+        "glpat-[A-Za-z0-9_-]{20,}",
+        "xox[abprs]-[A-Za-z0-9-]{10,}",
+        "sk-(?:proj-)?[A-Za-z0-9_-]{32,}",
+        // `api_key: ...` and the like, in frontmatter or anywhere else.
+        r#"(?im)^\s*(?:api[_-]?key|client[_-]?secret|access[_-]?token|auth[_-]?token|secret|token|password)\s*:\s*["']?[A-Za-z0-9_\-./+=]{16,}"#,
+    ]
+    .map(str::to_string)
+    .to_vec()
+}
+
+fn default_secret_allow() -> Vec<String> {
+    ["**/.env.example", "**/.env.sample"]
+        .map(str::to_string)
+        .to_vec()
+}
+
+fn default_redaction_paths() -> Vec<String> {
+    vec!["*.md".to_string()]
+}
+
+fn default_redaction_line_patterns() -> Vec<String> {
+    vec![r"#private\b".to_string()]
+}
+
+fn default_min_free_mb() -> u64 {
+    200
+}
+
+fn default_settle_millis() -> u64 {
+    1500
+}
+
+fn default_remote_retry_base_seconds() -> u64 {
+    5
+}
+
+fn default_remote_retry_max_seconds() -> u64 {
+    60
+}
+
+fn default_trash_dir() -> String {
+    ".obsyncgit-trash".to_string()
+}
+
+fn default_deletion_delay_hours() -> u64 {
+    24
+}
+
+fn default_trace_cycles() -> usize {
+    20
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     pub repo_url: String,
     #[serde(default = "default_branch")]
     pub branch: String,
-    #[serde(default = "default_remote")]
-    pub remote: String,
+    #[serde(default)]
+    pub remote: RemoteConfig,
     pub workdir: Utf8PathBuf,
+    /// Directory for daemon-owned runtime files; defaults to the platform's
+    /// local data directory. Mount a volume here when running in a container.
+    #[serde(default)]
+    pub state_dir: Option<Utf8PathBuf>,
     #[serde(default = "default_debounce_seconds")]
     pub debounce_seconds: u64,
     #[serde(default = "default_poll_interval_seconds")]
@@ -49,6 +228,60 @@ pub struct Config {
     pub self_update: SelfUpdateConfig,
     #[serde(default)]
     pub git: GitOptions,
+    #[serde(default)]
+    pub remote_availability: RemoteAvailabilityConfig,
+    #[serde(default)]
+    pub stability: StabilityConfig,
+    #[serde(default)]
+    pub shared_config: SharedConfigOptions,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub sync: SyncConfig,
+    #[serde(default)]
+    pub schedule: ScheduleConfig,
+    #[serde(default)]
+    pub deletions: DeletionConfig,
+    #[serde(default)]
+    pub trace: TraceConfig,
+    #[serde(default)]
+    pub watcher: WatcherConfig,
+    #[serde(default)]
+    pub submodules: SubmoduleConfig,
+    #[serde(default)]
+    pub gui: GuiConfig,
+    #[serde(default)]
+    pub bootstrap: BootstrapConfig,
+    #[serde(default)]
+    pub compat: CompatConfig,
+    #[serde(default)]
+    pub disk: DiskConfig,
+    #[serde(default)]
+    pub integrity: IntegrityConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    #[serde(default)]
+    pub power: PowerConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    /// Plain folders the daemon mirrors the vault into after each sync,
+    /// see [`crate::replica`].
+    #[serde(default)]
+    pub replicas: Vec<String>,
+    #[serde(default)]
+    pub digest: DigestConfig,
+    #[serde(default)]
+    pub mqtt: MqttConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub snapshots: SnapshotConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub shared_vault: SharedVaultConfig,
+    #[serde(default)]
+    pub launchd: LaunchdConfig,
 }
 
 impl Config {
@@ -56,28 +289,63 @@ impl Config {
         let path = path.as_ref();
         let contents = fs::read_to_string(path)
             .with_context(|| format!("failed to read config file at {path}"))?;
-        let mut config: Config = serde_yaml::from_str(&contents)
-            .with_context(|| format!("failed to parse YAML config at {path}"))?;
+        Self::parse(&contents).with_context(|| format!("failed to parse YAML config at {path}"))
+    }
+
+    /// Builds a configuration purely from `OBSYNCGIT_*` environment variables,
+    /// for containers. `__` separates nested keys
+    /// (`OBSYNCGIT_SYNC__METERED_POLICY=defer_all`), and values are read as
+    /// YAML scalars, so `true`, `30` or `["*.tmp", ".trash/**"]` work.
+    pub fn from_env() -> Result<Self> {
+        let document = env_document(config_env_vars()?.into_iter())?;
+        Self::parse(&document).context("failed to build configuration from environment")
+    }
+
+    pub(crate) fn parse(contents: &str) -> Result<Self> {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(contents)?;
+        untag_secret_references(&mut value);
+        // Round-trip through text: deserializing from a `Value` is strict about
+        // scalar types (`pin_version: 0.4` would be a float), text is not.
+        let contents = serde_yaml::to_string(&value)?;
+        let mut config: Config = serde_yaml::from_str(&contents)?;
         config.normalize();
         Ok(config)
     }
 
+    /// Replaces `!keyring:` and `!env:` references with their values. Only
+    /// call this on configs used at runtime; saving a resolved config would
+    /// write the secrets back in plaintext.
+    pub fn resolve_secrets(&mut self) -> Result<()> {
+        let mut value = serde_yaml::to_value(&*self).context("failed to render configuration")?;
+        resolve_secret_references(&mut value)?;
+        *self = serde_yaml::from_value(value).context("failed to apply resolved secrets")?;
+        Ok(())
+    }
+
     pub fn resolve_path(explicit: Option<Utf8PathBuf>) -> Result<Utf8PathBuf> {
         if let Some(path) = explicit {
             return Ok(path);
         }
 
-        if let Ok(env_path) =
-            std::env::var("OBSYNCGIT_CONFIG").or_else(|_| std::env::var("GIT_SYNCD_CONFIG"))
-        {
-            return Ok(Utf8PathBuf::from(env_path));
+        for name in ["OBSYNCGIT_CONFIG", "GIT_SYNCD_CONFIG"] {
+            match std::env::var(name) {
+                Ok(env_path) => return Ok(Utf8PathBuf::from(env_path)),
+                Err(VarError::NotUnicode(env_path)) => bail!(
+                    "{name} is not valid UTF-8 ({}); move the config file to a UTF-8 path",
+                    env_path.to_string_lossy()
+                ),
+                Err(VarError::NotPresent) => {}
+            }
         }
 
         let project_dirs = ProjectDirs::from("dev", "ObsyncGit", "ObsyncGit")
             .context("cannot determine default config directory")?;
-        Utf8PathBuf::from_path_buf(project_dirs.config_dir().join("config.yaml"))
-            .ok()
-            .context("default config path is not valid UTF-8")
+        Utf8PathBuf::from_path_buf(project_dirs.config_dir().join("config.yaml")).map_err(|path| {
+            anyhow!(
+                "default config path {} is not valid UTF-8; pass --config or set OBSYNCGIT_CONFIG",
+                path.display()
+            )
+        })
     }
 
     pub fn save_to_path<P: AsRef<Utf8Path>>(&self, path: P) -> Result<()> {
@@ -99,6 +367,20 @@ impl Config {
         Ok((cfg, path))
     }
 
+    pub fn state_dir(&self) -> Result<Utf8PathBuf> {
+        if let Some(dir) = &self.state_dir {
+            return Ok(dir.clone());
+        }
+        let project_dirs = ProjectDirs::from("dev", "ObsyncGit", "ObsyncGit")
+            .context("cannot determine default state directory")?;
+        Utf8PathBuf::from_path_buf(project_dirs.data_local_dir().to_path_buf()).map_err(|path| {
+            anyhow!(
+                "default state directory {} is not valid UTF-8; set state_dir in the config",
+                path.display()
+            )
+        })
+    }
+
     pub fn debounce_duration(&self) -> Duration {
         Duration::from_secs(self.debounce_seconds.max(1))
     }
@@ -117,7 +399,151 @@ impl Config {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Prefix of the environment variables read by [`Config::from_env`].
+pub const ENV_CONFIG_PREFIX: &str = "OBSYNCGIT_";
+
+/// Environment variables with the config prefix that are not config keys.
+const ENV_RESERVED: &[&str] = &[
+    "OBSYNCGIT_CONFIG",
+    "OBSYNCGIT_LOG",
+    "OBSYNCGIT_LOG_FORMAT",
+    "OBSYNCGIT_MINISIGN_PUBLIC_KEY",
+];
+
+/// The `OBSYNCGIT_*` variables. Other variables may hold any bytes, but
+/// config values must be UTF-8.
+fn config_env_vars() -> Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    for (name, value) in std::env::vars_os() {
+        let Some(name) = name
+            .to_str()
+            .filter(|name| name.starts_with(ENV_CONFIG_PREFIX))
+        else {
+            continue;
+        };
+        let value = value.into_string().map_err(|value| {
+            anyhow!(
+                "{name} is not valid UTF-8 ({}); config values must be UTF-8 text",
+                value.to_string_lossy()
+            )
+        })?;
+        vars.push((name.to_string(), value));
+    }
+    Ok(vars)
+}
+
+#[derive(Debug)]
+enum EnvNode {
+    Value(String),
+    Section(BTreeMap<String, EnvNode>),
+}
+
+/// Renders `OBSYNCGIT_*` variables as a YAML document, leaving values as
+/// plain scalars where that is safe so their type is decided by the field.
+fn env_document(vars: impl Iterator<Item = (String, String)>) -> Result<String> {
+    let mut root = BTreeMap::new();
+    for (name, value) in vars {
+        let Some(key) = name.strip_prefix(ENV_CONFIG_PREFIX) else {
+            continue;
+        };
+        if ENV_RESERVED.contains(&name.as_str()) || key.is_empty() {
+            continue;
+        }
+        let segments: Vec<String> = key.split("__").map(str::to_ascii_lowercase).collect();
+        let (leaf, sections) = segments.split_last().context("empty key")?;
+        let mut node = &mut root;
+        for section in sections {
+            let entry = node
+                .entry(section.clone())
+                .or_insert_with(|| EnvNode::Section(BTreeMap::new()));
+            node = match entry {
+                EnvNode::Section(children) => children,
+                EnvNode::Value(_) => bail!("{name} conflicts with {ENV_CONFIG_PREFIX}{section}"),
+            };
+        }
+        if node.insert(leaf.clone(), EnvNode::Value(value)).is_some() {
+            bail!("{name} conflicts with nested {name}__* variables");
+        }
+    }
+
+    fn render(node: &BTreeMap<String, EnvNode>, indent: usize, out: &mut String) -> Result<()> {
+        for (key, child) in node {
+            let pad = " ".repeat(indent);
+            match child {
+                EnvNode::Section(children) => {
+                    out.push_str(&format!("{pad}{key}:\n"));
+                    render(children, indent + 2, out)?;
+                }
+                EnvNode::Value(value) => {
+                    let plain = value.starts_with(['[', '{'])
+                        || (!value.is_empty()
+                            && !value.contains(": ")
+                            && !value.contains(" #")
+                            && !value.contains('\n')
+                            && !value.starts_with([
+                                '!', '&', '*', '"', '\'', '%', '@', '`', '|', '>', '#', '-', '?',
+                                ' ',
+                            ]));
+                    let rendered = if plain {
+                        value.clone()
+                    } else {
+                        serde_yaml::to_string(value)?.trim_end().to_string()
+                    };
+                    out.push_str(&format!("{pad}{key}: {rendered}\n"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    let mut document = String::new();
+    render(&root, 0, &mut document)?;
+    Ok(document)
+}
+
+/// An unquoted `!keyring:name` is a YAML tag rather than a string; turn such
+/// tags back into the plain reference strings the rest of the config uses.
+fn untag_secret_references(value: &mut serde_yaml::Value) {
+    use serde_yaml::Value;
+
+    match value {
+        Value::Tagged(tagged) => {
+            let reference = tagged.tag.to_string();
+            if secrets::is_reference(&reference) && tagged.value.is_null() {
+                *value = Value::String(reference);
+            } else {
+                untag_secret_references(&mut tagged.value);
+            }
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(untag_secret_references),
+        Value::Mapping(map) => map.values_mut().for_each(untag_secret_references),
+        _ => {}
+    }
+}
+
+fn resolve_secret_references(value: &mut serde_yaml::Value) -> Result<()> {
+    use serde_yaml::Value;
+
+    match value {
+        Value::String(text) if secrets::is_reference(text) => {
+            *text = secrets::resolve(text)?;
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                resolve_secret_references(item)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for item in map.values_mut() {
+                resolve_secret_references(item)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct CommitConfig {
     #[serde(default = "default_commit_prefix")]
     pub prefix: String,
@@ -125,6 +551,34 @@ pub struct CommitConfig {
     pub max_files_in_summary: usize,
     #[serde(default)]
     pub include_timestamp: bool,
+    #[serde(default)]
+    pub style: CommitStyle,
+    #[serde(default)]
+    pub trailers: CommitTrailers,
+    /// Adds a `Signed-off-by` trailer with the committer's identity, like
+    /// `git commit --signoff`.
+    #[serde(default)]
+    pub signoff: bool,
+    /// Records the device, file count and time since the first edit of
+    /// each commit as a git note instead, see [`crate::sync_notes`].
+    #[serde(default)]
+    pub notes: bool,
+    #[serde(default)]
+    pub mode: CommitMode,
+    /// Minutes between commits under `snapshot`.
+    #[serde(default = "default_snapshot_interval_minutes")]
+    pub snapshot_interval_minutes: u64,
+    /// Minutes without an edit that end a writing session under `session`.
+    #[serde(default = "default_session_idle_minutes")]
+    pub session_idle_minutes: u64,
+    /// Minutes during which new changes are folded into the daemon's
+    /// latest commit instead of a new one; 0 turns amending off.
+    #[serde(default)]
+    pub amend_window_minutes: u64,
+    /// Edits not worth a commit of their own, such as a plugin bumping an
+    /// `updated:` timestamp.
+    #[serde(default)]
+    pub insignificant: InsignificantChanges,
 }
 
 impl Default for CommitConfig {
@@ -133,22 +587,163 @@ impl Default for CommitConfig {
             prefix: default_commit_prefix(),
             max_files_in_summary: default_max_files_in_summary(),
             include_timestamp: false,
+            style: CommitStyle::default(),
+            trailers: CommitTrailers::default(),
+            signoff: false,
+            notes: false,
+            mode: CommitMode::default(),
+            snapshot_interval_minutes: default_snapshot_interval_minutes(),
+            session_idle_minutes: default_session_idle_minutes(),
+            amend_window_minutes: 0,
+            insignificant: InsignificantChanges::default(),
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+impl CommitConfig {
+    pub fn snapshot_interval(&self) -> Duration {
+        Duration::from_secs(self.snapshot_interval_minutes.max(1).saturating_mul(60))
+    }
+
+    pub fn session_idle(&self) -> Duration {
+        Duration::from_secs(self.session_idle_minutes.max(1).saturating_mul(60))
+    }
+
+    /// `None` when amending is off.
+    pub fn amend_window(&self) -> Option<Duration> {
+        (self.amend_window_minutes > 0)
+            .then(|| Duration::from_secs(self.amend_window_minutes.saturating_mul(60)))
+    }
+}
+
+/// Changes to a tracked file the daemon leaves uncommitted until the file
+/// changes for real, when the whole edit is committed.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InsignificantChanges {
+    /// Trailing spaces, line endings and blank lines at the end.
+    pub whitespace: bool,
+    /// Front matter properties, such as `updated` or `modified`.
+    pub frontmatter_keys: Vec<String>,
+    /// Puts back the committed values of `frontmatter_keys` before each
+    /// pull, when nothing else in the note changed.
+    pub restore_frontmatter: bool,
+}
+
+/// How often local edits become commits, i.e. how fine-grained the history
+/// is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitMode {
+    /// Commit each burst of edits once `debounce_seconds` pass without one.
+    #[default]
+    OnChange,
+    /// Commit whatever changed every `snapshot_interval_minutes`, however
+    /// busy the vault is.
+    Snapshot,
+    /// Commit once no edit followed for `session_idle_minutes`, when a
+    /// writing session has ended.
+    Session,
+}
+
+impl std::str::FromStr for CommitMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().replace('-', "_").to_ascii_lowercase().as_str() {
+            "on_change" => Ok(Self::OnChange),
+            "snapshot" => Ok(Self::Snapshot),
+            "session" => Ok(Self::Session),
+            other => {
+                bail!("unknown commit mode '{other}' (expected on_change, snapshot or session)")
+            }
+        }
+    }
+}
+
+/// `commit.trailers`: `true` for trailers naming the device and the number
+/// of files, or a list of `Key: value` trailers to add to every commit,
+/// such as ones an organisation's policy requires.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum CommitTrailers {
+    Device(bool),
+    Custom(Vec<String>),
+}
+
+impl Default for CommitTrailers {
+    fn default() -> Self {
+        Self::Device(false)
+    }
+}
+
+impl CommitTrailers {
+    /// Whether to add `X-Obsync-Device` and `X-Obsync-Files-Count`.
+    pub fn device(&self) -> bool {
+        *self == Self::Device(true)
+    }
+
+    /// The trailers added to every commit.
+    pub fn custom(&self) -> &[String] {
+        match self {
+            Self::Device(_) => &[],
+            Self::Custom(trailers) => trailers,
+        }
+    }
+}
+
+/// Layout of automatic commit messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitStyle {
+    /// Everything on one line: `auto: a.md, b.md`.
+    #[default]
+    Summary,
+    /// A short subject (`auto: a.md` or `auto: 3 files`) with one changed
+    /// path per line in the body, the layout mobile git clients such as
+    /// GitJournal and Working Copy show in their history views.
+    Mobile,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
 pub struct IgnoreConfig {
     #[serde(default)]
     pub globs: Vec<String>,
+    /// Files larger than this many megabytes are not committed; 0 turns
+    /// the limit off.
+    #[serde(default)]
+    pub max_file_size_mb: u64,
+    /// File extensions never committed, such as `mp4` or `.mov`.
+    #[serde(default)]
+    pub extensions_blocklist: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
 pub struct SelfUpdateConfig {
     pub enabled: bool,
+    /// Shell command run instead of the built-in GitHub updater.
     pub command: Option<String>,
+    /// Shell used to interpret `command`; defaults to `sh` (or `cmd` on Windows).
+    pub shell: Option<UpdateShell>,
+    /// Program and arguments run directly without a shell; wins over `command`.
+    pub command_args: Option<Vec<String>>,
+    pub command_timeout_seconds: u64,
     pub interval_hours: Option<u64>,
+    pub channel: UpdateChannel,
+    /// Accept any pre-release build (alpha, dev, ...) regardless of channel.
+    pub allow_prerelease: bool,
+    /// Stay on exactly this release instead of following the channel.
+    pub pin_version: Option<String>,
+    /// Minisign public key used to verify `SHA256SUMS.minisig`. Overrides the
+    /// key embedded at build time.
+    pub public_key: Option<String>,
+    /// Install updates checked against `SHA256SUMS` alone when no signing
+    /// key is known. The checksums come from the same place as the binary,
+    /// so they prove nothing about who published it.
+    pub allow_unsigned: bool,
+    /// Restart the daemon on the new binary once an update is installed.
+    pub restart_after_update: bool,
 }
 
 impl Default for SelfUpdateConfig {
@@ -156,16 +751,1454 @@ impl Default for SelfUpdateConfig {
         Self {
             enabled: false,
             command: None,
+            shell: None,
+            command_args: None,
+            command_timeout_seconds: default_update_command_timeout_seconds(),
             interval_hours: Some(24),
+            channel: UpdateChannel::default(),
+            allow_prerelease: false,
+            pin_version: None,
+            public_key: None,
+            allow_unsigned: false,
+            restart_after_update: true,
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateShell {
+    Sh,
+    Cmd,
+    Powershell,
+}
+
+impl Default for UpdateShell {
+    fn default() -> Self {
+        if cfg!(windows) { Self::Cmd } else { Self::Sh }
+    }
+}
+
+impl std::str::FromStr for UpdateShell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "sh" => Ok(Self::Sh),
+            "cmd" => Ok(Self::Cmd),
+            "powershell" | "pwsh" => Ok(Self::Powershell),
+            other => anyhow::bail!("unknown shell '{other}' (expected sh, cmd or powershell)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    /// Final releases only.
+    #[default]
+    Stable,
+    /// Final releases plus `-beta` and `-rc` pre-releases.
+    Beta,
+}
+
+impl std::str::FromStr for UpdateChannel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "stable" => Ok(Self::Stable),
+            "beta" => Ok(Self::Beta),
+            other => anyhow::bail!("unknown update channel '{other}' (expected stable or beta)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
 pub struct GitOptions {
     pub executable: Option<String>,
     pub author_name: Option<String>,
     pub author_email: Option<String>,
     pub ssh_key_path: Option<String>,
+    /// Keeps the repository outside the vault, so no `.git` folder is
+    /// exposed to Obsidian Sync, iCloud or similar tools.
+    pub separate_git_dir: Option<String>,
+    pub status_cache: StatusCache,
+    /// Longest a clone, push, pull or fetch may go without any progress
+    /// before it is killed and the remote treated as unreachable; 0 waits
+    /// forever.
+    pub timeout_seconds: u64,
+    /// Lets the repository's `pre-commit` and `commit-msg` hooks, from
+    /// `.git/hooks` or `core.hooksPath`, check the daemon's commits. Off,
+    /// commits are made with `--no-verify`.
+    pub run_hooks: bool,
+    /// Git settings written into the vault's own config when the daemon
+    /// starts, so the user's global config cannot change how it commits and
+    /// pulls. An empty value removes the setting.
+    #[serde(deserialize_with = "deserialize_repo_config")]
+    pub repo_config: BTreeMap<String, String>,
+}
+
+impl Default for GitOptions {
+    fn default() -> Self {
+        Self {
+            executable: None,
+            author_name: None,
+            author_email: None,
+            ssh_key_path: None,
+            separate_git_dir: None,
+            status_cache: StatusCache::default(),
+            timeout_seconds: default_git_timeout_seconds(),
+            run_hooks: false,
+            repo_config: default_git_repo_config(),
+        }
+    }
+}
+
+/// Reads `git.repo_config`, taking unquoted YAML booleans and numbers as
+/// the strings git expects, and `~` as an empty value.
+fn deserialize_repo_config<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+    use serde_yaml::Value;
+
+    BTreeMap::<String, Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(value) => value,
+                Value::Bool(value) => value.to_string(),
+                Value::Number(value) => value.to_string(),
+                Value::Null => String::new(),
+                _ => {
+                    return Err(D::Error::custom(format!(
+                        "git.repo_config.{key} must be a single value"
+                    )));
+                }
+            };
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// Whether to turn on git's untracked cache and, on macOS and Windows, its
+/// file system monitor, so `git status` only looks at what changed instead
+/// of every file in the vault.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusCache {
+    /// Once the vault tracks enough files for status to get slow.
+    #[default]
+    Auto,
+    On,
+    /// Leave the repository's own settings alone.
+    Off,
+}
+
+impl std::str::FromStr for StatusCache {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "on" | "true" => Ok(Self::On),
+            "off" | "false" => Ok(Self::Off),
+            other => {
+                anyhow::bail!("unknown status cache setting '{other}' (expected auto, on or off)")
+            }
+        }
+    }
+}
+
+/// Retry curve used while the remote is unreachable. Kept separate from the
+/// generic failure backoff so that short, routine outages (e.g. nightly
+/// maintenance on a self-hosted server) do not turn into long sync gaps.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RemoteAvailabilityConfig {
+    pub retry_base_seconds: u64,
+    pub retry_max_seconds: u64,
+}
+
+impl Default for RemoteAvailabilityConfig {
+    fn default() -> Self {
+        Self {
+            retry_base_seconds: default_remote_retry_base_seconds(),
+            retry_max_seconds: default_remote_retry_max_seconds(),
+        }
+    }
+}
+
+/// Guards against staging files that are still being written.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StabilityConfig {
+    /// Minimum time a file must stay unmodified before it is staged.
+    pub settle_millis: u64,
+    /// Skip files another process holds open for writing (Linux only).
+    pub detect_open_writers: bool,
+}
+
+impl Default for StabilityConfig {
+    fn default() -> Self {
+        Self {
+            settle_millis: default_settle_millis(),
+            detect_open_writers: true,
+        }
+    }
+}
+
+/// Opt-in propagation of policy settings through the vault itself; see
+/// [`crate::shared_config::SharedPolicy`] for what is shared.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct SharedConfigOptions {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct NetworkConfig {
+    pub proxy: Option<ProxyConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProxyConfig {
+    /// Proxy URL, e.g. `http://proxy.corp:3128` or `socks5h://127.0.0.1:1080`.
+    pub url: String,
+    /// Hosts or domain suffixes (`.corp.example`) that bypass the proxy.
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    pub mode: SyncMode,
+    /// Name of this machine in per-device branches; defaults to the hostname.
+    pub device_name: Option<String>,
+    pub topology: SyncTopology,
+    /// Run instead of merging locally under `device-branches`, e.g. to open a
+    /// pull request for the device branch.
+    pub merge_command: Option<String>,
+    /// How long `merge_command` may go without output before it is killed;
+    /// `0` waits forever.
+    pub merge_command_timeout_seconds: u64,
+    /// Where the `pull-request` topology opens its pull requests.
+    pub pull_request: PullRequestConfig,
+    pub metered_policy: MeteredPolicy,
+    /// Poll interval used instead of `poll_interval_seconds` while metered.
+    pub metered_poll_interval_seconds: u64,
+    /// Largest changed file that `defer_large` still pushes while metered.
+    pub metered_max_push_kb: u64,
+    /// What to do about paths that differ only in letter case.
+    pub case_collisions: CaseCollisionPolicy,
+    /// What to do about file names Windows cannot check out.
+    pub file_names: FileNamePolicy,
+    /// What to do about conflict copies made by other sync tools.
+    pub conflict_copies: ConflictCopyPolicy,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            mode: SyncMode::default(),
+            device_name: None,
+            topology: SyncTopology::default(),
+            merge_command: None,
+            merge_command_timeout_seconds: default_merge_command_timeout_seconds(),
+            pull_request: PullRequestConfig::default(),
+            metered_policy: MeteredPolicy::default(),
+            metered_poll_interval_seconds: default_metered_poll_interval_seconds(),
+            metered_max_push_kb: default_metered_max_push_kb(),
+            case_collisions: CaseCollisionPolicy::default(),
+            file_names: FileNamePolicy::default(),
+            conflict_copies: ConflictCopyPolicy::default(),
+        }
+    }
+}
+
+impl SyncConfig {
+    /// The device name reduced to characters that are safe in a branch name.
+    pub fn device_name(&self) -> String {
+        let raw = self
+            .device_name
+            .clone()
+            .filter(|name| !name.trim().is_empty())
+            .or_else(hostname)
+            .unwrap_or_else(|| "device".to_string());
+        let cleaned: String = raw
+            .trim()
+            .chars()
+            .map(|ch| {
+                if ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_') {
+                    ch.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let cleaned = cleaned.trim_matches('-').to_string();
+        if cleaned.is_empty() {
+            "device".to_string()
+        } else {
+            cleaned
+        }
+    }
+
+    /// Branch this device pushes to when it cannot update the shared branch.
+    pub fn device_branch(&self) -> String {
+        format!("device/{}", self.device_name())
+    }
+}
+
+fn hostname() -> Option<String> {
+    let from_env = std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME"));
+    if let Ok(name) = from_env
+        && !name.trim().is_empty()
+    {
+        return Some(name);
+    }
+    if let Ok(name) = fs::read_to_string("/etc/hostname")
+        && !name.trim().is_empty()
+    {
+        return Some(name);
+    }
+    let output = std::process::Command::new("hostname").output().ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+/// The git remote the vault syncs with. Written as just its name
+/// (`remote: origin`) unless it is not a git server.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(from = "RemoteSetting", into = "RemoteSetting")]
+pub struct RemoteConfig {
+    /// Name of the git remote in the vault's repository.
+    pub name: String,
+    pub kind: RemoteKind,
+    pub s3: S3Options,
+    pub webdav: WebdavOptions,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            name: default_remote(),
+            kind: RemoteKind::default(),
+            s3: S3Options::default(),
+            webdav: WebdavOptions::default(),
+        }
+    }
+}
+
+/// `remote` as written in the file: a name, or the full table.
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum RemoteSetting {
+    Name(String),
+    Full(RemoteTable),
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+struct RemoteTable {
+    name: String,
+    kind: RemoteKind,
+    #[serde(skip_serializing_if = "S3Options::is_empty")]
+    s3: S3Options,
+    #[serde(skip_serializing_if = "WebdavOptions::is_empty")]
+    webdav: WebdavOptions,
+}
+
+impl Default for RemoteTable {
+    fn default() -> Self {
+        RemoteConfig::default().into()
+    }
+}
+
+impl From<RemoteConfig> for RemoteTable {
+    fn from(remote: RemoteConfig) -> Self {
+        Self {
+            name: remote.name,
+            kind: remote.kind,
+            s3: remote.s3,
+            webdav: remote.webdav,
+        }
+    }
+}
+
+impl From<RemoteSetting> for RemoteConfig {
+    fn from(setting: RemoteSetting) -> Self {
+        match setting {
+            RemoteSetting::Name(name) => Self {
+                name,
+                ..Self::default()
+            },
+            RemoteSetting::Full(table) => Self {
+                name: table.name,
+                kind: table.kind,
+                s3: table.s3,
+                webdav: table.webdav,
+            },
+        }
+    }
+}
+
+impl From<RemoteConfig> for RemoteSetting {
+    fn from(remote: RemoteConfig) -> Self {
+        if remote.kind == RemoteKind::Git && remote.s3.is_empty() && remote.webdav.is_empty() {
+            Self::Name(remote.name)
+        } else {
+            Self::Full(remote.into())
+        }
+    }
+}
+
+/// What serves the repository.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteKind {
+    /// A git server, or anything else git can push to, at `repo_url`.
+    #[default]
+    Git,
+    /// A single `git bundle` object in an S3 bucket; `repo_url` is
+    /// `s3://bucket/path/vault.bundle`.
+    S3,
+    /// A single `git bundle` file on a WebDAV share; `repo_url` is its
+    /// `https://` URL.
+    Webdav,
+}
+
+impl std::str::FromStr for RemoteKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "git" => Ok(Self::Git),
+            "s3" => Ok(Self::S3),
+            "webdav" => Ok(Self::Webdav),
+            other => anyhow::bail!("unknown remote kind '{other}' (expected git, s3 or webdav)"),
+        }
+    }
+}
+
+/// Where and as whom to reach an S3 bucket. Keys fall back to the
+/// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` environment variables.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct S3Options {
+    /// Defaults to `us-east-1`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// For S3-compatible services such as MinIO, e.g.
+    /// `https://minio.example.com`; defaults to AWS for `region`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_key_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_access_key: Option<String>,
+}
+
+impl S3Options {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Credentials for a WebDAV share, sent as HTTP basic authentication.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WebdavOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl WebdavOptions {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Which directions the daemon synchronizes in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncMode {
+    /// Commit local changes, rebase onto the remote and push.
+    #[default]
+    TwoWay,
+    /// Read-only replica: never commit or push, only fast-forward to the
+    /// remote branch.
+    PullOnly,
+    /// Append-only archive: commit and push but never pull or rebase. When the
+    /// branch has moved on, commits go to a per-device branch instead.
+    PushOnly,
+}
+
+impl std::str::FromStr for SyncMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().replace('_', "-").to_ascii_lowercase().as_str() {
+            "two-way" => Ok(Self::TwoWay),
+            "pull-only" => Ok(Self::PullOnly),
+            "push-only" => Ok(Self::PushOnly),
+            other => anyhow::bail!(
+                "unknown sync mode '{other}' (expected two-way, pull-only or push-only)"
+            ),
+        }
+    }
+}
+
+/// How devices share the remote in `two-way` mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncTopology {
+    /// Every device rebases onto and pushes the configured branch.
+    #[default]
+    SharedBranch,
+    /// Every device pushes to `device/<name>` and merges the other device
+    /// branches into the configured branch.
+    DeviceBranches,
+    /// Every device pushes to `device/<name>` and keeps a pull request open
+    /// from it into the configured branch, for branches nobody may push to.
+    PullRequest,
+}
+
+impl std::str::FromStr for SyncTopology {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().replace('_', "-").to_ascii_lowercase().as_str() {
+            "shared-branch" => Ok(Self::SharedBranch),
+            "device-branches" => Ok(Self::DeviceBranches),
+            "pull-request" => Ok(Self::PullRequest),
+            other => anyhow::bail!(
+                "unknown sync topology '{other}' (expected shared-branch, device-branches or pull-request)"
+            ),
+        }
+    }
+}
+
+/// Provider API access for the `pull-request` topology.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PullRequestConfig {
+    /// Worked out from `repo_url` for github.com, gitlab.com, codeberg.org
+    /// and gitea.com.
+    pub provider: Option<Provider>,
+    /// API root; defaults to the public API, or to `https://<host>` of
+    /// `repo_url` for self-hosted Gitea and GitLab.
+    pub api_url: Option<String>,
+    /// API token or a `!keyring:`/`!env:` reference; defaults to
+    /// GITHUB_TOKEN, GITEA_TOKEN or GITLAB_TOKEN.
+    pub token: Option<String>,
+}
+
+/// What to do while the active connection is metered or roaming.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MeteredPolicy {
+    /// Sync exactly as on any other connection.
+    #[default]
+    Ignore,
+    /// Poll the remote less often.
+    ReducePolling,
+    /// Reduce polling and hold back pushes that carry large files.
+    DeferLarge,
+    /// Commit locally only; push and pull once the connection is unmetered.
+    DeferAll,
+}
+
+impl std::str::FromStr for MeteredPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().replace('-', "_").to_ascii_lowercase().as_str() {
+            "ignore" => Ok(Self::Ignore),
+            "reduce_polling" => Ok(Self::ReducePolling),
+            "defer_large" => Ok(Self::DeferLarge),
+            "defer_all" => Ok(Self::DeferAll),
+            other => anyhow::bail!(
+                "unknown metered policy '{other}' (expected ignore, reduce_polling, defer_large or defer_all)"
+            ),
+        }
+    }
+}
+
+/// What to do when a pull brings in paths that differ only in letter case,
+/// such as `Note.md` and `note.md`, which macOS and Windows cannot hold side
+/// by side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseCollisionPolicy {
+    /// Log a warning naming them.
+    #[default]
+    Warn,
+    /// Rename all but one, e.g. to `note (2).md`, and push the rename.
+    Rename,
+}
+
+impl std::str::FromStr for CaseCollisionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "warn" => Ok(Self::Warn),
+            "rename" => Ok(Self::Rename),
+            other => {
+                anyhow::bail!("unknown case collision policy '{other}' (expected warn or rename)")
+            }
+        }
+    }
+}
+
+/// What to do before committing files whose names Windows cannot check
+/// out, such as `a: b.md`, `why?.md` or `notes.`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileNamePolicy {
+    /// Commit them as they are.
+    Ignore,
+    /// Commit them and log a warning naming them.
+    #[default]
+    Warn,
+    /// Rename them first, recording the old names in the vault.
+    Rename,
+}
+
+impl std::str::FromStr for FileNamePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ignore" => Ok(Self::Ignore),
+            "warn" => Ok(Self::Warn),
+            "rename" => Ok(Self::Rename),
+            other => {
+                anyhow::bail!(
+                    "unknown file name policy '{other}' (expected ignore, warn or rename)"
+                )
+            }
+        }
+    }
+}
+
+/// What to do about conflict copies other sync tools write into the vault,
+/// such as Syncthing's `note.sync-conflict-<date>-<id>.md`, see
+/// [`crate::conflict_copies`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictCopyPolicy {
+    /// Never commit them, and leave them alone.
+    Ignore,
+    /// Never commit them, and list them in the conflict inbox to choose
+    /// between the note and the copy.
+    #[default]
+    Inbox,
+    /// Keep whichever of the note and the copy was modified last at the
+    /// note's path, and delete the other.
+    Newest,
+}
+
+impl std::str::FromStr for ConflictCopyPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ignore" => Ok(Self::Ignore),
+            "inbox" => Ok(Self::Inbox),
+            "newest" => Ok(Self::Newest),
+            other => {
+                anyhow::bail!(
+                    "unknown conflict copy policy '{other}' (expected ignore, inbox or newest)"
+                )
+            }
+        }
+    }
+}
+
+/// Time windows for network operations. Outside them the daemon keeps
+/// committing locally and publishes once the schedule allows it again.
+/// Times are in the local timezone.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    /// `HH:MM-HH:MM` windows in which pushing and pulling is allowed; empty
+    /// means always. Windows may wrap past midnight (`22:00-06:00`).
+    pub active_windows: Vec<String>,
+    /// `HH:MM-HH:MM` windows in which network operations are paused.
+    pub quiet_hours: Vec<String>,
+    /// Cron expressions (`minute hour day month weekday`); network operations
+    /// are paused during every minute they match, e.g. `* 9-11 * * 1-5`.
+    pub pause_cron: Vec<String>,
+}
+
+/// Safety net against deletions (e.g. a misbehaving plugin removing many
+/// notes) propagating to every device on the next sync.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DeletionConfig {
+    pub mode: DeletionMode,
+    /// Vault-relative folder deleted notes are moved to in `trash` mode.
+    pub trash_dir: String,
+    /// How long a file must stay deleted before `delay` mode commits it.
+    pub delay_hours: u64,
+}
+
+impl Default for DeletionConfig {
+    fn default() -> Self {
+        Self {
+            mode: DeletionMode::default(),
+            trash_dir: default_trash_dir(),
+            delay_hours: default_deletion_delay_hours(),
+        }
+    }
+}
+
+/// Git submodules embedded in the vault, e.g. a shared templates repo.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SubmoduleConfig {
+    /// Initialise submodules after cloning and check out the recorded
+    /// commit after every pull.
+    pub update: bool,
+    /// Submodule paths whose own edits are committed and pushed to their
+    /// repository too. Other submodules are only followed: edits inside
+    /// them and moves of their checkout are never committed.
+    pub sync: Vec<String>,
+}
+
+impl Default for SubmoduleConfig {
+    fn default() -> Self {
+        Self {
+            update: true,
+            sync: Vec::new(),
+        }
+    }
+}
+
+/// Starter files committed when the vault is created on an empty remote.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BootstrapConfig {
+    /// Populate a new vault instead of starting it with an empty commit.
+    pub enabled: bool,
+    /// `templates/` with daily and meeting note templates.
+    pub templates: bool,
+    /// `.gitattributes` keeping LF line endings and treating attachments
+    /// as binary.
+    pub gitattributes: bool,
+    /// `.gitignore` for per-device Obsidian state and OS files.
+    pub gitignore: bool,
+}
+
+impl Default for BootstrapConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            templates: true,
+            gitattributes: true,
+            gitignore: true,
+        }
+    }
+}
+
+/// Free space the daemon keeps on the disks holding the vault and its
+/// repository.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DiskConfig {
+    /// Below this many megabytes free the daemon stops committing and
+    /// pulling, so git never runs out of space halfway through writing.
+    /// `0` turns the check off.
+    pub min_free_mb: u64,
+}
+
+impl Default for DiskConfig {
+    fn default() -> Self {
+        Self {
+            min_free_mb: default_min_free_mb(),
+        }
+    }
+}
+
+/// Scheduled `git fsck` of the vault's repository.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct IntegrityConfig {
+    /// Hours between checks; `0` turns them off.
+    pub check_interval_hours: u64,
+    /// Let the daemon run `obsyncgit repair` by itself when a check finds
+    /// damage, instead of only reporting it.
+    pub auto_repair: bool,
+}
+
+impl Default for IntegrityConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_hours: default_integrity_check_interval_hours(),
+            auto_repair: false,
+        }
+    }
+}
+
+impl IntegrityConfig {
+    pub fn check_interval(&self) -> Option<Duration> {
+        (self.check_interval_hours > 0)
+            .then(|| Duration::from_secs(self.check_interval_hours.saturating_mul(3600)))
+    }
+}
+
+/// `git bundle` copies of the repository written to a second place, such
+/// as an external drive or a NAS, independent of the remote host.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Folder the bundles go to; unset turns backups off.
+    pub dir: Option<String>,
+    /// Hours between bundles; `0` leaves them to `obsyncgit backup now`.
+    pub interval_hours: u64,
+    /// Bundles kept; older ones are deleted.
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            interval_hours: default_backup_interval_hours(),
+            keep: default_backup_keep(),
+        }
+    }
+}
+
+impl BackupConfig {
+    /// How often the daemon writes a bundle, `None` when it does not.
+    pub fn interval(&self) -> Option<Duration> {
+        (self.dir.is_some() && self.interval_hours > 0)
+            .then(|| Duration::from_secs(self.interval_hours.saturating_mul(3600)))
+    }
+}
+
+/// How the daemon saves power on a laptop running on its battery.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PowerConfig {
+    /// Below this charge, on battery, the daemon polls less and holds back
+    /// large pushes until mains power is back. `0` turns this off.
+    pub low_battery_percent: u8,
+    /// Poll interval used instead of `poll_interval_seconds` on a low
+    /// battery.
+    pub battery_poll_interval_seconds: u64,
+    /// Largest changed file still pushed on a low battery.
+    pub battery_max_push_kb: u64,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        Self {
+            low_battery_percent: default_low_battery_percent(),
+            battery_poll_interval_seconds: default_battery_poll_interval_seconds(),
+            battery_max_push_kb: default_battery_max_push_kb(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
+pub struct SecurityConfig {
+    #[serde(default)]
+    pub secret_scan: SecretScanConfig,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+}
+
+/// Files that look like secrets are held back from commits, see
+/// [`crate::secret_scan`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SecretScanConfig {
+    pub enabled: bool,
+    /// Paths held back whatever they contain, such as `**/.env`.
+    pub file_globs: Vec<String>,
+    /// Regular expressions; a file whose content matches one is held back.
+    pub patterns: Vec<String>,
+    /// Paths never held back, such as `**/.env.example`.
+    pub allow: Vec<String>,
+}
+
+impl Default for SecretScanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            file_globs: default_secret_file_globs(),
+            patterns: default_secret_patterns(),
+            allow: default_secret_allow(),
+        }
+    }
+}
+
+/// Lines and frontmatter keys that stay in the note on disk but are left
+/// out of commits, see [`crate::redaction`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    /// Files redacted, as `.gitattributes` patterns.
+    pub paths: Vec<String>,
+    /// Regular expressions; matching lines are not committed.
+    pub line_patterns: Vec<String>,
+    /// Frontmatter keys whose entries, including indented or list
+    /// continuation lines, are not committed.
+    pub frontmatter_keys: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            paths: default_redaction_paths(),
+            line_patterns: default_redaction_line_patterns(),
+            frontmatter_keys: Vec::new(),
+        }
+    }
+}
+
+/// A periodic summary of what the daemon did, see [`crate::digest`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DigestConfig {
+    pub enabled: bool,
+    /// Hours between digests: `24` for a daily one, `168` for a weekly one.
+    pub interval_hours: u64,
+    /// Show the digest as a desktop notification.
+    pub desktop: bool,
+    /// Also POST it as JSON to this URL, e.g. a chat webhook.
+    pub webhook_url: Option<String>,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: default_digest_interval_hours(),
+            desktop: true,
+            webhook_url: None,
+        }
+    }
+}
+
+impl DigestConfig {
+    /// How often the daemon sends a digest, `None` when it sends none.
+    pub fn interval(&self) -> Option<Duration> {
+        (self.enabled && self.interval_hours > 0)
+            .then(|| Duration::from_secs(self.interval_hours.saturating_mul(3600)))
+    }
+}
+
+/// The vault's sync state on an MQTT broker, see [`crate::mqtt`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    /// `mqtt://host[:port]`, or `mqtts://host[:port]` for TLS.
+    pub broker: String,
+    /// Topics are published below this one; by default
+    /// `obsyncgit/<device>/<vault folder>`.
+    pub topic: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Announce the state to Home Assistant through MQTT discovery.
+    pub home_assistant: bool,
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            broker: String::new(),
+            topic: None,
+            username: None,
+            password: None,
+            home_assistant: true,
+            discovery_prefix: default_mqtt_discovery_prefix(),
+        }
+    }
+}
+
+/// When the daemon tells someone that syncing needs attention, see
+/// [`crate::alerts`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AlertsConfig {
+    /// Alert once this many sync cycles in a row failed; `0` never does.
+    pub after_failures: u32,
+    /// Alert when notes end up in a conflict.
+    pub on_conflict: bool,
+    pub email: EmailAlertConfig,
+    pub telegram: TelegramAlertConfig,
+    pub discord: DiscordAlertConfig,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            after_failures: default_alert_after_failures(),
+            on_conflict: true,
+            email: EmailAlertConfig::default(),
+            telegram: TelegramAlertConfig::default(),
+            discord: DiscordAlertConfig::default(),
+        }
+    }
+}
+
+/// Alerts sent as mail through an SMTP server; off until `server` and
+/// `to` are set.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct EmailAlertConfig {
+    pub to: Vec<String>,
+    /// The sender; the first of `to` when unset.
+    pub from: Option<String>,
+    pub server: Option<String>,
+    /// By default 587 for `starttls`, 465 for `tls` and 25 for `none`.
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl EmailAlertConfig {
+    pub fn is_set(&self) -> bool {
+        self.server
+            .as_deref()
+            .is_some_and(|server| !server.trim().is_empty())
+            && !self.to.is_empty()
+    }
+}
+
+/// Alerts sent by a Telegram bot; off until `bot_token` and `chat_id` are
+/// set.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TelegramAlertConfig {
+    pub bot_token: Option<String>,
+    /// A chat's number, or `@name` for a public channel.
+    #[serde(deserialize_with = "deserialize_chat_id")]
+    pub chat_id: Option<String>,
+    /// The message, see [`crate::alerts::Alert::render`].
+    pub template: String,
+    /// For a self-hosted Bot API server.
+    pub api_url: String,
+}
+
+impl Default for TelegramAlertConfig {
+    fn default() -> Self {
+        Self {
+            bot_token: None,
+            chat_id: None,
+            template: default_alert_template(),
+            api_url: default_telegram_api_url(),
+        }
+    }
+}
+
+impl TelegramAlertConfig {
+    pub fn is_set(&self) -> bool {
+        self.bot_token
+            .as_deref()
+            .is_some_and(|token| !token.trim().is_empty())
+            && self
+                .chat_id
+                .as_deref()
+                .is_some_and(|chat| !chat.trim().is_empty())
+    }
+}
+
+/// Alerts posted to a Discord channel's webhook; off until `webhook_url`
+/// is set.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DiscordAlertConfig {
+    pub webhook_url: Option<String>,
+    /// The message, see [`crate::alerts::Alert::render`].
+    pub template: String,
+}
+
+impl Default for DiscordAlertConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            template: default_discord_template(),
+        }
+    }
+}
+
+impl DiscordAlertConfig {
+    pub fn is_set(&self) -> bool {
+        self.webhook_url
+            .as_deref()
+            .is_some_and(|url| !url.trim().is_empty())
+    }
+}
+
+/// Telegram chat ids are numbers, and YAML reads them as such.
+fn deserialize_chat_id<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+    use serde_yaml::Value;
+
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        Value::String(chat) => Ok(Some(chat)),
+        Value::Number(chat) => Ok(Some(chat.to_string())),
+        _ => Err(D::Error::custom(
+            "alerts.telegram.chat_id must be a number or @name",
+        )),
+    }
+}
+
+/// How the connection to the SMTP server is encrypted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection with STARTTLS, and refuse a server that
+    /// cannot.
+    #[default]
+    Starttls,
+    /// TLS from the start, as on port 465.
+    Tls,
+    /// Unencrypted, for a relay on the same machine or network.
+    None,
+}
+
+impl std::str::FromStr for SmtpTls {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "starttls" => Ok(Self::Starttls),
+            "tls" | "ssl" => Ok(Self::Tls),
+            "none" | "off" => Ok(Self::None),
+            other => {
+                anyhow::bail!("unknown SMTP encryption '{other}' (expected starttls, tls or none)")
+            }
+        }
+    }
+}
+
+/// Calendar recovery points, see [`crate::snapshots`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// Tag the last commit of each day `snapshot/<date>` and push the tag.
+    pub tag_daily: bool,
+}
+
+/// Which old attachment versions `obsyncgit prune-history` drops, see
+/// [`crate::retention`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Globs of vault-relative paths whose old versions may be dropped,
+    /// e.g. `attachments/**`. Empty, the default, prunes nothing.
+    pub paths: Vec<String>,
+    /// Versions no commit of this many months still has are dropped.
+    pub keep_months: u32,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            keep_months: default_retention_keep_months(),
+        }
+    }
+}
+
+/// A vault several people share, see [`crate::shared_vault`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SharedVaultConfig {
+    pub enabled: bool,
+    /// This member's folder, e.g. `people/anna`; new files at the top of the
+    /// vault are moved into it before they are committed.
+    pub user_folder: Option<String>,
+    /// Globs of vault-relative paths this device pulls but never commits,
+    /// e.g. `shared/recipes/**`.
+    pub protected_paths: Vec<String>,
+    /// Tell the other devices which notes are being edited here, see
+    /// [`crate::locks`].
+    pub soft_locks: bool,
+    /// How long a note stays locked after its last edit.
+    pub lock_minutes: u64,
+}
+
+impl Default for SharedVaultConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            user_folder: None,
+            protected_paths: Vec::new(),
+            soft_locks: false,
+            lock_minutes: default_shared_vault_lock_minutes(),
+        }
+    }
+}
+
+/// The macOS LaunchAgent that starts the daemon at login. Applied when the
+/// agent is written, by `obsyncgit service install --launch-agent` or the
+/// desktop helper's autostart switch.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LaunchdConfig {
+    /// Start the daemon again when it crashes or exits with an error. A
+    /// clean exit, such as after `obsyncgit service stop`, leaves it
+    /// stopped.
+    pub keep_alive: bool,
+    /// Least seconds between two starts, so a daemon that fails right away
+    /// (a broken config, an unmounted vault) is not respawned every ten
+    /// seconds.
+    pub throttle_interval_seconds: u64,
+    pub process_type: LaunchdProcessType,
+    /// Make the daemon's disk access low priority whenever macOS throttles
+    /// it in the background, as during Power Nap dark wakes, so syncing
+    /// never slows down what the user is doing.
+    pub low_priority_io: bool,
+}
+
+impl Default for LaunchdConfig {
+    fn default() -> Self {
+        Self {
+            keep_alive: true,
+            throttle_interval_seconds: default_launchd_throttle_interval_seconds(),
+            process_type: LaunchdProcessType::default(),
+            low_priority_io: true,
+        }
+    }
+}
+
+/// launchd's `ProcessType`: how much CPU and disk time macOS gives the
+/// daemon.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LaunchdProcessType {
+    /// Throttled like other background jobs; syncing is seldom urgent.
+    #[default]
+    Background,
+    Standard,
+    /// Background, raised while the daemon works on behalf of an app.
+    Adaptive,
+    /// Never throttled.
+    Interactive,
+}
+
+impl LaunchdProcessType {
+    /// The value as launchd spells it.
+    pub fn as_plist(self) -> &'static str {
+        match self {
+            Self::Background => "Background",
+            Self::Standard => "Standard",
+            Self::Adaptive => "Adaptive",
+            Self::Interactive => "Interactive",
+        }
+    }
+}
+
+/// Workarounds for file systems that disagree about file names.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CompatConfig {
+    pub unicode_normalization: UnicodeNormalization,
+    pub line_endings: LineEndings,
+}
+
+/// What to do about file names spelled in different Unicode forms. macOS
+/// can hand out names decomposed (NFD: `e` plus a combining accent) where
+/// Linux and Windows use the composed form (NFC: `é`), so one note can end
+/// up tracked twice under names that look identical.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnicodeNormalization {
+    /// Compare and commit names byte for byte.
+    Off,
+    /// Hold back new files that would duplicate a note under another form
+    /// and log a warning naming the duplicates.
+    #[default]
+    Warn,
+    /// Also rename files to the composed form before committing them, and
+    /// tracked files after every pull.
+    Nfc,
+}
+
+impl std::str::FromStr for UnicodeNormalization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "warn" => Ok(Self::Warn),
+            "nfc" => Ok(Self::Nfc),
+            other => {
+                anyhow::bail!("unknown unicode normalization '{other}' (expected off, warn or nfc)")
+            }
+        }
+    }
+}
+
+/// Which line endings notes are stored with. Windows editors may save
+/// CRLF where macOS and Linux save LF, and a note whose line endings flip
+/// shows up as changed on every line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEndings {
+    /// Leave line endings to the vault's own `.gitattributes`.
+    #[default]
+    Off,
+    /// Store text files with LF, through rules in `.gitattributes`, and
+    /// convert the files already stored with CRLF in one commit.
+    Lf,
+}
+
+impl std::str::FromStr for LineEndings {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "lf" => Ok(Self::Lf),
+            other => anyhow::bail!("unknown line endings policy '{other}' (expected off or lf)"),
+        }
+    }
+}
+
+/// Desktop helper preferences.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GuiConfig {
+    /// Light or dark colours.
+    pub theme: GuiTheme,
+    /// Black-on-white colours with strong outlines. Unset follows the
+    /// system's high contrast setting.
+    pub high_contrast: Option<bool>,
+}
+
+/// Colour scheme of the desktop helper.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuiTheme {
+    /// Follow the system's dark mode setting.
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl std::str::FromStr for GuiTheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "system" | "auto" => Ok(Self::System),
+            "light" => Ok(Self::Light),
+            "dark" => Ok(Self::Dark),
+            other => anyhow::bail!("unknown theme '{other}' (expected system, light or dark)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WatcherConfig {
+    pub backend: WatcherBackend,
+}
+
+/// How the vault is watched for changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatcherBackend {
+    /// Native notifications (inotify, FSEvents, ReadDirectoryChangesW),
+    /// switching to selective watching or polling when the vault does not
+    /// fit into the OS watch limit.
+    #[default]
+    Auto,
+    /// Native notifications only; an exhausted watch limit stops the daemon.
+    Native,
+    /// Native notifications for the most recently active directories only,
+    /// a slow scan for the rest. For vaults larger than the watch budget.
+    Selective,
+    /// Periodically scan the vault. Works everywhere, costs more CPU.
+    Poll,
+}
+
+impl std::str::FromStr for WatcherBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "native" => Ok(Self::Native),
+            "selective" => Ok(Self::Selective),
+            "poll" | "polling" => Ok(Self::Poll),
+            other => {
+                anyhow::bail!(
+                    "unknown watcher backend '{other}' (expected auto, native, selective or poll)"
+                )
+            }
+        }
+    }
+}
+
+/// Records every git command of recent sync cycles for `obsyncgit status --trace`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TraceConfig {
+    pub enabled: bool,
+    /// Number of cycles kept in the trace file.
+    pub cycles: usize,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cycles: default_trace_cycles(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletionMode {
+    /// Commit deletions like any other change.
+    #[default]
+    Commit,
+    /// Move deleted files into `trash_dir` and commit the move instead.
+    Trash,
+    /// Hold deletions back until they are `delay_hours` old.
+    Delay,
+}
+
+impl std::str::FromStr for DeletionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "commit" => Ok(Self::Commit),
+            "trash" => Ok(Self::Trash),
+            "delay" => Ok(Self::Delay),
+            other => {
+                anyhow::bail!("unknown deletion mode '{other}' (expected commit, trash or delay)")
+            }
+        }
+    }
 }
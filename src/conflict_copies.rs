@@ -0,0 +1,125 @@
+//! Conflict copies other sync tools leave in the vault, such as Syncthing's
+//! `note.sync-conflict-20240501-181500-ABCDEF1.md` or Dropbox's
+//! `note (conflicted copy 2024-05-01).md`. Committed as they are, they
+//! spread to every device as clutter; `sync.conflict_copies` keeps them out
+//! of commits, lists them in the conflict inbox or settles them itself.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+
+use crate::conflicts::{Resolution, copy_path};
+
+/// The tool a conflict copy comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Syncthing,
+    /// Dropbox, Nextcloud and ownCloud, which name copies alike.
+    ConflictedCopy,
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Syncthing => "Syncthing",
+            Self::ConflictedCopy => "Dropbox/Nextcloud",
+        })
+    }
+}
+
+/// `<stem>.sync-conflict-<date>-<time>-<device id><ext>`.
+static SYNCTHING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<stem>.+)\.sync-conflict-\d{8}-\d{6}-[0-9A-Z]{7}(?P<ext>\.[^./]*)?$")
+        .expect("valid regex")
+});
+
+/// `<stem> (<who>'s conflicted copy <date>)<ext>` and `<stem> (conflicted
+/// copy <date> <time>)<ext>`.
+static CONFLICTED_COPY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<stem>.+?) \([^()/]*[Cc]onflicted [Cc]opy[^()/]*\)(?P<ext>\.[^./]*)?$")
+        .expect("valid regex")
+});
+
+/// A file another sync tool wrote beside a note it could not merge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictCopy {
+    /// Vault-relative path of the copy.
+    pub path: String,
+    /// The note it is a copy of.
+    pub original: String,
+    pub tool: Tool,
+}
+
+/// The conflict copy at vault-relative `path`, if its name marks it as one.
+pub fn parse(path: &str) -> Option<ConflictCopy> {
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (format!("{dir}/"), name),
+        None => (String::new(), path),
+    };
+    [
+        (&*SYNCTHING, Tool::Syncthing),
+        (&*CONFLICTED_COPY, Tool::ConflictedCopy),
+    ]
+    .into_iter()
+    .find_map(|(pattern, tool)| {
+        let captures = pattern.captures(name)?;
+        let ext = captures.name("ext").map_or("", |ext| ext.as_str());
+        Some(ConflictCopy {
+            path: path.to_string(),
+            original: format!("{dir}{}{ext}", &captures["stem"]),
+            tool,
+        })
+    })
+}
+
+/// The conflict copies among `paths`.
+pub fn find(paths: &[String]) -> Vec<ConflictCopy> {
+    paths.iter().filter_map(|path| parse(path)).collect()
+}
+
+/// Settles `copy` on disk: `Mine` deletes it, `Theirs` puts it in place of
+/// the note, and `Both` keeps it as `<note> (other device)`. The daemon
+/// commits the result like any other edit.
+pub fn resolve(root: &Path, copy: &ConflictCopy, resolution: Resolution) -> Result<()> {
+    let source = root.join(&copy.path);
+    match resolution {
+        Resolution::Mine => fs::remove_file(&source)
+            .with_context(|| format!("failed to delete {}", source.display())),
+        Resolution::Theirs => {
+            let target = root.join(&copy.original);
+            fs::rename(&source, &target)
+                .with_context(|| format!("failed to replace {}", target.display()))
+        }
+        Resolution::Both => {
+            let target = root.join(copy_path(&copy.original));
+            if target.exists() {
+                bail!(
+                    "{} already exists; rename or delete it first",
+                    target.display()
+                );
+            }
+            fs::rename(&source, &target)
+                .with_context(|| format!("failed to rename {}", source.display()))
+        }
+    }
+}
+
+/// `sync.conflict_copies: newest`: keeps whichever of the note and `copy`
+/// was modified last at the note's path. Returns which one that was.
+pub fn resolve_newest(root: &Path, copy: &ConflictCopy) -> Result<Resolution> {
+    let modified = |path: &str| {
+        fs::metadata(root.join(path))
+            .and_then(|meta| meta.modified())
+            .ok()
+    };
+    let resolution = match (modified(&copy.original), modified(&copy.path)) {
+        (Some(original), Some(copied)) if original >= copied => Resolution::Mine,
+        _ => Resolution::Theirs,
+    };
+    resolve(root, copy, resolution)?;
+    Ok(resolution)
+}
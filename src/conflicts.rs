@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, bail};
+
+use crate::config::Config;
+use crate::git::GitFacade;
+use crate::tr;
+
+/// Which side of a conflict to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// This device's version.
+    Mine,
+    /// The version on the remote.
+    Theirs,
+    /// This device's version at the original path and the remote one next
+    /// to it, see [`copy_path`].
+    Both,
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Mine => "mine",
+            Self::Theirs => "theirs",
+            Self::Both => "both",
+        })
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "mine" | "ours" | "local" => Ok(Self::Mine),
+            "theirs" | "remote" => Ok(Self::Theirs),
+            "both" => Ok(Self::Both),
+            other => bail!("unknown resolution '{other}' (expected mine, theirs or both)"),
+        }
+    }
+}
+
+/// A note edited both on this device and on the remote in ways git cannot
+/// merge. While any are open the daemon keeps committing locally but cannot
+/// pull or push.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    /// Vault-relative path.
+    pub path: String,
+    /// This device's version, `None` if it was deleted here.
+    pub mine: Option<String>,
+    /// The remote version, `None` if it was deleted there.
+    pub theirs: Option<String>,
+}
+
+impl Conflict {
+    /// What happened to the note on each side, for listing conflicts.
+    pub fn summary(&self) -> String {
+        match (&self.mine, &self.theirs) {
+            (None, _) => tr!("conflict-deleted-here"),
+            (_, None) => tr!("conflict-deleted-there"),
+            _ => tr!("conflict-edited-both"),
+        }
+    }
+}
+
+/// The remote branch local history is reconciled with.
+pub fn upstream(config: &Config) -> String {
+    format!("{}/{}", config.remote.name, config.branch)
+}
+
+/// The conflict inbox: notes that block the next pull, as of the last
+/// fetch. Nothing is stored; the list is worked out from the repository, so
+/// it empties itself once the conflicts are resolved from any device.
+pub fn list(git: &GitFacade, config: &Config) -> Result<Vec<Conflict>> {
+    let upstream = upstream(config);
+    git.merge_conflicts(&upstream)?
+        .into_iter()
+        .map(|path| {
+            Ok(Conflict {
+                mine: git.show_file("HEAD", &path)?,
+                theirs: git.show_file(&upstream, &path)?,
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Settles every open conflict with a merge commit that takes the chosen
+/// version of each note; the daemon pushes it on its next cycle. All open
+/// conflicts need a choice, as git can only record the merge as a whole.
+pub fn resolve(
+    git: &GitFacade,
+    config: &Config,
+    choices: &BTreeMap<String, Resolution>,
+) -> Result<()> {
+    let upstream = upstream(config);
+    let open = git.merge_conflicts(&upstream)?;
+    if open.is_empty() {
+        bail!("there are no conflicts to resolve");
+    }
+    let undecided: Vec<&str> = open
+        .iter()
+        .filter(|path| !choices.contains_key(*path))
+        .map(String::as_str)
+        .collect();
+    if !undecided.is_empty() {
+        bail!("choose a version for {} as well", undecided.join(", "));
+    }
+
+    let mut entries = Vec::new();
+    for path in &open {
+        let mine = git.tree_entry("HEAD", path)?;
+        let theirs = git.tree_entry(&upstream, path)?;
+        match choices[path] {
+            Resolution::Mine => entries.push((path.clone(), mine)),
+            Resolution::Theirs => entries.push((path.clone(), theirs)),
+            // Keep whichever exists when one side deleted the note.
+            Resolution::Both => match (mine, theirs) {
+                (Some(mine), Some(theirs)) => {
+                    entries.push((path.clone(), Some(mine)));
+                    entries.push((copy_path(path), Some(theirs)));
+                }
+                (mine, theirs) => entries.push((path.clone(), mine.or(theirs))),
+            },
+        }
+    }
+
+    let message = format!(
+        "{} resolve conflicts in {} file(s)",
+        config.commit.prefix,
+        open.len()
+    );
+    git.merge_resolved(&upstream, &entries, &message)
+}
+
+/// Where "keep both" puts the remote version: `notes/idea.md` becomes
+/// `notes/idea (other device).md`.
+pub fn copy_path(path: &str) -> String {
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (format!("{dir}/"), name),
+        None => (String::new(), path),
+    };
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{dir}{stem} (other device).{ext}"),
+        _ => format!("{dir}{name} (other device)"),
+    }
+}
+
+/// One row of a side-by-side comparison; `None` leaves that side blank.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRow {
+    pub mine: Option<String>,
+    pub theirs: Option<String>,
+}
+
+impl DiffRow {
+    pub fn is_change(&self) -> bool {
+        self.mine != self.theirs
+    }
+}
+
+/// Longest note, in lines, that is aligned line by line; longer ones are
+/// shown as two plain columns.
+const MAX_ALIGNED_LINES: usize = 4000;
+
+/// Lines of both versions lined up so unchanged lines sit side by side and
+/// edits, insertions and deletions stand out.
+pub fn side_by_side(mine: &str, theirs: &str) -> Vec<DiffRow> {
+    let mine: Vec<&str> = mine.lines().collect();
+    let theirs: Vec<&str> = theirs.lines().collect();
+    if mine.len() > MAX_ALIGNED_LINES || theirs.len() > MAX_ALIGNED_LINES {
+        let rows = mine.len().max(theirs.len());
+        return (0..rows)
+            .map(|row| DiffRow {
+                mine: mine.get(row).map(|line| line.to_string()),
+                theirs: theirs.get(row).map(|line| line.to_string()),
+            })
+            .collect();
+    }
+
+    // Longest common subsequence, filled from the end so the walk below
+    // can go front to back.
+    let mut common = vec![vec![0u32; theirs.len() + 1]; mine.len() + 1];
+    for i in (0..mine.len()).rev() {
+        for j in (0..theirs.len()).rev() {
+            common[i][j] = if mine[i] == theirs[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut rows = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut removed: Vec<&str> = Vec::new();
+    let mut added: Vec<&str> = Vec::new();
+    let flush = |rows: &mut Vec<DiffRow>, removed: &mut Vec<&str>, added: &mut Vec<&str>| {
+        // Pair changed lines up so an edited line shows next to its
+        // counterpart instead of as a deletion plus an insertion.
+        for row in 0..removed.len().max(added.len()) {
+            rows.push(DiffRow {
+                mine: removed.get(row).map(|line| line.to_string()),
+                theirs: added.get(row).map(|line| line.to_string()),
+            });
+        }
+        removed.clear();
+        added.clear();
+    };
+    while i < mine.len() || j < theirs.len() {
+        if i < mine.len() && j < theirs.len() && mine[i] == theirs[j] {
+            flush(&mut rows, &mut removed, &mut added);
+            rows.push(DiffRow {
+                mine: Some(mine[i].to_string()),
+                theirs: Some(theirs[j].to_string()),
+            });
+            i += 1;
+            j += 1;
+        } else if j == theirs.len() || (i < mine.len() && common[i + 1][j] >= common[i][j + 1]) {
+            removed.push(mine[i]);
+            i += 1;
+        } else {
+            added.push(theirs[j]);
+            j += 1;
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
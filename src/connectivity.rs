@@ -0,0 +1,155 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow};
+use camino::Utf8PathBuf;
+use tracing::warn;
+
+use crate::config::{Config, SubmoduleConfig};
+use crate::git::GitFacade;
+
+/// File committed to the test branch.
+const TEST_FILE: &str = "obsyncgit-verify.md";
+
+/// Steps of `obsyncgit verify`, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// List the sync branch on the remote: the URL, network and read access.
+    Reach,
+    /// Commit a test file with the configured identity.
+    Commit,
+    /// Push it to a throwaway branch: write access and branch rules.
+    Push,
+    /// Delete the throwaway branch again.
+    Delete,
+}
+
+impl Step {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Reach => "reach remote",
+            Self::Commit => "commit",
+            Self::Push => "push",
+            Self::Delete => "delete branch",
+        }
+    }
+
+    /// The usual causes when the step fails.
+    pub fn hint(self) -> &'static str {
+        match self {
+            Self::Reach => {
+                "check repo_url, the network and proxy settings, and that the SSH key or token may read the repository"
+            }
+            Self::Commit => {
+                "set git.author_name and git.author_email, or git's own user.name and user.email"
+            }
+            Self::Push => {
+                "the credentials can read but not write (e.g. a read-only deploy key or token), or a rule on the host blocks creating branches"
+            }
+            Self::Delete => {
+                "the host does not let these credentials delete branches; remove the test branch by hand"
+            }
+        }
+    }
+}
+
+/// Checks that this device can sync without touching the vault: a scratch
+/// repository commits a test file, pushes it to a throwaway branch and
+/// deletes that branch again, using the vault's remote, SSH key and proxy.
+/// `report` hears about every step as it finishes, with a detail line or
+/// the error. The run stops at the first failure, which is returned.
+pub fn round_trip(
+    config: &Config,
+    mut report: impl FnMut(Step, &Result<String>),
+) -> std::result::Result<(), Step> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let scratch =
+        std::env::temp_dir().join(format!("obsyncgit-verify-{}-{nanos}", std::process::id()));
+    let outcome = run_steps(config, &scratch, &mut report);
+    if scratch.exists()
+        && let Err(err) = fs::remove_dir_all(&scratch)
+    {
+        warn!(?err, path = %scratch.display(), "failed to remove the scratch repository");
+    }
+    outcome
+}
+
+fn run_steps(
+    config: &Config,
+    scratch: &Path,
+    report: &mut impl FnMut(Step, &Result<String>),
+) -> std::result::Result<(), Step> {
+    let mut check = |step: Step, result: Result<String>| {
+        report(step, &result);
+        result.map(drop).map_err(|_| step)
+    };
+
+    let git = match scratch_repo(config, scratch) {
+        Ok(git) => git,
+        Err(err) => return check(Step::Reach, Err(err)),
+    };
+    let reached = git
+        .init_scratch(&config.repo_url)
+        .and_then(|()| git.remote_branch_tip(&config.branch))
+        .map(|tip| match tip {
+            Some(commit) => format!(
+                "{} is at {} on {}",
+                config.branch,
+                commit.get(..10).unwrap_or(&commit),
+                config.repo_url
+            ),
+            None => format!(
+                "{} has no branch {} yet; the first sync creates it",
+                config.repo_url, config.branch
+            ),
+        });
+    check(Step::Reach, reached)?;
+
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let branch = format!("obsyncgit-verify-{secs}");
+    let committed = fs::write(
+        scratch.join(TEST_FILE),
+        format!("Connectivity check by `obsyncgit verify`; branch {branch} is deleted again.\n"),
+    )
+    .context("failed to write the test file")
+    .and_then(|()| git.stage_all())
+    .and_then(|()| git.commit("obsyncgit verify"))
+    .map(|_| format!("{TEST_FILE} committed in a scratch repository"));
+    check(Step::Commit, committed)?;
+
+    let pushed = git
+        .push_head_to(&branch)
+        .map(|()| format!("created {branch}"));
+    check(Step::Push, pushed)?;
+
+    let deleted = git
+        .delete_remote_branch(&branch)
+        .map(|()| format!("removed {branch}"));
+    check(Step::Delete, deleted)
+}
+
+/// A facade for an empty repository in `scratch` that talks to the vault's
+/// remote and commits as the daemon does. An identity set only in the
+/// vault's own git config is carried over.
+fn scratch_repo(config: &Config, scratch: &Path) -> Result<GitFacade> {
+    let mut scratch_config = config.clone();
+    let vault = GitFacade::observer(config)?;
+    if scratch_config.git.author_name.is_none() {
+        scratch_config.git.author_name = vault.config_value("user.name");
+    }
+    if scratch_config.git.author_email.is_none() {
+        scratch_config.git.author_email = vault.config_value("user.email");
+    }
+    scratch_config.workdir = Utf8PathBuf::from_path_buf(scratch.to_path_buf())
+        .map_err(|path| anyhow!("temporary directory {} is not UTF-8", path.display()))?;
+    scratch_config.git.separate_git_dir = None;
+    scratch_config.submodules = SubmoduleConfig::default();
+    GitFacade::new(&scratch_config)
+}
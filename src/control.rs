@@ -0,0 +1,246 @@
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use tokio::sync::Notify;
+use tracing::{debug, info};
+
+use crate::config::Config;
+use crate::git::CloneProgress;
+use crate::git_error::GitError;
+use crate::instance;
+use crate::shutdown::Shutdown;
+
+/// How often a daemon looks for requests from other processes.
+const INBOX_POLL: Duration = Duration::from_secs(2);
+
+/// Manual controls for a running daemon, used by interactive front ends
+/// such as `obsyncgit tui`. Like [`crate::shutdown::Shutdown`], clones share
+/// state and the daemon loop is woken as soon as something changes.
+#[derive(Debug, Clone, Default)]
+pub struct Control {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    paused: AtomicBool,
+    sync_requested: AtomicBool,
+    resumed: AtomicBool,
+    clone_progress: Mutex<Option<CloneProgress>>,
+    notify: Notify,
+}
+
+impl Control {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks for a full sync cycle (commit, pull and push) right away. Also
+    /// resumes a paused daemon.
+    pub fn request_sync(&self) {
+        self.inner.paused.store(false, Ordering::SeqCst);
+        self.inner.sync_requested.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_one();
+    }
+
+    /// Stops or resumes committing and network operations. Changes keep
+    /// being recorded while paused and are synced on resume.
+    pub fn set_paused(&self, paused: bool) {
+        self.inner.paused.store(paused, Ordering::SeqCst);
+        self.inner.notify.notify_one();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.paused.load(Ordering::SeqCst)
+    }
+
+    /// Consumes a pending [`Control::request_sync`].
+    pub fn take_sync_request(&self) -> bool {
+        self.inner.sync_requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Tells the daemon the machine woke from sleep. Unlike
+    /// [`Control::request_sync`] this leaves a pause in place.
+    pub fn notify_resumed(&self) {
+        self.inner.resumed.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_one();
+    }
+
+    /// Consumes a pending [`Control::notify_resumed`].
+    pub fn take_resumed(&self) -> bool {
+        self.inner.resumed.swap(false, Ordering::SeqCst)
+    }
+
+    /// Publishes how far the first clone of the vault got; `None` once it
+    /// finished or failed.
+    pub fn set_clone_progress(&self, progress: Option<CloneProgress>) {
+        *self
+            .inner
+            .clone_progress
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = progress;
+    }
+
+    /// The latest [`Control::set_clone_progress`], while a clone runs.
+    pub fn clone_progress(&self) -> Option<CloneProgress> {
+        self.inner
+            .clone_progress
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Resolves after the next pause, resume, sync request or wake-up; one that
+    /// happened while nobody was waiting is delivered to the next call.
+    pub async fn changed(&self) {
+        self.inner.notify.notified().await;
+    }
+}
+
+/// Requests to a daemon running in another process, such as the desktop
+/// helper's tray menu. They are left as files in the state directory, keyed
+/// by vault, and the daemon applies them to its [`Control`] within a couple
+/// of seconds. Nothing is lost when no daemon runs: the requests wait. The
+/// daemon answers the same way with the cause of its last failure.
+#[derive(Debug, Clone)]
+pub struct ControlInbox {
+    dir: Utf8PathBuf,
+}
+
+impl ControlInbox {
+    pub fn new(config: &Config) -> Result<Self> {
+        let dir = config
+            .state_dir()?
+            .join("control")
+            .join(format!("vault-{}", instance::vault_key(config)));
+        Ok(Self { dir })
+    }
+
+    /// The directory holding the requests and answers.
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    /// Asks for a sync right away, lifting a pause set through the inbox.
+    pub fn request_sync(&self) -> Result<()> {
+        self.remove("pause-until");
+        self.write("sync", "")
+    }
+
+    /// Pauses syncing for `duration`; the daemon resumes by itself after.
+    pub fn pause_for(&self, duration: Duration) -> Result<()> {
+        let until = SystemTime::now() + duration;
+        let secs = until
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.write("pause-until", &secs.to_string())
+    }
+
+    /// Lifts a pause set with [`ControlInbox::pause_for`] early.
+    pub fn resume(&self) {
+        self.remove("pause-until");
+    }
+
+    /// When the current pause ends, if one is in effect.
+    pub fn paused_until(&self) -> Option<SystemTime> {
+        let secs: u64 = fs::read_to_string(self.dir.join("pause-until"))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        let until = UNIX_EPOCH + Duration::from_secs(secs);
+        (until > SystemTime::now()).then_some(until)
+    }
+
+    /// Applies requests to `control` until shutdown. A pause that expires
+    /// is lifted again, but only if the inbox set it.
+    pub async fn serve(self, control: Control, shutdown: Shutdown) {
+        let mut paused_here = false;
+        loop {
+            if self.dir.join("sync").exists() {
+                self.remove("sync");
+                info!("sync requested from another process");
+                control.request_sync();
+                paused_here = false;
+            }
+            match self.paused_until() {
+                Some(until) if !paused_here => {
+                    let minutes = until
+                        .duration_since(SystemTime::now())
+                        .unwrap_or_default()
+                        .as_secs()
+                        .div_ceil(60);
+                    info!(minutes, "sync paused from another process");
+                    control.set_paused(true);
+                    paused_here = true;
+                }
+                None if paused_here => {
+                    self.remove("pause-until");
+                    control.set_paused(false);
+                    paused_here = false;
+                }
+                _ => {}
+            }
+            tokio::select! {
+                _ = shutdown.wait() => return,
+                _ = tokio::time::sleep(INBOX_POLL) => {}
+            }
+        }
+    }
+
+    /// Leaves the cause of the daemon's last failed cycle for `status` and
+    /// the desktop bars, until a cycle succeeds again.
+    pub fn record_failure(&self, failure: &GitError) -> Result<()> {
+        self.write(
+            "last-error",
+            &format!("{}\n{}", failure.category(), failure.message()),
+        )
+    }
+
+    pub fn clear_failure(&self) {
+        self.remove("last-error");
+    }
+
+    /// The failure left by [`ControlInbox::record_failure`], if any.
+    pub fn last_failure(&self) -> Option<GitError> {
+        let contents = fs::read_to_string(self.dir.join("last-error")).ok()?;
+        let (category, message) = contents.split_once('\n').unwrap_or((&contents, ""));
+        Some(GitError::from_parts(category, message.to_string()))
+    }
+
+    /// Leaves the files `security.secret_scan` holds back for `status` and
+    /// the desktop bars; an empty list clears them.
+    pub fn record_held_secrets(&self, paths: &[String]) -> Result<()> {
+        if paths.is_empty() {
+            self.remove("held-secrets");
+            return Ok(());
+        }
+        self.write("held-secrets", &paths.join("\n"))
+    }
+
+    /// The files left by [`ControlInbox::record_held_secrets`].
+    pub fn held_secrets(&self) -> Vec<String> {
+        fs::read_to_string(self.dir.join("held-secrets"))
+            .map(|contents| contents.lines().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, name: &str, contents: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir).with_context(|| format!("failed to create {}", self.dir))?;
+        let path = self.dir.join(name);
+        fs::write(&path, contents).with_context(|| format!("failed to write {path}"))
+    }
+
+    fn remove(&self, name: &str) {
+        if let Err(err) = fs::remove_file(self.dir.join(name))
+            && err.kind() != std::io::ErrorKind::NotFound
+        {
+            debug!(?err, name, "failed to remove control request");
+        }
+    }
+}
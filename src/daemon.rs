@@ -1,265 +1,2181 @@
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
-use crossbeam_channel::{Receiver, unbounded};
-use notify::{Config as NotifyConfig, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use anyhow::{Context, Result, bail};
+use chrono::{Local, NaiveDate};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::task::block_in_place;
 use tracing::{debug, error, info, warn};
 
-use crate::config::Config;
-use crate::git::GitFacade;
-use crate::ignore::IgnoreMatcher;
+use crate::alerts::Alerter;
+use crate::backup;
+use crate::case_collisions::{self, Collision};
+use crate::clock::{Deadline, SystemClock};
+use crate::cloud::{self, CloudProvider};
+use crate::config::{
+    CaseCollisionPolicy, CommitMode, CommitStyle, Config, ConflictCopyPolicy, FileNamePolicy,
+    MeteredPolicy, RemoteKind, SyncMode, SyncTopology, UnicodeNormalization, UpdateShell,
+};
+use crate::conflict_copies::{self, ConflictCopy};
+use crate::conflicts::{self, Resolution};
+use crate::control::{Control, ControlInbox};
+use crate::deletions::DeletionGuard;
+use crate::digest;
+use crate::events::{Event, EventLog};
+use crate::file_names;
+use crate::git::{self, GitFacade, Submodule};
+use crate::git_error::GitError;
+use crate::ignore::{self, Blocked, IgnoreMatcher};
+use crate::insignificant::InsignificantRules;
+use crate::integrity;
+use crate::line_endings;
+use crate::locks::LockBoard;
+use crate::metered::MeteredDetector;
+use crate::mqtt::MqttPublisher;
+use crate::normalization;
+use crate::own_writes::OwnWrites;
+use crate::pacing::{CommitTiming, CycleError, GitBackend, Pacer};
+use crate::power::PowerMonitor;
+use crate::preflight;
+use crate::provider::{PullRequest, PullRequestTarget};
+use crate::recovery;
+use crate::redaction;
+use crate::remote::{RemoteHealth, is_protected_branch, is_push_rejected, is_remote_unavailable};
+use crate::replica;
+use crate::resume;
+use crate::schedule::Schedule;
+use crate::secret_scan::{Finding, SecretScanner};
+use crate::shared_config::SharedPolicy;
+use crate::shared_vault::{self, SharedVault};
+use crate::shutdown::Shutdown;
+use crate::snapshots;
+use crate::stability::WriteStability;
+use crate::statusbar::SyncHealth;
+use crate::sync_notes::{self, SyncNote};
+use crate::trace::SyncTrace;
+use crate::updater::shell_command;
+use crate::watcher::{WatchEvent, WatcherSupervisor};
 
-#[derive(Debug)]
-enum SyncEvent {
-    Changed,
-    Rescan,
-    WatcherError(String),
-}
+/// How often the schedule is re-evaluated while network operations are deferred.
+const SCHEDULE_RECHECK: Duration = Duration::from_secs(60);
+/// How often a vanished vault (unmounted drive, dropped network share) is
+/// looked for again.
+const VAULT_RECHECK: Duration = Duration::from_secs(10);
+/// Least time between startup and a scheduled `git fsck`.
+const FSCK_STARTUP_DELAY: Duration = Duration::from_secs(300);
+/// Least time between startup and a scheduled backup bundle.
+const BACKUP_STARTUP_DELAY: Duration = Duration::from_secs(600);
+/// How soon a failed backup is tried again, e.g. once its drive is back.
+const BACKUP_RETRY: Duration = Duration::from_secs(3600);
+/// Least time between startup and an overdue digest, so it can count the
+/// first sync.
+const DIGEST_STARTUP_DELAY: Duration = Duration::from_secs(120);
+/// How soon a digest no channel took is sent again.
+const DIGEST_RETRY: Duration = Duration::from_secs(3600);
+/// How often free disk space is checked again while startup waits for it.
+const DISK_RECHECK: Duration = Duration::from_secs(60);
+/// Longest the loop sleeps without re-checking its environment.
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_secs(300);
+/// Backoff steps are powers of two seconds; this one waits about a minute.
+const MAX_BACKOFF_STEP: u32 = 6;
+/// About four minutes, for failures only the user can fix.
+const ALERT_BACKOFF_STEP: u32 = 8;
+/// At most 8 seconds between attempts while a lock or a racing push is in
+/// the way.
+const LOCK_BACKOFF_STEP: u32 = 3;
 
 pub struct SyncDaemon {
     config: Config,
     git: GitFacade,
-    ignore: IgnoreMatcher,
-    shutdown: Arc<AtomicBool>,
+    ignore: Arc<RwLock<IgnoreMatcher>>,
+    stability: WriteStability,
+    /// Set when the last commit skipped paths that were still being written.
+    unsettled: bool,
+    /// The commit this daemon made or amended last, see
+    /// `commit.amend_window_minutes`.
+    last_commit: Option<String>,
+    /// Set after amending a commit already on our device branch; the next
+    /// push replaces it there.
+    rewrite_device_branch: bool,
+    metered: MeteredDetector,
+    metered_now: bool,
+    power: PowerMonitor,
+    /// Whether the machine runs on a low battery, see `power`.
+    low_battery: bool,
+    schedule: Schedule,
+    /// Whether the schedule currently allows network operations.
+    schedule_open: bool,
+    deletions: DeletionGuard,
+    trace: Option<Arc<SyncTrace>>,
+    /// Cloud sync service whose folder holds the vault, if any.
+    cloud: Option<CloudProvider>,
+    /// Set under the `pull-request` topology.
+    pull_requests: Option<PullRequestTarget>,
+    /// Collisions found after the last pull, already warned about.
+    case_collisions: Vec<Collision>,
+    /// Pending files with names Windows rejects, already warned about.
+    unsafe_names: HashSet<String>,
+    /// Pending files kept out by the size limit or extension blocklist,
+    /// already warned about.
+    blocked_files: HashSet<String>,
+    /// Conflict copies of other sync tools kept out of commits, already
+    /// warned about.
+    conflict_copies: HashSet<String>,
+    secret_scan: Option<SecretScanner>,
+    insignificant: Option<InsignificantRules>,
+    /// Pending files held back as likely secrets, already warned about;
+    /// `None` until the first scan.
+    held_secrets: Option<HashSet<String>>,
+    /// Tracked names not in the composed Unicode form after the last pull,
+    /// already warned about.
+    decomposed: Vec<String>,
+    /// Pending files held back as duplicates in another Unicode form,
+    /// already warned about.
+    duplicate_forms: HashSet<String>,
+    shared_vault: Option<SharedVault>,
+    /// Pending files under `shared_vault.protected_paths`, already warned
+    /// about.
+    protected_files: HashSet<String>,
+    locks: Option<LockBoard>,
+    /// When the watcher saw the first change not committed yet.
+    changed_since: Option<Instant>,
+    /// Files the last pull wrote, whose watcher events are no edits.
+    own_writes: OwnWrites,
+    /// `None` when the state directory is unknown.
+    events: Option<EventLog>,
+    /// Started by [`Self::run`] when `mqtt.enabled` is set.
+    mqtt: Option<MqttPublisher>,
+    alerts: Option<Alerter>,
+    /// The commit each entry of `replicas` was last mirrored at; `None`
+    /// after a failure already warned about.
+    replicated: HashMap<String, Option<String>>,
+    /// The day `snapshots.tag_daily` last looked for days to tag.
+    snapshots_checked: Option<NaiveDate>,
+    control: Control,
+    shutdown: Shutdown,
 }
 
 impl SyncDaemon {
     pub fn new(config: Config) -> Result<Self> {
-        let git = GitFacade::new(&config)?;
-        let ignore = IgnoreMatcher::new(config.workdir.as_std_path(), &config.ignore.globs)?;
+        let mut git = GitFacade::new(&config)?;
+        let trace = if config.trace.enabled {
+            let trace = Arc::new(SyncTrace::new(&config.state_dir()?, config.trace.cycles));
+            git.set_trace(trace.clone());
+            Some(trace)
+        } else {
+            None
+        };
+        let cloud = cloud::detect(config.workdir.as_std_path());
+        let ignore = IgnoreMatcher::for_config(&config, cloud)?;
+        let stability = WriteStability::new(&config.stability);
+        let schedule = Schedule::new(&config.schedule).context("invalid schedule")?;
+        let deletions = DeletionGuard::new(&config.deletions);
+        let secret_scan = SecretScanner::new(&config.security.secret_scan)?;
+        let insignificant = InsignificantRules::new(&config.commit.insignificant);
+        let shared_vault = SharedVault::new(&config.shared_vault)?;
+        let locks = LockBoard::new(&config);
+        let own_writes = OwnWrites::new(config.workdir.as_std_path());
+        let events = EventLog::new(&config).ok();
+        let alerts = Alerter::new(&config);
+        let pull_requests = if config.sync.topology == SyncTopology::PullRequest {
+            Some(
+                PullRequestTarget::from_config(&config)
+                    .context("cannot use sync.topology pull-request")?,
+            )
+        } else {
+            None
+        };
         Ok(Self {
             config,
             git,
-            ignore,
-            shutdown: Arc::new(AtomicBool::new(false)),
+            ignore: Arc::new(RwLock::new(ignore)),
+            stability,
+            unsettled: false,
+            last_commit: None,
+            rewrite_device_branch: false,
+            metered: MeteredDetector::new(),
+            metered_now: false,
+            power: PowerMonitor::new(),
+            low_battery: false,
+            schedule,
+            schedule_open: true,
+            deletions,
+            trace,
+            cloud,
+            pull_requests,
+            case_collisions: Vec::new(),
+            unsafe_names: HashSet::new(),
+            blocked_files: HashSet::new(),
+            conflict_copies: HashSet::new(),
+            secret_scan,
+            insignificant,
+            held_secrets: None,
+            decomposed: Vec::new(),
+            duplicate_forms: HashSet::new(),
+            shared_vault,
+            protected_files: HashSet::new(),
+            locks,
+            changed_since: None,
+            own_writes,
+            events,
+            mqtt: None,
+            alerts,
+            replicated: HashMap::new(),
+            snapshots_checked: None,
+            control: Control::new(),
+            shutdown: Shutdown::new(),
         })
     }
 
-    pub fn shutdown_handle(&self) -> Arc<AtomicBool> {
+    pub fn shutdown_handle(&self) -> Shutdown {
         self.shutdown.clone()
     }
 
+    /// Stops the daemon on `shutdown` instead of a handle of its own, for a
+    /// caller that can be asked to stop before the daemon exists.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn control_handle(&self) -> Control {
+        self.control.clone()
+    }
+
     pub fn run(mut self) -> Result<()> {
         info!(path = %self.config.workdir, "starting ObsyncGit daemon");
 
         let shutdown = self.shutdown.clone();
-        ctrlc::set_handler(move || {
-            shutdown.store(true, Ordering::SeqCst);
-        })
-        .context("failed to install Ctrl-C handler")?;
+        ctrlc::set_handler(move || shutdown.trigger())
+            .context("failed to install Ctrl-C handler")?;
 
-        self.git.ensure_repo(&self.config.repo_url)?;
+        self.wait_for_disk_space();
+        if self.shutdown.is_triggered() {
+            return Ok(());
+        }
+        self.prepare()?;
+        self.mqtt = MqttPublisher::start(&self.config).context("cannot publish over MQTT")?;
 
-        let (tx, rx) = unbounded();
-        let ignore = Arc::new(self.ignore.clone());
-        let watcher_shutdown = self.shutdown.clone();
-        let debounce = self.config.debounce_duration();
-        let mut watcher = RecommendedWatcher::new(
-            move |res: notify::Result<Event>| {
-                if watcher_shutdown.load(Ordering::SeqCst) {
+        // `tx` stays alive for the whole run so the channel only closes when
+        // the daemon does, even without a watcher.
+        let (tx, rx) = mpsc::unbounded_channel();
+        let watcher = if self.config.sync.mode == SyncMode::PullOnly {
+            info!("pull-only mode: local changes are never committed or pushed");
+            None
+        } else {
+            let mut watcher = WatcherSupervisor::new(
+                self.config.workdir.clone().into_std_path_buf(),
+                self.ignore.clone(),
+                tx.clone(),
+                self.shutdown.clone(),
+                self.config.debounce_duration(),
+                self.config.watcher.backend,
+            );
+            watcher.start()?;
+            Some(watcher)
+        };
+
+        // Git work blocks, so it runs via `block_in_place` on a worker while
+        // the other worker keeps timers and future tasks responsive.
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("obsyncgit-daemon")
+            .enable_time()
+            .build()
+            .context("failed to start async runtime")?;
+        runtime.block_on(self.event_loop(rx, watcher))
+    }
+
+    /// Holds off startup while the disk is short of `disk.min_free_mb`, so a
+    /// service manager does not restart the daemon over and over meanwhile.
+    fn wait_for_disk_space(&mut self) {
+        let mut reported = false;
+        while let Err(err) = self.git.ensure_disk_space() {
+            if !reported {
+                error!(
+                    ?err,
+                    "the disk holding the vault is running out of space; syncing starts once space is freed"
+                );
+                self.record_failure(&err);
+                reported = true;
+            }
+            let recheck_at = Instant::now() + DISK_RECHECK;
+            while Instant::now() < recheck_at {
+                if self.shutdown.is_triggered() {
                     return;
                 }
-                match res {
-                    Ok(event) => {
-                        let mut relevant = false;
-                        for path in &event.paths {
-                            if ignore.should_ignore(path) {
-                                continue;
-                            }
-                            relevant = true;
-                        }
-                        if relevant {
-                            let _ = tx.send(SyncEvent::Changed);
-                        }
-                        if event.need_rescan() {
-                            let _ = tx.send(SyncEvent::Rescan);
-                        }
-                    }
-                    Err(err) => {
-                        let _ = tx.send(SyncEvent::WatcherError(err.to_string()));
-                    }
-                }
-            },
-            NotifyConfig::default().with_poll_interval(debounce),
-        )?;
+                std::thread::sleep(Duration::from_millis(200));
+            }
+        }
+    }
 
-        watcher
-            .watch(self.config.workdir.as_std_path(), RecursiveMode::Recursive)
-            .with_context(|| {
-                format!(
-                    "failed to start filesystem watcher on {}",
-                    self.config.workdir
-                )
-            })?;
+    async fn event_loop(
+        &mut self,
+        mut rx: UnboundedReceiver<WatchEvent>,
+        mut watcher: Option<WatcherSupervisor>,
+    ) -> Result<()> {
+        // Poll, backoff, fsck and backup deadlines count time asleep, so a
+        // laptop that slept past one catches up once it wakes.
+        let clock = SystemClock;
+        let mut pacer = Pacer::new(clock, self.commit_timing(), self.config.poll_interval());
+        let mut remote = RemoteHealth::new(&self.config.remote_availability);
+        let mut vault_present = true;
+        let shutdown = self.shutdown.clone();
+        let control = self.control.clone();
+        let mut paused = false;
+        match ControlInbox::new(&self.config) {
+            Ok(inbox) => {
+                tokio::spawn(inbox.serve(control.clone(), shutdown.clone()));
+            }
+            Err(err) => warn!(?err, "failed to set up the control inbox"),
+        }
+        tokio::spawn(resume::watch(control.clone(), shutdown.clone()));
 
-        self.event_loop(rx)
-    }
+        // Startup syncing goes first; a check that is long overdue can wait
+        // a few more minutes.
+        let mut fsck_at = integrity::next_check_in(&self.config)
+            .map(|due_in| Deadline::after(&clock, due_in.max(FSCK_STARTUP_DELAY)));
 
-    fn event_loop(&mut self, rx: Receiver<SyncEvent>) -> Result<()> {
-        let debounce = self.config.debounce_duration();
-        let poll_interval = self.config.poll_interval();
-        let mut dirty_since: Option<Instant> = None;
-        let mut last_poll = Instant::now()
-            .checked_sub(poll_interval)
-            .unwrap_or_else(Instant::now);
-        let mut backoff_until: Option<Instant> = None;
-        let mut backoff_step: u32 = 0;
-
-        while !self.shutdown.load(Ordering::SeqCst) {
-            let now = Instant::now();
+        let mut backup_at = backup::next_backup_in(&self.config)
+            .map(|due_in| Deadline::after(&clock, due_in.max(BACKUP_STARTUP_DELAY)));
 
-            if let Some(until) = backoff_until
-                && now >= until
-            {
-                backoff_until = None;
-                debug!("backoff window elapsed, resuming operations");
+        let mut digest_at = digest::next_digest_in(&self.config)
+            .map(|due_in| Deadline::after(&clock, due_in.max(DIGEST_STARTUP_DELAY)));
+
+        // Edits made while the daemon was stopped produce no events.
+        match block_in_place(|| self.reconcile()) {
+            Ok(true) => pacer.commit_now(),
+            Ok(false) => {}
+            Err(err) => warn!(?err, "failed to reconcile the vault on startup"),
+        }
+        block_in_place(|| self.report_state());
+
+        loop {
+            let now = Instant::now();
+            let present = self.vault_present();
+            if present != vault_present {
+                vault_present = present;
+                if present {
+                    info!(path = %self.config.workdir, "vault is available again, resuming");
+                    if let Some(watcher) = watcher.as_mut() {
+                        watcher.resume();
+                    }
+                    // Nothing was watched meanwhile, so look at everything.
+                    pacer.changed();
+                } else {
+                    warn!(
+                        path = %self.config.workdir,
+                        "vault is unavailable (drive or share disconnected?), suspending sync"
+                    );
+                    if let Some(watcher) = watcher.as_mut() {
+                        watcher.suspend();
+                    }
+                }
+            }
+            // Shared policy may have changed the intervals on the last pull.
+            let mut poll_interval = self.config.poll_interval();
+            self.metered_now = self.config.sync.metered_policy != MeteredPolicy::Ignore
+                && self.metered.is_metered();
+            if self.metered_now {
+                poll_interval = poll_interval.max(Duration::from_secs(
+                    self.config.sync.metered_poll_interval_seconds,
+                ));
+            }
+            let was_low_battery = self.low_battery;
+            let low_battery = self.power.is_low(&self.config.power);
+            self.low_battery = low_battery;
+            if low_battery {
+                poll_interval = poll_interval.max(Duration::from_secs(
+                    self.config.power.battery_poll_interval_seconds,
+                ));
+            }
+            pacer.set_intervals(self.commit_timing(), poll_interval);
+            if was_low_battery && !low_battery {
+                // Back on mains power: publish what waited right away.
+                pacer.commit_now();
+                pacer.poll_now();
             }
+            self.update_schedule();
+            let offline = self.network_deferred();
 
-            if backoff_until.is_none() {
-                if let Some(dirty_at) = dirty_since
-                    && now.duration_since(dirty_at) >= debounce
-                {
-                    match self.sync_once() {
+            let active = !pacer.is_backing_off() && vault_present && !paused;
+            let commit_at = pacer.commit_at(active);
+            let probe_at = remote.next_probe().filter(|_| active && !offline);
+            let poll_at = (active && !remote.is_down() && !offline).then(|| pacer.poll_at());
+            let restart_at = watcher.as_ref().and_then(WatcherSupervisor::restart_at);
+            let release_at = self
+                .deletions
+                .next_release()
+                .filter(|_| !pacer.is_dirty() && vault_present);
+            // Re-evaluate the schedule every minute while deferred or on a
+            // low battery, and the environment (metered connection) every
+            // few minutes otherwise.
+            let recheck_at = now
+                + if !vault_present {
+                    VAULT_RECHECK
+                } else if offline || low_battery {
+                    SCHEDULE_RECHECK
+                } else {
+                    HOUSEKEEPING_INTERVAL
+                };
+
+            tokio::select! {
+                biased;
+                _ = shutdown.wait() => break,
+                _ = control.changed() => {
+                    if control.take_sync_request() {
+                        info!("manual sync requested");
+                        pacer.end_backoff();
+                        pacer.commit_now();
+                        pacer.poll_now();
+                    }
+                    if control.take_resumed() {
+                        // Timers stood still while asleep; poll now and look
+                        // for edits the watcher may have missed.
+                        pacer.end_backoff();
+                        remote.probe_now();
+                        pacer.poll_now();
+                        if vault_present {
+                            match block_in_place(|| self.reconcile()) {
+                                Ok(true) => pacer.commit_now(),
+                                Ok(false) => {}
+                                Err(err) => warn!(?err, "failed to reconcile the vault after resume"),
+                            }
+                        }
+                    }
+                    if control.is_paused() != paused {
+                        paused = !paused;
+                        if paused {
+                            info!("sync paused");
+                        } else {
+                            info!("sync resumed");
+                        }
+                        block_in_place(|| self.report_state());
+                    }
+                }
+                _ = sleep_until(pacer.backoff_at()) => {
+                    pacer.end_backoff();
+                    debug!("backoff window elapsed, resuming operations");
+                }
+                _ = sleep_until(commit_at) => {
+                    // While the remote is down keep recording history locally;
+                    // the recovery probe publishes it once the remote is back.
+                    let local_only = remote.is_down() || offline;
+                    match block_in_place(|| pacer.commit(self, local_only)) {
                         Ok(changed) => {
                             if changed {
                                 info!("local changes synchronized");
                             }
-                            dirty_since = None;
-                            backoff_step = 0;
-                            last_poll = Instant::now();
-                            continue;
+                            if local_only {
+                                block_in_place(|| self.report_state());
+                            } else {
+                                block_in_place(|| self.synced());
+                            }
                         }
-                        Err(err) => {
-                            error!(?err, "synchronization failed");
-                            backoff_step = (backoff_step + 1).min(6);
-                            let backoff = backoff_delay(backoff_step);
-                            backoff_until = Some(Instant::now() + backoff);
-                            continue;
+                        Err(CycleError::Unavailable(err)) => {
+                            debug!(?err, "remote unavailable during sync");
+                            self.record_failure(&err);
+                            remote.record_unavailable();
+                        }
+                        Err(CycleError::Failed(err)) => {
+                            let step = pacer.backoff_step();
+                            pacer.back_off(self.after_failure(&err, step).unwrap_or_else(|| {
+                                error!(?err, "synchronization failed");
+                                (step + 1).min(MAX_BACKOFF_STEP)
+                            }));
                         }
                     }
                 }
-
-                if now.duration_since(last_poll) >= poll_interval {
-                    match self.pull_remote() {
+                _ = sleep_until(probe_at) => {
+                    match block_in_place(|| self.traced("probe", Self::publish)) {
                         Ok(()) => {
-                            last_poll = Instant::now();
-                            backoff_step = 0;
+                            remote.record_available();
+                            pacer.succeeded();
+                            block_in_place(|| self.synced());
+                        }
+                        Err(err) if is_remote_unavailable(&err) => {
+                            debug!(?err, "remote recovery probe failed");
+                            self.record_failure(&err);
+                            remote.record_unavailable();
                         }
                         Err(err) => {
-                            warn!(?err, "failed to pull remote updates");
-                            backoff_step = (backoff_step + 1).min(6);
-                            let backoff = backoff_delay(backoff_step);
-                            backoff_until = Some(Instant::now() + backoff);
+                            remote.record_available();
+                            let step = pacer.backoff_step();
+                            pacer.back_off(self.after_failure(&err, step).unwrap_or_else(|| {
+                                warn!(?err, "failed to synchronize after remote recovery");
+                                (step + 1).min(MAX_BACKOFF_STEP)
+                            }));
+                        }
+                    }
+                }
+                _ = sleep_until(poll_at) => {
+                    match block_in_place(|| pacer.poll(self)) {
+                        Ok(()) => block_in_place(|| self.synced()),
+                        Err(CycleError::Unavailable(err)) => {
+                            debug!(?err, "remote unavailable during poll");
+                            self.record_failure(&err);
+                            remote.record_unavailable();
                         }
+                        Err(CycleError::Failed(err)) => {
+                            let step = pacer.backoff_step();
+                            pacer.back_off(self.after_failure(&err, step).unwrap_or_else(|| {
+                                warn!(?err, "failed to pull remote updates");
+                                (step + 1).min(MAX_BACKOFF_STEP)
+                            }));
+                        }
+                    }
+                }
+                _ = sleep_until(fsck_at.filter(|_| active && !pacer.is_dirty()).map(|at| at.instant(&clock))) => {
+                    fsck_at = self
+                        .config
+                        .integrity
+                        .check_interval()
+                        .map(|interval| Deadline::after(&clock, interval));
+                    if block_in_place(|| self.check_integrity()) {
+                        if let Some(watcher) = watcher.as_mut() {
+                            watcher.suspend();
+                        }
+                        block_in_place(|| self.repair());
+                        if let Some(watcher) = watcher.as_mut() {
+                            watcher.resume();
+                        }
+                        // Carried-over local changes still need committing.
+                        pacer.changed();
+                    }
+                }
+                _ = sleep_until(backup_at.filter(|_| active && !pacer.is_dirty()).map(|at| at.instant(&clock))) => {
+                    let written = block_in_place(|| self.backup());
+                    backup_at = self.config.backup.interval().map(|interval| {
+                        Deadline::after(&clock, if written { interval } else { interval.min(BACKUP_RETRY) })
+                    });
+                }
+                _ = sleep_until(digest_at.map(|at| at.instant(&clock))) => {
+                    let sent = block_in_place(|| self.send_digest());
+                    digest_at = self.config.digest.interval().map(|interval| {
+                        Deadline::after(&clock, if sent { interval } else { interval.min(DIGEST_RETRY) })
+                    });
+                }
+                _ = sleep_until(release_at) => {
+                    // A held deletion has aged enough to be committed.
+                    pacer.changed();
+                }
+                _ = sleep_until(restart_at) => {
+                    if let Some(watcher) = watcher.as_mut()
+                        && watcher.restart()
+                    {
+                        // Catch up on whatever changed while nobody watched.
+                        pacer.changed();
                     }
-                    continue;
                 }
+                event = rx.recv() => match event {
+                    Some(WatchEvent::Changed(paths)) => {
+                        if let Some(watcher) = watcher.as_mut() {
+                            watcher.note_activity(&paths);
+                        }
+                        if self.wrote_itself(&paths) {
+                            debug!(?paths, "ignoring files written by the last pull");
+                        } else {
+                            if self.changed_since.is_none() {
+                                self.emit(Event::ChangeDetected {
+                                    paths: self.relative_paths(&paths),
+                                });
+                                block_in_place(|| self.report_state());
+                            }
+                            self.changed_since.get_or_insert_with(Instant::now);
+                            pacer.changed();
+                            debug!("filesystem change detected");
+                        }
+                    }
+                    Some(WatchEvent::Rescan) => {
+                        pacer.changed();
+                        debug!("filesystem watcher requested a rescan");
+                    }
+                    Some(WatchEvent::Error(err)) => {
+                        if let Some(watcher) = watcher.as_mut() {
+                            watcher.on_error(err);
+                        }
+                    }
+                    None => {
+                        warn!("watcher channel disconnected, shutting down");
+                        break;
+                    }
+                },
+                _ = sleep_until(Some(recheck_at)) => {}
+            }
+        }
+
+        info!("ObsyncGit shutting down");
+        if let Some(mqtt) = self.mqtt.take() {
+            block_in_place(|| mqtt.stop());
+        }
+        Ok(())
+    }
+
+    /// When edits become commits, after `commit.mode`.
+    fn commit_timing(&self) -> CommitTiming {
+        let debounce = self.config.debounce_duration();
+        match self.config.commit.mode {
+            CommitMode::OnChange => CommitTiming::AfterQuiet(debounce),
+            CommitMode::Session => {
+                CommitTiming::AfterQuiet(debounce.max(self.config.commit.session_idle()))
             }
+            CommitMode::Snapshot => CommitTiming::Every(self.config.commit.snapshot_interval()),
+        }
+    }
 
-            let timeout = compute_timeout(
-                now,
-                dirty_since,
-                debounce,
-                last_poll,
-                poll_interval,
-                backoff_until,
+    /// Compares the working tree with the index and the remote tracking
+    /// branch. Returns true when there is local work to sync.
+    pub fn reconcile(&mut self) -> Result<bool> {
+        if self.config.sync.mode == SyncMode::PullOnly {
+            return Ok(false);
+        }
+        let pending = self.git.list_pending_paths()?;
+        let unpushed = self.git.has_unpushed_commits()?;
+        if !pending.is_empty() {
+            info!(
+                changed = pending.len(),
+                "found local changes that have not been committed yet"
             );
+        }
+        if unpushed {
+            info!("found local commits that have not been pushed yet");
+        }
+        Ok(!pending.is_empty() || unpushed)
+    }
 
-            match rx.recv_timeout(timeout) {
-                Ok(event) => match event {
-                    SyncEvent::Changed | SyncEvent::Rescan => {
-                        dirty_since = Some(Instant::now());
-                        debug!("filesystem change detected");
-                    }
-                    SyncEvent::WatcherError(msg) => {
-                        warn!("watcher error: {msg}");
+    /// One full reconciliation: commit whatever changed locally, then pull
+    /// and push regardless of the schedule. Backs `obsyncgit rescan`.
+    pub fn rescan(&mut self) -> Result<()> {
+        self.traced("rescan", |daemon| {
+            if daemon.reconcile()? {
+                daemon.commit_local()?;
+                if daemon.unsettled {
+                    warn!("some files are still being written and were left for the next sync");
+                }
+            }
+            daemon.publish()
+        })
+    }
+
+    /// Whether the vault's repository is reachable. A missing repository
+    /// means the drive or share holding it is gone, or only its empty mount
+    /// point is left.
+    fn vault_present(&self) -> bool {
+        self.git.is_present()
+    }
+
+    /// Runs one cycle of git work, recording it when sync tracing is on.
+    fn traced<T>(&mut self, kind: &str, work: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let Some(trace) = self.trace.clone() else {
+            return work(self);
+        };
+        trace.begin_cycle(kind);
+        let started = Instant::now();
+        let result = work(self);
+        let outcome = match &result {
+            Ok(_) => "ok".to_string(),
+            Err(err) => format!("error: {err}"),
+        };
+        trace.end_cycle(started.elapsed(), &outcome);
+        result
+    }
+
+    /// Handles a failed cycle by its cause and returns the next backoff step,
+    /// or `None` when nothing specific applies. Failures only the user can
+    /// fix wait the longest between attempts; ones the next cycle gets past
+    /// barely back off.
+    fn after_failure(&mut self, err: &anyhow::Error, step: u32) -> Option<u32> {
+        self.record_failure(err);
+        match GitError::of(err) {
+            GitError::Auth(_) => {
+                error!(
+                    ?err,
+                    "the remote refused the credentials; update them, until then syncing retries every few minutes"
+                );
+                Some(ALERT_BACKOFF_STEP)
+            }
+            GitError::UnknownHost(_) => {
+                error!(
+                    ?err,
+                    "ssh does not know the remote's host key yet; run `obsyncgit trust-host` to check and pin it"
+                );
+                Some(ALERT_BACKOFF_STEP)
+            }
+            GitError::HostKeyChanged(_) => {
+                error!(
+                    ?err,
+                    "the remote's SSH host key has changed since it was pinned; confirm the new fingerprint with the host, then run `obsyncgit trust-host --replace`"
+                );
+                Some(ALERT_BACKOFF_STEP)
+            }
+            GitError::DiskFull(_) => {
+                error!(
+                    ?err,
+                    "the disk holding the vault is running out of space; syncing stops until space is freed and checks again every few minutes"
+                );
+                Some(ALERT_BACKOFF_STEP)
+            }
+            GitError::Hook(_) => {
+                error!(
+                    ?err,
+                    "a git hook of the vault refused the commit; fix what it reports, or set git.run_hooks to false"
+                );
+                Some(ALERT_BACKOFF_STEP)
+            }
+            GitError::LockHeld(_) => {
+                warn!(
+                    ?err,
+                    "another git process holds the vault's lock, retrying shortly"
+                );
+                if let Err(err) = self.git.clear_stale_index_lock() {
+                    debug!(?err, "failed to inspect index.lock");
+                }
+                Some((step + 1).min(LOCK_BACKOFF_STEP))
+            }
+            GitError::NonFastForward(_) => {
+                debug!("the remote moved on during the cycle, pulling again");
+                Some(step.min(LOCK_BACKOFF_STEP))
+            }
+            GitError::Conflict(_) => {
+                self.report_conflicts();
+                None
+            }
+            GitError::Network(_) | GitError::Unknown(_) => None,
+        }
+    }
+
+    /// Runs the scheduled `git fsck`. Returns true when it found damage and
+    /// `integrity.auto_repair` asks for a repair.
+    fn check_integrity(&self) -> bool {
+        match integrity::check(&self.git, &self.config) {
+            Ok(report) if report.is_damaged() => {
+                error!(
+                    problems = ?report.problems,
+                    "git fsck found the repository damaged; run `obsyncgit repair` to replace it with a fresh clone"
+                );
+                self.config.integrity.auto_repair
+            }
+            Ok(_) => {
+                debug!("git fsck found no damage");
+                false
+            }
+            Err(err) => {
+                warn!(?err, "failed to check the repository with git fsck");
+                false
+            }
+        }
+    }
+
+    /// Replaces the damaged repository with a fresh clone, as
+    /// `obsyncgit repair` does.
+    fn repair(&mut self) {
+        match integrity::repair(&self.config) {
+            Ok(repair) => {
+                warn!(
+                    damaged = %repair.damaged.display(),
+                    kept = repair.kept.len() + repair.removed.len(),
+                    copies = ?repair.copies,
+                    "replaced the damaged repository with a fresh clone"
+                );
+                if let Err(err) = integrity::check(&self.git, &self.config) {
+                    warn!(?err, "failed to check the fresh clone");
+                }
+            }
+            Err(err) => error!(?err, "failed to repair the repository"),
+        }
+    }
+
+    /// Writes the scheduled backup bundle. Returns false when it failed.
+    fn backup(&self) -> bool {
+        match backup::write(&self.git, &self.config) {
+            Ok(_) => true,
+            Err(err) => {
+                warn!(?err, "failed to write the backup bundle");
+                false
+            }
+        }
+    }
+
+    /// Sends the `digest` of the period that just ended. Returns false when
+    /// no channel took it.
+    fn send_digest(&self) -> bool {
+        match digest::send(&self.git, &self.config) {
+            Ok(summary) => {
+                info!(
+                    syncs = summary.tally.syncs,
+                    notes_changed = summary.notes_changed,
+                    conflicts = summary.tally.conflicts.len(),
+                    errors = summary.tally.errors,
+                    "sent the sync digest"
+                );
+                true
+            }
+            Err(err) => {
+                warn!(?err, "failed to send the sync digest");
+                false
+            }
+        }
+    }
+
+    /// Follow-up work after a cycle that reached the remote.
+    fn synced(&mut self) {
+        self.clear_failure();
+        if let Some(alerts) = &mut self.alerts {
+            alerts.synced();
+        }
+        digest::record(&self.config, |tally| tally.syncs += 1);
+        self.mirror_replicas();
+        self.tag_snapshots();
+        self.share_locks();
+        self.share_notes();
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.synced();
+        }
+        self.report_state();
+    }
+
+    /// `commit.notes`: merges the other devices' sync notes and pushes this
+    /// device's. A failure is retried after the next sync.
+    pub fn share_notes(&self) {
+        if !self.config.commit.notes || self.config.remote.kind != RemoteKind::Git {
+            return;
+        }
+        let push = self.config.sync.mode != SyncMode::PullOnly;
+        if let Err(err) = self.git.sync_notes(sync_notes::NOTES_REF, push) {
+            warn!(?err, "failed to share the sync notes");
+        }
+    }
+
+    /// `shared_vault.soft_locks`: pushes this device's locks when they
+    /// changed and fetches the other devices'. A failure is retried after
+    /// the next sync; locks only warn, so nothing waits for them.
+    pub fn share_locks(&mut self) {
+        if let Some(locks) = &mut self.locks
+            && let Err(err) = locks.share(&self.git)
+        {
+            warn!(?err, "failed to share the soft locks");
+        }
+    }
+
+    /// `snapshots.tag_daily`: tags the days that ended since the newest
+    /// snapshot, looking once a day. A failure waits for the next day,
+    /// which catches up on the missed ones.
+    pub fn tag_snapshots(&mut self) {
+        if !self.config.snapshots.tag_daily || self.config.sync.mode == SyncMode::PullOnly {
+            return;
+        }
+        let today = Local::now().date_naive();
+        if self.snapshots_checked == Some(today) {
+            return;
+        }
+        self.snapshots_checked = Some(today);
+        if let Err(err) = snapshots::tag_finished_days(&self.git, today) {
+            warn!(?err, "failed to tag the daily snapshots");
+        }
+    }
+
+    /// Brings each of `replicas` up to date after a successful sync, unless
+    /// it already mirrors HEAD. A replica on a drive that is not mounted is
+    /// tried again after the next sync.
+    pub fn mirror_replicas(&mut self) {
+        if self.config.replicas.is_empty() {
+            return;
+        }
+        let head = match self.git.head_commit() {
+            Ok(head) => head,
+            Err(err) => {
+                debug!(?err, "failed to read HEAD");
+                return;
+            }
+        };
+        let mut files = None;
+        for entry in &self.config.replicas {
+            if self.replicated.get(entry) == Some(&Some(head.clone())) {
+                continue;
+            }
+            let dir = replica::replica_dir(entry);
+            if !replica::available(&dir) {
+                debug!(replica = %dir.display(), "replica is not available");
+                continue;
+            }
+            let files = match &mut files {
+                Some(files) => files,
+                None => match self.git.tracked_files() {
+                    Ok(tracked) => files.insert(tracked),
+                    Err(err) => {
+                        debug!(?err, "failed to list tracked files");
+                        return;
                     }
                 },
-                Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
-                    // loop recomputes state
+            };
+            match replica::mirror(&self.config, &dir, files) {
+                Ok(report) => {
+                    if report.copied + report.deleted > 0 {
+                        info!(
+                            replica = %dir.display(),
+                            copied = report.copied,
+                            deleted = report.deleted,
+                            "updated the replica"
+                        );
+                    }
+                    self.replicated.insert(entry.clone(), Some(head.clone()));
                 }
-                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
-                    warn!("watcher channel disconnected, shutting down");
-                    break;
+                Err(err) => {
+                    if self.replicated.insert(entry.clone(), None) != Some(None) {
+                        warn!(?err, replica = %dir.display(), "failed to update the replica");
+                    }
                 }
             }
         }
+    }
 
-        info!("ObsyncGit shutting down");
+    /// Leaves the cause of a failed cycle for `status` and the desktop bars.
+    fn record_failure(&mut self, err: &anyhow::Error) {
+        let failure = GitError::of(err);
+        if !failure.is_transient() {
+            digest::record(&self.config, |tally| {
+                tally.errors += 1;
+                tally.last_error = Some(failure.category().to_string());
+            });
+        }
+        let recorded =
+            ControlInbox::new(&self.config).and_then(|inbox| inbox.record_failure(&failure));
+        if let Err(err) = recorded {
+            debug!(?err, "failed to record the sync failure");
+        }
+        self.emit(Event::Error {
+            category: failure.category().to_string(),
+            message: failure.message().to_string(),
+        });
+        if let Some(alerts) = &mut self.alerts {
+            alerts.failed(&failure);
+        }
+        self.report_state();
+    }
+
+    /// Publishes the vault's state over MQTT, when it is set up.
+    fn report_state(&self) {
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.report(&SyncHealth::read(&self.config));
+        }
+    }
+
+    /// `paths` as the watcher reports them, relative to the vault.
+    fn relative_paths(&self, paths: &[PathBuf]) -> Vec<String> {
+        let root = self.config.workdir.as_std_path();
+        paths
+            .iter()
+            .filter_map(|path| path.strip_prefix(root).ok())
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| path.to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    /// Adds `event` to the stream `obsyncgit events` follows.
+    fn emit(&self, event: Event) {
+        if let Some(events) = &self.events
+            && let Err(err) = events.append(event)
+        {
+            debug!(?err, "failed to record a sync event");
+        }
+    }
+
+    fn clear_failure(&self) {
+        if let Ok(inbox) = ControlInbox::new(&self.config) {
+            inbox.clear_failure();
+        }
+    }
+
+    /// Clones the vault if needed and applies the shared policy; `run` does
+    /// this before entering the event loop.
+    pub fn prepare(&mut self) -> Result<()> {
+        if !self.git.has_repo() {
+            preflight::ensure_room(&self.git, &self.config)?;
+        }
+        let control = self.control.clone();
+        let events = self.events.clone();
+        let mut log = git::log_clone_progress();
+        let mut record = git::clone_progress_steps(|progress| {
+            if let Some(events) = &events
+                && let Err(err) = events.append(Event::CloneProgress {
+                    phase: progress.phase.clone(),
+                    percent: progress.percent,
+                    rate: progress.rate.clone(),
+                })
+            {
+                debug!(?err, "failed to record clone progress");
+            }
+        });
+        let cloned = self
+            .git
+            .ensure_repo_with_progress(&self.config.repo_url, &mut |progress| {
+                control.set_clone_progress(Some(progress.clone()));
+                log(progress);
+                record(progress);
+            });
+        self.control.set_clone_progress(None);
+        cloned?;
+        if let Some(main) = self.git.main_worktree()? {
+            info!(main = %main.display(), "vault is a linked worktree");
+        }
+        recovery::recover(&self.git);
+        self.git
+            .apply_repo_config()
+            .context("failed to apply git.repo_config")?;
+        if self.shared_vault.is_some() {
+            match shared_vault::identity(&self.git, &self.config) {
+                Some((name, email)) => {
+                    info!(%name, %email, "committing to the shared vault");
+                    if let Some(locks) = &mut self.locks {
+                        locks.set_author(name);
+                    }
+                }
+                None => bail!(
+                    "shared_vault is on but this device has no identity to commit under; set git.author_name and git.author_email"
+                ),
+            }
+        }
+        if let Err(err) = digest::start(&self.git, &self.config) {
+            warn!(?err, "failed to start counting for the sync digest");
+        }
+        // Committing without the filter would push the private lines.
+        redaction::install(&self.git, &self.config.security.redaction)
+            .context("failed to set up security.redaction")?;
+        match self.git.configure_status_cache() {
+            Ok(true) => debug!("git status cache is on"),
+            Ok(false) => {}
+            Err(err) => warn!(?err, "failed to turn on git's status cache"),
+        }
+        // Rebasing onto other devices' commits would leave the notes behind
+        // on the commits it replaces.
+        if self.config.commit.notes
+            && let Err(err) = self.git.set_config_value(
+                "notes.rewriteRef",
+                &format!("refs/notes/{}", sync_notes::NOTES_REF),
+            )
+        {
+            warn!(?err, "failed to keep sync notes on rebased commits");
+        }
+        if cfg!(target_os = "macos")
+            && self.config.compat.unicode_normalization == UnicodeNormalization::Nfc
+            && let Err(err) = self.git.set_config_value("core.precomposeunicode", "true")
+        {
+            warn!(
+                ?err,
+                "failed to make git report composed Unicode file names"
+            );
+        }
+        if let Some(provider) = self.cloud {
+            warn!(
+                %provider,
+                "the vault is inside a cloud-synced folder; its temporary and placeholder files are ignored, run `obsyncgit doctor` for known conflicts"
+            );
+            if let Err(err) = self.git.set_local_excludes(provider.ignore_globs()) {
+                warn!(?err, "failed to exclude cloud sync files from commits");
+            }
+        }
+        self.after_pull();
         Ok(())
     }
 
     pub fn sync_once(&mut self) -> Result<bool> {
-        self.git.stage_all()?;
-        let files = self.git.list_changed_files()?;
+        let committed = self.commit_local()?;
+        if !committed && !self.git.has_unpushed_commits()? {
+            return Ok(false);
+        }
+        self.publish()?;
+        Ok(true)
+    }
+
+    /// [`Self::sync_once`] recorded as a `sync` cycle when tracing is on.
+    pub fn traced_sync_once(&mut self) -> Result<bool> {
+        self.traced("sync", Self::sync_once)
+    }
+
+    /// Stages and commits pending changes without touching the network.
+    /// Paths that are still being written are left for a later cycle.
+    fn commit_local(&mut self) -> Result<bool> {
+        // Renames and staging write too; stop before any of it.
+        self.git.ensure_disk_space()?;
+        let submodules_deferred = self.commit_submodules();
+        if self.config.sync.file_names == FileNamePolicy::Rename {
+            self.rename_unsafe_names()?;
+        }
+        let normalization = self.config.compat.unicode_normalization;
+        if normalization == UnicodeNormalization::Nfc {
+            self.compose_pending_names()?;
+        }
+        self.adopt_loose_notes()?;
+        let root = self.config.workdir.as_std_path();
+        let mut held = self.deletions.intercept(&self.git, root)?;
+        let mut pending = self.git.list_pending_paths()?;
+        // Deleting a copy that was committed before is committed as usual.
+        let copies: Vec<ConflictCopy> = conflict_copies::find(&pending)
+            .into_iter()
+            .filter(|copy| root.join(&copy.path).is_file())
+            .collect();
+        if !copies.is_empty() {
+            digest::record(&self.config, |tally| {
+                tally
+                    .conflicts
+                    .extend(copies.iter().map(|copy| copy.original.clone()));
+            });
+            let policy = self.config.sync.conflict_copies;
+            if policy == ConflictCopyPolicy::Newest {
+                for copy in &copies {
+                    match conflict_copies::resolve_newest(root, copy) {
+                        Ok(kept) => info!(
+                            copy = %copy.path,
+                            note = %copy.original,
+                            kept = if kept == Resolution::Mine { "note" } else { "copy" },
+                            "settled a conflict copy made by {}, keeping the newer version",
+                            copy.tool
+                        ),
+                        Err(err) => {
+                            warn!(?err, copy = %copy.path, "failed to settle a conflict copy");
+                            held.push(copy.path.clone());
+                        }
+                    }
+                }
+                pending = self.git.list_pending_paths()?;
+            } else {
+                warn_conflict_copies(&mut self.conflict_copies, &copies, policy);
+                held.extend(copies.into_iter().map(|copy| copy.path));
+            }
+        }
+        if self.config.sync.file_names == FileNamePolicy::Warn {
+            warn_unsafe_names(&mut self.unsafe_names, &pending);
+        }
+        let mangled: Vec<String> = pending
+            .iter()
+            .filter(|path| git::is_mangled_path(path))
+            .cloned()
+            .collect();
+        if !mangled.is_empty() {
+            warn!(
+                paths = ?mangled,
+                "file names that are not valid UTF-8 are not synced; rename them to sync their content"
+            );
+            held.extend(mangled);
+        }
+        if normalization != UnicodeNormalization::Off
+            && pending.iter().any(|path| !normalization::is_nfc(path))
+        {
+            let tracked = self.git.tracked_files()?;
+            let duplicating = normalization::duplicating(root, &pending, &tracked);
+            warn_duplicate_forms(&mut self.duplicate_forms, &duplicating);
+            held.extend(duplicating);
+        }
+        if !self.case_collisions.is_empty() && self.git.ignores_case() {
+            // One file on disk stands in for several tracked ones; its
+            // content would be committed over the others.
+            held.extend(
+                pending
+                    .iter()
+                    .filter(|path| {
+                        self.case_collisions
+                            .iter()
+                            .any(|collision| collision.covers(path))
+                    })
+                    .cloned(),
+            );
+        }
+        let online_only = cloud::online_only(root, &pending);
+        if !online_only.is_empty() {
+            debug!(paths = ?online_only, "skipping files whose content is only in the cloud");
+            held.extend(online_only);
+        }
+        let blocked: Vec<(String, Blocked)> = pending
+            .iter()
+            .filter_map(|path| {
+                let reason = ignore::staging_block(&self.config.ignore, root, path)?;
+                Some((path.clone(), reason))
+            })
+            .collect();
+        warn_blocked_files(&mut self.blocked_files, &blocked);
+        held.extend(blocked.into_iter().map(|(path, _)| path));
+        if let Some(shared) = &self.shared_vault {
+            let protected = shared.protected(&pending);
+            warn_protected_files(&mut self.protected_files, &protected);
+            held.extend(protected);
+        }
+        if let Some(scanner) = &self.secret_scan {
+            let secrets = scanner.scan(root, &pending);
+            hold_secrets(&mut self.held_secrets, &self.config, &secrets);
+            held.extend(secrets.into_iter().map(|(path, _)| path));
+        }
+        if let Some(rules) = &self.insignificant {
+            let unchanged = rules.unchanged(&self.git, root, &pending);
+            if !unchanged.is_empty() {
+                debug!(paths = ?unchanged, "leaving out insignificant changes");
+                held.extend(unchanged);
+            }
+        }
+        if let Some(locks) = &mut self.locks {
+            let edited: Vec<String> = pending
+                .iter()
+                .filter(|path| root.join(path).is_file())
+                .cloned()
+                .collect();
+            locks.touch(&edited);
+            for lock in locks.contested(&edited) {
+                warn!(
+                    path = %lock.path,
+                    holder = %lock.holder(),
+                    "this note is also being edited on another device; expect a conflict"
+                );
+            }
+        }
+        let unstable = self.stability.unstable_paths(root, &pending);
+        self.unsettled = !unstable.is_empty() || submodules_deferred;
+        if unstable.is_empty() && held.is_empty() {
+            self.git.stage_all()?;
+        } else {
+            let settled: Vec<String> = pending
+                .into_iter()
+                .filter(|path| !unstable.contains(path) && !held.contains(path))
+                .collect();
+            self.git.stage_paths(&settled)?;
+        }
+        let files = self.git.list_staged_files()?;
         if files.is_empty() {
             debug!("no staged changes detected");
             return Ok(false);
         }
-        let message = self.build_commit_message(&files);
-        self.git.commit(&message)?;
-        self.git.pull_rebase()?;
-        self.git.push()?;
-        info!(?files, "pushed commit");
+        let amend = match self.amend_target()? {
+            Some(amend) => {
+                let combined = self.git.staged_files_since_parent()?;
+                // Changes that undo HEAD would leave it empty.
+                (!combined.is_empty()).then_some((amend, combined))
+            }
+            None => None,
+        };
+        let amended = amend.is_some();
+        let files = if let Some((amend, files)) = amend {
+            let message = self.build_commit_message(&files);
+            self.git.amend(&message)?;
+            self.rewrite_device_branch |= amend == Amend::DeviceBranch;
+            info!(?files, "amended the previous commit with local changes");
+            files
+        } else {
+            let message = self.build_commit_message(&files);
+            self.git.commit(&message)?;
+            info!(?files, "committed local changes");
+            files
+        };
+        let count = files.len();
+        let head = self.git.head_commit()?;
+        self.emit(Event::CommitCreated {
+            commit: head.clone(),
+            files,
+            amended,
+        });
+        let latency = self.changed_since.take().map(|since| since.elapsed());
+        if self.config.commit.notes {
+            let note = SyncNote::new(self.config.sync.device_name(), count, latency);
+            if let Err(err) = sync_notes::record(&self.git, &head, &note) {
+                warn!(?err, "failed to record the commit's sync note");
+            }
+        }
+        self.last_commit = Some(head);
         Ok(true)
     }
 
-    fn pull_remote(&self) -> Result<()> {
-        self.git.pull_rebase()?;
+    /// Whether the staged changes may be folded into HEAD rather than
+    /// committed on their own: HEAD is a plain commit this daemon made
+    /// within `commit.amend_window_minutes`, and no other device can have
+    /// it yet. Under the `pull-request` topology a commit only on our
+    /// device branch still qualifies; the branch is force-pushed.
+    fn amend_target(&self) -> Result<Option<Amend>> {
+        let Some(window) = self.config.commit.amend_window() else {
+            return Ok(None);
+        };
+        let Some(last_commit) = &self.last_commit else {
+            return Ok(None);
+        };
+        if self.git.head_commit()? != *last_commit {
+            return Ok(None);
+        }
+        let (authored, single_parent) = self.git.head_authored()?;
+        let recent = u64::try_from(chrono::Utc::now().timestamp() - authored)
+            .is_ok_and(|age| age < window.as_secs());
+        if !single_parent || !recent {
+            return Ok(None);
+        }
+        let published = self.git.remote_branches_containing_head()?;
+        if published.is_empty() {
+            return Ok(Some(Amend::Unpublished));
+        }
+        let own = format!(
+            "{}/{}",
+            self.config.remote.name,
+            self.config.sync.device_branch()
+        );
+        let only_own = published.iter().all(|branch| *branch == own);
+        Ok((self.pull_requests.is_some() && only_own).then_some(Amend::DeviceBranch))
+    }
+
+    /// `sync.file_names: rename`: gives pending files that Windows cannot
+    /// check out a name it can before they are staged.
+    fn rename_unsafe_names(&self) -> Result<()> {
+        let unsafe_paths: Vec<String> = self
+            .git
+            .list_pending_paths()?
+            .into_iter()
+            .filter(|path| {
+                !git::is_mangled_path(path) && file_names::windows_problem(path).is_some()
+            })
+            .collect();
+        let root = self.config.workdir.as_std_path();
+        for entry in file_names::rename_for_windows(root, &unsafe_paths)? {
+            info!(from = %entry.from, to = %entry.to, "renamed file for Windows");
+        }
+        Ok(())
+    }
+
+    /// `compat.unicode_normalization: nfc`: gives pending files the
+    /// composed form of their name before they are staged.
+    fn compose_pending_names(&self) -> Result<()> {
+        let decomposed: Vec<String> = self
+            .git
+            .list_pending_paths()?
+            .into_iter()
+            .filter(|path| !git::is_mangled_path(path) && !normalization::is_nfc(path))
+            .collect();
+        let root = self.config.workdir.as_std_path();
+        for (from, to) in normalization::compose_files(root, &decomposed)? {
+            info!(%from, %to, "renamed file to its composed Unicode name");
+        }
         Ok(())
     }
 
+    /// `shared_vault.user_folder`: moves new files at the top of the vault
+    /// into the member's folder before they are staged. Files still being
+    /// written and conflict copies of other sync tools stay where they are.
+    fn adopt_loose_notes(&self) -> Result<()> {
+        let Some(shared) = &self.shared_vault else {
+            return Ok(());
+        };
+        let loose = shared.loose_notes(&self.git.list_untracked_files()?);
+        if loose.is_empty() {
+            return Ok(());
+        }
+        let root = self.config.workdir.as_std_path();
+        let unstable = self.stability.unstable_paths(root, &loose);
+        let copies: Vec<String> = conflict_copies::find(&loose)
+            .into_iter()
+            .map(|copy| copy.path)
+            .collect();
+        let settled: Vec<String> = loose
+            .into_iter()
+            .filter(|path| !unstable.contains(path) && !copies.contains(path))
+            .collect();
+        for (from, to) in shared.adopt(&self.config, &settled)? {
+            info!(%from, %to, "moved a new file into the member's folder");
+        }
+        Ok(())
+    }
+
+    /// Commits edits inside `submodules.sync` submodules to their own
+    /// repository first, so the vault's commit records the new submodule
+    /// commit. Returns whether a submodule still had files being written.
+    fn commit_submodules(&self) -> bool {
+        let submodules = match self.git.synced_submodules() {
+            Ok(submodules) => submodules,
+            Err(err) => {
+                warn!(?err, "failed to list submodules");
+                return false;
+            }
+        };
+        let mut deferred = false;
+        for submodule in submodules {
+            match self.commit_submodule(&submodule) {
+                Ok(settled) => deferred |= !settled,
+                Err(err) => {
+                    warn!(?err, path = %submodule.path, "failed to commit inside submodule")
+                }
+            }
+        }
+        deferred
+    }
+
+    fn commit_submodule(&self, submodule: &Submodule) -> Result<bool> {
+        let repo = self.git.submodule_repo(submodule)?;
+        repo.checkout_branch()?;
+        let pending = repo.list_pending_paths()?;
+        if pending.is_empty() {
+            return Ok(true);
+        }
+        let root = self.config.workdir.join(&submodule.path);
+        if !self
+            .stability
+            .unstable_paths(root.as_std_path(), &pending)
+            .is_empty()
+        {
+            return Ok(false);
+        }
+        repo.stage_all()?;
+        let files = repo.list_staged_files()?;
+        if repo.commit(&self.build_commit_message(&files))? {
+            info!(submodule = %submodule.path, ?files, "committed changes inside submodule");
+        }
+        Ok(true)
+    }
+
+    /// Brings `submodules.sync` submodules up to date with their remote and
+    /// pushes their commits before the vault is pushed, so the submodule
+    /// commits the vault records can be fetched elsewhere.
+    fn publish_submodules(&self, pull: bool) {
+        let submodules = match self.git.synced_submodules() {
+            Ok(submodules) => submodules,
+            Err(err) => {
+                warn!(?err, "failed to list submodules");
+                return;
+            }
+        };
+        for submodule in submodules {
+            let result = self.git.submodule_repo(&submodule).and_then(|repo| {
+                if pull {
+                    repo.pull_rebase()?;
+                }
+                if repo.has_unpushed_commits()? {
+                    repo.push()?;
+                    info!(submodule = %submodule.path, "pushed submodule commits");
+                }
+                Ok(())
+            });
+            if let Err(err) = result {
+                warn!(?err, path = %submodule.path, "failed to sync submodule");
+            }
+        }
+    }
+
+    /// Rebases onto the remote branch and pushes any local commits. The
+    /// files the pull wrote are remembered, so their watcher events do not
+    /// start another cycle.
+    fn publish(&mut self) -> Result<()> {
+        let before = self.git.head_commit().ok();
+        let mut written = Vec::new();
+        if self.config.sync.mode != SyncMode::PushOnly {
+            written = self.restore_frontmatter();
+        }
+        let result = self.pull_and_push();
+        if let Some(before) = before
+            && let Ok(after) = self.git.head_commit()
+            && after != before
+        {
+            match self.git.changed_paths_since(&before) {
+                Ok(changed) => {
+                    if !changed.is_empty() {
+                        self.emit(Event::PullMerged {
+                            commit: after,
+                            files: changed.clone(),
+                        });
+                    }
+                    written.extend(changed);
+                }
+                Err(err) => debug!(?err, "failed to list the files the pull changed"),
+            }
+        }
+        self.own_writes.record(&written);
+        result
+    }
+
+    fn pull_and_push(&mut self) -> Result<()> {
+        if self.config.sync.mode == SyncMode::PullOnly {
+            self.git.pull_fast_forward()?;
+            self.after_pull();
+            return Ok(());
+        }
+        self.publish_submodules(self.config.sync.mode == SyncMode::TwoWay);
+        if self.config.sync.mode == SyncMode::TwoWay
+            && self.config.sync.topology != SyncTopology::SharedBranch
+        {
+            return self.publish_device_branch();
+        }
+        let push_only = self.config.sync.mode == SyncMode::PushOnly;
+        if !push_only {
+            if let Err(err) = self.git.pull_rebase() {
+                self.report_conflicts();
+                return Err(err);
+            }
+            self.after_pull();
+        }
+        if self.git.has_unpushed_commits()? {
+            if self.defer_large_push()? {
+                return Ok(());
+            }
+            if push_only && self.shared_branch_moved_on()? {
+                return self.push_to_device_branch();
+            }
+            match self.git.push() {
+                Ok(()) => {
+                    info!("pushed local commits");
+                    self.emit(Event::PushOk {
+                        branch: self.config.branch.clone(),
+                    });
+                }
+                Err(err) if push_only && is_push_rejected(&err) => self.push_to_device_branch()?,
+                Err(err) if is_protected_branch(&err) => {
+                    return Err(err.context(format!(
+                        "{} is protected on the remote; set sync.topology to pull-request to publish through pull requests",
+                        self.config.branch
+                    )));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Under `push-only`, whether the shared branch has commits this device
+    /// never pulls, so pushing to it would be refused. Fetches first, since
+    /// nothing else refreshes the tracking branch in this mode.
+    fn shared_branch_moved_on(&self) -> Result<bool> {
+        self.git.fetch()?;
+        let shared = format!("{}/{}", self.config.remote.name, self.config.branch);
+        Ok(self.git.tracking_tip().is_some() && self.git.has_commits_from(&shared)?)
+    }
+
+    /// Publishes to `device/<name>` when the shared branch moved on under
+    /// `push-only`, unless that branch has everything already.
+    fn push_to_device_branch(&mut self) -> Result<()> {
+        let branch = self.config.sync.device_branch();
+        if !self.git.is_ahead_of(&branch)? {
+            return Ok(());
+        }
+        self.git.push_head_to(&branch)?;
+        info!(%branch, "remote branch has moved on, pushed to device branch instead");
+        self.emit(Event::PushOk { branch });
+        Ok(())
+    }
+
+    /// Whether the watcher reported only files the last pull wrote, and
+    /// nobody changed since.
+    pub fn wrote_itself(&self, paths: &[PathBuf]) -> bool {
+        self.own_writes.covers(paths)
+    }
+
+    /// Puts back notes whose only change is to an ignored front matter
+    /// property and that the pull is about to change, under
+    /// `commit.insignificant.restore_frontmatter`. Returns the notes put
+    /// back.
+    fn restore_frontmatter(&self) -> Vec<String> {
+        let Some(rules) = &self.insignificant else {
+            return Vec::new();
+        };
+        let pending = match self.git.list_pending_paths() {
+            Ok(pending) => pending,
+            Err(err) => {
+                debug!(?err, "failed to list pending files");
+                return Vec::new();
+            }
+        };
+        let root = self.config.workdir.as_std_path();
+        let candidates = rules.restorable(&self.git, root, &pending);
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+        // Only notes the pull is going to write need putting back; the rest
+        // stay as the editor left them.
+        let incoming: HashSet<String> =
+            match self.git.fetch().and_then(|()| self.git.changed_upstream()) {
+                Ok(incoming) => incoming.into_iter().collect(),
+                Err(err) => {
+                    debug!(?err, "failed to list the incoming changes");
+                    return Vec::new();
+                }
+            };
+        let mut restored = Vec::new();
+        for (path, checked) in candidates {
+            if !incoming.contains(&path) {
+                continue;
+            }
+            // Typed into since the check: no longer only a front matter bump.
+            if std::fs::read_to_string(root.join(&path)).ok().as_deref() != Some(checked.as_str()) {
+                continue;
+            }
+            match self.git.checkout_head(&path) {
+                Ok(()) => {
+                    debug!(%path, "put back an ignored front matter change before pulling");
+                    restored.push(path);
+                }
+                Err(err) => warn!(?err, %path, "failed to put back an ignored front matter change"),
+            }
+        }
+        restored
+    }
+
+    /// Points at the conflict inbox when a failed pull was caused by notes
+    /// edited on both sides.
+    fn report_conflicts(&mut self) {
+        match conflicts::list(&self.git, &self.config) {
+            Ok(open) if !open.is_empty() => {
+                let files: Vec<&str> = open.iter().map(|conflict| conflict.path.as_str()).collect();
+                digest::record(&self.config, |tally| {
+                    tally
+                        .conflicts
+                        .extend(files.iter().map(|path| path.to_string()));
+                });
+                warn!(
+                    ?files,
+                    "notes were changed here and on another device; choose which version to keep in obsyncgit-gui or with `obsyncgit conflicts`"
+                );
+                if let Some(alerts) = &mut self.alerts {
+                    alerts.conflicts(&files);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => debug!(?err, "failed to list conflicts"),
+        }
+    }
+
+    /// `device-branches` and `pull-request` topologies: merge what the other
+    /// devices published, push our history to `device/<name>`, then advance
+    /// the shared branch, hand over to `sync.merge_command` or open a pull
+    /// request.
+    fn publish_device_branch(&mut self) -> Result<()> {
+        let own = self.config.sync.device_branch();
+        self.git.fetch()?;
+        let mut sources = vec![format!(
+            "{}/{}",
+            self.config.remote.name, self.config.branch
+        )];
+        if self.pull_requests.is_none() && self.config.sync.merge_command.is_none() {
+            let own_ref = format!("{}/{}", self.config.remote.name, own);
+            sources.extend(
+                self.git
+                    .remote_device_branches()?
+                    .into_iter()
+                    .filter(|reference| *reference != own_ref),
+            );
+        }
+        for source in &sources {
+            if !self.git.has_commits_from(source)? {
+                continue;
+            }
+            match self.git.merge(source) {
+                Ok(()) => info!(%source, "merged remote changes"),
+                // The conflict stays on the remote until resolved by hand; our
+                // own branch keeps receiving this device's changes meanwhile.
+                Err(err) => warn!(?err, %source, "could not merge, leaving it for later"),
+            }
+        }
+        self.after_pull();
+
+        if !self.git.is_ahead_of(&own)? {
+            return Ok(());
+        }
+        if self.defer_large_push()? {
+            return Ok(());
+        }
+        if self.rewrite_device_branch {
+            self.git.force_push_head_to(&own)?;
+            self.rewrite_device_branch = false;
+        } else {
+            self.git.push_head_to(&own)?;
+        }
+        info!(branch = %own, "pushed local commits to device branch");
+        self.emit(Event::PushOk {
+            branch: own.clone(),
+        });
+
+        if let Some(target) = &self.pull_requests {
+            self.open_pull_request(target, &own);
+        } else if let Some(command) = self.config.sync.merge_command.clone() {
+            self.run_merge_command(&command, &own);
+        } else if self.git.has_unpushed_commits()? {
+            match self.git.push() {
+                Ok(()) => {
+                    info!(branch = %self.config.branch, "advanced shared branch");
+                    self.emit(Event::PushOk {
+                        branch: self.config.branch.clone(),
+                    });
+                }
+                Err(err) if is_push_rejected(&err) => {
+                    debug!("shared branch moved on, merging again on the next poll");
+                }
+                Err(err) if is_protected_branch(&err) => {
+                    warn!(
+                        branch = %self.config.branch,
+                        "shared branch is protected; set sync.topology to pull-request to publish through pull requests"
+                    );
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps a pull request open from `device_branch` into the shared branch.
+    /// Failures are only logged: the device branch is pushed either way and
+    /// the next push tries again.
+    fn open_pull_request(&self, target: &PullRequestTarget, device_branch: &str) {
+        let pull = PullRequest {
+            head: device_branch.to_string(),
+            base: self.config.branch.clone(),
+            title: format!("Notes from {}", self.config.sync.device_name()),
+            body: "Opened by ObsyncGit; new notes from this device are added as they are pushed."
+                .to_string(),
+        };
+        match target
+            .client()
+            .and_then(|client| client.ensure_pull_request(&target.repo, &pull))
+        {
+            Ok(open) if open.created => info!(url = %open.web_url, "opened pull request"),
+            Ok(open) => debug!(url = %open.web_url, "pull request already open"),
+            Err(err) => warn!(?err, branch = %device_branch, "failed to open pull request"),
+        }
+    }
+
+    fn run_merge_command(&self, command: &str, device_branch: &str) {
+        let mut cmd = shell_command(UpdateShell::default(), command);
+        cmd.current_dir(self.config.workdir.as_std_path())
+            .stdin(Stdio::null())
+            .env("OBSYNCGIT_DEVICE_BRANCH", device_branch)
+            .env("OBSYNCGIT_TARGET_BRANCH", &self.config.branch)
+            .env("OBSYNCGIT_REMOTE", &self.config.remote.name)
+            .env("OBSYNCGIT_REPO_URL", &self.config.repo_url)
+            .envs(self.git.repo_env());
+        // A command stuck on the network or a prompt would otherwise hold up
+        // syncing for good; it is killed with whatever it started.
+        let timeout = self.config.sync.merge_command_timeout_seconds;
+        let stall = (timeout > 0).then(|| Duration::from_secs(timeout));
+        let output = git::spawn_group(&mut cmd)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut child| git::watch_output(&mut child, stall, |_| {}));
+        match output {
+            Ok(Some(output)) if output.status.success() => {
+                debug!(command, "merge command finished");
+            }
+            Ok(Some(output)) => warn!(
+                command,
+                status = %output.status,
+                stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+                "merge command failed"
+            ),
+            Ok(None) => warn!(
+                command,
+                "merge command sent nothing for {}, killed it",
+                humantime::format_duration(Duration::from_secs(timeout))
+            ),
+            Err(err) => warn!(?err, command, "failed to run merge command"),
+        }
+    }
+
+    /// Whether network operations are currently on hold; local commits
+    /// continue and are published once this clears.
+    fn network_deferred(&self) -> bool {
+        !self.schedule_open
+            || (self.metered_now && self.config.sync.metered_policy == MeteredPolicy::DeferAll)
+    }
+
+    fn update_schedule(&mut self) {
+        let open = self.schedule.network_allowed(&Local::now());
+        if open != self.schedule_open {
+            if open {
+                info!("sync window opened, resuming network operations");
+            } else {
+                info!("outside the sync window, committing locally only");
+            }
+        }
+        self.schedule_open = open;
+    }
+
+    /// Whether to hold back a push carrying large files, on a metered
+    /// connection under `defer_large` or on a low battery.
+    fn defer_large_push(&self) -> Result<bool> {
+        let metered =
+            self.metered_now && self.config.sync.metered_policy == MeteredPolicy::DeferLarge;
+        let limit_kb = match (metered, self.low_battery) {
+            (false, false) => return Ok(false),
+            (true, false) => self.config.sync.metered_max_push_kb,
+            (false, true) => self.config.power.battery_max_push_kb,
+            (true, true) => self
+                .config
+                .sync
+                .metered_max_push_kb
+                .min(self.config.power.battery_max_push_kb),
+        };
+        let limit = limit_kb.saturating_mul(1024);
+        let root = self.config.workdir.as_std_path();
+        let large: Vec<String> = self
+            .git
+            .unpushed_paths()?
+            .into_iter()
+            .filter(|path| std::fs::metadata(root.join(path)).is_ok_and(|meta| meta.len() > limit))
+            .collect();
+        if large.is_empty() {
+            return Ok(false);
+        }
+        if self.low_battery {
+            info!(
+                ?large,
+                "deferring push of large files until mains power is back"
+            );
+        } else {
+            info!(
+                ?large,
+                "deferring push of large files until the connection is unmetered"
+            );
+        }
+        Ok(true)
+    }
+
+    /// Picks up what a pull or clone brought in besides notes.
+    fn after_pull(&mut self) {
+        self.refresh_shared_policy();
+        self.check_case_collisions();
+        self.check_unicode_forms();
+        self.check_line_endings();
+    }
+
+    /// Warns about tracked paths that differ only in letter case, once per
+    /// change, and renames them under `sync.case_collisions: rename`.
+    fn check_case_collisions(&mut self) {
+        let collisions = match self.git.tracked_files() {
+            Ok(tracked) => case_collisions::find(&tracked),
+            Err(err) => {
+                debug!(?err, "failed to list tracked files");
+                return;
+            }
+        };
+        if collisions == self.case_collisions {
+            return;
+        }
+        if !collisions.is_empty()
+            && self.config.sync.case_collisions == CaseCollisionPolicy::Rename
+            && self.config.sync.mode != SyncMode::PullOnly
+        {
+            match case_collisions::resolve(&self.git, &collisions) {
+                Ok(moved) => {
+                    info!(?moved, "renamed paths that differed only in letter case");
+                    self.case_collisions.clear();
+                    return;
+                }
+                // Tried again after the next pull.
+                Err(err) => warn!(
+                    ?err,
+                    "failed to rename paths that differ only in letter case"
+                ),
+            }
+        }
+        for collision in &collisions {
+            warn!(
+                paths = ?collision.paths,
+                "paths differ only in letter case and macOS and Windows keep only one of them; rename all but one, or run `obsyncgit case-collisions --rename`"
+            );
+        }
+        self.case_collisions = collisions;
+    }
+
+    /// Warns about tracked names in more than one Unicode form, once per
+    /// change, and renames every decomposed name under
+    /// `compat.unicode_normalization: nfc`.
+    fn check_unicode_forms(&mut self) {
+        let policy = self.config.compat.unicode_normalization;
+        if policy == UnicodeNormalization::Off {
+            return;
+        }
+        let tracked = match self.git.tracked_files() {
+            Ok(tracked) => tracked,
+            Err(err) => {
+                debug!(?err, "failed to list tracked files");
+                return;
+            }
+        };
+        let decomposed: Vec<String> = tracked
+            .iter()
+            .filter(|path| !normalization::is_nfc(path))
+            .cloned()
+            .collect();
+        if decomposed == self.decomposed {
+            return;
+        }
+        if !decomposed.is_empty()
+            && policy == UnicodeNormalization::Nfc
+            && self.config.sync.mode != SyncMode::PullOnly
+        {
+            match normalization::resolve(&self.git) {
+                Ok(moved) => {
+                    info!(?moved, "renamed files to their composed Unicode names");
+                    self.decomposed.clear();
+                    return;
+                }
+                // Tried again after the next pull.
+                Err(err) => warn!(?err, "failed to rename files to composed Unicode names"),
+            }
+        }
+        for paths in normalization::duplicates(&tracked) {
+            warn!(
+                ?paths,
+                "paths are one name in different Unicode forms and macOS keeps only one of them; merge them, or set compat.unicode_normalization to nfc"
+            );
+        }
+        self.decomposed = decomposed;
+    }
+
+    /// Writes the rules of `compat.line_endings` into `.gitattributes` and
+    /// commits the files converted to them, once.
+    fn check_line_endings(&mut self) {
+        if self.config.sync.mode == SyncMode::PullOnly {
+            return;
+        }
+        match line_endings::enforce(&self.git, self.config.compat.line_endings) {
+            Ok(Some(converted)) => info!(
+                files = converted.len(),
+                "committed the vault's line endings policy"
+            ),
+            Ok(None) => {}
+            // Tried again after the next pull.
+            Err(err) => warn!(?err, "failed to apply compat.line_endings"),
+        }
+    }
+
+    /// Seeds or applies `.obsyncgit/config.shared.yaml` when shared config is
+    /// enabled. Failures are logged and the current settings are kept.
+    fn refresh_shared_policy(&mut self) {
+        if !self.config.shared_config.enabled {
+            return;
+        }
+        let workdir = self.config.workdir.clone();
+        let policy = match SharedPolicy::load(&workdir) {
+            Ok(Some(policy)) => policy,
+            // A read-only replica must not leave files of its own in the vault.
+            Ok(None) if self.config.sync.mode == SyncMode::PullOnly => return,
+            Ok(None) => {
+                match SharedPolicy::from_config(&self.config).save(&workdir) {
+                    Ok(path) => info!(%path, "published shared configuration to the vault"),
+                    Err(err) => warn!(?err, "failed to publish shared configuration"),
+                }
+                return;
+            }
+            Err(err) => {
+                warn!(?err, "ignoring unreadable shared configuration");
+                return;
+            }
+        };
+
+        let mut updated = self.config.clone();
+        policy.apply_to(&mut updated);
+        if SharedPolicy::from_config(&updated) == SharedPolicy::from_config(&self.config) {
+            return;
+        }
+        let ignore = match IgnoreMatcher::for_config(&updated, self.cloud) {
+            Ok(ignore) => ignore,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "shared configuration has invalid ignore globs, not applying"
+                );
+                return;
+            }
+        };
+        let schedule = match Schedule::new(&updated.schedule) {
+            Ok(schedule) => schedule,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "shared configuration has an invalid schedule, not applying"
+                );
+                return;
+            }
+        };
+        *self.ignore.write().unwrap_or_else(|err| err.into_inner()) = ignore;
+        self.stability = WriteStability::new(&updated.stability);
+        self.schedule = schedule;
+        if updated.deletions != self.config.deletions {
+            self.deletions = DeletionGuard::new(&updated.deletions);
+        }
+        self.config = updated;
+        info!("applied shared configuration from the vault");
+    }
+
     fn build_commit_message(&self, files: &[String]) -> String {
         use chrono::{SecondsFormat, Utc};
 
         let cfg = &self.config.commit;
         let prefix = cfg.prefix.trim();
-        let summary = if files.len() <= cfg.max_files_in_summary {
-            files.join(", ")
-        } else {
-            format!("updated {} files", files.len())
+        let summary = match cfg.style {
+            CommitStyle::Summary if files.len() <= cfg.max_files_in_summary => files.join(", "),
+            CommitStyle::Summary => format!("updated {} files", files.len()),
+            CommitStyle::Mobile if files.len() == 1 => files[0].clone(),
+            CommitStyle::Mobile => format!("{} files", files.len()),
         };
         let mut message = format!("{} {}", prefix, summary);
         if cfg.include_timestamp {
             let ts = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
             message.push_str(&format!(" ({ts})"));
         }
+        if cfg.style == CommitStyle::Mobile && files.len() > 1 {
+            message.push_str("\n\n");
+            message.push_str(&files.join("\n"));
+        }
+        if cfg.trailers.device() {
+            message.push_str(&format!(
+                "\n\nX-Obsync-Device: {}\nX-Obsync-Files-Count: {}",
+                self.config.sync.device_name(),
+                files.len()
+            ));
+        }
         message
     }
 }
 
-fn compute_timeout(
-    now: Instant,
-    dirty_since: Option<Instant>,
-    debounce: Duration,
-    last_poll: Instant,
-    poll_interval: Duration,
-    backoff_until: Option<Instant>,
-) -> Duration {
-    let mut deadline = now + Duration::from_secs(300);
+/// Why HEAD may be amended, see [`SyncDaemon::amend_target`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Amend {
+    /// HEAD has not left this machine.
+    Unpublished,
+    /// HEAD is on our device branch only.
+    DeviceBranch,
+}
+
+impl GitBackend for SyncDaemon {
+    fn sync(&mut self) -> Result<bool> {
+        self.traced("sync", Self::sync_once)
+    }
+
+    fn commit(&mut self) -> Result<bool> {
+        self.traced("commit", Self::commit_local)
+    }
+
+    fn poll(&mut self) -> Result<()> {
+        self.traced("poll", Self::publish)
+    }
+
+    fn unsettled(&self) -> bool {
+        self.unsettled
+    }
+}
+
+/// Sleeps until `deadline`, or forever when there is none, so disabled
+/// timers simply never fire inside `select!`.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Warns about each file `security.secret_scan` newly holds back and
+/// leaves the list for `status` and the desktop bars; `held` remembers the
+/// files while they stay held back.
+fn hold_secrets(
+    held: &mut Option<HashSet<String>>,
+    config: &Config,
+    secrets: &[(String, Finding)],
+) {
+    let paths: HashSet<String> = secrets.iter().map(|(path, _)| path.clone()).collect();
+    if held.as_ref() == Some(&paths) {
+        return;
+    }
+    let known = held.take().unwrap_or_default();
+    for (path, finding) in secrets {
+        if !known.contains(path) {
+            warn!(
+                %path,
+                %finding,
+                "not committing a file that looks like a secret; move it out of the vault, ignore it, or list it in security.secret_scan.allow"
+            );
+        }
+    }
+    let mut listed: Vec<String> = paths.iter().cloned().collect();
+    listed.sort();
+    let recorded = ControlInbox::new(config).and_then(|inbox| inbox.record_held_secrets(&listed));
+    if let Err(err) = recorded {
+        debug!(?err, "failed to record the held back secrets");
+    }
+    *held = Some(paths);
+}
 
-    if let Some(until) = backoff_until {
-        deadline = deadline.min(until);
+/// `ignore.max_file_size_mb` and `ignore.extensions_blocklist`: names each
+/// pending file they keep out of commits, once; `reported` remembers them
+/// while they stay blocked.
+fn warn_blocked_files(reported: &mut HashSet<String>, blocked: &[(String, Blocked)]) {
+    reported.retain(|path| blocked.iter().any(|(blocked, _)| blocked == path));
+    for (path, reason) in blocked {
+        if reported.insert(path.clone()) {
+            warn!(%path, %reason, "not committing this file");
+        }
     }
+}
 
-    if let Some(dirty_at) = dirty_since {
-        let dirty_deadline = dirty_at + debounce;
-        deadline = deadline.min(dirty_deadline);
+/// `shared_vault.protected_paths`: names each pending file kept out of
+/// commits, once; `reported` remembers them while they stay changed.
+fn warn_protected_files(reported: &mut HashSet<String>, protected: &[String]) {
+    reported.retain(|path| protected.contains(path));
+    for path in protected {
+        if reported.insert(path.clone()) {
+            warn!(
+                %path,
+                "not committing a change to a protected path of the shared vault; `git restore` it to take the shared version"
+            );
+        }
     }
+}
 
-    let poll_deadline = last_poll + poll_interval;
-    deadline = deadline.min(poll_deadline);
+/// `sync.conflict_copies: ignore` or `inbox`: names each conflict copy
+/// kept out of commits, once; `reported` remembers them while they stay.
+fn warn_conflict_copies(
+    reported: &mut HashSet<String>,
+    copies: &[ConflictCopy],
+    policy: ConflictCopyPolicy,
+) {
+    reported.retain(|path| copies.iter().any(|copy| &copy.path == path));
+    for copy in copies {
+        if !reported.insert(copy.path.clone()) {
+            continue;
+        }
+        if policy == ConflictCopyPolicy::Inbox {
+            warn!(
+                copy = %copy.path,
+                note = %copy.original,
+                tool = %copy.tool,
+                "not committing a conflict copy made by another sync tool; choose a version with `obsyncgit conflicts`"
+            );
+        } else {
+            warn!(
+                copy = %copy.path,
+                tool = %copy.tool,
+                "not committing a conflict copy made by another sync tool"
+            );
+        }
+    }
+}
 
-    deadline
-        .saturating_duration_since(now)
-        .min(Duration::from_secs(300))
-        .max(Duration::from_millis(200))
+/// `sync.file_names: warn`: names each pending file Windows cannot check
+/// out, once; `reported` remembers them while they stay pending.
+fn warn_unsafe_names(reported: &mut HashSet<String>, pending: &[String]) {
+    reported.retain(|path| pending.contains(path));
+    for path in pending {
+        let Some(problem) = file_names::windows_problem(path) else {
+            continue;
+        };
+        if reported.insert(path.clone()) {
+            warn!(
+                %path,
+                problem,
+                "Windows cannot check out this file; rename it, or set sync.file_names to rename"
+            );
+        }
+    }
 }
 
-fn backoff_delay(step: u32) -> Duration {
-    let seconds = 1u64 << step;
-    let base = Duration::from_secs(seconds);
-    base.min(Duration::from_secs(300))
+/// Names each pending file held back as a duplicate in another Unicode
+/// form, once; `reported` remembers them while they stay held.
+fn warn_duplicate_forms(reported: &mut HashSet<String>, duplicating: &[String]) {
+    reported.retain(|path| duplicating.contains(path));
+    for path in duplicating {
+        if reported.insert(path.clone()) {
+            warn!(
+                %path,
+                composed = %normalization::nfc(path),
+                "not committing a file whose name differs from another only in Unicode form; merge the two notes into one"
+            );
+        }
+    }
 }
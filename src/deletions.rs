@@ -0,0 +1,174 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use tracing::{info, warn};
+
+use crate::cloud;
+use crate::config::{DeletionConfig, DeletionMode};
+use crate::git::GitFacade;
+
+/// Intercepts deleted files before they are committed, according to the
+/// configured [`DeletionMode`].
+#[derive(Debug)]
+pub struct DeletionGuard {
+    config: DeletionConfig,
+    /// When each held deletion was first noticed (`delay` mode). Kept in
+    /// memory only, so a restart starts the delay over.
+    first_seen: HashMap<String, Instant>,
+}
+
+impl DeletionGuard {
+    pub fn new(config: &DeletionConfig) -> Self {
+        Self {
+            config: config.clone(),
+            first_seen: HashMap::new(),
+        }
+    }
+
+    /// Applies the deletion policy to the working tree and returns the
+    /// deleted paths that must not be staged in this cycle.
+    pub fn intercept(&mut self, git: &GitFacade, root: &Path) -> Result<Vec<String>> {
+        if self.config.mode == DeletionMode::Commit {
+            return Ok(Vec::new());
+        }
+        let trash_prefix = format!("{}/", self.config.trash_dir.trim_matches('/'));
+        let deleted: Vec<String> = git
+            .list_deleted_files()?
+            .into_iter()
+            .filter(|path| !path.starts_with(&trash_prefix))
+            .collect();
+        let deleted = self.without_renames(git, deleted)?;
+        // Evicted to the cloud, not deleted; restoring or trashing them
+        // would fight the sync service.
+        let evicted = cloud::online_only(root, &deleted);
+        let deleted: Vec<String> = deleted
+            .into_iter()
+            .filter(|path| !evicted.contains(path))
+            .collect();
+
+        match self.config.mode {
+            DeletionMode::Commit => Ok(Vec::new()),
+            DeletionMode::Trash => {
+                for path in &deleted {
+                    if let Err(err) = self.move_to_trash(git, root, path) {
+                        warn!(?err, path, "failed to move deleted file to trash");
+                    }
+                }
+                Ok(Vec::new())
+            }
+            DeletionMode::Delay => {
+                let now = Instant::now();
+                let delay = self.delay();
+                let hours = self.config.delay_hours;
+                self.first_seen.retain(|path, _| deleted.contains(path));
+                let mut held = Vec::new();
+                for path in deleted {
+                    let seen = *self.first_seen.entry(path.clone()).or_insert_with(|| {
+                        info!(path, hours, "holding back deletion");
+                        now
+                    });
+                    if now.duration_since(seen) < delay {
+                        held.push(path);
+                    }
+                }
+                Ok(held)
+            }
+        }
+    }
+
+    /// When the oldest held deletion becomes eligible for commit.
+    pub fn next_release(&self) -> Option<Instant> {
+        let delay = self.delay();
+        self.first_seen.values().map(|seen| *seen + delay).min()
+    }
+
+    fn delay(&self) -> Duration {
+        Duration::from_secs(self.config.delay_hours.saturating_mul(3600))
+    }
+
+    /// Drops deletions whose exact content reappeared under a new untracked
+    /// path, so renames and moves are committed as usual.
+    fn without_renames(&self, git: &GitFacade, deleted: Vec<String>) -> Result<Vec<String>> {
+        if deleted.is_empty() {
+            return Ok(deleted);
+        }
+        let untracked = git.list_untracked_files()?;
+        if untracked.is_empty() {
+            return Ok(deleted);
+        }
+        let new_content: HashSet<String> = git.hash_files(&untracked)?.into_iter().collect();
+        let renamed: HashSet<String> = git
+            .head_blob_ids(&deleted)?
+            .into_iter()
+            .filter(|(_, id)| new_content.contains(id))
+            .map(|(path, _)| path)
+            .collect();
+        Ok(deleted
+            .into_iter()
+            .filter(|path| !renamed.contains(path))
+            .collect())
+    }
+
+    fn move_to_trash(&self, git: &GitFacade, root: &Path, path: &str) -> Result<()> {
+        git.checkout_head(path)?;
+        let source = root.join(path);
+        let target = unused_path(
+            root.join(self.config.trash_dir.trim_matches('/'))
+                .join(path),
+        );
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs::rename(&source, &target).with_context(|| {
+            format!(
+                "failed to move {} to {}",
+                source.display(),
+                target.display()
+            )
+        })?;
+        // Restoring the file recreated folders the user had removed.
+        let mut dir = source.parent();
+        while let Some(current) = dir
+            && current != root
+            && fs::remove_dir(current).is_ok()
+        {
+            dir = current.parent();
+        }
+        info!(path, trash = %target.display(), "moved deleted file to trash");
+        Ok(())
+    }
+}
+
+/// `path`, or when something is already there, the same name with the time
+/// before the extension (`note.20240115-093000.md`), so a note deleted
+/// again does not overwrite its earlier copy in the trash.
+fn unused_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    (1..)
+        .map(|attempt| {
+            let suffix = if attempt == 1 {
+                String::new()
+            } else {
+                format!("-{attempt}")
+            };
+            path.with_file_name(format!("{stem}.{stamp}{suffix}{extension}"))
+        })
+        .find(|candidate| !candidate.exists())
+        .expect("an unused name")
+}
@@ -0,0 +1,326 @@
+//! `digest`: a daily or weekly summary of the daemon's work, shown as a
+//! desktop notification and/or posted to a webhook, so a quiet daemon can
+//! be told apart from a stuck one. The daemon tallies syncs, conflicts and
+//! errors in the state directory as they happen; notes changed are counted
+//! from git when the digest goes out.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use camino::Utf8PathBuf;
+use chrono::{DateTime, Utc};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::git::GitFacade;
+use crate::git_error::GitError;
+use crate::instance;
+use crate::tr;
+
+const USER_AGENT: &str = concat!("obsyncgit/", env!("CARGO_PKG_VERSION"));
+
+/// What happened since the last digest, kept in the state directory so a
+/// restart does not lose the count.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Tally {
+    /// RFC 3339; when the period started.
+    pub since: String,
+    /// HEAD when the period started; notes changed are counted against it.
+    pub start_commit: Option<String>,
+    /// Cycles that reached the remote: commits pushed, polls and recovery
+    /// probes.
+    pub syncs: u64,
+    /// Failures the daemon could not retry its way past; lost connections
+    /// and locks are not counted.
+    pub errors: u64,
+    /// Category of the last counted error, see [`GitError::category`].
+    pub last_error: Option<String>,
+    /// Notes that ended up in a conflict.
+    pub conflicts: BTreeSet<String>,
+    /// RFC 3339; when the last digest went out.
+    pub sent_at: Option<String>,
+}
+
+impl Tally {
+    fn new(start_commit: Option<String>) -> Self {
+        Self {
+            since: Utc::now().to_rfc3339(),
+            start_commit,
+            ..Self::default()
+        }
+    }
+
+    /// Time since the period started; zero when the timestamp is
+    /// unreadable.
+    pub fn age(&self) -> Duration {
+        DateTime::parse_from_rfc3339(&self.since)
+            .ok()
+            .and_then(|since| (Utc::now() - since.with_timezone(&Utc)).to_std().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// A digest as sent.
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub tally: Tally,
+    pub notes_changed: usize,
+}
+
+impl Summary {
+    pub fn title(&self) -> String {
+        if self.tally.errors == 0 && self.tally.conflicts.is_empty() {
+            tr!("digest-title-ok")
+        } else {
+            tr!("digest-title-attention")
+        }
+    }
+
+    pub fn body(&self) -> String {
+        let hours = (self.tally.age().as_secs() + 1800) / 3600;
+        let mut body = tr!(
+            "digest-body",
+            hours = hours,
+            syncs = self.tally.syncs,
+            notes = self.notes_changed,
+            conflicts = self.tally.conflicts.len(),
+            errors = self.tally.errors,
+        );
+        if let Some(category) = &self.tally.last_error {
+            body.push('\n');
+            body.push_str(&tr!(
+                "digest-last-error",
+                cause = GitError::from_parts(category, String::new()).cause()
+            ));
+        }
+        body
+    }
+}
+
+fn tally_path(config: &Config) -> Result<Utf8PathBuf> {
+    Ok(config
+        .state_dir()?
+        .join("digest")
+        .join(format!("vault-{}.json", instance::vault_key(config))))
+}
+
+/// The tally of the current period, if one was started.
+pub fn load(config: &Config) -> Option<Tally> {
+    let contents = fs::read_to_string(tally_path(config).ok()?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn save(config: &Config, tally: &Tally) -> Result<()> {
+    let path = tally_path(config)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("failed to create {parent}"))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(tally)?)
+        .with_context(|| format!("failed to write {tmp}"))?;
+    fs::rename(&tmp, &path).with_context(|| format!("failed to replace {path}"))
+}
+
+/// Starts counting unless a period is already running, e.g. from before a
+/// restart.
+pub fn start(git: &GitFacade, config: &Config) -> Result<()> {
+    if config.digest.interval().is_none() || load(config).is_some() {
+        return Ok(());
+    }
+    save(config, &Tally::new(git.head_commit().ok()))
+}
+
+/// Adds to the current period's tally; does nothing when digests are off.
+pub fn record(config: &Config, update: impl FnOnce(&mut Tally)) {
+    if config.digest.interval().is_none() {
+        return;
+    }
+    let mut tally = load(config).unwrap_or_else(|| Tally::new(None));
+    update(&mut tally);
+    if let Err(err) = save(config, &tally) {
+        debug!(?err, "failed to update the digest tally");
+    }
+}
+
+/// How long until the next digest is due; zero when it is overdue, `None`
+/// when the daemon sends none.
+pub fn next_digest_in(config: &Config) -> Option<Duration> {
+    let interval = config.digest.interval()?;
+    Some(
+        load(config)
+            .map(|tally| interval.saturating_sub(tally.age()))
+            .unwrap_or(interval),
+    )
+}
+
+/// The digest of the period so far.
+pub fn summarize(git: &GitFacade, config: &Config) -> Summary {
+    let tally = load(config).unwrap_or_else(|| Tally::new(None));
+    let notes_changed = match &tally.start_commit {
+        Some(commit) => git
+            .changed_paths_since(commit)
+            .map(|paths| paths.len())
+            .unwrap_or_else(|err| {
+                debug!(?err, "failed to count the notes changed");
+                0
+            }),
+        None => 0,
+    };
+    Summary {
+        tally,
+        notes_changed,
+    }
+}
+
+/// Sends the digest of the period so far and starts the next one. The
+/// period carries on when no channel took the digest, so it is not lost.
+pub fn send(git: &GitFacade, config: &Config) -> Result<Summary> {
+    let summary = summarize(git, config);
+    if !config.digest.desktop && config.digest.webhook_url.is_none() {
+        bail!("digest.desktop is off and digest.webhook_url is not set; nowhere to send it");
+    }
+    let mut failures = Vec::new();
+    let mut delivered = false;
+    if config.digest.desktop {
+        match platform::notify(&summary.title(), &summary.body()) {
+            Ok(()) => delivered = true,
+            Err(err) => failures.push(format!("desktop notification: {err:#}")),
+        }
+    }
+    if let Some(url) = &config.digest.webhook_url {
+        match post_webhook(url, config, &summary) {
+            Ok(()) => delivered = true,
+            Err(err) => failures.push(format!("webhook: {err:#}")),
+        }
+    }
+    if !delivered {
+        bail!("failed to send the digest ({})", failures.join("; "));
+    }
+    for failure in &failures {
+        debug!(%failure, "digest delivered elsewhere");
+    }
+    let mut next = Tally::new(git.head_commit().ok());
+    next.sent_at = Some(next.since.clone());
+    save(config, &next)?;
+    Ok(summary)
+}
+
+fn post_webhook(url: &str, config: &Config, summary: &Summary) -> Result<()> {
+    let http = Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(Duration::from_secs(30))
+        .build()
+        .context("failed to build HTTP client")?;
+    // `text` is what Slack, Mattermost and most chat webhooks display.
+    let body = json!({
+        "text": format!("{}\n{}", summary.title(), summary.body()),
+        "vault": config.workdir,
+        "device": config.sync.device_name(),
+        "since": summary.tally.since,
+        "syncs": summary.tally.syncs,
+        "notes_changed": summary.notes_changed,
+        "conflicts": summary.tally.conflicts,
+        "errors": summary.tally.errors,
+        "last_error": summary.tally.last_error,
+    });
+    let response = http
+        .post(url)
+        .json(&body)
+        .send()
+        // Slack and Discord webhook URLs carry their token.
+        .map_err(reqwest::Error::without_url)
+        .context("failed to reach the webhook")?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("the webhook answered {status}");
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::process::{Command, Stdio};
+
+    use anyhow::{Context, Result, bail};
+
+    pub(super) fn notify(title: &str, body: &str) -> Result<()> {
+        let status = Command::new("notify-send")
+            .args(["--app-name=ObsyncGit", title, body])
+            .stdin(Stdio::null())
+            .status()
+            .context("failed to run notify-send; is libnotify installed?")?;
+        if !status.success() {
+            bail!("notify-send failed with {status}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::process::{Command, Stdio};
+
+    use anyhow::{Context, Result, bail};
+
+    /// The text is passed through the environment, which spares quoting it
+    /// for AppleScript.
+    const SCRIPT: &str = r#"display notification (system attribute "OBSYNCGIT_BODY") with title (system attribute "OBSYNCGIT_TITLE")"#;
+
+    pub(super) fn notify(title: &str, body: &str) -> Result<()> {
+        let status = Command::new("osascript")
+            .args(["-e", SCRIPT])
+            .env("OBSYNCGIT_TITLE", title)
+            .env("OBSYNCGIT_BODY", body)
+            .stdin(Stdio::null())
+            .status()
+            .context("failed to run osascript")?;
+        if !status.success() {
+            bail!("osascript failed with {status}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::process::{Command, Stdio};
+
+    use anyhow::{Context, Result, bail};
+
+    /// A WinRT toast; the text comes through the environment, which spares
+    /// quoting it for PowerShell.
+    const SCRIPT: &str = "$null = [Windows.UI.Notifications.ToastNotificationManager,Windows.UI.Notifications,ContentType=WindowsRuntime]; \
+        $x = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+        $t = $x.GetElementsByTagName('text'); \
+        $null = $t.Item(0).AppendChild($x.CreateTextNode($env:OBSYNCGIT_TITLE)); \
+        $null = $t.Item(1).AppendChild($x.CreateTextNode($env:OBSYNCGIT_BODY)); \
+        [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('ObsyncGit').Show([Windows.UI.Notifications.ToastNotification]::new($x))";
+
+    pub(super) fn notify(title: &str, body: &str) -> Result<()> {
+        let status = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+            .env("OBSYNCGIT_TITLE", title)
+            .env("OBSYNCGIT_BODY", body)
+            .stdin(Stdio::null())
+            .status()
+            .context("failed to run powershell")?;
+        if !status.success() {
+            bail!("powershell failed with {status}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+mod platform {
+    use anyhow::{Result, bail};
+
+    pub(super) fn notify(_title: &str, _body: &str) -> Result<()> {
+        bail!("desktop notifications are not supported on this platform")
+    }
+}
@@ -0,0 +1,35 @@
+use std::path::{Path, PathBuf};
+
+use tracing::debug;
+
+/// Bytes available to this user on the disk holding `path`. A path that
+/// does not exist yet, such as a vault about to be cloned, is measured at
+/// its nearest existing parent. `None` when the file system cannot tell.
+pub fn available(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    match fs4::available_space(existing) {
+        Ok(bytes) => Some(bytes),
+        Err(err) => {
+            debug!(?err, path = %existing.display(), "failed to read free disk space");
+            None
+        }
+    }
+}
+
+/// The first of `paths` on a disk with less than `min_free` bytes
+/// available, with what is left there.
+pub fn short_of(paths: &[PathBuf], min_free: u64) -> Option<(PathBuf, u64)> {
+    if min_free == 0 {
+        return None;
+    }
+    paths.iter().find_map(|path| {
+        available(path)
+            .filter(|bytes| *bytes < min_free)
+            .map(|bytes| (path.clone(), bytes))
+    })
+}
+
+/// Whole megabytes in `bytes`, for messages.
+pub fn megabytes(bytes: u64) -> u64 {
+    bytes / (1024 * 1024)
+}
@@ -0,0 +1,190 @@
+//! The daemon's event stream: what it noticed and did, one JSON object per
+//! line in its control directory next to the other [`ControlInbox`] files.
+//! `obsyncgit events --follow` tails it, and so can any other program that
+//! wants to react to syncs. The file is rotated once it grows past
+//! [`MAX_BYTES`], keeping the previous one as `events.1.jsonl`.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::control::ControlInbox;
+
+const FILE_NAME: &str = "events.jsonl";
+const ROTATED_NAME: &str = "events.1.jsonl";
+/// Size at which the stream starts a new file.
+pub const MAX_BYTES: u64 = 1024 * 1024;
+/// How often `--follow` looks for new events.
+const FOLLOW_POLL: Duration = Duration::from_millis(500);
+
+/// Something the daemon noticed or did. Paths are relative to the vault.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The first clone of the vault got this far, in 10% steps per phase.
+    CloneProgress {
+        phase: String,
+        percent: u8,
+        /// Transfer rate as printed by git (`3.00 MiB/s`), while downloading.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rate: Option<String>,
+    },
+    /// The watcher saw the first edit since the last commit.
+    ChangeDetected { paths: Vec<String> },
+    /// Local edits became a commit, or were folded into the previous one.
+    CommitCreated {
+        commit: String,
+        files: Vec<String>,
+        amended: bool,
+    },
+    /// Local commits reached the remote branch.
+    PushOk { branch: String },
+    /// A pull changed files in the vault.
+    PullMerged { commit: String, files: Vec<String> },
+    /// A sync cycle failed; `category` as in `obsyncgit status`.
+    Error { category: String, message: String },
+}
+
+/// An [`Event`] with the time it happened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    /// RFC 3339, in UTC.
+    pub time: String,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// The event stream of one vault.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    path: Utf8PathBuf,
+    rotated: Utf8PathBuf,
+}
+
+impl EventLog {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self::in_dir(ControlInbox::new(config)?.dir()))
+    }
+
+    fn in_dir(dir: &Utf8Path) -> Self {
+        Self {
+            path: dir.join(FILE_NAME),
+            rotated: dir.join(ROTATED_NAME),
+        }
+    }
+
+    /// Where the stream is written.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Appends `event`, stamped with the current time.
+    pub fn append(&self, event: Event) -> Result<()> {
+        let record = Record {
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            event,
+        };
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("failed to create {dir}"))?;
+        }
+        if fs::metadata(&self.path).is_ok_and(|metadata| metadata.len() >= MAX_BYTES) {
+            fs::rename(&self.path, &self.rotated)
+                .with_context(|| format!("failed to rotate {}", self.path))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("failed to open {}", self.path))?;
+        // One write per line, so a reader never sees half of one.
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("failed to write {}", self.path))
+    }
+
+    /// The newest `limit` events, oldest first.
+    pub fn recent(&self, limit: usize) -> Vec<Record> {
+        let mut records = read_records(&self.rotated);
+        records.extend(read_records(&self.path));
+        let skip = records.len().saturating_sub(limit);
+        records.split_off(skip)
+    }
+
+    /// Calls `each` with every event appended from now on, never
+    /// returning unless `each` fails. A rotation is followed into the new
+    /// file, after the lines the old one got since the last look.
+    pub fn follow(&self, mut each: impl FnMut(Record) -> Result<()>) -> Result<()> {
+        let mut offset = fs::metadata(&self.path).map_or(0, |metadata| metadata.len());
+        loop {
+            let len = fs::metadata(&self.path).map_or(0, |metadata| metadata.len());
+            if len < offset {
+                // The file we were reading is events.1.jsonl now.
+                if self.rotated.exists() {
+                    read_from(&self.rotated, offset, &mut each)?;
+                }
+                offset = 0;
+            }
+            if len > offset {
+                offset = read_from(&self.path, offset, &mut each)?;
+            }
+            std::thread::sleep(FOLLOW_POLL);
+        }
+    }
+}
+
+/// Calls `each` with the complete lines of `path` from byte `offset` on and
+/// returns the offset after the last of them.
+fn read_from(
+    path: &Utf8Path,
+    mut offset: u64,
+    each: &mut impl FnMut(Record) -> Result<()>,
+) -> Result<u64> {
+    let mut file = File::open(path).with_context(|| format!("failed to open {path}"))?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        // A line still being written is read again next time.
+        if !line.ends_with('\n') {
+            break;
+        }
+        offset += line.len() as u64;
+        if let Ok(record) = serde_json::from_str(line.trim_end()) {
+            each(record)?;
+        }
+        line.clear();
+    }
+    Ok(offset)
+}
+
+impl Record {
+    /// The record as one JSON line, as it is stored.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// When the event happened; `None` for a malformed time.
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.time)
+            .ok()
+            .map(|time| time.with_timezone(&Utc))
+    }
+}
+
+fn read_records(path: &Utf8Path) -> Vec<Record> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
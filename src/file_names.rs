@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Record of the files renamed for Windows, relative to the vault root.
+pub const RENAMES_PATH: &str = ".obsyncgit/renamed-files.yaml";
+
+/// Names Windows reserves for devices, with or without an extension.
+const RESERVED: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// Why Windows cannot check out `path`, or `None` if it can.
+pub fn windows_problem(path: &str) -> Option<&'static str> {
+    path.split('/').find_map(component_problem)
+}
+
+fn component_problem(name: &str) -> Option<&'static str> {
+    if name.chars().any(|ch| ch.is_control()) {
+        return Some("contains a control character");
+    }
+    if name.contains(['<', '>', ':', '"', '|', '?', '*', '\\']) {
+        return Some("contains one of < > : \" | ? * \\");
+    }
+    if name.ends_with(['.', ' ']) {
+        return Some("ends with a dot or space");
+    }
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if RESERVED.contains(&stem.to_ascii_lowercase().as_str()) {
+        return Some("is a reserved device name");
+    }
+    None
+}
+
+/// `path` with every part made acceptable to Windows: forbidden characters
+/// become their full-width look-alikes (`a: b?.md` becomes `a： b？.md`),
+/// control characters are dropped, trailing dots and spaces are trimmed and
+/// reserved names get a `_`.
+pub fn windows_safe(path: &str) -> String {
+    path.split('/')
+        .map(safe_component)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn safe_component(name: &str) -> String {
+    let mut safe: String = name
+        .chars()
+        .filter(|ch| !ch.is_control())
+        .map(|ch| match ch {
+            '<' => '＜',
+            '>' => '＞',
+            ':' => '：',
+            '"' => '＂',
+            '|' => '｜',
+            '?' => '？',
+            '*' => '＊',
+            '\\' => '＼',
+            other => other,
+        })
+        .collect();
+    safe.truncate(safe.trim_end_matches(['.', ' ']).len());
+    if safe.is_empty() {
+        return "_".to_string();
+    }
+    let stem_len = safe.find('.').unwrap_or(safe.len());
+    if RESERVED.contains(&safe[..stem_len].trim_end().to_ascii_lowercase().as_str()) {
+        safe.insert(stem_len, '_');
+    }
+    safe
+}
+
+/// One entry of [`RENAMES_PATH`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Renamed {
+    pub from: String,
+    pub to: String,
+}
+
+/// Renames the files among `paths` that Windows cannot check out, in the
+/// working tree under `root`, and appends them to [`RENAMES_PATH`]. Files
+/// that no longer exist or whose new name is taken are left alone.
+pub fn rename_for_windows(root: &Path, paths: &[String]) -> Result<Vec<Renamed>> {
+    let mut renamed = Vec::new();
+    for path in paths {
+        if windows_problem(path).is_none() {
+            continue;
+        }
+        let from = root.join(path);
+        let safe = windows_safe(path);
+        let to = root.join(&safe);
+        if !from.is_file() || to.exists() {
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs::rename(&from, &to)
+            .with_context(|| format!("failed to rename {} to {}", from.display(), to.display()))?;
+        // Leave no empty directories behind under the old name.
+        let mut dir = from.parent();
+        while let Some(parent) = dir {
+            if parent == root || fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+        renamed.push(Renamed {
+            from: path.clone(),
+            to: safe,
+        });
+    }
+    if !renamed.is_empty() {
+        record(root, &renamed)?;
+    }
+    Ok(renamed)
+}
+
+fn record(root: &Path, renamed: &[Renamed]) -> Result<()> {
+    let path = root.join(RENAMES_PATH);
+    let mut entries: Vec<Renamed> = match fs::read_to_string(&path) {
+        Ok(contents) => serde_yaml::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", path.display()))?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+    entries.extend_from_slice(renamed);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let serialized = serde_yaml::to_string(&entries).context("failed to render renamed files")?;
+    fs::write(&path, serialized).with_context(|| format!("failed to write {}", path.display()))
+}
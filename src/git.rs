@@ -1,11 +1,37 @@
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
-use std::time::Duration;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result, anyhow, bail};
-use tracing::{debug, warn};
+use anyhow::{Context, Result, bail};
+use tracing::{debug, info, warn};
 
-use crate::config::{Config, GitOptions};
+use crate::bootstrap;
+use crate::config::{BootstrapConfig, Config, GitOptions, StatusCache, SubmoduleConfig};
+use crate::disk;
+use crate::git_error::GitError;
+use crate::network;
+use crate::trace::SyncTrace;
+use crate::transport::BundleRemote;
+
+/// Message of the stash created around `pull --rebase` and merges.
+const AUTOSTASH_MESSAGE: &str = "obsyncgit-autostash";
+/// Tracked files from which `git.status_cache: auto` turns the caches on;
+/// below this a full `git status` takes well under a second.
+pub const LARGE_VAULT_FILES: usize = 5_000;
+/// How long the output of a git command that exited is still waited for.
+const EXITED_OUTPUT_GRACE: Duration = Duration::from_secs(1);
+/// Hooks that can refuse a commit; `--no-verify` skips all but
+/// `prepare-commit-msg`.
+const COMMIT_HOOKS: &[&str] = &[
+    "pre-commit",
+    "prepare-commit-msg",
+    "commit-msg",
+    "pre-merge-commit",
+];
 
 #[derive(Debug, Clone)]
 pub struct GitFacade {
@@ -14,6 +40,199 @@ pub struct GitFacade {
     remote: String,
     branch: String,
     git_options: GitOptions,
+    proxy_env: Vec<(&'static str, String)>,
+    submodules: SubmoduleConfig,
+    bootstrap: BootstrapConfig,
+    /// `git.separate_git_dir`, with `~` expanded.
+    separate_git_dir: Option<PathBuf>,
+    /// Cleared for observers running next to a daemon, see
+    /// [`GitFacade::observer`].
+    optional_locks: bool,
+    /// `disk.min_free_mb` in bytes.
+    min_free_bytes: u64,
+    /// `commit.trailers` when it lists trailers of its own.
+    trailers: Vec<String>,
+    /// `commit.signoff`.
+    signoff: bool,
+    trace: Option<Arc<SyncTrace>>,
+    /// Set for `remote.kind: s3` and `webdav`, whose repository is a single
+    /// bundle rather than a git server.
+    bundle_remote: Option<Arc<BundleRemote>>,
+}
+
+/// A submodule as declared in `.gitmodules`.
+#[derive(Debug, Clone)]
+pub struct Submodule {
+    pub name: String,
+    /// Path relative to the vault root.
+    pub path: String,
+    /// Branch followed by the submodule, when `.gitmodules` names one.
+    pub branch: Option<String>,
+}
+
+/// A commit that touched a single file, as shown by `obsyncgit log`.
+#[derive(Debug, Clone)]
+pub struct FileRevision {
+    pub commit: String,
+    /// Author date in strict ISO 8601 format.
+    pub date: String,
+    pub summary: String,
+    /// `A`, `M`, `D` or `R` as reported by `git log --name-status`.
+    pub change: char,
+}
+
+/// The git ignore rule that decides about a path, as shown by
+/// `obsyncgit ignore explain`.
+#[derive(Debug, Clone)]
+pub struct GitIgnoreRule {
+    /// `.gitignore`, `.git/info/exclude` or `core.excludesFile`, relative to
+    /// the vault where it is inside it.
+    pub file: String,
+    pub line: u32,
+    /// The pattern as written; one starting with `!` re-includes the path.
+    pub pattern: String,
+}
+
+/// A commit on the vault branch with the files it changed, as shown in the
+/// history browser.
+#[derive(Debug, Clone)]
+pub struct CommitSummary {
+    pub commit: String,
+    /// Author date in strict ISO 8601 format.
+    pub date: String,
+    pub summary: String,
+    /// `A`, `M` or `D` and the vault-relative path of every changed file.
+    pub files: Vec<(char, String)>,
+}
+
+/// A commit with everything `git commit-tree` needs to write it again.
+#[derive(Debug, Clone)]
+pub struct RawCommit {
+    pub commit: String,
+    pub parents: Vec<String>,
+    pub tree: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// Git's raw date format, `<unix seconds> <zone>`.
+    pub author_date: String,
+    pub committer_name: String,
+    pub committer_email: String,
+    pub committer_date: String,
+    pub message: String,
+}
+
+impl RawCommit {
+    /// Committer date as Unix seconds.
+    pub fn committed_at(&self) -> i64 {
+        self.committer_date
+            .split_whitespace()
+            .next()
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+/// A file in a tree, as listed by `git ls-tree -r -l`.
+#[derive(Debug, Clone)]
+pub struct TreeFile {
+    pub blob: String,
+    pub size: u64,
+    pub path: String,
+}
+
+/// A commit as `obsyncgit history` lists it.
+#[derive(Debug, Clone)]
+pub struct NotedCommit {
+    pub commit: String,
+    /// Committer date, strict ISO 8601.
+    pub date: String,
+    pub author: String,
+    pub subject: String,
+    /// The commit's note; empty without one.
+    pub note: String,
+}
+
+/// A `git.repo_config` setting git does not see with its pinned value.
+#[derive(Debug, Clone)]
+pub struct ConfigDrift {
+    pub key: String,
+    /// Empty when the setting should be removed.
+    pub wanted: String,
+    /// The value in effect and where it comes from, if it is set.
+    pub current: Option<(String, String)>,
+}
+
+/// One progress update from `git clone --progress`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloneProgress {
+    /// Phase reported by git, e.g. `Receiving objects` or `Resolving deltas`.
+    pub phase: String,
+    pub percent: u8,
+    pub done: u64,
+    pub total: u64,
+    /// Amount received so far as printed by git (`12.00 MiB`), if reported.
+    pub transferred: Option<String>,
+    /// Transfer rate as printed by git (`3.00 MiB/s`), if reported.
+    pub rate: Option<String>,
+}
+
+impl CloneProgress {
+    /// Parses lines such as
+    /// `Receiving objects:  45% (450/1000), 12.00 MiB | 3.00 MiB/s`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.strip_prefix("remote:").unwrap_or(line).trim();
+        let (phase, rest) = line.split_once(':')?;
+        let rest = rest.trim().trim_end_matches(", done.");
+        let (percent, rest) = rest.split_once('%')?;
+        let percent = percent.trim().parse::<u8>().ok()?;
+        let (counts, rest) = rest.trim_start().strip_prefix('(')?.split_once(')')?;
+        let (done, total) = counts.split_once('/')?;
+        let mut transfer = rest.trim_start_matches(',').split('|').map(str::trim);
+        let transferred = transfer.next().filter(|value| !value.is_empty());
+        let rate = transfer.next().filter(|value| !value.is_empty());
+        Some(Self {
+            phase: phase.trim().to_string(),
+            percent,
+            done: done.trim().parse().ok()?,
+            total: total.trim().parse().ok()?,
+            transferred: transferred.map(str::to_string),
+            rate: rate.map(str::to_string),
+        })
+    }
+}
+
+/// Passes clone progress on to `each` at most once per phase and 10% step.
+pub fn clone_progress_steps(mut each: impl FnMut(&CloneProgress)) -> impl FnMut(&CloneProgress) {
+    let mut last: Option<(String, u8)> = None;
+    move |progress| {
+        let step = progress.percent / 10;
+        if last
+            .as_ref()
+            .is_some_and(|(phase, seen)| *phase == progress.phase && *seen == step)
+        {
+            return;
+        }
+        last = Some((progress.phase.clone(), step));
+        each(progress);
+    }
+}
+
+/// Logs clone progress at most once per phase and 10% step. Only the
+/// download itself is logged at info level; the other phases are quick.
+pub fn log_clone_progress() -> impl FnMut(&CloneProgress) {
+    clone_progress_steps(|progress| {
+        if progress.phase == "Receiving objects" {
+            info!(
+                percent = progress.percent,
+                objects = format!("{}/{}", progress.done, progress.total),
+                received = progress.transferred.as_deref().unwrap_or("-"),
+                rate = progress.rate.as_deref().unwrap_or("-"),
+                "cloning repository"
+            );
+        } else {
+            debug!(phase = %progress.phase, percent = progress.percent, "cloning repository");
+        }
+    })
 }
 
 #[derive(Debug)]
@@ -33,14 +252,79 @@ impl GitFacade {
         Ok(Self {
             executable: exe,
             repo_path: config.workdir.clone().into_std_path_buf(),
-            remote: config.remote.clone(),
+            remote: config.remote.name.clone(),
             branch: config.branch.clone(),
             git_options: config.git.clone(),
+            proxy_env: network::proxy_env(&config.network),
+            submodules: config.submodules.clone(),
+            bootstrap: config.bootstrap.clone(),
+            separate_git_dir: config.git.separate_git_dir.as_deref().map(expand_home),
+            optional_locks: true,
+            min_free_bytes: config.disk.min_free_mb.saturating_mul(1024 * 1024),
+            trailers: config.commit.trailers.custom().to_vec(),
+            signoff: config.commit.signoff,
+            trace: None,
+            bundle_remote: BundleRemote::new(config)?.map(Arc::new),
         })
     }
 
+    /// A facade for watching the vault while a daemon syncs it. `git status`
+    /// normally refreshes the index under `index.lock`, which would make the
+    /// daemon's next `git add` fail; observers skip that refresh.
+    pub fn observer(config: &Config) -> Result<Self> {
+        let mut git = Self::new(config)?;
+        git.optional_locks = false;
+        Ok(git)
+    }
+
+    /// Records every git command into `trace` from now on.
+    pub fn set_trace(&mut self, trace: Arc<SyncTrace>) {
+        self.trace = Some(trace);
+    }
+
+    /// Fails with [`GitError::DiskFull`] when the disk holding the vault or
+    /// its repository has less than `disk.min_free_mb` left. Checked before
+    /// every command that writes objects, so git does not run out of space
+    /// halfway and leave a broken index or pack behind.
+    pub fn ensure_disk_space(&self) -> Result<()> {
+        let mut paths = vec![self.repo_path.clone()];
+        paths.extend(self.separate_git_dir.clone());
+        if let Some((path, available)) = disk::short_of(&paths, self.min_free_bytes) {
+            return Err(GitError::DiskFull(format!(
+                "only {} MB left on the disk holding {}; syncing stops below {} MB (disk.min_free_mb)",
+                disk::megabytes(available),
+                path.display(),
+                disk::megabytes(self.min_free_bytes)
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
     pub fn ensure_repo(&self, repo_url: &str) -> Result<()> {
-        if self.repo_path.join(".git").exists() {
+        self.ensure_repo_with_progress(repo_url, &mut log_clone_progress())
+    }
+
+    /// Like [`Self::ensure_repo`], reporting progress of an initial clone to
+    /// `on_progress`.
+    pub fn ensure_repo_with_progress(
+        &self,
+        repo_url: &str,
+        on_progress: &mut dyn FnMut(&CloneProgress),
+    ) -> Result<()> {
+        self.ensure_disk_space()?;
+        if let Some(git_dir) = &self.separate_git_dir
+            && self.repo_path.join(".git").exists()
+        {
+            bail!(
+                "{} has its own .git although git.separate_git_dir is set; move it to {} and run `git --git-dir {} config core.worktree {}`, or unset the option",
+                self.repo_path.display(),
+                git_dir.display(),
+                git_dir.display(),
+                self.repo_path.display()
+            );
+        }
+        if self.has_repo() {
             debug!(path = %self.repo_path.display(), "repository already present, refreshing configuration");
             self.set_remote(repo_url)?;
             self.fetch()?;
@@ -76,19 +360,169 @@ impl GitFacade {
             )
         })?;
 
-        self.clone_repo(repo_url)?;
+        if let Some(parent) = self.separate_git_dir.as_deref().and_then(Path::parent) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let branch_exists = self.remote_has_branch(repo_url)?;
+        if !branch_exists {
+            info!(url = repo_url, branch = %self.branch, "remote branch does not exist yet, it will be created");
+        }
+        self.clone_repo(repo_url, branch_exists, on_progress)?;
+        if self.separate_git_dir.is_some() {
+            // `--separate-git-dir` leaves a `.git` file pointing at the
+            // repository; sync tools would copy it to other machines.
+            let git_file = self.repo_path.join(".git");
+            std::fs::remove_file(&git_file)
+                .with_context(|| format!("failed to remove {}", git_file.display()))?;
+            let worktree = self
+                .repo_path
+                .to_str()
+                .context("vault path is not valid UTF-8")?;
+            self.run_git(&["config", "core.worktree", worktree], false)?;
+        }
         self.checkout_branch()?;
+        self.update_submodules();
         Ok(())
     }
 
-    fn clone_repo(&self, repo_url: &str) -> Result<()> {
+    /// Whether `repo_url` has the configured branch, asked before there is
+    /// a repository to fetch into.
+    pub fn remote_has_branch(&self, repo_url: &str) -> Result<bool> {
+        if !self.repo_path.exists() {
+            // Asked before the vault folder exists; git needs a folder to
+            // run in, and none of the vault's.
+            let mut outside = self.clone();
+            outside.repo_path = std::env::temp_dir();
+            outside.separate_git_dir = None;
+            return outside.remote_has_branch(repo_url);
+        }
+        if let Some(bundle) = &self.bundle_remote {
+            bundle.download()?;
+            if !bundle.cache().exists() {
+                return Ok(false);
+            }
+        }
+        let repo_url = self.git_url(repo_url)?;
+        let reference = format!("refs/heads/{}", self.branch);
+        let output = self
+            .run_git(&["ls-remote", "--heads", repo_url, &reference], false)
+            .context("failed to reach the remote repository")?;
+        Ok(!output.stdout.trim().is_empty())
+    }
+
+    /// Clones `repo_url` into the work directory. Without `branch_exists`
+    /// the remote's default branch is cloned, or nothing for an empty
+    /// repository; [`Self::checkout_branch`] then creates the branch.
+    fn clone_repo(
+        &self,
+        repo_url: &str,
+        branch_exists: bool,
+        on_progress: &mut dyn FnMut(&CloneProgress),
+    ) -> Result<()> {
         debug!(url = repo_url, path = %self.repo_path.display(), "Cloning repository");
-        let args = ["clone", "--branch", &self.branch, repo_url, "."];
-        self.run_git(&args, false).context("git clone failed")?;
+        let repo_url = self.git_url(repo_url)?;
+        if self
+            .bundle_remote
+            .as_ref()
+            .is_some_and(|bundle| !bundle.cache().exists())
+        {
+            // There is no bundle to clone yet; the first push uploads one.
+            return self.init_for_empty_bundle(repo_url);
+        }
+        let separate = self
+            .separate_git_dir
+            .as_deref()
+            .map(|dir| {
+                dir.to_str()
+                    .context("git.separate_git_dir is not valid UTF-8")
+            })
+            .transpose()?;
+        let mut args = vec!["clone", "--progress"];
+        if branch_exists {
+            args.extend(["--branch", &self.branch]);
+        }
+        if let Some(dir) = separate {
+            args.extend(["--separate-git-dir", dir]);
+        }
+        args.extend([repo_url, "."]);
+        let mut child =
+            spawn_group(&mut self.command(&args, false)).context("failed to execute git clone")?;
+
+        // Progress lines are terminated by `\r` while they update in place.
+        let mut messages = Vec::new();
+        let mut line = Vec::new();
+        let stall = Some(self.git_options.timeout_seconds)
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs);
+        let output = watch_output(&mut child, stall, |chunk| {
+            for &byte in chunk {
+                if byte != b'\r' && byte != b'\n' {
+                    line.push(byte);
+                    continue;
+                }
+                let text = String::from_utf8_lossy(&line).trim().to_string();
+                line.clear();
+                if let Some(progress) = CloneProgress::parse(&text) {
+                    on_progress(&progress);
+                } else if !text.is_empty() {
+                    messages.push(text);
+                }
+            }
+        })?;
+        let Some(output) = output else {
+            return Err(stalled(&args, stall.unwrap_or_default()));
+        };
+        if !line.is_empty() {
+            messages.push(String::from_utf8_lossy(&line).trim().to_string());
+        }
+
+        let status = output.status;
+        if !status.success() {
+            bail!(
+                "git clone failed with code {}: {}",
+                status.code().unwrap_or(-1),
+                messages.join("\n")
+            );
+        }
         Ok(())
     }
 
-    fn set_remote(&self, repo_url: &str) -> Result<()> {
+    /// Starts an empty repository for a bundle remote that has no bundle
+    /// yet, laid out as `git clone` would leave it.
+    fn init_for_empty_bundle(&self, repo_url: &str) -> Result<()> {
+        let mut args = vec!["init", "--quiet"];
+        let separate = self
+            .separate_git_dir
+            .as_deref()
+            .map(|dir| {
+                dir.to_str()
+                    .context("git.separate_git_dir is not valid UTF-8")
+            })
+            .transpose()?;
+        if let Some(dir) = separate {
+            args.extend(["--separate-git-dir", dir]);
+        }
+        args.push(".");
+        self.run_git(&args, false)?;
+        self.set_remote(repo_url)
+    }
+
+    /// The URL git itself uses for `repo_url`: the local copy of the bundle
+    /// for a bundle remote, `repo_url` unchanged otherwise.
+    fn git_url<'a>(&'a self, repo_url: &'a str) -> Result<&'a str> {
+        match &self.bundle_remote {
+            Some(bundle) => bundle
+                .cache()
+                .to_str()
+                .context("state directory is not valid UTF-8"),
+            None => Ok(repo_url),
+        }
+    }
+
+    /// Points the configured remote at `repo_url`, adding it if missing.
+    pub fn set_remote(&self, repo_url: &str) -> Result<()> {
+        let repo_url = self.git_url(repo_url)?;
         let result = self.run_git(&["remote", "get-url", &self.remote], false);
         match result {
             Ok(current) => {
@@ -106,7 +540,175 @@ impl GitFacade {
         Ok(())
     }
 
+    /// A git setting as seen from the vault, including its own
+    /// `.git/config`. `None` when unset or the vault does not exist yet.
+    pub fn config_value(&self, key: &str) -> Option<String> {
+        if !self.has_repo() {
+            return None;
+        }
+        let output = self.run_git(&["config", "--get", key], false).ok()?;
+        Some(output.stdout.trim().to_string()).filter(|value| !value.is_empty())
+    }
+
+    /// Sets a git option in the vault's own `.git/config`.
+    pub fn set_config_value(&self, key: &str, value: &str) -> Result<()> {
+        self.run_git(&["config", key, value], false)?;
+        Ok(())
+    }
+
+    /// Removes `key` from the repository's config; a missing key is fine.
+    pub fn unset_config_value(&self, key: &str) -> Result<()> {
+        let output = self
+            .command(&["config", "--unset-all", key], false)
+            .output()
+            .context("failed to run git config --unset-all")?;
+        // Exit code 5 means the key was not set.
+        if !matches!(output.status.code(), Some(0 | 5)) {
+            bail!(
+                "git config --unset-all {key} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    /// Writes `git.repo_config` into the vault's own config, where it wins
+    /// over the user's global settings and their `includeIf` sections. In
+    /// a linked worktree with `extensions.worktreeConfig` on, the settings
+    /// go into this worktree's config, leaving the other checkouts alone.
+    pub fn apply_repo_config(&self) -> Result<()> {
+        let scope = self.repo_config_scope();
+        for (key, value) in &self.git_options.repo_config {
+            if value.is_empty() {
+                let output = self
+                    .command(&["config", scope, "--unset-all", key], false)
+                    .output()
+                    .context("failed to run git config --unset-all")?;
+                if !matches!(output.status.code(), Some(0 | 5)) {
+                    bail!(
+                        "failed to remove git.repo_config.{key}: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                }
+            } else {
+                self.run_git(&["config", scope, key, value], false)
+                    .with_context(|| format!("failed to set git.repo_config.{key}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Settings of `git.repo_config` that git does not see as configured,
+    /// with the value in effect and the file it comes from, such as one
+    /// set through `GIT_CONFIG_*` or `-c`. Empty before the vault is cloned.
+    pub fn repo_config_drift(&self) -> Vec<ConfigDrift> {
+        if !self.has_repo() {
+            return Vec::new();
+        }
+        self.git_options
+            .repo_config
+            .iter()
+            .filter_map(|(key, value)| {
+                let current = self
+                    .run_git(&["config", "--show-origin", "--get", key], false)
+                    .ok()
+                    .and_then(|output| {
+                        let line = output.stdout.trim().to_string();
+                        let (origin, current) = line.split_once('\t')?;
+                        let origin = match origin.strip_prefix("file:") {
+                            Some(path) => path.to_string(),
+                            None if origin == "command line:" => "GIT_CONFIG_* or -c".to_string(),
+                            None => origin.trim_end_matches(':').to_string(),
+                        };
+                        Some((current.to_string(), origin))
+                    });
+                let matches = match &current {
+                    Some((current, _)) => same_config_value(current, value),
+                    None => value.is_empty(),
+                };
+                (!matches).then(|| ConfigDrift {
+                    key: key.clone(),
+                    wanted: value.clone(),
+                    current,
+                })
+            })
+            .collect()
+    }
+
+    fn repo_config_scope(&self) -> &'static str {
+        let linked = self.main_worktree().ok().flatten().is_some();
+        if linked
+            && self
+                .config_value("extensions.worktreeConfig")
+                .is_some_and(|value| same_config_value(&value, "true"))
+        {
+            "--worktree"
+        } else {
+            "--local"
+        }
+    }
+
+    /// Stages every tracked file again through the current attributes, so
+    /// a newly configured filter applies to files git saw unchanged.
+    pub fn renormalize(&self) -> Result<()> {
+        self.run_git(&["add", "--renormalize", "--", "."], false)?;
+        Ok(())
+    }
+
+    /// Tracked text files stored with CRLF or mixed line endings, leaving
+    /// out those the attributes mark as not text.
+    pub fn crlf_files(&self) -> Result<Vec<String>> {
+        let output = self.run_git(&["ls-files", "--eol", "-z"], false)?;
+        Ok(output
+            .stdout
+            .split('\0')
+            .filter_map(|entry| {
+                let (info, path) = entry.split_once('\t')?;
+                let crlf = matches!(info.split_whitespace().next(), Some("i/crlf" | "i/mixed"));
+                (crlf && !info.contains("attr/-text")).then(|| path.to_string())
+            })
+            .collect())
+    }
+
+    /// Applies `git.status_cache`, returning whether the caches are on. The
+    /// file system monitor is git's built-in one, which needs git 2.36 on
+    /// macOS or Windows; elsewhere only the untracked cache is used.
+    pub fn configure_status_cache(&self) -> Result<bool> {
+        let enable = match self.git_options.status_cache {
+            StatusCache::Off => return Ok(false),
+            StatusCache::On => true,
+            StatusCache::Auto => self.tracked_files()?.len() >= LARGE_VAULT_FILES,
+        };
+        if !enable {
+            return Ok(false);
+        }
+        self.set_config_value("core.untrackedCache", "true")?;
+        if self.has_builtin_fsmonitor() {
+            self.set_config_value("core.fsmonitor", "true")?;
+        }
+        Ok(true)
+    }
+
+    fn has_builtin_fsmonitor(&self) -> bool {
+        cfg!(any(target_os = "macos", target_os = "windows"))
+            && self
+                .version()
+                .ok()
+                .and_then(|version| parse_version(&version))
+                .is_some_and(|version| version >= (2, 36))
+    }
+
+    /// Creates an empty repository in the work directory with the remote
+    /// pointing at `repo_url`, for checks that must leave the vault alone.
+    pub fn init_scratch(&self, repo_url: &str) -> Result<()> {
+        std::fs::create_dir_all(&self.repo_path)
+            .with_context(|| format!("failed to create {}", self.repo_path.display()))?;
+        self.run_git(&["init", "--quiet"], false)?;
+        self.set_remote(repo_url)
+    }
+
     pub fn fetch(&self) -> Result<()> {
+        self.ensure_disk_space()?;
         self.run_git(&["fetch", &self.remote], false)?;
         Ok(())
     }
@@ -115,151 +717,2104 @@ impl GitFacade {
         if let Ok(output) = self.run_git(&["rev-parse", "--abbrev-ref", "HEAD"], false)
             && output.stdout.trim() == self.branch
         {
-            return Ok(());
+            return Ok(());
+        }
+
+        if let Err(err) = self.run_git(&["checkout", &self.branch], false) {
+            debug!(
+                ?err,
+                "branch checkout failed, attempting to create tracking branch"
+            );
+            let remote_ref = format!("{}/{}", self.remote, self.branch);
+            if self.has_ref(&format!("refs/remotes/{remote_ref}")) {
+                self.run_git(&["checkout", "-b", &self.branch, &remote_ref], false)
+                    .context("failed to create tracking branch")?;
+            } else {
+                self.create_remote_branch()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts the configured branch, which the remote does not have yet,
+    /// and pushes it. It branches off the current commit, or gets a first
+    /// commit in a repository without any, as cloned from a freshly created
+    /// remote: the starter files of `bootstrap`, or an empty one.
+    fn create_remote_branch(&self) -> Result<()> {
+        if self.has_ref("HEAD") {
+            self.run_git(&["checkout", "-b", &self.branch], false)
+                .context("failed to create branch")?;
+        } else {
+            let head = format!("refs/heads/{}", self.branch);
+            self.run_git(&["symbolic-ref", "HEAD", &head], false)?;
+            let starter = bootstrap::write_starter(&self.repo_path, &self.bootstrap)?;
+            if !starter.is_empty() {
+                let mut args = vec!["add", "--"];
+                args.extend(starter.iter().copied());
+                self.run_git(&args, false)?;
+                info!(files = ?starter, "added starter files to the new vault");
+            }
+            let message = self.with_trailers("Initialize vault repository")?;
+            self.run_commit(&["commit", "--allow-empty", "-m", &message])
+                .context("failed to create the initial commit")?;
+        }
+        self.run_git(
+            &["push", "--set-upstream", &self.remote, &self.branch],
+            false,
+        )
+        .with_context(|| format!("failed to create branch {} on the remote", self.branch))?;
+        info!(branch = %self.branch, remote = %self.remote, "created branch on the remote");
+        Ok(())
+    }
+
+    /// Whether `reference` resolves to a commit.
+    fn has_ref(&self, reference: &str) -> bool {
+        let spec = format!("{reference}^{{commit}}");
+        self.run_git(&["rev-parse", "--verify", "--quiet", &spec], false)
+            .is_ok()
+    }
+
+    pub fn list_changed_files(&self) -> Result<Vec<String>> {
+        let status = self.run_git(&["status", "--short"], false)?;
+        self.without_followed(parse_status_paths(&status.stdout))
+    }
+
+    /// Output of `git --version`.
+    pub fn version(&self) -> Result<String> {
+        let output = Command::new(&self.executable)
+            .arg("--version")
+            .output()
+            .with_context(|| format!("failed to run {}", self.executable))?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// `## branch...upstream [ahead N, behind M]` line of `git status`.
+    pub fn branch_status(&self) -> Result<String> {
+        let output = self.run_git(&["status", "--short", "--branch"], false)?;
+        Ok(output
+            .stdout
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("## ")
+            .to_string())
+    }
+
+    /// Changed and untracked paths, with untracked directories expanded to
+    /// their individual files.
+    pub fn list_pending_paths(&self) -> Result<Vec<String>> {
+        let status = self.run_git(&["status", "--porcelain", "--untracked-files=all"], false)?;
+        self.without_followed(parse_status_paths(&status.stdout))
+    }
+
+    pub fn list_staged_files(&self) -> Result<Vec<String>> {
+        let output = self.run_git(&["diff", "--cached", "--name-only"], false)?;
+        Ok(output
+            .stdout
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    /// Tracked files that are missing from the working tree or staged for
+    /// deletion, relative to HEAD.
+    pub fn list_deleted_files(&self) -> Result<Vec<String>> {
+        let output = self.run_git(&["diff", "--name-only", "--diff-filter=D", "HEAD"], false)?;
+        Ok(non_empty_lines(&output.stdout))
+    }
+
+    /// Every file in the index.
+    pub fn tracked_files(&self) -> Result<Vec<String>> {
+        let output = self.run_git(&["ls-files"], false)?;
+        Ok(non_empty_lines(&output.stdout))
+    }
+
+    /// Whether `path` (vault-relative) is in the index.
+    pub fn is_tracked(&self, path: &str) -> Result<bool> {
+        let output = self.run_git(&["ls-files", "--", path], false)?;
+        Ok(!output.stdout.trim().is_empty())
+    }
+
+    /// The last ignore rule matching `path` (vault-relative) in git's
+    /// ignore files, tracked or not; `None` when none does.
+    pub fn ignore_rule(&self, path: &str) -> Result<Option<GitIgnoreRule>> {
+        let args = ["check-ignore", "--verbose", "--no-index", "--", path];
+        let output = self
+            .command(&args, false)
+            .output()
+            .with_context(|| format!("failed to execute git command: git {}", join_args(&args)))?;
+        // Exit code 1 means no rule matched.
+        if !matches!(output.status.code(), Some(0 | 1)) {
+            bail!(
+                "git {} failed: {}",
+                join_args(&args),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        // `<file>:<line>:<pattern>\t<path>`. Windows paths and patterns may
+        // hold colons too; the line number is what tells them apart.
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let Some((rule, _)) = stdout.split_once('\t') else {
+            return Ok(None);
+        };
+        let parsed = rule.match_indices(':').find_map(|(at, _)| {
+            let (line, pattern) = rule[at + 1..].split_once(':')?;
+            Some(GitIgnoreRule {
+                file: rule[..at].to_string(),
+                line: line.parse().ok()?,
+                pattern: pattern.to_string(),
+            })
+        });
+        parsed
+            .map(Some)
+            .with_context(|| format!("unexpected git check-ignore output: {rule}"))
+    }
+
+    /// Whether git found the vault's file system to ignore letter case when
+    /// it created the repository, as on macOS and Windows.
+    pub fn ignores_case(&self) -> bool {
+        self.config_value("core.ignorecase")
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+    }
+
+    /// Moves tracked files, or every file under a tracked directory, to new
+    /// paths in the index and the working tree, returning the files moved.
+    /// On a case-insensitive file system a renamed path and the path it
+    /// collides with are one file on disk, so the paths in `keep` are
+    /// checked out again afterwards. Local edits to any of them are lost.
+    pub fn rename_tracked(
+        &self,
+        renames: &[(String, String)],
+        keep: &[String],
+    ) -> Result<Vec<(String, String)>> {
+        let staged = self.run_git(&["ls-files", "--stage"], false)?;
+        let mut moved = Vec::new();
+        for line in staged.stdout.lines() {
+            // `<mode> <object> <stage>\t<path>`
+            let Some((meta, path)) = line.split_once('\t') else {
+                continue;
+            };
+            let mut fields = meta.split_whitespace();
+            let (Some(mode), Some(object)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let target = renames.iter().find_map(|(from, to)| {
+                if path == from {
+                    return Some(to.clone());
+                }
+                path.strip_prefix(from.as_str())
+                    .and_then(|rest| rest.strip_prefix('/'))
+                    .map(|rest| format!("{to}/{rest}"))
+            });
+            let Some(target) = target else {
+                continue;
+            };
+            let info = format!("{mode},{object},{target}");
+            self.run_git(&["update-index", "--add", "--cacheinfo", &info], false)?;
+            self.run_git(&["update-index", "--force-remove", "--", path], false)?;
+            moved.push((path.to_string(), target));
+        }
+
+        let targets: Vec<String> = moved.iter().map(|(_, to)| to.clone()).collect();
+        self.checkout_paths(&targets)?;
+        for (from, _) in &moved {
+            let old = self.repo_path.join(from);
+            match std::fs::remove_file(&old) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err).with_context(|| format!("failed to remove {}", old.display()));
+                }
+            }
+            // Leave no empty directories behind under the old spelling.
+            let mut dir = old.parent();
+            while let Some(parent) = dir {
+                if parent == self.repo_path || std::fs::remove_dir(parent).is_err() {
+                    break;
+                }
+                dir = parent.parent();
+            }
+        }
+        self.checkout_paths(keep)?;
+        Ok(moved)
+    }
+
+    /// Writes `paths` from the index into the working tree.
+    fn checkout_paths(&self, paths: &[String]) -> Result<()> {
+        for chunk in paths.chunks(128) {
+            let mut args = vec!["checkout", "--"];
+            args.extend(chunk.iter().map(String::as_str));
+            self.run_git(&args, false)?;
+        }
+        Ok(())
+    }
+
+    pub fn list_untracked_files(&self) -> Result<Vec<String>> {
+        let output = self.run_git(&["ls-files", "--others", "--exclude-standard"], false)?;
+        Ok(non_empty_lines(&output.stdout))
+    }
+
+    /// Blob ids of `paths` as committed in HEAD.
+    pub fn head_blob_ids(&self, paths: &[String]) -> Result<Vec<(String, String)>> {
+        let mut ids = Vec::new();
+        for chunk in paths.chunks(128) {
+            let mut args = vec!["ls-tree", "-r", "HEAD", "--"];
+            args.extend(chunk.iter().map(String::as_str));
+            let output = self.run_git(&args, false)?;
+            for line in output.stdout.lines() {
+                // `<mode> <type> <object>\t<path>`
+                if let Some((meta, path)) = line.split_once('\t')
+                    && let Some(object) = meta.split_whitespace().nth(2)
+                {
+                    ids.push((path.to_string(), object.to_string()));
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Blob ids the working-tree files at `paths` would get if committed.
+    pub fn hash_files(&self, paths: &[String]) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        for chunk in paths.chunks(128) {
+            let mut args = vec!["hash-object", "--"];
+            args.extend(chunk.iter().map(String::as_str));
+            let output = self.run_git(&args, false)?;
+            ids.extend(non_empty_lines(&output.stdout));
+        }
+        Ok(ids)
+    }
+
+    /// Recreates `path` in the working tree from HEAD.
+    pub fn checkout_head(&self, path: &str) -> Result<()> {
+        self.run_git(&["checkout", "HEAD", "--", path], false)?;
+        Ok(())
+    }
+
+    pub fn stage_all(&self) -> Result<()> {
+        let excludes: Vec<String> = self
+            .followed_submodules()?
+            .into_iter()
+            .map(|submodule| format!(":(exclude){}", submodule.path))
+            .collect();
+        let mut args = vec!["add", "-A", "--", "."];
+        args.extend(excludes.iter().map(String::as_str));
+        self.run_git(&args, false)?;
+        Ok(())
+    }
+
+    pub fn stage_paths(&self, paths: &[String]) -> Result<()> {
+        for chunk in paths.chunks(128) {
+            let mut args = vec!["add", "-A", "--"];
+            args.extend(chunk.iter().map(String::as_str));
+            self.run_git(&args, false)?;
+        }
+        Ok(())
+    }
+
+    fn worktree_status(&self) -> Result<String> {
+        let status = self.run_git(&["status", "--porcelain"], false)?;
+        Ok(status.stdout)
+    }
+
+    pub fn is_worktree_clean(&self) -> Result<bool> {
+        let status = self.worktree_status()?;
+        Ok(self
+            .without_followed(parse_status_paths(&status))?
+            .is_empty())
+    }
+
+    /// Submodules declared in `.gitmodules`; empty for most vaults.
+    pub fn submodules(&self) -> Result<Vec<Submodule>> {
+        if !self.repo_path.join(".gitmodules").exists() {
+            return Ok(Vec::new());
+        }
+        let output = match self.run_git(
+            &[
+                "config",
+                "--file",
+                ".gitmodules",
+                "--get-regexp",
+                r"^submodule\..*\.(path|branch)$",
+            ],
+            false,
+        ) {
+            Ok(output) => output,
+            // `--get-regexp` exits with 1 when nothing matches.
+            Err(_) => return Ok(Vec::new()),
+        };
+        let mut submodules: Vec<Submodule> = Vec::new();
+        for line in output.stdout.lines() {
+            let Some((key, value)) = line.split_once(' ') else {
+                continue;
+            };
+            let Some(key) = key.strip_prefix("submodule.") else {
+                continue;
+            };
+            let Some((name, field)) = key.rsplit_once('.') else {
+                continue;
+            };
+            let index = match submodules
+                .iter()
+                .position(|submodule| submodule.name == name)
+            {
+                Some(index) => index,
+                None => {
+                    submodules.push(Submodule {
+                        name: name.to_string(),
+                        path: String::new(),
+                        branch: None,
+                    });
+                    submodules.len() - 1
+                }
+            };
+            match field {
+                "path" => submodules[index].path = value.trim().to_string(),
+                _ => submodules[index].branch = Some(value.trim().to_string()),
+            }
+        }
+        submodules.retain(|submodule| !submodule.path.is_empty());
+        Ok(submodules)
+    }
+
+    /// Submodules listed in `submodules.sync`.
+    pub fn synced_submodules(&self) -> Result<Vec<Submodule>> {
+        Ok(self
+            .submodules()?
+            .into_iter()
+            .filter(|submodule| self.is_synced(submodule))
+            .collect())
+    }
+
+    /// Submodules the vault only follows; see [`SubmoduleConfig::sync`].
+    fn followed_submodules(&self) -> Result<Vec<Submodule>> {
+        Ok(self
+            .submodules()?
+            .into_iter()
+            .filter(|submodule| !self.is_synced(submodule))
+            .collect())
+    }
+
+    fn is_synced(&self, submodule: &Submodule) -> bool {
+        self.submodules
+            .sync
+            .iter()
+            .any(|path| path.trim_matches('/') == submodule.path)
+    }
+
+    fn without_followed(&self, mut paths: Vec<String>) -> Result<Vec<String>> {
+        let followed = self.followed_submodules()?;
+        if !followed.is_empty() {
+            paths.retain(|path| !followed.iter().any(|submodule| submodule.path == *path));
+        }
+        Ok(paths)
+    }
+
+    /// Checks out the commits the vault records for followed submodules and
+    /// initialises synced ones on first use. Failures are logged only, so a
+    /// broken submodule never blocks syncing the notes themselves.
+    pub fn update_submodules(&self) {
+        if !self.submodules.update {
+            return;
+        }
+        let (synced, followed) = match self.submodules() {
+            Ok(all) => all
+                .into_iter()
+                .partition::<Vec<_>, _>(|submodule| self.is_synced(submodule)),
+            Err(err) => {
+                warn!(?err, "failed to read .gitmodules");
+                return;
+            }
+        };
+        if !followed.is_empty() {
+            let mut args = vec!["submodule", "update", "--init", "--recursive", "--"];
+            args.extend(followed.iter().map(|submodule| submodule.path.as_str()));
+            if let Err(err) = self.run_git(&args, false) {
+                warn!(?err, "failed to update submodules");
+            }
+        }
+        for submodule in synced {
+            if self.repo_path.join(&submodule.path).join(".git").exists() {
+                continue;
+            }
+            if let Err(err) = self
+                .run_git(
+                    &["submodule", "update", "--init", "--", &submodule.path],
+                    false,
+                )
+                .and_then(|_| self.submodule_repo(&submodule)?.checkout_branch())
+            {
+                warn!(?err, path = %submodule.path, "failed to initialise submodule");
+            }
+        }
+    }
+
+    /// A facade operating inside `submodule`, on the branch it follows (the
+    /// submodule remote's default branch unless `.gitmodules` names one).
+    pub fn submodule_repo(&self, submodule: &Submodule) -> Result<GitFacade> {
+        let mut repo = self.clone();
+        repo.repo_path = self.repo_path.join(&submodule.path);
+        repo.remote = "origin".to_string();
+        repo.separate_git_dir = None;
+        repo.submodules = SubmoduleConfig {
+            update: false,
+            sync: Vec::new(),
+        };
+        repo.branch = match &submodule.branch {
+            Some(branch) => branch.clone(),
+            None => {
+                let head = repo
+                    .run_git(&["rev-parse", "--abbrev-ref", "origin/HEAD"], false)
+                    .with_context(|| {
+                        format!(
+                            "cannot tell which branch submodule {} follows",
+                            submodule.path
+                        )
+                    })?;
+                head.stdout.trim().trim_start_matches("origin/").to_string()
+            }
+        };
+        Ok(repo)
+    }
+
+    /// Stashes local edits and returns the stash commit's SHA, which
+    /// identifies the entry even after the user pushes or drops stashes of
+    /// their own in the meantime.
+    fn ensure_autostash(&self) -> Result<Option<String>> {
+        if self.is_worktree_clean()? {
+            return Ok(None);
+        }
+
+        let before = self.stash_tip()?;
+        self.run_git(
+            &[
+                "stash",
+                "push",
+                "--include-untracked",
+                "--message",
+                AUTOSTASH_MESSAGE,
+            ],
+            true,
+        )
+        .context("failed to stash local changes before pull --rebase")?;
+
+        let after = self
+            .stash_tip()
+            .context("failed to inspect git stash after push")?;
+        // `stash push` exits successfully without a new entry when nothing
+        // it tracks changed.
+        if after.is_none() || after == before {
+            return Ok(None);
+        }
+        Ok(after)
+    }
+
+    /// SHA of the newest stash entry, if there is any.
+    fn stash_tip(&self) -> Result<Option<String>> {
+        let output = self.run_git(&["stash", "list", "--max-count=1", "--format=%H"], false)?;
+        Ok(non_empty_lines(&output.stdout).into_iter().next())
+    }
+
+    /// Current `stash@{n}` name of the entry with commit `sha`.
+    fn stash_ref(&self, sha: &str) -> Result<Option<String>> {
+        let list = self.run_git(&["stash", "list", "--format=%gd %H"], false)?;
+        Ok(list.stdout.lines().find_map(|line| {
+            let (stash_ref, entry) = line.split_once(' ')?;
+            (entry.trim() == sha).then(|| stash_ref.to_string())
+        }))
+    }
+
+    /// SHAs of the stash entries obsyncgit created on this vault's branch,
+    /// newest first. The stash is shared by all worktrees of a repository,
+    /// and each branch is checked out in at most one of them.
+    pub fn autostashes(&self) -> Result<Vec<String>> {
+        let list = self.run_git(&["stash", "list", "--format=%H %gs"], false)?;
+        let expected = format!("On {}: {AUTOSTASH_MESSAGE}", self.branch);
+        Ok(list
+            .stdout
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter(|(_, subject)| subject.trim() == expected)
+            .map(|(sha, _)| sha.to_string())
+            .collect())
+    }
+
+    /// Applies and drops the stash entry with commit `sha`; on conflicts the
+    /// entry is kept. Fails rather than popping another entry when it is
+    /// gone.
+    pub fn restore_stash(&self, sha: &str) -> Result<()> {
+        let Some(stash_ref) = self.stash_ref(sha)? else {
+            bail!("stash {sha} is no longer in the stash list");
+        };
+        self.run_git(&["stash", "pop", &stash_ref], false)?;
+        Ok(())
+    }
+
+    fn pop_stash(&self, sha: &str) {
+        if let Err(err) = self.restore_stash(sha) {
+            warn!(?err, "failed to restore stash after pull --rebase");
+        }
+    }
+
+    pub fn commit(&self, message: &str) -> Result<bool> {
+        let status = self.run_git(&["status", "--short"], false)?;
+        if status.stdout.trim().is_empty() {
+            return Ok(false);
+        }
+        self.ensure_disk_space()?;
+        let message = self.with_trailers(message)?;
+        self.run_commit(&["commit", "-m", &message])?;
+        Ok(true)
+    }
+
+    /// Runs `git commit` with `args`, skipping the repository's hooks unless
+    /// `git.run_hooks` is set. A commit a hook refused fails with
+    /// [`GitError::Hook`].
+    fn run_commit(&self, args: &[&str]) -> Result<()> {
+        let mut args = args.to_vec();
+        if !self.git_options.run_hooks {
+            args.push("--no-verify");
+        }
+        match self.run_git(&args, true) {
+            Ok(_) => Ok(()),
+            Err(err)
+                if self.git_options.run_hooks
+                    && matches!(GitError::of(&err), GitError::Unknown(_))
+                    && !self.commit_hooks().is_empty() =>
+            {
+                Err(GitError::Hook(format!(
+                    "{} refused the commit: {}",
+                    self.commit_hooks().join(" or "),
+                    GitError::of(&err).message()
+                ))
+                .into())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The hooks that check commits, such as `pre-commit`, in the folder
+    /// git runs hooks from: `core.hooksPath` when set, else `.git/hooks`.
+    pub fn commit_hooks(&self) -> Vec<String> {
+        let Ok(output) = self.run_git(&["rev-parse", "--git-path", "hooks"], false) else {
+            return Vec::new();
+        };
+        let dir = self.repo_path.join(output.stdout.trim());
+        COMMIT_HOOKS
+            .iter()
+            .filter(|hook| is_executable(&dir.join(hook)))
+            .map(|hook| hook.to_string())
+            .collect()
+    }
+
+    /// `message` with `commit.trailers` and `commit.signoff` added to its
+    /// trailer block. A trailer the message already has is not repeated,
+    /// so amending keeps one of each.
+    fn with_trailers(&self, message: &str) -> Result<String> {
+        if self.trailers.is_empty() && !self.signoff {
+            return Ok(message.to_string());
+        }
+        let mut trailers = Vec::new();
+        for trailer in &self.trailers {
+            match trailer.split_once(':') {
+                Some((key, value))
+                    if !key.trim().is_empty()
+                        && !key.trim().contains(char::is_whitespace)
+                        && !value.trim().is_empty() =>
+                {
+                    trailers.push(format!("{}: {}", key.trim(), value.trim()));
+                }
+                _ => bail!("invalid commit.trailers entry '{trailer}' (expected 'Key: value')"),
+            }
+        }
+        if self.signoff {
+            // `Name <email> 1700000000 +0100`
+            let ident = self.run_git(&["var", "GIT_COMMITTER_IDENT"], true)?.stdout;
+            let ident = ident
+                .trim()
+                .rsplitn(3, ' ')
+                .nth(2)
+                .context("git reported no committer identity for commit.signoff")?;
+            trailers.push(format!("Signed-off-by: {ident}"));
+        }
+        let mut args = vec!["interpret-trailers", "--if-exists", "addIfDifferent"];
+        for trailer in &trailers {
+            args.extend(["--trailer", trailer.as_str()]);
+        }
+        // Without a final newline the subject is read as a trailer line.
+        self.run_git_with(&args, &[], Some(&format!("{}\n", message.trim_end())))
+    }
+
+    /// Tags `commit` with the lightweight tag `name`.
+    pub fn create_tag(&self, name: &str, commit: &str) -> Result<()> {
+        self.run_git(&["tag", name, commit], false)?;
+        Ok(())
+    }
+
+    pub fn delete_tag(&self, name: &str) -> Result<()> {
+        self.run_git(&["tag", "--delete", name], false)?;
+        Ok(())
+    }
+
+    /// Tags whose names start with `prefix`, e.g. `snapshot/`, sorted by
+    /// name, with the commit each points at and its committer date in
+    /// strict ISO 8601 format.
+    pub fn list_tags(&self, prefix: &str) -> Result<Vec<(String, String, String)>> {
+        let pattern = format!("refs/tags/{}", prefix.trim_end_matches('/'));
+        let output = self.run_git(
+            &[
+                "for-each-ref",
+                "--sort=refname",
+                "--format=%(refname:strip=2)%09%(objectname)%09%(creatordate:iso-strict)",
+                &pattern,
+            ],
+            false,
+        )?;
+        Ok(output
+            .stdout
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                Some((
+                    fields.next()?.to_string(),
+                    fields.next()?.to_string(),
+                    fields.next()?.to_string(),
+                ))
+            })
+            .filter(|(name, _, _)| name.starts_with(prefix))
+            .collect())
+    }
+
+    /// Fetches the remote's tags whose names start with `prefix`; where a
+    /// local tag of the same name points elsewhere, the remote's wins.
+    pub fn fetch_tags(&self, prefix: &str) -> Result<()> {
+        let refspec = format!("+refs/tags/{prefix}*:refs/tags/{prefix}*");
+        self.run_git(&["fetch", "--quiet", &self.remote, &refspec], false)?;
+        Ok(())
+    }
+
+    /// Pushes the tag `name` to the remote.
+    pub fn push_tag(&self, name: &str) -> Result<()> {
+        let refspec = format!("refs/tags/{name}");
+        self.run_git(&["push", &self.remote, &refspec], false)?;
+        Ok(())
+    }
+
+    /// The newest commit of the branch made from `since` up to `until`
+    /// (dates as git reads them), if any.
+    pub fn last_commit_between(&self, since: &str, until: &str) -> Result<Option<String>> {
+        let since = format!("--since={since}");
+        let until = format!("--until={until}");
+        let output = self.run_git(&["rev-list", "-1", &since, &until, "HEAD"], false)?;
+        let commit = output.stdout.trim();
+        Ok((!commit.is_empty()).then(|| commit.to_string()))
+    }
+
+    /// Every commit reachable from HEAD, parents before children.
+    pub fn history(&self) -> Result<Vec<RawCommit>> {
+        let output = self.run_git(
+            &[
+                "log",
+                "-z",
+                "--reverse",
+                "--topo-order",
+                "--date=raw",
+                "--format=%H%x1f%P%x1f%T%x1f%an%x1f%ae%x1f%ad%x1f%cn%x1f%ce%x1f%cd%x1f%B",
+                "HEAD",
+            ],
+            false,
+        )?;
+        output
+            .stdout
+            .split('\0')
+            .filter(|record| !record.is_empty())
+            .map(|record| {
+                let fields: Vec<&str> = record.splitn(10, '\x1f').collect();
+                let [commit, parents, tree, an, ae, ad, cn, ce, cd, message] = fields[..] else {
+                    bail!("unexpected git log output: {record}");
+                };
+                Ok(RawCommit {
+                    commit: commit.to_string(),
+                    parents: parents.split_whitespace().map(str::to_string).collect(),
+                    tree: tree.to_string(),
+                    author_name: an.to_string(),
+                    author_email: ae.to_string(),
+                    author_date: ad.to_string(),
+                    committer_name: cn.to_string(),
+                    committer_email: ce.to_string(),
+                    committer_date: cd.to_string(),
+                    message: message.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// The files of `tree`, with their blobs and sizes. Submodules are
+    /// left out.
+    pub fn tree_files(&self, tree: &str) -> Result<Vec<TreeFile>> {
+        let output = self.run_git(&["ls-tree", "-r", "-l", "-z", tree], false)?;
+        Ok(output
+            .stdout
+            .split('\0')
+            .filter_map(|entry| {
+                // `<mode> <type> <object> <padded size>\t<path>`
+                let (meta, path) = entry.split_once('\t')?;
+                let mut fields = meta.split_whitespace();
+                let (_mode, kind, blob, size) = (
+                    fields.next()?,
+                    fields.next()?,
+                    fields.next()?,
+                    fields.next()?,
+                );
+                (kind == "blob").then(|| TreeFile {
+                    blob: blob.to_string(),
+                    size: size.parse().unwrap_or(0),
+                    path: path.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Ids of the blobs in the trees of `commits`.
+    pub fn blobs_of(&self, commits: &[String]) -> Result<HashSet<String>> {
+        let mut input = commits.join("\n");
+        input.push('\n');
+        let stdout = self.run_git_with(
+            &["rev-list", "--objects", "--no-walk", "--stdin"],
+            &[],
+            Some(&input),
+        )?;
+        let objects: HashSet<&str> = stdout
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .collect();
+        // `rev-list` does not say which objects are blobs.
+        let mut blobs = HashSet::new();
+        let mut input = objects.into_iter().collect::<Vec<_>>().join("\n");
+        input.push('\n');
+        let types = self.run_git_with(
+            &["cat-file", "--batch-check=%(objectname) %(objecttype)"],
+            &[],
+            Some(&input),
+        )?;
+        for line in types.lines() {
+            if let Some((id, "blob")) = line.split_once(' ') {
+                blobs.insert(id.to_string());
+            }
+        }
+        Ok(blobs)
+    }
+
+    /// Writes `tree` without the files at `paths` and returns the new
+    /// tree. Works on a scratch index, leaving the vault's alone.
+    pub fn tree_without(&self, tree: &str, paths: &[String]) -> Result<String> {
+        let index = self.git_dir()?.join("obsyncgit-scratch.index");
+        let index = index.to_str().context("git directory is not valid UTF-8")?;
+        let env = [("GIT_INDEX_FILE", index)];
+        let result = (|| {
+            self.run_git_with(&["read-tree", tree], &env, None)?;
+            let mut input = paths.join("\0");
+            input.push('\0');
+            self.run_git_with(
+                &["update-index", "--force-remove", "-z", "--stdin"],
+                &env,
+                Some(&input),
+            )?;
+            Ok(self
+                .run_git_with(&["write-tree"], &env, None)?
+                .trim()
+                .to_string())
+        })();
+        let _ = fs::remove_file(index);
+        result
+    }
+
+    /// Writes `commit` again with `tree` and `parents`, keeping its
+    /// message, authorship and dates. Returns the new commit.
+    pub fn commit_tree(
+        &self,
+        commit: &RawCommit,
+        tree: &str,
+        parents: &[String],
+    ) -> Result<String> {
+        let mut args = vec!["commit-tree", tree];
+        for parent in parents {
+            args.extend(["-p", parent.as_str()]);
+        }
+        args.extend(["-F", "-"]);
+        let env = [
+            ("GIT_AUTHOR_NAME", commit.author_name.as_str()),
+            ("GIT_AUTHOR_EMAIL", commit.author_email.as_str()),
+            ("GIT_AUTHOR_DATE", commit.author_date.as_str()),
+            ("GIT_COMMITTER_NAME", commit.committer_name.as_str()),
+            ("GIT_COMMITTER_EMAIL", commit.committer_email.as_str()),
+            ("GIT_COMMITTER_DATE", commit.committer_date.as_str()),
+        ];
+        Ok(self
+            .run_git_with(&args, &env, Some(&commit.message))?
+            .trim()
+            .to_string())
+    }
+
+    /// Points `reference` at `new`, provided it still points at `old`.
+    pub fn update_ref(&self, reference: &str, new: &str, old: &str) -> Result<()> {
+        self.run_git(&["update-ref", reference, new, old], false)?;
+        Ok(())
+    }
+
+    /// Names of the tags on the remote.
+    pub fn remote_tags(&self) -> Result<Vec<String>> {
+        let output = self.run_git(&["ls-remote", "--tags", "--refs", &self.remote], false)?;
+        Ok(output
+            .stdout
+            .lines()
+            .filter_map(|line| line.split_whitespace().nth(1)?.strip_prefix("refs/tags/"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Pushes the tags `names`, replacing the remote's tags of those names.
+    pub fn force_push_tags(&self, names: &[String]) -> Result<()> {
+        let refspecs: Vec<String> = names
+            .iter()
+            .map(|name| format!("refs/tags/{name}"))
+            .collect();
+        let mut args = vec!["push", "--force", self.remote.as_str()];
+        args.extend(refspecs.iter().map(String::as_str));
+        self.run_git(&args, false)?;
+        Ok(())
+    }
+
+    /// Deletes the objects no branch or tag reaches any more, dropping the
+    /// reflogs that would keep them. Stash entries live in the reflog of
+    /// `refs/stash`, so that one is left alone.
+    pub fn prune_unreachable(&self) -> Result<()> {
+        self.run_git(
+            &[
+                "-c",
+                "gc.reflogExpire=now",
+                "-c",
+                "gc.reflogExpireUnreachable=now",
+                "-c",
+                "gc.refs/stash.reflogExpire=never",
+                "-c",
+                "gc.refs/stash.reflogExpireUnreachable=never",
+                "reflog",
+                "expire",
+                "--all",
+            ],
+            false,
+        )?;
+        self.run_git(&["gc", "--quiet", "--prune=now"], false)?;
+        Ok(())
+    }
+
+    /// Force-pushes a commit whose only file is `name` with `contents` to
+    /// `refname` on the remote, outside of any branch. Locally this only
+    /// leaves a few loose objects behind.
+    pub fn push_file_ref(&self, refname: &str, name: &str, contents: &str) -> Result<()> {
+        let blob = self.run_git_with(&["hash-object", "-w", "--stdin"], &[], Some(contents))?;
+        let entry = format!("100644 blob {}\t{name}\n", blob.trim());
+        let tree = self.run_git_with(&["mktree"], &[], Some(&entry))?;
+        // Never shown anywhere; a fixed identity spares devices without one.
+        let env = [
+            ("GIT_AUTHOR_NAME", "ObsyncGit"),
+            ("GIT_AUTHOR_EMAIL", "obsyncgit@localhost"),
+            ("GIT_COMMITTER_NAME", "ObsyncGit"),
+            ("GIT_COMMITTER_EMAIL", "obsyncgit@localhost"),
+        ];
+        let commit =
+            self.run_git_with(&["commit-tree", tree.trim(), "-F", "-"], &env, Some(name))?;
+        let refspec = format!("+{}:{refname}", commit.trim());
+        self.run_git(&["push", "--quiet", &self.remote, &refspec], false)?;
+        Ok(())
+    }
+
+    /// Fetches the remote's refs under `prefix`, such as
+    /// `refs/obsyncgit/locks/`, to the same names locally, dropping the
+    /// ones deleted there.
+    pub fn fetch_refs(&self, prefix: &str) -> Result<()> {
+        let refspec = format!("+{prefix}*:{prefix}*");
+        self.run_git(
+            &["fetch", "--quiet", "--prune", &self.remote, &refspec],
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// The local refs under `prefix` and the commit each points at.
+    pub fn list_refs(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        let output = self.run_git(
+            &[
+                "for-each-ref",
+                "--format=%(refname)%09%(objectname)",
+                prefix.trim_end_matches('/'),
+            ],
+            false,
+        )?;
+        Ok(output
+            .stdout
+            .lines()
+            .filter_map(|line| {
+                let (name, commit) = line.split_once('\t')?;
+                Some((name.to_string(), commit.to_string()))
+            })
+            .collect())
+    }
+
+    /// Attaches `text` to `commit` as its note in `refs/notes/<notes_ref>`,
+    /// replacing an earlier note.
+    pub fn add_note(&self, notes_ref: &str, commit: &str, text: &str) -> Result<()> {
+        let notes = format!("--ref={notes_ref}");
+        self.run_git(&["notes", &notes, "add", "-f", "-m", text, commit], true)?;
+        Ok(())
+    }
+
+    /// Fetches the remote's `refs/notes/<notes_ref>` and merges it into the
+    /// local one, keeping both sides' notes of a commit. With `push`, the
+    /// merged notes are pushed back when the remote lacks some of them;
+    /// a push rejected because another device pushed meanwhile is merged
+    /// on the next call.
+    pub fn sync_notes(&self, notes_ref: &str, push: bool) -> Result<()> {
+        let local = format!("refs/notes/{notes_ref}");
+        let fetched = format!("refs/obsyncgit/remote-notes/{notes_ref}");
+        let refspec = format!("+{local}:{fetched}");
+        match self.run_git(&["fetch", "--quiet", &self.remote, &refspec], false) {
+            Ok(_) => {
+                let notes = format!("--ref={notes_ref}");
+                self.run_git(
+                    &[
+                        "notes",
+                        &notes,
+                        "merge",
+                        "--quiet",
+                        "-s",
+                        "cat_sort_uniq",
+                        &fetched,
+                    ],
+                    true,
+                )?;
+            }
+            // Nobody pushed notes yet.
+            Err(err) if format!("{err:#}").contains("couldn't find remote ref") => {
+                let _ = self.run_git(&["update-ref", "-d", &fetched], false);
+            }
+            Err(err) => return Err(err),
+        }
+        if !push {
+            return Ok(());
+        }
+        let resolve = |reference: &str| {
+            self.run_git(&["rev-parse", "--verify", "--quiet", reference], false)
+                .ok()
+                .map(|output| output.stdout.trim().to_string())
+        };
+        let ours = resolve(&local);
+        if ours.is_some() && ours != resolve(&fetched) {
+            self.run_git(&["push", "--quiet", &self.remote, &local], false)?;
+        }
+        Ok(())
+    }
+
+    /// The newest `limit` commits of HEAD, or of those touching `path`,
+    /// with their notes from `refs/notes/<notes_ref>`.
+    pub fn log_with_notes(
+        &self,
+        notes_ref: &str,
+        limit: usize,
+        path: Option<&str>,
+    ) -> Result<Vec<NotedCommit>> {
+        let notes = format!("--notes={notes_ref}");
+        let count = format!("-{limit}");
+        let mut args = vec![
+            "log",
+            notes.as_str(),
+            count.as_str(),
+            "--format=%H%x1f%cI%x1f%an%x1f%s%x1f%N%x1e",
+        ];
+        if let Some(path) = path {
+            args.extend(["--", path]);
+        }
+        let output = self.run_git(&args, false)?;
+        Ok(output
+            .stdout
+            .split('\x1e')
+            .filter_map(|record| {
+                let mut fields = record.trim_start_matches('\n').split('\x1f');
+                Some(NotedCommit {
+                    commit: fields
+                        .next()
+                        .filter(|commit| !commit.is_empty())?
+                        .to_string(),
+                    date: fields.next()?.to_string(),
+                    author: fields.next()?.to_string(),
+                    subject: fields.next()?.to_string(),
+                    note: fields.next()?.trim().to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// The commit the remote tracking branch points at, if it exists.
+    pub fn tracking_tip(&self) -> Option<String> {
+        let tracking = format!("refs/remotes/{}/{}", self.remote, self.branch);
+        self.run_git(&["rev-parse", "--verify", "--quiet", &tracking], false)
+            .ok()
+            .map(|output| output.stdout.trim().to_string())
+    }
+
+    /// Whether the remote branch was rewritten, as by
+    /// `obsyncgit prune-history`, since the tracking branch pointed at
+    /// `before`: the old tip is no longer part of it.
+    fn history_rewritten(&self, before: Option<&str>) -> bool {
+        let (Some(before), Some(after)) = (before, self.tracking_tip()) else {
+            return false;
+        };
+        before != after
+            && self
+                .command(&["merge-base", "--is-ancestor", before, &after], false)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.code() == Some(1))
+    }
+
+    /// The commit HEAD points at.
+    pub fn head_commit(&self) -> Result<String> {
+        let output = self.run_git(&["rev-parse", "HEAD"], false)?;
+        Ok(output.stdout.trim().to_string())
+    }
+
+    /// HEAD's author date as Unix seconds, and whether HEAD has exactly one
+    /// parent.
+    pub fn head_authored(&self) -> Result<(i64, bool)> {
+        let output = self.run_git(&["log", "-1", "--format=%at %P", "HEAD"], false)?;
+        let mut fields = output.stdout.split_whitespace();
+        let authored = fields
+            .next()
+            .and_then(|time| time.parse().ok())
+            .context("unexpected git log output")?;
+        Ok((authored, fields.count() == 1))
+    }
+
+    /// Remote-tracking branches that contain HEAD, e.g. `origin/main`.
+    pub fn remote_branches_containing_head(&self) -> Result<Vec<String>> {
+        let output = self.run_git(
+            &[
+                "branch",
+                "--remotes",
+                "--contains",
+                "HEAD",
+                "--format=%(refname:short)",
+            ],
+            false,
+        )?;
+        Ok(non_empty_lines(&output.stdout))
+    }
+
+    /// Paths that HEAD with the staged changes folded in would change.
+    pub fn staged_files_since_parent(&self) -> Result<Vec<String>> {
+        let output = self.run_git(&["diff", "--cached", "--name-only", "HEAD~1"], false)?;
+        Ok(non_empty_lines(&output.stdout))
+    }
+
+    /// Folds the staged changes into HEAD under `message`. The author date
+    /// stays that of the original commit.
+    pub fn amend(&self, message: &str) -> Result<()> {
+        self.ensure_disk_space()?;
+        let message = self.with_trailers(message)?;
+        self.run_commit(&["commit", "--amend", "-m", &message])?;
+        Ok(())
+    }
+
+    pub fn pull_rebase(&self) -> Result<()> {
+        self.ensure_disk_space()?;
+        let before = self.tracking_tip();
+        // A resolved conflict is a merge commit. Rebasing would flatten it
+        // and run into the same conflict again, so such history is merged.
+        let merging = self.has_local_merges();
+        if merging {
+            // Merging rewritten history into the old one would bring back
+            // everything the rewrite dropped, twice over.
+            self.run_git(&["fetch", &self.remote, &self.branch], false)?;
+            if self.history_rewritten(before.as_deref()) {
+                bail!(
+                    "the remote's history was rewritten (obsyncgit prune-history) while this device had unpushed merges; run `obsyncgit repair --force` to clone it again, keeping local changes"
+                );
+            }
+        }
+        let autostash = self.ensure_autostash()?;
+        let mode: &[&str] = if merging {
+            &["--no-rebase", "--no-edit"]
+        } else {
+            &["--rebase"]
+        };
+        let mut args = vec!["pull"];
+        args.extend_from_slice(mode);
+        if merging && self.signoff {
+            args.push("--signoff");
+        }
+        if merging && !self.git_options.run_hooks {
+            args.push("--no-verify");
+        }
+        args.extend([self.remote.as_str(), self.branch.as_str()]);
+        // Rebasing rewrites commits, so it needs the committer identity too.
+        let result = self.run_git(&args, true);
+
+        match result {
+            Ok(_) => {
+                if let Some(stash) = autostash {
+                    self.pop_stash(&stash);
+                }
+                self.update_submodules();
+                // The rebase replayed only this device's commits, found by
+                // the tracking branch's reflog; the old history is let go.
+                if self.history_rewritten(before.as_deref()) {
+                    self.reclaim_rewritten();
+                }
+                Ok(())
+            }
+            Err(err) if merging => {
+                warn!(?err, "git pull failed, attempting to abort merge");
+                let _ = self.run_git(&["merge", "--abort"], false);
+                if let Some(stash) = autostash {
+                    self.pop_stash(&stash);
+                }
+                Err(err)
+            }
+            Err(err) => {
+                warn!(?err, "git pull --rebase failed, attempting to abort rebase");
+                let _ = self.run_git(&["rebase", "--abort"], false);
+                if let Some(stash) = autostash {
+                    self.pop_stash(&stash);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Whether HEAD has merge commits the remote branch does not, as made
+    /// by resolving conflicts.
+    fn has_local_merges(&self) -> bool {
+        let range = format!("{}/{}..HEAD", self.remote, self.branch);
+        self.run_git(&["rev-list", "--merges", "--count", &range], false)
+            .is_ok_and(|output| output.stdout.trim().parse::<u64>().unwrap_or(0) > 0)
+    }
+
+    /// Remote tracking refs (`<remote>/device/...`) of all device branches.
+    pub fn remote_device_branches(&self) -> Result<Vec<String>> {
+        let pattern = format!("refs/remotes/{}/device/", self.remote);
+        let output = self.run_git(
+            &["for-each-ref", "--format=%(refname:short)", &pattern],
+            false,
+        )?;
+        Ok(non_empty_lines(&output.stdout))
+    }
+
+    /// Whether `reference` has commits that HEAD does not.
+    pub fn has_commits_from(&self, reference: &str) -> Result<bool> {
+        let range = format!("HEAD..{reference}");
+        let output = self.run_git(&["rev-list", "--count", &range], false)?;
+        Ok(output.stdout.trim().parse::<u64>().unwrap_or(0) > 0)
+    }
+
+    /// Merges `reference` into HEAD. A conflicting merge is aborted and
+    /// reported as an error, leaving HEAD unchanged.
+    pub fn merge(&self, reference: &str) -> Result<()> {
+        let autostash = self.ensure_autostash()?;
+        let mut args = vec!["merge", "--no-edit"];
+        if self.signoff {
+            args.push("--signoff");
+        }
+        if !self.git_options.run_hooks {
+            args.push("--no-verify");
+        }
+        args.push(reference);
+        let result = self.run_git(&args, true);
+        if let Err(err) = &result {
+            warn!(
+                ?err,
+                reference, "git merge failed, attempting to abort merge"
+            );
+            let _ = self.run_git(&["merge", "--abort"], false);
+        }
+        if let Some(stash) = autostash {
+            self.pop_stash(&stash);
+        }
+        if result.is_ok() {
+            self.update_submodules();
+        }
+        result.map(|_| ())
+    }
+
+    /// Paths that would conflict if `reference` were merged into HEAD,
+    /// worked out without touching the working tree (needs git 2.38).
+    pub fn merge_conflicts(&self, reference: &str) -> Result<Vec<String>> {
+        Ok(self.merge_tree(reference)?.1)
+    }
+
+    /// Tree of merging `reference` into HEAD, with conflict markers in the
+    /// conflicting files, and the conflicting paths.
+    fn merge_tree(&self, reference: &str) -> Result<(String, Vec<String>)> {
+        let args = [
+            "merge-tree",
+            "--write-tree",
+            "--name-only",
+            "--no-messages",
+            "HEAD",
+            reference,
+        ];
+        let output = self
+            .command(&args, false)
+            .output()
+            .with_context(|| format!("failed to execute git command: git {}", join_args(&args)))?;
+        // Exit code 1 means the merge has conflicts, not that git failed.
+        if !matches!(output.status.code(), Some(0 | 1)) {
+            bail!(
+                "git {} failed: {}",
+                join_args(&args),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = non_empty_lines(&stdout).into_iter();
+        let tree = lines
+            .next()
+            .context("git merge-tree did not print a tree")?;
+        Ok((tree, lines.collect()))
+    }
+
+    /// `<mode> <object>` of `path` in `commit`, or `None` if it does not
+    /// exist there.
+    pub fn tree_entry(&self, commit: &str, path: &str) -> Result<Option<(String, String)>> {
+        let output = self.run_git(&["ls-tree", commit, "--", path], false)?;
+        Ok(output.stdout.lines().find_map(|line| {
+            let (meta, _) = line.split_once('\t')?;
+            let mut fields = meta.split_whitespace();
+            let mode = fields.next()?;
+            let object = fields.nth(1)?;
+            Some((mode.to_string(), object.to_string()))
+        }))
+    }
+
+    /// Content of `path` in `commit`, or `None` if it does not exist there.
+    pub fn show_file(&self, commit: &str, path: &str) -> Result<Option<String>> {
+        if !self.file_exists_at(commit, path) {
+            return Ok(None);
+        }
+        let object = format!("{commit}:{path}");
+        Ok(Some(
+            self.run_git(&["cat-file", "blob", &object], false)?.stdout,
+        ))
+    }
+
+    /// Merges `reference` into HEAD with `entries` (path and
+    /// `<mode> <object>`, `None` to delete) in place of the conflicting
+    /// files. The merge commit is built in a scratch index and only then
+    /// fast-forwarded onto, so a daemon syncing the vault meanwhile never
+    /// sees a half-merged working tree.
+    pub fn merge_resolved(
+        &self,
+        reference: &str,
+        entries: &[(String, Option<(String, String)>)],
+        message: &str,
+    ) -> Result<()> {
+        let (tree, _) = self.merge_tree(reference)?;
+        let index = self.git_dir()?.join("obsyncgit-resolve.index");
+        let message = self.with_trailers(message)?;
+        let result = self.build_resolved_commit(&index, &tree, reference, entries, &message);
+        let _ = std::fs::remove_file(&index);
+        let commit = result?;
+        self.run_git(&["merge", "--ff-only", &commit], false)?;
+        Ok(())
+    }
+
+    fn build_resolved_commit(
+        &self,
+        index: &Path,
+        tree: &str,
+        reference: &str,
+        entries: &[(String, Option<(String, String)>)],
+        message: &str,
+    ) -> Result<String> {
+        let run = |args: &[&str], author: bool| -> Result<String> {
+            let output = self
+                .command(args, author)
+                .env("GIT_INDEX_FILE", index)
+                .output()
+                .with_context(|| {
+                    format!("failed to execute git command: git {}", join_args(args))
+                })?;
+            if !output.status.success() {
+                bail!(
+                    "git {} failed: {}",
+                    join_args(args),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        run(&["read-tree", tree], false)?;
+        for (path, entry) in entries {
+            match entry {
+                Some((mode, object)) => {
+                    let info = format!("{mode},{object},{path}");
+                    run(&["update-index", "--add", "--cacheinfo", &info], false)?;
+                }
+                None => {
+                    run(&["update-index", "--force-remove", "--", path], false)?;
+                }
+            }
+        }
+        let resolved = run(&["write-tree"], false)?;
+        run(
+            &[
+                "commit-tree",
+                &resolved,
+                "-p",
+                "HEAD",
+                "-p",
+                reference,
+                "-m",
+                message,
+            ],
+            true,
+        )
+    }
+
+    /// Whether the vault's repository exists.
+    /// Whether the vault has been cloned.
+    pub fn has_repo(&self) -> bool {
+        match &self.separate_git_dir {
+            Some(git_dir) => git_dir.join("HEAD").exists(),
+            None => self.repo_path.join(".git").exists(),
+        }
+    }
+
+    /// Whether both the repository and the vault's files are reachable.
+    /// With a separate git dir an empty vault folder is taken to be a mount
+    /// point whose drive is gone.
+    pub fn is_present(&self) -> bool {
+        if self.separate_git_dir.is_none() {
+            return self.has_repo();
+        }
+        self.has_repo()
+            && std::fs::read_dir(&self.repo_path).is_ok_and(|mut entries| entries.next().is_some())
+    }
+
+    /// Environment that points git at the repository when it lives outside
+    /// the vault, for commands run on the vault by other tools.
+    pub fn repo_env(&self) -> Vec<(&'static str, &Path)> {
+        match &self.separate_git_dir {
+            Some(git_dir) => vec![
+                ("GIT_DIR", git_dir.as_path()),
+                ("GIT_WORK_TREE", self.repo_path.as_path()),
+            ],
+            None => Vec::new(),
+        }
+    }
+
+    /// `git.separate_git_dir`, when the repository lives outside the vault.
+    pub fn separate_git_dir(&self) -> Option<&Path> {
+        self.separate_git_dir.as_deref()
+    }
+
+    /// Runs `git fsck` over every object and returns what it found wrong,
+    /// one line per problem; empty for a healthy repository.
+    pub fn fsck(&self) -> Result<Vec<String>> {
+        let output = self
+            .command(&["fsck", "--no-progress", "--no-dangling"], false)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .context("failed to execute git fsck")?;
+        if output.status.success() {
+            return Ok(Vec::new());
+        }
+        let mut problems = non_empty_lines(&String::from_utf8_lossy(&output.stdout));
+        problems.extend(non_empty_lines(&String::from_utf8_lossy(&output.stderr)));
+        if problems.is_empty() {
+            problems.push(format!("git fsck failed with {}", output.status));
+        }
+        Ok(problems)
+    }
+
+    /// Writes every branch and tag into a `git bundle` at `path` and reads
+    /// it back, so a bundle that cannot be cloned from is never kept.
+    pub fn bundle(&self, path: &Path) -> Result<()> {
+        let path = path.to_str().context("bundle path is not valid UTF-8")?;
+        self.run_git(&["bundle", "create", path, "--branches", "--tags"], false)
+            .context("failed to write the bundle")?;
+        self.run_git(&["bundle", "verify", "--quiet", path], false)
+            .context("the written bundle does not verify")?;
+        Ok(())
+    }
+
+    /// The vault's own git directory, holding its index, HEAD and any
+    /// rebase or merge state. That is `.git` itself, except in a linked
+    /// worktree (or a submodule), where `.git` is a file pointing elsewhere,
+    /// e.g. at `<main>/.git/worktrees/<name>`.
+    pub fn git_dir(&self) -> Result<PathBuf> {
+        if let Some(git_dir) = &self.separate_git_dir {
+            return Ok(git_dir.clone());
+        }
+        let dot_git = self.repo_path.join(".git");
+        if dot_git.is_dir() {
+            return Ok(dot_git);
+        }
+        let output = self.run_git(&["rev-parse", "--absolute-git-dir"], false)?;
+        Ok(PathBuf::from(output.stdout.trim()))
+    }
+
+    /// Root of the main working tree when the vault is a linked worktree
+    /// (`git worktree add`) of a repository checked out elsewhere.
+    pub fn main_worktree(&self) -> Result<Option<PathBuf>> {
+        if !self.repo_path.join(".git").is_file() {
+            return Ok(None);
+        }
+        let output = self.run_git(
+            &["rev-parse", "--path-format=absolute", "--git-common-dir"],
+            false,
+        )?;
+        let common = PathBuf::from(output.stdout.trim());
+        if common == self.git_dir()? {
+            // A submodule: `.git` is a file but there is no other worktree.
+            return Ok(None);
+        }
+        Ok(common.parent().map(Path::to_path_buf))
+    }
+
+    /// Writes `patterns` into `.git/info/exclude`, which applies to this
+    /// clone only, replacing what an earlier call wrote there.
+    pub fn set_local_excludes(&self, patterns: &[&str]) -> Result<()> {
+        self.write_managed_block("info/exclude", patterns)
+    }
+
+    /// Writes `lines` into `.git/info/attributes`, which applies to this
+    /// clone only and wins over `.gitattributes` files in the vault.
+    pub fn set_local_attributes(&self, lines: &[&str]) -> Result<()> {
+        self.write_managed_block("info/attributes", lines)
+    }
+
+    /// Writes `lines` into the vault's own `.gitattributes`, which every
+    /// device checks out, replacing what an earlier call wrote there.
+    /// Returns whether the file changed.
+    pub fn set_vault_attributes(&self, lines: &[&str]) -> Result<bool> {
+        write_managed_block(&self.repo_path.join(".gitattributes"), lines)
+    }
+
+    /// Whether the vault's `.gitattributes` already holds `lines` as
+    /// [`Self::set_vault_attributes`] writes them.
+    pub fn has_vault_attributes(&self, lines: &[&str]) -> bool {
+        let existing =
+            std::fs::read_to_string(self.repo_path.join(".gitattributes")).unwrap_or_default();
+        with_managed_block(&existing, lines) == existing
+    }
+
+    /// Replaces the block between our markers in the file at `git_path`
+    /// (relative to the git directory) with `patterns`.
+    fn write_managed_block(&self, git_path: &str, patterns: &[&str]) -> Result<()> {
+        let output = self.run_git(&["rev-parse", "--git-path", git_path], false)?;
+        write_managed_block(&self.repo_path.join(output.stdout.trim()), patterns)?;
+        Ok(())
+    }
+
+    /// Whether a rebase was left half-done, e.g. by a crash mid-pull.
+    pub fn rebase_in_progress(&self) -> bool {
+        self.git_dir().is_ok_and(|git_dir| {
+            git_dir.join("rebase-merge").exists() || git_dir.join("rebase-apply").exists()
+        })
+    }
+
+    /// Returns to the commit the interrupted rebase started from. Falls back
+    /// to `--quit`, which only drops the rebase state, when git can no
+    /// longer abort it.
+    pub fn abort_rebase(&self) -> Result<()> {
+        if let Err(err) = self.run_git(&["rebase", "--abort"], false) {
+            warn!(?err, "git rebase --abort failed, dropping the rebase state");
+            self.run_git(&["rebase", "--quit"], false)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a merge was left unfinished (`MERGE_HEAD` still exists).
+    pub fn merge_in_progress(&self) -> bool {
+        self.git_dir()
+            .is_ok_and(|git_dir| git_dir.join("MERGE_HEAD").exists())
+    }
+
+    pub fn abort_merge(&self) -> Result<()> {
+        self.run_git(&["merge", "--abort"], false)?;
+        Ok(())
+    }
+
+    /// Fast-forwards to the remote branch without creating any commits.
+    /// Fails if the branches diverged or local edits would be overwritten.
+    pub fn pull_fast_forward(&self) -> Result<()> {
+        let before = self.tracking_tip();
+        self.run_git(&["fetch", &self.remote, &self.branch], false)?;
+        if self.history_rewritten(before.as_deref())
+            && before.is_some_and(|before| self.head_commit().is_ok_and(|head| head == before))
+        {
+            // Nothing of this device's own is lost by following the
+            // rewritten branch.
+            let tracking = format!("{}/{}", self.remote, self.branch);
+            self.run_git(&["reset", "--keep", &tracking], false)?;
+            self.reclaim_rewritten();
+        } else {
+            self.run_git(&["pull", "--ff-only", &self.remote, &self.branch], false)?;
         }
+        self.update_submodules();
+        Ok(())
+    }
 
-        if let Err(err) = self.run_git(&["checkout", &self.branch], false) {
-            debug!(
+    /// Lets go of the history a rewrite of the remote branch dropped. Only
+    /// the tracking branch's reflog is cleared: this device's own reflogs
+    /// and stash entries may still need the old commits, so git's regular
+    /// garbage collection deletes them once those expire.
+    fn reclaim_rewritten(&self) {
+        info!("the remote's history was rewritten; git will delete the old history here in time");
+        let tracking = format!("refs/remotes/{}/{}", self.remote, self.branch);
+        if let Err(err) = self.run_git(&["reflog", "expire", "--expire=now", &tracking], false) {
+            warn!(
                 ?err,
-                "branch checkout failed, attempting to create tracking branch"
+                "failed to forget the old history of the tracking branch"
             );
-            let remote_ref = format!("{}/{}", self.remote, self.branch);
-            self.run_git(&["checkout", "-b", &self.branch, &remote_ref], false)
-                .context("failed to create tracking branch")?;
         }
-        Ok(())
     }
 
-    pub fn list_changed_files(&self) -> Result<Vec<String>> {
-        let status = self.run_git(&["status", "--short"], false)?;
-        let mut files = Vec::new();
-        for line in status.stdout.lines() {
-            if line.trim().is_empty() {
-                continue;
+    /// Whether HEAD contains commits that the remote tracking branch does not.
+    pub fn has_unpushed_commits(&self) -> Result<bool> {
+        self.is_ahead_of(&self.branch)
+    }
+
+    /// Whether HEAD contains commits that the remote tracking branch for
+    /// `branch` does not. A missing tracking branch counts as behind.
+    pub fn is_ahead_of(&self, branch: &str) -> Result<bool> {
+        let range = format!("{}/{}..HEAD", self.remote, branch);
+        match self.run_git(&["rev-list", "--count", &range], false) {
+            Ok(output) => Ok(output.stdout.trim().parse::<u64>().unwrap_or(0) > 0),
+            Err(err) => {
+                debug!(
+                    ?err,
+                    "remote tracking branch unavailable, assuming unpushed commits"
+                );
+                Ok(true)
             }
-            let payload = if line.len() > 3 { &line[3..] } else { line };
-            let path = if let Some(pos) = payload.rfind(" -> ") {
-                &payload[pos + 4..]
-            } else {
-                payload
-            };
-            files.push(path.trim().to_string());
         }
-        Ok(files)
     }
 
-    pub fn stage_all(&self) -> Result<()> {
-        self.run_git(&["add", "-A"], false)?;
-        Ok(())
+    /// Paths touched by commits that have not reached the remote yet.
+    pub fn unpushed_paths(&self) -> Result<Vec<String>> {
+        // Three dots: only what HEAD changed since it forked from upstream.
+        let range = format!("{}/{}...HEAD", self.remote, self.branch);
+        let output = self.run_git(&["diff", "--name-only", &range], false)?;
+        Ok(output
+            .stdout
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.to_string())
+            .collect())
     }
 
-    fn worktree_status(&self) -> Result<String> {
-        let status = self.run_git(&["status", "--porcelain"], false)?;
-        Ok(status.stdout)
+    /// Paths that differ between `commit` and HEAD, whichever side changed
+    /// them.
+    pub fn changed_paths_since(&self, commit: &str) -> Result<Vec<String>> {
+        let output = self.run_git(&["diff", "--name-only", commit, "HEAD", "--"], false)?;
+        Ok(non_empty_lines(&output.stdout))
     }
 
-    fn is_worktree_clean(&self) -> Result<bool> {
-        Ok(self.worktree_status()?.trim().is_empty())
+    /// Files that differ between HEAD and the remote tracking branch, so
+    /// the next pull may write them.
+    pub fn changed_upstream(&self) -> Result<Vec<String>> {
+        let tracking = format!("refs/remotes/{}/{}", self.remote, self.branch);
+        let output = self.run_git(&["diff", "--name-only", "HEAD", &tracking, "--"], false)?;
+        Ok(non_empty_lines(&output.stdout))
     }
 
-    fn ensure_autostash(&self) -> Result<Option<String>> {
-        if self.is_worktree_clean()? {
-            return Ok(None);
+    /// Commits that touched `path` (vault-relative), newest first.
+    pub fn file_history(&self, path: &str, limit: usize) -> Result<Vec<FileRevision>> {
+        let limit = format!("--max-count={limit}");
+        let output = self.run_git(
+            &[
+                "log",
+                &limit,
+                "--format=%x1e%H%x1f%aI%x1f%s",
+                "--name-status",
+                "--",
+                path,
+            ],
+            false,
+        )?;
+        let revisions = output
+            .stdout
+            .split('\x1e')
+            .filter_map(|record| {
+                let mut lines = record.lines();
+                let mut header = lines.next()?.split('\x1f');
+                let commit = header.next()?.to_string();
+                let date = header.next()?.to_string();
+                let summary = header.next().unwrap_or_default().to_string();
+                let change = lines
+                    .find_map(|line| line.chars().next().filter(|ch| ch.is_ascii_uppercase()))
+                    .unwrap_or('M');
+                Some(FileRevision {
+                    commit,
+                    date,
+                    summary,
+                    change,
+                })
+            })
+            .collect();
+        Ok(revisions)
+    }
+
+    /// The newest commits on the current branch, newest first. Renames are
+    /// reported as a deletion plus an addition so every path can be
+    /// restored on its own.
+    pub fn recent_commits(&self, limit: usize) -> Result<Vec<CommitSummary>> {
+        let limit = format!("--max-count={limit}");
+        let output = self.run_git(
+            &[
+                "log",
+                &limit,
+                "--format=%x1e%H%x1f%aI%x1f%s",
+                "--name-status",
+                "--no-renames",
+            ],
+            false,
+        )?;
+        let commits = output
+            .stdout
+            .split('\x1e')
+            .filter_map(|record| {
+                let mut lines = record.lines();
+                let mut header = lines.next()?.split('\x1f');
+                let commit = header.next()?.to_string();
+                let date = header.next()?.to_string();
+                let summary = header.next().unwrap_or_default().to_string();
+                let files = lines
+                    .filter_map(|line| {
+                        let (status, path) = line.split_once('\t')?;
+                        Some((status.chars().next()?, path.to_string()))
+                    })
+                    .collect();
+                Some(CommitSummary {
+                    commit,
+                    date,
+                    summary,
+                    files,
+                })
+            })
+            .collect();
+        Ok(commits)
+    }
+
+    /// Resolves the commit to restore `path` from: the state of the branch
+    /// at `at` (any date git understands), or the newest version that still
+    /// contained the file.
+    pub fn restore_point(&self, path: &str, at: Option<&str>) -> Result<String> {
+        let commit = match at {
+            Some(date) => self.commit_before(date)?,
+            None => {
+                let output = self.run_git(&["rev-list", "-1", "HEAD", "--", path], false)?;
+                let commit = output.stdout.trim().to_string();
+                if commit.is_empty() {
+                    bail!("{path} has no history in this vault");
+                }
+                if self.file_exists_at(&commit, path) {
+                    commit
+                } else {
+                    // The newest commit touching the file deleted it.
+                    format!("{commit}^")
+                }
+            }
+        };
+        if !self.file_exists_at(&commit, path) {
+            bail!("{path} did not exist at that point in history");
         }
+        let output = self.run_git(&["rev-parse", "--verify", &commit], false)?;
+        Ok(output.stdout.trim().to_string())
+    }
 
-        const STASH_MESSAGE: &str = "obsyncgit-autostash";
+    /// The newest commit of the branch made before `date`.
+    fn commit_before(&self, date: &str) -> Result<String> {
+        let before = format!("--before={date}");
+        let output = self.run_git(&["rev-list", "-1", &before, "HEAD"], false)?;
+        let commit = output.stdout.trim().to_string();
+        if commit.is_empty() {
+            bail!("the vault has no history before {date}");
+        }
+        Ok(commit)
+    }
 
-        self.run_git(
+    /// Resolves `at` to a full commit id: a commit or ref name, otherwise
+    /// the state of the branch at that time. `None` is `HEAD`.
+    pub fn resolve_commit(&self, at: Option<&str>) -> Result<String> {
+        let at = at.unwrap_or("HEAD");
+        let object = format!("{at}^{{commit}}");
+        if let Ok(output) = self.run_git(&["rev-parse", "--verify", "--quiet", &object], false) {
+            return Ok(output.stdout.trim().to_string());
+        }
+        self.commit_before(at)
+    }
+
+    /// Writes the files of `commit` to `output` as a `format` archive (any
+    /// format `git archive` knows, such as `zip` or `tar.gz`), each path
+    /// under `prefix`. Notes are exported as committed: lines kept back by
+    /// `security.redaction` are not put back.
+    pub fn archive(&self, commit: &str, format: &str, prefix: &str, output: &Path) -> Result<()> {
+        let smudge = format!("filter.{}.smudge=cat", crate::redaction::FILTER_NAME);
+        let format = format!("--format={format}");
+        let prefix = format!("--prefix={prefix}");
+        let output_arg = format!("--output={}", output.display());
+        let result = self.run_git(
             &[
-                "stash",
-                "push",
-                "--include-untracked",
-                "--message",
-                STASH_MESSAGE,
+                "-c",
+                &smudge,
+                "archive",
+                &format,
+                &prefix,
+                &output_arg,
+                commit,
             ],
             false,
-        )
-        .context("failed to stash local changes before pull --rebase")?;
+        );
+        if result.is_err() {
+            let _ = fs::remove_file(output);
+        }
+        result.map(|_| ())
+    }
 
-        let list = self
-            .run_git(&["stash", "list", "--format=%gd:%gs"], false)
-            .context("failed to inspect git stash after push")?;
+    fn file_exists_at(&self, commit: &str, path: &str) -> bool {
+        let object = format!("{commit}:{path}");
+        self.run_git(&["cat-file", "-e", &object], false).is_ok()
+    }
+
+    /// Overwrites the working copy of `path` with its content at `commit`.
+    pub fn restore_file(&self, path: &str, commit: &str) -> Result<()> {
+        if !self.file_exists_at(commit, path) {
+            bail!("{path} does not exist in commit {commit}");
+        }
+        self.run_git(&["checkout", commit, "--", path], false)?;
+        Ok(())
+    }
 
-        for line in list.stdout.lines() {
-            if let Some((stash_ref, message)) = line.split_once(':')
-                && message.trim() == STASH_MESSAGE
+    /// Turns an absolute, working-directory-relative or vault-relative path
+    /// into the slash-separated vault-relative form git expects.
+    pub fn vault_relative(&self, path: &Path) -> Result<String> {
+        let root = self
+            .repo_path
+            .canonicalize()
+            .unwrap_or_else(|_| self.repo_path.clone());
+        let candidates = if path.is_absolute() {
+            vec![path.to_path_buf()]
+        } else {
+            std::env::current_dir()
+                .map(|cwd| vec![cwd.join(path)])
+                .unwrap_or_default()
+        };
+        for candidate in candidates {
+            let absolute = candidate
+                .parent()
+                .and_then(|parent| parent.canonicalize().ok())
+                .zip(candidate.file_name())
+                .map(|(parent, name)| parent.join(name))
+                .unwrap_or(candidate);
+            if let Ok(relative) = absolute
+                .strip_prefix(&root)
+                .or_else(|_| absolute.strip_prefix(&self.repo_path))
             {
-                return Ok(Some(stash_ref.trim().to_string()));
+                return Ok(to_slash(relative));
             }
         }
+        if path.is_absolute() {
+            bail!(
+                "{} is outside the vault at {}",
+                path.display(),
+                self.repo_path.display()
+            );
+        }
+        Ok(to_slash(path))
+    }
 
-        // Fallback: assume newest stash (stash@{0}) belongs to us.
-        Ok(Some("stash@{0}".to_string()))
+    pub fn push(&self) -> Result<()> {
+        self.run_git(&["push", &self.remote, &self.branch], false)?;
+        Ok(())
     }
 
-    fn pop_stash(&self, stash_ref: &str) {
-        if let Err(err) = self.run_git(&["stash", "pop", stash_ref], false) {
-            warn!(?err, "failed to restore stash after pull --rebase");
-        }
+    /// Pushes HEAD to `branch` on the remote.
+    pub fn push_head_to(&self, branch: &str) -> Result<()> {
+        let refspec = format!("HEAD:refs/heads/{branch}");
+        self.run_git(&["push", &self.remote, &refspec], false)?;
+        Ok(())
     }
 
-    pub fn commit(&self, message: &str) -> Result<bool> {
-        let status = self.run_git(&["status", "--short"], false)?;
-        if status.stdout.trim().is_empty() {
-            return Ok(false);
-        }
-        self.run_git(&["commit", "-m", message], true)?;
-        Ok(true)
+    /// Pushes HEAD to `branch` on the remote, replacing what is there as
+    /// long as it is still what the last fetch or push saw.
+    pub fn force_push_head_to(&self, branch: &str) -> Result<()> {
+        let refspec = format!("HEAD:refs/heads/{branch}");
+        let lease = format!("--force-with-lease=refs/heads/{branch}");
+        self.run_git(&["push", &lease, &self.remote, &refspec], false)?;
+        Ok(())
     }
 
-    pub fn pull_rebase(&self) -> Result<()> {
-        let autostash = self.ensure_autostash()?;
-        let result = self.run_git(&["pull", "--rebase", &self.remote, &self.branch], false);
+    /// Deletes `branch` on the remote.
+    pub fn delete_remote_branch(&self, branch: &str) -> Result<()> {
+        let refspec = format!(":refs/heads/{branch}");
+        self.run_git(&["push", &self.remote, &refspec], false)?;
+        Ok(())
+    }
 
-        match result {
-            Ok(_) => {
-                if let Some(stash_ref) = autostash {
-                    self.pop_stash(&stash_ref);
+    /// The commit `branch` points at, asked of the remote itself rather
+    /// than read from the last fetch. `None` if the branch does not exist.
+    pub fn remote_branch_tip(&self, branch: &str) -> Result<Option<String>> {
+        let reference = format!("refs/heads/{branch}");
+        let output = self.run_git(&["ls-remote", &self.remote, &reference], false)?;
+        Ok(output.stdout.split_whitespace().next().map(str::to_string))
+    }
+
+    /// `git push` to a bundle remote: the remote's branches and tags, with
+    /// the pushed ref updated, are written into a new bundle that replaces
+    /// the remote one. Refuses what git would refuse, a push that is not a
+    /// fast-forward.
+    fn push_bundle(&self, bundle: &BundleRemote, args: &[&str]) -> Result<CommandOutput> {
+        let operands: Vec<&str> = args[1..]
+            .iter()
+            .copied()
+            .filter(|arg| !arg.starts_with('-'))
+            .collect();
+        let [_, refspec] = operands[..] else {
+            bail!(
+                "git {} is not supported for a bundle remote",
+                join_args(args)
+            );
+        };
+        let (source, target) = refspec.split_once(':').unwrap_or((refspec, refspec));
+        let target = if target.starts_with("refs/") {
+            target.to_string()
+        } else {
+            format!("refs/heads/{target}")
+        };
+
+        bundle.download()?;
+        let mut refs = BTreeMap::new();
+        if bundle.cache().exists() {
+            // Objects of the remote's other branches go into the new
+            // bundle too.
+            self.run_git(&["fetch", "--quiet", &self.remote], false)?;
+            let cache = bundle
+                .cache()
+                .to_str()
+                .context("state directory is not valid UTF-8")?;
+            for line in non_empty_lines(
+                &self
+                    .run_git(&["bundle", "list-heads", cache], false)?
+                    .stdout,
+            ) {
+                if let Some((commit, name)) = line.split_once(' ')
+                    && name.starts_with("refs/")
+                {
+                    refs.insert(name.to_string(), commit.to_string());
                 }
-                Ok(())
             }
-            Err(err) => {
-                warn!(?err, "git pull --rebase failed, attempting to abort rebase");
-                let _ = self.run_git(&["rebase", "--abort"], false);
-                if let Some(stash_ref) = autostash {
-                    self.pop_stash(&stash_ref);
-                }
-                Err(err)
+        }
+        let old = refs.get(&target).cloned();
+        let new = if source.is_empty() {
+            None
+        } else {
+            let spec = format!("{source}^{{commit}}");
+            Some(
+                self.run_git(&["rev-parse", "--verify", &spec], false)?
+                    .stdout
+                    .trim()
+                    .to_string(),
+            )
+        };
+        if old == new {
+            return Ok(CommandOutput {
+                stdout: String::new(),
+                stderr: "Everything up-to-date\n".to_string(),
+            });
+        }
+        if let (Some(old), Some(new)) = (&old, &new)
+            && self
+                .run_git(&["merge-base", "--is-ancestor", old, new], false)
+                .is_err()
+        {
+            return Err(GitError::NonFastForward(format!(
+                " ! [rejected] {source} -> {target} (fetch first)\nUpdates were rejected because the remote contains work that you do not have locally."
+            ))
+            .into());
+        }
+        match &new {
+            Some(new) => refs.insert(target.clone(), new.clone()),
+            None => refs.remove(&target),
+        };
+        if refs.is_empty() {
+            bail!("cannot delete the last branch of a bundle remote");
+        }
+
+        let written = self.write_bundle(bundle, &refs);
+        let upload = written.and_then(|path| {
+            let uploaded = bundle.upload(&path);
+            let _ = fs::remove_file(&path);
+            uploaded
+        });
+        upload?;
+
+        if let Some(name) = target.strip_prefix("refs/heads/") {
+            let tracking = format!("refs/remotes/{}/{name}", self.remote);
+            match &new {
+                Some(new) => self.run_git(&["update-ref", &tracking, new], false)?,
+                None => self.run_git(&["update-ref", "-d", &tracking], false)?,
+            };
+            if args.contains(&"--set-upstream") {
+                let upstream = format!("--set-upstream-to={}/{name}", self.remote);
+                self.run_git(&["branch", &upstream, source], false)?;
             }
         }
+        Ok(CommandOutput {
+            stdout: String::new(),
+            stderr: format!("To {}\n   {source} -> {target}\n", self.remote),
+        })
     }
 
-    pub fn push(&self) -> Result<()> {
-        self.run_git(&["push", &self.remote, &self.branch], false)?;
-        Ok(())
+    /// Writes `refs` (full ref name to commit) into a bundle next to the
+    /// remote's local copy. The refs are set in a scratch repository that
+    /// borrows the vault's objects, so nothing is copied but the bundle.
+    fn write_bundle(
+        &self,
+        bundle: &BundleRemote,
+        refs: &BTreeMap<String, String>,
+    ) -> Result<PathBuf> {
+        let scratch = bundle.cache().with_extension("push");
+        let output = bundle.cache().with_extension("bundle.new");
+        if let Some(parent) = scratch.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let _ = fs::remove_dir_all(&scratch);
+        let scratch_git = |args: &[&str]| -> Result<()> {
+            let output = Command::new(&self.executable)
+                .arg("--git-dir")
+                .arg(&scratch)
+                .args(args)
+                .env_remove("GIT_DIR")
+                .env_remove("GIT_WORK_TREE")
+                .env("LC_ALL", "C")
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .output()
+                .context("failed to execute git")?;
+            if !output.status.success() {
+                bail!(
+                    "git {} failed: {}",
+                    join_args(args),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            Ok(())
+        };
+        let result = (|| {
+            scratch_git(&["init", "--quiet", "--bare"])?;
+            let common_dir = self.run_git(&["rev-parse", "--git-common-dir"], false)?;
+            let objects = self
+                .repo_path
+                .join(common_dir.stdout.trim())
+                .join("objects");
+            fs::write(
+                scratch.join("objects").join("info").join("alternates"),
+                format!("{}\n", objects.display()),
+            )
+            .context("failed to link the vault's objects")?;
+            for (name, commit) in refs {
+                scratch_git(&["update-ref", name, commit])?;
+            }
+            let head = format!("refs/heads/{}", self.branch);
+            if refs.contains_key(&head) {
+                scratch_git(&["symbolic-ref", "HEAD", &head])?;
+            }
+            let path = output
+                .to_str()
+                .context("state directory is not valid UTF-8")?;
+            scratch_git(&["bundle", "create", path, "--all"])
+        })();
+        let _ = fs::remove_dir_all(&scratch);
+        result.map(|()| output)
     }
 
     fn run_git(&self, args: &[&str], include_author_env: bool) -> Result<CommandOutput> {
         self.run_git_inner(args, include_author_env, true)
     }
 
-    fn run_git_inner(
+    /// Runs a local git command with extra environment and, optionally,
+    /// `stdin`; returns its stdout.
+    fn run_git_with(
         &self,
         args: &[&str],
-        include_author_env: bool,
-        allow_retry: bool,
-    ) -> Result<CommandOutput> {
+        env: &[(&str, &str)],
+        stdin: Option<&str>,
+    ) -> Result<String> {
         debug!(cmd = ?args, "running git command");
+        let mut child = self
+            .command(args, false)
+            .envs(env.iter().copied())
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to execute git command: git {}", join_args(args)))?;
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            // Written from a thread: git may fill stdout before reading all
+            // of stdin.
+            let input = input.to_string();
+            std::thread::spawn(move || {
+                use std::io::Write;
+                let _ = pipe.write_all(input.as_bytes());
+            });
+        }
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("failed to execute git command: git {}", join_args(args)))?;
+        if !output.status.success() {
+            return Err(GitError::classify(format!(
+                "git {} failed with code {}: {}",
+                join_args(args),
+                output.status.code().unwrap_or(-1),
+                String::from_utf8_lossy(&output.stderr)
+            ))
+            .into());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// A git invocation in the repository with identity, SSH key and proxy
+    /// settings applied; stdio is left to the caller.
+    fn command(&self, args: &[&str], include_author_env: bool) -> Command {
         let mut cmd = Command::new(&self.executable);
         cmd.current_dir(&self.repo_path)
             .arg("-c")
             .arg("core.quotepath=false")
             .args(args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
             .env("GIT_TERMINAL_PROMPT", "0")
             .env("LC_ALL", "C")
             .env("LANG", "C");
+        if !self.optional_locks {
+            cmd.env("GIT_OPTIONAL_LOCKS", "0");
+        }
+
+        cmd.envs(self.proxy_env.iter().map(|(key, value)| (key, value)));
+        // `clone` and `init` set the repository up themselves via
+        // `--separate-git-dir`.
+        if !matches!(args.first(), Some(&"clone" | &"init")) {
+            cmd.envs(self.repo_env());
+        }
 
         if let Some(key_path) = &self.git_options.ssh_key_path {
             let escaped = key_path.replace('\'', "'\\''");
@@ -278,12 +2833,74 @@ impl GitFacade {
             }
         }
 
-        let output = cmd
-            .output()
-            .with_context(|| format!("failed to execute git command: git {}", join_args(args)))?;
+        cmd
+    }
+
+    fn run_git_inner(
+        &self,
+        args: &[&str],
+        include_author_env: bool,
+        allow_retry: bool,
+    ) -> Result<CommandOutput> {
+        if let Some(bundle) = &self.bundle_remote
+            && talks_to_remote(args)
+            && args.first() != Some(&"submodule")
+        {
+            if args.first() == Some(&"push") {
+                return self.push_bundle(bundle, args);
+            }
+            bundle.download()?;
+            if !bundle.cache().exists() {
+                // Like an empty repository: nothing to fetch or pull.
+                debug!(cmd = ?args, "the remote has no bundle yet, skipping");
+                return Ok(CommandOutput {
+                    stdout: String::new(),
+                    stderr: String::new(),
+                });
+            }
+        }
+        debug!(cmd = ?args, "running git command");
+        let started = Instant::now();
+        let stall = Some(self.git_options.timeout_seconds)
+            .filter(|seconds| *seconds > 0 && talks_to_remote(args))
+            .map(Duration::from_secs);
+        let output = match stall {
+            Some(stall) => match self.output_within(args, include_author_env, stall) {
+                Ok(output) => output,
+                Err(err) => {
+                    if let Some(trace) = &self.trace {
+                        trace.record(
+                            &join_args(args),
+                            started.elapsed(),
+                            -1,
+                            "",
+                            &format!("{err:#}"),
+                        );
+                    }
+                    return Err(err);
+                }
+            },
+            None => self
+                .command(args, include_author_env)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .output()
+                .with_context(|| {
+                    format!("failed to execute git command: git {}", join_args(args))
+                })?,
+        };
 
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if let Some(trace) = &self.trace {
+            trace.record(
+                &join_args(args),
+                started.elapsed(),
+                output.status.code().unwrap_or(-1),
+                &stdout,
+                &stderr,
+            );
+        }
 
         if !stderr.trim().is_empty() {
             debug!(stderr = %stderr.trim(), cmd = %join_args(args), "git stderr");
@@ -307,30 +2924,58 @@ impl GitFacade {
                 }
             }
 
-            return Err(anyhow!(
+            return Err(GitError::classify(format!(
                 "git {} failed with code {}: {}{}",
                 join_args(args),
                 code,
-                if !stderr.is_empty() {
-                    stderr.clone()
-                } else {
-                    String::new()
-                },
+                stderr,
                 if !stdout.is_empty() && stderr.is_empty() {
                     format!(" stdout: {stdout}")
                 } else {
                     String::new()
                 }
-            ));
+            ))
+            .into());
         }
 
         Ok(CommandOutput { stdout, stderr })
     }
 
-    fn clear_stale_index_lock(&self) -> Result<bool> {
+    /// Runs a command that talks to the remote, killing it together with the
+    /// ssh or credential helper processes it started once it goes `stall`
+    /// without any output, as it does when a connection dies without being
+    /// closed. `--progress` keeps a slow but healthy transfer talking.
+    fn output_within(
+        &self,
+        args: &[&str],
+        include_author_env: bool,
+        stall: Duration,
+    ) -> Result<Output> {
+        let mut progress_args = args.to_vec();
+        match args.first() {
+            Some(&"ls-remote") => {}
+            Some(&"submodule") => progress_args.insert(2.min(args.len()), "--progress"),
+            _ => progress_args.insert(1, "--progress"),
+        }
+        let mut cmd = self.command(&progress_args, include_author_env);
+        let mut child = spawn_group(&mut cmd)
+            .with_context(|| format!("failed to execute git command: git {}", join_args(args)))?;
+        match watch_output(&mut child, Some(stall), |_| {})? {
+            Some(mut output) => {
+                output.stderr =
+                    strip_progress(&String::from_utf8_lossy(&output.stderr)).into_bytes();
+                Ok(output)
+            }
+            None => Err(stalled(args, stall)),
+        }
+    }
+
+    /// Removes `.git/index.lock` when no git process touched it for 30
+    /// seconds. Returns whether a lock was removed.
+    pub fn clear_stale_index_lock(&self) -> Result<bool> {
         use std::fs;
 
-        let lock_path = self.repo_path.join(".git/index.lock");
+        let lock_path = self.git_dir()?.join("index.lock");
         if !lock_path.exists() {
             return Ok(false);
         }
@@ -380,6 +3025,351 @@ impl GitFacade {
     }
 }
 
+/// Whether `args` is a git command that contacts the remote and can hang
+/// on a dead connection.
+fn talks_to_remote(args: &[&str]) -> bool {
+    match args.first() {
+        Some(&"push" | &"pull" | &"fetch" | &"ls-remote" | &"clone") => true,
+        Some(&"submodule") => args.get(1) == Some(&"update"),
+        _ => false,
+    }
+}
+
+/// Spawns `cmd` with its output piped and, on Unix, as the leader of a new
+/// process group, so [`kill_tree`] reaches the processes it starts.
+pub(crate) fn spawn_group(cmd: &mut Command) -> std::io::Result<Child> {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(cmd, 0);
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()
+}
+
+/// Collects the output of a child from [`spawn_group`], passing stderr to
+/// `on_stderr` as it arrives. Once the child went `stall` without writing
+/// anything it is killed with its process group and `None` is returned.
+/// Helpers that outlive the child and keep its pipes open are not waited
+/// for.
+pub(crate) fn watch_output(
+    child: &mut Child,
+    stall: Option<Duration>,
+    mut on_stderr: impl FnMut(&[u8]),
+) -> Result<Option<Output>> {
+    let (sender, chunks) = mpsc::channel();
+    let pipes: [(bool, Option<Box<dyn Read + Send>>); 2] = [
+        (false, child.stdout.take().map(|pipe| Box::new(pipe) as _)),
+        (true, child.stderr.take().map(|pipe| Box::new(pipe) as _)),
+    ];
+    for (is_stderr, pipe) in pipes {
+        let Some(mut pipe) = pipe else {
+            continue;
+        };
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            let mut buffer = [0; 8192];
+            while let Ok(read @ 1..) = pipe.read(&mut buffer) {
+                if sender.send((is_stderr, buffer[..read].to_vec())).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(sender);
+
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let mut status = None;
+    let mut exited = None;
+    let mut last_output = Instant::now();
+    loop {
+        match chunks.recv_timeout(Duration::from_millis(50)) {
+            Ok((true, chunk)) => {
+                on_stderr(&chunk);
+                stderr.extend(chunk);
+                last_output = Instant::now();
+                continue;
+            }
+            Ok((false, chunk)) => {
+                stdout.extend(chunk);
+                last_output = Instant::now();
+                continue;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                let status = match status {
+                    Some(status) => status,
+                    None => child.wait().context("failed to wait for git")?,
+                };
+                return Ok(Some(Output {
+                    status,
+                    stdout,
+                    stderr,
+                }));
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+        if status.is_none() {
+            status = child.try_wait().context("failed to wait for git")?;
+            exited = status.map(|_| Instant::now());
+        }
+        if let (Some(status), Some(exited)) = (status, exited) {
+            // Exited, but a helper that escaped the process group may hold
+            // the pipes; what it has not written by now is not waited for.
+            if exited.elapsed() >= EXITED_OUTPUT_GRACE
+                && last_output.elapsed() >= EXITED_OUTPUT_GRACE
+            {
+                return Ok(Some(Output {
+                    status,
+                    stdout,
+                    stderr,
+                }));
+            }
+        } else if stall.is_some_and(|stall| last_output.elapsed() >= stall) {
+            kill_tree(child);
+            return Ok(None);
+        }
+    }
+}
+
+/// The error for a remote command [`watch_output`] gave up on.
+fn stalled(args: &[&str], stall: Duration) -> anyhow::Error {
+    GitError::Network(format!(
+        "git {} sent nothing for {}; the remote stopped responding",
+        join_args(args),
+        humantime::format_duration(stall)
+    ))
+    .into()
+}
+
+/// `stderr` without the meters `--progress` adds, so errors read as they
+/// would without it.
+fn strip_progress(stderr: &str) -> String {
+    let mut kept = Vec::new();
+    for line in stderr.split('\n') {
+        if !line.contains('\r') {
+            if !is_progress(line) {
+                kept.push(line);
+            }
+            continue;
+        }
+        // A meter redraws itself after each `\r`.
+        kept.extend(
+            line.split('\r')
+                .filter(|part| !part.trim().is_empty() && !is_progress(part)),
+        );
+    }
+    kept.join("\n")
+}
+
+/// Whether `line` is one of git's progress messages, such as
+/// `Writing objects:  40% (2/5)` or `Enumerating objects: 5, done.`.
+fn is_progress(line: &str) -> bool {
+    let line = line.strip_prefix("remote:").unwrap_or(line).trim();
+    if CloneProgress::parse(line).is_some()
+        || line.starts_with("Delta compression using up to ")
+        || (line.starts_with("Total ") && line.contains("(delta "))
+    {
+        return true;
+    }
+    line.split_once(':').is_some_and(|(phase, count)| {
+        phase.starts_with(|first: char| first.is_ascii_uppercase())
+            && phase.chars().all(|c| c.is_ascii_alphabetic() || c == ' ')
+            && count
+                .trim()
+                .trim_end_matches(", done.")
+                .parse::<u64>()
+                .is_ok()
+    })
+}
+
+/// Kills `child` and, on Unix, the process group it leads, so helpers such
+/// as ssh go down with it.
+pub(crate) fn kill_tree(child: &mut Child) {
+    let pid = child.id().to_string();
+    let killed = if cfg!(windows) {
+        Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    } else {
+        Command::new("kill")
+            .args(["-KILL", "--", &format!("-{pid}")])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+    };
+    if !killed.is_ok_and(|status| status.success()) {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+}
+
+/// `(major, minor)` from `git version 2.39.5` or `git version 2.45.1.windows.1`.
+fn parse_version(output: &str) -> Option<(u32, u32)> {
+    let mut parts = output.strip_prefix("git version ")?.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Whether `path`, as printed by git, had bytes that are not UTF-8. Such a
+/// file cannot be named in a git command, and macOS and Windows cannot
+/// check it out.
+pub fn is_mangled_path(path: &str) -> bool {
+    path.contains(char::REPLACEMENT_CHARACTER)
+}
+
+fn parse_status_paths(status: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    for line in status.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let payload = if line.len() > 3 { &line[3..] } else { line };
+        let path = if let Some(pos) = payload.rfind(" -> ") {
+            &payload[pos + 4..]
+        } else {
+            payload
+        };
+        files.push(unquote_path(path.trim()));
+    }
+    files
+}
+
+/// Undoes the C-style quoting git applies to paths with spaces, quotes,
+/// backslashes or control characters in `status --porcelain`.
+fn unquote_path(path: &str) -> String {
+    let Some(inner) = path
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    else {
+        return path.to_string();
+    };
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.bytes().peekable();
+    while let Some(byte) = chars.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match chars.next() {
+            Some(b'a') => bytes.push(0x07),
+            Some(b'b') => bytes.push(0x08),
+            Some(b'f') => bytes.push(0x0c),
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'v') => bytes.push(0x0b),
+            // Octal escapes carry the bytes of non-ASCII characters.
+            Some(digit @ b'0'..=b'7') => {
+                let mut value = u32::from(digit - b'0');
+                for _ in 0..2 {
+                    match chars.peek() {
+                        Some(next @ b'0'..=b'7') => {
+                            value = value * 8 + u32::from(next - b'0');
+                            chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            Some(other) => bytes.push(other),
+            None => bytes.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => directories::BaseDirs::new()
+            .map(|dirs| dirs.home_dir().join(rest))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Whether git would run `path` as a hook.
+/// Replaces the block between our markers in the file at `path` with
+/// `patterns`, dropping the block when there are none. Returns whether the
+/// file changed.
+fn write_managed_block(path: &Path, patterns: &[&str]) -> Result<bool> {
+    let existing = std::fs::read_to_string(path).unwrap_or_default();
+    let updated = with_managed_block(&existing, patterns);
+    if updated == existing {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, updated).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(true)
+}
+
+/// `existing` with the block between our markers replaced by `patterns`.
+fn with_managed_block(existing: &str, patterns: &[&str]) -> String {
+    const BEGIN: &str = "# >>> obsyncgit";
+    const END: &str = "# <<< obsyncgit";
+
+    let mut lines = Vec::new();
+    let mut ours = false;
+    for line in existing.lines() {
+        match line {
+            BEGIN => ours = true,
+            END => ours = false,
+            _ if !ours => lines.push(line.to_string()),
+            _ => {}
+        }
+    }
+    if !patterns.is_empty() {
+        lines.push(BEGIN.to_string());
+        lines.extend(patterns.iter().map(|pattern| pattern.to_string()));
+        lines.push(END.to_string());
+    }
+    if lines.is_empty() {
+        String::new()
+    } else {
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Whether two git config values mean the same, such as `false` and `no`.
+fn same_config_value(a: &str, b: &str) -> bool {
+    let boolean = |value: &str| match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    };
+    a == b || boolean(a).is_some_and(|a| boolean(b) == Some(a))
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path)
+            .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+fn non_empty_lines(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn to_slash(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 fn join_args(args: &[&str]) -> String {
     args.iter()
         .map(|arg| {
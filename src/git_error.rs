@@ -0,0 +1,222 @@
+use crate::tr;
+
+/// Failure markers emitted by git/ssh/curl when the remote cannot be reached
+/// at all, as opposed to rejecting the operation (conflicts, auth, hooks).
+const NETWORK_MARKERS: &[&str] = &[
+    "could not resolve host",
+    "could not resolve hostname",
+    "temporary failure in name resolution",
+    "name or service not known",
+    "connection refused",
+    "connection timed out",
+    "connection reset",
+    "operation timed out",
+    "network is unreachable",
+    "no route to host",
+    "failed to connect to",
+    "ssh: connect to host",
+    "the remote end hung up unexpectedly",
+    // Our own timeout, see `git.timeout_seconds`.
+    "the remote stopped responding",
+    "the requested url returned error: 502",
+    "the requested url returned error: 503",
+    "the requested url returned error: 504",
+];
+
+/// Credentials missing, expired or refused by the remote.
+const AUTH_MARKERS: &[&str] = &[
+    "permission denied (publickey",
+    "authentication failed",
+    "invalid username or password",
+    "invalid credentials",
+    "could not read username",
+    "could not read password",
+    "terminal prompts disabled",
+    "the requested url returned error: 401",
+    "the requested url returned error: 403",
+];
+
+/// ssh refused a host whose key differs from the pinned one: a reinstalled
+/// server, or someone in the middle.
+const HOST_KEY_CHANGED_MARKERS: &[&str] = &[
+    "remote host identification has changed",
+    "has changed and you have requested strict checking",
+];
+
+/// ssh refused a host it has no key for, since nobody can answer its
+/// question whether to trust it.
+const UNKNOWN_HOST_MARKERS: &[&str] = &["host key verification failed", "host key is known for"];
+
+/// Pushes refused because the remote branch has commits we lack.
+const NON_FAST_FORWARD_MARKERS: &[&str] = &[
+    "non-fast-forward",
+    "fetch first",
+    "updates were rejected because",
+];
+
+/// Merges and rebases stopped by notes changed on both sides.
+const CONFLICT_MARKERS: &[&str] = &[
+    "conflict (",
+    "automatic merge failed",
+    "you have unmerged paths",
+    "needs merge",
+    "could not apply",
+];
+
+/// Another git process, or a crashed one, holds a lock in `.git`.
+const LOCK_MARKERS: &[&str] = &["index.lock", "cannot lock ref", ".lock': file exists"];
+
+const DISK_FULL_MARKERS: &[&str] = &[
+    "no space left on device",
+    "disk quota exceeded",
+    "not enough space on the disk",
+];
+
+/// A failed git command, sorted by what the daemon can do about it. Each
+/// variant carries the full message, `git <args> failed with code ...`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum GitError {
+    /// The remote refused the credentials; retrying will not help until the
+    /// user fixes them.
+    #[error("{0}")]
+    Auth(String),
+    /// The SSH host key is not in `known_hosts` yet; `obsyncgit trust-host`
+    /// pins it.
+    #[error("{0}")]
+    UnknownHost(String),
+    /// The SSH host key differs from the pinned one. Not retried around:
+    /// the user has to check the new key with the host's owner.
+    #[error("{0}")]
+    HostKeyChanged(String),
+    /// The remote could not be reached or stopped responding.
+    #[error("{0}")]
+    Network(String),
+    /// A merge or rebase stopped on notes changed on both sides.
+    #[error("{0}")]
+    Conflict(String),
+    /// The remote branch moved on; pulling first fixes it.
+    #[error("{0}")]
+    NonFastForward(String),
+    /// A lock file in `.git` is held by another process.
+    #[error("{0}")]
+    LockHeld(String),
+    /// The disk holding the vault is full.
+    #[error("{0}")]
+    DiskFull(String),
+    /// A hook of the repository, such as `pre-commit`, refused a commit
+    /// under `git.run_hooks`.
+    #[error("{0}")]
+    Hook(String),
+    #[error("{0}")]
+    Unknown(String),
+}
+
+impl GitError {
+    /// Sorts a failure by the output of git and the tools it runs. Checked
+    /// from the most specific cause down: a full disk or a lock can break
+    /// any command, and a refused login often ends with the remote hanging
+    /// up.
+    pub fn classify(message: String) -> Self {
+        let lower = message.to_ascii_lowercase();
+        let has = |markers: &[&str]| markers.iter().any(|marker| lower.contains(marker));
+        if has(DISK_FULL_MARKERS) {
+            Self::DiskFull(message)
+        } else if has(LOCK_MARKERS) {
+            Self::LockHeld(message)
+        } else if has(HOST_KEY_CHANGED_MARKERS) {
+            Self::HostKeyChanged(message)
+        } else if has(UNKNOWN_HOST_MARKERS) {
+            Self::UnknownHost(message)
+        } else if has(AUTH_MARKERS) {
+            Self::Auth(message)
+        } else if has(NON_FAST_FORWARD_MARKERS) {
+            Self::NonFastForward(message)
+        } else if has(CONFLICT_MARKERS) {
+            Self::Conflict(message)
+        } else if has(NETWORK_MARKERS) && !lower.contains("permission denied") {
+            Self::Network(message)
+        } else {
+            Self::Unknown(message)
+        }
+    }
+
+    /// The git failure behind `err`, or its message classified the same way
+    /// when it did not come from git itself (an ssh probe, a helper).
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<GitError>())
+            .cloned()
+            .unwrap_or_else(|| Self::classify(format!("{err:#}")))
+    }
+
+    /// Rebuilds an error saved with [`GitError::category`].
+    pub fn from_parts(category: &str, message: String) -> Self {
+        match category {
+            "auth" => Self::Auth(message),
+            "unknown_host" => Self::UnknownHost(message),
+            "host_key_changed" => Self::HostKeyChanged(message),
+            "network" => Self::Network(message),
+            "conflict" => Self::Conflict(message),
+            "non_fast_forward" => Self::NonFastForward(message),
+            "lock_held" => Self::LockHeld(message),
+            "disk_full" => Self::DiskFull(message),
+            "hook" => Self::Hook(message),
+            _ => Self::Unknown(message),
+        }
+    }
+
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::Auth(_) => "auth",
+            Self::UnknownHost(_) => "unknown_host",
+            Self::HostKeyChanged(_) => "host_key_changed",
+            Self::Network(_) => "network",
+            Self::Conflict(_) => "conflict",
+            Self::NonFastForward(_) => "non_fast_forward",
+            Self::LockHeld(_) => "lock_held",
+            Self::DiskFull(_) => "disk_full",
+            Self::Hook(_) => "hook",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Auth(message)
+            | Self::UnknownHost(message)
+            | Self::HostKeyChanged(message)
+            | Self::Network(message)
+            | Self::Conflict(message)
+            | Self::NonFastForward(message)
+            | Self::LockHeld(message)
+            | Self::DiskFull(message)
+            | Self::Hook(message)
+            | Self::Unknown(message) => message,
+        }
+    }
+
+    /// Whether the daemon gets past this by itself, by retrying or pulling,
+    /// rather than needing the user.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Network(_) | Self::NonFastForward(_) | Self::LockHeld(_)
+        )
+    }
+
+    /// The cause in a few words, for `status` and the desktop bars.
+    pub fn cause(&self) -> String {
+        match self {
+            Self::Auth(_) => tr!("git-error-auth"),
+            Self::UnknownHost(_) => tr!("git-error-unknown-host"),
+            Self::HostKeyChanged(_) => tr!("git-error-host-key-changed"),
+            Self::Network(_) => tr!("git-error-network"),
+            Self::Conflict(_) => tr!("git-error-conflict"),
+            Self::NonFastForward(_) => tr!("git-error-non-fast-forward"),
+            Self::LockHeld(_) => tr!("git-error-lock-held"),
+            Self::DiskFull(_) => tr!("git-error-disk-full"),
+            Self::Hook(_) => tr!("git-error-hook"),
+            Self::Unknown(_) => tr!("git-error-unknown"),
+        }
+    }
+}
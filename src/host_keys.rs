@@ -0,0 +1,237 @@
+//! SSH host keys of the remote. Git runs with `GIT_TERMINAL_PROMPT=0` and
+//! the daemon has no terminal, so ssh cannot ask whether to trust a host it
+//! has not seen; `obsyncgit trust-host` fetches the keys, shows their
+//! fingerprints and pins them in `~/.ssh/known_hosts` instead.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use sha2::{Digest, Sha256};
+use tracing::debug;
+
+use crate::git;
+
+/// Seconds `ssh-keyscan` waits for the host.
+const SCAN_TIMEOUT_SECONDS: &str = "10";
+
+/// The SSH server behind a remote URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshHost {
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl SshHost {
+    /// The host of an `ssh://` or `user@host:path` URL; `None` for other
+    /// remotes.
+    pub fn parse(url: &str) -> Option<Self> {
+        if let Some(rest) = url.strip_prefix("ssh://") {
+            let authority = rest.split('/').next()?;
+            let host = authority
+                .rsplit_once('@')
+                .map_or(authority, |(_, host)| host);
+            return Some(match host.rsplit_once(':') {
+                Some((host, port)) => Self {
+                    host: host.to_string(),
+                    port: port.parse().ok(),
+                },
+                None => Self {
+                    host: host.to_string(),
+                    port: None,
+                },
+            });
+        }
+        if url.contains("://") {
+            return None;
+        }
+        let (authority, _) = url.split_once(':')?;
+        // `C:\vault.git` is a Windows path, not a host.
+        if authority.len() == 1 || authority.contains(['/', '\\']) {
+            return None;
+        }
+        Some(Self {
+            host: authority
+                .rsplit_once('@')
+                .map_or(authority, |(_, host)| host)
+                .to_string(),
+            port: None,
+        })
+    }
+
+    /// The host name and port ssh actually connects to, after `Host`
+    /// aliases in `~/.ssh/config`. Falls back to the URL's when `ssh -G`
+    /// is unavailable.
+    pub fn resolve(&self) -> Self {
+        let mut resolved = self.clone();
+        let mut command = Command::new("ssh");
+        command.arg("-G");
+        if let Some(port) = self.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        let output = match command.arg(&self.host).output() {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                debug!(stderr = %String::from_utf8_lossy(&output.stderr), "ssh -G failed");
+                return resolved;
+            }
+            Err(err) => {
+                debug!(?err, "failed to run ssh -G");
+                return resolved;
+            }
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            match line.split_once(' ') {
+                Some(("hostname", host)) => resolved.host = host.to_string(),
+                Some(("port", port)) => {
+                    resolved.port = port.parse().ok().filter(|port| *port != 22)
+                }
+                _ => {}
+            }
+        }
+        resolved
+    }
+
+    /// The name ssh looks the host up by in `known_hosts`.
+    pub fn known_hosts_name(&self) -> String {
+        match self.port {
+            Some(port) if port != 22 => format!("[{}]:{port}", self.host),
+            _ => self.host.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for SshHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.known_hosts_name())
+    }
+}
+
+/// One public key a host offered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostKey {
+    /// `ssh-ed25519`, `ecdsa-sha2-nistp256`, `ssh-rsa`, ...
+    pub kind: String,
+    /// The key, base64 as in `known_hosts`.
+    pub key: String,
+}
+
+impl HostKey {
+    /// `SHA256:...`, as ssh and the hosts' documentation print it.
+    pub fn fingerprint(&self) -> String {
+        match STANDARD.decode(&self.key) {
+            Ok(blob) => format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(blob))),
+            Err(_) => "SHA256:?".to_string(),
+        }
+    }
+}
+
+/// `~/.ssh/known_hosts`, where ssh looks first.
+pub fn known_hosts_path() -> PathBuf {
+    git::expand_home("~/.ssh/known_hosts")
+}
+
+/// Fetches the keys `host` offers. They are not trusted yet: compare the
+/// fingerprints with the ones the host publishes before pinning them.
+pub fn scan(host: &SshHost) -> Result<Vec<HostKey>> {
+    let mut command = Command::new("ssh-keyscan");
+    command.args(["-T", SCAN_TIMEOUT_SECONDS]);
+    if let Some(port) = host.port {
+        command.arg("-p").arg(port.to_string());
+    }
+    let output = command
+        .arg(&host.host)
+        .output()
+        .context("failed to run ssh-keyscan; is OpenSSH installed?")?;
+    let keys: Vec<HostKey> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            Some(HostKey {
+                kind: fields.next()?.to_string(),
+                key: fields.next()?.to_string(),
+            })
+        })
+        .collect();
+    if keys.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "{host} offered no SSH host keys: {}",
+            stderr
+                .lines()
+                .find(|line| !line.starts_with('#') && !line.trim().is_empty())
+                .unwrap_or("no answer")
+        );
+    }
+    Ok(keys)
+}
+
+/// Whether `known_hosts` has a key for `host` already.
+pub fn is_known(host: &SshHost) -> Result<bool> {
+    let path = known_hosts_path();
+    if !path.exists() {
+        return Ok(false);
+    }
+    let output = Command::new("ssh-keygen")
+        .arg("-F")
+        .arg(host.known_hosts_name())
+        .arg("-f")
+        .arg(&path)
+        .output()
+        .context("failed to run ssh-keygen; is OpenSSH installed?")?;
+    Ok(output.status.success() && !output.stdout.is_empty())
+}
+
+/// Adds `keys` for `host` to `known_hosts`. With `replace`, the host's old
+/// keys are removed first, after a key change was confirmed with the
+/// host's owner; ssh keeps the previous file as `known_hosts.old`.
+pub fn pin(host: &SshHost, keys: &[HostKey], replace: bool) -> Result<PathBuf> {
+    let path = known_hosts_path();
+    if let Some(dir) = path.parent()
+        && !dir.exists()
+    {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+                .with_context(|| format!("failed to restrict {}", dir.display()))?;
+        }
+    }
+    let name = host.known_hosts_name();
+    if replace && path.exists() {
+        let output = Command::new("ssh-keygen")
+            .arg("-R")
+            .arg(&name)
+            .arg("-f")
+            .arg(&path)
+            .output()
+            .context("failed to run ssh-keygen; is OpenSSH installed?")?;
+        if !output.status.success() {
+            bail!(
+                "failed to remove the old keys of {name}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let mut lines = String::new();
+    if fs::read(&path).is_ok_and(|contents| contents.last().is_some_and(|byte| *byte != b'\n')) {
+        lines.push('\n');
+    }
+    for key in keys {
+        lines.push_str(&format!("{name} {} {}\n", key.kind, key.key));
+    }
+    file.write_all(lines.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
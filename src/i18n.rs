@@ -0,0 +1,112 @@
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentResource, FluentValue};
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+pub use fluent_bundle::FluentArgs;
+
+/// Bundled translations. English comes first: it is the fallback for any
+/// message another language lacks.
+pub(crate) const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("ru", include_str!("../locales/ru.ftl")),
+];
+
+/// Overrides the system language, e.g. `OBSYNCGIT_LANG=ru`.
+pub const LANG_ENV: &str = "OBSYNCGIT_LANG";
+
+/// User-facing text in one language, falling back to English.
+pub struct Localizer {
+    /// Preferred language first, English last.
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+
+impl Localizer {
+    /// Messages for `tag` (`ru`, `ru-RU`, `ru_RU.UTF-8`, …). Unknown
+    /// languages get English.
+    pub fn for_language(tag: &str) -> Self {
+        let language = tag
+            .split(['-', '_', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let mut bundles = Vec::new();
+        if let Some((code, source)) = LOCALES.iter().skip(1).find(|(code, _)| *code == language) {
+            bundles.push(bundle(code, source));
+        }
+        let (code, source) = LOCALES[0];
+        bundles.push(bundle(code, source));
+        Self { bundles }
+    }
+
+    /// Language of the messages, such as `en` or `ru`.
+    pub fn language(&self) -> String {
+        self.bundles[0].locales[0].language.to_string()
+    }
+
+    /// The message `id` with `args` filled in. A message missing from every
+    /// bundle comes out as its id, so a typo shows up instead of vanishing.
+    pub fn format(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        for bundle in &self.bundles {
+            if let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) {
+                let mut errors = Vec::new();
+                let text = bundle.format_pattern(pattern, args, &mut errors);
+                if !errors.is_empty() {
+                    warn!(id, ?errors, "failed to format message");
+                }
+                return text.into_owned();
+            }
+        }
+        id.to_string()
+    }
+}
+
+fn bundle(code: &str, source: &str) -> FluentBundle<FluentResource> {
+    let language: LanguageIdentifier = code.parse().expect("bundled locale code");
+    let resource =
+        FluentResource::try_new(source.to_string()).unwrap_or_else(|(resource, _)| resource);
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // Isolation marks around arguments show up as stray characters in
+    // terminals and in the GUI fonts.
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .unwrap_or_else(|_| panic!("duplicate message in bundled {code} locale"));
+    bundle
+}
+
+/// The process-wide localizer: `OBSYNCGIT_LANG` if set, otherwise the
+/// system language.
+pub fn current() -> &'static Localizer {
+    static CURRENT: OnceLock<Localizer> = OnceLock::new();
+    CURRENT.get_or_init(|| {
+        let tag = std::env::var(LANG_ENV)
+            .ok()
+            .filter(|tag| !tag.trim().is_empty())
+            .or_else(sys_locale::get_locale)
+            .unwrap_or_default();
+        Localizer::for_language(&tag)
+    })
+}
+
+/// Converts a message argument; numbers keep plural rules working.
+pub fn arg<'a>(value: impl Into<FluentValue<'a>>) -> FluentValue<'a> {
+    value.into()
+}
+
+/// Looks up a message in the current language:
+/// `tr!("cli-log-empty", path = relative)`. Arguments are strings or
+/// numbers; format other values with `to_string()` first.
+#[macro_export]
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::i18n::current().format($id, None)
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = $crate::i18n::FluentArgs::new();
+        $(args.set(stringify!($name), $crate::i18n::arg($value));)+
+        $crate::i18n::current().format($id, Some(&args))
+    }};
+}
@@ -1,61 +1,308 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use anyhow::{Context, Result};
 use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
 
+use crate::cloud::CloudProvider;
+use crate::config::{Config, IgnoreConfig};
+
+/// Default ignores to avoid feedback loops and OS artifacts.
+const BUILTIN: [&str; 5] = [
+    ".git",
+    ".git/**",
+    ".gitignore",
+    "**/.DS_Store",
+    "**/Thumbs.db",
+];
+
+/// Directory verdicts kept before the cache starts over.
+const DIR_CACHE_LIMIT: usize = 4096;
+
+/// Why `ignore.max_file_size_mb` or `ignore.extensions_blocklist` keeps a
+/// file out of commits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Blocked {
+    /// The extension, lowercase and without the dot.
+    Extension(String),
+    Size {
+        bytes: u64,
+        limit_mb: u64,
+    },
+}
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Extension(extension) => {
+                write!(f, ".{extension} is in ignore.extensions_blocklist")
+            }
+            Self::Size { bytes, limit_mb } => write!(
+                f,
+                "{} MB is over ignore.max_file_size_mb ({limit_mb} MB)",
+                bytes.div_ceil(1024 * 1024)
+            ),
+        }
+    }
+}
+
+/// Whether the size limit or the extension blocklist of `config` keeps the
+/// file at `path` (vault-relative) from being staged. Deleting such a file
+/// is still committed.
+pub fn staging_block(config: &IgnoreConfig, root: &Path, path: &str) -> Option<Blocked> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+    if let Some(extension) = extension
+        && config.extensions_blocklist.iter().any(|blocked| {
+            blocked
+                .trim()
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(&extension)
+        })
+        && root.join(path).exists()
+    {
+        return Some(Blocked::Extension(extension));
+    }
+    if config.max_file_size_mb == 0 {
+        return None;
+    }
+    let bytes = std::fs::metadata(root.join(path))
+        .ok()
+        .filter(|meta| meta.is_file())?
+        .len();
+    (bytes > config.max_file_size_mb.saturating_mul(1024 * 1024)).then_some(Blocked::Size {
+        bytes,
+        limit_mb: config.max_file_size_mb,
+    })
+}
+
+/// Where an ignore pattern comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleSource {
+    Builtin,
+    /// `ignore.globs`.
+    Config,
+    /// The scratch files of the cloud service holding the vault.
+    Cloud(CloudProvider),
+}
+
+impl fmt::Display for RuleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Builtin => f.write_str("builtin"),
+            Self::Config => f.write_str("ignore.globs"),
+            Self::Cloud(provider) => write!(f, "{provider}"),
+        }
+    }
+}
+
+/// One pattern of an [`IgnoreMatcher`].
+#[derive(Debug, Clone)]
+pub struct IgnoreRule {
+    /// The pattern as written, including a leading `!`.
+    pub pattern: String,
+    pub source: RuleSource,
+    /// A `!pattern` that re-includes what earlier patterns ignore.
+    pub negated: bool,
+}
+
 #[derive(Clone)]
 pub struct IgnoreMatcher {
     root: PathBuf,
     set: GlobSet,
+    /// The patterns in `set`, in the same order.
+    rules: Vec<IgnoreRule>,
+    /// The `<dir>` of `<dir>/**` patterns that no later `!pattern` can
+    /// re-include from: a directory matching one is ignored with everything
+    /// in it, so events below it skip the full set.
+    dirs: GlobSet,
+    /// `dirs` verdicts by vault-relative directory, shared between clones.
+    dir_cache: Arc<Mutex<HashMap<String, bool>>>,
 }
 
 impl IgnoreMatcher {
     pub fn new(root: &Path, patterns: &[String]) -> Result<Self> {
+        Self::with_rules(root, patterns, None)
+    }
+
+    /// The matcher the daemon watches the vault with: `ignore.globs` plus the
+    /// scratch files of `cloud`.
+    pub fn for_config(config: &Config, cloud: Option<CloudProvider>) -> Result<Self> {
+        Self::with_rules(config.workdir.as_std_path(), &config.ignore.globs, cloud)
+    }
+
+    fn with_rules(root: &Path, patterns: &[String], cloud: Option<CloudProvider>) -> Result<Self> {
         let mut builder = GlobSetBuilder::new();
-        // Default ignores to avoid feedback loops and OS artifacts.
-        for pattern in [
-            ".git",
-            ".git/**",
-            ".gitignore",
-            "**/.DS_Store",
-            "**/Thumbs.db",
-        ] {
+        let mut rules = Vec::new();
+        // Each rule's glob and whether `*` stops at `/`, for `dirs`.
+        let mut globs = Vec::new();
+        for pattern in BUILTIN {
             let glob = GlobBuilder::new(pattern)
                 .literal_separator(true)
                 .build()
                 .with_context(|| format!("failed to compile builtin ignore pattern '{pattern}'"))?;
             builder.add(glob);
+            globs.push((pattern, true));
+            rules.push(IgnoreRule {
+                pattern: pattern.to_string(),
+                source: RuleSource::Builtin,
+                negated: false,
+            });
         }
 
-        for pattern in patterns {
+        let configured = patterns
+            .iter()
+            .map(|pattern| (pattern.as_str(), RuleSource::Config));
+        let scratch = cloud.into_iter().flat_map(|provider| {
+            provider
+                .ignore_globs()
+                .iter()
+                .map(move |pattern| (*pattern, RuleSource::Cloud(provider)))
+        });
+        for (pattern, source) in configured.chain(scratch) {
             if pattern.trim().is_empty() {
                 continue;
             }
-            let glob = GlobBuilder::new(pattern)
+            // As in .gitignore; `\!` matches a literal `!`.
+            let (text, negated) = match pattern.strip_prefix('!') {
+                Some(rest) => (rest, true),
+                None => (pattern, false),
+            };
+            let glob = GlobBuilder::new(text)
                 .literal_separator(false)
                 .build()
                 .with_context(|| format!("failed to compile ignore pattern '{pattern}'"))?;
             builder.add(glob);
+            globs.push((text, false));
+            rules.push(IgnoreRule {
+                pattern: pattern.to_string(),
+                source,
+                negated,
+            });
         }
 
         let set = builder.build().context("failed to build ignore set")?;
+
+        let mut dirs = GlobSetBuilder::new();
+        for (index, (text, literal_separator)) in globs.iter().enumerate() {
+            let rule = &rules[index];
+            let Some(dir) = text.strip_suffix("/**").filter(|dir| !dir.is_empty()) else {
+                continue;
+            };
+            let reincluded = rule.source != RuleSource::Builtin
+                && globs[index + 1..]
+                    .iter()
+                    .zip(&rules[index + 1..])
+                    .any(|((later, _), later_rule)| later_rule.negated && may_overlap(dir, later));
+            if rule.negated || reincluded {
+                continue;
+            }
+            let glob = GlobBuilder::new(dir)
+                .literal_separator(*literal_separator)
+                .build()
+                .with_context(|| format!("failed to compile ignore pattern '{}'", rule.pattern))?;
+            dirs.add(glob);
+        }
+        let dirs = dirs.build().context("failed to build ignore set")?;
+
         Ok(Self {
             root: root.to_path_buf(),
             set,
+            rules,
+            dirs,
+            dir_cache: Arc::default(),
         })
     }
 
+    /// Whether events for `path` are skipped. The last matching pattern
+    /// decides, as in .gitignore, so `!pattern` re-includes paths earlier
+    /// patterns ignore. Builtin patterns cannot be overridden.
     pub fn should_ignore<P: AsRef<Path>>(&self, path: P) -> bool {
-        let path = path.as_ref();
-        if let Ok(rel) = path.strip_prefix(&self.root) {
-            if rel.as_os_str().is_empty() {
-                return false;
-            }
-            if let Some(rel_str) = rel.to_str() {
-                let normalized = rel_str.replace('\\', "/");
-                return self.set.is_match(normalized.as_str());
-            }
+        let Some(rel) = self.relative(path.as_ref()) else {
+            return false;
+        };
+        if self.ignored_ancestor(&rel) {
+            return true;
+        }
+        let matches = self.set.matches(rel.as_str());
+        if matches
+            .iter()
+            .any(|&index| self.rules[index].source == RuleSource::Builtin)
+        {
+            return true;
+        }
+        matches
+            .into_iter()
+            .max()
+            .is_some_and(|index| !self.rules[index].negated)
+    }
+
+    /// Whether the directory at `path` is ignored with everything in it, so
+    /// a walk of the vault need not enter it.
+    pub fn ignores_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.relative(path.as_ref())
+            .is_some_and(|rel| self.ignored_ancestor(&rel) || self.dir_ignored(&rel))
+    }
+
+    /// Whether a directory above `rel` is ignored with all it holds.
+    fn ignored_ancestor(&self, rel: &str) -> bool {
+        rel.match_indices('/')
+            .any(|(at, _)| self.dir_ignored(&rel[..at]))
+    }
+
+    fn dir_ignored(&self, dir: &str) -> bool {
+        if self.dirs.is_empty() {
+            return false;
+        }
+        let mut cache = self.dir_cache.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(&ignored) = cache.get(dir) {
+            return ignored;
+        }
+        let ignored = self.dirs.is_match(dir);
+        if cache.len() >= DIR_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(dir.to_string(), ignored);
+        ignored
+    }
+
+    /// Every rule that matches `path` in order, including ones overridden by
+    /// later rules, for `obsyncgit ignore explain`.
+    pub fn matching_rules<P: AsRef<Path>>(&self, path: P) -> Vec<&IgnoreRule> {
+        let Some(rel) = self.relative(path.as_ref()) else {
+            return Vec::new();
+        };
+        let mut matches = self.set.matches(rel.as_str());
+        matches.sort_unstable();
+        matches
+            .into_iter()
+            .map(|index| &self.rules[index])
+            .collect()
+    }
+
+    /// `path` relative to the vault, with forward slashes.
+    fn relative(&self, path: &Path) -> Option<String> {
+        let rel = path.strip_prefix(&self.root).ok()?;
+        if rel.as_os_str().is_empty() {
+            return None;
         }
-        false
+        Some(rel.to_str()?.replace('\\', "/"))
     }
 }
+
+/// Whether a path below `dir` could match `glob`, judged by the literal
+/// text before their first wildcard: neither may extend the other.
+fn may_overlap(dir: &str, glob: &str) -> bool {
+    let (dir, glob) = (literal_prefix(dir), literal_prefix(glob));
+    dir.starts_with(glob) || glob.starts_with(dir)
+}
+
+/// `glob` up to its first wildcard or escape.
+fn literal_prefix(glob: &str) -> &str {
+    glob.find(['*', '?', '[', '{', '\\'])
+        .map_or(glob, |at| &glob[..at])
+}
@@ -0,0 +1,352 @@
+//! `obsyncgit import`: copies notes from a folder or a zip archive, such as
+//! a phone's export of the vault, into the vault and commits them as one
+//! tagged commit. Files only come in; nothing in the vault is deleted.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, bail};
+use chrono::{Local, NaiveDate, TimeZone};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::git::GitFacade;
+use crate::ignore::{self, IgnoreMatcher};
+use crate::secret_scan::SecretScanner;
+
+/// What to do when the vault already has a different version of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnCollision {
+    /// Keep whichever was modified last.
+    Newer,
+    /// Keep the vault's version and save the imported one beside it as
+    /// `name (imported).ext`.
+    KeepBoth,
+}
+
+impl FromStr for OnCollision {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "newer" | "newer-wins" => Ok(Self::Newer),
+            "keep-both" | "both" => Ok(Self::KeepBoth),
+            other => Err(format!(
+                "unknown collision policy: {other} (expected newer or keep-both)"
+            )),
+        }
+    }
+}
+
+/// What an import did, by vault-relative path.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    /// The vault's path and where the imported version was saved.
+    pub copies: Vec<(String, String)>,
+    /// Different from the vault's version but older.
+    pub skipped: Vec<String>,
+    /// Already in the vault with the same content.
+    pub unchanged: usize,
+    /// Written but left for the daemon, which holds them back from commits
+    /// like any other blocked file or likely secret.
+    pub held: Vec<String>,
+    /// The import commit and its tag; `None` for a dry run or when nothing
+    /// changed.
+    pub commit: Option<(String, String)>,
+}
+
+impl ImportReport {
+    fn written(&self) -> impl Iterator<Item = &String> {
+        self.added
+            .iter()
+            .chain(&self.updated)
+            .chain(self.copies.iter().map(|(_, copy)| copy))
+    }
+}
+
+/// Copies the files of `source`, a folder or a `.zip` archive, into the
+/// vault. A zip whose entries all sit in one folder, as `obsyncgit export`
+/// writes them, is imported from inside that folder. Paths the vault
+/// ignores are skipped. Unless `dry_run` is set, the files are committed
+/// and the commit tagged `import/<time>-<commit>`; the vault must have no
+/// uncommitted changes, so the commit holds the import only.
+pub fn import(
+    git: &GitFacade,
+    config: &Config,
+    source: &Path,
+    on_collision: OnCollision,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    if !dry_run {
+        let dirty = git.list_pending_paths()?;
+        if !dirty.is_empty() {
+            bail!(
+                "the vault has uncommitted changes ({}); let the daemon commit them first",
+                dirty.join(", ")
+            );
+        }
+    }
+    let mut importer = Importer {
+        root: config.workdir.as_std_path(),
+        ignore: IgnoreMatcher::for_config(config, None)?,
+        on_collision,
+        dry_run,
+        report: ImportReport::default(),
+    };
+    if source.is_dir() {
+        for (path, file) in folder_files(source)? {
+            let modified = fs::metadata(&file).and_then(|meta| meta.modified()).ok();
+            importer.place(&path, modified, || {
+                fs::read(&file).with_context(|| format!("failed to read {}", file.display()))
+            })?;
+        }
+    } else {
+        import_zip(&mut importer, source)?;
+    }
+
+    let mut report = importer.report;
+    if dry_run {
+        return Ok(report);
+    }
+    let scanner = SecretScanner::new(&config.security.secret_scan)?;
+    let root = config.workdir.as_std_path();
+    let written: Vec<String> = report.written().cloned().collect();
+    let mut staged = Vec::new();
+    for path in written {
+        let blocked = ignore::staging_block(&config.ignore, root, &path)
+            .map(|blocked| blocked.to_string())
+            .or_else(|| Some(scanner.as_ref()?.check(root, &path)?.to_string()));
+        match blocked {
+            Some(reason) => {
+                warn!(%path, %reason, "imported but not committing this file");
+                report.held.push(path);
+            }
+            None => staged.push(path),
+        }
+    }
+    if staged.is_empty() {
+        return Ok(report);
+    }
+    git.stage_paths(&staged)?;
+    let name = source.file_name().map_or_else(
+        || source.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    let message = format!(
+        "{} import {} file(s) from {name}",
+        config.commit.prefix,
+        staged.len()
+    );
+    if git.commit(&message)? {
+        let commit = git.head_commit()?;
+        let tag = format!(
+            "import/{}-{}",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            commit.get(..7).unwrap_or(&commit)
+        );
+        git.create_tag(&tag, &commit)?;
+        report.commit = Some((commit, tag));
+    }
+    Ok(report)
+}
+
+struct Importer<'a> {
+    root: &'a Path,
+    ignore: IgnoreMatcher,
+    on_collision: OnCollision,
+    dry_run: bool,
+    report: ImportReport,
+}
+
+impl Importer<'_> {
+    /// Brings in the file for vault-relative `path`, reading it with `read`
+    /// unless the vault ignores that path.
+    fn place(
+        &mut self,
+        path: &str,
+        modified: Option<SystemTime>,
+        read: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<()> {
+        let target = self.root.join(path);
+        if self.ignore.should_ignore(&target) {
+            return Ok(());
+        }
+        let contents = read()?;
+        let existing = match fs::read(&target) {
+            Ok(existing) => existing,
+            Err(_) if !target.exists() => {
+                self.write(&target, &contents, modified)?;
+                self.report.added.push(path.to_string());
+                return Ok(());
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to read {}", target.display()));
+            }
+        };
+        if existing == contents {
+            self.report.unchanged += 1;
+            return Ok(());
+        }
+        match self.on_collision {
+            OnCollision::Newer => {
+                let current = fs::metadata(&target).and_then(|meta| meta.modified()).ok();
+                // Without a time for the imported file the vault's copy wins.
+                if modified.is_some_and(|modified| current.is_none_or(|current| modified > current))
+                {
+                    self.write(&target, &contents, modified)?;
+                    self.report.updated.push(path.to_string());
+                } else {
+                    self.report.skipped.push(path.to_string());
+                }
+            }
+            OnCollision::KeepBoth => {
+                for attempt in 1.. {
+                    let copy = copy_path(path, attempt);
+                    let copy_target = self.root.join(&copy);
+                    match fs::read(&copy_target) {
+                        Ok(saved) if saved == contents => {
+                            self.report.unchanged += 1;
+                            break;
+                        }
+                        Ok(_) => continue,
+                        Err(_) => {
+                            self.write(&copy_target, &contents, modified)?;
+                            self.report.copies.push((path.to_string(), copy));
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&self, target: &Path, contents: &[u8], modified: Option<SystemTime>) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs::write(target, contents)
+            .with_context(|| format!("failed to write {}", target.display()))?;
+        // Keeps a second import of the same files from counting as newer.
+        if let Some(modified) = modified {
+            let _ = fs::File::options()
+                .write(true)
+                .open(target)
+                .and_then(|file| file.set_modified(modified));
+        }
+        Ok(())
+    }
+}
+
+/// Where "keep both" saves the imported version: `notes/idea.md` becomes
+/// `notes/idea (imported).md`, then `notes/idea (imported 2).md`.
+fn copy_path(path: &str, attempt: u32) -> String {
+    let suffix = match attempt {
+        1 => " (imported)".to_string(),
+        n => format!(" (imported {n})"),
+    };
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (format!("{dir}/"), name),
+        None => (String::new(), path),
+    };
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{dir}{stem}{suffix}.{ext}"),
+        _ => format!("{dir}{name}{suffix}"),
+    }
+}
+
+/// Every file below `root` with its path relative to it, skipping `.git`.
+fn folder_files(root: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry.with_context(|| format!("failed to read {}", dir.display()))?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if entry.file_name() != ".git" {
+                    pending.push(path);
+                }
+            } else if file_type.is_file()
+                && let Some(relative) = path
+                    .strip_prefix(root)
+                    .ok()
+                    .and_then(|relative| relative.to_str())
+            {
+                files.push((relative.replace('\\', "/"), path.clone()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn import_zip(importer: &mut Importer<'_>, source: &Path) -> Result<()> {
+    let file =
+        fs::File::open(source).with_context(|| format!("failed to open {}", source.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("{} is neither a folder nor a zip archive", source.display()))?;
+
+    let mut names = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        // `enclosed_name` drops entries that would land outside the vault.
+        if entry.is_file()
+            && let Some(name) = entry
+                .enclosed_name()
+                .and_then(|name| name.to_str().map(|name| name.replace('\\', "/")))
+        {
+            names.push((index, name));
+        }
+    }
+    let top = names
+        .first()
+        .and_then(|(_, name)| name.split_once('/'))
+        .map(|(top, _)| format!("{top}/"))
+        .filter(|top| names.iter().all(|(_, name)| name.starts_with(top.as_str())));
+
+    for (index, name) in names {
+        let path = top
+            .as_deref()
+            .and_then(|top| name.strip_prefix(top))
+            .unwrap_or(&name);
+        if path.split('/').any(|part| part == ".git") {
+            continue;
+        }
+        let mut entry = archive.by_index(index)?;
+        let modified = entry.last_modified().and_then(|time| {
+            let local = NaiveDate::from_ymd_opt(
+                time.year().into(),
+                time.month().into(),
+                time.day().into(),
+            )?
+            .and_hms_opt(
+                time.hour().into(),
+                time.minute().into(),
+                time.second().into(),
+            )?;
+            // Zip times carry no zone; they are the exporting device's.
+            Some(Local.from_local_datetime(&local).earliest()?.into())
+        });
+        importer.place(path, modified, || {
+            let mut contents = Vec::with_capacity(entry.size() as usize);
+            entry
+                .read_to_end(&mut contents)
+                .with_context(|| format!("failed to extract {name}"))?;
+            Ok(contents)
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! `commit.insignificant`: edits that change a note without changing what
+//! it says, such as trailing spaces an editor trimmed or an `updated:`
+//! timestamp a plugin bumps on every save. The daemon leaves such a file
+//! uncommitted until it changes in some other way; git itself already
+//! skips files saved again without any change. Committed, such bumps would
+//! go back and forth between devices, each opening the note the other just
+//! synced.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::config::InsignificantChanges;
+use crate::git::GitFacade;
+
+/// `commit.insignificant` as the daemon applies it.
+#[derive(Debug)]
+pub struct InsignificantRules {
+    whitespace: bool,
+    keys: HashSet<String>,
+    restore: bool,
+}
+
+impl InsignificantRules {
+    /// `None` when no change counts as insignificant.
+    pub fn new(config: &InsignificantChanges) -> Option<Self> {
+        let keys: HashSet<String> = config
+            .frontmatter_keys
+            .iter()
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .collect();
+        (config.whitespace || !keys.is_empty()).then_some(Self {
+            whitespace: config.whitespace,
+            restore: config.restore_frontmatter && !keys.is_empty(),
+            keys,
+        })
+    }
+
+    /// The files among `pending` whose only changes since HEAD are
+    /// insignificant. New, deleted and binary files never are.
+    pub fn unchanged(&self, git: &GitFacade, root: &Path, pending: &[String]) -> Vec<String> {
+        self.matching(git, root, pending, self.whitespace)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Under `restore_frontmatter`, the files among `pending` whose only
+    /// changes since HEAD are to ignored front matter properties, with the
+    /// text they had when checked. Putting them back before a pull keeps a
+    /// bumped timestamp from running into the other devices' edits of the
+    /// note.
+    pub fn restorable(
+        &self,
+        git: &GitFacade,
+        root: &Path,
+        pending: &[String],
+    ) -> Vec<(String, String)> {
+        if !self.restore {
+            return Vec::new();
+        }
+        self.matching(git, root, pending, false)
+    }
+
+    fn matching(
+        &self,
+        git: &GitFacade,
+        root: &Path,
+        pending: &[String],
+        whitespace: bool,
+    ) -> Vec<(String, String)> {
+        pending
+            .iter()
+            .filter_map(|path| {
+                let current = fs::read_to_string(root.join(path)).ok()?;
+                let committed = git.show_file("HEAD", path).ok()??;
+                (current != committed
+                    && self.normalize(&current, whitespace)
+                        == self.normalize(&committed, whitespace))
+                .then(|| (path.clone(), current))
+            })
+            .collect()
+    }
+
+    /// `text` without what the rules ignore.
+    fn normalize(&self, text: &str, whitespace: bool) -> String {
+        let mut lines: Vec<&str> = Vec::new();
+        let mut frontmatter = false;
+        // Indented lines continue the value of a dropped property.
+        let mut dropping = false;
+        for (number, line) in text.split('\n').enumerate() {
+            let line = if self.whitespace {
+                line.trim_end()
+            } else {
+                line
+            };
+            let fence = line.trim_end() == "---";
+            if number == 0 && fence {
+                frontmatter = true;
+            } else if frontmatter && fence {
+                frontmatter = false;
+                dropping = false;
+            } else if frontmatter && !self.keys.is_empty() {
+                if dropping && line.starts_with([' ', '\t']) {
+                    continue;
+                }
+                dropping = line
+                    .split_once(':')
+                    .is_some_and(|(key, _)| self.keys.contains(key.trim()));
+                if dropping {
+                    continue;
+                }
+            }
+            lines.push(line);
+        }
+        if whitespace {
+            while lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,210 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use camino::Utf8PathBuf;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::config::Config;
+
+/// How long `--takeover` waits for the previous owner to exit.
+const TAKEOVER_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Exclusive claim of one obsyncgit process on a vault, so two daemons never
+/// commit and rebase in the same working tree. Backed by an OS file lock
+/// (`flock` on Unix, `LockFileEx` on Windows) that the kernel releases when
+/// the process dies, so a crash never leaves the vault claimed.
+///
+/// The lock lives in the state directory, keyed by the vault path, rather
+/// than inside `.git`: it has to be taken before the first clone and must
+/// survive the vault's drive being unplugged.
+#[derive(Debug)]
+pub struct VaultLock {
+    _file: File,
+    pid_path: Utf8PathBuf,
+}
+
+impl VaultLock {
+    /// Claims the vault or explains who holds it.
+    pub fn acquire(config: &Config) -> Result<Self> {
+        let paths = LockPaths::new(config)?;
+        match paths.try_lock()? {
+            Some(lock) => Ok(lock),
+            None => bail!(
+                "{} is already synced by another obsyncgit process{}; stop it first or use `run --takeover`",
+                config.workdir,
+                owner_suffix(paths.owner_pid())
+            ),
+        }
+    }
+
+    /// Claims the vault, asking the current owner to shut down first.
+    pub fn takeover(config: &Config) -> Result<Self> {
+        let paths = LockPaths::new(config)?;
+        if let Some(lock) = paths.try_lock()? {
+            return Ok(lock);
+        }
+        let Some(pid) = paths.owner_pid() else {
+            bail!(
+                "{} is locked by a process that did not record its PID",
+                config.workdir
+            );
+        };
+        info!(pid, "asking the running daemon to stop");
+        terminate(pid)?;
+        let deadline = Instant::now() + TAKEOVER_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Some(lock) = paths.try_lock()? {
+                info!(pid, "took over the vault");
+                return Ok(lock);
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+        bail!(
+            "obsyncgit (PID {pid}) did not release {} within {}s",
+            config.workdir,
+            TAKEOVER_TIMEOUT.as_secs()
+        )
+    }
+}
+
+impl Drop for VaultLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.pid_path);
+    }
+}
+
+/// PID of the process currently holding the vault, if any.
+pub fn owner(config: &Config) -> Result<Option<u32>> {
+    let paths = LockPaths::new(config)?;
+    // Probing takes the lock for a moment; it is released on drop.
+    Ok(match paths.probe()? {
+        true => None,
+        false => paths.owner_pid(),
+    })
+}
+
+struct LockPaths {
+    lock: Utf8PathBuf,
+    /// Windows locks are mandatory, so the owner's PID is kept next to the
+    /// lock file where other processes can still read it.
+    pid: Utf8PathBuf,
+}
+
+impl LockPaths {
+    fn new(config: &Config) -> Result<Self> {
+        let dir = config.state_dir()?.join("locks");
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {dir}"))?;
+        let key = vault_key(config);
+        Ok(Self {
+            lock: dir.join(format!("vault-{key}.lock")),
+            pid: dir.join(format!("vault-{key}.pid")),
+        })
+    }
+
+    fn open(&self) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&self.lock)
+            .with_context(|| format!("failed to open {}", self.lock))
+    }
+
+    fn try_lock(&self) -> Result<Option<VaultLock>> {
+        let file = self.open()?;
+        match file.try_lock() {
+            Ok(()) => {
+                fs::write(&self.pid, std::process::id().to_string())
+                    .with_context(|| format!("failed to write {}", self.pid))?;
+                Ok(Some(VaultLock {
+                    _file: file,
+                    pid_path: self.pid.clone(),
+                }))
+            }
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(err)) => {
+                Err(err).with_context(|| format!("failed to lock {}", self.lock))
+            }
+        }
+    }
+
+    /// Whether the lock is free, without claiming it.
+    fn probe(&self) -> Result<bool> {
+        let file = self.open()?;
+        match file.try_lock() {
+            Ok(()) => Ok(true),
+            Err(TryLockError::WouldBlock) => Ok(false),
+            Err(TryLockError::Error(err)) => {
+                Err(err).with_context(|| format!("failed to lock {}", self.lock))
+            }
+        }
+    }
+
+    fn owner_pid(&self) -> Option<u32> {
+        fs::read_to_string(&self.pid).ok()?.trim().parse().ok()
+    }
+}
+
+/// Short stable name for the vault, used to keep per-vault files apart in
+/// the shared state directory. Spellings of the same folder (`/home/me/Notes/`,
+/// `/home/me/../me/Notes`, a symlink to it) get the same key.
+pub(crate) fn vault_key(config: &Config) -> String {
+    let workdir = canonical_workdir(config.workdir.as_std_path());
+    let digest = Sha256::digest(workdir.as_os_str().as_encoded_bytes());
+    digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Resolves `workdir` through its deepest existing ancestor, since the
+/// vault folder itself does not exist before the first clone. Rebuilding
+/// the path from components also drops trailing separators.
+fn canonical_workdir(workdir: &Path) -> PathBuf {
+    let mut existing = workdir;
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            return missing
+                .into_iter()
+                .rev()
+                .fold(resolved, |path, name| path.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return workdir.components().collect(),
+        }
+    }
+}
+
+fn owner_suffix(pid: Option<u32>) -> String {
+    pid.map(|pid| format!(" (PID {pid})")).unwrap_or_default()
+}
+
+/// Stops the daemon holding the vault. On Unix it gets SIGTERM, which it
+/// handles like Ctrl-C; Windows cannot ask a windowless console process to
+/// close, so there it is killed and the OS releases the lock.
+fn terminate(pid: u32) -> Result<()> {
+    let status = if cfg!(windows) {
+        Command::new("taskkill")
+            .args(["/PID", &pid.to_string(), "/F"])
+            .status()
+    } else {
+        Command::new("kill")
+            .args(["-TERM", &pid.to_string()])
+            .status()
+    }
+    .with_context(|| format!("failed to signal process {pid}"))?;
+    if !status.success() {
+        bail!("could not stop process {pid}; stop it manually");
+    }
+    Ok(())
+}
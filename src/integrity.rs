@@ -0,0 +1,281 @@
+//! Scheduled `git fsck` of the vault's repository, and `obsyncgit repair`
+//! for when it finds damage: a fresh clone takes the repository's place and
+//! local work is carried over into it.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use camino::Utf8PathBuf;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::case_collisions;
+use crate::config::Config;
+use crate::git::GitFacade;
+use crate::instance;
+
+/// Outcome of the last check, kept in the state directory for `status` and
+/// the desktop bars.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    /// RFC 3339.
+    pub checked_at: String,
+    /// What `git fsck` found wrong; empty for a healthy repository.
+    pub problems: Vec<String>,
+}
+
+impl Report {
+    pub fn is_damaged(&self) -> bool {
+        !self.problems.is_empty()
+    }
+
+    /// Time since the check ran; zero when the timestamp is unreadable.
+    pub fn age(&self) -> Duration {
+        DateTime::parse_from_rfc3339(&self.checked_at)
+            .ok()
+            .and_then(|checked| (Utc::now() - checked.with_timezone(&Utc)).to_std().ok())
+            .unwrap_or_default()
+    }
+}
+
+fn report_path(config: &Config) -> Result<Utf8PathBuf> {
+    Ok(config
+        .state_dir()?
+        .join("integrity")
+        .join(format!("vault-{}.json", instance::vault_key(config))))
+}
+
+/// The report of the last check, if one ran.
+pub fn load(config: &Config) -> Option<Report> {
+    let contents = fs::read_to_string(report_path(config).ok()?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn save(config: &Config, report: &Report) -> Result<()> {
+    let path = report_path(config)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("failed to create {parent}"))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_string_pretty(report)?)
+        .with_context(|| format!("failed to write {tmp}"))?;
+    fs::rename(&tmp, &path).with_context(|| format!("failed to replace {path}"))
+}
+
+/// Runs `git fsck` and records the outcome.
+pub fn check(git: &GitFacade, config: &Config) -> Result<Report> {
+    let report = Report {
+        checked_at: Utc::now().to_rfc3339(),
+        problems: git.fsck()?,
+    };
+    save(config, &report)?;
+    Ok(report)
+}
+
+/// How long until the next scheduled check is due; zero when it is overdue
+/// or none ran yet, `None` when checks are off.
+pub fn next_check_in(config: &Config) -> Option<Duration> {
+    let interval = config.integrity.check_interval()?;
+    Some(
+        load(config)
+            .map(|report| interval.saturating_sub(report.age()))
+            .unwrap_or_default(),
+    )
+}
+
+/// What [`repair`] did.
+#[derive(Debug, Clone, Default)]
+pub struct Repair {
+    /// Where the damaged vault was moved, with its repository.
+    pub damaged: PathBuf,
+    /// Local files carried over into the fresh clone.
+    pub kept: Vec<String>,
+    /// Local versions kept next to the remote's as `note (2).md`, where it
+    /// could not be told which one is newer.
+    pub copies: Vec<(String, String)>,
+    /// Files deleted locally and removed from the fresh clone as well.
+    pub removed: Vec<String>,
+}
+
+/// Replaces the vault's repository with a fresh clone of the remote. The
+/// clone is made next to the vault; files with local changes, committed or
+/// not, and ignored files are copied into it; then it takes the vault's
+/// place and the damaged vault is kept beside it as `<vault>.damaged-<time>`.
+/// Nothing else may sync the vault meanwhile.
+pub fn repair(config: &Config) -> Result<Repair> {
+    let workdir = config.workdir.as_std_path();
+    let old = GitFacade::new(config)?;
+    if old.separate_git_dir().is_none() && workdir.join(".git").is_file() {
+        bail!(
+            "{} is a linked worktree or submodule; repair the repository it belongs to instead",
+            workdir.display()
+        );
+    }
+    let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let beside = |path: &Path, suffix: &str| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{suffix}-{stamp}"));
+        PathBuf::from(name)
+    };
+
+    let fresh_dir = beside(workdir, "repair");
+    let mut fresh_config = config.clone();
+    fresh_config.workdir = Utf8PathBuf::from_path_buf(fresh_dir.clone())
+        .map_err(|path| anyhow::anyhow!("{} is not valid UTF-8", path.display()))?;
+    fresh_config.git.separate_git_dir = old
+        .separate_git_dir()
+        .map(|dir| beside(dir, "repair").to_string_lossy().into_owned());
+    fresh_config.bootstrap.enabled = false;
+    let fresh = GitFacade::new(&fresh_config)?;
+    info!(path = %fresh_dir.display(), "cloning a fresh copy of the repository");
+    if let Err(err) = fresh.ensure_repo(&config.repo_url) {
+        let _ = fs::remove_dir_all(&fresh_dir);
+        if let Some(dir) = fresh.separate_git_dir() {
+            let _ = fs::remove_dir_all(dir);
+        }
+        return Err(err.context("failed to clone a fresh copy of the repository"));
+    }
+
+    let mut repair = carry_over(&old, workdir, &fresh_dir)?;
+
+    repair.damaged = beside(workdir, "damaged");
+    fs::rename(workdir, &repair.damaged).with_context(|| {
+        format!(
+            "failed to move {} to {}",
+            workdir.display(),
+            repair.damaged.display()
+        )
+    })?;
+    fs::rename(&fresh_dir, workdir).with_context(|| {
+        format!(
+            "failed to move {} to {}",
+            fresh_dir.display(),
+            workdir.display()
+        )
+    })?;
+    if let (Some(git_dir), Some(fresh_git_dir)) = (old.separate_git_dir(), fresh.separate_git_dir())
+    {
+        let damaged_git_dir = beside(git_dir, "damaged");
+        fs::rename(git_dir, &damaged_git_dir)
+            .with_context(|| format!("failed to move {}", git_dir.display()))?;
+        fs::rename(fresh_git_dir, git_dir)
+            .with_context(|| format!("failed to move {}", fresh_git_dir.display()))?;
+        let worktree = workdir.to_str().context("vault path is not valid UTF-8")?;
+        GitFacade::new(config)?.set_config_value("core.worktree", worktree)?;
+    }
+    info!(damaged = %repair.damaged.display(), "replaced the vault with a fresh clone");
+    Ok(repair)
+}
+
+/// Copies local work from the damaged vault at `root` into the fresh clone.
+/// What counts as local comes from the damaged repository as far as it can
+/// still be read; without it, every file that differs is kept.
+fn carry_over(old: &GitFacade, root: &Path, fresh: &Path) -> Result<Repair> {
+    let known = (|| -> Result<_> {
+        let mut local: HashSet<String> = old.list_pending_paths()?.into_iter().collect();
+        local.extend(old.unpushed_paths()?);
+        let tracked: HashSet<String> = old.tracked_files()?.into_iter().collect();
+        Ok((local, tracked))
+    })();
+    let (local, tracked) = match known {
+        Ok(known) => (Some(known.0), known.1),
+        Err(err) => {
+            warn!(
+                ?err,
+                "the damaged repository cannot tell which files changed; keeping every file that differs from the remote"
+            );
+            (None, HashSet::new())
+        }
+    };
+
+    let mut repair = Repair::default();
+    for path in files(root)? {
+        let source = root.join(&path);
+        let target = fresh.join(&path);
+        let is_local = local.as_ref().is_some_and(|local| local.contains(&path));
+        let copy_to = if !target.exists() {
+            // Tracked, unchanged and gone from the remote: deleted there.
+            (local.is_none() || is_local || !tracked.contains(&path)).then(|| path.clone())
+        } else if same_contents(&source, &target) {
+            None
+        } else if is_local {
+            Some(path.clone())
+        } else if local.is_none() {
+            let copy = free_name(fresh, &path);
+            repair.copies.push((path.clone(), copy.clone()));
+            Some(copy)
+        } else {
+            // An older version the remote has since changed.
+            None
+        };
+        if let Some(name) = copy_to {
+            let target = fresh.join(&name);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create {}", parent.display()))?;
+            }
+            fs::copy(&source, &target)
+                .with_context(|| format!("failed to copy {}", source.display()))?;
+            if name == path {
+                repair.kept.push(path);
+            }
+        }
+    }
+    for path in local.iter().flatten() {
+        let target = fresh.join(path);
+        if !root.join(path).exists() && target.is_file() {
+            fs::remove_file(&target)
+                .with_context(|| format!("failed to remove {}", target.display()))?;
+            repair.removed.push(path.clone());
+        }
+    }
+    repair.kept.sort();
+    repair.removed.sort();
+    Ok(repair)
+}
+
+/// Vault-relative paths of the files under `root`, outside `.git`.
+fn files(root: &Path) -> Result<Vec<String>> {
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in
+            fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?
+        {
+            let entry = entry?;
+            let path = entry.path();
+            if dir == root && entry.file_name() == ".git" {
+                continue;
+            }
+            let kind = entry.file_type()?;
+            if kind.is_dir() {
+                pending.push(path);
+            } else if path.is_file()
+                && let Some(relative) = path.strip_prefix(root).ok().and_then(Path::to_str)
+            {
+                found.push(relative.replace('\\', "/"));
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+fn same_contents(a: &Path, b: &Path) -> bool {
+    match (fs::read(a), fs::read(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// `path` numbered as `note (2).md` and up until no file in `root` has it.
+fn free_name(root: &Path, path: &str) -> String {
+    (2..)
+        .map(|number| case_collisions::numbered(path, number, false))
+        .find(|candidate| !root.join(candidate).exists())
+        .unwrap_or_else(|| path.to_string())
+}
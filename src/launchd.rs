@@ -0,0 +1,202 @@
+//! The macOS LaunchAgent that starts the daemon at login. Both
+//! `obsyncgit service install --launch-agent` and the desktop helper's
+//! autostart switch write it through here, so they produce the same agent.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow, bail};
+use camino::Utf8PathBuf;
+use directories::BaseDirs;
+
+use crate::config::LaunchdConfig;
+
+/// The agent's launchd label, also its file name.
+pub const LABEL: &str = "dev.obsyncgit.daemon";
+
+/// A LaunchAgent running `obsyncgit run` with one configuration.
+#[derive(Debug, Clone)]
+pub struct LaunchAgent {
+    pub daemon: PathBuf,
+    pub config_path: Utf8PathBuf,
+    /// `~/Library/Logs`, where the daemon's output goes.
+    pub logs_dir: PathBuf,
+    pub options: LaunchdConfig,
+}
+
+impl LaunchAgent {
+    pub fn new(daemon: PathBuf, config_path: Utf8PathBuf, options: LaunchdConfig) -> Result<Self> {
+        let dirs = BaseDirs::new().context("failed to determine home directory")?;
+        Ok(Self {
+            daemon,
+            config_path,
+            logs_dir: dirs.home_dir().join("Library/Logs"),
+            options,
+        })
+    }
+
+    /// The agent's property list.
+    pub fn plist(&self) -> String {
+        let string = |value: &str| format!("<string>{}</string>", xml_escape(value));
+        let daemon = self.daemon.to_string_lossy();
+        let config = self.config_path.as_str();
+        let mut plist = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n  <dict>\n",
+        );
+        let mut entry = |key: &str, value: String| {
+            plist.push_str(&format!("    <key>{key}</key>\n    {value}\n"));
+        };
+        entry("Label", string(LABEL));
+        entry(
+            "ProgramArguments",
+            format!(
+                "<array>\n      {}\n      {}\n      {}\n      {}\n    </array>",
+                string(&daemon),
+                string("run"),
+                string("--config"),
+                string(config)
+            ),
+        );
+        entry("RunAtLoad", "<true/>".to_string());
+        if self.options.keep_alive {
+            // Not plain `true`: launchd would restart a daemon that was
+            // stopped on purpose as well.
+            entry(
+                "KeepAlive",
+                "<dict>\n      <key>SuccessfulExit</key>\n      <false/>\n      <key>Crashed</key>\n      <true/>\n    </dict>"
+                    .to_string(),
+            );
+        }
+        entry(
+            "ThrottleInterval",
+            format!(
+                "<integer>{}</integer>",
+                self.options.throttle_interval_seconds
+            ),
+        );
+        entry("ProcessType", string(self.options.process_type.as_plist()));
+        if self.options.low_priority_io {
+            entry("LowPriorityBackgroundIO", "<true/>".to_string());
+        }
+        entry(
+            "StandardOutPath",
+            string(&self.logs_dir.join("obsyncgit.log").to_string_lossy()),
+        );
+        entry(
+            "StandardErrorPath",
+            string(&self.logs_dir.join("obsyncgit.err.log").to_string_lossy()),
+        );
+        entry(
+            "EnvironmentVariables",
+            format!(
+                "<dict>\n      <key>OBSYNCGIT_CONFIG</key>\n      {}\n    </dict>",
+                string(config)
+            ),
+        );
+        plist.push_str("  </dict>\n</plist>\n");
+        plist
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// `~/Library/LaunchAgents/dev.obsyncgit.daemon.plist`.
+pub fn agent_path() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().context("failed to determine home directory")?;
+    Ok(dirs
+        .home_dir()
+        .join("Library/LaunchAgents")
+        .join(format!("{LABEL}.plist")))
+}
+
+/// Writes the agent and (re)loads it, which starts the daemon. Returns the
+/// agent's path.
+pub fn install(agent: &LaunchAgent) -> Result<PathBuf> {
+    let path = agent_path()?;
+    for dir in [path.parent(), Some(agent.logs_dir.as_path())]
+        .into_iter()
+        .flatten()
+    {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    fs::write(&path, agent.plist())
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    // An agent loaded before keeps its old settings until it is unloaded.
+    let _ = launchctl([OsStr::new("unload"), path.as_os_str()]);
+    launchctl([OsStr::new("load"), OsStr::new("-w"), path.as_os_str()])?;
+    Ok(path)
+}
+
+/// Unloads the agent, which stops the daemon, and deletes it.
+pub fn uninstall() -> Result<()> {
+    let path = agent_path()?;
+    if !path.exists() {
+        bail!("no LaunchAgent at {}", path.display());
+    }
+    launchctl([OsStr::new("unload"), OsStr::new("-w"), path.as_os_str()])?;
+    fs::remove_file(&path).with_context(|| format!("failed to delete {}", path.display()))
+}
+
+pub fn start() -> Result<()> {
+    launchctl(["start", LABEL])
+}
+
+/// Stops the daemon; with `keep_alive` launchd leaves it stopped, since it
+/// exits cleanly.
+pub fn stop() -> Result<()> {
+    launchctl(["stop", LABEL])
+}
+
+/// Whether the agent is loaded and the daemon's PID, or `None` when no
+/// agent is installed.
+pub fn status() -> Result<Option<String>> {
+    if !agent_path()?.exists() {
+        return Ok(None);
+    }
+    let output = Command::new("launchctl")
+        .args(["list", LABEL])
+        .output()
+        .context("failed to invoke launchctl")?;
+    if !output.status.success() {
+        return Ok(Some("not loaded".to_string()));
+    }
+    // `launchctl list <label>` prints a property list with `"PID" = 123;`
+    // while the daemon runs.
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let pid = listing.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("\"PID\" = ")?
+            .trim_end_matches(';')
+            .parse::<u32>()
+            .ok()
+    });
+    Ok(Some(match pid {
+        Some(pid) => format!("running (PID {pid})"),
+        None => "loaded, not running".to_string(),
+    }))
+}
+
+fn launchctl<I, S>(args: I) -> Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let status = Command::new("launchctl")
+        .args(args)
+        .status()
+        .context("failed to invoke launchctl")?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("launchctl command failed with status {status}"))
+    }
+}
@@ -1,5 +1,67 @@
+pub mod alerts;
+pub mod backup;
+#[cfg(any(test, feature = "selftest"))]
+pub mod bench;
+pub mod bootstrap;
+pub mod case_collisions;
+pub mod clock;
+pub mod cloud;
 pub mod config;
+pub mod conflict_copies;
+pub mod conflicts;
+pub mod connectivity;
+pub mod control;
 pub mod daemon;
+pub mod deletions;
+pub mod digest;
+pub mod disk;
+pub mod events;
+pub mod file_names;
 pub mod git;
+pub mod git_error;
+pub mod host_keys;
+pub mod i18n;
 pub mod ignore;
+pub mod import;
+pub mod insignificant;
+pub mod instance;
+pub mod integrity;
+pub mod launchd;
+pub mod line_endings;
+pub mod locks;
+pub mod metered;
+pub mod mqtt;
+pub mod network;
+pub mod normalization;
+pub mod own_writes;
+pub mod pacing;
+pub mod power;
+pub mod preflight;
+pub mod provider;
+pub mod recovery;
+pub mod redaction;
+pub mod remote;
+pub mod replica;
+pub mod resume;
+pub mod retention;
+pub mod schedule;
+pub mod secret_scan;
+pub mod secrets;
+pub mod service;
+pub mod setup_bundle;
+pub mod shared_config;
+pub mod shared_vault;
+pub mod shutdown;
+pub mod snapshots;
+pub mod stability;
+pub mod statusbar;
+pub mod sync_notes;
+#[cfg(any(test, feature = "selftest"))]
+pub mod testsupport;
+pub mod trace;
+pub mod transport;
+#[cfg(feature = "tui")]
+pub mod tui;
 pub mod updater;
+pub mod verify;
+pub mod watcher;
@@ -0,0 +1,52 @@
+//! `compat.line_endings`: keeps notes stored with LF whichever device
+//! saved them. The rules go into a block of the vault's `.gitattributes`,
+//! so every device applies them, and the files already stored with CRLF
+//! are converted in a single commit when the block is first written.
+
+use anyhow::{Result, bail};
+
+use crate::config::LineEndings;
+use crate::git::GitFacade;
+
+/// The `.gitattributes` rules for `lf`: git decides what is text, and
+/// notes and canvases always are.
+pub const LF_RULES: &[&str] = &[
+    "* text=auto eol=lf",
+    "*.md text eol=lf",
+    "*.canvas text eol=lf",
+];
+
+/// Writes the rules of `policy` into `.gitattributes` if they are not
+/// there yet, and commits them with the tracked files stored with CRLF
+/// converted. Returns the files converted; `None` when the vault already
+/// follows the policy. The vault must have nothing uncommitted, so the
+/// commit holds only the conversion.
+pub fn enforce(git: &GitFacade, policy: LineEndings) -> Result<Option<Vec<String>>> {
+    if policy == LineEndings::Off {
+        return Ok(None);
+    }
+    // Files git treats as text only by `text=auto` keep the CRLF they were
+    // stored with until they are renormalized.
+    if git.has_vault_attributes(LF_RULES) && git.crlf_files()?.is_empty() {
+        return Ok(None);
+    }
+    let pending = git.list_pending_paths()?;
+    if !pending.is_empty() {
+        bail!(
+            "the vault has uncommitted changes ({}); let the daemon commit them first",
+            pending.join(", ")
+        );
+    }
+    git.set_vault_attributes(LF_RULES)?;
+    git.renormalize()?;
+    git.stage_paths(&[".gitattributes".to_string()])?;
+    let converted: Vec<String> = git
+        .list_staged_files()?
+        .into_iter()
+        .filter(|path| path != ".gitattributes")
+        .collect();
+    if !git.commit("Normalize line endings to LF")? {
+        return Ok(None);
+    }
+    Ok(Some(converted))
+}
@@ -0,0 +1,219 @@
+//! `shared_vault.soft_locks`: advisory locks on the notes being edited, so
+//! members of a shared vault see that someone is in the middle of a note
+//! before two of them change it at once. Each device pushes the notes it
+//! edited in the last `lock_minutes` to a ref of its own,
+//! `refs/obsyncgit/locks/<device>`, outside the vault's branch and history,
+//! and fetches the other devices' refs after every sync. Nothing is
+//! refused: a locked note can still be edited, and a lock runs out by
+//! itself once its device stops editing, goes offline or is switched off.
+
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, RemoteKind};
+use crate::git::GitFacade;
+
+pub const REF_PREFIX: &str = "refs/obsyncgit/locks/";
+const FILE_NAME: &str = "locks.json";
+
+/// What a device pushes to its lock ref.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LockFile {
+    device: String,
+    author: Option<String>,
+    locks: Vec<LockEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LockEntry {
+    path: String,
+    /// RFC 3339; the first edit.
+    since: String,
+    /// RFC 3339; the last edit plus `lock_minutes`.
+    expires: String,
+}
+
+/// A note another device is editing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lock {
+    pub path: String,
+    pub device: String,
+    /// The member's name, when their device has one.
+    pub author: Option<String>,
+    pub expires: DateTime<Utc>,
+}
+
+impl Lock {
+    /// Who holds the lock, in words: `Anna on laptop`, or just the device.
+    pub fn holder(&self) -> String {
+        match &self.author {
+            Some(author) => format!("{author} on {}", self.device),
+            None => self.device.clone(),
+        }
+    }
+}
+
+/// This device's locks and the latest it heard of the others', as the
+/// daemon keeps them.
+#[derive(Debug)]
+pub struct LockBoard {
+    device: String,
+    author: Option<String>,
+    ttl: TimeDelta,
+    /// Path, first edit and expiry.
+    mine: BTreeMap<String, (DateTime<Utc>, DateTime<Utc>)>,
+    /// `mine` changed since it was last pushed.
+    changed: bool,
+    others: Vec<Lock>,
+    /// Other devices' locks on notes edited here, already warned about.
+    contested: HashSet<(String, String)>,
+}
+
+impl LockBoard {
+    /// `None` unless `shared_vault.soft_locks` is on. S3 and WebDAV
+    /// remotes have no refs to keep locks in.
+    pub fn new(config: &Config) -> Option<Self> {
+        let shared = &config.shared_vault;
+        if !shared.enabled || !shared.soft_locks || config.remote.kind != RemoteKind::Git {
+            return None;
+        }
+        let minutes = i64::try_from(shared.lock_minutes.max(1)).unwrap_or(i64::MAX);
+        Some(Self {
+            device: config.sync.device_name(),
+            author: config.git.author_name.clone(),
+            ttl: TimeDelta::try_minutes(minutes).unwrap_or(TimeDelta::MAX),
+            mine: BTreeMap::new(),
+            changed: false,
+            others: Vec::new(),
+            contested: HashSet::new(),
+        })
+    }
+
+    /// The name the other devices show next to this device's locks.
+    pub fn set_author(&mut self, author: String) {
+        self.author = Some(author);
+    }
+
+    /// Locks `edited`, or extends their locks. A lock is only extended once
+    /// half its time has passed, so not every save needs a push.
+    pub fn touch(&mut self, edited: &[String]) {
+        let now = Utc::now();
+        self.expire(now);
+        for path in edited {
+            match self.mine.get_mut(path) {
+                Some((_, expires)) if *expires - now > self.ttl / 2 => {}
+                Some((_, expires)) => {
+                    *expires = now + self.ttl;
+                    self.changed = true;
+                }
+                None => {
+                    self.mine.insert(path.clone(), (now, now + self.ttl));
+                    self.changed = true;
+                }
+            }
+        }
+    }
+
+    /// The other devices' locks on `edited` not warned about yet.
+    pub fn contested(&mut self, edited: &[String]) -> Vec<Lock> {
+        let now = Utc::now();
+        let current: Vec<&Lock> = self
+            .others
+            .iter()
+            .filter(|lock| lock.expires > now && edited.contains(&lock.path))
+            .collect();
+        self.contested.retain(|(path, device)| {
+            current
+                .iter()
+                .any(|lock| &lock.path == path && &lock.device == device)
+        });
+        current
+            .into_iter()
+            .filter(|lock| {
+                self.contested
+                    .insert((lock.path.clone(), lock.device.clone()))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Pushes this device's locks when they changed, then fetches the
+    /// other devices'.
+    pub fn share(&mut self, git: &GitFacade) -> Result<()> {
+        self.expire(Utc::now());
+        if self.changed {
+            let file = LockFile {
+                device: self.device.clone(),
+                author: self.author.clone(),
+                locks: self
+                    .mine
+                    .iter()
+                    .map(|(path, (since, expires))| LockEntry {
+                        path: path.clone(),
+                        since: since.to_rfc3339(),
+                        expires: expires.to_rfc3339(),
+                    })
+                    .collect(),
+            };
+            let contents = serde_json::to_string_pretty(&file)?;
+            git.push_file_ref(
+                &format!("{REF_PREFIX}{}", self.device),
+                FILE_NAME,
+                &contents,
+            )
+            .context("failed to push this device's locks")?;
+            self.changed = false;
+        }
+        git.fetch_refs(REF_PREFIX)
+            .context("failed to fetch the other devices' locks")?;
+        self.others = others(git, &self.device)?;
+        Ok(())
+    }
+
+    fn expire(&mut self, now: DateTime<Utc>) {
+        let before = self.mine.len();
+        self.mine.retain(|_, (_, expires)| *expires > now);
+        self.changed |= self.mine.len() != before;
+    }
+}
+
+/// The other devices' locks that have not run out, as of the last fetch,
+/// ordered by path.
+pub fn current(git: &GitFacade, config: &Config) -> Result<Vec<Lock>> {
+    others(git, &config.sync.device_name())
+}
+
+fn others(git: &GitFacade, own_device: &str) -> Result<Vec<Lock>> {
+    let now = Utc::now();
+    let mut locks = Vec::new();
+    for (name, commit) in git.list_refs(REF_PREFIX)? {
+        if name.strip_prefix(REF_PREFIX) == Some(own_device) {
+            continue;
+        }
+        let Some(contents) = git.show_file(&commit, FILE_NAME)? else {
+            continue;
+        };
+        let Ok(file) = serde_json::from_str::<LockFile>(&contents) else {
+            continue;
+        };
+        for entry in file.locks {
+            let Ok(expires) = DateTime::parse_from_rfc3339(&entry.expires) else {
+                continue;
+            };
+            let expires = expires.with_timezone(&Utc);
+            if expires > now {
+                locks.push(Lock {
+                    path: entry.path,
+                    device: file.device.clone(),
+                    author: file.author.clone(),
+                    expires,
+                });
+            }
+        }
+    }
+    locks.sort_by(|a, b| a.path.cmp(&b.path).then_with(|| a.device.cmp(&b.device)));
+    Ok(locks)
+}
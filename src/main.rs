@@ -1,14 +1,74 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use anyhow::{Context, Result, bail};
-use camino::Utf8PathBuf;
-use clap::{Parser, Subcommand};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
 use directories::BaseDirs;
-use obsyncgit::config::{CommitConfig, Config, GitOptions, IgnoreConfig, SelfUpdateConfig};
+use obsyncgit::alerts::{self, Alert, Channel};
+use obsyncgit::backup;
+#[cfg(feature = "selftest")]
+use obsyncgit::bench;
+use obsyncgit::case_collisions;
+use obsyncgit::cloud;
+use obsyncgit::config::{
+    AlertsConfig, BackupConfig, BootstrapConfig, CommitConfig, CompatConfig, Config,
+    ConflictCopyPolicy, DeletionConfig, DigestConfig, DiscordAlertConfig, DiskConfig, GitOptions,
+    GuiConfig, IgnoreConfig, IntegrityConfig, LaunchdConfig, LineEndings, MqttConfig,
+    NetworkConfig, PowerConfig, RemoteAvailabilityConfig, RemoteConfig, RetentionConfig,
+    ScheduleConfig, SecurityConfig, SelfUpdateConfig, SharedConfigOptions, SharedVaultConfig,
+    SnapshotConfig, StabilityConfig, SubmoduleConfig, SyncConfig, TelegramAlertConfig, TraceConfig,
+    WatcherConfig,
+};
+use obsyncgit::conflict_copies::{self, ConflictCopy};
+use obsyncgit::conflicts::{self, Resolution};
+use obsyncgit::connectivity;
+use obsyncgit::control::ControlInbox;
 use obsyncgit::daemon::SyncDaemon;
-use obsyncgit::updater::SelfUpdateManager;
+use obsyncgit::digest;
+use obsyncgit::disk;
+use obsyncgit::events::{Event, EventLog, Record};
+use obsyncgit::file_names;
+use obsyncgit::git::{GitFacade, LARGE_VAULT_FILES};
+use obsyncgit::host_keys::{self, SshHost};
+use obsyncgit::ignore::{self, Blocked, IgnoreMatcher, RuleSource};
+use obsyncgit::import::{self, OnCollision};
+use obsyncgit::instance::{self, VaultLock};
+use obsyncgit::integrity;
+use obsyncgit::launchd::{self, LaunchAgent};
+use obsyncgit::locks;
+use obsyncgit::mqtt;
+use obsyncgit::network;
+use obsyncgit::normalization;
+use obsyncgit::power;
+use obsyncgit::preflight;
+use obsyncgit::provider::{NewRepo, Provider, ProviderClient};
+use obsyncgit::redaction;
+use obsyncgit::replica;
+use obsyncgit::retention;
+use obsyncgit::secret_scan::SecretScanner;
+use obsyncgit::secrets;
+use obsyncgit::service;
+use obsyncgit::setup_bundle::{self, SetupBundle};
+use obsyncgit::shared_config::SharedPolicy;
+use obsyncgit::shared_vault;
+use obsyncgit::snapshots;
+use obsyncgit::statusbar::SyncHealth;
+use obsyncgit::sync_notes;
+#[cfg(feature = "selftest")]
+use obsyncgit::testsupport::{self, Simulation};
+use obsyncgit::tr;
+#[cfg(feature = "tui")]
+use obsyncgit::tui;
+use obsyncgit::updater::{SelfUpdateManager, restart_daemon};
+use obsyncgit::watcher::{self, WatchBudget};
 use tracing::{info, warn};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
 const BIN_NAME: &str = env!("CARGO_BIN_NAME");
 
@@ -26,24 +86,386 @@ struct Cli {
 #[derive(Subcommand, Debug, Clone)]
 enum Command {
     /// Run the background synchronizer (default)
-    Run,
+    Run {
+        /// Container mode: JSON logs on stdout and no self-update
+        #[arg(long)]
+        foreground: bool,
+        /// Read the whole configuration from OBSYNCGIT_* environment variables
+        #[arg(long, requires = "foreground")]
+        config_from_env: bool,
+        /// Record every git command of recent cycles for `status --trace`
+        #[arg(long)]
+        trace_sync: bool,
+        /// Stop the daemon already syncing this vault and replace it
+        #[arg(long)]
+        takeover: bool,
+    },
+    /// Check the environment for common problems
+    Doctor,
+    /// Push a test commit to a throwaway branch and delete it, to check access to the remote
+    Verify,
+    /// Fetch the SSH host keys of the remote, show their fingerprints and add them to known_hosts
+    TrustHost {
+        /// Trust the keys without asking
+        #[arg(long)]
+        yes: bool,
+        /// Replace keys already pinned for the host, after it changed them
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Commit and sync edits made while the daemon was not running
+    Rescan,
+    /// Check the repository with git fsck and, if it is damaged, replace it with a fresh clone keeping local changes
+    Repair {
+        /// Replace the repository even when git fsck finds nothing wrong
+        #[arg(long)]
+        force: bool,
+    },
+    /// Drop old versions of the attachments under retention.paths from the history and force-push it, so clones stay small
+    PruneHistory {
+        /// Only show what would be dropped
+        #[arg(long)]
+        dry_run: bool,
+        /// Rewrite without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// List the daily snapshot tags made under snapshots.tag_daily
+    Snapshots {
+        #[command(subcommand)]
+        command: SnapshotsCommand,
+    },
+    /// Write a git bundle of the repository to backup.dir, or clone a vault back out of one
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// Start the daemon at boot as a Windows service, or at login as a macOS LaunchAgent
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommand,
+    },
+    /// Run the daemon with an interactive terminal view; sets up a config on first run
+    #[cfg(feature = "tui")]
+    Tui {
+        /// Stop the daemon already syncing this vault and replace it
+        #[arg(long)]
+        takeover: bool,
+    },
+    /// Show the state of the vault repository
+    Status {
+        /// Print the git commands of the last traced sync cycles
+        #[arg(long)]
+        trace: bool,
+        /// text, waybar (JSON for a custom module) or xbar (also SwiftBar)
+        #[arg(long, default_value = "text")]
+        format: StatusFormat,
+    },
     /// Create a starter configuration file
     Install {
         /// Overwrite an existing file
         #[arg(long)]
         force: bool,
     },
+    /// Write the configuration to a bundle for setting up another device
+    ExportSetup {
+        /// Bundle file to write (conventionally *.ogx)
+        path: Utf8PathBuf,
+        /// Also print the bundle as a one-line setup code
+        #[arg(long)]
+        code: bool,
+    },
+    /// Create the configuration from a bundle written by `export-setup`
+    ImportSetup {
+        /// Bundle file, or a setup code printed by `export-setup --code`
+        source: String,
+        /// Put the vault here instead of where it was on the exporting device
+        #[arg(long, value_name = "DIR")]
+        workdir: Option<Utf8PathBuf>,
+        /// Overwrite an existing configuration
+        #[arg(long)]
+        force: bool,
+    },
     /// Manually trigger a binary self-update
     Update {
         /// Force the updater even if auto-updates are disabled
         #[arg(long)]
         force: bool,
     },
+    /// Create the vault repository on GitHub, Gitea or GitLab and point the config at it
+    InitRemote {
+        /// github, gitea or gitlab
+        #[arg(long)]
+        provider: Provider,
+        /// Repository name (defaults to the vault folder name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Organization or group to create the repository in
+        #[arg(long)]
+        owner: Option<String>,
+        /// Create a private repository
+        #[arg(long)]
+        private: bool,
+        /// Enable Git LFS for the repository where the provider has a per-repository switch
+        #[arg(long)]
+        lfs: bool,
+        /// API root for self-hosted instances (e.g. https://gitea.example.com)
+        #[arg(long, value_name = "URL")]
+        api_url: Option<String>,
+        /// API token or `!keyring:`/`!env:` reference (defaults to GITHUB_TOKEN, GITEA_TOKEN or GITLAB_TOKEN)
+        #[arg(long)]
+        token: Option<String>,
+        /// Store the HTTPS clone URL instead of the SSH one
+        #[arg(long)]
+        https: bool,
+    },
+    #[cfg(feature = "selftest")]
+    /// Run the end-to-end sync checks against throwaway local repositories
+    #[command(hide = true)]
+    Selftest,
+    #[cfg(feature = "selftest")]
+    /// Time sync cycles against a generated vault in a throwaway sandbox
+    #[command(hide = true)]
+    Bench {
+        /// Notes in the generated vault
+        #[arg(long, default_value_t = 1000)]
+        files: usize,
+        /// Single-note edits to time
+        #[arg(long, default_value_t = 10)]
+        cycles: usize,
+    },
+    /// Git clean/smudge filter for security.redaction; git runs it
+    #[command(hide = true)]
+    Filter {
+        #[command(subcommand)]
+        command: FilterCommand,
+    },
+    #[cfg(feature = "selftest")]
+    /// Developer tools for reproducing reported failures
+    #[command(hide = true)]
+    Debug {
+        #[command(subcommand)]
+        command: DebugCommand,
+    },
     /// Inspect or change configuration values
     Settings {
         #[command(subcommand)]
         command: SettingsCommand,
     },
+    /// List notes changed here and on another device, or choose which version to keep
+    Conflicts {
+        /// Keep this device's version of PATH
+        #[arg(long, value_name = "PATH")]
+        mine: Vec<PathBuf>,
+        /// Keep the remote version of PATH
+        #[arg(long, value_name = "PATH")]
+        theirs: Vec<PathBuf>,
+        /// Keep both; the remote version is saved as "<name> (other device)"
+        #[arg(long, value_name = "PATH")]
+        both: Vec<PathBuf>,
+    },
+    /// List paths that differ only in letter case, which macOS and Windows cannot hold side by side
+    CaseCollisions {
+        /// Rename all but one spelling of each, e.g. to "note (2).md", and commit the result
+        #[arg(long)]
+        rename: bool,
+    },
+    /// List files whose names Windows cannot check out
+    FileNames {
+        /// Rename them, e.g. "a: b.md" to "a： b.md"; the daemon commits the renames
+        #[arg(long)]
+        rename: bool,
+    },
+    /// List file names spelled in the decomposed Unicode form macOS can produce
+    UnicodeNames {
+        /// Rename them to the composed form and commit the result
+        #[arg(long)]
+        rename: bool,
+    },
+    /// Check whether a path is left out of commits, and which rules say so
+    Ignore {
+        #[command(subcommand)]
+        command: IgnoreCommand,
+    },
+    /// Show the saved versions of a note
+    Log {
+        /// Path inside the vault (absolute or vault-relative)
+        path: PathBuf,
+        /// Maximum number of versions to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// List recent commits with the device, file count and delay recorded under commit.notes
+    History {
+        /// Only commits touching this path inside the vault
+        path: Option<PathBuf>,
+        /// Maximum number of commits to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show what the daemon noticed and did: changes, commits, pushes, pulls and errors
+    Events {
+        /// Keep printing events as the daemon records them
+        #[arg(long)]
+        follow: bool,
+        /// One JSON object per line, for other programs
+        #[arg(long)]
+        json: bool,
+        /// Number of past events to show first
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Bring back an earlier version of a note, including deleted ones
+    Restore {
+        /// Path inside the vault (absolute or vault-relative)
+        path: PathBuf,
+        /// Restore the version saved at this time (e.g. "2024-05-01 18:00" or "yesterday")
+        #[arg(long, conflicts_with_all = ["commit", "snapshot"])]
+        at: Option<String>,
+        /// Restore the version from this commit, as listed by `obsyncgit log`
+        #[arg(long, conflicts_with = "snapshot")]
+        commit: Option<String>,
+        /// Restore the version from the end of this day (YYYY-MM-DD), as listed by `obsyncgit snapshots list`
+        #[arg(long)]
+        snapshot: Option<String>,
+    },
+    /// Write the vault's files at any point in history to a zip or tar.gz archive
+    Export {
+        /// Archive to write (defaults to VAULT-COMMIT.zip in the current directory)
+        output: Option<PathBuf>,
+        /// zip or tar.gz (defaults to the extension of OUTPUT, otherwise zip)
+        #[arg(long)]
+        format: Option<ArchiveFormat>,
+        /// Export this commit, as listed by `obsyncgit log`, or the state at this time
+        #[arg(long)]
+        at: Option<String>,
+    },
+    /// Copy notes from a folder or zip archive into the vault and commit them
+    Import {
+        /// Folder or .zip file to import from
+        source: PathBuf,
+        /// newer (keep the later-modified version) or keep-both (save the imported one as "<name> (imported)")
+        #[arg(long, default_value = "newer")]
+        on_collision: OnCollision,
+        /// List what would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the sync digest of the current period, or send it now
+    Digest {
+        /// Send it now and start a new period
+        #[arg(long)]
+        send: bool,
+    },
+    /// Show where alerts about failing syncs and conflicts go
+    Alerts {
+        /// Send a test alert
+        #[arg(long)]
+        test: bool,
+    },
+    /// Print a completion script for bash, zsh, fish, elvish or powershell
+    Completions { shell: Shell },
+    /// Print the manual page
+    Man {
+        /// Write obsyncgit.1 and a page per subcommand into DIR instead
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum ServiceCommand {
+    /// Register the daemon with the service control manager
+    Install {
+        /// Install as a Windows service rather than a login task
+        #[arg(long)]
+        windows_service: bool,
+        /// Install the macOS LaunchAgent, with the options in the config's launchd section
+        #[arg(long, conflicts_with = "windows_service")]
+        launch_agent: bool,
+        /// Account to run as, e.g. .\alice; asks for its password. Defaults to LocalSystem
+        #[arg(long, value_name = "USER", requires = "windows_service")]
+        account: Option<String>,
+        /// Replace the installed service or agent
+        #[arg(long)]
+        force: bool,
+    },
+    /// Stop the service and remove it
+    Uninstall,
+    /// Start the installed service
+    Start,
+    /// Stop the service, waiting for the sync cycle in progress
+    Stop,
+    /// Show whether the service is installed and running
+    Status,
+    /// Entry point for the service control manager
+    #[command(hide = true)]
+    Run,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum BackupCommand {
+    /// Write a bundle now, whatever the schedule
+    Now,
+    /// Clone the vault out of a bundle; the remote is set to repo_url again
+    Restore {
+        /// Bundle to restore (defaults to the newest one in backup.dir)
+        bundle: Option<PathBuf>,
+        /// Restore into DIR (defaults to the vault when it is gone, otherwise a folder beside it)
+        #[arg(long, value_name = "DIR")]
+        to: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum SnapshotsCommand {
+    /// List the days tagged, newest first
+    List,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum IgnoreCommand {
+    /// Print whether PATH is synced or ignored
+    Test {
+        /// Path inside the vault (absolute or vault-relative)
+        path: PathBuf,
+    },
+    /// List the builtin, ignore.globs and .gitignore patterns matching PATH
+    Explain {
+        /// Path inside the vault (absolute or vault-relative)
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum FilterCommand {
+    /// Copy stdin to stdout without the redacted lines
+    Clean {
+        #[arg(long)]
+        rules: PathBuf,
+        /// Vault-relative path git is filtering
+        path: String,
+    },
+    /// Copy stdin to stdout with the redacted lines put back
+    Smudge {
+        #[arg(long)]
+        rules: PathBuf,
+        path: String,
+    },
+}
+
+#[cfg(feature = "selftest")]
+#[derive(Subcommand, Debug, Clone)]
+enum DebugCommand {
+    /// Recreate a failure mode in a temporary sandbox and run the sync cycle on it
+    Simulate {
+        /// diverged, conflict, force-push or index-lock
+        #[arg(long)]
+        scenario: Simulation,
+        /// Keep the sandbox directory for inspection
+        #[arg(long)]
+        keep: bool,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -52,6 +474,15 @@ enum SettingsCommand {
     Show,
     /// Update a configuration value (e.g. self-update.enabled true)
     Set { key: SettingsKey, value: String },
+    /// Publish this device's policy settings to the vault's shared config
+    Share,
+    /// Store a secret in the OS keyring for use as `!keyring:<name>`
+    SetSecret {
+        name: String,
+        /// Read the secret from this environment variable instead of prompting
+        #[arg(long, value_name = "VAR")]
+        from_env: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -59,11 +490,136 @@ enum SettingsKey {
     RepoUrl,
     Branch,
     Remote,
+    RemoteKind,
     Workdir,
     SelfUpdateEnabled,
     SelfUpdateIntervalHours,
     SelfUpdateCommand,
+    SelfUpdateShell,
+    SelfUpdateChannel,
+    SelfUpdateAllowPrerelease,
+    SelfUpdateAllowUnsigned,
+    SelfUpdatePinVersion,
     GitSshKeyPath,
+    GitStatusCache,
+    GitRunHooks,
+    SharedConfigEnabled,
+    SyncMode,
+    SyncTopology,
+    SyncDeviceName,
+    SyncMeteredPolicy,
+    CommitMode,
+    CommitAmendWindowMinutes,
+    CommitNotes,
+    CommitSignoff,
+    IgnoreMaxFileSizeMb,
+    SyncCaseCollisions,
+    SyncFileNames,
+    SyncConflictCopies,
+    CompatUnicodeNormalization,
+    CompatLineEndings,
+    DiskMinFreeMb,
+    IntegrityCheckIntervalHours,
+    IntegrityAutoRepair,
+    BackupDir,
+    BackupIntervalHours,
+    BackupKeep,
+    PowerLowBatteryPercent,
+    DigestEnabled,
+    DigestIntervalHours,
+    DigestDesktop,
+    DigestWebhookUrl,
+    MqttEnabled,
+    MqttBroker,
+    MqttTopic,
+    MqttUsername,
+    MqttPassword,
+    MqttHomeAssistant,
+    AlertsAfterFailures,
+    AlertsOnConflict,
+    AlertsEmailTo,
+    AlertsEmailFrom,
+    AlertsEmailServer,
+    AlertsEmailPort,
+    AlertsEmailTls,
+    AlertsEmailUsername,
+    AlertsEmailPassword,
+    AlertsTelegramBotToken,
+    AlertsTelegramChatId,
+    AlertsTelegramTemplate,
+    AlertsDiscordWebhookUrl,
+    AlertsDiscordTemplate,
+    SnapshotsTagDaily,
+    SharedVaultEnabled,
+    SharedVaultUserFolder,
+    SharedVaultSoftLocks,
+    DeletionsMode,
+    WatcherBackend,
+    GuiTheme,
+    GuiHighContrast,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusFormat {
+    Text,
+    Waybar,
+    Xbar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// The extension, which is also the name `git archive` knows it by.
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "zip" => Ok(Self::Zip),
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            other => Err(format!(
+                "unknown archive format: {other} (expected zip or tar.gz)"
+            )),
+        }
+    }
+}
+
+impl FromStr for StatusFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "waybar" => Ok(Self::Waybar),
+            "xbar" | "swiftbar" => Ok(Self::Xbar),
+            other => Err(format!(
+                "unknown status format: {other} (expected text, waybar or xbar)"
+            )),
+        }
+    }
 }
 
 impl FromStr for SettingsKey {
@@ -74,13 +630,85 @@ impl FromStr for SettingsKey {
             "repo-url" => Ok(Self::RepoUrl),
             "branch" => Ok(Self::Branch),
             "remote" => Ok(Self::Remote),
+            "remote.kind" | "remote-kind" => Ok(Self::RemoteKind),
             "workdir" | "work-dir" => Ok(Self::Workdir),
             "self-update.enabled" | "self-update-enabled" => Ok(Self::SelfUpdateEnabled),
             "self-update.interval-hours" | "self-update-interval" | "self-update.interval" => {
                 Ok(Self::SelfUpdateIntervalHours)
             }
             "self-update.command" | "self-update-command" => Ok(Self::SelfUpdateCommand),
+            "self-update.shell" | "self-update-shell" => Ok(Self::SelfUpdateShell),
+            "self-update.channel" | "self-update-channel" => Ok(Self::SelfUpdateChannel),
+            "self-update.allow-prerelease" | "self-update-allow-prerelease" => {
+                Ok(Self::SelfUpdateAllowPrerelease)
+            }
+            "self-update.allow-unsigned" => Ok(Self::SelfUpdateAllowUnsigned),
+            "self-update.pin-version" | "self-update-pin-version" | "self-update.pin" => {
+                Ok(Self::SelfUpdatePinVersion)
+            }
             "git.ssh-key" | "git.ssh-key-path" | "ssh-key" => Ok(Self::GitSshKeyPath),
+            "git.status-cache" | "status-cache" => Ok(Self::GitStatusCache),
+            "git.run-hooks" | "run-hooks" => Ok(Self::GitRunHooks),
+            "shared-config.enabled" | "shared-config" => Ok(Self::SharedConfigEnabled),
+            "sync.mode" | "sync-mode" => Ok(Self::SyncMode),
+            "sync.topology" | "topology" => Ok(Self::SyncTopology),
+            "sync.device-name" | "device-name" => Ok(Self::SyncDeviceName),
+            "sync.metered-policy" | "metered-policy" => Ok(Self::SyncMeteredPolicy),
+            "commit.mode" | "commit-mode" => Ok(Self::CommitMode),
+            "commit.amend-window-minutes" | "amend-window" => Ok(Self::CommitAmendWindowMinutes),
+            "commit.notes" | "notes" => Ok(Self::CommitNotes),
+            "commit.signoff" | "signoff" => Ok(Self::CommitSignoff),
+            "ignore.max-file-size-mb" | "max-file-size" => Ok(Self::IgnoreMaxFileSizeMb),
+            "sync.case-collisions" | "case-collisions" => Ok(Self::SyncCaseCollisions),
+            "sync.file-names" | "file-names" => Ok(Self::SyncFileNames),
+            "sync.conflict-copies" | "conflict-copies" => Ok(Self::SyncConflictCopies),
+            "compat.unicode-normalization" | "unicode-normalization" => {
+                Ok(Self::CompatUnicodeNormalization)
+            }
+            "compat.line-endings" | "line-endings" => Ok(Self::CompatLineEndings),
+            "disk.min-free-mb" | "min-free-mb" => Ok(Self::DiskMinFreeMb),
+            "integrity.check-interval-hours" | "fsck-interval" => {
+                Ok(Self::IntegrityCheckIntervalHours)
+            }
+            "integrity.auto-repair" | "auto-repair" => Ok(Self::IntegrityAutoRepair),
+            "backup.dir" | "backup-dir" => Ok(Self::BackupDir),
+            "backup.interval-hours" | "backup-interval" => Ok(Self::BackupIntervalHours),
+            "backup.keep" => Ok(Self::BackupKeep),
+            "power.low-battery-percent" | "low-battery" => Ok(Self::PowerLowBatteryPercent),
+            "digest.enabled" | "digest" => Ok(Self::DigestEnabled),
+            "digest.interval-hours" | "digest-interval" => Ok(Self::DigestIntervalHours),
+            "digest.desktop" => Ok(Self::DigestDesktop),
+            "digest.webhook-url" | "digest-webhook" => Ok(Self::DigestWebhookUrl),
+            "mqtt.enabled" | "mqtt" => Ok(Self::MqttEnabled),
+            "mqtt.broker" | "mqtt-broker" => Ok(Self::MqttBroker),
+            "mqtt.topic" | "mqtt-topic" => Ok(Self::MqttTopic),
+            "mqtt.username" | "mqtt-username" => Ok(Self::MqttUsername),
+            "mqtt.password" | "mqtt-password" => Ok(Self::MqttPassword),
+            "mqtt.home-assistant" | "home-assistant" => Ok(Self::MqttHomeAssistant),
+            "alerts.after-failures" | "alert-after-failures" => Ok(Self::AlertsAfterFailures),
+            "alerts.on-conflict" | "alert-on-conflict" => Ok(Self::AlertsOnConflict),
+            "alerts.email.to" | "alert-email" => Ok(Self::AlertsEmailTo),
+            "alerts.email.from" => Ok(Self::AlertsEmailFrom),
+            "alerts.email.server" | "smtp-server" => Ok(Self::AlertsEmailServer),
+            "alerts.email.port" | "smtp-port" => Ok(Self::AlertsEmailPort),
+            "alerts.email.tls" | "smtp-tls" => Ok(Self::AlertsEmailTls),
+            "alerts.email.username" | "smtp-username" => Ok(Self::AlertsEmailUsername),
+            "alerts.email.password" | "smtp-password" => Ok(Self::AlertsEmailPassword),
+            "alerts.telegram.bot-token" | "telegram-bot-token" => Ok(Self::AlertsTelegramBotToken),
+            "alerts.telegram.chat-id" | "telegram-chat-id" => Ok(Self::AlertsTelegramChatId),
+            "alerts.telegram.template" => Ok(Self::AlertsTelegramTemplate),
+            "alerts.discord.webhook-url" | "discord-webhook-url" => {
+                Ok(Self::AlertsDiscordWebhookUrl)
+            }
+            "alerts.discord.template" => Ok(Self::AlertsDiscordTemplate),
+            "snapshots.tag-daily" | "snapshots" => Ok(Self::SnapshotsTagDaily),
+            "shared-vault.enabled" | "shared-vault" => Ok(Self::SharedVaultEnabled),
+            "shared-vault.user-folder" | "user-folder" => Ok(Self::SharedVaultUserFolder),
+            "shared-vault.soft-locks" | "soft-locks" => Ok(Self::SharedVaultSoftLocks),
+            "deletions.mode" | "deletion-mode" => Ok(Self::DeletionsMode),
+            "watcher.backend" | "watcher" => Ok(Self::WatcherBackend),
+            "gui.theme" | "theme" => Ok(Self::GuiTheme),
+            "gui.high-contrast" | "high-contrast" => Ok(Self::GuiHighContrast),
             other => Err(format!("unknown configuration key: {other}")),
         }
     }
@@ -88,33 +716,205 @@ impl FromStr for SettingsKey {
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    init_logging();
-
     let Cli { config, command } = cli;
-    match command.unwrap_or(Command::Run) {
-        Command::Run => handle_run(config),
+    let command = command.unwrap_or(Command::Run {
+        foreground: false,
+        config_from_env: false,
+        trace_sync: false,
+        takeover: false,
+    });
+    #[cfg(feature = "tui")]
+    let tui_log = tui::LogBuffer::new();
+    let writer = match &command {
+        #[cfg(feature = "tui")]
+        Command::Tui { .. } => BoxMakeWriter::new(tui_log.clone()),
+        Command::Service {
+            command: ServiceCommand::Run,
+        } => service_log_writer(config.clone()),
+        _ => BoxMakeWriter::new(std::io::stderr),
+    };
+    init_logging(
+        matches!(
+            command,
+            Command::Run {
+                foreground: true,
+                ..
+            }
+        ),
+        writer,
+    );
+
+    match command {
+        Command::Run {
+            foreground,
+            config_from_env,
+            trace_sync,
+            takeover,
+        } => handle_run(config, foreground, config_from_env, trace_sync, takeover),
+        Command::Status { trace, format } => handle_status(config, trace, format),
+        Command::Doctor => handle_doctor(config),
+        Command::Verify => handle_verify(config),
+        Command::Rescan => handle_rescan(config),
+        Command::TrustHost { yes, replace } => handle_trust_host(config, yes, replace),
+        Command::Repair { force } => handle_repair(config, force),
+        Command::PruneHistory { dry_run, yes } => handle_prune_history(config, dry_run, yes),
+        Command::Snapshots { command } => handle_snapshots(config, command),
+        Command::Backup { command } => handle_backup(config, command),
+        Command::Service { command } => handle_service(config, command),
+        #[cfg(feature = "tui")]
+        Command::Tui { takeover } => handle_tui(config, takeover, tui_log),
         Command::Install { force } => handle_install(config, force),
+        Command::ExportSetup { path, code } => handle_export_setup(config, &path, code),
+        Command::ImportSetup {
+            source,
+            workdir,
+            force,
+        } => handle_import_setup(config, &source, workdir, force),
         Command::Update { force } => handle_update(config, force),
+        Command::InitRemote {
+            provider,
+            name,
+            owner,
+            private,
+            lfs,
+            api_url,
+            token,
+            https,
+        } => handle_init_remote(
+            config,
+            provider,
+            NewRepoArgs {
+                name,
+                owner,
+                private,
+                lfs,
+                api_url,
+                token,
+                https,
+            },
+        ),
+        #[cfg(feature = "selftest")]
+        Command::Selftest => handle_selftest(),
+        #[cfg(feature = "selftest")]
+        Command::Bench { files, cycles } => handle_bench(files, cycles),
+        #[cfg(feature = "selftest")]
+        Command::Debug {
+            command: DebugCommand::Simulate { scenario, keep },
+        } => handle_simulate(scenario, keep),
         Command::Settings { command } => handle_settings(config, command),
+        Command::Conflicts { mine, theirs, both } => handle_conflicts(
+            config,
+            &[
+                (Resolution::Mine, mine),
+                (Resolution::Theirs, theirs),
+                (Resolution::Both, both),
+            ],
+        ),
+        Command::CaseCollisions { rename } => handle_case_collisions(config, rename),
+        Command::FileNames { rename } => handle_file_names(config, rename),
+        Command::UnicodeNames { rename } => handle_unicode_names(config, rename),
+        Command::Ignore { command } => handle_ignore(config, command),
+        Command::Filter { command } => match command {
+            FilterCommand::Clean { rules, path } => redaction::clean(&rules, &path),
+            FilterCommand::Smudge { rules, path } => redaction::smudge(&rules, &path),
+        },
+        Command::Log { path, limit } => handle_log(config, &path, limit),
+        Command::History { path, limit } => handle_history(config, path.as_deref(), limit),
+        Command::Events {
+            follow,
+            json,
+            limit,
+        } => handle_events(config, follow, json, limit),
+        Command::Restore {
+            path,
+            at,
+            commit,
+            snapshot,
+        } => handle_restore(
+            config,
+            &path,
+            at.as_deref(),
+            commit.as_deref(),
+            snapshot.as_deref(),
+        ),
+        Command::Export { output, format, at } => {
+            handle_export(config, output.as_deref(), format, at.as_deref())
+        }
+        Command::Import {
+            source,
+            on_collision,
+            dry_run,
+        } => handle_import(config, &source, on_collision, dry_run),
+        Command::Digest { send } => handle_digest(config, send),
+        Command::Alerts { test } => handle_alerts(config, test),
+        Command::Completions { shell } => handle_completions(shell),
+        Command::Man { out_dir } => handle_man(out_dir.as_deref()),
     }
 }
 
-fn handle_run(config_arg: Option<Utf8PathBuf>) -> Result<()> {
-    let (config, config_path) = Config::detect_and_load(config_arg.clone())?;
-    info!(path = %config_path, "configuration loaded");
+fn handle_run(
+    config_arg: Option<Utf8PathBuf>,
+    foreground: bool,
+    config_from_env: bool,
+    trace_sync: bool,
+    takeover: bool,
+) -> Result<()> {
+    let (mut config, config_path) = if config_from_env {
+        let config = Config::from_env()?;
+        info!("configuration loaded from environment");
+        (config, None)
+    } else {
+        let (config, path) = Config::detect_and_load(config_arg)?;
+        info!(path = %path, "configuration loaded");
+        (config, Some(path))
+    };
+    config.resolve_secrets()?;
+    config.trace.enabled |= trace_sync;
+    // SAFETY: no other threads exist yet.
+    unsafe { network::export_proxy_env(&config.network) };
+
+    if foreground {
+        if std::process::id() == 1 {
+            warn!(
+                "running as PID 1; start the container with an init such as tini (`docker run --init`) so orphaned git and ssh processes are reaped"
+            );
+        }
+        let state_dir = config.state_dir()?;
+        std::fs::create_dir_all(&state_dir)
+            .with_context(|| format!("failed to create state directory {state_dir}"))?;
+    }
 
+    let lock = if takeover {
+        VaultLock::takeover(&config)?
+    } else {
+        VaultLock::acquire(&config)?
+    };
+
+    // Resolve before any update swaps the file underneath us.
+    let current_exe = std::env::current_exe().context("failed to locate running executable")?;
     let daemon = SyncDaemon::new(config.clone())?;
     let shutdown = daemon.shutdown_handle();
-    let update_handle =
-        SelfUpdateManager::spawn_if_enabled(&config.self_update, &config_path, shutdown.clone());
+    let restart = Arc::new(AtomicBool::new(false));
+    let update_handle = match &config_path {
+        Some(path) if !foreground => {
+            SelfUpdateManager::spawn_if_enabled(&config, path, shutdown.clone(), restart.clone())
+        }
+        _ => None,
+    };
 
     daemon.run()?;
-    shutdown.store(true, Ordering::SeqCst);
+    shutdown.trigger();
     if let Some(handle) = update_handle
         && let Err(err) = handle.join()
     {
         warn!(?err, "self-update worker exited unexpectedly");
     }
+    // The relaunched daemon claims the vault again.
+    drop(lock);
+    if restart.load(Ordering::SeqCst) {
+        info!(exe = %current_exe.display(), "relaunching updated daemon");
+        restart_daemon(&current_exe)?;
+    }
     Ok(())
 }
 
@@ -128,82 +928,2200 @@ fn handle_install(config_arg: Option<Utf8PathBuf>, force: bool) -> Result<()> {
     }
     let cfg = default_config();
     cfg.save_to_path(&path)?;
-    println!("Created configuration at {path}. Edit this file before running `obsyncgit run`.");
+    println!("{}", tr!("cli-config-created", path = path.to_string()));
     Ok(())
 }
 
-fn handle_update(config_arg: Option<Utf8PathBuf>, force: bool) -> Result<()> {
-    let (config, config_path) = Config::detect_and_load(config_arg)?;
-    if !config.self_update.enabled && !force {
+fn handle_export_setup(config_arg: Option<Utf8PathBuf>, path: &Utf8Path, code: bool) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let (bundle, notes) = SetupBundle::export(&config)?;
+    std::fs::write(path, bundle.to_file_contents()?)
+        .with_context(|| format!("failed to write setup bundle to {path}"))?;
+    println!("{}", tr!("cli-bundle-written", path = path.to_string()));
+    for key in &notes.stripped_credentials {
+        println!(
+            "Left out the credentials embedded in {key}; store them on the new device with `obsyncgit settings set-secret` and a `!keyring:` reference."
+        );
+    }
+    for key in &notes.stripped_secrets {
+        println!(
+            "Left out {key}, which holds a secret; set it on the new device with `obsyncgit settings set-secret` and a `!keyring:` reference."
+        );
+    }
+    if !bundle.keyring_secrets.is_empty() {
         println!(
-            "Auto-updates are disabled in the configuration. Re-run with --force or enable them via \"obsyncgit settings set self-update.enabled true\"."
+            "The new device needs these keyring secrets: {}",
+            bundle.keyring_secrets.join(", ")
+        );
+    }
+    if code {
+        println!("\nSetup code (paste it into `obsyncgit import-setup` on the new device):\n");
+        println!("{}", bundle.to_code()?);
+    }
+    Ok(())
+}
+
+fn handle_import_setup(
+    config_arg: Option<Utf8PathBuf>,
+    source: &str,
+    workdir: Option<Utf8PathBuf>,
+    force: bool,
+) -> Result<()> {
+    let path = Config::resolve_path(config_arg)?;
+    if path.exists() && !force {
+        bail!(
+            "configuration already exists at {} (use --force to overwrite)",
+            path
         );
+    }
+    let bundle = SetupBundle::read(source)?;
+    let secrets = bundle.keyring_secrets.clone();
+    let config = bundle.into_config(workdir)?;
+    config.save_to_path(&path)?;
+    println!(
+        "{}",
+        tr!(
+            "cli-bundle-imported",
+            path = path.to_string(),
+            workdir = config.workdir.to_string()
+        )
+    );
+    for name in &secrets {
+        if secrets::load(name).is_err() {
+            println!("Store the secret '{name}' with `obsyncgit settings set-secret {name}`.");
+        }
+    }
+    if let Some((_, lines)) = clone_estimate(&config) {
+        for line in lines {
+            println!("{line}");
+        }
+    }
+    if let Some(steps) = setup_bundle::ssh_instructions(&config) {
+        println!("\n{steps}\n");
+    }
+    println!("{}", tr!("cli-bundle-start"));
+    Ok(())
+}
+
+/// What the first clone of the vault will take, in words, and whether the
+/// disk has room for it; `None` once the vault is cloned.
+fn clone_estimate(config: &Config) -> Option<(bool, Vec<String>)> {
+    let mut config = config.clone();
+    // Secrets not stored yet leave the API asked without a token.
+    let _ = config.resolve_secrets();
+    let git = GitFacade::new(&config).ok()?;
+    if git.has_repo() {
+        return None;
+    }
+    let estimate = match preflight::estimate(&git, &config) {
+        Ok(estimate) => estimate,
+        Err(err) => {
+            let reason = format!("{err:#}");
+            return Some((true, vec![tr!("cli-preflight-unknown", reason = reason)]));
+        }
+    };
+    let Some(needed) = estimate.needed() else {
+        let reason = estimate.unknown.unwrap_or_default();
+        return Some((true, vec![tr!("cli-preflight-unknown", reason = reason)]));
+    };
+    if estimate.empty {
+        return Some((true, vec![tr!("cli-preflight-empty")]));
+    }
+    let mut lines = vec![match estimate.lfs_bytes.filter(|bytes| *bytes > 0) {
+        Some(lfs) => tr!(
+            "cli-preflight-needed-lfs",
+            needed = disk::megabytes(needed),
+            lfs = disk::megabytes(lfs)
+        ),
+        None => tr!("cli-preflight-needed", needed = disk::megabytes(needed)),
+    }];
+    if let Some(available) = estimate.available {
+        lines.push(tr!(
+            "cli-preflight-free",
+            available = disk::megabytes(available),
+            path = config.workdir.to_string()
+        ));
+    }
+    let fits = estimate.too_big() != Some(true);
+    if !fits {
+        lines.push(tr!("cli-preflight-short", min = config.disk.min_free_mb));
+    }
+    Some((fits, lines))
+}
+
+fn handle_update(config_arg: Option<Utf8PathBuf>, force: bool) -> Result<()> {
+    let (mut config, config_path) = Config::detect_and_load(config_arg)?;
+    config.resolve_secrets()?;
+    // SAFETY: no other threads exist yet.
+    unsafe { network::export_proxy_env(&config.network) };
+    if !config.self_update.enabled && !force {
+        println!("{}", tr!("cli-update-disabled"));
         return Ok(());
     }
-    let manager = SelfUpdateManager::new(&config.self_update, &config_path);
-    manager.check_now(force)?;
-    println!("Self-update check completed.");
+    let manager =
+        SelfUpdateManager::new(&config.self_update, &config_path).with_network(&config.network);
+    if manager.check_now(force)? {
+        println!("{}", tr!("cli-update-installed"));
+    }
+    println!("{}", tr!("cli-update-done"));
     if !config.self_update.enabled {
+        println!("{}", tr!("cli-update-still-disabled"));
+    }
+    Ok(())
+}
+
+fn handle_rescan(config_arg: Option<Utf8PathBuf>) -> Result<()> {
+    let (mut config, _) = Config::detect_and_load(config_arg)?;
+    config.resolve_secrets()?;
+    // SAFETY: no other threads exist yet.
+    unsafe { network::export_proxy_env(&config.network) };
+    let _lock = VaultLock::acquire(&config)?;
+    let mut daemon = SyncDaemon::new(config)?;
+    daemon.prepare()?;
+    daemon.rescan()?;
+    println!("{}", tr!("cli-rescan-done"));
+    Ok(())
+}
+
+fn handle_trust_host(config_arg: Option<Utf8PathBuf>, yes: bool, replace: bool) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let Some(host) = SshHost::parse(&config.repo_url) else {
+        bail!("repo_url is not an SSH remote, so there is no host key to trust");
+    };
+    let host = host.resolve();
+    let known = host_keys::is_known(&host)?;
+    if known && !replace {
+        println!("{}", tr!("cli-trust-host-known", host = host.to_string()));
+        return Ok(());
+    }
+    let keys = host_keys::scan(&host)?;
+    println!("{}", tr!("cli-trust-host-keys", host = host.to_string()));
+    for key in &keys {
+        println!("  {}  {}", key.fingerprint(), key.kind);
+    }
+    println!("{}", tr!("cli-trust-host-compare"));
+    if !yes && !confirm(&tr!("cli-trust-host-prompt"))? {
+        println!("{}", tr!("cli-trust-host-declined"));
+        return Ok(());
+    }
+    let path = host_keys::pin(&host, &keys, known)?;
+    println!(
+        "{}",
+        tr!(
+            "cli-trust-host-pinned",
+            count = keys.len(),
+            host = host.to_string(),
+            path = path.display().to_string()
+        )
+    );
+    Ok(())
+}
+
+fn handle_repair(config_arg: Option<Utf8PathBuf>, force: bool) -> Result<()> {
+    let (mut config, _) = Config::detect_and_load(config_arg)?;
+    config.resolve_secrets()?;
+    // SAFETY: no other threads exist yet.
+    unsafe { network::export_proxy_env(&config.network) };
+    let _lock = VaultLock::acquire(&config)?;
+    let report = integrity::check(&GitFacade::new(&config)?, &config)?;
+    if !report.is_damaged() && !force {
+        println!("{}", tr!("cli-repair-healthy"));
+        return Ok(());
+    }
+    if report.is_damaged() {
         println!(
-            "Auto-updates are currently disabled. Enable them with `obsyncgit settings set self-update.enabled true` if desired."
+            "{}",
+            tr!("cli-repair-damaged", count = report.problems.len())
+        );
+        for problem in &report.problems {
+            println!("  {problem}");
+        }
+    }
+    let repair = integrity::repair(&config)?;
+    println!(
+        "{}",
+        tr!(
+            "cli-repair-done",
+            path = repair.damaged.display().to_string()
+        )
+    );
+    println!(
+        "{}",
+        tr!(
+            "cli-repair-kept",
+            count = repair.kept.len() + repair.removed.len()
+        )
+    );
+    for (path, copy) in &repair.copies {
+        println!(
+            "{}",
+            tr!(
+                "cli-repair-copy",
+                path = path.as_str(),
+                copy = copy.as_str()
+            )
         );
     }
+    let mut daemon = SyncDaemon::new(config.clone())?;
+    daemon.prepare()?;
+    daemon.rescan()?;
+    integrity::check(&GitFacade::new(&config)?, &config)?;
+    println!("{}", tr!("cli-rescan-done"));
     Ok(())
 }
 
-fn handle_settings(config_arg: Option<Utf8PathBuf>, command: SettingsCommand) -> Result<()> {
-    match command {
-        SettingsCommand::Show => {
-            let (config, _) = Config::detect_and_load(config_arg)?;
-            let rendered =
-                serde_yaml::to_string(&config).context("failed to render configuration as YAML")?;
-            println!("{rendered}");
-            Ok(())
+fn handle_prune_history(config_arg: Option<Utf8PathBuf>, dry_run: bool, yes: bool) -> Result<()> {
+    let (mut config, _) = Config::detect_and_load(config_arg)?;
+    config.resolve_secrets()?;
+    // SAFETY: no other threads exist yet.
+    unsafe { network::export_proxy_env(&config.network) };
+    let _lock = VaultLock::acquire(&config)?;
+    let git = GitFacade::new(&config)?;
+    let describe = |plan: &retention::Plan| {
+        if plan.is_empty() {
+            println!("{}", tr!("cli-prune-none"));
+            return false;
         }
-        SettingsCommand::Set { key, value } => {
-            let path = Config::resolve_path(config_arg.clone())?;
-            let mut config = Config::load_from_path(&path)?;
-            apply_setting(&mut config, key, &value)?;
-            config.save_to_path(&path)?;
-            println!("Updated {key:?} in {path}");
-            Ok(())
+        println!(
+            "{}",
+            tr!(
+                "cli-prune-plan",
+                commits = plan.commits,
+                cutoff = plan
+                    .cutoff
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d")
+                    .to_string(),
+                versions = plan.blobs.len(),
+                size = disk::megabytes(plan.bytes()).max(1)
+            )
+        );
+        true
+    };
+    if dry_run {
+        describe(&retention::plan(&git, &config)?);
+        return Ok(());
+    }
+    let pruned = retention::prune(&git, &config, |plan| {
+        if !describe(plan) {
+            return Ok(false);
+        }
+        println!("{}", tr!("cli-prune-warning"));
+        if !yes && !confirm(&tr!("cli-prune-prompt"))? {
+            println!("{}", tr!("cli-prune-declined"));
+            return Ok(false);
+        }
+        Ok(true)
+    })?;
+    if let Some(pruned) = pruned {
+        println!(
+            "{}",
+            tr!("cli-prune-done", path = pruned.backup.display().to_string())
+        );
+        if !pruned.tags.is_empty() {
+            println!("{}", tr!("cli-prune-tags", count = pruned.tags.len()));
         }
     }
+    Ok(())
 }
 
-fn apply_setting(config: &mut Config, key: SettingsKey, value: &str) -> Result<()> {
-    match key {
-        SettingsKey::RepoUrl => config.repo_url = value.to_string(),
-        SettingsKey::Branch => config.branch = value.to_string(),
-        SettingsKey::Remote => config.remote = value.to_string(),
-        SettingsKey::Workdir => {
-            if value.trim().is_empty() {
-                bail!("workdir cannot be empty");
+fn handle_snapshots(config_arg: Option<Utf8PathBuf>, command: SnapshotsCommand) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    match command {
+        SnapshotsCommand::List => {
+            let snapshots = snapshots::list(&git)?;
+            if snapshots.is_empty() {
+                println!("{}", tr!("cli-snapshots-none"));
+            }
+            for snapshot in &snapshots {
+                let short = snapshot.commit.get(..10).unwrap_or(&snapshot.commit);
+                let committed = snapshot
+                    .committed
+                    .get(..16)
+                    .unwrap_or(&snapshot.committed)
+                    .replace('T', " ");
+                println!("{}  {short}  {committed}", snapshot.tag());
             }
-            config.workdir = Utf8PathBuf::from(value);
-        }
-        SettingsKey::SelfUpdateEnabled => {
-            config.self_update.enabled = parse_bool(value)?;
         }
-        SettingsKey::SelfUpdateIntervalHours => {
-            config.self_update.interval_hours = parse_optional_hours(value)?;
+    }
+    Ok(())
+}
+
+fn handle_backup(config_arg: Option<Utf8PathBuf>, command: BackupCommand) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    match command {
+        BackupCommand::Now => {
+            let bundle = backup::write(&GitFacade::new(&config)?, &config)?;
+            println!(
+                "{}",
+                tr!("cli-backup-written", path = bundle.display().to_string())
+            );
         }
-        SettingsKey::SelfUpdateCommand => {
-            let cleaned = value.trim();
-            if cleaned.eq_ignore_ascii_case("none") || cleaned.is_empty() {
-                config.self_update.command = None;
+        BackupCommand::Restore { bundle, to } => {
+            let (bundle, target) = backup::restore(&config, bundle.as_deref(), to.as_deref())?;
+            println!(
+                "{}",
+                tr!(
+                    "cli-backup-restored",
+                    bundle = bundle.display().to_string(),
+                    path = target.display().to_string()
+                )
+            );
+            if target == config.workdir.as_std_path() {
+                println!("{}", tr!("cli-backup-restored-vault"));
             } else {
-                config.self_update.command = Some(cleaned.to_string());
+                println!("{}", tr!("cli-backup-restored-beside"));
             }
         }
-        SettingsKey::GitSshKeyPath => {
-            let cleaned = value.trim();
-            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+    }
+    Ok(())
+}
+
+fn handle_service(config_arg: Option<Utf8PathBuf>, command: ServiceCommand) -> Result<()> {
+    match command {
+        ServiceCommand::Install {
+            windows_service,
+            launch_agent,
+            account,
+            force,
+        } => {
+            let path = Config::resolve_path(config_arg)?;
+            let config_path = if path.exists() {
+                path.canonicalize_utf8()
+                    .with_context(|| format!("failed to resolve {path}"))?
+            } else {
+                path
+            };
+            if launch_agent {
+                if !cfg!(target_os = "macos") {
+                    bail!("LaunchAgents exist only on macOS");
+                }
+                if launchd::agent_path()?.exists() && !force {
+                    bail!("the LaunchAgent is installed already (use --force to replace it)");
+                }
+                // The installer sets the agent up before there is a config;
+                // the daemon waits for it, restarted by launchd.
+                let options = if config_path.exists() {
+                    Config::load_from_path(&config_path)?.launchd
+                } else {
+                    LaunchdConfig::default()
+                };
+                let daemon =
+                    std::env::current_exe().context("failed to locate running executable")?;
+                let agent = launchd::install(&LaunchAgent::new(daemon, config_path, options)?)?;
+                println!(
+                    "{}",
+                    tr!(
+                        "cli-launch-agent-installed",
+                        path = agent.display().to_string()
+                    )
+                );
+                return Ok(());
+            }
+            if !windows_service {
+                bail!("pass --windows-service on Windows or --launch-agent on macOS");
+            }
+            if !config_path.exists() {
+                bail!("no configuration at {config_path}; run `obsyncgit install` first");
+            }
+            let password = match &account {
+                Some(account) => Some(read_secret(&format!(
+                    "Password for {account} (empty for built-in accounts): "
+                ))?)
+                .filter(|password| !password.is_empty()),
+                None => None,
+            };
+            service::install(&service::InstallOptions {
+                config_path,
+                account,
+                password,
+                force,
+            })?;
+            println!(
+                "{}",
+                tr!("cli-service-installed", name = service::SERVICE_NAME)
+            );
+        }
+        ServiceCommand::Uninstall => {
+            service::uninstall()?;
+            println!(
+                "{}",
+                tr!("cli-service-uninstalled", name = service::SERVICE_NAME)
+            );
+        }
+        ServiceCommand::Start => {
+            service::start()?;
+            println!(
+                "{}",
+                tr!("cli-service-started", name = service::SERVICE_NAME)
+            );
+        }
+        ServiceCommand::Stop => {
+            service::stop()?;
+            println!(
+                "{}",
+                tr!("cli-service-stopped", name = service::SERVICE_NAME)
+            );
+        }
+        ServiceCommand::Status => match service::status()? {
+            Some(state) => println!("{}: {state}", service::SERVICE_NAME),
+            None => println!("{}: not installed", service::SERVICE_NAME),
+        },
+        ServiceCommand::Run => {
+            let (mut config, path) = Config::detect_and_load(config_arg)?;
+            info!(path = %path, "configuration loaded");
+            config.resolve_secrets()?;
+            // SAFETY: the service control manager starts the daemon's thread
+            // only in `service::run`.
+            unsafe { network::export_proxy_env(&config.network) };
+            // Updates are left to `obsyncgit update` and a service restart:
+            // the updater relaunches the daemon outside the service.
+            service::run(move |shutdown| {
+                let _lock = VaultLock::acquire(&config)?;
+                SyncDaemon::new(config)?.with_shutdown(shutdown).run()
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// The service's log file; stderr when it cannot be opened, which nobody
+/// reads but keeps the daemon running.
+fn service_log_writer(config_arg: Option<Utf8PathBuf>) -> BoxMakeWriter {
+    let file = Config::resolve_path(config_arg).and_then(|path| {
+        let log = service::log_path(&path);
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log)
+            .with_context(|| format!("failed to open {}", log.display()))
+    });
+    match file {
+        Ok(file) => BoxMakeWriter::new(std::sync::Mutex::new(file)),
+        Err(_) => BoxMakeWriter::new(std::io::stderr),
+    }
+}
+
+#[cfg(feature = "tui")]
+fn handle_tui(config_arg: Option<Utf8PathBuf>, takeover: bool, logs: tui::LogBuffer) -> Result<()> {
+    let path = Config::resolve_path(config_arg)?;
+    if !path.exists() {
+        let mut config = default_config();
+        let Some(answers) = tui::first_run(&config)? else {
+            println!("{}", tr!("cli-setup-cancelled"));
+            return Ok(());
+        };
+        config.repo_url = answers.repo_url;
+        config.workdir = answers.workdir;
+        config.branch = answers.branch;
+        config.git.author_name = answers.author_name;
+        config.git.author_email = answers.author_email;
+        config.save_to_path(&path)?;
+        info!(path = %path, "created configuration");
+    }
+    let mut config = Config::load_from_path(&path)?;
+    config.resolve_secrets()?;
+    // SAFETY: no other threads exist yet.
+    unsafe { network::export_proxy_env(&config.network) };
+    let lock = if takeover {
+        VaultLock::takeover(&config)?
+    } else {
+        VaultLock::acquire(&config)?
+    };
+
+    let daemon = SyncDaemon::new(config.clone())?;
+    let shutdown = daemon.shutdown_handle();
+    let control = daemon.control_handle();
+    let worker = {
+        let shutdown = shutdown.clone();
+        std::thread::spawn(move || {
+            let result = daemon.run();
+            // Takes the monitor down with the daemon.
+            shutdown.trigger();
+            result
+        })
+    };
+    let shown = tui::monitor(&config, &control, &shutdown, &logs);
+    shutdown.trigger();
+    let ran = worker
+        .join()
+        .map_err(|_| anyhow::anyhow!("sync daemon panicked"))?;
+    drop(lock);
+    shown?;
+    ran
+}
+
+fn handle_doctor(config_arg: Option<Utf8PathBuf>) -> Result<()> {
+    let (config, path) = Config::detect_and_load(config_arg)?;
+    println!("[ok]   config: {path}");
+    let git = GitFacade::new(&config)?;
+    match git.version() {
+        Ok(version) => println!("[ok]   git: {version}"),
+        Err(err) => println!("[fail] git: {err:#}"),
+    }
+    if let Some(host) = SshHost::parse(&config.repo_url) {
+        let host = host.resolve();
+        match host_keys::is_known(&host) {
+            Ok(true) => println!("[ok]   ssh host key: {host} is in known_hosts"),
+            Ok(false) => println!(
+                "[warn] ssh host key: {host} is not in known_hosts yet, so ssh refuses to connect; run `obsyncgit trust-host`"
+            ),
+            Err(err) => println!("[warn] ssh host key: {err:#}"),
+        }
+    }
+    if let Some(provider) = cloud::detect(config.workdir.as_std_path()) {
+        println!(
+            "[warn] cloud sync: the vault is inside {provider}, which copies files behind git's back"
+        );
+        for conflict in provider.known_conflicts() {
+            println!("         - {conflict}");
+        }
+        if config.git.separate_git_dir.is_none() {
+            println!(
+                "         - the .git folder is synced too and can be corrupted; move it out of the vault with git.separate_git_dir"
+            );
+        }
+        if let Some(marker) = provider.conflict_marker()
+            && let Ok(pending) = git.list_pending_paths()
+        {
+            let copies = pending.iter().filter(|path| path.contains(marker)).count();
+            if copies > 0 {
+                println!("         - {copies} conflict duplicate(s) waiting to be committed");
+            }
+        }
+    }
+    if let Some((fits, lines)) = clone_estimate(&config) {
+        let tag = if fits { "[ok]  " } else { "[warn]" };
+        println!("{tag} first clone: {}", lines.join(" "));
+    }
+    if !config.workdir.exists() {
+        println!(
+            "[warn] vault: {} does not exist yet; `run` clones it",
+            config.workdir
+        );
+        return Ok(());
+    }
+    match git.branch_status() {
+        Ok(status) => println!("[ok]   vault: {} ({status})", config.workdir),
+        Err(err) => println!("[fail] vault: {err:#}"),
+    }
+    if let Ok(Some(main)) = git.main_worktree() {
+        println!(
+            "[ok]   worktree: linked worktree of {}; its branch cannot be checked out there",
+            main.display()
+        );
+    }
+
+    let directories = watcher::count_directories(config.workdir.as_std_path());
+    match WatchBudget::current() {
+        Some(budget) => {
+            let spare = budget.available();
+            let summary = format!(
+                "{directories} directories to watch; {} of {} inotify watches in use by this user, {spare} spare",
+                budget.used, budget.limit
+            );
+            if directories <= spare {
+                println!("[ok]   file watching: {summary}");
+            } else {
+                println!(
+                    "[warn] file watching: {summary}. The daemon falls back to watching recently active directories only (backend `auto`). {}",
+                    watcher::RAISE_LIMIT_ADVICE
+                );
+            }
+        }
+        None => println!("[ok]   file watching: {directories} directories"),
+    }
+    if let Ok(tracked) = git.tracked_files() {
+        let enabled = |key: &str| {
+            git.config_value(key)
+                .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+        };
+        let untracked_cache = enabled("core.untrackedCache");
+        let summary = format!(
+            "{} tracked files; untracked cache {}, file system monitor {}",
+            tracked.len(),
+            if untracked_cache { "on" } else { "off" },
+            if enabled("core.fsmonitor") {
+                "on"
+            } else {
+                "off"
+            }
+        );
+        if untracked_cache || tracked.len() < LARGE_VAULT_FILES {
+            println!("[ok]   git status: {summary}");
+        } else {
+            println!(
+                "[warn] git status: {summary}. Every sync scans the whole vault; set git.status_cache to auto or on"
+            );
+        }
+    }
+    if let Some(available) = disk::available(config.workdir.as_std_path()) {
+        let summary = format!("{} MB free", disk::megabytes(available));
+        if available < config.disk.min_free_mb.saturating_mul(1024 * 1024) {
+            println!(
+                "[fail] disk space: {summary}, below disk.min_free_mb ({} MB); syncing is stopped until space is freed",
+                config.disk.min_free_mb
+            );
+        } else {
+            println!("[ok]   disk space: {summary}");
+        }
+    }
+    match SecretScanner::new(&config.security.secret_scan) {
+        Ok(Some(scanner)) => {
+            let root = config.workdir.as_std_path();
+            let committed = scanner.scan(root, &git.tracked_files()?);
+            let pending = scanner.scan(root, &git.list_pending_paths()?);
+            for (path, finding) in &committed {
+                println!(
+                    "[warn] secret scan: {path} is already committed ({finding}); untrack it with `git rm --cached` and rotate the credential"
+                );
+            }
+            if !pending.is_empty() {
+                let paths: Vec<&str> = pending.iter().map(|(path, _)| path.as_str()).collect();
+                println!(
+                    "[warn] secret scan: held back from commits: {}; move them out of the vault, ignore them, or list them in security.secret_scan.allow",
+                    paths.join(", ")
+                );
+            } else if committed.is_empty() {
+                println!("[ok]   secret scan: no files look like secrets");
+            }
+        }
+        Ok(None) => println!("[warn] secret scan: off (security.secret_scan.enabled)"),
+        Err(err) => println!("[fail] secret scan: {err:#}"),
+    }
+    if let Ok(pending) = git.list_pending_paths() {
+        let root = config.workdir.as_std_path();
+        let waiting = conflict_copies::find(&pending)
+            .into_iter()
+            .filter(|copy| root.join(&copy.path).is_file())
+            .count();
+        if waiting > 0 {
+            let hint = match config.sync.conflict_copies {
+                ConflictCopyPolicy::Inbox => "choose a version with `obsyncgit conflicts`",
+                ConflictCopyPolicy::Ignore => "they are never committed; merge them by hand",
+                ConflictCopyPolicy::Newest => {
+                    "the daemon keeps the newer version on its next commit"
+                }
+            };
+            println!(
+                "[warn] conflict copies: {waiting} made by another sync tool are not committed; {hint}"
+            );
+        }
+        let committed: Vec<String> = conflict_copies::find(&git.tracked_files()?)
+            .into_iter()
+            .map(|copy| copy.path)
+            .collect();
+        if !committed.is_empty() {
+            println!(
+                "[warn] conflict copies: already committed: {}; merge them into their notes and delete them",
+                committed.join(", ")
+            );
+        }
+    }
+    if git.has_repo()
+        && let Ok(crlf) = git.crlf_files()
+        && !crlf.is_empty()
+    {
+        match config.compat.line_endings {
+            LineEndings::Off => println!(
+                "[warn] line endings: {} tracked files are stored with CRLF, e.g. {}; set compat.line_endings to lf to convert them",
+                crlf.len(),
+                crlf[0]
+            ),
+            LineEndings::Lf => println!(
+                "[warn] line endings: {} tracked files are still stored with CRLF, e.g. {}; the daemon converts them once the vault has nothing uncommitted",
+                crlf.len(),
+                crlf[0]
+            ),
+        }
+    }
+    let drift = git.repo_config_drift();
+    if drift.is_empty() {
+        if !config.git.repo_config.is_empty() {
+            println!(
+                "[ok]   git repo config: {} settings pinned in the vault",
+                config.git.repo_config.len()
+            );
+        }
+    } else {
+        for drift in drift {
+            let (key, wanted) = (&drift.key, &drift.wanted);
+            let wanted = if wanted.is_empty() { "unset" } else { wanted };
+            match drift.current {
+                Some((value, origin)) if origin.starts_with("GIT_CONFIG") => println!(
+                    "[warn] git repo config: {key} is {value} (from {origin}), not {wanted}; the vault's own config cannot override it"
+                ),
+                Some((value, origin)) => println!(
+                    "[warn] git repo config: {key} is {value} (from {origin}), not {wanted}; the daemon sets it in the vault when it starts"
+                ),
+                None => println!(
+                    "[warn] git repo config: {key} is unset, not {wanted}; the daemon sets it in the vault when it starts"
+                ),
+            }
+        }
+    }
+    let hooks = git.commit_hooks();
+    if !hooks.is_empty() {
+        if config.git.run_hooks {
+            println!(
+                "[ok]   git hooks: {} check the daemon's commits",
+                hooks.join(", ")
+            );
+        } else {
+            println!(
+                "[warn] git hooks: the daemon's commits skip {}; set git.run_hooks to run them",
+                hooks.join(", ")
+            );
+        }
+    }
+    let redaction = &config.security.redaction;
+    if redaction.enabled {
+        let filter = format!("filter.{}.clean", redaction::FILTER_NAME);
+        if git.config_value(&filter).is_some() {
+            println!(
+                "[ok]   redaction: private lines in {} stay out of commits",
+                redaction.paths.join(", ")
+            );
+        } else {
+            println!(
+                "[warn] redaction: the filter is not set up yet; start the daemon before editing private lines"
+            );
+        }
+    }
+    if config.shared_vault.enabled {
+        match shared_vault::identity(&git, &config) {
+            Some((name, email)) => {
+                let folder = match &config.shared_vault.user_folder {
+                    Some(folder) => format!(", new notes go into {folder}"),
+                    None => String::new(),
+                };
+                println!("[ok]   shared vault: committing as {name} <{email}>{folder}");
+            }
+            None => println!(
+                "[fail] shared vault: no identity to commit under; set git.author_name and git.author_email"
+            ),
+        }
+    }
+    if config.mqtt.enabled {
+        match mqtt::Broker::parse(&config.mqtt.broker)
+            .and_then(|broker| mqtt::reachable(&broker).map(|()| broker))
+        {
+            Ok(broker) => println!(
+                "[ok]   mqtt: {broker} reachable, publishing to {}/state",
+                mqtt::base_topic(&config).unwrap_or_default()
+            ),
+            Err(err) => println!("[fail] mqtt: {err:#}"),
+        }
+    }
+    let channels = Channel::all(&config.alerts);
+    if !channels.is_empty() {
+        let mut resolved = config.clone();
+        match resolved.resolve_secrets() {
+            Ok(()) => {
+                for (channel, shown) in Channel::all(&resolved.alerts).iter().zip(&channels) {
+                    match channel.check() {
+                        Ok(()) => println!("[ok]   alerts: {}", shown.target()),
+                        Err(err) => println!("[fail] alerts: {}: {err:#}", channel.name()),
+                    }
+                }
+            }
+            Err(err) => println!("[fail] alerts: {err:#}"),
+        }
+    }
+    if let Some(state) = power::probe() {
+        let source = if state.on_battery {
+            "on battery"
+        } else {
+            "on mains power"
+        };
+        let summary = match state.battery_percent {
+            Some(percent) => format!("{source}, {percent}% charged"),
+            None => source.to_string(),
+        };
+        if state.is_low(&config.power) {
+            println!(
+                "[warn] power: {summary}, below power.low_battery_percent ({}%); polling less and holding back large pushes",
+                config.power.low_battery_percent
+            );
+        } else {
+            println!("[ok]   power: {summary}");
+        }
+    }
+    Ok(())
+}
+
+fn handle_verify(config_arg: Option<Utf8PathBuf>) -> Result<()> {
+    let (mut config, _) = Config::detect_and_load(config_arg)?;
+    config.resolve_secrets()?;
+    // SAFETY: no other threads exist yet.
+    unsafe { network::export_proxy_env(&config.network) };
+    let outcome = connectivity::round_trip(&config, |step, result| match result {
+        Ok(detail) => println!("[ok]   {}: {detail}", step.label()),
+        Err(err) => {
+            println!("[fail] {}: {err:#}", step.label());
+            println!("       {}", step.hint());
+        }
+    });
+    if let Err(step) = outcome {
+        bail!("verification failed at step '{}'", step.label());
+    }
+    Ok(())
+}
+
+fn handle_status(config_arg: Option<Utf8PathBuf>, trace: bool, format: StatusFormat) -> Result<()> {
+    let (config, path) = Config::detect_and_load(config_arg)?;
+    match format {
+        StatusFormat::Text => {}
+        StatusFormat::Waybar => {
+            println!("{}", SyncHealth::read(&config).waybar());
+            return Ok(());
+        }
+        StatusFormat::Xbar => {
+            print!("{}", SyncHealth::read(&config).xbar());
+            return Ok(());
+        }
+    }
+    if trace {
+        let cycles = obsyncgit::trace::load(&config.state_dir()?)?;
+        for cycle in cycles {
+            println!(
+                "{}  {}  {} ms  {}",
+                cycle.started_at, cycle.kind, cycle.millis, cycle.outcome
+            );
+            for command in cycle.commands {
+                println!(
+                    "  git {}  ({} ms, exit {})",
+                    command.args, command.millis, command.code
+                );
+                for line in command.stdout.lines().chain(command.stderr.lines()) {
+                    println!("    {line}");
+                }
+            }
+        }
+        return Ok(());
+    }
+    let git = GitFacade::observer(&config)?;
+    println!("Config:   {path}");
+    println!("Vault:    {}", config.workdir);
+    println!("Remote:   {} ({})", config.remote.name, config.repo_url);
+    match instance::owner(&config)? {
+        Some(pid) => println!("Daemon:   running (PID {pid})"),
+        None => println!("Daemon:   not running"),
+    }
+    println!("Branch:   {}", git.branch_status()?);
+    println!(
+        "Pending:  {} changed path(s)",
+        git.list_pending_paths()?.len()
+    );
+    if let Some(failure) = ControlInbox::new(&config)?.last_failure() {
+        println!("Failing:  {}", failure.cause());
+        if let Some(line) = failure
+            .message()
+            .lines()
+            .find(|line| !line.trim().is_empty())
+        {
+            println!("          {}", line.trim());
+        }
+    }
+    let held_secrets = ControlInbox::new(&config)?.held_secrets();
+    if !held_secrets.is_empty() {
+        println!(
+            "Secrets:  {} file(s) held back as likely secrets: {}",
+            held_secrets.len(),
+            held_secrets.join(", ")
+        );
+    }
+    if config.shared_vault.enabled && config.shared_vault.soft_locks {
+        for lock in locks::current(&git, &config)? {
+            println!(
+                "Locked:   {} is being edited by {} (until {})",
+                lock.path,
+                lock.holder(),
+                lock.expires.with_timezone(&chrono::Local).format("%H:%M")
+            );
+        }
+    }
+    if let Some(report) = integrity::load(&config) {
+        let ago = humantime::format_duration(Duration::from_secs(report.age().as_secs() / 60 * 60));
+        if report.is_damaged() {
+            println!(
+                "Fsck:     {} problem(s) found {ago} ago; run `obsyncgit repair`",
+                report.problems.len()
+            );
+        } else {
+            println!("Fsck:     ok, checked {ago} ago");
+        }
+    }
+    if config.backup.dir.is_some() {
+        match backup::load(&config) {
+            Some(record) => {
+                let ago = humantime::format_duration(Duration::from_secs(
+                    record.age().as_secs() / 60 * 60,
+                ));
+                println!("Backup:   {} ({ago} ago)", record.bundle.display());
+            }
+            None => println!("Backup:   none written yet"),
+        }
+    }
+    for entry in &config.replicas {
+        let dir = replica::replica_dir(entry);
+        match replica::load(&dir).and_then(|manifest| manifest.age()) {
+            Some(age) => {
+                let ago = humantime::format_duration(Duration::from_secs(age.as_secs() / 60 * 60));
+                println!("Replica:  {} (mirrored {ago} ago)", dir.display());
+            }
+            None if replica::available(&dir) => {
+                println!("Replica:  {} (not mirrored yet)", dir.display())
+            }
+            None => println!("Replica:  {} (not available)", dir.display()),
+        }
+    }
+    Ok(())
+}
+
+fn handle_conflicts(
+    config_arg: Option<Utf8PathBuf>,
+    choices: &[(Resolution, Vec<PathBuf>)],
+) -> Result<()> {
+    let (mut config, _) = Config::detect_and_load(config_arg)?;
+    config.resolve_secrets()?;
+    // SAFETY: no other threads exist yet.
+    unsafe { network::export_proxy_env(&config.network) };
+    let git = GitFacade::new(&config)?;
+    if let Err(err) = git.fetch() {
+        warn!(
+            ?err,
+            "could not reach the remote; showing conflicts as of the last sync"
+        );
+    }
+
+    let root = config.workdir.as_std_path();
+    let copies: Vec<ConflictCopy> = if config.sync.conflict_copies == ConflictCopyPolicy::Inbox {
+        conflict_copies::find(&git.list_pending_paths()?)
+            .into_iter()
+            .filter(|copy| root.join(&copy.path).is_file())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut chosen = BTreeMap::new();
+    let mut settled = 0;
+    for (resolution, paths) in choices {
+        for path in paths {
+            let relative = git.vault_relative(path)?;
+            let matching: Vec<&ConflictCopy> = copies
+                .iter()
+                .filter(|copy| copy.path == relative || copy.original == relative)
+                .collect();
+            match matching.as_slice() {
+                [] => {
+                    chosen.insert(relative, *resolution);
+                }
+                [copy] => {
+                    conflict_copies::resolve(root, copy, *resolution)?;
+                    settled += 1;
+                }
+                several => bail!(
+                    "{relative} has {} conflict copies; pass the path of the copy instead",
+                    several.len()
+                ),
+            }
+        }
+    }
+    if !chosen.is_empty() {
+        conflicts::resolve(&git, &config, &chosen)?;
+    }
+    if settled + chosen.len() > 0 {
+        println!(
+            "{}",
+            tr!("cli-conflicts-resolved", count = settled + chosen.len())
+        );
+        return Ok(());
+    }
+
+    let open = conflicts::list(&git, &config)?;
+    if open.is_empty() && copies.is_empty() {
+        println!("{}", tr!("cli-conflicts-none"));
+        return Ok(());
+    }
+    for conflict in &open {
+        println!("{}  ({})", conflict.path, conflict.summary());
+    }
+    for copy in &copies {
+        println!(
+            "{}  ({})",
+            copy.original,
+            tr!(
+                "conflict-copy",
+                tool = copy.tool.to_string(),
+                copy = copy.path.as_str()
+            )
+        );
+    }
+    println!("\n{}", tr!("cli-conflicts-hint"));
+    if !copies.is_empty() {
+        println!("{}", tr!("cli-conflict-copies-hint"));
+    }
+    Ok(())
+}
+
+fn handle_case_collisions(config_arg: Option<Utf8PathBuf>, rename: bool) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    let collisions = case_collisions::find(&git.tracked_files()?);
+    if collisions.is_empty() {
+        println!("{}", tr!("cli-case-none"));
+        return Ok(());
+    }
+    if rename {
+        let moved = case_collisions::resolve(&git, &collisions)?;
+        for (from, to) in &moved {
+            println!("{from} -> {to}");
+        }
+        println!("{}", tr!("cli-case-renamed", count = moved.len()));
+        return Ok(());
+    }
+    for collision in &collisions {
+        println!("{}", collision.paths.join("  "));
+    }
+    println!("\n{}", tr!("cli-case-hint"));
+    Ok(())
+}
+
+fn handle_file_names(config_arg: Option<Utf8PathBuf>, rename: bool) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    let mut paths: BTreeSet<String> = git.tracked_files()?.into_iter().collect();
+    paths.extend(git.list_pending_paths()?);
+    let unsafe_paths: Vec<String> = paths
+        .into_iter()
+        .filter(|path| file_names::windows_problem(path).is_some())
+        .collect();
+    if unsafe_paths.is_empty() {
+        println!("{}", tr!("cli-file-names-none"));
+        return Ok(());
+    }
+    if rename {
+        let renamed = file_names::rename_for_windows(config.workdir.as_std_path(), &unsafe_paths)?;
+        for entry in &renamed {
+            println!("{} -> {}", entry.from, entry.to);
+        }
+        println!(
+            "{}",
+            tr!(
+                "cli-file-names-renamed",
+                count = renamed.len(),
+                record = file_names::RENAMES_PATH
+            )
+        );
+        return Ok(());
+    }
+    for path in &unsafe_paths {
+        let problem = file_names::windows_problem(path).unwrap_or_default();
+        println!("{path}  ({problem})");
+    }
+    println!("\n{}", tr!("cli-file-names-hint"));
+    Ok(())
+}
+
+fn handle_unicode_names(config_arg: Option<Utf8PathBuf>, rename: bool) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    let tracked = git.tracked_files()?;
+    let pending: Vec<String> = git
+        .list_pending_paths()?
+        .into_iter()
+        .filter(|path| !tracked.contains(path))
+        .collect();
+    let mut paths = tracked;
+    paths.extend(pending.iter().cloned());
+    let decomposed: Vec<&String> = paths
+        .iter()
+        .filter(|path| !normalization::is_nfc(path))
+        .collect();
+    if decomposed.is_empty() {
+        println!("{}", tr!("cli-unicode-none"));
+        return Ok(());
+    }
+    if rename {
+        let mut moved = normalization::resolve(&git)?;
+        moved.extend(normalization::compose_files(
+            config.workdir.as_std_path(),
+            &pending,
+        )?);
+        for (from, to) in &moved {
+            println!("{from} -> {to}");
+        }
+        println!("{}", tr!("cli-unicode-renamed", count = moved.len()));
+        return Ok(());
+    }
+    let duplicates = normalization::duplicates(&paths);
+    for path in decomposed {
+        match duplicates.iter().find(|group| group.contains(path)) {
+            Some(group) => println!("{path}  ({})", group.join("  ")),
+            None => println!("{path}"),
+        }
+    }
+    println!("\n{}", tr!("cli-unicode-hint"));
+    Ok(())
+}
+
+fn handle_ignore(config_arg: Option<Utf8PathBuf>, command: IgnoreCommand) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    let (path, explain) = match command {
+        IgnoreCommand::Test { path } => (path, false),
+        IgnoreCommand::Explain { path } => (path, true),
+    };
+    let relative = git.vault_relative(&path)?;
+    let root = config.workdir.as_std_path();
+    let watcher = IgnoreMatcher::for_config(&config, cloud::detect(root))?;
+    let watcher_rules = watcher.matching_rules(root.join(&relative));
+    let watcher_ignored = watcher.should_ignore(root.join(&relative));
+    let git_rule = git.ignore_rule(&relative)?;
+    let tracked = git.is_tracked(&relative)?;
+    let git_ignored = git_rule
+        .as_ref()
+        .is_some_and(|rule| !rule.pattern.starts_with('!'));
+    let blocked = ignore::staging_block(&config.ignore, root, &relative);
+
+    if explain {
+        for rule in &watcher_rules {
+            println!(
+                "{}",
+                tr!(
+                    "cli-ignore-watcher-rule",
+                    pattern = rule.pattern.as_str(),
+                    source = rule.source.to_string()
+                )
+            );
+        }
+        if let Some(rule) = &git_rule {
+            println!(
+                "{}",
+                tr!(
+                    "cli-ignore-git-rule",
+                    pattern = rule.pattern.as_str(),
+                    file = rule.file.as_str(),
+                    line = rule.line
+                )
+            );
+        }
+        match &blocked {
+            Some(Blocked::Extension(extension)) => println!(
+                "{}",
+                tr!(
+                    "cli-ignore-blocked-extension",
+                    extension = extension.as_str()
+                )
+            ),
+            Some(Blocked::Size { bytes, limit_mb }) => println!(
+                "{}",
+                tr!(
+                    "cli-ignore-blocked-size",
+                    size = bytes.div_ceil(1024 * 1024),
+                    limit = *limit_mb
+                )
+            ),
+            None => {}
+        }
+        if watcher_rules.is_empty() && git_rule.is_none() && blocked.is_none() {
+            println!("{}", tr!("cli-ignore-no-rules"));
+        }
+        println!();
+    }
+    // `git add -A` commits what the watcher skips unless git ignores it
+    // too, and a tracked file whatever the rules.
+    let verdict = if blocked.is_some() || (git_ignored && !tracked) {
+        "cli-ignore-ignored"
+    } else if !watcher_ignored {
+        "cli-ignore-synced"
+    } else {
+        "cli-ignore-synced-quietly"
+    };
+    println!("{}", tr!(verdict, path = relative.as_str()));
+    if explain && blocked.is_none() {
+        if tracked && git_ignored {
+            println!(
+                "{}",
+                tr!("cli-ignore-tracked-hint", path = relative.as_str())
+            );
+        } else if watcher_ignored
+            && !git_ignored
+            && watcher_rules
+                .iter()
+                .any(|rule| rule.source == RuleSource::Config)
+        {
+            println!("{}", tr!("cli-ignore-globs-hint"));
+        }
+    }
+    Ok(())
+}
+
+fn handle_log(config_arg: Option<Utf8PathBuf>, path: &Path, limit: usize) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    let relative = git.vault_relative(path)?;
+    let revisions = git.file_history(&relative, limit)?;
+    if revisions.is_empty() {
+        println!("{}", tr!("cli-log-empty", path = relative));
+        return Ok(());
+    }
+    for revision in revisions {
+        let change = match revision.change {
+            'A' => "created",
+            'D' => "deleted",
+            'R' => "renamed",
+            _ => "modified",
+        };
+        let date = revision
+            .date
+            .get(..16)
+            .unwrap_or(&revision.date)
+            .replace('T', " ");
+        let short = revision.commit.get(..10).unwrap_or(&revision.commit);
+        println!("{short}  {date}  {change:<8}  {}", revision.summary);
+    }
+    println!("\n{}", tr!("cli-log-hint", path = relative));
+    Ok(())
+}
+
+fn handle_history(
+    config_arg: Option<Utf8PathBuf>,
+    path: Option<&Path>,
+    limit: usize,
+) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    let relative = path.map(|path| git.vault_relative(path)).transpose()?;
+    let entries = sync_notes::history(&git, limit, relative.as_deref())?;
+    if entries.is_empty() {
+        println!("{}", tr!("cli-history-empty"));
+        return Ok(());
+    }
+    let mut noted = false;
+    for entry in entries {
+        let commit = &entry.commit;
+        let date = commit
+            .date
+            .get(..16)
+            .unwrap_or(&commit.date)
+            .replace('T', " ");
+        let short = commit.commit.get(..10).unwrap_or(&commit.commit);
+        let details = match &entry.note {
+            Some(note) => {
+                noted = true;
+                let latency = note.latency.map_or_else(
+                    || "-".to_string(),
+                    |latency| {
+                        humantime::format_duration(Duration::from_secs(latency.as_secs()))
+                            .to_string()
+                    },
+                );
+                format!(
+                    "{:<16}  {:>4} file(s)  {latency:>8}",
+                    note.device, note.files
+                )
+            }
+            None => format!("{:<16}  {:>12}  {:>8}", commit.author, "", ""),
+        };
+        println!("{short}  {date}  {details}  {}", commit.subject);
+    }
+    if !noted && !config.commit.notes {
+        println!("\n{}", tr!("cli-history-hint"));
+    }
+    Ok(())
+}
+
+fn handle_events(
+    config_arg: Option<Utf8PathBuf>,
+    follow: bool,
+    json: bool,
+    limit: usize,
+) -> Result<()> {
+    use std::io::Write;
+
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let log = EventLog::new(&config)?;
+    let print = |record: Record| -> Result<()> {
+        let line = if json {
+            record.to_json()
+        } else {
+            describe_event(&record)
+        };
+        writeln!(std::io::stdout(), "{line}").context("failed to write to stdout")
+    };
+    let recent = log.recent(limit);
+    if recent.is_empty() && !follow {
+        println!("{}", tr!("cli-events-empty"));
+        return Ok(());
+    }
+    for record in recent {
+        print(record)?;
+    }
+    if follow {
+        log.follow(print)?;
+    }
+    Ok(())
+}
+
+/// One line of `obsyncgit events`: the local time and what happened.
+fn describe_event(record: &Record) -> String {
+    let time = record.time().map_or_else(
+        || record.time.clone(),
+        |time| {
+            time.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        },
+    );
+    let short = |commit: &str| commit.get(..10).unwrap_or(commit).to_string();
+    let list = |paths: &[String]| {
+        let mut shown = paths.iter().take(5).cloned().collect::<Vec<_>>().join(", ");
+        if paths.len() > 5 {
+            shown.push_str(", …");
+        }
+        shown
+    };
+    let what = match &record.event {
+        Event::ChangeDetected { paths } => tr!("cli-events-change", paths = list(paths)),
+        Event::CommitCreated {
+            commit,
+            files,
+            amended,
+        } => tr!(
+            if *amended {
+                "cli-events-amend"
+            } else {
+                "cli-events-commit"
+            },
+            commit = short(commit),
+            count = files.len(),
+            files = list(files)
+        ),
+        Event::CloneProgress {
+            phase,
+            percent,
+            rate: None,
+        } => tr!(
+            "cli-events-clone",
+            phase = phase.clone(),
+            percent = *percent
+        ),
+        Event::CloneProgress {
+            phase,
+            percent,
+            rate: Some(rate),
+        } => tr!(
+            "cli-events-clone-rate",
+            phase = phase.clone(),
+            percent = *percent,
+            rate = rate.clone()
+        ),
+        Event::PushOk { branch } => tr!("cli-events-push", branch = branch.clone()),
+        Event::PullMerged { commit, files } => tr!(
+            "cli-events-pull",
+            commit = short(commit),
+            count = files.len(),
+            files = list(files)
+        ),
+        Event::Error { category, message } => tr!(
+            "cli-events-error",
+            category = category.clone(),
+            message = message.clone()
+        ),
+    };
+    format!("{time}  {what}")
+}
+
+fn handle_restore(
+    config_arg: Option<Utf8PathBuf>,
+    path: &Path,
+    at: Option<&str>,
+    commit: Option<&str>,
+    snapshot: Option<&str>,
+) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    let relative = git.vault_relative(path)?;
+    let commit = match (commit, snapshot) {
+        (Some(commit), _) => commit.to_string(),
+        (None, Some(day)) => snapshots::commit(&git, snapshots::parse_day(day)?)?,
+        (None, None) => git.restore_point(&relative, at)?,
+    };
+    git.restore_file(&relative, &commit)?;
+    let short = commit.get(..10).unwrap_or(&commit);
+    println!(
+        "{}",
+        tr!("cli-restored", path = relative.as_str(), commit = short)
+    );
+    Ok(())
+}
+
+fn handle_export(
+    config_arg: Option<Utf8PathBuf>,
+    output: Option<&Path>,
+    format: Option<ArchiveFormat>,
+    at: Option<&str>,
+) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    let commit = git.resolve_commit(at)?;
+    let short = commit.get(..10).unwrap_or(&commit);
+    let vault = config.workdir.file_name().unwrap_or("vault");
+    let format = format
+        .or_else(|| output.and_then(ArchiveFormat::from_path))
+        .unwrap_or(ArchiveFormat::Zip);
+    let output = match output {
+        Some(path) => path.to_path_buf(),
+        None => PathBuf::from(format!("{vault}-{short}.{}", format.extension())),
+    };
+    // git runs in the vault, so a relative path would land there.
+    let output = std::path::absolute(&output)
+        .with_context(|| format!("failed to resolve {}", output.display()))?;
+    git.archive(&commit, format.extension(), &format!("{vault}/"), &output)?;
+    println!(
+        "{}",
+        tr!(
+            "cli-exported",
+            commit = short,
+            path = output.display().to_string()
+        )
+    );
+    Ok(())
+}
+
+fn handle_import(
+    config_arg: Option<Utf8PathBuf>,
+    source: &Path,
+    on_collision: OnCollision,
+    dry_run: bool,
+) -> Result<()> {
+    let (config, _) = Config::detect_and_load(config_arg)?;
+    let git = GitFacade::new(&config)?;
+    let report = import::import(&git, &config, source, on_collision, dry_run)?;
+    for path in &report.added {
+        println!("+ {path}");
+    }
+    for path in &report.updated {
+        println!("~ {path}");
+    }
+    for (path, copy) in &report.copies {
+        println!("+ {copy}  ({path})");
+    }
+    for path in &report.skipped {
+        println!("  {path}  ({})", tr!("cli-import-vault-newer"));
+    }
+    let written = report.added.len() + report.updated.len() + report.copies.len();
+    println!(
+        "{}",
+        tr!(
+            "cli-import-summary",
+            count = written,
+            unchanged = report.unchanged,
+            skipped = report.skipped.len()
+        )
+    );
+    if dry_run {
+        println!("{}", tr!("cli-import-dry-run"));
+    } else if let Some((commit, tag)) = &report.commit {
+        let short = commit.get(..10).unwrap_or(commit);
+        println!(
+            "{}",
+            tr!("cli-import-committed", commit = short, tag = tag.as_str())
+        );
+    }
+    if !report.held.is_empty() {
+        println!("{}", tr!("cli-import-held", paths = report.held.join(", ")));
+    }
+    Ok(())
+}
+
+fn handle_alerts(config_arg: Option<Utf8PathBuf>, test: bool) -> Result<()> {
+    let (mut config, _) = Config::detect_and_load(config_arg)?;
+    config.resolve_secrets()?;
+    let channels = Channel::all(&config.alerts);
+    if channels.is_empty() {
+        println!("{}", tr!("cli-alerts-off"));
+        return Ok(());
+    }
+    for channel in &channels {
+        let line = match channel {
+            Channel::Email(email) => tr!(
+                "cli-alerts-email",
+                to = email.to.join(", "),
+                server = alerts::server(email)
+            ),
+            Channel::Telegram(telegram) => {
+                tr!("cli-alerts-telegram", chat = alerts::chat(telegram))
+            }
+            Channel::Discord(discord) => {
+                tr!("cli-alerts-discord", host = alerts::webhook_host(discord))
+            }
+        };
+        println!("{line}");
+    }
+    let failures = config.alerts.after_failures;
+    println!(
+        "{}",
+        if failures == 0 {
+            tr!("cli-alerts-failures-off")
+        } else {
+            tr!("cli-alerts-failures", count = failures)
+        }
+    );
+    if config.alerts.on_conflict {
+        println!("{}", tr!("cli-alerts-conflicts"));
+    }
+    if test {
+        let alert = Alert::test(&config);
+        let mut failed = 0;
+        for channel in &channels {
+            match channel.send(&alert) {
+                Ok(()) => println!("{}", tr!("cli-alerts-sent", channel = channel.name())),
+                Err(err) => {
+                    failed += 1;
+                    println!(
+                        "{}",
+                        tr!(
+                            "cli-alerts-send-failed",
+                            channel = channel.name(),
+                            error = format!("{err:#}")
+                        )
+                    );
+                }
+            }
+        }
+        if failed > 0 {
+            bail!(
+                "{failed} of {} alert channels did not take the test alert",
+                channels.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn handle_digest(config_arg: Option<Utf8PathBuf>, send: bool) -> Result<()> {
+    let (mut config, _) = Config::detect_and_load(config_arg)?;
+    config.resolve_secrets()?;
+    // SAFETY: no other threads exist yet.
+    unsafe { network::export_proxy_env(&config.network) };
+    let git = GitFacade::new(&config)?;
+    let summary = if send {
+        digest::send(&git, &config)?
+    } else {
+        digest::summarize(&git, &config)
+    };
+    println!("{}\n{}", summary.title(), summary.body());
+    if send {
+        println!("{}", tr!("cli-digest-sent"));
+        return Ok(());
+    }
+    match digest::next_digest_in(&config) {
+        Some(due_in) => {
+            let due_in =
+                humantime::format_duration(Duration::from_secs(due_in.as_secs() / 60 * 60));
+            println!("{}", tr!("cli-digest-next", time = due_in.to_string()));
+        }
+        None => println!("{}", tr!("cli-digest-off")),
+    }
+    Ok(())
+}
+
+fn handle_completions(shell: Shell) -> Result<()> {
+    clap_complete::generate(shell, &mut Cli::command(), BIN_NAME, &mut std::io::stdout());
+    Ok(())
+}
+
+fn handle_man(out_dir: Option<&Path>) -> Result<()> {
+    let command = Cli::command();
+    let Some(dir) = out_dir else {
+        return clap_mangen::Man::new(command)
+            .render(&mut std::io::stdout())
+            .context("failed to write the manual page");
+    };
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    clap_mangen::generate_to(command, dir)
+        .with_context(|| format!("failed to write manual pages to {}", dir.display()))?;
+    println!(
+        "{}",
+        tr!("cli-man-written", dir = dir.display().to_string())
+    );
+    Ok(())
+}
+
+#[cfg(feature = "selftest")]
+fn handle_selftest() -> Result<()> {
+    let mut failed = 0;
+    for (name, scenario) in testsupport::scenarios() {
+        match scenario() {
+            Ok(()) => println!("ok      {name}"),
+            Err(err) => {
+                failed += 1;
+                println!("FAILED  {name}: {err:#}");
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{failed} selftest scenario(s) failed");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "selftest")]
+fn handle_bench(files: usize, cycles: usize) -> Result<()> {
+    println!("generating {files} notes...");
+    let report = bench::run(files, cycles)?;
+    print!("{}", report.render());
+    Ok(())
+}
+
+#[cfg(feature = "selftest")]
+fn handle_simulate(scenario: Simulation, keep: bool) -> Result<()> {
+    let mut outcome = testsupport::simulate(scenario)?;
+    match &outcome.recovery {
+        Ok(changed) => println!("recovery succeeded (changes published: {changed})"),
+        Err(err) => println!("recovery failed: {err:#}"),
+    }
+    println!("vault state:\n{}", outcome.device.status()?.trim_end());
+    if keep {
+        outcome.sandbox.keep();
+        println!(
+            "sandbox kept at {} (device in {})",
+            outcome.sandbox.root().display(),
+            outcome.device.workdir().display()
+        );
+    }
+    Ok(())
+}
+
+struct NewRepoArgs {
+    name: Option<String>,
+    owner: Option<String>,
+    private: bool,
+    lfs: bool,
+    api_url: Option<String>,
+    token: Option<String>,
+    https: bool,
+}
+
+fn handle_init_remote(
+    config_arg: Option<Utf8PathBuf>,
+    provider: Provider,
+    args: NewRepoArgs,
+) -> Result<()> {
+    let path = Config::resolve_path(config_arg)?;
+    let mut config = Config::load_from_path(&path)?;
+    let token = match args.token {
+        Some(token) => secrets::resolve(&token)?,
+        None => {
+            let var = provider.token_env();
+            std::env::var(var)
+                .with_context(|| format!("no API token: pass --token or set {var}"))?
+        }
+    };
+    let name = match args.name {
+        Some(name) => name,
+        None => config
+            .workdir
+            .file_name()
+            .map(str::to_string)
+            .context("cannot derive a repository name from the workdir; pass --name")?,
+    };
+    let repo = NewRepo {
+        name,
+        owner: args.owner,
+        private: args.private,
+        default_branch: config.branch.clone(),
+        lfs: args.lfs,
+    };
+    let client = ProviderClient::new(provider, args.api_url.as_deref(), token, &config.network)?;
+    let created = client
+        .create_repo(&repo)
+        .with_context(|| format!("failed to create repository '{}'", repo.name))?;
+    println!("Created {}", created.web_url);
+    if args.lfs && provider != Provider::Gitlab {
+        println!(
+            "LFS has no per-repository switch on {provider:?}; it is available when the instance allows it."
+        );
+    }
+
+    config.repo_url = if args.https {
+        created.https_url
+    } else {
+        created.ssh_url
+    };
+    config.save_to_path(&path)?;
+    println!(
+        "Set repo_url to {} in {path}. The first push creates branch '{}' as the default.",
+        config.repo_url, config.branch
+    );
+    Ok(())
+}
+
+fn handle_settings(config_arg: Option<Utf8PathBuf>, command: SettingsCommand) -> Result<()> {
+    match command {
+        SettingsCommand::Show => {
+            let (config, _) = Config::detect_and_load(config_arg)?;
+            let rendered =
+                serde_yaml::to_string(&config).context("failed to render configuration as YAML")?;
+            println!("{rendered}");
+            Ok(())
+        }
+        SettingsCommand::Set { key, value } => {
+            let path = Config::resolve_path(config_arg.clone())?;
+            let mut config = Config::load_from_path(&path)?;
+            apply_setting(&mut config, key, &value)?;
+            config.save_to_path(&path)?;
+            println!("Updated {key:?} in {path}");
+            Ok(())
+        }
+        SettingsCommand::Share => {
+            let (config, _) = Config::detect_and_load(config_arg)?;
+            if !config.shared_config.enabled {
+                bail!(
+                    "shared config is disabled; enable it with `obsyncgit settings set shared-config.enabled true`"
+                );
+            }
+            let path = SharedPolicy::from_config(&config).save(&config.workdir)?;
+            println!("Wrote shared policy to {path}. The daemon will commit it on its next cycle.");
+            Ok(())
+        }
+        SettingsCommand::SetSecret { name, from_env } => {
+            let secret = match from_env {
+                Some(var) => std::env::var(&var)
+                    .with_context(|| format!("environment variable {var} is not set"))?,
+                None => read_secret(&format!("Secret for '{name}': "))?,
+            };
+            if secret.is_empty() {
+                bail!("refusing to store an empty secret");
+            }
+            secrets::store(&name, &secret)?;
+            println!(
+                "Stored secret '{name}' in the OS keyring. Reference it in the config as \"!keyring:{name}\"."
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Asks a yes/no question on the terminal; anything but "y" or "yes" is a
+/// no. Fails without a terminal, where nobody can answer.
+fn confirm(prompt: &str) -> Result<bool> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        bail!("no terminal to confirm on; pass --yes to answer yes");
+    }
+    eprint!("{prompt} [y/N] ");
+    std::io::stderr().flush().ok();
+    let mut line = String::new();
+    stdin
+        .lock()
+        .read_line(&mut line)
+        .context("failed to read the answer from stdin")?;
+    Ok(matches!(line.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Reads one line from stdin, hiding the input when it comes from a terminal.
+fn read_secret(prompt: &str) -> Result<String> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    if interactive {
+        eprint!("{prompt}");
+        std::io::stderr().flush().ok();
+    }
+    let echo_off = interactive && set_terminal_echo(false);
+    let mut line = String::new();
+    let result = stdin.lock().read_line(&mut line);
+    if echo_off {
+        set_terminal_echo(true);
+        eprintln!();
+    }
+    result.context("failed to read secret from stdin")?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(unix)]
+fn set_terminal_echo(enabled: bool) -> bool {
+    std::process::Command::new("stty")
+        .arg(if enabled { "echo" } else { "-echo" })
+        .stdin(std::process::Stdio::inherit())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(not(unix))]
+fn set_terminal_echo(_enabled: bool) -> bool {
+    false
+}
+
+fn apply_setting(config: &mut Config, key: SettingsKey, value: &str) -> Result<()> {
+    match key {
+        SettingsKey::RepoUrl => config.repo_url = value.to_string(),
+        SettingsKey::Branch => config.branch = value.to_string(),
+        SettingsKey::Remote => config.remote.name = value.to_string(),
+        SettingsKey::RemoteKind => config.remote.kind = value.parse()?,
+        SettingsKey::Workdir => {
+            if value.trim().is_empty() {
+                bail!("workdir cannot be empty");
+            }
+            config.workdir = Utf8PathBuf::from(value);
+        }
+        SettingsKey::SelfUpdateEnabled => {
+            config.self_update.enabled = parse_bool(value)?;
+        }
+        SettingsKey::SelfUpdateIntervalHours => {
+            config.self_update.interval_hours = parse_optional_hours(value)?;
+        }
+        SettingsKey::SelfUpdateCommand => {
+            let cleaned = value.trim();
+            if cleaned.eq_ignore_ascii_case("none") || cleaned.is_empty() {
+                config.self_update.command = None;
+            } else {
+                config.self_update.command = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::SelfUpdateShell => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.self_update.shell = None;
+            } else {
+                config.self_update.shell = Some(cleaned.parse()?);
+            }
+        }
+        SettingsKey::SelfUpdateChannel => {
+            config.self_update.channel = value.parse()?;
+        }
+        SettingsKey::SelfUpdateAllowPrerelease => {
+            config.self_update.allow_prerelease = parse_bool(value)?;
+        }
+        SettingsKey::SelfUpdateAllowUnsigned => {
+            config.self_update.allow_unsigned = parse_bool(value)?;
+        }
+        SettingsKey::SelfUpdatePinVersion => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.self_update.pin_version = None;
+            } else {
+                config.self_update.pin_version = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::SharedConfigEnabled => {
+            config.shared_config.enabled = parse_bool(value)?;
+        }
+        SettingsKey::SyncMode => {
+            config.sync.mode = value.parse()?;
+        }
+        SettingsKey::SyncTopology => {
+            config.sync.topology = value.parse()?;
+        }
+        SettingsKey::SyncDeviceName => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.sync.device_name = None;
+            } else {
+                config.sync.device_name = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::SyncMeteredPolicy => {
+            config.sync.metered_policy = value.parse()?;
+        }
+        SettingsKey::CommitMode => {
+            config.commit.mode = value.parse()?;
+        }
+        SettingsKey::IgnoreMaxFileSizeMb => {
+            config.ignore.max_file_size_mb = value
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid size in megabytes '{value}'"))?;
+        }
+        SettingsKey::CommitAmendWindowMinutes => {
+            config.commit.amend_window_minutes = value
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid number of minutes '{value}'"))?;
+        }
+        SettingsKey::CommitNotes => {
+            config.commit.notes = parse_bool(value)?;
+        }
+        SettingsKey::CommitSignoff => {
+            config.commit.signoff = parse_bool(value)?;
+        }
+        SettingsKey::SyncCaseCollisions => {
+            config.sync.case_collisions = value.parse()?;
+        }
+        SettingsKey::SyncFileNames => {
+            config.sync.file_names = value.parse()?;
+        }
+        SettingsKey::SyncConflictCopies => {
+            config.sync.conflict_copies = value.parse()?;
+        }
+        SettingsKey::CompatUnicodeNormalization => {
+            config.compat.unicode_normalization = value.parse()?;
+        }
+        SettingsKey::CompatLineEndings => {
+            config.compat.line_endings = value.parse()?;
+        }
+        SettingsKey::DeletionsMode => {
+            config.deletions.mode = value.parse()?;
+        }
+        SettingsKey::WatcherBackend => {
+            config.watcher.backend = value.parse()?;
+        }
+        SettingsKey::GuiTheme => {
+            config.gui.theme = value.parse()?;
+        }
+        SettingsKey::GuiHighContrast => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("auto") {
+                config.gui.high_contrast = None;
+            } else {
+                config.gui.high_contrast = Some(parse_bool(cleaned)?);
+            }
+        }
+        SettingsKey::GitSshKeyPath => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
                 config.git.ssh_key_path = None;
             } else {
                 config.git.ssh_key_path = Some(cleaned.to_string());
             }
         }
+        SettingsKey::GitStatusCache => {
+            config.git.status_cache = value.parse()?;
+        }
+        SettingsKey::GitRunHooks => {
+            config.git.run_hooks = parse_bool(value)?;
+        }
+        SettingsKey::DiskMinFreeMb => {
+            config.disk.min_free_mb = value
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid number of megabytes '{value}'"))?;
+        }
+        SettingsKey::IntegrityCheckIntervalHours => {
+            config.integrity.check_interval_hours = value
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid number of hours '{value}'"))?;
+        }
+        SettingsKey::IntegrityAutoRepair => {
+            config.integrity.auto_repair = parse_bool(value)?;
+        }
+        SettingsKey::BackupDir => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.backup.dir = None;
+            } else {
+                config.backup.dir = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::BackupIntervalHours => {
+            config.backup.interval_hours = value
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid number of hours '{value}'"))?;
+        }
+        SettingsKey::BackupKeep => {
+            config.backup.keep = value
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid number of bundles '{value}'"))?;
+        }
+        SettingsKey::PowerLowBatteryPercent => {
+            let percent: u8 = value
+                .trim()
+                .trim_end_matches('%')
+                .parse()
+                .with_context(|| format!("invalid battery percentage '{value}'"))?;
+            if percent > 100 {
+                bail!("invalid battery percentage '{value}' (expected 0 to 100)");
+            }
+            config.power.low_battery_percent = percent;
+        }
+        SettingsKey::DigestEnabled => {
+            config.digest.enabled = parse_bool(value)?;
+        }
+        SettingsKey::DigestIntervalHours => {
+            config.digest.interval_hours = value
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid number of hours '{value}'"))?;
+        }
+        SettingsKey::DigestDesktop => {
+            config.digest.desktop = parse_bool(value)?;
+        }
+        SettingsKey::DigestWebhookUrl => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.digest.webhook_url = None;
+            } else {
+                config.digest.webhook_url = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::MqttEnabled => {
+            config.mqtt.enabled = parse_bool(value)?;
+        }
+        SettingsKey::MqttBroker => {
+            let cleaned = value.trim();
+            if !cleaned.is_empty() {
+                mqtt::Broker::parse(cleaned)?;
+            }
+            config.mqtt.broker = cleaned.to_string();
+        }
+        SettingsKey::MqttTopic => {
+            let cleaned = value.trim().trim_matches('/');
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.mqtt.topic = None;
+            } else if cleaned.contains(['+', '#']) {
+                bail!("invalid topic '{value}' (wildcards + and # cannot be published to)");
+            } else {
+                config.mqtt.topic = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::MqttUsername => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.mqtt.username = None;
+            } else {
+                config.mqtt.username = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::MqttPassword => {
+            if value.is_empty() || value.eq_ignore_ascii_case("none") {
+                config.mqtt.password = None;
+            } else {
+                config.mqtt.password = Some(value.to_string());
+            }
+        }
+        SettingsKey::MqttHomeAssistant => {
+            config.mqtt.home_assistant = parse_bool(value)?;
+        }
+        SettingsKey::AlertsAfterFailures => {
+            config.alerts.after_failures = value
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid number of failures '{value}'"))?;
+        }
+        SettingsKey::AlertsOnConflict => {
+            config.alerts.on_conflict = parse_bool(value)?;
+        }
+        SettingsKey::AlertsEmailTo => {
+            config.alerts.email.to = value
+                .split(',')
+                .map(str::trim)
+                .filter(|address| !address.is_empty() && !address.eq_ignore_ascii_case("none"))
+                .map(str::to_string)
+                .collect();
+        }
+        SettingsKey::AlertsEmailFrom => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.alerts.email.from = None;
+            } else {
+                config.alerts.email.from = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::AlertsEmailServer => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.alerts.email.server = None;
+            } else {
+                config.alerts.email.server = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::AlertsEmailPort => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.alerts.email.port = None;
+            } else {
+                config.alerts.email.port = Some(
+                    cleaned
+                        .parse()
+                        .with_context(|| format!("invalid port '{value}'"))?,
+                );
+            }
+        }
+        SettingsKey::AlertsEmailTls => {
+            config.alerts.email.tls = value.parse()?;
+        }
+        SettingsKey::AlertsEmailUsername => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.alerts.email.username = None;
+            } else {
+                config.alerts.email.username = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::AlertsEmailPassword => {
+            if value.is_empty() || value.eq_ignore_ascii_case("none") {
+                config.alerts.email.password = None;
+            } else {
+                config.alerts.email.password = Some(value.to_string());
+            }
+        }
+        SettingsKey::AlertsTelegramBotToken => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.alerts.telegram.bot_token = None;
+            } else {
+                config.alerts.telegram.bot_token = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::AlertsTelegramChatId => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.alerts.telegram.chat_id = None;
+            } else {
+                config.alerts.telegram.chat_id = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::AlertsTelegramTemplate => {
+            config.alerts.telegram.template = if value.trim().is_empty() {
+                TelegramAlertConfig::default().template
+            } else {
+                value.replace("\\n", "\n")
+            };
+        }
+        SettingsKey::AlertsDiscordWebhookUrl => {
+            let cleaned = value.trim();
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.alerts.discord.webhook_url = None;
+            } else {
+                config.alerts.discord.webhook_url = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::AlertsDiscordTemplate => {
+            config.alerts.discord.template = if value.trim().is_empty() {
+                DiscordAlertConfig::default().template
+            } else {
+                value.replace("\\n", "\n")
+            };
+        }
+        SettingsKey::SnapshotsTagDaily => {
+            config.snapshots.tag_daily = parse_bool(value)?;
+        }
+        SettingsKey::SharedVaultEnabled => {
+            config.shared_vault.enabled = parse_bool(value)?;
+        }
+        SettingsKey::SharedVaultUserFolder => {
+            let cleaned = value.trim().trim_matches('/');
+            if cleaned.is_empty() || cleaned.eq_ignore_ascii_case("none") {
+                config.shared_vault.user_folder = None;
+            } else {
+                config.shared_vault.user_folder = Some(cleaned.to_string());
+            }
+        }
+        SettingsKey::SharedVaultSoftLocks => {
+            config.shared_vault.soft_locks = parse_bool(value)?;
+        }
     }
     Ok(())
 }
@@ -236,8 +3154,9 @@ fn default_config() -> Config {
     Config {
         repo_url: "git@github.com:username/repo.git".to_string(),
         branch: "main".to_string(),
-        remote: "origin".to_string(),
+        remote: RemoteConfig::default(),
         workdir,
+        state_dir: None,
         debounce_seconds: 5,
         poll_interval_seconds: 300,
         commit: CommitConfig::default(),
@@ -247,17 +3166,45 @@ fn default_config() -> Config {
                 "**/*.tmp".to_string(),
                 "**/*.swp".to_string(),
             ],
+            ..IgnoreConfig::default()
         },
         self_update: SelfUpdateConfig {
             enabled: true,
-            command: None,
-            interval_hours: Some(24),
+            ..SelfUpdateConfig::default()
         },
         git: GitOptions::default(),
+        remote_availability: RemoteAvailabilityConfig::default(),
+        stability: StabilityConfig::default(),
+        shared_config: SharedConfigOptions::default(),
+        network: NetworkConfig::default(),
+        sync: SyncConfig::default(),
+        schedule: ScheduleConfig::default(),
+        deletions: DeletionConfig::default(),
+        trace: TraceConfig::default(),
+        watcher: WatcherConfig::default(),
+        submodules: SubmoduleConfig::default(),
+        gui: GuiConfig::default(),
+        bootstrap: BootstrapConfig::default(),
+        compat: CompatConfig::default(),
+        disk: DiskConfig::default(),
+        integrity: IntegrityConfig::default(),
+        backup: BackupConfig::default(),
+        power: PowerConfig::default(),
+        security: SecurityConfig::default(),
+        replicas: Vec::new(),
+        digest: DigestConfig::default(),
+        mqtt: MqttConfig::default(),
+        alerts: AlertsConfig::default(),
+        snapshots: SnapshotConfig::default(),
+        retention: RetentionConfig::default(),
+        shared_vault: SharedVaultConfig::default(),
+        launchd: LaunchdConfig::default(),
     }
 }
 
-fn init_logging() {
+/// Sets up logging into `writer`; `json` (or `OBSYNCGIT_LOG_FORMAT=json`)
+/// switches to one JSON object per line for log collectors.
+fn init_logging(json: bool, writer: BoxMakeWriter) {
     use tracing_subscriber::EnvFilter;
 
     let filter = std::env::var("OBSYNCGIT_LOG")
@@ -265,13 +3212,20 @@ fn init_logging() {
         .or_else(|_| std::env::var("RUST_LOG"))
         .unwrap_or_else(|_| "info".to_string());
 
-    let subscriber = tracing_subscriber::fmt()
+    let json = json
+        || std::env::var("OBSYNCGIT_LOG_FORMAT")
+            .is_ok_and(|format| format.eq_ignore_ascii_case("json"));
+    let builder = tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::new(filter))
         .with_target(false)
-        .compact()
-        .finish();
+        .with_writer(writer);
+    let result = if json {
+        tracing::subscriber::set_global_default(builder.json().flatten_event(true).finish())
+    } else {
+        tracing::subscriber::set_global_default(builder.compact().finish())
+    };
 
-    if let Err(err) = tracing::subscriber::set_global_default(subscriber) {
+    if let Err(err) = result {
         eprintln!("failed to initialize logging: {err}");
     }
 }
@@ -0,0 +1,106 @@
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info};
+
+/// How often the platform is asked about the connection cost. Detection
+/// spawns helper processes, so results are cached in between.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Detects whether the active network connection is metered or roaming.
+#[derive(Debug, Default)]
+pub struct MeteredDetector {
+    last_check: Option<Instant>,
+    metered: bool,
+}
+
+impl MeteredDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_metered(&mut self) -> bool {
+        let due = self
+            .last_check
+            .is_none_or(|checked| checked.elapsed() >= RECHECK_INTERVAL);
+        if due {
+            let metered = platform::detect().unwrap_or(false);
+            if metered != self.metered {
+                if metered {
+                    info!("metered connection detected, applying metered sync policy");
+                } else {
+                    info!("connection is no longer metered, resuming normal sync");
+                }
+            }
+            self.metered = metered;
+            self.last_check = Some(Instant::now());
+        }
+        self.metered
+    }
+}
+
+#[cfg_attr(not(any(target_os = "linux", target_os = "windows")), allow(dead_code))]
+fn command_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| debug!(?err, program, "metered detection helper unavailable"))
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::command_stdout;
+
+    /// Reads NetworkManager's global `Metered` property (NMMetered enum:
+    /// 1 = yes, 3 = guess-yes).
+    pub(super) fn detect() -> Option<bool> {
+        let output = command_stdout(
+            "busctl",
+            &[
+                "get-property",
+                "org.freedesktop.NetworkManager",
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.NetworkManager",
+                "Metered",
+            ],
+        )?;
+        let value: u32 = output.split_whitespace().last()?.parse().ok()?;
+        Some(matches!(value, 1 | 3))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::command_stdout;
+
+    const SCRIPT: &str = "$p = [Windows.Networking.Connectivity.NetworkInformation,Windows.Networking.Connectivity,ContentType=WindowsRuntime]::GetInternetConnectionProfile(); \
+        if ($p) { $c = $p.GetConnectionCost(); \"$($c.NetworkCostType) $($c.Roaming)\" }";
+
+    /// Queries the WinRT connection cost of the current internet profile.
+    pub(super) fn detect() -> Option<bool> {
+        let output = command_stdout(
+            "powershell",
+            &["-NoProfile", "-NonInteractive", "-Command", SCRIPT],
+        )?;
+        let mut parts = output.split_whitespace();
+        let cost = parts.next()?;
+        let roaming = parts
+            .next()
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+        Some(roaming || !(cost.eq_ignore_ascii_case("Unrestricted") || cost == "Unknown"))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform {
+    pub(super) fn detect() -> Option<bool> {
+        None
+    }
+}
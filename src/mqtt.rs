@@ -0,0 +1,413 @@
+//! `mqtt`: publishes the vault's sync state to an MQTT broker, so a home
+//! automation dashboard such as Home Assistant can show whether the vault
+//! is in sync and raise an alert once it stops syncing. The state is a
+//! retained JSON message on `<topic>/state`, sent again whenever it
+//! changes, and `<topic>/availability` reads `online` while the daemon runs;
+//! the broker turns it to `offline` when the daemon's connection drops.
+//! With `home_assistant` on, discovery messages create the entities.
+
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use chrono::{SecondsFormat, Utc};
+use rumqttc::{Client, Connection, Event, LastWill, MqttOptions, Outgoing, Packet, QoS, Transport};
+use serde_json::{Value, json};
+use tracing::{debug, info, warn};
+
+use crate::config::Config;
+use crate::instance;
+use crate::statusbar::SyncHealth;
+
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+/// Time between attempts to reach a broker that is down.
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// How long stopping waits for `offline` to go out.
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Messages waiting for the connection: the discovery messages and a few
+/// states.
+const QUEUE: usize = 32;
+
+/// Where `mqtt.broker` points.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Broker {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+impl Broker {
+    /// Reads `mqtt://host[:port]` or `mqtts://host[:port]`; a bare host
+    /// is `mqtt://`.
+    pub fn parse(url: &str) -> Result<Self> {
+        let url = url.trim().trim_end_matches('/');
+        if url.is_empty() {
+            bail!("mqtt.broker is not set");
+        }
+        let (tls, address) = match url.split_once("://") {
+            Some(("mqtts" | "ssl", address)) => (true, address),
+            Some(("mqtt" | "tcp", address)) => (false, address),
+            Some((scheme, _)) => {
+                bail!("mqtt.broker '{url}' uses {scheme}://; expected mqtt:// or mqtts://")
+            }
+            None => (false, url),
+        };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => {
+                let port = port
+                    .parse()
+                    .with_context(|| format!("invalid port in mqtt.broker '{url}'"))?;
+                (host, port)
+            }
+            _ => (address, if tls { 8883 } else { 1883 }),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() || host.contains('/') {
+            bail!("mqtt.broker '{url}' has no host");
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            tls,
+        })
+    }
+}
+
+impl std::fmt::Display for Broker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scheme = if self.tls { "mqtts" } else { "mqtt" };
+        if self.host.contains(':') {
+            write!(f, "{scheme}://[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{scheme}://{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// The topic the state is published below: `mqtt.topic`, or else
+/// `obsyncgit/<device>/<vault folder>`.
+pub fn base_topic(config: &Config) -> Result<String> {
+    let topic = match &config.mqtt.topic {
+        Some(topic) => topic.trim().trim_matches('/').to_string(),
+        None => {
+            let folder: String = config
+                .workdir
+                .file_name()
+                .unwrap_or("vault")
+                .chars()
+                .map(|ch| {
+                    if ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_') {
+                        ch.to_ascii_lowercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            format!("obsyncgit/{}/{folder}", config.sync.device_name())
+        }
+    };
+    if topic.is_empty() || topic.contains(['+', '#']) {
+        bail!("mqtt.topic '{topic}' is not a topic to publish to");
+    }
+    Ok(topic)
+}
+
+/// Whether the broker accepts connections, for `obsyncgit doctor`. Only
+/// the TCP connection is tried; credentials are checked by the daemon.
+pub fn reachable(broker: &Broker) -> Result<()> {
+    let addresses: Vec<_> = (broker.host.as_str(), broker.port)
+        .to_socket_addrs()
+        .with_context(|| format!("cannot resolve {}", broker.host))?
+        .collect();
+    let mut last = None;
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, Duration::from_secs(5)) {
+            Ok(_) => return Ok(()),
+            Err(err) => last = Some(err),
+        }
+    }
+    match last {
+        Some(err) => Err(err).with_context(|| format!("cannot connect to {broker}")),
+        None => bail!("{} has no address", broker.host),
+    }
+}
+
+/// The daemon's connection to the broker. A background thread keeps it
+/// up, reconnecting every [`RECONNECT_DELAY`] while the broker is down.
+pub struct MqttPublisher {
+    client: Client,
+    state_topic: String,
+    availability_topic: String,
+    /// The last state published; sent again after a reconnect.
+    latest: Arc<Mutex<Option<String>>>,
+    connected: Arc<AtomicBool>,
+    /// RFC 3339; when a sync last reached the remote.
+    last_sync: Mutex<Option<String>>,
+    connection: JoinHandle<()>,
+}
+
+impl MqttPublisher {
+    /// `None` unless `mqtt.enabled` is set. Fails on settings that cannot
+    /// work; an unreachable broker is only retried.
+    pub fn start(config: &Config) -> Result<Option<Self>> {
+        let settings = &config.mqtt;
+        if !settings.enabled {
+            return Ok(None);
+        }
+        let broker = Broker::parse(&settings.broker)?;
+        let base = base_topic(config)?;
+        let availability_topic = format!("{base}/availability");
+        let state_topic = format!("{base}/state");
+        let node = format!("obsyncgit_{}", instance::vault_key(config));
+
+        let mut options = MqttOptions::new(
+            format!("{node}_{}", config.sync.device_name()),
+            broker.host.clone(),
+            broker.port,
+        );
+        options.set_keep_alive(Duration::from_secs(60));
+        options.set_last_will(LastWill::new(
+            &availability_topic,
+            OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &settings.username {
+            options.set_credentials(username, settings.password.clone().unwrap_or_default());
+        }
+        if broker.tls {
+            options.set_transport(Transport::tls_with_default_config());
+        }
+        let (client, connection) = Client::new(options, QUEUE);
+
+        let mut announcements = vec![(availability_topic.clone(), ONLINE.to_string())];
+        if settings.home_assistant {
+            announcements.extend(discovery(config, &node, &state_topic, &availability_topic));
+        }
+        let latest = Arc::new(Mutex::new(None));
+        let connected = Arc::new(AtomicBool::new(false));
+        let link = Link {
+            client: client.clone(),
+            broker: broker.to_string(),
+            announcements,
+            state_topic: state_topic.clone(),
+            latest: latest.clone(),
+            connected: connected.clone(),
+        };
+        let connection = thread::Builder::new()
+            .name("obsyncgit-mqtt".to_string())
+            .spawn(move || link.drive(connection))
+            .context("failed to start the MQTT connection")?;
+        info!(broker = %broker, topic = %state_topic, "publishing the sync state over MQTT");
+        Ok(Some(Self {
+            client,
+            state_topic,
+            availability_topic,
+            latest,
+            connected,
+            last_sync: Mutex::new(None),
+            connection,
+        }))
+    }
+
+    /// Notes that a sync just reached the remote.
+    pub fn synced(&self) {
+        *self.last_sync.lock().unwrap() =
+            Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+    }
+
+    /// Publishes `health` unless it is what the broker already has. While
+    /// disconnected only the latest state is kept, to be sent on reconnect.
+    pub fn report(&self, health: &SyncHealth) {
+        let payload = state(health, self.last_sync.lock().unwrap().as_deref()).to_string();
+        let mut latest = self.latest.lock().unwrap();
+        if latest.as_deref() == Some(payload.as_str()) {
+            return;
+        }
+        if self.connected.load(Ordering::Relaxed)
+            && let Err(err) =
+                self.client
+                    .try_publish(&self.state_topic, QoS::AtLeastOnce, true, payload.clone())
+        {
+            debug!(?err, "failed to queue the sync state for MQTT");
+        }
+        *latest = Some(payload);
+    }
+
+    /// Marks the daemon `offline` and disconnects, waiting briefly for the
+    /// broker to take it.
+    pub fn stop(self) {
+        if self.connected.load(Ordering::Relaxed) {
+            let _ =
+                self.client
+                    .try_publish(&self.availability_topic, QoS::AtLeastOnce, true, OFFLINE);
+        }
+        let _ = self.client.try_disconnect();
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while !self.connection.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+/// What the connection thread needs.
+struct Link {
+    client: Client,
+    broker: String,
+    /// Sent, retained, on every connect.
+    announcements: Vec<(String, String)>,
+    state_topic: String,
+    latest: Arc<Mutex<Option<String>>>,
+    connected: Arc<AtomicBool>,
+}
+
+impl Link {
+    fn drive(self, mut connection: Connection) {
+        let mut warned = false;
+        for notification in connection.iter() {
+            match notification {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    if warned {
+                        info!(broker = %self.broker, "reached the MQTT broker again");
+                    }
+                    warned = false;
+                    self.announce();
+                }
+                Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                Ok(_) => {}
+                Err(err) => {
+                    self.connected.store(false, Ordering::Relaxed);
+                    if warned {
+                        debug!(?err, "MQTT broker still unreachable");
+                    } else {
+                        warn!(broker = %self.broker, ?err, "failed to reach the MQTT broker; retrying");
+                        warned = true;
+                    }
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+        self.connected.store(false, Ordering::Relaxed);
+    }
+
+    fn announce(&self) {
+        let latest = self.latest.lock().unwrap();
+        let state = latest
+            .as_ref()
+            .map(|payload| (self.state_topic.clone(), payload.clone()));
+        for (topic, payload) in self.announcements.iter().cloned().chain(state) {
+            if let Err(err) = self
+                .client
+                .try_publish(topic, QoS::AtLeastOnce, true, payload)
+            {
+                debug!(?err, "failed to queue an MQTT message");
+            }
+        }
+        self.connected.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The JSON published on `<topic>/state`.
+fn state(health: &SyncHealth, last_sync: Option<&str>) -> Value {
+    let error = health
+        .failure
+        .as_ref()
+        .map(|failure| failure.message().to_string())
+        .or_else(|| health.error.clone());
+    json!({
+        "state": health.health.as_str(),
+        "vault": health.vault,
+        "branch": health.branch,
+        "pending": health.pending,
+        "unpushed": health.unpushed,
+        "conflicts": health.conflicts,
+        "paused_minutes": health.paused_minutes,
+        "error": error,
+        "error_category": health.failure.as_ref().map(|failure| failure.category()),
+        "damaged": health.damage > 0,
+        "held_secrets": health.held_secrets,
+        "last_sync": last_sync,
+    })
+}
+
+/// Home Assistant discovery messages: the state, a problem flag for
+/// alerts, the pending changes and the last sync.
+fn discovery(
+    config: &Config,
+    node: &str,
+    state_topic: &str,
+    availability_topic: &str,
+) -> Vec<(String, String)> {
+    let prefix = config.mqtt.discovery_prefix.trim().trim_matches('/');
+    let device = json!({
+        "identifiers": [node],
+        "name": format!(
+            "ObsyncGit {} on {}",
+            config.workdir.file_name().unwrap_or("vault"),
+            config.sync.device_name()
+        ),
+        "manufacturer": "ObsyncGit",
+        "model": "ObsyncGit",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    let entity = |component: &str, object: &str, fields: Value| {
+        let mut payload = json!({
+            "unique_id": format!("{node}_{object}"),
+            "object_id": format!("{node}_{object}"),
+            "state_topic": state_topic,
+            "availability_topic": availability_topic,
+            "device": device,
+        });
+        if let (Some(payload), Value::Object(fields)) = (payload.as_object_mut(), fields) {
+            payload.extend(fields);
+        }
+        (
+            format!("{prefix}/{component}/{node}/{object}/config"),
+            payload.to_string(),
+        )
+    };
+    vec![
+        entity(
+            "sensor",
+            "sync_state",
+            json!({
+                "name": "Sync state",
+                "icon": "mdi:sync",
+                "value_template": "{{ value_json.state }}",
+                "json_attributes_topic": state_topic,
+            }),
+        ),
+        entity(
+            "binary_sensor",
+            "sync_problem",
+            json!({
+                "name": "Sync problem",
+                "device_class": "problem",
+                "value_template": "{{ 'ON' if value_json.state in ['error', 'conflict'] else 'OFF' }}",
+            }),
+        ),
+        entity(
+            "sensor",
+            "pending_changes",
+            json!({
+                "name": "Pending changes",
+                "icon": "mdi:file-clock",
+                "state_class": "measurement",
+                "value_template": "{{ value_json.pending }}",
+            }),
+        ),
+        entity(
+            "sensor",
+            "last_sync",
+            json!({
+                "name": "Last sync",
+                "device_class": "timestamp",
+                "value_template": "{{ value_json.last_sync }}",
+            }),
+        ),
+    ]
+}
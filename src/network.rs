@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use reqwest::{NoProxy, Proxy};
+use tracing::debug;
+
+use crate::config::NetworkConfig;
+
+/// Environment variables understood by both git's curl transport and the
+/// HTTP client used for self-updates.
+pub fn proxy_env(config: &NetworkConfig) -> Vec<(&'static str, String)> {
+    let Some(proxy) = &config.proxy else {
+        return Vec::new();
+    };
+    let url = proxy.url.trim();
+    if url.is_empty() {
+        return Vec::new();
+    }
+
+    let mut vars = Vec::new();
+    for key in [
+        "http_proxy",
+        "HTTP_PROXY",
+        "https_proxy",
+        "HTTPS_PROXY",
+        "all_proxy",
+        "ALL_PROXY",
+    ] {
+        vars.push((key, url.to_string()));
+    }
+    if !proxy.no_proxy.is_empty() {
+        let hosts = proxy.no_proxy.join(",");
+        vars.push(("no_proxy", hosts.clone()));
+        vars.push(("NO_PROXY", hosts));
+    }
+    vars
+}
+
+/// The configured proxy, SOCKS included, for an HTTP client built here.
+pub fn http_proxy(config: &NetworkConfig) -> Result<Option<Proxy>> {
+    let Some(proxy) = &config.proxy else {
+        return Ok(None);
+    };
+    let url = proxy.url.trim();
+    if url.is_empty() {
+        return Ok(None);
+    }
+    let http = Proxy::all(url).with_context(|| format!("invalid proxy URL '{url}'"))?;
+    Ok(Some(
+        http.no_proxy(NoProxy::from_string(&proxy.no_proxy.join(","))),
+    ))
+}
+
+/// Exports the proxy settings into this process's environment so that HTTP
+/// clients we cannot configure, such as the one listing releases for
+/// self-update, pick them up too.
+///
+/// # Safety
+///
+/// Must be called before any other threads are spawned, as modifying the
+/// environment concurrently with reads is undefined behaviour.
+pub unsafe fn export_proxy_env(config: &NetworkConfig) {
+    let vars = proxy_env(config);
+    if vars.is_empty() {
+        return;
+    }
+    for (key, value) in vars {
+        // SAFETY: upheld by the caller.
+        unsafe { std::env::set_var(key, value) };
+    }
+    debug!("proxy settings exported for self-update");
+}
@@ -0,0 +1,151 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use icu_normalizer::ComposingNormalizerBorrowed;
+
+use crate::case_collisions;
+use crate::git::GitFacade;
+
+/// `path` in the composed form (NFC), as Linux and Windows spell it.
+pub fn nfc(path: &str) -> Cow<'_, str> {
+    ComposingNormalizerBorrowed::new_nfc().normalize(path)
+}
+
+/// Whether `path` is already in the composed form.
+pub fn is_nfc(path: &str) -> bool {
+    ComposingNormalizerBorrowed::new_nfc().is_normalized(path)
+}
+
+/// Groups of `paths` that are one name in different Unicode forms, each
+/// sorted. macOS shows one file for each group.
+pub fn duplicates(paths: &[String]) -> Vec<Vec<String>> {
+    let mut forms: BTreeMap<Cow<'_, str>, BTreeSet<&str>> = BTreeMap::new();
+    for path in paths {
+        forms.entry(nfc(path)).or_default().insert(path);
+    }
+    forms
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .map(|paths| paths.into_iter().map(str::to_string).collect())
+        .collect()
+}
+
+/// Files among `pending` whose name is not composed while another tracked
+/// or pending path is the same name in another form. Committing them would
+/// track the note twice.
+pub fn duplicating(root: &Path, pending: &[String], tracked: &[String]) -> Vec<String> {
+    let others: HashSet<&str> = pending.iter().chain(tracked).map(String::as_str).collect();
+    pending
+        .iter()
+        .filter(|path| !is_nfc(path) && others.contains(nfc(path).as_ref()))
+        // A deleted file only removes a duplicate.
+        .filter(|path| root.join(path).symlink_metadata().is_ok())
+        .cloned()
+        .collect()
+}
+
+/// Renames the files among `paths` whose name is not composed, in the
+/// working tree under `root`, returning the renames. Files whose composed
+/// name is taken are left alone.
+pub fn compose_files(root: &Path, paths: &[String]) -> Result<Vec<(String, String)>> {
+    let mut renamed = Vec::new();
+    for path in paths {
+        let composed = nfc(path);
+        if composed == path.as_str() {
+            continue;
+        }
+        let from = root.join(path);
+        let to = root.join(composed.as_ref());
+        // On macOS both names open the same file.
+        if !from.is_file() || to.exists() {
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs::rename(&from, &to)
+            .with_context(|| format!("failed to rename {} to {}", from.display(), to.display()))?;
+        let mut dir = from.parent();
+        while let Some(parent) = dir {
+            if parent == root || fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+        renamed.push((path.clone(), composed.into_owned()));
+    }
+    Ok(renamed)
+}
+
+/// New names for the `tracked` files that are not composed: the composed
+/// name, or `note (2).md` and up when a file already has it.
+pub fn renames(tracked: &[String]) -> Vec<(String, String)> {
+    let mut taken: HashSet<String> = tracked
+        .iter()
+        .filter(|path| is_nfc(path))
+        .cloned()
+        .collect();
+    let mut renames = Vec::new();
+    for path in tracked {
+        let composed = nfc(path);
+        if composed == path.as_str() {
+            continue;
+        }
+        let mut target = composed.to_string();
+        let mut number = 2;
+        while !taken.insert(target.clone()) {
+            target = case_collisions::numbered(&composed, number, false);
+            number += 1;
+        }
+        renames.push((path.clone(), target));
+    }
+    renames
+}
+
+/// Renames every tracked file whose name is not composed and commits the
+/// result, returning the files moved. Other local changes must be committed
+/// first.
+pub fn resolve(git: &GitFacade) -> Result<Vec<(String, String)>> {
+    let tracked = git.tracked_files()?;
+    let renames = renames(&tracked);
+    if renames.is_empty() {
+        return Ok(Vec::new());
+    }
+    // On macOS the renamed files may look modified, so only the rest of the
+    // vault has to be clean.
+    let affected: HashSet<Cow<'_, str>> = renames.iter().map(|(from, _)| nfc(from)).collect();
+    let dirty: Vec<String> = git
+        .list_pending_paths()?
+        .into_iter()
+        .filter(|path| !affected.contains(&nfc(path)))
+        .collect();
+    if !dirty.is_empty() {
+        bail!(
+            "the vault has uncommitted changes ({}); let the daemon commit them first",
+            dirty.join(", ")
+        );
+    }
+    // Where both forms name one file on disk, removing the old name removes
+    // the new one and any file already tracked under it too.
+    let mut keep: Vec<String> = renames.iter().map(|(_, to)| to.clone()).collect();
+    keep.extend(
+        renames
+            .iter()
+            .map(|(from, _)| nfc(from).into_owned())
+            .filter(|composed| tracked.contains(composed)),
+    );
+    let moved = git.rename_tracked(&renames, &keep)?;
+    let summary: Vec<String> = renames
+        .iter()
+        .map(|(from, to)| format!("{from} -> {to}"))
+        .collect();
+    git.commit(&format!(
+        "Rename files to composed Unicode names: {}",
+        summary.join(", ")
+    ))?;
+    Ok(moved)
+}
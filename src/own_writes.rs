@@ -0,0 +1,75 @@
+//! Files the daemon itself just wrote into the vault, such as those a pull
+//! brought in. The watcher reports them like any edit; left alone, each
+//! pull would be followed by a commit cycle that finds nothing to commit.
+//! A reported file is only taken for the daemon's own write while its size
+//! and modification time are still what they were right after the write,
+//! so an edit made meanwhile is never missed.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a write is remembered. Watcher events arrive within a second
+/// or two; polling backends take longer.
+const REMEMBER_FOR: Duration = Duration::from_secs(120);
+
+/// A file as the daemon left it; `None` when it removed the file.
+type Written = Option<(u64, Option<SystemTime>)>;
+
+#[derive(Debug)]
+pub struct OwnWrites {
+    root: PathBuf,
+    written: HashMap<PathBuf, (Written, Instant)>,
+}
+
+impl OwnWrites {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            written: HashMap::new(),
+        }
+    }
+
+    /// Remembers `paths`, relative to the vault, as just written by the
+    /// daemon, along with the folders holding them.
+    pub fn record(&mut self, paths: &[String]) {
+        let now = Instant::now();
+        self.written
+            .retain(|_, (_, recorded)| now.duration_since(*recorded) < REMEMBER_FOR);
+        for path in paths {
+            let path = self.root.join(path);
+            for folder in path.ancestors().skip(1) {
+                if folder == self.root || !folder.starts_with(&self.root) {
+                    break;
+                }
+                let state = snapshot(folder);
+                self.written.insert(folder.to_path_buf(), (state, now));
+            }
+            let state = snapshot(&path);
+            self.written.insert(path, (state, now));
+        }
+    }
+
+    /// Whether every one of `paths`, as the watcher reports them, is a
+    /// file the daemon wrote and nobody changed since. A folder counts as
+    /// long as it holds a written file; its own times change with every
+    /// file added to it.
+    pub fn covers(&self, paths: &[PathBuf]) -> bool {
+        let now = Instant::now();
+        paths.iter().all(|path| {
+            let Some((state, recorded)) = self.written.get(path) else {
+                return false;
+            };
+            if now.duration_since(*recorded) >= REMEMBER_FOR {
+                return false;
+            }
+            path.is_dir() || snapshot(path) == *state
+        })
+    }
+}
+
+fn snapshot(path: &Path) -> Written {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
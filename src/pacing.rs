@@ -0,0 +1,219 @@
+//! When the daemon loop commits, polls and retries. [`Pacer`] keeps the
+//! debounce, poll and backoff state apart from the git work, which it
+//! reaches through [`GitBackend`], so `obsyncgit selftest` can check the
+//! timing against a [`crate::clock::MockClock`] and scripted results
+//! without a repository.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::clock::{Clock, Deadline, Moment, SystemClock};
+use crate::remote::is_remote_unavailable;
+
+/// Longest wait between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// The git work behind the loop's cycles. [`crate::daemon::SyncDaemon`]
+/// runs real git.
+pub trait GitBackend {
+    /// Commits pending changes, then pulls and pushes. Returns whether
+    /// anything was synced.
+    fn sync(&mut self) -> Result<bool>;
+    /// Commits pending changes without touching the network.
+    fn commit(&mut self) -> Result<bool>;
+    /// Pulls remote changes and pushes local commits.
+    fn poll(&mut self) -> Result<()>;
+    /// Whether the last commit skipped paths still being written.
+    fn unsettled(&self) -> bool;
+}
+
+/// How a cycle failed.
+#[derive(Debug)]
+pub enum CycleError {
+    /// The remote could not be reached; the loop records an outage.
+    Unavailable(anyhow::Error),
+    /// Anything else; the loop picks a backoff step for it.
+    Failed(anyhow::Error),
+}
+
+impl CycleError {
+    fn of(err: anyhow::Error) -> Self {
+        if is_remote_unavailable(&err) {
+            Self::Unavailable(err)
+        } else {
+            Self::Failed(err)
+        }
+    }
+}
+
+/// When a waiting change is committed, see `commit.mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitTiming {
+    /// Once this long passed without another change.
+    AfterQuiet(Duration),
+    /// This long after the last commit, however many changes follow.
+    Every(Duration),
+}
+
+/// The loop's commit, poll and backoff timers.
+#[derive(Debug)]
+pub struct Pacer<C: Clock = SystemClock> {
+    clock: C,
+    commit_timing: CommitTiming,
+    poll_interval: Duration,
+    /// The latest change not committed yet.
+    dirty_since: Option<Moment>,
+    last_commit: Moment,
+    last_poll: Moment,
+    backoff_until: Option<Deadline>,
+    backoff_step: u32,
+}
+
+impl<C: Clock> Pacer<C> {
+    /// A pacer that polls right away.
+    pub fn new(clock: C, commit_timing: CommitTiming, poll_interval: Duration) -> Self {
+        let now = Moment::now(&clock);
+        Self {
+            clock,
+            commit_timing,
+            poll_interval,
+            dirty_since: None,
+            last_commit: now,
+            last_poll: now.earlier(poll_interval),
+            backoff_until: None,
+            backoff_step: 0,
+        }
+    }
+
+    /// Takes up intervals changed by shared policy, a metered connection or
+    /// a low battery.
+    pub fn set_intervals(&mut self, commit_timing: CommitTiming, poll_interval: Duration) {
+        self.commit_timing = commit_timing;
+        self.poll_interval = poll_interval;
+    }
+
+    /// Notes a change, to be committed as [`CommitTiming`] says.
+    pub fn changed(&mut self) {
+        self.dirty_since = Some(Moment::now(&self.clock));
+    }
+
+    /// Commits at the next chance, without waiting for quiet or the next
+    /// snapshot.
+    pub fn commit_now(&mut self) {
+        let now = Moment::now(&self.clock);
+        match self.commit_timing {
+            CommitTiming::AfterQuiet(wait) => self.dirty_since = Some(now.earlier(wait)),
+            CommitTiming::Every(interval) => {
+                self.dirty_since = Some(now);
+                self.last_commit = now.earlier(interval);
+            }
+        }
+    }
+
+    /// Polls at the next chance.
+    pub fn poll_now(&mut self) {
+        self.last_poll = Moment::now(&self.clock).earlier(self.poll_interval);
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_since.is_some()
+    }
+
+    pub fn is_backing_off(&self) -> bool {
+        self.backoff_until.is_some()
+    }
+
+    /// Retries at the next chance; the backoff step is kept.
+    pub fn end_backoff(&mut self) {
+        self.backoff_until = None;
+    }
+
+    pub fn backoff_step(&self) -> u32 {
+        self.backoff_step
+    }
+
+    /// Waits `2^step` seconds before the next cycle.
+    pub fn back_off(&mut self, step: u32) {
+        self.backoff_step = step;
+        self.backoff_until = Some(Deadline::after(&self.clock, backoff_delay(step)));
+    }
+
+    /// When the waiting change is due, unless the loop is held up.
+    pub fn commit_at(&self, active: bool) -> Option<Instant> {
+        let since = self.dirty_since.filter(|_| active)?;
+        let deadline = match self.commit_timing {
+            CommitTiming::AfterQuiet(wait) => Deadline::new(since, wait),
+            CommitTiming::Every(interval) => Deadline::new(self.last_commit, interval),
+        };
+        Some(deadline.instant(&self.clock))
+    }
+
+    pub fn poll_at(&self) -> Instant {
+        Deadline::new(self.last_poll, self.poll_interval).instant(&self.clock)
+    }
+
+    pub fn backoff_at(&self) -> Option<Instant> {
+        self.backoff_until.map(|until| until.instant(&self.clock))
+    }
+
+    /// Runs the due commit cycle: [`GitBackend::commit`] while `local_only`,
+    /// [`GitBackend::sync`] otherwise.
+    pub fn commit(
+        &mut self,
+        backend: &mut impl GitBackend,
+        local_only: bool,
+    ) -> Result<bool, CycleError> {
+        let result = if local_only {
+            backend.commit()
+        } else {
+            backend.sync()
+        };
+        // Revisit paths that were skipped mid-write.
+        let unsettled = backend.unsettled();
+        match result {
+            Ok(changed) => {
+                self.dirty_since = unsettled.then(|| Moment::now(&self.clock));
+                self.last_commit = Moment::now(&self.clock);
+                self.succeeded();
+                Ok(changed)
+            }
+            Err(err) => {
+                let err = CycleError::of(err);
+                if let CycleError::Unavailable(_) = err {
+                    self.dirty_since = unsettled.then(|| Moment::now(&self.clock));
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Runs the due poll.
+    pub fn poll(&mut self, backend: &mut impl GitBackend) -> Result<(), CycleError> {
+        match backend.poll() {
+            Ok(()) => {
+                self.succeeded();
+                Ok(())
+            }
+            Err(err) => {
+                let err = CycleError::of(err);
+                if let CycleError::Unavailable(_) = err {
+                    self.last_poll = Moment::now(&self.clock);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Records a cycle that reached the remote: the next poll is a full
+    /// interval away and backoff starts over.
+    pub fn succeeded(&mut self) {
+        self.last_poll = Moment::now(&self.clock);
+        self.backoff_step = 0;
+    }
+}
+
+/// Backoff steps are powers of two seconds, at most five minutes.
+pub fn backoff_delay(step: u32) -> Duration {
+    Duration::from_secs(1u64 << step.min(16)).min(MAX_BACKOFF)
+}
@@ -0,0 +1,192 @@
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info};
+
+use crate::config::PowerConfig;
+
+/// How often the platform is asked about the power source. Results are
+/// cached in between, since on macOS and Windows asking spawns a helper.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where the machine draws its power from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerState {
+    pub on_battery: bool,
+    /// Charge left, when the machine has a battery.
+    pub battery_percent: Option<u8>,
+}
+
+impl PowerState {
+    /// Whether `config` asks for sync to save power in this state.
+    pub fn is_low(&self, config: &PowerConfig) -> bool {
+        self.on_battery
+            && self
+                .battery_percent
+                .is_some_and(|percent| percent < config.low_battery_percent)
+    }
+}
+
+/// The current power state; `None` on desktops and where it cannot be told.
+pub fn probe() -> Option<PowerState> {
+    platform::detect()
+}
+
+/// Tracks whether the machine runs on a low battery.
+#[derive(Debug, Default)]
+pub struct PowerMonitor {
+    last_check: Option<Instant>,
+    low: bool,
+}
+
+impl PowerMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_low(&mut self, config: &PowerConfig) -> bool {
+        if config.low_battery_percent == 0 {
+            self.low = false;
+            return false;
+        }
+        let due = self
+            .last_check
+            .is_none_or(|checked| checked.elapsed() >= RECHECK_INTERVAL);
+        if due {
+            let state = probe();
+            let low = state.is_some_and(|state| state.is_low(config));
+            if low != self.low {
+                if low {
+                    info!(
+                        percent = state.and_then(|state| state.battery_percent),
+                        "battery is low, polling less and holding back large pushes"
+                    );
+                } else {
+                    info!("running on mains power again, resuming normal sync");
+                }
+            }
+            self.low = low;
+            self.last_check = Some(Instant::now());
+        }
+        self.low
+    }
+}
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+fn command_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| debug!(?err, program, "power state helper unavailable"))
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::fs;
+    use std::path::Path;
+
+    use super::PowerState;
+
+    const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+    /// Reads the kernel's power supplies: the machine is on battery when
+    /// no mains adapter is online and a system battery is discharging.
+    pub(super) fn detect() -> Option<PowerState> {
+        let read = |supply: &Path, name: &str| {
+            fs::read_to_string(supply.join(name))
+                .ok()
+                .map(|value| value.trim().to_string())
+        };
+        let mut mains_online = false;
+        let mut discharging = false;
+        let mut percent = None;
+        for entry in fs::read_dir(POWER_SUPPLY).ok()?.flatten() {
+            let supply = entry.path();
+            match read(&supply, "type").as_deref() {
+                Some("Mains") => mains_online |= read(&supply, "online").as_deref() == Some("1"),
+                // Batteries of mice and headsets report `scope` Device.
+                Some("Battery") if read(&supply, "scope").as_deref() != Some("Device") => {
+                    discharging |= read(&supply, "status").as_deref() == Some("Discharging");
+                    let capacity = read(&supply, "capacity").and_then(|value| value.parse().ok());
+                    percent = percent.min(capacity).or(capacity);
+                }
+                _ => {}
+            }
+        }
+        percent?;
+        Some(PowerState {
+            on_battery: discharging && !mains_online,
+            battery_percent: percent,
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{PowerState, command_stdout};
+
+    /// Parses `pmset -g batt`, which starts with `Now drawing from 'AC
+    /// Power'` or `'Battery Power'` and lists each battery's charge as
+    /// `85%;`.
+    pub(super) fn detect() -> Option<PowerState> {
+        let output = command_stdout("pmset", &["-g", "batt"])?;
+        let percent = output.lines().skip(1).find_map(|line| {
+            let (before, _) = line.split_once("%;")?;
+            before
+                .rsplit(|ch: char| !ch.is_ascii_digit())
+                .next()?
+                .parse()
+                .ok()
+        });
+        percent?;
+        Some(PowerState {
+            on_battery: output.contains("'Battery Power'"),
+            battery_percent: percent,
+        })
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{PowerState, command_stdout};
+
+    const SCRIPT: &str = "Add-Type -AssemblyName System.Windows.Forms; \
+        $s = [System.Windows.Forms.SystemInformation]::PowerStatus; \
+        \"$($s.PowerLineStatus) $([int]$s.BatteryChargeStatus) $([int]($s.BatteryLifePercent * 100))\"";
+
+    /// Queries `GetSystemPowerStatus` through .NET.
+    pub(super) fn detect() -> Option<PowerState> {
+        let output = command_stdout(
+            "powershell",
+            &["-NoProfile", "-NonInteractive", "-Command", SCRIPT],
+        )?;
+        let mut parts = output.split_whitespace();
+        let line = parts.next()?;
+        let charge: u8 = parts.next()?.parse().ok()?;
+        let percent: u8 = parts.next()?.parse().ok()?;
+        // `BatteryChargeStatus` flags: 128 is NoSystemBattery, 255 Unknown.
+        if charge & 128 != 0 {
+            return None;
+        }
+        Some(PowerState {
+            on_battery: line.eq_ignore_ascii_case("Offline"),
+            battery_percent: Some(percent.min(100)),
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+mod platform {
+    use super::PowerState;
+
+    pub(super) fn detect() -> Option<PowerState> {
+        None
+    }
+}
@@ -0,0 +1,147 @@
+//! Checks before the first clone of a vault: how much the remote holds,
+//! asked of the hosting service's API, against the free space where the
+//! vault goes, so a large repository is not cloned onto a disk it would
+//! fill halfway through.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use tracing::debug;
+
+use crate::config::{Config, RemoteKind};
+use crate::disk;
+use crate::git::GitFacade;
+use crate::git_error::GitError;
+use crate::provider::{Provider, PullRequestTarget};
+
+/// What a clone of the vault would take.
+#[derive(Debug, Clone)]
+pub struct CloneEstimate {
+    /// The service the size was asked of.
+    pub provider: Option<Provider>,
+    /// The packed history; `None` when the size could not be found out.
+    pub repo_bytes: Option<u64>,
+    /// Git LFS files, where the service reports them.
+    pub lfs_bytes: Option<u64>,
+    /// Why the size is unknown.
+    pub unknown: Option<String>,
+    /// The remote branch does not exist yet, so there is nothing to clone.
+    pub empty: bool,
+    /// Free space where the vault goes, if the file system tells.
+    pub available: Option<u64>,
+    /// `disk.min_free_mb` in bytes.
+    pub min_free: u64,
+}
+
+impl CloneEstimate {
+    /// Space the clone takes up: the history, the checked-out files, taken
+    /// to be as large again, and for LFS files both their store and their
+    /// checkout.
+    pub fn needed(&self) -> Option<u64> {
+        if self.empty {
+            return Some(0);
+        }
+        let repo = self.repo_bytes?;
+        Some(repo.saturating_mul(2) + self.lfs_bytes.unwrap_or(0).saturating_mul(2))
+    }
+
+    /// Whether the clone would leave less than `disk.min_free_mb` free;
+    /// `None` when either side is unknown.
+    pub fn too_big(&self) -> Option<bool> {
+        Some(self.needed()?.saturating_add(self.min_free) > self.available?)
+    }
+}
+
+/// Asks the remote whether the vault branch exists and the hosting service
+/// how large the repository is; a remote that is a local folder is
+/// measured on disk. Only a remote that cannot be reached is an
+/// error; a size the service does not give is left unknown.
+pub fn estimate(git: &GitFacade, config: &Config) -> Result<CloneEstimate> {
+    let mut estimate = CloneEstimate {
+        provider: None,
+        repo_bytes: None,
+        lfs_bytes: None,
+        unknown: None,
+        empty: !git.remote_has_branch(&config.repo_url)?,
+        available: disk::available(config.workdir.as_std_path()),
+        min_free: config.disk.min_free_mb.saturating_mul(1024 * 1024),
+    };
+    if estimate.empty {
+        return Ok(estimate);
+    }
+    if config.remote.kind != RemoteKind::Git {
+        estimate.unknown = Some("S3 and WebDAV remotes do not report a size".to_string());
+        return Ok(estimate);
+    }
+    if let Some(bytes) = local_size(&config.repo_url) {
+        estimate.repo_bytes = Some(bytes);
+        return Ok(estimate);
+    }
+    let size = PullRequestTarget::for_reading(config).and_then(|target| {
+        estimate.provider = Some(target.provider);
+        target.client()?.repo_size(&target.repo)
+    });
+    match size {
+        Ok(size) => {
+            estimate.repo_bytes = Some(size.bytes);
+            estimate.lfs_bytes = size.lfs_bytes;
+        }
+        Err(err) => estimate.unknown = Some(format!("{err:#}")),
+    }
+    Ok(estimate)
+}
+
+/// Refuses a first clone that the disk has no room for, with
+/// [`GitError::DiskFull`]. Anything short of
+/// a known size that does not fit lets the clone go ahead, where
+/// `disk.min_free_mb` still stops it before the disk is full.
+pub fn ensure_room(git: &GitFacade, config: &Config) -> Result<()> {
+    git.ensure_disk_space()?;
+    let estimate = match estimate(git, config) {
+        Ok(estimate) => estimate,
+        Err(err) => {
+            debug!(?err, "failed to estimate the size of the clone");
+            return Ok(());
+        }
+    };
+    match (estimate.too_big(), estimate.needed(), estimate.available) {
+        (Some(true), Some(needed), Some(available)) => Err(GitError::DiskFull(format!(
+            "cloning {} needs about {} MB, but only {} MB are free at {} (with disk.min_free_mb at {}); free some space or choose a workdir on another disk",
+            config.repo_url,
+            disk::megabytes(needed),
+            disk::megabytes(available),
+            config.workdir,
+            config.disk.min_free_mb
+        ))
+        .into()),
+        _ => {
+            if let Some(reason) = &estimate.unknown {
+                debug!(%reason, "cannot tell how large the clone will be");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The size on disk of a remote that is a folder, such as a repository on
+/// a network share.
+fn local_size(repo_url: &str) -> Option<u64> {
+    let root = Path::new(repo_url.strip_prefix("file://").unwrap_or(repo_url));
+    if !root.is_dir() {
+        return None;
+    }
+    let mut total = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).ok()?.flatten() {
+            let Ok(meta) = entry.metadata() else { continue };
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else {
+                total += meta.len();
+            }
+        }
+    }
+    Some(total)
+}
@@ -0,0 +1,523 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::config::{Config, NetworkConfig};
+use crate::network;
+use crate::remote;
+
+const USER_AGENT: &str = concat!("obsyncgit/", env!("CARGO_PKG_VERSION"));
+
+/// Git hosting service whose API is used to create the vault repository
+/// and to open pull requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    Github,
+    #[serde(alias = "forgejo")]
+    Gitea,
+    Gitlab,
+}
+
+impl Provider {
+    /// API root used when `--api-url` is not given. Gitea has no public
+    /// default instance.
+    pub fn default_api_url(self) -> Option<&'static str> {
+        match self {
+            Self::Github => Some("https://api.github.com"),
+            Self::Gitea => None,
+            Self::Gitlab => Some("https://gitlab.com"),
+        }
+    }
+
+    /// The provider of the public instances behind `repo_url`.
+    pub fn detect(repo_url: &str) -> Option<Self> {
+        let web_url = remote::web_url(repo_url)?;
+        let host = web_url.trim_start_matches("https://").split('/').next()?;
+        match host.to_ascii_lowercase().as_str() {
+            "github.com" => Some(Self::Github),
+            "gitlab.com" => Some(Self::Gitlab),
+            "codeberg.org" | "gitea.com" => Some(Self::Gitea),
+            _ => None,
+        }
+    }
+
+    /// Environment variable the provider's own tooling reads the token from.
+    pub fn token_env(self) -> &'static str {
+        match self {
+            Self::Github => "GITHUB_TOKEN",
+            Self::Gitea => "GITEA_TOKEN",
+            Self::Gitlab => "GITLAB_TOKEN",
+        }
+    }
+}
+
+impl std::str::FromStr for Provider {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "github" => Ok(Self::Github),
+            "gitea" | "forgejo" => Ok(Self::Gitea),
+            "gitlab" => Ok(Self::Gitlab),
+            other => anyhow::bail!("unknown provider '{other}' (expected github, gitea or gitlab)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewRepo {
+    pub name: String,
+    /// User or organization/group that owns the repository; the token's
+    /// user when `None`.
+    pub owner: Option<String>,
+    pub private: bool,
+    pub default_branch: String,
+    pub lfs: bool,
+}
+
+/// A pull request (merge request on GitLab) from `head` into `base`.
+#[derive(Debug, Clone)]
+pub struct PullRequest {
+    pub head: String,
+    pub base: String,
+    pub title: String,
+    pub body: String,
+}
+
+/// An open pull request.
+#[derive(Debug, Clone)]
+pub struct OpenPullRequest {
+    pub web_url: String,
+    /// Whether this call opened it, rather than finding it open already.
+    pub created: bool,
+}
+
+/// Where the `pull-request` topology opens its pull requests: the settings
+/// of `sync.pull_request`, filled in from the remote URL and environment.
+#[derive(Debug, Clone)]
+pub struct PullRequestTarget {
+    pub provider: Provider,
+    pub api_url: String,
+    pub token: String,
+    /// `owner/name`, or the group path on GitLab.
+    pub repo: String,
+    pub network: NetworkConfig,
+}
+
+impl PullRequestTarget {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::resolve(config, true)
+    }
+
+    /// Like [`Self::from_config`], with an empty token when none is set,
+    /// which is enough to read about a public repository.
+    pub fn for_reading(config: &Config) -> Result<Self> {
+        Self::resolve(config, false)
+    }
+
+    fn resolve(config: &Config, token_required: bool) -> Result<Self> {
+        let settings = &config.sync.pull_request;
+        let web_url = remote::web_url(&config.repo_url)
+            .with_context(|| format!("cannot tell the repository from {}", config.repo_url))?;
+        let (host, repo) = web_url
+            .trim_start_matches("https://")
+            .split_once('/')
+            .context("repo_url has no repository path")?;
+        let provider = match settings
+            .provider
+            .or_else(|| Provider::detect(&config.repo_url))
+        {
+            Some(provider) => provider,
+            None => bail!(
+                "cannot tell the hosting service of {host}; set sync.pull_request.provider to github, gitea or gitlab"
+            ),
+        };
+        let api_url = settings
+            .api_url
+            .clone()
+            .or_else(|| provider.default_api_url().map(str::to_string))
+            .unwrap_or_else(|| format!("https://{host}"));
+        let token = match &settings.token {
+            Some(token) => token.clone(),
+            None => {
+                let var = provider.token_env();
+                match std::env::var(var) {
+                    Ok(token) => token,
+                    Err(_) if !token_required => String::new(),
+                    Err(_) => bail!(
+                        "no API token for pull requests: set sync.pull_request.token or {var}"
+                    ),
+                }
+            }
+        };
+        Ok(Self {
+            provider,
+            api_url,
+            token,
+            repo: repo.to_string(),
+            network: config.network.clone(),
+        })
+    }
+
+    pub fn client(&self) -> Result<ProviderClient> {
+        ProviderClient::new(
+            self.provider,
+            Some(&self.api_url),
+            self.token.clone(),
+            &self.network,
+        )
+    }
+}
+
+/// What the hosting service reports a repository takes up.
+#[derive(Debug, Clone, Copy)]
+pub struct RepoSize {
+    /// The git history as the service stores it, packed.
+    pub bytes: u64,
+    /// Git LFS files; `None` where the service does not say.
+    pub lfs_bytes: Option<u64>,
+}
+
+/// Clone URLs of a freshly created repository.
+#[derive(Debug, Clone)]
+pub struct CreatedRepo {
+    pub web_url: String,
+    pub ssh_url: String,
+    pub https_url: String,
+}
+
+pub struct ProviderClient {
+    provider: Provider,
+    api_url: String,
+    token: String,
+    http: Client,
+}
+
+impl ProviderClient {
+    pub fn new(
+        provider: Provider,
+        api_url: Option<&str>,
+        token: String,
+        network: &NetworkConfig,
+    ) -> Result<Self> {
+        let api_url = match api_url.or(provider.default_api_url()) {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => bail!("--api-url is required for {provider:?}, e.g. https://gitea.example.com"),
+        };
+        let mut builder = Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(30));
+        if let Some(proxy) = network::http_proxy(network)? {
+            builder = builder.proxy(proxy);
+        }
+        let http = builder.build().context("failed to build HTTP client")?;
+        Ok(Self {
+            provider,
+            api_url,
+            token,
+            http,
+        })
+    }
+
+    /// Creates an empty repository. The default branch is set at creation
+    /// where the API allows it; GitHub and GitLab adopt the first pushed
+    /// branch of an empty repository instead.
+    pub fn create_repo(&self, repo: &NewRepo) -> Result<CreatedRepo> {
+        match self.provider {
+            Provider::Github => self.create_github(repo),
+            Provider::Gitea => self.create_gitea(repo),
+            Provider::Gitlab => self.create_gitlab(repo),
+        }
+    }
+
+    fn create_github(&self, repo: &NewRepo) -> Result<CreatedRepo> {
+        let url = match &repo.owner {
+            Some(org) => format!("{}/orgs/{org}/repos", self.api_url),
+            None => format!("{}/user/repos", self.api_url),
+        };
+        let body = json!({
+            "name": repo.name,
+            "private": repo.private,
+            "auto_init": false,
+        });
+        let request = self.github(self.http.post(url)).json(&body);
+        let created = send(request)?;
+        Ok(CreatedRepo {
+            web_url: field(&created, "html_url")?,
+            ssh_url: field(&created, "ssh_url")?,
+            https_url: field(&created, "clone_url")?,
+        })
+    }
+
+    fn create_gitea(&self, repo: &NewRepo) -> Result<CreatedRepo> {
+        let url = match &repo.owner {
+            Some(org) => format!("{}/api/v1/orgs/{org}/repos", self.api_url),
+            None => format!("{}/api/v1/user/repos", self.api_url),
+        };
+        let body = json!({
+            "name": repo.name,
+            "private": repo.private,
+            "default_branch": repo.default_branch,
+            "auto_init": false,
+        });
+        let request = self.gitea(self.http.post(url)).json(&body);
+        let created = send(request)?;
+        Ok(CreatedRepo {
+            web_url: field(&created, "html_url")?,
+            ssh_url: field(&created, "ssh_url")?,
+            https_url: field(&created, "clone_url")?,
+        })
+    }
+
+    fn create_gitlab(&self, repo: &NewRepo) -> Result<CreatedRepo> {
+        let mut body = json!({
+            "name": repo.name,
+            "path": repo.name,
+            "visibility": if repo.private { "private" } else { "public" },
+            "lfs_enabled": repo.lfs,
+        });
+        if let Some(group) = &repo.owner {
+            let url = format!(
+                "{}/api/v4/namespaces/{}",
+                self.api_url,
+                group.replace('/', "%2F")
+            );
+            let namespace = send(self.gitlab(self.http.get(url)))
+                .with_context(|| format!("failed to look up GitLab namespace '{group}'"))?;
+            body["namespace_id"] = namespace
+                .get("id")
+                .cloned()
+                .context("GitLab namespace response has no id")?;
+        }
+        let url = format!("{}/api/v4/projects", self.api_url);
+        let created = send(self.gitlab(self.http.post(url).json(&body)))?;
+        Ok(CreatedRepo {
+            web_url: field(&created, "web_url")?,
+            ssh_url: field(&created, "ssh_url_to_repo")?,
+            https_url: field(&created, "http_url_to_repo")?,
+        })
+    }
+
+    /// Opens `pull` in `repo` (`owner/name`, or the full group path on
+    /// GitLab) unless one from the same head into the same base is open
+    /// already. Pushing to the head branch updates an open pull request, so
+    /// there is nothing else to do for it.
+    pub fn ensure_pull_request(&self, repo: &str, pull: &PullRequest) -> Result<OpenPullRequest> {
+        let existing = match self.provider {
+            Provider::Github => self.find_github_pull(repo, pull)?,
+            Provider::Gitea => self.find_gitea_pull(repo, pull)?,
+            Provider::Gitlab => self.find_gitlab_merge_request(repo, pull)?,
+        };
+        if let Some(web_url) = existing {
+            return Ok(OpenPullRequest {
+                web_url,
+                created: false,
+            });
+        }
+        let web_url = match self.provider {
+            Provider::Github => {
+                let url = format!("{}/repos/{repo}/pulls", self.api_url);
+                let body = json!({
+                    "title": pull.title,
+                    "body": pull.body,
+                    "head": pull.head,
+                    "base": pull.base,
+                });
+                field(
+                    &send(self.github(self.http.post(url)).json(&body))?,
+                    "html_url",
+                )?
+            }
+            Provider::Gitea => {
+                let url = format!("{}/api/v1/repos/{repo}/pulls", self.api_url);
+                let body = json!({
+                    "title": pull.title,
+                    "body": pull.body,
+                    "head": pull.head,
+                    "base": pull.base,
+                });
+                field(
+                    &send(self.gitea(self.http.post(url)).json(&body))?,
+                    "html_url",
+                )?
+            }
+            Provider::Gitlab => {
+                let url = format!(
+                    "{}/api/v4/projects/{}/merge_requests",
+                    self.api_url,
+                    repo.replace('/', "%2F")
+                );
+                let body = json!({
+                    "title": pull.title,
+                    "description": pull.body,
+                    "source_branch": pull.head,
+                    "target_branch": pull.base,
+                });
+                field(
+                    &send(self.gitlab(self.http.post(url).json(&body)))?,
+                    "web_url",
+                )?
+            }
+        };
+        Ok(OpenPullRequest {
+            web_url,
+            created: true,
+        })
+    }
+
+    /// The size of `repo` as the service reports it. GitHub and Gitea
+    /// give the history's size only; GitLab includes LFS files but needs a
+    /// token with at least Reporter access.
+    pub fn repo_size(&self, repo: &str) -> Result<RepoSize> {
+        let kilobytes = |value: &Value| {
+            value
+                .get("size")
+                .and_then(Value::as_u64)
+                .map(|size| size * 1024)
+                .context("API response has no repository size")
+        };
+        match self.provider {
+            Provider::Github => {
+                let url = format!("{}/repos/{repo}", self.api_url);
+                Ok(RepoSize {
+                    bytes: kilobytes(&send(self.github(self.http.get(url)))?)?,
+                    lfs_bytes: None,
+                })
+            }
+            Provider::Gitea => {
+                let url = format!("{}/api/v1/repos/{repo}", self.api_url);
+                Ok(RepoSize {
+                    bytes: kilobytes(&send(self.gitea(self.http.get(url)))?)?,
+                    lfs_bytes: None,
+                })
+            }
+            Provider::Gitlab => {
+                let url = format!(
+                    "{}/api/v4/projects/{}",
+                    self.api_url,
+                    repo.replace('/', "%2F")
+                );
+                let project = send(
+                    self.gitlab(self.http.get(url))
+                        .query(&[("statistics", "true")]),
+                )?;
+                let statistics = project
+                    .get("statistics")
+                    .context("GitLab only shows repository statistics to Reporters and above")?;
+                let bytes = |name: &str| statistics.get(name).and_then(Value::as_u64);
+                Ok(RepoSize {
+                    bytes: bytes("repository_size")
+                        .context("API response has no repository size")?,
+                    lfs_bytes: bytes("lfs_objects_size"),
+                })
+            }
+        }
+    }
+
+    fn find_github_pull(&self, repo: &str, pull: &PullRequest) -> Result<Option<String>> {
+        let owner = repo.split('/').next().unwrap_or(repo);
+        let url = format!("{}/repos/{repo}/pulls", self.api_url);
+        let head = format!("{owner}:{}", pull.head);
+        let request = self.github(self.http.get(url)).query(&[
+            ("state", "open"),
+            ("head", head.as_str()),
+            ("base", pull.base.as_str()),
+        ]);
+        first_url(&send(request)?, "html_url")
+    }
+
+    fn find_gitea_pull(&self, repo: &str, pull: &PullRequest) -> Result<Option<String>> {
+        let url = format!("{}/api/v1/repos/{repo}/pulls", self.api_url);
+        let open = send(self.gitea(self.http.get(url)).query(&[("state", "open")]))?;
+        let branch = |pull: &Value, side: &str| {
+            pull.get(side)
+                .and_then(|side| side.get("ref"))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        Ok(open
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|candidate| {
+                branch(candidate, "head").as_deref() == Some(pull.head.as_str())
+                    && branch(candidate, "base").as_deref() == Some(pull.base.as_str())
+            })
+            .and_then(|candidate| candidate.get("html_url"))
+            .and_then(Value::as_str)
+            .map(str::to_string))
+    }
+
+    fn find_gitlab_merge_request(&self, repo: &str, pull: &PullRequest) -> Result<Option<String>> {
+        let url = format!(
+            "{}/api/v4/projects/{}/merge_requests",
+            self.api_url,
+            repo.replace('/', "%2F")
+        );
+        let request = self.gitlab(self.http.get(url)).query(&[
+            ("state", "opened"),
+            ("source_branch", pull.head.as_str()),
+            ("target_branch", pull.base.as_str()),
+        ]);
+        first_url(&send(request)?, "web_url")
+    }
+
+    // Without a token requests go out anonymously, which reads public
+    // repositories.
+
+    fn github(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = request.header("Accept", "application/vnd.github+json");
+        if self.token.is_empty() {
+            return request;
+        }
+        request.bearer_auth(&self.token)
+    }
+
+    fn gitea(&self, request: RequestBuilder) -> RequestBuilder {
+        if self.token.is_empty() {
+            return request;
+        }
+        request.header("Authorization", format!("token {}", self.token))
+    }
+
+    fn gitlab(&self, request: RequestBuilder) -> RequestBuilder {
+        if self.token.is_empty() {
+            return request;
+        }
+        request.header("PRIVATE-TOKEN", &self.token)
+    }
+}
+
+fn send(request: RequestBuilder) -> Result<Value> {
+    let response = request.send().context("API request failed")?;
+    let status = response.status();
+    let body = response.text().context("failed to read API response")?;
+    if !status.is_success() {
+        let message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|value| value.get("message").map(|message| message.to_string()))
+            .unwrap_or(body);
+        bail!("API returned {status}: {message}");
+    }
+    serde_json::from_str(&body).context("API response is not valid JSON")
+}
+
+/// `name` of the first entry of a list response, if the list has any.
+fn first_url(list: &Value, name: &str) -> Result<Option<String>> {
+    match list.as_array().and_then(|items| items.first()) {
+        Some(item) => field(item, name).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn field(value: &Value, name: &str) -> Result<String> {
+    value
+        .get(name)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .with_context(|| format!("API response has no '{name}'"))
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::git::GitFacade;
+
+/// Something left behind by an interrupted git or obsyncgit process that
+/// [`recover`] put right.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair {
+    /// Removed a `.git/index.lock` no process was holding.
+    IndexLock,
+    /// Aborted a rebase that never finished.
+    Rebase,
+    /// Aborted a merge that never finished.
+    Merge,
+    /// Restored local edits stashed before a pull that never finished,
+    /// identified by the stash commit's SHA.
+    Autostash(String),
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IndexLock => f.write_str("removed a stale index.lock"),
+            Self::Rebase => f.write_str("aborted an interrupted rebase"),
+            Self::Merge => f.write_str("aborted an interrupted merge"),
+            Self::Autostash(stash) => {
+                let short = stash.get(..10).unwrap_or(stash);
+                write!(f, "restored leftover autostash {short}")
+            }
+        }
+    }
+}
+
+/// Brings the vault back to a state where the daemon can commit and pull.
+/// Runs on startup, while the vault lock guarantees no other obsyncgit
+/// process is mid-operation, so anything found was left by a crash, a kill
+/// or a power loss. Problems that cannot be repaired safely are logged and
+/// left alone.
+pub fn recover(git: &GitFacade) -> Vec<Repair> {
+    let mut repairs = Vec::new();
+
+    match git.clear_stale_index_lock() {
+        Ok(true) => repairs.push(Repair::IndexLock),
+        Ok(false) => {}
+        Err(err) => warn!(?err, "failed to remove stale index.lock"),
+    }
+
+    if git.rebase_in_progress() {
+        match git.abort_rebase() {
+            Ok(()) => repairs.push(Repair::Rebase),
+            Err(err) => warn!(?err, "failed to clean up interrupted rebase"),
+        }
+    }
+
+    if git.merge_in_progress() {
+        match git.abort_merge() {
+            Ok(()) => repairs.push(Repair::Merge),
+            Err(err) => warn!(?err, "failed to abort interrupted merge"),
+        }
+    }
+
+    if let Err(err) = restore_autostashes(git, &mut repairs) {
+        warn!(?err, "failed to inspect leftover autostashes");
+    }
+
+    for repair in &repairs {
+        info!(%repair, "repaired vault after an interrupted sync");
+    }
+    repairs
+}
+
+/// Puts back edits stashed by a pull that never got to pop them. Popping
+/// onto new edits could conflict, so a stash is only restored into a clean
+/// working tree; the rest stay listed in `git stash list`.
+fn restore_autostashes(git: &GitFacade, repairs: &mut Vec<Repair>) -> Result<()> {
+    let stashes = git.autostashes()?;
+    // Oldest first, as they were created.
+    for stash in stashes.iter().rev() {
+        if !git.is_worktree_clean()? {
+            warn!(
+                stash = %stash,
+                "kept a leftover obsyncgit autostash because the vault has new edits; restore it with `git stash pop`"
+            );
+            continue;
+        }
+        match git.restore_stash(stash) {
+            Ok(()) => repairs.push(Repair::Autostash(stash.clone())),
+            Err(err) => warn!(?err, stash = %stash, "failed to restore leftover autostash"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,270 @@
+//! `security.redaction`: lines and frontmatter keys that stay in the note on
+//! disk but never reach the remote. The vault's git repository runs every
+//! matching file through `obsyncgit filter clean` when it is staged, which
+//! drops the private lines and keeps them under the git directory, and
+//! through `obsyncgit filter smudge` when a pull rewrites the file, which
+//! puts them back after the line they followed.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::RedactionConfig;
+use crate::git::GitFacade;
+
+/// The filter driver named in `.git/info/attributes`.
+pub const FILTER_NAME: &str = "obsyncgit-redact";
+
+/// Below the git directory: the rules the filter runs with and the lines
+/// it removed, one file per note.
+const STATE_DIR: &str = "obsyncgit/redaction";
+const RULES_FILE: &str = "rules.yaml";
+const FRAGMENTS_DIR: &str = "fragments";
+
+/// Consecutive lines left out of the committed file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Fragment {
+    /// The kept line they followed, `None` when no line was kept before.
+    after: Option<String>,
+    /// The lines, with their line endings.
+    lines: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Fragments {
+    fragments: Vec<Fragment>,
+}
+
+struct Redactor {
+    lines: RegexSet,
+    keys: Vec<String>,
+}
+
+impl Redactor {
+    fn new(config: &RedactionConfig) -> Result<Self> {
+        Ok(Self {
+            lines: RegexSet::new(&config.line_patterns)
+                .context("invalid pattern in security.redaction.line_patterns")?,
+            keys: config.frontmatter_keys.clone(),
+        })
+    }
+
+    /// Splits `text` into what is committed and the lines left out.
+    fn split(&self, text: &str) -> (String, Vec<Fragment>) {
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let frontmatter_end = (lines.first().map(|line| line.trim_end()) == Some("---"))
+            .then(|| {
+                lines[1..]
+                    .iter()
+                    .position(|line| line.trim_end() == "---")
+                    .map(|at| at + 1)
+            })
+            .flatten();
+
+        let mut kept = String::with_capacity(text.len());
+        let mut removed = Vec::new();
+        let mut last_kept: Option<&str> = None;
+        let mut in_key = false;
+        // Whether the previous line was left out too.
+        let mut in_run = false;
+        for (index, line) in lines.iter().enumerate() {
+            if frontmatter_end.is_some_and(|end| index > 0 && index < end) {
+                // Indented and `- ` lines continue the key above them.
+                if !line.starts_with([' ', '\t', '-']) {
+                    in_key = line
+                        .split_once(':')
+                        .is_some_and(|(key, _)| self.keys.iter().any(|k| k == key.trim()));
+                }
+            } else {
+                in_key = false;
+            }
+            if !(in_key || self.lines.is_match(line)) {
+                kept.push_str(line);
+                last_kept = Some(line);
+                in_run = false;
+                continue;
+            }
+            match removed.last_mut() {
+                Some(Fragment { lines, .. }) if in_run => lines.push(line.to_string()),
+                _ => removed.push(Fragment {
+                    after: last_kept.map(str::to_string),
+                    lines: vec![line.to_string()],
+                }),
+            }
+            in_run = true;
+        }
+        (kept, removed)
+    }
+}
+
+/// `text` with `fragments` put back after the lines they followed. A
+/// fragment whose line is gone, e.g. edited on another device, goes to the
+/// end of the file.
+fn restore(text: &str, fragments: &[Fragment]) -> String {
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(str::to_string).collect();
+    let mut cursor = 0;
+    for fragment in fragments {
+        let at = match &fragment.after {
+            None => cursor,
+            Some(anchor) => lines[cursor..]
+                .iter()
+                .position(|line| line == anchor)
+                .map_or(lines.len(), |at| cursor + at + 1),
+        };
+        if at > 0 && !lines[at - 1].ends_with('\n') {
+            lines[at - 1].push('\n');
+        }
+        let mut run = fragment.lines.clone();
+        if at < lines.len()
+            && let Some(last) = run.last_mut()
+            && !last.ends_with('\n')
+        {
+            last.push('\n');
+        }
+        cursor = at + run.len();
+        lines.splice(at..at, run);
+    }
+    lines.concat()
+}
+
+/// Points the repository at the filter when redaction is on and removes it
+/// when it is off. Turning it on, or changing the rules, stages tracked
+/// files again so lines committed before are dropped from the next commit.
+pub fn install(git: &GitFacade, config: &RedactionConfig) -> Result<()> {
+    let state = git.git_dir()?.join(STATE_DIR);
+    let rules = state.join(RULES_FILE);
+    if !config.enabled {
+        git.set_local_attributes(&[])?;
+        git.unset_config_value(&format!("filter.{FILTER_NAME}.clean"))?;
+        git.unset_config_value(&format!("filter.{FILTER_NAME}.smudge"))?;
+        git.unset_config_value(&format!("filter.{FILTER_NAME}.required"))?;
+        if rules.exists() {
+            fs::remove_file(&rules)
+                .with_context(|| format!("failed to remove {}", rules.display()))?;
+        }
+        return Ok(());
+    }
+    // Fail here rather than in git, where a bad pattern would stop every
+    // commit.
+    Redactor::new(config)?;
+
+    let serialized =
+        serde_yaml::to_string(config).context("failed to serialize redaction rules")?;
+    let changed = fs::read_to_string(&rules).ok().as_deref() != Some(serialized.as_str());
+    if changed {
+        fs::create_dir_all(&state)
+            .with_context(|| format!("failed to create {}", state.display()))?;
+        fs::write(&rules, &serialized)
+            .with_context(|| format!("failed to write {}", rules.display()))?;
+    }
+
+    let exe = std::env::current_exe().context("failed to locate the obsyncgit executable")?;
+    for mode in ["clean", "smudge"] {
+        git.set_config_value(
+            &format!("filter.{FILTER_NAME}.{mode}"),
+            &format!(
+                "{} filter {mode} --rules {} %f",
+                shell_quote(&exe),
+                shell_quote(&rules)
+            ),
+        )?;
+    }
+    // Without the filter git would commit the file as it is on disk.
+    git.set_config_value(&format!("filter.{FILTER_NAME}.required"), "true")?;
+    let attributes: Vec<String> = config
+        .paths
+        .iter()
+        .filter(|path| !path.trim().is_empty())
+        .map(|path| format!("{path} filter={FILTER_NAME}"))
+        .collect();
+    git.set_local_attributes(&attributes.iter().map(String::as_str).collect::<Vec<_>>())?;
+    if changed {
+        git.renormalize()?;
+    }
+    Ok(())
+}
+
+/// `obsyncgit filter clean`: copies stdin to stdout without the redacted
+/// lines, which are saved for `smudge`.
+pub fn clean(rules: &Path, path: &str) -> Result<()> {
+    let input = read_stdin()?;
+    let Ok(text) = std::str::from_utf8(&input) else {
+        return write_stdout(&input);
+    };
+    let config: RedactionConfig = serde_yaml::from_str(
+        &fs::read_to_string(rules)
+            .with_context(|| format!("failed to read {}", rules.display()))?,
+    )
+    .with_context(|| format!("failed to parse {}", rules.display()))?;
+    let (kept, fragments) = Redactor::new(&config)?.split(text);
+
+    let file = fragments_file(rules, path);
+    if fragments.is_empty() {
+        if file.exists() {
+            fs::remove_file(&file)
+                .with_context(|| format!("failed to remove {}", file.display()))?;
+        }
+    } else {
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        let saved = serde_json::to_vec(&Fragments { fragments })
+            .context("failed to serialize redacted lines")?;
+        fs::write(&file, saved).with_context(|| format!("failed to write {}", file.display()))?;
+    }
+    write_stdout(kept.as_bytes())
+}
+
+/// `obsyncgit filter smudge`: copies stdin to stdout with the lines the
+/// last `clean` of `path` removed put back.
+pub fn smudge(rules: &Path, path: &str) -> Result<()> {
+    let input = read_stdin()?;
+    let saved = fs::read(fragments_file(rules, path))
+        .ok()
+        .and_then(|saved| serde_json::from_slice::<Fragments>(&saved).ok());
+    match (saved, std::str::from_utf8(&input)) {
+        (Some(saved), Ok(text)) if !saved.fragments.is_empty() => {
+            write_stdout(restore(text, &saved.fragments).as_bytes())
+        }
+        _ => write_stdout(&input),
+    }
+}
+
+/// Where the lines removed from `path` are kept, next to `rules`.
+fn fragments_file(rules: &Path, path: &str) -> PathBuf {
+    let digest = Sha256::digest(path.as_bytes());
+    let name: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+    rules
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(FRAGMENTS_DIR)
+        .join(format!("{name}.json"))
+}
+
+fn read_stdin() -> Result<Vec<u8>> {
+    let mut input = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut input)
+        .context("failed to read the file from git")?;
+    Ok(input)
+}
+
+fn write_stdout(bytes: &[u8]) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout
+        .write_all(bytes)
+        .and_then(|()| stdout.flush())
+        .context("failed to write the file to git")
+}
+
+/// Quotes `path` for the shell git runs filters with.
+fn shell_quote(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    format!("'{}'", path.replace('\'', r"'\''"))
+}
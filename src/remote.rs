@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Timelike, Utc};
+use tracing::{info, warn};
+
+use crate::config::RemoteAvailabilityConfig;
+use crate::git_error::GitError;
+
+/// Number of past outages kept around to describe availability patterns.
+const OUTAGE_HISTORY: usize = 32;
+
+/// Failure markers of pushes refused by branch protection on GitHub, GitLab
+/// and Gitea.
+const PROTECTED_MARKERS: &[&str] = &[
+    "protected branch",
+    "gh006",
+    "gh013",
+    "repository rule violations",
+    "not allowed to push code to protected branches",
+    "not allowed to push to protected branch",
+];
+
+/// Browser address of the repository behind a clone URL, for the usual
+/// `git@host:owner/repo.git`, `ssh://` and `https://` forms. Credentials and
+/// ports are dropped; the page is assumed to be served over HTTPS on the
+/// same host, as on GitHub, GitLab and Gitea.
+pub fn web_url(repo_url: &str) -> Option<String> {
+    let url = repo_url.trim();
+    let (host, path) = match url.split_once("://") {
+        Some((_, rest)) => {
+            let (authority, path) = rest.split_once('/')?;
+            let host = authority
+                .rsplit_once('@')
+                .map_or(authority, |(_, host)| host);
+            (host.split(':').next().unwrap_or(host), path)
+        }
+        None => {
+            let (authority, path) = url.split_once(':')?;
+            (
+                authority
+                    .rsplit_once('@')
+                    .map_or(authority, |(_, host)| host),
+                path,
+            )
+        }
+    };
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    // A single letter is a Windows drive, as in `C:\vaults\notes.git`.
+    if host.len() <= 1 || path.is_empty() {
+        return None;
+    }
+    Some(format!("https://{host}/{path}"))
+}
+
+/// Returns true when the error chain looks like the remote being unreachable
+/// rather than a problem with the repository or credentials.
+pub fn is_remote_unavailable(err: &anyhow::Error) -> bool {
+    matches!(GitError::of(err), GitError::Network(_))
+}
+
+/// Returns true when a push was refused because the remote branch contains
+/// commits that are not in the local history.
+pub fn is_push_rejected(err: &anyhow::Error) -> bool {
+    matches!(GitError::of(err), GitError::NonFastForward(_))
+}
+
+/// Returns true when a push was refused because the branch is protected, so
+/// retrying will never succeed.
+pub fn is_protected_branch(err: &anyhow::Error) -> bool {
+    let message = format!("{err:#}").to_ascii_lowercase();
+    PROTECTED_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+#[derive(Debug, Clone)]
+pub struct Outage {
+    pub started_at: DateTime<Utc>,
+    pub duration: Duration,
+}
+
+/// Tracks whether the remote is reachable and schedules recovery probes on a
+/// dedicated, shorter retry curve while it is not.
+#[derive(Debug)]
+pub struct RemoteHealth {
+    config: RemoteAvailabilityConfig,
+    down_since: Option<(Instant, DateTime<Utc>)>,
+    consecutive_failures: u32,
+    next_probe: Option<Instant>,
+    outages: VecDeque<Outage>,
+}
+
+impl RemoteHealth {
+    pub fn new(config: &RemoteAvailabilityConfig) -> Self {
+        Self {
+            config: config.clone(),
+            down_since: None,
+            consecutive_failures: 0,
+            next_probe: None,
+            outages: VecDeque::new(),
+        }
+    }
+
+    pub fn is_down(&self) -> bool {
+        self.down_since.is_some()
+    }
+
+    /// Moment at which the next reachability probe should run, if the remote
+    /// is currently considered down.
+    pub fn next_probe(&self) -> Option<Instant> {
+        self.next_probe
+    }
+
+    pub fn probe_due(&self, now: Instant) -> bool {
+        self.next_probe.is_some_and(|at| now >= at)
+    }
+
+    /// Brings the next probe forward to now, e.g. once the machine has
+    /// woken up and its network may be back.
+    pub fn probe_now(&mut self) {
+        if self.is_down() {
+            self.next_probe = Some(Instant::now());
+        }
+    }
+
+    pub fn record_unavailable(&mut self) {
+        let now = Instant::now();
+        if self.down_since.is_none() {
+            self.down_since = Some((now, Utc::now()));
+            warn!("remote is unreachable; committing locally until it recovers");
+        }
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        let delay = self.retry_delay();
+        self.next_probe = Some(now + delay);
+        warn!(
+            attempts = self.consecutive_failures,
+            retry_in = %humantime::format_duration(delay),
+            "remote still unreachable"
+        );
+    }
+
+    pub fn record_available(&mut self) {
+        self.consecutive_failures = 0;
+        self.next_probe = None;
+        let Some((since, started_at)) = self.down_since.take() else {
+            return;
+        };
+        let duration = since.elapsed();
+        if self.outages.len() == OUTAGE_HISTORY {
+            self.outages.pop_front();
+        }
+        self.outages.push_back(Outage {
+            started_at,
+            duration,
+        });
+        info!(
+            downtime = %humantime::format_duration(Duration::from_secs(duration.as_secs())),
+            outages_last_day = self.outages_within(chrono::Duration::hours(24)),
+            typical_start_hour_utc = ?self.typical_start_hour(),
+            "remote is reachable again, resuming push/pull"
+        );
+    }
+
+    pub fn outages(&self) -> impl Iterator<Item = &Outage> {
+        self.outages.iter()
+    }
+
+    fn outages_within(&self, window: chrono::Duration) -> usize {
+        let cutoff = Utc::now() - window;
+        self.outages
+            .iter()
+            .filter(|outage| outage.started_at >= cutoff)
+            .count()
+    }
+
+    /// Hour of day (UTC) at which most recorded outages started, once the
+    /// same hour has been seen at least twice.
+    pub fn typical_start_hour(&self) -> Option<u32> {
+        let mut counts = [0usize; 24];
+        for outage in &self.outages {
+            counts[outage.started_at.hour() as usize] += 1;
+        }
+        let (hour, count) = counts.iter().enumerate().max_by_key(|(_, count)| **count)?;
+        (*count >= 2).then_some(hour as u32)
+    }
+
+    fn retry_delay(&self) -> Duration {
+        let base = self.config.retry_base_seconds.max(1);
+        let max = self.config.retry_max_seconds.max(base);
+        let step = self.consecutive_failures.saturating_sub(1).min(16);
+        Duration::from_secs(base.saturating_mul(1u64 << step).min(max))
+    }
+}
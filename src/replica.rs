@@ -0,0 +1,237 @@
+//! `replicas`: plain folders, such as a USB stick, that the daemon copies
+//! the vault's tracked files into after each successful sync. A replica is
+//! a backup any file manager can read, without git. It is a one-way
+//! mirror: files are copied when their checksum differs, and files the
+//! vault no longer has are deleted from the replica.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::config::Config;
+use crate::git;
+
+/// What the replica holds, kept in its root so a copy on a removable drive
+/// describes itself.
+const MANIFEST: &str = ".obsyncgit-replica.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The vault the replica mirrors; one folder cannot mirror two vaults.
+    pub vault: String,
+    /// RFC 3339.
+    pub mirrored_at: Option<String>,
+    files: BTreeMap<String, Entry>,
+}
+
+impl Manifest {
+    /// Time since the last mirror; `None` before the first one.
+    pub fn age(&self) -> Option<Duration> {
+        let mirrored = DateTime::parse_from_rfc3339(self.mirrored_at.as_deref()?).ok()?;
+        (Utc::now() - mirrored.with_timezone(&Utc)).to_std().ok()
+    }
+}
+
+/// A file as last copied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    sha256: String,
+    size: u64,
+    /// Modification times in nanoseconds since the epoch, which let an
+    /// unchanged file skip hashing.
+    source_modified: u64,
+    replica_modified: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MirrorReport {
+    pub copied: usize,
+    pub deleted: usize,
+}
+
+/// The replica folder `entry` of `replicas` names.
+pub fn replica_dir(entry: &str) -> PathBuf {
+    git::expand_home(entry)
+}
+
+/// Whether the replica can be written: its folder exists, or its parent
+/// does, so an unmounted drive is not recreated on the system disk.
+pub fn available(dir: &Path) -> bool {
+    dir.is_dir() || dir.parent().is_some_and(Path::is_dir)
+}
+
+/// The replica's manifest, if it has been written to.
+pub fn load(dir: &Path) -> Option<Manifest> {
+    let contents = fs::read(dir.join(MANIFEST)).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+/// Brings the replica at `dir` up to date with `files` (vault-relative, as
+/// tracked by git). Files changed in the replica since they were copied
+/// are overwritten, but not deleted.
+pub fn mirror(config: &Config, dir: &Path, files: &[String]) -> Result<MirrorReport> {
+    let root = config.workdir.as_std_path();
+    if dir.starts_with(root) || root.starts_with(dir) {
+        bail!(
+            "replica {} overlaps the vault; pick a folder outside it",
+            dir.display()
+        );
+    }
+    if !available(dir) {
+        bail!(
+            "{} is not available; is the drive holding it mounted?",
+            dir.display()
+        );
+    }
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let vault = config.workdir.to_string();
+    let previous = match load(dir) {
+        Some(manifest) if manifest.vault != vault => bail!(
+            "{} holds a replica of {}; give each vault its own folder",
+            dir.display(),
+            manifest.vault
+        ),
+        Some(manifest) => manifest.files,
+        None => BTreeMap::new(),
+    };
+
+    let mut report = MirrorReport::default();
+    let mut current = BTreeMap::new();
+    for path in files {
+        let source = root.join(path);
+        let Ok(meta) = fs::metadata(&source) else {
+            continue;
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        let target = dir.join(path);
+        let source_modified = nanos(meta.modified().ok());
+        let target_meta = fs::metadata(&target).ok();
+        let target_modified = nanos(target_meta.as_ref().and_then(|meta| meta.modified().ok()));
+        let target_size = target_meta.as_ref().map(|meta| meta.len());
+        let known = previous.get(path);
+        // Neither side changed since the last copy.
+        if let Some(entry) = known
+            && entry.size == meta.len()
+            && entry.source_modified == source_modified
+            && target_size == Some(entry.size)
+            && entry.replica_modified == target_modified
+        {
+            current.insert(path.clone(), entry.clone());
+            continue;
+        }
+        let sha256 = hash_file(&source)?;
+        let up_to_date = target_size == Some(meta.len())
+            && (known.is_some_and(|entry| {
+                entry.sha256 == sha256 && entry.replica_modified == target_modified
+            }) || hash_file(&target).is_ok_and(|existing| existing == sha256));
+        let replica_modified = if up_to_date {
+            target_modified
+        } else {
+            copy(&source, &target)?;
+            report.copied += 1;
+            nanos(fs::metadata(&target).and_then(|meta| meta.modified()).ok())
+        };
+        current.insert(
+            path.clone(),
+            Entry {
+                sha256,
+                size: meta.len(),
+                source_modified,
+                replica_modified,
+            },
+        );
+    }
+
+    for (path, entry) in &previous {
+        if current.contains_key(path) {
+            continue;
+        }
+        let target = dir.join(path);
+        let Ok(meta) = fs::metadata(&target) else {
+            continue;
+        };
+        if meta.len() != entry.size || nanos(meta.modified().ok()) != entry.replica_modified {
+            warn!(
+                path = %target.display(),
+                "the vault no longer has this file, but it was changed in the replica; leaving it there"
+            );
+            continue;
+        }
+        fs::remove_file(&target)
+            .with_context(|| format!("failed to delete {}", target.display()))?;
+        report.deleted += 1;
+        prune_empty_parents(dir, &target);
+    }
+
+    save(
+        dir,
+        &Manifest {
+            vault,
+            mirrored_at: Some(Utc::now().to_rfc3339()),
+            files: current,
+        },
+    )?;
+    Ok(report)
+}
+
+fn save(dir: &Path, manifest: &Manifest) -> Result<()> {
+    let path = dir.join(MANIFEST);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(manifest)?)
+        .with_context(|| format!("failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, &path).with_context(|| format!("failed to replace {}", path.display()))
+}
+
+/// Copies through a temporary file beside `target`, so a drive pulled out
+/// mid-copy leaves the old version rather than half a file.
+fn copy(source: &Path, target: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut name = target.file_name().unwrap_or_default().to_os_string();
+    name.push(".obsyncgit-tmp");
+    let tmp = target.with_file_name(name);
+    if let Err(err) = fs::copy(source, &tmp) {
+        let _ = fs::remove_file(&tmp);
+        return Err(err).with_context(|| format!("failed to copy to {}", tmp.display()));
+    }
+    fs::rename(&tmp, target).with_context(|| format!("failed to replace {}", target.display()))
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+fn nanos(time: Option<SystemTime>) -> u64 {
+    time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos() as u64)
+}
+
+/// Removes the folders between `file` and `root` that deleting it left
+/// empty.
+fn prune_empty_parents(root: &Path, file: &Path) {
+    let mut dir = file.parent();
+    while let Some(current) = dir {
+        if current == root || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
@@ -0,0 +1,40 @@
+//! Notices when the machine wakes from sleep. The daemon's timers run on
+//! the monotonic clock, which stands still while Linux and macOS are
+//! suspended, so after a night asleep the next poll would still be minutes
+//! away. A check that comes back much later than asked, counting time
+//! asleep as [`Moment`] does, means the machine slept.
+
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::clock::{Moment, SystemClock};
+use crate::control::Control;
+use crate::shutdown::Shutdown;
+
+/// How often the clocks are compared.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Time lost beyond `CHECK_INTERVAL` that counts as a suspend; clock
+/// corrections by NTP stay well below it.
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// Calls [`Control::notify_resumed`] after each wake-up, until shutdown.
+pub async fn watch(control: Control, shutdown: Shutdown) {
+    let clock = SystemClock;
+    let mut checked = Moment::now(&clock);
+    loop {
+        tokio::select! {
+            _ = shutdown.wait() => return,
+            _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+        }
+        let asleep = checked.elapsed(&clock).saturating_sub(CHECK_INTERVAL);
+        if asleep > SUSPEND_THRESHOLD {
+            info!(
+                asleep = %humantime::format_duration(Duration::from_secs(asleep.as_secs())),
+                "system resumed from sleep, syncing"
+            );
+            control.notify_resumed();
+        }
+        checked = Moment::now(&clock);
+    }
+}
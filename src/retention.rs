@@ -0,0 +1,262 @@
+//! `obsyncgit prune-history`: rewrites the vault's history so that old
+//! versions of attachments, such as images replaced years ago, are no
+//! longer part of it and a fresh clone no longer downloads them. Only paths
+//! matched by `retention.paths` are touched, and only versions no commit of
+//! the last `retention.keep_months` months still has; notes, current files
+//! and commit messages, authors and dates stay as they are.
+//!
+//! The rewritten branch is force-pushed. Other devices notice on their next
+//! pull: a rebase replays only their own commits onto the new history and
+//! the old one is deleted; a device with unpushed merges stops and asks for
+//! `obsyncgit repair --force` instead of merging the old history back in.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, Months, Utc};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use tracing::{info, warn};
+
+use crate::config::{Config, RemoteKind, SyncMode, SyncTopology};
+use crate::git::{GitFacade, RawCommit};
+use crate::instance;
+
+/// What a prune would drop.
+#[derive(Debug)]
+pub struct Plan {
+    /// Versions older than this are candidates.
+    pub cutoff: DateTime<Utc>,
+    /// Commits that lose files.
+    pub commits: usize,
+    /// Blobs no longer reachable afterwards, with their sizes.
+    pub blobs: HashMap<String, u64>,
+    history: Vec<RawCommit>,
+    /// Paths each affected tree loses.
+    drops: HashMap<String, Vec<String>>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.drops.is_empty()
+    }
+
+    /// Size of the dropped versions before compression; the space saved is
+    /// up to this much.
+    pub fn bytes(&self) -> u64 {
+        self.blobs.values().sum()
+    }
+}
+
+/// What a prune did.
+#[derive(Debug)]
+pub struct Pruned {
+    pub plan: Plan,
+    /// Bundle of the history as it was before, to undo the prune with.
+    pub backup: PathBuf,
+    /// Tags moved onto the rewritten commits.
+    pub tags: Vec<String>,
+}
+
+/// Works out which attachment versions a prune would drop, without
+/// changing anything.
+pub fn plan(git: &GitFacade, config: &Config) -> Result<Plan> {
+    let retention = &config.retention;
+    if retention.paths.is_empty() {
+        bail!(
+            "retention.paths is empty; list the attachment folders to prune, e.g. attachments/**"
+        );
+    }
+    let matcher = matcher(&retention.paths)?;
+    let cutoff = Utc::now()
+        .checked_sub_months(Months::new(retention.keep_months))
+        .context("retention.keep_months is out of range")?;
+    let history = git.history()?;
+    let head = git.head_commit()?;
+
+    // Versions that recent commits still have are kept in old commits too;
+    // dropping them there would save nothing.
+    let recent: Vec<String> = history
+        .iter()
+        .filter(|commit| commit.committed_at() >= cutoff.timestamp() || commit.commit == head)
+        .map(|commit| commit.commit.clone())
+        .collect();
+    let keep = git.blobs_of(&recent)?;
+    let recent: HashSet<&String> = recent.iter().collect();
+
+    let mut examined = HashSet::new();
+    let mut drops = HashMap::new();
+    let mut blobs = HashMap::new();
+    let mut commits = 0;
+    for commit in history
+        .iter()
+        .filter(|commit| !recent.contains(&commit.commit))
+    {
+        if examined.insert(commit.tree.clone()) {
+            let mut paths = Vec::new();
+            for file in git.tree_files(&commit.tree)? {
+                if !keep.contains(&file.blob) && matcher.is_match(&file.path) {
+                    blobs.insert(file.blob, file.size);
+                    paths.push(file.path);
+                }
+            }
+            if !paths.is_empty() {
+                drops.insert(commit.tree.clone(), paths);
+            }
+        }
+        if drops.contains_key(&commit.tree) {
+            commits += 1;
+        }
+    }
+    Ok(Plan {
+        cutoff,
+        commits,
+        blobs,
+        history,
+        drops,
+    })
+}
+
+/// Rewrites the history as [`plan`] works it out, force-pushes it and
+/// deletes the old history from the local repository. `approve` is shown
+/// the plan first; nothing changes unless it agrees. A bundle of the old
+/// history is written to the state directory before the rewrite. Refuses
+/// unless this device is in step with the remote, so nothing another
+/// device pushed is lost.
+pub fn prune(
+    git: &GitFacade,
+    config: &Config,
+    approve: impl FnOnce(&Plan) -> Result<bool>,
+) -> Result<Option<Pruned>> {
+    if config.sync.topology != SyncTopology::SharedBranch {
+        bail!("prune-history only works with sync.topology: shared-branch");
+    }
+    if config.sync.mode == SyncMode::PullOnly {
+        bail!("sync.mode is pull-only; prune the history on a device that pushes");
+    }
+    if config.remote.kind != RemoteKind::Git {
+        bail!("prune-history needs a git server; S3 and WebDAV remotes cannot be rewritten");
+    }
+    if git.rebase_in_progress() || git.merge_in_progress() {
+        bail!("a rebase or merge is unfinished; let the daemon sync first");
+    }
+    let dirty = git.list_pending_paths()?;
+    if !dirty.is_empty() {
+        bail!(
+            "the vault has uncommitted changes ({}); let the daemon commit and push them first",
+            dirty.join(", ")
+        );
+    }
+    git.fetch()?;
+    let head = git.head_commit()?;
+    if git.tracking_tip().as_deref() != Some(head.as_str()) {
+        bail!("this device is not in step with the remote; let the daemon sync first");
+    }
+
+    let plan = plan(git, config)?;
+    if !approve(&plan)? || plan.is_empty() {
+        return Ok(None);
+    }
+    let backup = write_backup(git, config)?;
+    info!(path = %backup.display(), "saved the history before pruning it");
+
+    info!(commits = plan.history.len(), "rewriting the history");
+    let mapped = rewrite(git, &plan)?;
+    let new_head = &mapped[&head];
+    let branch = format!("refs/heads/{}", config.branch);
+    git.update_ref(&branch, new_head, &head)?;
+    let mut moved = Vec::new();
+    for (name, commit, _) in git.list_tags("")? {
+        if let Some(new) = mapped.get(&commit).filter(|new| **new != commit) {
+            git.update_ref(&format!("refs/tags/{name}"), new, &commit)?;
+            moved.push((name, commit));
+        }
+    }
+
+    // The lease fails when another device pushed since the fetch above.
+    if let Err(err) = git.force_push_head_to(&config.branch) {
+        let _ = git.update_ref(&branch, &head, new_head);
+        for (name, commit) in &moved {
+            let _ = git.update_ref(&format!("refs/tags/{name}"), commit, &mapped[commit]);
+        }
+        return Err(err.context("failed to push the pruned history; nothing was changed"));
+    }
+    let remote_tags: HashSet<String> = git.remote_tags()?.into_iter().collect();
+    let tags: Vec<String> = moved.into_iter().map(|(name, _)| name).collect();
+    let shared: Vec<String> = tags
+        .iter()
+        .filter(|name| remote_tags.contains(*name))
+        .cloned()
+        .collect();
+    if !shared.is_empty()
+        && let Err(err) = git.force_push_tags(&shared)
+    {
+        warn!(
+            ?err,
+            "failed to push the moved tags; the remote keeps the old history until they are"
+        );
+    }
+    git.prune_unreachable()?;
+    Ok(Some(Pruned { plan, backup, tags }))
+}
+
+/// Writes every commit again, oldest first, with the planned files taken
+/// out of old trees. Returns the new id of every commit; a commit neither
+/// losing files nor having a rewritten parent keeps its id.
+fn rewrite(git: &GitFacade, plan: &Plan) -> Result<HashMap<String, String>> {
+    let mut trees: HashMap<&str, String> = HashMap::new();
+    let mut mapped: HashMap<String, String> = HashMap::new();
+    for commit in &plan.history {
+        let tree = match plan.drops.get(&commit.tree) {
+            Some(paths) => match trees.get(commit.tree.as_str()) {
+                Some(tree) => tree.clone(),
+                None => {
+                    let tree = git.tree_without(&commit.tree, paths)?;
+                    trees.insert(&commit.tree, tree.clone());
+                    tree
+                }
+            },
+            None => commit.tree.clone(),
+        };
+        let parents: Vec<String> = commit
+            .parents
+            .iter()
+            .map(|parent| mapped.get(parent).unwrap_or(parent).clone())
+            .collect();
+        let new = if tree == commit.tree && parents == commit.parents {
+            commit.commit.clone()
+        } else {
+            git.commit_tree(commit, &tree, &parents)?
+        };
+        mapped.insert(commit.commit.clone(), new);
+    }
+    Ok(mapped)
+}
+
+fn write_backup(git: &GitFacade, config: &Config) -> Result<PathBuf> {
+    let dir = config.state_dir()?.join("retention");
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {dir}"))?;
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    let bundle = dir
+        .join(format!(
+            "vault-{}-{stamp}.bundle",
+            instance::vault_key(config)
+        ))
+        .into_std_path_buf();
+    git.bundle(&bundle)?;
+    Ok(bundle)
+}
+
+fn matcher(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            GlobBuilder::new(pattern.trim_start_matches('/'))
+                .literal_separator(true)
+                .build()
+                .with_context(|| format!("invalid retention.paths pattern '{pattern}'"))?,
+        );
+    }
+    builder.build().context("failed to compile retention.paths")
+}
@@ -0,0 +1,160 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Timelike};
+
+use crate::config::ScheduleConfig;
+
+/// Decides when network operations (pull/push) are allowed. Local commits are
+/// never affected by the schedule.
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    active_windows: Vec<TimeWindow>,
+    quiet_hours: Vec<TimeWindow>,
+    pauses: Vec<CronExpr>,
+}
+
+impl Schedule {
+    pub fn new(config: &ScheduleConfig) -> Result<Self> {
+        let parse_windows = |windows: &[String]| -> Result<Vec<TimeWindow>> {
+            windows
+                .iter()
+                .map(|window| TimeWindow::parse(window))
+                .collect()
+        };
+        Ok(Self {
+            active_windows: parse_windows(&config.active_windows)?,
+            quiet_hours: parse_windows(&config.quiet_hours)?,
+            pauses: config
+                .pause_cron
+                .iter()
+                .map(|expr| CronExpr::parse(expr))
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn network_allowed<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        let time = now.time();
+        if !self.active_windows.is_empty()
+            && !self
+                .active_windows
+                .iter()
+                .any(|window| window.contains(time))
+        {
+            return false;
+        }
+        if self.quiet_hours.iter().any(|window| window.contains(time)) {
+            return false;
+        }
+        !self.pauses.iter().any(|cron| cron.matches(now))
+    }
+}
+
+/// A daily `HH:MM-HH:MM` window; the end may wrap past midnight.
+#[derive(Debug, Clone, Copy)]
+struct TimeWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl TimeWindow {
+    fn parse(spec: &str) -> Result<Self> {
+        let (start, end) = spec
+            .split_once('-')
+            .with_context(|| format!("time window '{spec}' must look like HH:MM-HH:MM"))?;
+        let parse = |value: &str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M")
+                .with_context(|| format!("invalid time '{value}' in window '{spec}'"))
+        };
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Five-field cron expression (`minute hour day-of-month month day-of-week`)
+/// supporting `*`, numbers, ranges, lists and `/step`. As in standard cron,
+/// a day matches either day field when both are restricted.
+#[derive(Debug, Clone)]
+struct CronExpr {
+    fields: [Vec<bool>; 5],
+    /// Whether day-of-month and day-of-week start with `*`.
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+const CRON_RANGES: [(u32, u32); 5] = [(0, 59), (0, 23), (1, 31), (1, 12), (0, 7)];
+
+impl CronExpr {
+    fn parse(expr: &str) -> Result<Self> {
+        let parts: Vec<&str> = expr.split_whitespace().collect();
+        if parts.len() != 5 {
+            bail!("cron expression '{expr}' must have 5 fields");
+        }
+        let mut fields: [Vec<bool>; 5] = Default::default();
+        for (index, part) in parts.iter().enumerate() {
+            let (min, max) = CRON_RANGES[index];
+            fields[index] = parse_cron_field(part, min, max)
+                .with_context(|| format!("invalid field '{part}' in cron expression '{expr}'"))?;
+        }
+        // Sunday may be written as 0 or 7.
+        if fields[4][7] {
+            fields[4][0] = true;
+        }
+        Ok(Self {
+            fields,
+            any_day_of_month: parts[2].starts_with('*'),
+            any_day_of_week: parts[4].starts_with('*'),
+        })
+    }
+
+    fn matches<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> bool {
+        let [minute, hour, day_of_month, month, day_of_week] = &self.fields;
+        let by_month = day_of_month[now.day() as usize];
+        let by_week = day_of_week[now.weekday().num_days_from_sunday() as usize];
+        let day = if self.any_day_of_month || self.any_day_of_week {
+            by_month && by_week
+        } else {
+            by_month || by_week
+        };
+        minute[now.minute() as usize]
+            && hour[now.hour() as usize]
+            && month[now.month() as usize]
+            && day
+    }
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<Vec<bool>> {
+    let mut allowed = vec![false; max as usize + 1];
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (item, 1),
+        };
+        if step == 0 {
+            bail!("step must be positive");
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse()?, end.parse()?)
+        } else {
+            let value = range.parse()?;
+            (value, if item.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            bail!("value out of range {min}-{max}");
+        }
+        for value in (start..=end).step_by(step as usize) {
+            allowed[value as usize] = true;
+        }
+    }
+    Ok(allowed)
+}
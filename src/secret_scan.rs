@@ -0,0 +1,110 @@
+//! `security.secret_scan`: keeps files that look like credentials out of
+//! commits. A vault pushed to a hosted remote is easy to share by accident,
+//! and once a key is in the history it has to be rotated rather than
+//! deleted. Files are flagged by name (`.env`, `*.pem`, SSH keys) or by
+//! content (private key blocks, provider token formats, `api_key:` lines in
+//! frontmatter).
+
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::bytes::RegexSet;
+
+use crate::config::SecretScanConfig;
+
+/// Larger files are checked by name only; credentials live in small files.
+const MAX_SCAN_BYTES: u64 = 1024 * 1024;
+
+/// Why a file looks like a secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The path matches this entry of `file_globs`.
+    Name(String),
+    /// The content matches this entry of `patterns`.
+    Content(String),
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Name(glob) => write!(f, "its name matches {glob}"),
+            Self::Content(pattern) => write!(f, "its content matches {pattern}"),
+        }
+    }
+}
+
+pub struct SecretScanner {
+    files: GlobSet,
+    file_globs: Vec<String>,
+    allow: GlobSet,
+    content: RegexSet,
+    patterns: Vec<String>,
+}
+
+impl SecretScanner {
+    /// `None` when the scan is turned off.
+    pub fn new(config: &SecretScanConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            files: glob_set(&config.file_globs)?,
+            file_globs: config.file_globs.clone(),
+            allow: glob_set(&config.allow)?,
+            content: RegexSet::new(&config.patterns)
+                .context("invalid pattern in security.secret_scan.patterns")?,
+            patterns: config.patterns.clone(),
+        }))
+    }
+
+    /// Why the file at `path` (vault-relative) looks like a secret, if it
+    /// does. Missing files, i.e. deletions, never do.
+    pub fn check(&self, root: &Path, path: &str) -> Option<Finding> {
+        if self.allow.is_match(path) {
+            return None;
+        }
+        let full = root.join(path);
+        if !full.is_file() {
+            return None;
+        }
+        if let Some(&index) = self.files.matches(path).first() {
+            return Some(Finding::Name(self.file_globs[index].clone()));
+        }
+        if self.content.is_empty() {
+            return None;
+        }
+        let mut content = Vec::new();
+        let file = fs::File::open(&full).ok()?;
+        if file.metadata().ok()?.len() > MAX_SCAN_BYTES {
+            return None;
+        }
+        file.take(MAX_SCAN_BYTES).read_to_end(&mut content).ok()?;
+        let index = self.content.matches(&content).into_iter().next()?;
+        Some(Finding::Content(self.patterns[index].clone()))
+    }
+
+    /// The files among `paths` that look like secrets.
+    pub fn scan(&self, root: &Path, paths: &[String]) -> Vec<(String, Finding)> {
+        paths
+            .iter()
+            .filter_map(|path| Some((path.clone(), self.check(root, path)?)))
+            .collect()
+    }
+}
+
+fn glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(
+            Glob::new(glob)
+                .with_context(|| format!("invalid glob '{glob}' in security.secret_scan"))?,
+        );
+    }
+    builder
+        .build()
+        .context("failed to build security.secret_scan globs")
+}
@@ -0,0 +1,228 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+/// Prefix of config values looked up in the OS keyring.
+pub const KEYRING_PREFIX: &str = "!keyring:";
+/// Prefix of config values read from an environment variable.
+pub const ENV_PREFIX: &str = "!env:";
+/// Keyring service under which secrets are stored.
+const SERVICE: &str = "obsyncgit";
+
+/// Whether `value` is a secret reference rather than a literal.
+pub fn is_reference(value: &str) -> bool {
+    value.starts_with(KEYRING_PREFIX) || value.starts_with(ENV_PREFIX)
+}
+
+/// Resolves `!keyring:name` and `!env:VAR` references; other values are
+/// returned unchanged.
+pub fn resolve(value: &str) -> Result<String> {
+    if let Some(name) = value.strip_prefix(KEYRING_PREFIX) {
+        return load(name.trim());
+    }
+    if let Some(var) = value.strip_prefix(ENV_PREFIX) {
+        let var = var.trim();
+        return std::env::var(var).with_context(|| {
+            format!("environment variable {var} referenced in config is not set")
+        });
+    }
+    Ok(value.to_string())
+}
+
+/// Reads a secret from the OS keyring.
+pub fn load(name: &str) -> Result<String> {
+    validate_name(name)?;
+    let secret = platform::load(name)
+        .with_context(|| format!("failed to read secret '{name}' from the OS keyring"))?;
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Stores a secret in the OS keyring, replacing any previous value.
+pub fn store(name: &str, secret: &str) -> Result<()> {
+    validate_name(name)?;
+    platform::store(name, secret)
+        .with_context(|| format!("failed to store secret '{name}' in the OS keyring"))
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+    {
+        bail!("secret name '{name}' may only contain letters, digits, '-', '_' and '.'");
+    }
+    Ok(())
+}
+
+/// Runs a keyring helper, optionally feeding `input` on stdin, and returns
+/// its stdout.
+fn run_helper(
+    program: &str,
+    args: &[&str],
+    envs: &[(&str, &str)],
+    input: Option<&str>,
+) -> Result<String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .envs(envs.iter().copied())
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to launch keyring helper {program}"))?;
+    if let Some(input) = input {
+        let mut stdin = child
+            .stdin
+            .take()
+            .context("keyring helper stdin unavailable")?;
+        stdin
+            .write_all(input.as_bytes())
+            .context("failed to pass secret to keyring helper")?;
+    }
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("failed to wait for keyring helper {program}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{program} exited with {}: {}", output.status, stderr.trim());
+    }
+    String::from_utf8(output.stdout).context("keyring helper returned non UTF-8 output")
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use anyhow::{Result, bail};
+
+    use super::{SERVICE, run_helper};
+
+    /// Uses libsecret's `secret-tool` (GNOME Keyring, KWallet via the Secret
+    /// Service API).
+    pub(super) fn load(name: &str) -> Result<String> {
+        let secret = run_helper(
+            "secret-tool",
+            &["lookup", "service", SERVICE, "account", name],
+            &[],
+            None,
+        )?;
+        if secret.is_empty() {
+            bail!("no such secret");
+        }
+        Ok(secret)
+    }
+
+    pub(super) fn store(name: &str, secret: &str) -> Result<()> {
+        let label = format!("ObsyncGit {name}");
+        run_helper(
+            "secret-tool",
+            &[
+                "store", "--label", &label, "service", SERVICE, "account", name,
+            ],
+            &[],
+            Some(secret),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use anyhow::Result;
+
+    use super::{SERVICE, run_helper};
+
+    /// Uses the login keychain through `security`.
+    pub(super) fn load(name: &str) -> Result<String> {
+        run_helper(
+            "security",
+            &["find-generic-password", "-s", SERVICE, "-a", name, "-w"],
+            &[],
+            None,
+        )
+    }
+
+    /// `security` only accepts the password as an argument (or from a tty),
+    /// so it is briefly visible in the process list while stored.
+    pub(super) fn store(name: &str, secret: &str) -> Result<()> {
+        run_helper(
+            "security",
+            &[
+                "add-generic-password",
+                "-U",
+                "-s",
+                SERVICE,
+                "-a",
+                name,
+                "-w",
+                secret,
+            ],
+            &[],
+            None,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use anyhow::Result;
+
+    use super::{SERVICE, run_helper};
+
+    const VAULT: &str = "$null = [Windows.Security.Credentials.PasswordVault,Windows.Security.Credentials,ContentType=WindowsRuntime]; \
+        $v = New-Object Windows.Security.Credentials.PasswordVault;";
+
+    /// Uses the Windows Credential Locker (WinRT `PasswordVault`). Names and
+    /// secrets travel through the environment and stdin, never the command line.
+    pub(super) fn load(name: &str) -> Result<String> {
+        let script = format!(
+            "{VAULT} $c = $v.Retrieve($env:OBSYNCGIT_SECRET_SERVICE, $env:OBSYNCGIT_SECRET_NAME); \
+             $c.RetrievePassword(); [Console]::Out.Write($c.Password)"
+        );
+        run_helper(
+            "powershell",
+            &["-NoProfile", "-NonInteractive", "-Command", &script],
+            &[
+                ("OBSYNCGIT_SECRET_SERVICE", SERVICE),
+                ("OBSYNCGIT_SECRET_NAME", name),
+            ],
+            None,
+        )
+    }
+
+    pub(super) fn store(name: &str, secret: &str) -> Result<()> {
+        let script = format!(
+            "{VAULT} $s = [Console]::In.ReadToEnd(); \
+             $c = New-Object Windows.Security.Credentials.PasswordCredential($env:OBSYNCGIT_SECRET_SERVICE, $env:OBSYNCGIT_SECRET_NAME, $s); \
+             $v.Add($c)"
+        );
+        run_helper(
+            "powershell",
+            &["-NoProfile", "-NonInteractive", "-Command", &script],
+            &[
+                ("OBSYNCGIT_SECRET_SERVICE", SERVICE),
+                ("OBSYNCGIT_SECRET_NAME", name),
+            ],
+            Some(secret),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+mod platform {
+    use anyhow::{Result, bail};
+
+    pub(super) fn load(_name: &str) -> Result<String> {
+        bail!("no OS keyring support on this platform; use !env: references instead")
+    }
+
+    pub(super) fn store(_name: &str, _secret: &str) -> Result<()> {
+        bail!("no OS keyring support on this platform; use !env: references instead")
+    }
+}
@@ -0,0 +1,317 @@
+//! `obsyncgit service`: the daemon as a Windows service, which the service
+//! control manager starts at boot whether or not anyone logs in. On macOS
+//! the same commands manage the LaunchAgent from [`crate::launchd`]; Linux
+//! uses a systemd user unit instead, see `examples/`.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::shutdown::Shutdown;
+
+/// Name the service is registered under.
+pub const SERVICE_NAME: &str = "ObsyncGit";
+
+/// How `obsyncgit service install --windows-service` registers the daemon.
+#[derive(Debug, Clone)]
+pub struct InstallOptions {
+    /// Absolute path of the configuration the service loads.
+    pub config_path: Utf8PathBuf,
+    /// Account the service runs as, e.g. `.\alice`; `None` for LocalSystem.
+    pub account: Option<String>,
+    pub password: Option<String>,
+    /// Replace the settings of a service installed before.
+    pub force: bool,
+}
+
+/// The service's log file, next to its configuration: a service has no
+/// console to log to.
+pub fn log_path(config_path: &Utf8Path) -> PathBuf {
+    config_path
+        .parent()
+        .map_or_else(|| PathBuf::from("."), |dir| dir.as_std_path().to_path_buf())
+        .join("obsyncgit-service.log")
+}
+
+#[cfg(windows)]
+pub use windows::{install, run, start, status, stop, uninstall};
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::{OsStr, OsString};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use anyhow::{Context, Result, bail};
+    use tracing::{error, info};
+    use windows_service::service::{
+        ServiceAccess, ServiceAction, ServiceActionType, ServiceControl, ServiceControlAccept,
+        ServiceErrorControl, ServiceExitCode, ServiceFailureActions, ServiceFailureResetPeriod,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    use windows_service::{define_windows_service, service_dispatcher};
+
+    use super::{InstallOptions, SERVICE_NAME, Shutdown};
+
+    const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+    /// How long `stop` waits for the daemon to finish the cycle it is in.
+    const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+    const ERROR_SERVICE_DOES_NOT_EXIST: i32 = 1060;
+
+    type Daemon = Box<dyn FnOnce(Shutdown) -> Result<()> + Send>;
+
+    /// The daemon [`run`] hands to the service's main function, which the
+    /// dispatcher calls on a thread of its own.
+    static DAEMON: Mutex<Option<Daemon>> = Mutex::new(None);
+
+    fn manager(access: ServiceManagerAccess) -> Result<ServiceManager> {
+        ServiceManager::local_computer(None::<&str>, access).context(
+            "failed to connect to the service control manager; run from an administrator prompt",
+        )
+    }
+
+    fn is_missing(err: &windows_service::Error) -> bool {
+        matches!(err, windows_service::Error::Winapi(err)
+            if err.raw_os_error() == Some(ERROR_SERVICE_DOES_NOT_EXIST))
+    }
+
+    pub fn install(options: &InstallOptions) -> Result<()> {
+        let manager =
+            manager(ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)?;
+        let info = ServiceInfo {
+            name: OsString::from(SERVICE_NAME),
+            display_name: OsString::from("ObsyncGit vault sync"),
+            service_type: SERVICE_TYPE,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: std::env::current_exe()
+                .context("failed to locate running executable")?,
+            launch_arguments: vec![
+                OsString::from("--config"),
+                OsString::from(options.config_path.as_str()),
+                OsString::from("service"),
+                OsString::from("run"),
+            ],
+            dependencies: vec![],
+            account_name: options.account.as_ref().map(OsString::from),
+            account_password: options.password.as_ref().map(OsString::from),
+        };
+        let access = ServiceAccess::CHANGE_CONFIG | ServiceAccess::START;
+        let service = match manager.open_service(SERVICE_NAME, access) {
+            Ok(_) if !options.force => {
+                bail!(
+                    "the {SERVICE_NAME} service is installed already (use --force to replace its settings)"
+                )
+            }
+            Ok(service) => {
+                service
+                    .change_config(&info)
+                    .context("failed to update the service")?;
+                service
+            }
+            Err(err) if is_missing(&err) => manager
+                .create_service(&info, access)
+                .context("failed to create the service")?,
+            Err(err) => return Err(err).context("failed to open the service"),
+        };
+        service.set_description(
+            "Commits and syncs an Obsidian vault with its git remote, from boot on.",
+        )?;
+        // Starting once the network is up saves a round of retries.
+        service.set_delayed_auto_start(true)?;
+        // Restart after the daemon fails, backing off, and forget failures
+        // after a day.
+        let restart = |seconds| ServiceAction {
+            action_type: ServiceActionType::Restart,
+            delay: Duration::from_secs(seconds),
+        };
+        service.update_failure_actions(ServiceFailureActions {
+            reset_period: ServiceFailureResetPeriod::After(Duration::from_secs(24 * 60 * 60)),
+            reboot_msg: None,
+            command: None,
+            actions: Some(vec![restart(60), restart(300), restart(900)]),
+        })?;
+        service.set_failure_actions_on_non_crash_failures(true)?;
+        info!(config = %options.config_path, "installed the Windows service");
+        Ok(())
+    }
+
+    pub fn uninstall() -> Result<()> {
+        let manager = manager(ServiceManagerAccess::CONNECT)?;
+        let service = manager
+            .open_service(
+                SERVICE_NAME,
+                ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+            )
+            .context("failed to open the service; is it installed?")?;
+        // Marked for deletion, the service goes away once it has stopped.
+        service.delete().context("failed to delete the service")?;
+        if service.query_status()?.current_state != ServiceState::Stopped {
+            service.stop().context("failed to stop the service")?;
+        }
+        Ok(())
+    }
+
+    pub fn start() -> Result<()> {
+        let manager = manager(ServiceManagerAccess::CONNECT)?;
+        let service = manager
+            .open_service(SERVICE_NAME, ServiceAccess::START)
+            .context("failed to open the service; is it installed?")?;
+        service
+            .start::<&OsStr>(&[])
+            .context("failed to start the service")
+    }
+
+    /// Stops the service and waits until the daemon has exited.
+    pub fn stop() -> Result<()> {
+        let manager = manager(ServiceManagerAccess::CONNECT)?;
+        let service = manager
+            .open_service(
+                SERVICE_NAME,
+                ServiceAccess::QUERY_STATUS | ServiceAccess::STOP,
+            )
+            .context("failed to open the service; is it installed?")?;
+        if service.query_status()?.current_state == ServiceState::Stopped {
+            return Ok(());
+        }
+        service.stop().context("failed to stop the service")?;
+        let started = Instant::now();
+        while service.query_status()?.current_state != ServiceState::Stopped {
+            if started.elapsed() > STOP_TIMEOUT {
+                bail!(
+                    "the service did not stop within {} seconds",
+                    STOP_TIMEOUT.as_secs()
+                );
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+        Ok(())
+    }
+
+    /// The service's state, or `None` when it is not installed.
+    pub fn status() -> Result<Option<String>> {
+        let manager = manager(ServiceManagerAccess::CONNECT)?;
+        let service = match manager.open_service(SERVICE_NAME, ServiceAccess::QUERY_STATUS) {
+            Ok(service) => service,
+            Err(err) if is_missing(&err) => return Ok(None),
+            Err(err) => return Err(err).context("failed to open the service"),
+        };
+        let state = match service.query_status()?.current_state {
+            ServiceState::Stopped => "stopped",
+            ServiceState::StartPending => "starting",
+            ServiceState::StopPending => "stopping",
+            ServiceState::Running => "running",
+            ServiceState::ContinuePending | ServiceState::PausePending | ServiceState::Paused => {
+                "paused"
+            }
+        };
+        Ok(Some(state.to_string()))
+    }
+
+    /// Hands `daemon` to the service control manager, which runs it on its
+    /// own thread. Returns once the service has stopped.
+    pub fn run(daemon: impl FnOnce(Shutdown) -> Result<()> + Send + 'static) -> Result<()> {
+        *DAEMON.lock().unwrap() = Some(Box::new(daemon));
+        service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+            .context("failed to reach the service control manager; `service run` is started by Windows, use `obsyncgit run` from a prompt")
+    }
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    fn service_main(_arguments: Vec<OsString>) {
+        if let Err(err) = run_service() {
+            error!(?err, "the Windows service failed");
+        }
+    }
+
+    fn run_service() -> Result<()> {
+        let shutdown = Shutdown::new();
+        let handler_shutdown = shutdown.clone();
+        let status =
+            service_control_handler::register(SERVICE_NAME, move |control| match control {
+                ServiceControl::Stop | ServiceControl::Shutdown => {
+                    handler_shutdown.trigger();
+                    ServiceControlHandlerResult::NoError
+                }
+                ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+                _ => ServiceControlHandlerResult::NotImplemented,
+            })?;
+        let report = |state, exit_code: ServiceExitCode| {
+            status.set_service_status(ServiceStatus {
+                service_type: SERVICE_TYPE,
+                current_state: state,
+                controls_accepted: if state == ServiceState::Running {
+                    ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+                } else {
+                    ServiceControlAccept::empty()
+                },
+                exit_code,
+                checkpoint: 0,
+                wait_hint: Duration::default(),
+                process_id: None,
+            })
+        };
+        report(ServiceState::Running, ServiceExitCode::NO_ERROR)?;
+        let daemon = DAEMON
+            .lock()
+            .unwrap()
+            .take()
+            .context("the service was started twice")?;
+        let result = daemon(shutdown);
+        if let Err(err) = &result {
+            error!(?err, "the daemon stopped with an error");
+        }
+        // A non-zero exit code counts as a failure, which the failure
+        // actions set up by `install` answer with a restart.
+        let exit_code = match &result {
+            Ok(()) => ServiceExitCode::NO_ERROR,
+            Err(_) => ServiceExitCode::ServiceSpecific(1),
+        };
+        report(ServiceState::Stopped, exit_code)?;
+        result
+    }
+}
+
+#[cfg(not(windows))]
+fn unsupported() -> anyhow::Error {
+    anyhow::anyhow!(
+        "Windows services exist only on Windows; on Linux start the daemon with the systemd user unit in examples/"
+    )
+}
+
+#[cfg(not(windows))]
+pub fn install(_options: &InstallOptions) -> Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(target_os = "macos")]
+pub use crate::launchd::{start, status, stop, uninstall};
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn uninstall() -> Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn start() -> Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn stop() -> Result<()> {
+    Err(unsupported())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn status() -> Result<Option<String>> {
+    Err(unsupported())
+}
+
+#[cfg(not(windows))]
+pub fn run(_daemon: impl FnOnce(Shutdown) -> Result<()> + Send + 'static) -> Result<()> {
+    Err(unsupported())
+}
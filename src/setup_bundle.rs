@@ -0,0 +1,308 @@
+use std::fs;
+
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use camino::{Utf8Path, Utf8PathBuf};
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::host_keys::SshHost;
+use crate::secrets;
+
+/// Format version written into every bundle.
+pub const BUNDLE_VERSION: u32 = 1;
+/// Prefix of the single-line form of a bundle printed by `export-setup --code`.
+pub const CODE_PREFIX: &str = "ogx1:";
+
+const HEADER: &str = "# obsyncgit setup bundle: import with `obsyncgit import-setup <file>`\n";
+
+/// Settings that only make sense on the device they were written on. They
+/// are left out of a bundle and take their defaults on the new device.
+const DEVICE_KEYS: &[&[&str]] = &[
+    &["workdir"],
+    &["state_dir"],
+    &["sync", "device_name"],
+    &["git", "executable"],
+    &["git", "ssh_key_path"],
+    &["git", "separate_git_dir"],
+    &["replicas"],
+];
+
+/// Settings holding a secret. A literal value is left out of a bundle;
+/// `!keyring:`/`!env:` references travel as they are.
+const SECRET_KEYS: &[&[&str]] = &[
+    &["sync", "pull_request", "token"],
+    &["remote", "s3", "access_key_id"],
+    &["remote", "s3", "secret_access_key"],
+    &["remote", "webdav", "password"],
+    &["mqtt", "password"],
+    &["alerts", "email", "password"],
+    &["alerts", "telegram", "bot_token"],
+    &["alerts", "discord", "webhook_url"],
+    &["digest", "webhook_url"],
+];
+
+/// A configuration prepared for setting up another device (`.ogx` file).
+/// Paths under the home directory are stored as `~/…` so they land in the
+/// same place for a different user name, device-specific settings are
+/// dropped and secrets stay `!keyring:`/`!env:` references: a bundle never
+/// contains a resolved secret, and [`SECRET_KEYS`] written out in the
+/// config are left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetupBundle {
+    pub version: u32,
+    /// Vault folder on the exporting device, `~/`-relative where possible.
+    pub workdir: String,
+    /// SSH key the exporting device used, `~/`-relative where possible.
+    /// The new device gets the same path; see [`ssh_instructions`] for
+    /// creating a key there.
+    #[serde(default)]
+    pub ssh_key_path: Option<String>,
+    /// Keyring entries the configuration refers to; they have to be stored
+    /// again on the new device with `obsyncgit settings set-secret`.
+    #[serde(default)]
+    pub keyring_secrets: Vec<String>,
+    /// The configuration without [`DEVICE_KEYS`] and settings left at their
+    /// defaults.
+    pub config: serde_yaml::Value,
+}
+
+/// What [`SetupBundle::export`] had to leave out.
+#[derive(Debug, Default)]
+pub struct ExportNotes {
+    /// Config keys whose URL carried a plaintext user name or password.
+    pub stripped_credentials: Vec<&'static str>,
+    /// Secret settings left out because they held the secret itself
+    /// rather than a reference, e.g. `sync.pull_request.token`.
+    pub stripped_secrets: Vec<String>,
+}
+
+impl SetupBundle {
+    /// Builds a bundle from the configuration file as written, i.e. before
+    /// [`Config::resolve_secrets`].
+    pub fn export(config: &Config) -> Result<(Self, ExportNotes)> {
+        let mut config = config.clone();
+        let mut notes = ExportNotes::default();
+        if let Some(url) = strip_credentials(&config.repo_url) {
+            config.repo_url = url;
+            notes.stripped_credentials.push("repo_url");
+        }
+        if let Some(proxy) = &mut config.network.proxy
+            && let Some(url) = strip_credentials(&proxy.url)
+        {
+            proxy.url = url;
+            notes.stripped_credentials.push("network.proxy.url");
+        }
+
+        let mut value = serde_yaml::to_value(&config).context("failed to render configuration")?;
+        let mut keyring_secrets = Vec::new();
+        collect_keyring_names(&value, &mut keyring_secrets);
+        keyring_secrets.sort();
+        keyring_secrets.dedup();
+        for key in DEVICE_KEYS {
+            remove_key(&mut value, key);
+        }
+        for key in SECRET_KEYS {
+            if lookup(&value, key)
+                .and_then(serde_yaml::Value::as_str)
+                .is_some_and(|secret| !secrets::is_reference(secret.trim()))
+            {
+                remove_key(&mut value, key);
+                notes.stripped_secrets.push(key.join("."));
+            }
+        }
+        // Only settings that differ from the defaults, which keeps the code short.
+        let defaults = Config::parse("repo_url: ''\nworkdir: ''\n")?;
+        let defaults = serde_yaml::to_value(&defaults).context("failed to render configuration")?;
+        prune_defaults(&mut value, &defaults);
+
+        let bundle = Self {
+            version: BUNDLE_VERSION,
+            workdir: home_relative(config.workdir.as_str()),
+            ssh_key_path: config.git.ssh_key_path.as_deref().map(home_relative),
+            keyring_secrets,
+            config: value,
+        };
+        Ok((bundle, notes))
+    }
+
+    /// Turns the bundle into a configuration for this device. The vault goes
+    /// to `workdir` when given, otherwise to where it was on the exporting
+    /// device, relative to this device's home directory.
+    pub fn into_config(self, workdir: Option<Utf8PathBuf>) -> Result<Config> {
+        let mut value = self.config;
+        let workdir = match workdir {
+            Some(dir) => dir,
+            None => expand_home(&self.workdir)?,
+        };
+        let serde_yaml::Value::Mapping(map) = &mut value else {
+            bail!("setup bundle does not contain a configuration");
+        };
+        map.insert("workdir".into(), workdir.as_str().into());
+        if let Some(key) = &self.ssh_key_path {
+            let key = expand_home(key)?;
+            let git = map
+                .entry("git".into())
+                .or_insert_with(|| serde_yaml::Mapping::new().into());
+            if let serde_yaml::Value::Mapping(git) = git {
+                git.insert("ssh_key_path".into(), key.as_str().into());
+            }
+        }
+        let contents = serde_yaml::to_string(&value).context("failed to render configuration")?;
+        Config::parse(&contents).context("setup bundle contains an invalid configuration")
+    }
+
+    /// Renders the bundle as the contents of a `.ogx` file.
+    pub fn to_file_contents(&self) -> Result<String> {
+        let body = serde_yaml::to_string(self).context("failed to render setup bundle")?;
+        Ok(format!("{HEADER}{body}"))
+    }
+
+    /// The bundle as one line of text, for pasting into a chat or a QR code
+    /// generator on the way to the new device. Needs the same secrets care
+    /// as the file: it is the whole bundle, merely encoded.
+    pub fn to_code(&self) -> Result<String> {
+        let body = serde_yaml::to_string(self).context("failed to render setup bundle")?;
+        Ok(format!("{CODE_PREFIX}{}", URL_SAFE_NO_PAD.encode(body)))
+    }
+
+    /// Reads a bundle from a `.ogx` file or from a code printed by
+    /// [`SetupBundle::to_code`].
+    pub fn read(source: &str) -> Result<Self> {
+        let contents = match source.trim().strip_prefix(CODE_PREFIX) {
+            Some(code) => {
+                let bytes = URL_SAFE_NO_PAD
+                    .decode(code)
+                    .context("setup code is damaged; copy it again")?;
+                String::from_utf8(bytes).context("setup code is damaged; copy it again")?
+            }
+            None => fs::read_to_string(source)
+                .with_context(|| format!("failed to read setup bundle {source}"))?,
+        };
+        let bundle: Self =
+            serde_yaml::from_str(&contents).context("file is not an obsyncgit setup bundle")?;
+        if bundle.version > BUNDLE_VERSION {
+            bail!(
+                "setup bundle version {} is newer than this obsyncgit supports; update obsyncgit first",
+                bundle.version
+            );
+        }
+        Ok(bundle)
+    }
+}
+
+/// Steps for giving this device SSH access to the vault remote, when the
+/// remote is reached over SSH and the configured key does not exist yet.
+/// Keys are per device so one can be revoked without locking out the others.
+pub fn ssh_instructions(config: &Config) -> Option<String> {
+    let host = SshHost::parse(&config.repo_url)?.host;
+    let key = match &config.git.ssh_key_path {
+        Some(path) if Utf8Path::new(path).exists() => return None,
+        Some(path) => path.clone(),
+        None => "~/.ssh/obsyncgit_ed25519".to_string(),
+    };
+    let device = config.sync.device_name();
+    let mut steps = format!(
+        "This vault syncs over SSH. Create a key for this device and allow it on {host}:\n\n    \
+         ssh-keygen -t ed25519 -f {key} -C \"obsyncgit@{device}\"\n\n\
+         then add the contents of {key}.pub as a deploy key with write access, or to your account's SSH keys."
+    );
+    if config.git.ssh_key_path.is_none() {
+        steps.push_str(&format!(
+            "\nIf ssh does not pick the key up by default, run `obsyncgit settings set git.ssh-key-path {key}`."
+        ));
+    }
+    Some(steps)
+}
+
+/// Removes the `user:password@` part of a URL, returning the cleaned URL if
+/// there was one. SSH-style `git@host:path` remotes are left alone: the user
+/// name there is not a secret.
+fn strip_credentials(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(authority_end);
+    let (userinfo, host) = authority.rsplit_once('@')?;
+    if secrets::is_reference(userinfo) || (scheme == "ssh" && !userinfo.contains(':')) {
+        return None;
+    }
+    Some(format!("{scheme}://{host}{path}"))
+}
+
+fn collect_keyring_names(value: &serde_yaml::Value, names: &mut Vec<String>) {
+    use serde_yaml::Value;
+
+    match value {
+        Value::String(text) => {
+            if let Some(name) = text.strip_prefix(secrets::KEYRING_PREFIX) {
+                names.push(name.trim().to_string());
+            }
+        }
+        Value::Sequence(items) => items
+            .iter()
+            .for_each(|item| collect_keyring_names(item, names)),
+        Value::Mapping(map) => map
+            .values()
+            .for_each(|item| collect_keyring_names(item, names)),
+        _ => {}
+    }
+}
+
+fn lookup<'a>(value: &'a serde_yaml::Value, path: &[&str]) -> Option<&'a serde_yaml::Value> {
+    path.iter()
+        .try_fold(value, |current, key| current.get(*key))
+}
+
+fn remove_key(value: &mut serde_yaml::Value, path: &[&str]) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut current = value;
+    for key in parents {
+        match current.get_mut(*key) {
+            Some(next) => current = next,
+            None => return,
+        }
+    }
+    if let serde_yaml::Value::Mapping(map) = current {
+        map.remove(*last);
+    }
+}
+
+fn prune_defaults(value: &mut serde_yaml::Value, defaults: &serde_yaml::Value) {
+    let (serde_yaml::Value::Mapping(map), serde_yaml::Value::Mapping(defaults)) = (value, defaults)
+    else {
+        return;
+    };
+    map.retain(|key, item| match defaults.get(key) {
+        Some(default) => {
+            prune_defaults(item, default);
+            item != default && !matches!(item, serde_yaml::Value::Mapping(map) if map.is_empty())
+        }
+        None => true,
+    });
+}
+
+fn home_relative(path: &str) -> String {
+    let Some(dirs) = BaseDirs::new() else {
+        return path.to_string();
+    };
+    let Some(home) = Utf8Path::from_path(dirs.home_dir()) else {
+        return path.to_string();
+    };
+    match Utf8Path::new(path).strip_prefix(home) {
+        Ok(rest) => format!("~/{rest}"),
+        Err(_) => path.to_string(),
+    }
+}
+
+fn expand_home(path: &str) -> Result<Utf8PathBuf> {
+    let Some(rest) = path.strip_prefix("~/") else {
+        return Ok(Utf8PathBuf::from(path));
+    };
+    let dirs = BaseDirs::new().context("cannot determine the home directory")?;
+    let home = Utf8Path::from_path(dirs.home_dir()).context("home directory is not valid UTF-8")?;
+    Ok(home.join(rest))
+}
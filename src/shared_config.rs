@@ -0,0 +1,109 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    CommitConfig, Config, DeletionConfig, IgnoreConfig, RemoteAvailabilityConfig, ScheduleConfig,
+    StabilityConfig,
+};
+
+/// Location of the shared policy file, relative to the vault root.
+pub const SHARED_CONFIG_PATH: &str = ".obsyncgit/config.shared.yaml";
+
+/// The device-independent part of the configuration that can travel with the
+/// vault. Secrets, identities and machine-specific paths (`repo_url`,
+/// `workdir`, `git`, `self_update`) are deliberately left out.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SharedPolicy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debounce_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll_interval_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<CommitConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore: Option<IgnoreConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stability: Option<StabilityConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_availability: Option<RemoteAvailabilityConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ScheduleConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deletions: Option<DeletionConfig>,
+}
+
+impl SharedPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            debounce_seconds: Some(config.debounce_seconds),
+            poll_interval_seconds: Some(config.poll_interval_seconds),
+            commit: Some(config.commit.clone()),
+            ignore: Some(config.ignore.clone()),
+            stability: Some(config.stability.clone()),
+            remote_availability: Some(config.remote_availability.clone()),
+            schedule: Some(config.schedule.clone()),
+            deletions: Some(config.deletions.clone()),
+        }
+    }
+
+    /// Overlays every section present in the shared file onto `config`.
+    pub fn apply_to(&self, config: &mut Config) {
+        if let Some(value) = self.debounce_seconds {
+            config.debounce_seconds = value;
+        }
+        if let Some(value) = self.poll_interval_seconds {
+            config.poll_interval_seconds = value;
+        }
+        if let Some(commit) = &self.commit {
+            config.commit = commit.clone();
+        }
+        if let Some(ignore) = &self.ignore {
+            config.ignore = ignore.clone();
+        }
+        if let Some(stability) = &self.stability {
+            config.stability = stability.clone();
+        }
+        if let Some(remote_availability) = &self.remote_availability {
+            config.remote_availability = remote_availability.clone();
+        }
+        if let Some(schedule) = &self.schedule {
+            config.schedule = schedule.clone();
+        }
+        if let Some(deletions) = &self.deletions {
+            config.deletions = deletions.clone();
+        }
+    }
+
+    pub fn path_in(workdir: &Utf8Path) -> Utf8PathBuf {
+        workdir.join(SHARED_CONFIG_PATH)
+    }
+
+    pub fn load(workdir: &Utf8Path) -> Result<Option<Self>> {
+        let path = Self::path_in(workdir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read shared config at {path}"))?;
+        let policy = serde_yaml::from_str(&contents)
+            .with_context(|| format!("failed to parse shared config at {path}"))?;
+        Ok(Some(policy))
+    }
+
+    pub fn save(&self, workdir: &Utf8Path) -> Result<Utf8PathBuf> {
+        let path = Self::path_in(workdir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directories for {parent}"))?;
+        }
+        let serialized =
+            serde_yaml::to_string(self).context("failed to render shared config to YAML")?;
+        fs::write(&path, serialized)
+            .with_context(|| format!("failed to write shared config to {path}"))?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,125 @@
+//! `shared_vault`: a vault several people share, such as a family's or a
+//! small team's. Every device commits under its own member's name, so the
+//! history shows who wrote what; new notes a member drops at the top of the
+//! vault are moved into that member's folder; and `protected_paths`, such
+//! as a folder one person curates, are pulled on every device but only
+//! committed where they are not protected.
+
+use std::fs;
+
+use anyhow::{Context, Result, bail};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::config::{Config, SharedVaultConfig};
+use crate::git::GitFacade;
+
+/// `shared_vault` as the daemon applies it.
+#[derive(Debug)]
+pub struct SharedVault {
+    folder: Option<String>,
+    protected: GlobSet,
+}
+
+impl SharedVault {
+    /// `None` unless `shared_vault.enabled` is set.
+    pub fn new(config: &SharedVaultConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let folder = config
+            .user_folder
+            .as_deref()
+            .map(|folder| folder.trim_matches('/').to_string())
+            .filter(|folder| !folder.is_empty());
+        if let Some(folder) = &folder
+            && folder.split('/').any(|part| part == ".." || part == ".git")
+        {
+            bail!("shared_vault.user_folder '{folder}' must be a folder inside the vault");
+        }
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &config.protected_paths {
+            builder.add(
+                GlobBuilder::new(pattern.trim_start_matches('/'))
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| {
+                        format!("invalid shared_vault.protected_paths pattern '{pattern}'")
+                    })?,
+            );
+        }
+        let protected = builder
+            .build()
+            .context("failed to compile shared_vault.protected_paths")?;
+        Ok(Some(Self { folder, protected }))
+    }
+
+    /// This member's folder, if one is set.
+    pub fn folder(&self) -> Option<&str> {
+        self.folder.as_deref()
+    }
+
+    /// The paths among `paths` this device does not commit.
+    pub fn protected(&self, paths: &[String]) -> Vec<String> {
+        paths
+            .iter()
+            .filter(|path| self.protected.is_match(path.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// Files among `untracked` that sit at the top of the vault and belong
+    /// in the member's folder. Dot files, such as `.gitignore`, stay.
+    pub fn loose_notes(&self, untracked: &[String]) -> Vec<String> {
+        if self.folder.is_none() {
+            return Vec::new();
+        }
+        untracked
+            .iter()
+            .filter(|path| !path.contains('/') && !path.starts_with('.'))
+            .filter(|path| !self.protected.is_match(path.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// Moves `paths` into the member's folder. A file whose name is taken
+    /// there stays where it is. Returns each file moved with its new path.
+    pub fn adopt(&self, config: &Config, paths: &[String]) -> Result<Vec<(String, String)>> {
+        let Some(folder) = &self.folder else {
+            return Ok(Vec::new());
+        };
+        let root = config.workdir.as_std_path();
+        let mut moved = Vec::new();
+        for path in paths {
+            let target = format!("{folder}/{path}");
+            let destination = root.join(&target);
+            if destination.exists() {
+                continue;
+            }
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create {}", parent.display()))?;
+            }
+            fs::rename(root.join(path), &destination)
+                .with_context(|| format!("failed to move {path} to {target}"))?;
+            moved.push((path.clone(), target));
+        }
+        Ok(moved)
+    }
+}
+
+/// The name and email this device commits under: `git.author_name` and
+/// `git.author_email`, or else git's own `user.name` and `user.email`.
+/// `None` when either is missing.
+pub fn identity(git: &GitFacade, config: &Config) -> Option<(String, String)> {
+    let name = config
+        .git
+        .author_name
+        .clone()
+        .or_else(|| git.config_value("user.name"))?;
+    let email = config
+        .git
+        .author_email
+        .clone()
+        .or_else(|| git.config_value("user.email"))?;
+    Some((name, email))
+}
@@ -0,0 +1,107 @@
+//! `snapshots.tag_daily`: a lightweight `snapshot/<date>` tag on the last
+//! commit of each day, pushed to the remote, so the vault can be looked at
+//! or restored as it was at the end of a calendar day. Days are local to
+//! the device that tags them, and a day without commits gets no tag.
+
+use anyhow::{Context, Result, bail};
+use chrono::{Days, NaiveDate};
+use tracing::info;
+
+use crate::git::GitFacade;
+
+pub const TAG_PREFIX: &str = "snapshot/";
+/// Days tagged at most in one go, e.g. for a daemon stopped over a long
+/// holiday.
+const MAX_CATCH_UP_DAYS: u64 = 31;
+
+/// A day's tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub day: NaiveDate,
+    pub commit: String,
+    /// Committer date of the tagged commit, strict ISO 8601.
+    pub committed: String,
+}
+
+impl Snapshot {
+    pub fn tag(&self) -> String {
+        tag_name(self.day)
+    }
+}
+
+pub fn tag_name(day: NaiveDate) -> String {
+    format!("{TAG_PREFIX}{day}")
+}
+
+/// Reads `2025-01-15` or `snapshot/2025-01-15`.
+pub fn parse_day(value: &str) -> Result<NaiveDate> {
+    let value = value.trim();
+    NaiveDate::parse_from_str(value.strip_prefix(TAG_PREFIX).unwrap_or(value), "%Y-%m-%d")
+        .with_context(|| format!("invalid snapshot day '{value}' (expected YYYY-MM-DD)"))
+}
+
+/// The day tags in the local repository, newest first.
+pub fn list(git: &GitFacade) -> Result<Vec<Snapshot>> {
+    let mut snapshots: Vec<Snapshot> = git
+        .list_tags(TAG_PREFIX)?
+        .into_iter()
+        .filter_map(|(name, commit, committed)| {
+            Some(Snapshot {
+                day: NaiveDate::parse_from_str(name.strip_prefix(TAG_PREFIX)?, "%Y-%m-%d").ok()?,
+                commit,
+                committed,
+            })
+        })
+        .collect();
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.day));
+    Ok(snapshots)
+}
+
+/// The commit tagged for `day`.
+pub fn commit(git: &GitFacade, day: NaiveDate) -> Result<String> {
+    match list(git)?.into_iter().find(|snapshot| snapshot.day == day) {
+        Some(snapshot) => Ok(snapshot.commit),
+        None => {
+            bail!("there is no snapshot of {day}; `obsyncgit snapshots list` shows the days tagged")
+        }
+    }
+}
+
+/// Tags every day that ended since the newest snapshot, up to yesterday,
+/// and pushes the tags. Tags other devices pushed are fetched first, so a
+/// day is tagged once. Returns the tags created.
+pub fn tag_finished_days(git: &GitFacade, today: NaiveDate) -> Result<Vec<String>> {
+    git.fetch_tags(TAG_PREFIX)?;
+    let Some(yesterday) = today.pred_opt() else {
+        return Ok(Vec::new());
+    };
+    let earliest = today
+        .checked_sub_days(Days::new(MAX_CATCH_UP_DAYS))
+        .unwrap_or(yesterday);
+    let mut day = match list(git)?.first() {
+        Some(newest) => newest.day.succ_opt().unwrap_or(today).max(earliest),
+        // Tagging starts with the day it was turned on; older history has
+        // no snapshots.
+        None => yesterday,
+    };
+    let mut created = Vec::new();
+    while day <= yesterday {
+        let Some(next) = day.succ_opt() else { break };
+        if let Some(commit) =
+            git.last_commit_between(&format!("{day} 00:00:00"), &format!("{next} 00:00:00"))?
+        {
+            let tag = tag_name(day);
+            git.create_tag(&tag, &commit)?;
+            if let Err(err) = git.push_tag(&tag) {
+                // Tagged again on the next try, unless another device's tag
+                // for the day arrives first.
+                let _ = git.delete_tag(&tag);
+                return Err(err.context(format!("failed to push {tag}")));
+            }
+            info!(%tag, %commit, "tagged the day's last commit");
+            created.push(tag);
+        }
+        day = next;
+    }
+    Ok(created)
+}
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::config::StabilityConfig;
+
+/// Decides whether a changed path has finished being written and can be
+/// staged safely. Editors such as Obsidian or pandoc write through temporary
+/// files and renames, so a path observed mid-write may hold partial content.
+#[derive(Debug, Clone)]
+pub struct WriteStability {
+    settle: Duration,
+    detect_open_writers: bool,
+}
+
+impl WriteStability {
+    pub fn new(config: &StabilityConfig) -> Self {
+        Self {
+            settle: Duration::from_millis(config.settle_millis),
+            detect_open_writers: config.detect_open_writers,
+        }
+    }
+
+    /// Returns the subset of `files` (relative to `root`) that are still being
+    /// written: modified within the settle window or held open for writing.
+    pub fn unstable_paths(&self, root: &Path, files: &[String]) -> Vec<String> {
+        if files.is_empty() {
+            return Vec::new();
+        }
+        let open_writers = if self.detect_open_writers {
+            open_for_writing()
+        } else {
+            HashSet::new()
+        };
+        // Descriptor targets are reported as resolved paths.
+        let resolved_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+        files
+            .iter()
+            .filter(|file| {
+                let path = root.join(file);
+                let unstable = self.recently_modified(&path)
+                    || open_writers.contains(&resolved_root.join(file));
+                if unstable {
+                    debug!(path = %file, "deferring path that is still being written");
+                }
+                unstable
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn recently_modified(&self, path: &Path) -> bool {
+        if self.settle.is_zero() {
+            return false;
+        }
+        // Deleted paths have nothing left to settle.
+        let Ok(metadata) = std::fs::symlink_metadata(path) else {
+            return false;
+        };
+        match metadata.modified().map(|modified| modified.elapsed()) {
+            Ok(Ok(age)) => age < self.settle,
+            // A timestamp in the future means it was written just now.
+            Ok(Err(_)) => true,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Paths currently open with write access by any process we can inspect.
+#[cfg(target_os = "linux")]
+fn open_for_writing() -> HashSet<PathBuf> {
+    const O_ACCMODE: u32 = 0o3;
+
+    let mut paths = HashSet::new();
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return paths;
+    };
+    for proc_entry in procs.flatten() {
+        let pid_dir = proc_entry.path();
+        let Ok(fds) = std::fs::read_dir(pid_dir.join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = std::fs::read_link(fd.path()) else {
+                continue;
+            };
+            if !target.is_absolute() {
+                continue;
+            }
+            let fdinfo = pid_dir.join("fdinfo").join(fd.file_name());
+            let Ok(info) = std::fs::read_to_string(fdinfo) else {
+                continue;
+            };
+            let writable = info
+                .lines()
+                .find_map(|line| line.strip_prefix("flags:"))
+                .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok())
+                .is_some_and(|flags| flags & O_ACCMODE != 0);
+            if writable {
+                paths.insert(target);
+            }
+        }
+    }
+    paths
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_for_writing() -> HashSet<PathBuf> {
+    HashSet::new()
+}
@@ -0,0 +1,218 @@
+use std::time::SystemTime;
+
+use anyhow::Result;
+use serde_json::json;
+
+use crate::config::Config;
+use crate::conflicts;
+use crate::control::ControlInbox;
+use crate::git::GitFacade;
+use crate::git_error::GitError;
+use crate::instance;
+use crate::integrity;
+use crate::tr;
+
+/// Overall state of a vault, worst first. The names double as the CSS
+/// class for Waybar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// The repository could not be read or is damaged, or syncing fails in
+    /// a way only the user can fix.
+    Error,
+    /// Notes changed on two devices are waiting for a choice.
+    Conflict,
+    /// No daemon syncs the vault.
+    Stopped,
+    /// The daemon was paused from the tray menu.
+    Paused,
+    /// Edits or commits have not reached the remote yet.
+    Pending,
+    Synced,
+}
+
+impl Health {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Conflict => "conflict",
+            Self::Stopped => "stopped",
+            Self::Paused => "paused",
+            Self::Pending => "pending",
+            Self::Synced => "synced",
+        }
+    }
+}
+
+/// What a desktop bar shows for the vault, read without disturbing a
+/// running daemon.
+#[derive(Debug, Clone)]
+pub struct SyncHealth {
+    pub health: Health,
+    pub vault: String,
+    pub branch: String,
+    pub daemon: Option<u32>,
+    pub paused_minutes: Option<u64>,
+    pub pending: usize,
+    pub unpushed: bool,
+    pub conflicts: usize,
+    pub error: Option<String>,
+    /// Why the daemon's last cycle failed, until one succeeds.
+    pub failure: Option<GitError>,
+    /// Problems the last `git fsck` found.
+    pub damage: usize,
+    /// Files `security.secret_scan` holds back from commits.
+    pub held_secrets: usize,
+}
+
+impl SyncHealth {
+    /// Never fails: a bar polls this every few seconds and should show the
+    /// problem rather than go blank.
+    pub fn read(config: &Config) -> Self {
+        let daemon = instance::owner(config).ok().flatten();
+        let inbox = ControlInbox::new(config).ok();
+        let paused_minutes = inbox
+            .as_ref()
+            .and_then(|inbox| inbox.paused_until())
+            .map(|until| {
+                until
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default()
+                    .as_secs()
+                    .div_ceil(60)
+            });
+        let mut health = Self {
+            health: Health::Synced,
+            vault: config.workdir.to_string(),
+            branch: String::new(),
+            daemon,
+            paused_minutes,
+            pending: 0,
+            unpushed: false,
+            conflicts: 0,
+            error: None,
+            failure: inbox.as_ref().and_then(|inbox| inbox.last_failure()),
+            held_secrets: inbox.map_or(0, |inbox| inbox.held_secrets().len()),
+            damage: integrity::load(config).map_or(0, |report| report.problems.len()),
+        };
+        if let Err(err) = health.read_repository(config) {
+            health.error = Some(format!("{err:#}"));
+        }
+        health.health = health.classify();
+        health
+    }
+
+    fn read_repository(&mut self, config: &Config) -> Result<()> {
+        let git = GitFacade::observer(config)?;
+        self.branch = git.branch_status()?;
+        self.pending = git.list_pending_paths()?.len();
+        self.unpushed = git.has_unpushed_commits()?;
+        self.conflicts = git
+            .merge_conflicts(&conflicts::upstream(config))
+            .map(|paths| paths.len())
+            .unwrap_or(0);
+        Ok(())
+    }
+
+    fn classify(&self) -> Health {
+        let lasting = self
+            .failure
+            .as_ref()
+            .is_some_and(|failure| !failure.is_transient());
+        if self.error.is_some() || self.damage > 0 || (lasting && self.daemon.is_some()) {
+            Health::Error
+        } else if self.conflicts > 0 {
+            Health::Conflict
+        } else if self.daemon.is_none() {
+            Health::Stopped
+        } else if self.paused_minutes.is_some() {
+            Health::Paused
+        } else if self.pending > 0 || self.unpushed {
+            Health::Pending
+        } else {
+            Health::Synced
+        }
+    }
+
+    /// One or two words for the bar itself.
+    pub fn short_text(&self) -> String {
+        match self.health {
+            Health::Error => tr!("bar-error"),
+            Health::Conflict => tr!("bar-conflict", count = self.conflicts),
+            Health::Stopped => tr!("bar-stopped"),
+            Health::Paused => tr!("bar-paused"),
+            Health::Pending if self.pending > 0 => tr!("bar-pending", count = self.pending),
+            Health::Pending => tr!("bar-unpushed"),
+            Health::Synced => tr!("bar-synced"),
+        }
+    }
+
+    /// The details, one per line.
+    pub fn details(&self) -> Vec<String> {
+        let mut lines = vec![tr!("bar-vault", path = self.vault.as_str())];
+        if let Some(error) = &self.error {
+            lines.push(error.clone());
+            return lines;
+        }
+        lines.push(tr!("bar-branch", branch = self.branch.as_str()));
+        lines.push(match self.daemon {
+            Some(pid) => tr!("bar-daemon-running", pid = pid),
+            None => tr!("bar-daemon-stopped"),
+        });
+        if let Some(minutes) = self.paused_minutes {
+            lines.push(tr!("bar-paused-for", minutes = minutes));
+        }
+        lines.push(tr!("bar-pending-count", count = self.pending));
+        if self.damage > 0 {
+            lines.push(tr!("bar-damaged", count = self.damage));
+        }
+        if let Some(failure) = &self.failure {
+            lines.push(tr!("bar-last-error", cause = failure.cause()));
+        }
+        if self.held_secrets > 0 {
+            lines.push(tr!("bar-held-secrets", count = self.held_secrets));
+        }
+        if self.conflicts > 0 {
+            lines.push(tr!("bar-conflict-hint", count = self.conflicts));
+        }
+        lines
+    }
+
+    /// A line of JSON for a Waybar `custom` module with `return-type: json`.
+    /// `alt` carries the state too, for `format-icons`.
+    pub fn waybar(&self) -> String {
+        json!({
+            "text": self.short_text(),
+            "alt": self.health.as_str(),
+            "tooltip": self.details().join("\n"),
+            "class": self.health.as_str(),
+        })
+        .to_string()
+    }
+
+    /// Output for an xbar or SwiftBar plugin: the title, then the menu.
+    pub fn xbar(&self) -> String {
+        let colour = match self.health {
+            Health::Error | Health::Conflict => Some("red"),
+            Health::Stopped | Health::Paused => Some("orange"),
+            Health::Pending | Health::Synced => None,
+        };
+        let mut out = match colour {
+            Some(colour) => format!("{} | color={colour}\n", self.short_text()),
+            None => format!("{}\n", self.short_text()),
+        };
+        out.push_str("---\n");
+        for line in self.details() {
+            // `|` starts the parameters in this format.
+            out.push_str(&line.replace('|', "¦"));
+            out.push('\n');
+        }
+        out.push_str("---\n");
+        out.push_str(&format!(
+            "{} | href=file://{}\n",
+            tr!("tray-open-vault"),
+            self.vault
+        ));
+        out.push_str(&format!("{} | refresh=true\n", tr!("bar-refresh")));
+        out
+    }
+}
@@ -0,0 +1,90 @@
+//! `commit.notes`: what the daemon knows about each commit it makes (the
+//! device, how many files, how long after the first edit it was committed)
+//! kept as a git note in `refs/notes/obsyncgit` instead of in the commit
+//! message. Every device merges the others' notes and pushes its own after
+//! a sync, so `obsyncgit history` shows them for the whole vault.
+
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::git::{GitFacade, NotedCommit};
+
+/// The notes ref, below `refs/notes/`.
+pub const NOTES_REF: &str = "obsyncgit";
+
+/// One commit's metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncNote {
+    pub device: String,
+    pub files: usize,
+    /// From the first change the watcher saw to the commit; `None` for a
+    /// commit of changes found without the watcher, such as at startup.
+    pub latency: Option<Duration>,
+    /// The ObsyncGit version that made the commit.
+    pub version: String,
+}
+
+impl SyncNote {
+    pub fn new(device: String, files: usize, latency: Option<Duration>) -> Self {
+        Self {
+            device,
+            files,
+            latency,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// `key: value` lines, like commit trailers.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("device: {}\nfiles: {}\n", self.device, self.files);
+        if let Some(latency) = self.latency {
+            text.push_str(&format!("latency-ms: {}\n", latency.as_millis()));
+        }
+        text.push_str(&format!("obsyncgit: {}\n", self.version));
+        text
+    }
+
+    /// Reads [`Self::to_text`]; `None` for a note without a device, such
+    /// as one written by hand. Merged notes keep the first value of a key.
+    pub fn parse(text: &str) -> Option<Self> {
+        let field = |key: &str| {
+            text.lines().find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                (name.trim() == key).then(|| value.trim().to_string())
+            })
+        };
+        Some(Self {
+            device: field("device")?,
+            files: field("files").and_then(|files| files.parse().ok())?,
+            latency: field("latency-ms")
+                .and_then(|millis| millis.parse().ok())
+                .map(Duration::from_millis),
+            version: field("obsyncgit").unwrap_or_default(),
+        })
+    }
+}
+
+/// A commit with its metadata, if it has any.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub commit: NotedCommit,
+    pub note: Option<SyncNote>,
+}
+
+/// Records `note` for `commit`.
+pub fn record(git: &GitFacade, commit: &str, note: &SyncNote) -> Result<()> {
+    git.add_note(NOTES_REF, commit, &note.to_text())
+}
+
+/// The newest `limit` commits, or those touching `path`, with their notes.
+pub fn history(git: &GitFacade, limit: usize, path: Option<&str>) -> Result<Vec<Entry>> {
+    Ok(git
+        .log_with_notes(NOTES_REF, limit, path)?
+        .into_iter()
+        .map(|commit| Entry {
+            note: SyncNote::parse(&commit.note),
+            commit,
+        })
+        .collect())
+}
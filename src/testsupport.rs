@@ -0,0 +1,2664 @@
+//! Headless end-to-end checks of the sync cycle against throwaway local
+//! repositories. Used by `obsyncgit selftest`, which `cargo test --features
+//! selftest` runs; nothing here touches the user's configuration or vault.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, RwLock, mpsc};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use chrono::{Local, TimeZone, Utc};
+
+use crate::backup;
+use crate::clock::{Clock, Deadline, MockClock, Moment};
+use crate::config::{
+    CaseCollisionPolicy, CommitTrailers, Config, ConflictCopyPolicy, FileNamePolicy,
+    InsignificantChanges, LineEndings, RemoteKind, ScheduleConfig, UnicodeNormalization,
+    WatcherBackend,
+};
+use crate::conflict_copies;
+use crate::conflicts::{self, Conflict, Resolution};
+use crate::connectivity::{self, Step};
+use crate::control::ControlInbox;
+use crate::daemon::SyncDaemon;
+use crate::digest;
+use crate::events::{Event, EventLog, MAX_BYTES, Record};
+use crate::file_names;
+use crate::git::GitFacade;
+use crate::git_error::GitError;
+use crate::i18n::{self, FluentArgs, Localizer};
+use crate::ignore::IgnoreMatcher;
+use crate::import::{self, OnCollision};
+use crate::integrity;
+use crate::locks;
+use crate::pacing::{CommitTiming, CycleError, GitBackend, Pacer, backoff_delay};
+use crate::retention;
+use crate::schedule::Schedule;
+use crate::setup_bundle::SetupBundle;
+use crate::shutdown::Shutdown;
+use crate::snapshots;
+use crate::sync_notes;
+use crate::watcher::WatcherSupervisor;
+
+const BRANCH: &str = "main";
+
+/// A bare "remote" repository in a temporary directory, removed on drop.
+pub struct Sandbox {
+    root: PathBuf,
+    remote: PathBuf,
+    keep: bool,
+}
+
+impl Sandbox {
+    pub fn new() -> Result<Self> {
+        let sandbox = Self::empty()?;
+        // Devices expect an existing branch to clone.
+        let seed = sandbox.root.join("seed");
+        git(
+            &sandbox.root,
+            &["clone", "--quiet", path_str(&sandbox.remote)?, "seed"],
+        )?;
+        fs::write(seed.join("README.md"), "# selftest vault\n")?;
+        git(&seed, &["add", "README.md"])?;
+        git(&seed, &["commit", "--quiet", "-m", "seed"])?;
+        git(&seed, &["push", "--quiet", "origin", "HEAD:main"])?;
+        Ok(sandbox)
+    }
+
+    /// A remote without any commits, as freshly created on a hosting
+    /// service.
+    pub fn empty() -> Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default();
+        let root =
+            std::env::temp_dir().join(format!("obsyncgit-selftest-{}-{nanos}", std::process::id()));
+        fs::create_dir_all(&root)
+            .with_context(|| format!("failed to create {}", root.display()))?;
+        let sandbox = Self {
+            remote: root.join("remote.git"),
+            root,
+            keep: false,
+        };
+
+        git(&sandbox.root, &["init", "--quiet", "--bare", "remote.git"])?;
+        git(
+            &sandbox.remote,
+            &["symbolic-ref", "HEAD", "refs/heads/main"],
+        )?;
+        Ok(sandbox)
+    }
+
+    /// Clones a new device with default settings tuned for immediate commits.
+    pub fn device(&self, name: &str) -> Result<Device> {
+        self.device_with(name, |_| {})
+    }
+
+    /// Like [`Self::device`], letting the caller adjust the configuration.
+    pub fn device_with(&self, name: &str, configure: impl FnOnce(&mut Config)) -> Result<Device> {
+        let workdir = self.root.join(name);
+        let mut config = Config::parse(&format!(
+            "repo_url: {remote}\nworkdir: {workdir}\nbranch: {BRANCH}\nstate_dir: {state}\n",
+            remote = path_str(&self.remote)?,
+            workdir = path_str(&workdir)?,
+            state = path_str(&self.root.join("state"))?,
+        ))?;
+        config.git.author_name = Some(format!("selftest {name}"));
+        config.git.author_email = Some(format!("{name}@selftest.invalid"));
+        config.stability.settle_millis = 0;
+        config.stability.detect_open_writers = false;
+        configure(&mut config);
+
+        let mut daemon = SyncDaemon::new(config.clone())?;
+        daemon.prepare()?;
+        Ok(Device {
+            workdir,
+            config,
+            daemon,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Leaves the directory in place on drop so it can be inspected.
+    pub fn keep(&mut self) {
+        self.keep = true;
+    }
+
+    /// Replaces the remote history with a single new commit on top of the
+    /// seed, as a force-push from another machine would.
+    pub fn force_push(&self, path: &str, contents: &str) -> Result<()> {
+        let seed = self.root.join("seed");
+        git(&seed, &["reset", "--quiet", "--hard", "HEAD"])?;
+        fs::write(seed.join(path), contents)?;
+        git(&seed, &["add", path])?;
+        git(&seed, &["commit", "--quiet", "-m", "rewritten history"])?;
+        git(
+            &seed,
+            &["push", "--quiet", "--force", "origin", "HEAD:main"],
+        )?;
+        Ok(())
+    }
+
+    /// Makes the remote refuse every push, as a read-only deploy key would.
+    pub fn reject_pushes(&self) -> Result<()> {
+        let hook = self.remote.join("hooks").join("pre-receive");
+        fs::write(&hook, "#!/bin/sh\necho 'read-only access' >&2\nexit 1\n")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    }
+
+    /// Branches on the remote.
+    pub fn remote_branches(&self) -> Result<Vec<String>> {
+        Ok(git(&self.remote, &["branch", "--format=%(refname:short)"])?
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Contents of `path` on the remote branch, if it exists there.
+    pub fn remote_file(&self, path: &str) -> Option<String> {
+        git(
+            &self.remote,
+            &["cat-file", "-p", &format!("{BRANCH}:{path}")],
+        )
+        .ok()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// One vault clone driven by a [`SyncDaemon`] without the event loop.
+pub struct Device {
+    workdir: PathBuf,
+    config: Config,
+    daemon: SyncDaemon,
+}
+
+impl Device {
+    pub fn write(&self, path: &str, contents: &str) -> Result<()> {
+        let target = self.workdir.join(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, contents).with_context(|| format!("failed to write {path}"))
+    }
+
+    pub fn read(&self, path: &str) -> Option<String> {
+        fs::read_to_string(self.workdir.join(path)).ok()
+    }
+
+    /// Marks `path` as last modified `age` ago, so it counts as settled.
+    pub fn backdate(&self, path: &str, age: Duration) -> Result<()> {
+        let file = fs::File::options()
+            .write(true)
+            .open(self.workdir.join(path))?;
+        file.set_modified(SystemTime::now() - age)?;
+        Ok(())
+    }
+
+    /// Runs one commit-and-publish cycle, as the daemon does after a change.
+    pub fn sync(&mut self) -> Result<bool> {
+        self.daemon.sync_once()
+    }
+
+    /// Commits pending changes without publishing them, as the daemon does
+    /// while offline.
+    pub fn commit(&mut self) -> Result<bool> {
+        GitBackend::commit(&mut self.daemon)
+    }
+
+    /// Pulls and publishes without committing first, as the daemon does
+    /// every `poll_interval_seconds`.
+    pub fn poll(&mut self) -> Result<()> {
+        GitBackend::poll(&mut self.daemon)
+    }
+
+    /// Whether watcher events for `paths` would be taken for the daemon's
+    /// own writes.
+    pub fn wrote_itself(&self, paths: &[&str]) -> bool {
+        let paths: Vec<PathBuf> = paths.iter().map(|path| self.workdir.join(path)).collect();
+        self.daemon.wrote_itself(&paths)
+    }
+
+    /// Mirrors the vault into `replicas`, as the daemon does after a
+    /// successful sync.
+    pub fn mirror_replicas(&mut self) {
+        self.daemon.mirror_replicas();
+    }
+
+    /// Pushes this device's soft locks and fetches the others', as the
+    /// daemon does after a successful sync.
+    pub fn share_locks(&mut self) {
+        self.daemon.share_locks();
+    }
+
+    /// Merges the other devices' sync notes and pushes this device's, as
+    /// the daemon does after a successful sync.
+    pub fn share_notes(&mut self) {
+        self.daemon.share_notes();
+    }
+
+    /// Like [`Self::sync`], recording the git commands in the sync trace
+    /// when `trace.enabled` is set.
+    pub fn traced_sync(&mut self) -> Result<bool> {
+        self.daemon.traced_sync_once()
+    }
+
+    /// Restarts the daemon with changed settings, skipping the startup work
+    /// a fresh device does.
+    pub fn reconfigure(&mut self, configure: impl FnOnce(&mut Config)) -> Result<()> {
+        configure(&mut self.config);
+        self.daemon = SyncDaemon::new(self.config.clone())?;
+        Ok(())
+    }
+
+    pub fn workdir(&self) -> &Path {
+        &self.workdir
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Short `git status` of the vault.
+    pub fn status(&self) -> Result<String> {
+        git(&self.workdir, &["status", "--short", "--branch"])
+    }
+
+    pub fn head(&self) -> Result<String> {
+        Ok(git(&self.workdir, &["rev-parse", "HEAD"])?
+            .trim()
+            .to_string())
+    }
+
+    /// Stashes all local edits, as a user would with `git stash -u`.
+    pub fn stash(&self, message: &str) -> Result<()> {
+        git(
+            &self.workdir,
+            &[
+                "stash",
+                "push",
+                "--quiet",
+                "--include-untracked",
+                "-m",
+                message,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Stash subjects (`On main: <message>`), newest first.
+    pub fn stashes(&self) -> Result<Vec<String>> {
+        Ok(git(&self.workdir, &["stash", "list", "--format=%gs"])?
+            .lines()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Open conflicts with the remote, as the desktop app lists them.
+    pub fn conflicts(&self) -> Result<Vec<Conflict>> {
+        conflicts::list(&GitFacade::new(&self.config)?, &self.config)
+    }
+
+    /// Settles open conflicts as the desktop app does.
+    pub fn resolve(&self, choices: &[(&str, Resolution)]) -> Result<()> {
+        let choices = choices
+            .iter()
+            .map(|(path, resolution)| (path.to_string(), *resolution))
+            .collect();
+        conflicts::resolve(&GitFacade::new(&self.config)?, &self.config, &choices)
+    }
+
+    /// Runs `obsyncgit verify` for this device; the failed step on error.
+    pub fn verify(&self) -> std::result::Result<(), Step> {
+        connectivity::round_trip(&self.config, |_, _| {})
+    }
+
+    /// Runs the daemon's startup steps again, as after a crash.
+    pub fn restart(&mut self) -> Result<()> {
+        self.daemon.prepare()
+    }
+
+    /// Whether a rebase or merge was left half-done.
+    pub fn mid_operation(&self) -> bool {
+        let git_dir = self.workdir.join(".git");
+        ["rebase-merge", "rebase-apply", "MERGE_HEAD"]
+            .iter()
+            .any(|name| git_dir.join(name).exists())
+    }
+}
+
+pub type Scenario = fn() -> Result<()>;
+
+/// Every scenario, by name.
+pub fn scenarios() -> Vec<(&'static str, Scenario)> {
+    vec![
+        ("push", push),
+        ("pull", pull),
+        ("concurrent-edits", concurrent_edits),
+        ("conflict", conflict),
+        ("resolve-conflict", resolve_conflict),
+        ("autostash", autostash),
+        ("manual-stash", manual_stash),
+        ("verify", verify),
+        ("empty-remote", empty_remote),
+        ("case-collisions", case_collisions),
+        ("file-names", file_names),
+        ("unicode-names", unicode_names),
+        ("line-endings", line_endings),
+        ("disk-space", disk_space),
+        ("repair", repair),
+        ("backup", bundle_backup),
+        ("webdav-remote", webdav_remote),
+        ("clock", clock),
+        ("pause-cron", pause_cron),
+        ("pacing", pacing),
+        ("commit-modes", commit_modes),
+        ("amend", amend),
+        ("trailers", trailers),
+        ("insignificant", insignificant),
+        ("frontmatter-restore", frontmatter_restore),
+        ("own-writes", own_writes),
+        ("watcher-errors", watcher_errors),
+        ("events", events),
+        ("hooks", hooks),
+        ("repo-config", repo_config),
+        ("ignore-rules", ignore_rules),
+        ("blocked-files", blocked_files),
+        ("secret-scan", secret_scan),
+        ("redaction", redaction),
+        ("setup-secrets", setup_secrets),
+        ("export", export),
+        ("import", import),
+        ("replicas", replicas),
+        ("conflict-copies", conflict_copies),
+        ("digest", digest),
+        ("snapshots", snapshots),
+        ("retention", retention),
+        ("shared-vault", shared_vault),
+        ("soft-locks", soft_locks),
+        ("sync-notes", sync_notes),
+        ("stalled-remote", stalled_remote),
+        ("locales", locales),
+    ]
+}
+
+/// A local edit reaches the remote.
+fn push() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    laptop.write("notes/today.md", "hello\n")?;
+    ensure!(laptop.sync()?, "sync reported no changes");
+    ensure!(
+        sandbox.remote_file("notes/today.md").as_deref() == Some("hello\n"),
+        "note did not reach the remote"
+    );
+    Ok(())
+}
+
+/// A change pushed by one device shows up on another.
+fn pull() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    let mut phone = sandbox.device("phone")?;
+    laptop.write("todo.md", "- milk\n")?;
+    laptop.sync()?;
+    phone.write("other.md", "x\n")?;
+    phone.sync()?;
+    ensure!(
+        phone.read("todo.md").as_deref() == Some("- milk\n"),
+        "remote change was not pulled"
+    );
+    Ok(())
+}
+
+/// Edits to different files on two devices end up everywhere.
+fn concurrent_edits() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    let mut phone = sandbox.device("phone")?;
+    laptop.write("a.md", "from laptop\n")?;
+    phone.write("b.md", "from phone\n")?;
+    laptop.sync()?;
+    phone.sync()?;
+    laptop.write("c.md", "again\n")?;
+    laptop.sync()?;
+    for device in [&laptop, &phone] {
+        ensure!(device.read("a.md").is_some(), "a.md missing on a device");
+    }
+    ensure!(laptop.read("b.md").is_some(), "b.md did not reach laptop");
+    ensure!(
+        sandbox.remote_file("b.md").is_some() && sandbox.remote_file("c.md").is_some(),
+        "remote is missing pushed notes"
+    );
+    Ok(())
+}
+
+/// Conflicting edits fail the sync without losing local work or leaving the
+/// repository mid-rebase.
+fn conflict() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    let mut phone = sandbox.device("phone")?;
+    laptop.write("shared.md", "base\n")?;
+    laptop.sync()?;
+    phone.write("unrelated.md", "x\n")?;
+    phone.sync()?;
+
+    laptop.write("shared.md", "laptop version\n")?;
+    laptop.sync()?;
+    phone.write("shared.md", "phone version\n")?;
+    if phone.sync().is_ok() {
+        bail!("conflicting sync unexpectedly succeeded");
+    }
+    ensure!(!phone.mid_operation(), "rebase was not aborted");
+    ensure!(
+        phone.read("shared.md").as_deref() == Some("phone version\n"),
+        "local edit was lost"
+    );
+    ensure!(
+        sandbox.remote_file("shared.md").as_deref() == Some("laptop version\n"),
+        "remote was overwritten"
+    );
+    Ok(())
+}
+
+/// A conflict shows up in the inbox, and keeping both versions lets the
+/// sync through with neither edit lost, even if the remote moved on before
+/// the resolution was pushed.
+fn resolve_conflict() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    let mut phone = sandbox.device("phone")?;
+    laptop.write("ideas/plan.md", "base\n")?;
+    laptop.sync()?;
+    phone.sync()?;
+
+    laptop.write("ideas/plan.md", "laptop version\n")?;
+    laptop.sync()?;
+    phone.write("ideas/plan.md", "phone version\n")?;
+    ensure!(
+        phone.sync().is_err(),
+        "conflicting sync unexpectedly succeeded"
+    );
+
+    let open = phone.conflicts()?;
+    ensure!(
+        open.len() == 1
+            && open[0].path == "ideas/plan.md"
+            && open[0].mine.as_deref() == Some("phone version\n")
+            && open[0].theirs.as_deref() == Some("laptop version\n"),
+        "conflict inbox shows {open:?}"
+    );
+    phone.resolve(&[("ideas/plan.md", Resolution::Both)])?;
+    ensure!(
+        phone.conflicts()?.is_empty(),
+        "conflict still open after resolving"
+    );
+    // The remote moves on before the resolution is pushed.
+    laptop.write("ideas/later.md", "later\n")?;
+    laptop.sync()?;
+    phone.sync()?;
+    ensure!(
+        phone.read("ideas/later.md").is_some(),
+        "later note did not arrive"
+    );
+
+    ensure!(
+        sandbox.remote_file("ideas/plan.md").as_deref() == Some("phone version\n"),
+        "remote does not have this device's version"
+    );
+    ensure!(
+        sandbox
+            .remote_file("ideas/plan (other device).md")
+            .as_deref()
+            == Some("laptop version\n"),
+        "remote lost the other device's version"
+    );
+    ensure!(
+        phone.read("ideas/plan (other device).md").as_deref() == Some("laptop version\n"),
+        "the other device's version was not written into the vault"
+    );
+    Ok(())
+}
+
+/// Files still being written are stashed around the rebase and come back
+/// untouched.
+fn autostash() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    let mut phone = sandbox.device_with("phone", |config| {
+        config.stability.settle_millis = 60_000;
+    })?;
+    laptop.write("a.md", "from laptop\n")?;
+    laptop.sync()?;
+
+    phone.write("b.md", "settled\n")?;
+    phone.backdate("b.md", Duration::from_secs(3600))?;
+    phone.write("draft.md", "half written")?;
+    let before = phone.head()?;
+    phone.sync()?;
+    ensure!(phone.head()? != before, "settled file was not committed");
+    ensure!(
+        phone.read("draft.md").as_deref() == Some("half written"),
+        "in-progress file was not restored after the rebase"
+    );
+    ensure!(phone.read("a.md").is_some(), "remote change was not pulled");
+    ensure!(
+        sandbox.remote_file("draft.md").is_none(),
+        "in-progress file was pushed"
+    );
+    Ok(())
+}
+
+/// The user's own stashes survive the autostash around a pull, and only
+/// obsyncgit's leftover entry is restored after a crash, even with a manual
+/// stash pushed on top of it.
+fn manual_stash() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    let mut phone = sandbox.device_with("phone", |config| {
+        config.stability.settle_millis = 60_000;
+    })?;
+    phone.write("mine.md", "kept aside\n")?;
+    phone.stash("my own work")?;
+    laptop.write("a.md", "from laptop\n")?;
+    laptop.sync()?;
+
+    phone.write("b.md", "settled\n")?;
+    phone.backdate("b.md", Duration::from_secs(3600))?;
+    phone.write("draft.md", "half written")?;
+    phone.sync()?;
+    ensure!(
+        phone.read("draft.md").as_deref() == Some("half written"),
+        "in-progress file was not restored after the rebase"
+    );
+    ensure!(
+        phone.read("mine.md").is_none(),
+        "the user's stash was popped"
+    );
+    ensure!(
+        phone.stashes()? == ["On main: my own work"],
+        "stash list changed"
+    );
+
+    // A crash between stash and pop, then the user stashes again.
+    fs::remove_file(phone.workdir().join("draft.md"))?;
+    phone.write("lost.md", "stashed by obsyncgit\n")?;
+    phone.stash("obsyncgit-autostash")?;
+    phone.write("later.md", "stashed by the user\n")?;
+    phone.stash("more of my work")?;
+    phone.restart()?;
+    ensure!(
+        phone.read("lost.md").is_some(),
+        "leftover autostash was not restored"
+    );
+    ensure!(
+        phone.read("later.md").is_none() && phone.read("mine.md").is_none(),
+        "a manual stash was popped"
+    );
+    ensure!(phone.stashes()?.len() == 2, "manual stashes were dropped");
+    Ok(())
+}
+
+/// `obsyncgit verify` pushes and removes its test branch without touching
+/// the vault, and names the push as the step a read-only remote fails.
+fn verify() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let laptop = sandbox.device("laptop")?;
+    let before = (laptop.head()?, laptop.status()?);
+    ensure!(
+        laptop.verify().is_ok(),
+        "round trip failed on a writable remote"
+    );
+    ensure!(
+        sandbox.remote_branches()? == [BRANCH],
+        "test branch was left on the remote"
+    );
+    ensure!(
+        (laptop.head()?, laptop.status()?) == before,
+        "verify touched the vault"
+    );
+
+    sandbox.reject_pushes()?;
+    let failed = laptop.verify().err();
+    ensure!(
+        failed == Some(Step::Push),
+        "read-only remote failed at {failed:?} instead of the push"
+    );
+    Ok(())
+}
+
+/// The first device on a freshly created remote creates the branch with the
+/// starter files, and the next one clones it.
+fn empty_remote() -> Result<()> {
+    let sandbox = Sandbox::empty()?;
+    let mut laptop = sandbox.device_with("laptop", |config| config.bootstrap.enabled = true)?;
+    ensure!(
+        sandbox.remote_branches()? == [BRANCH],
+        "branch was not created on the remote"
+    );
+    ensure!(
+        sandbox.remote_file(".gitattributes").is_some()
+            && sandbox.remote_file("templates/Daily note.md").is_some(),
+        "starter files were not pushed"
+    );
+    laptop.write("first.md", "hello\n")?;
+    laptop.sync()?;
+    let phone = sandbox.device("phone")?;
+    ensure!(
+        phone.read("first.md").as_deref() == Some("hello\n"),
+        "second device did not get the first note"
+    );
+    Ok(())
+}
+
+/// Paths that differ only in letter case are renamed after the pull that
+/// brings them in, and the rename reaches the remote.
+fn case_collisions() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut linux = sandbox.device("linux")?;
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.sync.case_collisions = CaseCollisionPolicy::Rename;
+    })?;
+    linux.write("Note.md", "upper\n")?;
+    linux.write("note.md", "lower\n")?;
+    linux.write("Ideas/a.md", "a\n")?;
+    linux.write("ideas/b.md", "b\n")?;
+    linux.sync()?;
+
+    laptop.write("other.md", "x\n")?;
+    laptop.sync()?;
+    ensure!(
+        laptop.read("Note.md").as_deref() == Some("upper\n")
+            && laptop.read("note (2).md").as_deref() == Some("lower\n"),
+        "colliding notes were not renamed"
+    );
+    ensure!(
+        laptop.read("Ideas/a.md").is_some() && laptop.read("ideas (2)/b.md").is_some(),
+        "colliding folders were not renamed"
+    );
+    ensure!(
+        sandbox.remote_file("note.md").is_none()
+            && sandbox.remote_file("note (2).md").as_deref() == Some("lower\n"),
+        "the rename did not reach the remote"
+    );
+    Ok(())
+}
+
+/// Files Windows cannot check out are renamed before they are committed,
+/// and the old names are recorded in the vault.
+fn file_names() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.sync.file_names = FileNamePolicy::Rename;
+    })?;
+    laptop.write("plans: 2025?.md", "q\n")?;
+    laptop.write("con.md", "c\n")?;
+    laptop.write("fine.md", "f\n")?;
+    laptop.sync()?;
+    ensure!(
+        sandbox.remote_file("plans： 2025？.md").as_deref() == Some("q\n")
+            && sandbox.remote_file("con_.md").as_deref() == Some("c\n")
+            && sandbox.remote_file("fine.md").is_some(),
+        "files were not renamed for Windows"
+    );
+    ensure!(
+        sandbox.remote_file("plans: 2025?.md").is_none(),
+        "the old name was committed"
+    );
+    let record = sandbox
+        .remote_file(file_names::RENAMES_PATH)
+        .context("renamed files were not recorded")?;
+    ensure!(
+        record.contains("plans: 2025?.md"),
+        "old name missing from the record"
+    );
+    Ok(())
+}
+
+/// A decomposed name committed by one device is renamed to the composed
+/// form by a device set to `nfc`, and a second spelling of the same note is
+/// held back instead of being committed next to it.
+fn unicode_names() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut mac = sandbox.device("mac")?;
+    let mut linux = sandbox.device_with("linux", |config| {
+        config.compat.unicode_normalization = UnicodeNormalization::Nfc;
+    })?;
+    mac.write("Cafe\u{301}.md", "menu\n")?;
+    mac.sync()?;
+
+    linux.write("Nai\u{308}ve.md", "n\n")?;
+    linux.sync()?;
+    ensure!(
+        sandbox.remote_file("Caf\u{e9}.md").as_deref() == Some("menu\n")
+            && sandbox.remote_file("Cafe\u{301}.md").is_none(),
+        "the tracked name was not composed"
+    );
+    ensure!(
+        sandbox.remote_file("Na\u{ef}ve.md").as_deref() == Some("n\n"),
+        "the new file was not committed under its composed name"
+    );
+
+    mac.write("pulled.md", "p\n")?;
+    mac.sync()?;
+    mac.write("Cafe\u{301}.md", "copy\n")?;
+    mac.write("other.md", "o\n")?;
+    mac.sync()?;
+    ensure!(
+        sandbox.remote_file("other.md").is_some()
+            && sandbox.remote_file("Cafe\u{301}.md").is_none()
+            && sandbox.remote_file("Caf\u{e9}.md").as_deref() == Some("menu\n"),
+        "a second spelling of a note was committed"
+    );
+    Ok(())
+}
+
+/// `compat.line_endings: lf` converts notes stored with CRLF in one
+/// commit, and later CRLF edits are stored with LF.
+fn line_endings() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    laptop.write("notes/windows.md", "one\r\ntwo\r\n")?;
+    laptop.sync()?;
+    ensure!(
+        sandbox.remote_file("notes/windows.md").as_deref() == Some("one\r\ntwo\r\n"),
+        "the note was not stored with CRLF to begin with"
+    );
+
+    let mut phone = sandbox.device_with("phone", |config| {
+        config.compat.line_endings = LineEndings::Lf;
+    })?;
+    let subject = git(phone.workdir(), &["log", "-1", "--format=%s"])?;
+    ensure!(
+        subject.trim() == "Normalize line endings to LF",
+        "no conversion commit, HEAD is {subject:?}"
+    );
+    phone.write("notes/new.md", "three\r\n")?;
+    phone.sync()?;
+    for (path, contents) in [
+        ("notes/windows.md", "one\ntwo\n"),
+        ("notes/new.md", "three\n"),
+    ] {
+        ensure!(
+            sandbox.remote_file(path).as_deref() == Some(contents),
+            "{path} was not stored with LF"
+        );
+    }
+    let attributes = sandbox.remote_file(".gitattributes").unwrap_or_default();
+    ensure!(
+        attributes.contains("* text=auto eol=lf"),
+        "the rules did not reach the remote: {attributes:?}"
+    );
+    let head = phone.head()?;
+    phone.reconfigure(|_| {})?;
+    phone.sync()?;
+    ensure!(phone.head()? == head, "the conversion was committed again");
+    Ok(())
+}
+
+/// Below `disk.min_free_mb` nothing is cloned or committed, and syncing
+/// picks up where it stopped once space is back.
+fn disk_space() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let full = |config: &mut Config| config.disk.min_free_mb = u64::MAX / (1024 * 1024);
+    let err = match sandbox.device_with("phone", full) {
+        Ok(_) => bail!("a vault was cloned onto a full disk"),
+        Err(err) => err,
+    };
+    ensure!(
+        matches!(GitError::of(&err), GitError::DiskFull(_)),
+        "a clone onto a full disk failed with {err:#}"
+    );
+    ensure!(
+        !sandbox.root().join("phone/.git").exists(),
+        "a clone onto a full disk was started"
+    );
+
+    let mut laptop = sandbox.device("laptop")?;
+    let head = laptop.head()?;
+    laptop.reconfigure(full)?;
+    laptop.write("late.md", "written on a full disk\n")?;
+    match laptop.sync() {
+        Ok(_) => bail!("a note was synced on a full disk"),
+        Err(err) => ensure!(
+            matches!(GitError::of(&err), GitError::DiskFull(_)),
+            "syncing on a full disk failed with {err:#}"
+        ),
+    }
+    ensure!(laptop.head()? == head, "a commit was made on a full disk");
+
+    laptop.reconfigure(|config| config.disk.min_free_mb = 0)?;
+    laptop.sync()?;
+    ensure!(
+        sandbox.remote_file("late.md").is_some(),
+        "the note did not reach the remote once space was freed"
+    );
+    Ok(())
+}
+
+/// A repository missing an object is reported by the integrity check and
+/// replaced by a fresh clone that keeps the edits not synced yet.
+fn repair() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    laptop.write("kept.md", "synced\n")?;
+    laptop.write("lost.md", "synced\n")?;
+    laptop.sync()?;
+    let object = git(laptop.workdir(), &["rev-parse", "HEAD:lost.md"])?;
+    let object = object.trim();
+    fs::remove_file(
+        laptop
+            .workdir()
+            .join(".git/objects")
+            .join(&object[..2])
+            .join(&object[2..]),
+    )?;
+    laptop.write("kept.md", "edited\n")?;
+    laptop.write("new.md", "not committed\n")?;
+
+    let report = integrity::check(&GitFacade::new(laptop.config())?, laptop.config())?;
+    ensure!(report.is_damaged(), "git fsck missed a deleted object");
+    let repaired = integrity::repair(laptop.config())?;
+    ensure!(
+        repaired.damaged.join("lost.md").exists(),
+        "the damaged vault was not kept"
+    );
+    ensure!(
+        laptop.read("kept.md").as_deref() == Some("edited\n")
+            && laptop.read("new.md").as_deref() == Some("not committed\n")
+            && laptop.read("lost.md").as_deref() == Some("synced\n"),
+        "local edits were lost in the repair"
+    );
+    let report = integrity::check(&GitFacade::new(laptop.config())?, laptop.config())?;
+    ensure!(!report.is_damaged(), "the fresh clone is damaged too");
+    laptop.sync()?;
+    ensure!(
+        sandbox.remote_file("new.md").is_some()
+            && sandbox.remote_file("kept.md").as_deref() == Some("edited\n"),
+        "carried-over edits did not reach the remote"
+    );
+    Ok(())
+}
+
+/// Bundles keep only `backup.keep` copies, and a vault restored from one
+/// has the committed notes and syncs with the remote again.
+fn bundle_backup() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let backups = path_str(&sandbox.root().join("backups"))?.to_string();
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.backup.dir = Some(backups);
+        config.backup.keep = 1;
+    })?;
+    laptop.write("note.md", "backed up\n")?;
+    laptop.sync()?;
+    let repo = GitFacade::new(laptop.config())?;
+    backup::write(&repo, laptop.config())?;
+    std::thread::sleep(Duration::from_millis(1100));
+    let newest = backup::write(&repo, laptop.config())?;
+    ensure!(
+        backup::list(laptop.config())? == [newest],
+        "old bundles were not deleted"
+    );
+
+    let target = sandbox.root().join("restored");
+    let (_, restored) = backup::restore(laptop.config(), None, Some(&target))?;
+    ensure!(
+        fs::read_to_string(restored.join("note.md")).ok().as_deref() == Some("backed up\n"),
+        "the restored vault lacks the backed-up note"
+    );
+    ensure!(
+        git(&restored, &["remote", "get-url", "origin"])?.trim() == path_str(&sandbox.remote)?,
+        "the restored vault does not point at the remote"
+    );
+    Ok(())
+}
+
+/// Two devices sync through a bundle on a WebDAV share: the first creates
+/// it, edits from both meet, and a push that is not a fast-forward is
+/// rejected instead of overwriting the other device's work.
+fn webdav_remote() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let url = serve_dav()?;
+    let on_dav = |config: &mut Config| {
+        config.remote.kind = RemoteKind::Webdav;
+        config.repo_url = url.clone();
+    };
+    let mut laptop = sandbox.device_with("laptop", on_dav)?;
+    laptop.write("laptop.md", "from laptop\n")?;
+    laptop.sync()?;
+    let mut phone = sandbox.device_with("phone", on_dav)?;
+    ensure!(
+        phone.read("laptop.md").as_deref() == Some("from laptop\n"),
+        "the second device did not clone the bundle"
+    );
+
+    phone.write("phone.md", "from phone\n")?;
+    phone.sync()?;
+    laptop.write("laptop.md", "edited on laptop\n")?;
+    laptop.sync()?;
+    phone.write("phone-2.md", "x\n")?;
+    phone.sync()?;
+    ensure!(
+        phone.read("laptop.md").as_deref() == Some("edited on laptop\n")
+            && laptop.read("phone.md").as_deref() == Some("from phone\n"),
+        "edits did not meet through the bundle"
+    );
+
+    // A commit made before pulling the phone's is not a fast-forward.
+    phone.write("phone.md", "phone again\n")?;
+    phone.sync()?;
+    laptop.write("laptop.md", "laptop again\n")?;
+    let repo = GitFacade::new(laptop.config())?;
+    repo.stage_all()?;
+    repo.commit("laptop again")?;
+    let err = match repo.push() {
+        Ok(()) => bail!("a push not based on the remote bundle replaced it"),
+        Err(err) => err,
+    };
+    ensure!(
+        matches!(GitError::of(&err), GitError::NonFastForward(_)),
+        "the stale push failed for another reason: {err:#}"
+    );
+    laptop.write("laptop-2.md", "x\n")?;
+    laptop.sync()?;
+    phone.write("phone-3.md", "x\n")?;
+    phone.sync()?;
+    ensure!(
+        phone.read("laptop.md").as_deref() == Some("laptop again\n")
+            && laptop.read("phone.md").as_deref() == Some("phone again\n"),
+        "the rejected push lost an edit"
+    );
+    Ok(())
+}
+
+/// A WebDAV share in this process serving a single file from memory:
+/// enough of GET and PUT, with ETags and their preconditions, for a
+/// bundle remote. Returns the file's URL.
+fn serve_dav() -> Result<String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}/dav/vault.bundle", listener.local_addr()?);
+    std::thread::spawn(move || {
+        let mut file: Option<(Vec<u8>, u32)> = None;
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).is_err() {
+                continue;
+            }
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+                }
+            }
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.as_str())
+            };
+            let etag = file.as_ref().map(|(_, version)| format!("\"{version}\""));
+            let (status, body) = if request_line.starts_with("GET ") {
+                match &file {
+                    None => ("404 Not Found", Vec::new()),
+                    Some(_) if header("if-none-match") == etag.as_deref() => {
+                        ("304 Not Modified", Vec::new())
+                    }
+                    Some((contents, _)) => ("200 OK", contents.clone()),
+                }
+            } else if request_line.starts_with("PUT ") {
+                let length = header("content-length")
+                    .and_then(|length| length.parse().ok())
+                    .unwrap_or(0);
+                let mut contents = vec![0; length];
+                if std::io::Read::read_exact(&mut reader, &mut contents).is_err() {
+                    continue;
+                }
+                let fresh = header("if-none-match") == Some("*") && file.is_none();
+                let current = header("if-match").is_some() && header("if-match") == etag.as_deref();
+                if fresh || current {
+                    let version = file.as_ref().map_or(1, |(_, version)| version + 1);
+                    file = Some((contents, version));
+                    ("201 Created", Vec::new())
+                } else {
+                    ("412 Precondition Failed", Vec::new())
+                }
+            } else {
+                ("405 Method Not Allowed", Vec::new())
+            };
+            let etag = file
+                .as_ref()
+                .map(|(_, version)| format!("\"{version}\""))
+                .unwrap_or_default();
+            let mut stream = &stream;
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nETag: {etag}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(&body);
+        }
+    });
+    Ok(url)
+}
+
+/// Deadlines count time the machine slept and ignore a clock set back.
+fn clock() -> Result<()> {
+    let hour = Duration::from_secs(3600);
+    let clock = MockClock::new();
+    let poll = Deadline::after(&clock, hour);
+    clock.advance(hour / 2);
+    ensure!(
+        poll.remaining(&clock) == hour / 2,
+        "half an hour awake did not count"
+    );
+    clock.suspend(hour);
+    ensure!(poll.is_due(&clock), "a deadline passed asleep was not due");
+
+    let update = Deadline::after(&clock, hour);
+    clock.set_back(2 * hour);
+    clock.advance(hour / 4);
+    ensure!(
+        update.remaining(&clock) == hour * 3 / 4,
+        "setting the clock back moved the deadline"
+    );
+
+    let last_poll = Moment::now(&clock).earlier(hour);
+    ensure!(
+        Deadline::new(last_poll, hour).is_due(&clock),
+        "a backdated poll was not due"
+    );
+    Ok(())
+}
+
+/// `pause_cron` treats a restricted day of the month and day of the week as
+/// alternatives, and a `*` in either as requiring both.
+fn pause_cron() -> Result<()> {
+    let paused = |expr: &str, (year, month, day): (i32, u32, u32)| -> Result<bool> {
+        let schedule = Schedule::new(&ScheduleConfig {
+            pause_cron: vec![expr.to_string()],
+            ..ScheduleConfig::default()
+        })?;
+        let now = Utc
+            .with_ymd_and_hms(year, month, day, 12, 0, 0)
+            .single()
+            .context("invalid date")?;
+        Ok(!schedule.network_allowed(&now))
+    };
+    // 2024-01-01 and 2024-01-15 are Mondays.
+    for (expr, date, expected) in [
+        ("* * 1 * 1", (2024, 1, 15), true),
+        ("* * 1 * 1", (2024, 2, 1), true),
+        ("* * 1 * 1", (2024, 1, 2), false),
+        ("* * 1 * *", (2024, 1, 15), false),
+        ("* * * * 1", (2024, 2, 1), false),
+        ("* * */2 * 1", (2024, 1, 15), true),
+        ("* * */2 * 1", (2024, 1, 8), false),
+        ("* * */2 * 1", (2024, 1, 3), false),
+    ] {
+        ensure!(
+            paused(expr, date)? == expected,
+            "'{expr}' on {date:?} should {}pause",
+            if expected { "" } else { "not " }
+        );
+    }
+    Ok(())
+}
+
+/// A [`GitBackend`] that answers each cycle with the next scripted result
+/// and counts what it was asked to do.
+#[derive(Debug, Default)]
+pub struct ScriptedGit {
+    results: VecDeque<std::result::Result<bool, String>>,
+    unsettled: bool,
+    pub syncs: usize,
+    pub commits: usize,
+    pub polls: usize,
+}
+
+impl ScriptedGit {
+    /// Answers the next cycle with success, reporting `changed`.
+    pub fn succeed(&mut self, changed: bool) -> &mut Self {
+        self.results.push_back(Ok(changed));
+        self
+    }
+
+    /// Answers the next cycle with a git error reading `message`.
+    pub fn fail(&mut self, message: &str) -> &mut Self {
+        self.results.push_back(Err(message.to_string()));
+        self
+    }
+
+    fn next(&mut self) -> Result<bool> {
+        match self.results.pop_front() {
+            Some(Ok(changed)) => Ok(changed),
+            Some(Err(message)) => Err(anyhow!(message)),
+            None => bail!("no scripted result left"),
+        }
+    }
+}
+
+impl GitBackend for ScriptedGit {
+    fn sync(&mut self) -> Result<bool> {
+        self.syncs += 1;
+        self.next()
+    }
+
+    fn commit(&mut self) -> Result<bool> {
+        self.commits += 1;
+        self.next()
+    }
+
+    fn poll(&mut self) -> Result<()> {
+        self.polls += 1;
+        self.next().map(|_| ())
+    }
+
+    fn unsettled(&self) -> bool {
+        self.unsettled
+    }
+}
+
+/// Debounce, poll and backoff timing of the daemon loop.
+fn pacing() -> Result<()> {
+    let debounce = Duration::from_secs(5);
+    let poll_interval = Duration::from_secs(300);
+    let clock = MockClock::new();
+    let mut pacer = Pacer::new(
+        clock.clone(),
+        CommitTiming::AfterQuiet(debounce),
+        poll_interval,
+    );
+    let mut git = ScriptedGit::default();
+    let at = |instant: Option<std::time::Instant>| {
+        instant.map(|instant| instant.saturating_duration_since(clock.monotonic()))
+    };
+
+    ensure!(
+        at(Some(pacer.poll_at())) == Some(Duration::ZERO),
+        "the first poll was not due right away"
+    );
+    ensure!(
+        at(pacer.commit_at(true)).is_none(),
+        "a commit was due unasked"
+    );
+
+    // Every change starts the debounce time over.
+    pacer.changed();
+    clock.advance(Duration::from_secs(3));
+    pacer.changed();
+    ensure!(
+        at(pacer.commit_at(true)) == Some(debounce),
+        "a second change did not put the commit off"
+    );
+    ensure!(
+        pacer.commit_at(false).is_none(),
+        "a commit was due while held up"
+    );
+
+    // Failures back off, an unreachable remote does not.
+    git.fail("fatal: unable to write new index file")
+        .fail("ssh: Could not resolve hostname example.invalid: Name or service not known");
+    match pacer.commit(&mut git, false) {
+        Err(CycleError::Failed(_)) => {}
+        other => bail!("a git failure came back as {other:?}"),
+    }
+    pacer.back_off(pacer.backoff_step() + 1);
+    ensure!(
+        at(pacer.backoff_at()) == Some(backoff_delay(1)),
+        "backoff did not wait two seconds"
+    );
+    clock.advance(backoff_delay(1));
+    pacer.end_backoff();
+    match pacer.commit(&mut git, false) {
+        Err(CycleError::Unavailable(_)) => {}
+        other => bail!("an unreachable remote came back as {other:?}"),
+    }
+    ensure!(
+        !pacer.is_dirty(),
+        "a settled change stayed pending after the remote was down"
+    );
+
+    // Offline, changes are committed locally; success starts backoff over.
+    pacer.changed();
+    git.succeed(true);
+    ensure!(
+        matches!(pacer.commit(&mut git, true), Ok(true)),
+        "local commit failed"
+    );
+    ensure!(
+        git.commits == 1 && git.syncs == 2,
+        "wrong cycles ran: {git:?}"
+    );
+    ensure!(pacer.backoff_step() == 0, "success did not reset backoff");
+    ensure!(
+        at(Some(pacer.poll_at())) == Some(poll_interval),
+        "a commit did not put the next poll off"
+    );
+
+    // A poll that finds the remote down waits a full interval too.
+    clock.advance(poll_interval);
+    git.fail("fatal: unable to access 'https://example.invalid/': Could not resolve host: example.invalid");
+    ensure!(
+        matches!(pacer.poll(&mut git), Err(CycleError::Unavailable(_))),
+        "an unreachable remote was not reported"
+    );
+    ensure!(
+        at(Some(pacer.poll_at())) == Some(poll_interval),
+        "a failed poll was retried right away"
+    );
+    pacer.poll_now();
+    ensure!(
+        at(Some(pacer.poll_at())) == Some(Duration::ZERO),
+        "a requested poll was not due"
+    );
+    ensure!(
+        backoff_delay(20) == Duration::from_secs(300),
+        "backoff grew past five minutes"
+    );
+    Ok(())
+}
+
+/// `commit.mode`: snapshots commit on a fixed beat, sessions after a quiet
+/// spell.
+fn commit_modes() -> Result<()> {
+    let minute = Duration::from_secs(60);
+    let clock = MockClock::new();
+    let at = |pacer: &Pacer<MockClock>| {
+        pacer
+            .commit_at(true)
+            .map(|instant| instant.saturating_duration_since(clock.monotonic()))
+    };
+    let mut git = ScriptedGit::default();
+
+    let mut snapshot = Pacer::new(clock.clone(), CommitTiming::Every(30 * minute), minute);
+    ensure!(
+        at(&snapshot).is_none(),
+        "a snapshot was due without changes"
+    );
+    clock.advance(10 * minute);
+    snapshot.changed();
+    clock.advance(10 * minute);
+    snapshot.changed();
+    ensure!(
+        at(&snapshot) == Some(10 * minute),
+        "edits moved the snapshot"
+    );
+    clock.advance(10 * minute);
+    git.succeed(true);
+    ensure!(
+        matches!(snapshot.commit(&mut git, false), Ok(true)),
+        "snapshot failed"
+    );
+    snapshot.changed();
+    ensure!(
+        at(&snapshot) == Some(30 * minute),
+        "the next snapshot did not wait a full interval"
+    );
+    snapshot.commit_now();
+    ensure!(
+        at(&snapshot) == Some(Duration::ZERO),
+        "a requested snapshot was not due"
+    );
+
+    let mut session = Pacer::new(clock.clone(), CommitTiming::AfterQuiet(10 * minute), minute);
+    session.changed();
+    clock.advance(9 * minute);
+    session.changed();
+    ensure!(
+        at(&session) == Some(10 * minute),
+        "an edit did not extend the session"
+    );
+    Ok(())
+}
+
+/// `commit.amend_window_minutes`: edits fold into the daemon's unpushed
+/// commit, never into one the remote has.
+fn amend() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.commit.amend_window_minutes = 10;
+    })?;
+    let commits = |device: &Device| -> Result<String> {
+        Ok(git(device.workdir(), &["rev-list", "--count", "HEAD"])?
+            .trim()
+            .to_string())
+    };
+    laptop.write("notes/a.md", "a\n")?;
+    ensure!(laptop.commit()?, "first edit was not committed");
+    let first = laptop.head()?;
+    let count = commits(&laptop)?;
+    laptop.write("notes/b.md", "b\n")?;
+    ensure!(laptop.commit()?, "second edit was not committed");
+    ensure!(laptop.head()? != first, "HEAD did not change");
+    ensure!(commits(&laptop)? == count, "second edit made a new commit");
+    let files = git(
+        laptop.workdir(),
+        &["show", "--name-only", "--format=", "HEAD"],
+    )?;
+    ensure!(
+        files.contains("notes/a.md") && files.contains("notes/b.md"),
+        "amended commit lost a file: {files}"
+    );
+
+    ensure!(laptop.sync()?, "sync reported no changes");
+    laptop.write("notes/c.md", "c\n")?;
+    ensure!(laptop.commit()?, "third edit was not committed");
+    ensure!(commits(&laptop)? != count, "a pushed commit was amended");
+    Ok(())
+}
+
+/// `commit.trailers` as a list and `commit.signoff` end up in the trailer
+/// block, once each even after an amend.
+fn trailers() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.commit.trailers =
+            CommitTrailers::Custom(vec!["Reviewed-by: Ops <ops@example.com>".to_string()]);
+        config.commit.signoff = true;
+        config.commit.amend_window_minutes = 10;
+    })?;
+    laptop.write("notes/a.md", "a\n")?;
+    laptop.commit()?;
+    laptop.write("notes/b.md", "b\n")?;
+    laptop.commit()?;
+    let trailers = git(
+        laptop.workdir(),
+        &["log", "-1", "--format=%(trailers:only,unfold)", "HEAD"],
+    )?;
+    let expected = "Reviewed-by: Ops <ops@example.com>\nSigned-off-by: selftest laptop <laptop@selftest.invalid>";
+    ensure!(
+        trailers.trim() == expected,
+        "unexpected trailers: {trailers:?}"
+    );
+    Ok(())
+}
+
+/// `commit.insignificant`: a bumped `updated:` property and trailing
+/// spaces are not committed by themselves, and go along with the next real
+/// edit.
+fn insignificant() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.commit.insignificant = InsignificantChanges {
+            whitespace: true,
+            frontmatter_keys: vec!["updated".to_string()],
+            ..InsignificantChanges::default()
+        };
+    })?;
+    laptop.write(
+        "notes/a.md",
+        "---\ntitle: A\nupdated: 2026-01-01\n---\nbody\n",
+    )?;
+    ensure!(laptop.commit()?, "the new note was not committed");
+    let head = laptop.head()?;
+
+    let bumped = "---\ntitle: A\nupdated: 2026-01-02T10:00\n---\nbody  \r\n\n";
+    laptop.write("notes/a.md", bumped)?;
+    ensure!(!laptop.commit()?, "an insignificant change was committed");
+    ensure!(laptop.head()? == head, "HEAD moved");
+
+    laptop.write(
+        "notes/a.md",
+        "---\ntitle: B\nupdated: 2026-01-02T10:00\n---\nbody\n",
+    )?;
+    ensure!(laptop.commit()?, "a changed property was not committed");
+    laptop.write(
+        "notes/a.md",
+        "---\ntitle: B\nupdated: 2026-01-03\n---\nbody\nmore\n",
+    )?;
+    ensure!(laptop.commit()?, "a changed body was not committed");
+    let committed = git(laptop.workdir(), &["show", "HEAD:notes/a.md"])?;
+    ensure!(
+        committed.contains("updated: 2026-01-03"),
+        "the bumped property did not go along: {committed:?}"
+    );
+    Ok(())
+}
+
+/// `commit.insignificant.restore_frontmatter`: a bumped `modified:` held
+/// back on one device is put back before pulling, so another device's edit
+/// of the note arrives without a conflict. Notes the pull leaves alone keep
+/// their bump.
+fn frontmatter_restore() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let bumps = |config: &mut Config| {
+        config.commit.insignificant = InsignificantChanges {
+            frontmatter_keys: vec!["modified".to_string()],
+            restore_frontmatter: true,
+            ..InsignificantChanges::default()
+        };
+    };
+    let mut laptop = sandbox.device_with("laptop", bumps)?;
+    laptop.write("notes/a.md", "---\nmodified: 1\n---\nbody\n")?;
+    laptop.write("notes/b.md", "---\nmodified: 1\n---\nopen\n")?;
+    laptop.sync()?;
+    let mut phone = sandbox.device_with("phone", bumps)?;
+    phone.write("notes/a.md", "---\nmodified: 2\n---\nbody\n")?;
+    let open = "---\nmodified: 2\n---\nopen\n";
+    phone.write("notes/b.md", open)?;
+    phone.sync()?;
+    ensure!(
+        sandbox.remote_file("notes/a.md").as_deref() == Some("---\nmodified: 1\n---\nbody\n"),
+        "the bumped property was pushed"
+    );
+
+    let edited = "---\nmodified: 3\n---\nbody\nmore\n";
+    laptop.write("notes/a.md", edited)?;
+    laptop.sync()?;
+    phone.poll()?;
+    ensure!(
+        phone.read("notes/a.md").as_deref() == Some(edited),
+        "the edit did not arrive: {:?}",
+        phone.read("notes/a.md")
+    );
+    ensure!(
+        phone.read("notes/b.md").as_deref() == Some(open),
+        "a note the pull did not touch was put back"
+    );
+    let status = git(phone.workdir(), &["status", "--porcelain"])?;
+    ensure!(
+        status.trim() == "M notes/b.md",
+        "the phone was left with {status:?}"
+    );
+    Ok(())
+}
+
+/// An error about one path below the vault is only logged; one without
+/// paths means the backend broke and the watcher is restarted.
+fn watcher_errors() -> Result<()> {
+    let sandbox = Sandbox::empty()?;
+    let root = sandbox.root().join("vault");
+    fs::create_dir_all(root.join("notes"))?;
+    let ignore = Arc::new(RwLock::new(IgnoreMatcher::new(&root, &[])?));
+    let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = WatcherSupervisor::new(
+        root.clone(),
+        ignore,
+        tx,
+        Shutdown::new(),
+        Duration::from_secs(1),
+        WatcherBackend::Poll,
+    );
+    watcher.start()?;
+
+    watcher.on_error(notify::Error::path_not_found().add_path(root.join("notes")));
+    ensure!(
+        watcher.restart_at().is_none(),
+        "an error about one folder restarted the watcher"
+    );
+    watcher.on_error(notify::Error::generic("event stream closed"));
+    ensure!(
+        watcher.restart_at().is_some(),
+        "a broken backend was not restarted"
+    );
+    Ok(())
+}
+
+/// Files a pull wrote do not count as edits, until someone changes them.
+fn own_writes() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    let mut phone = sandbox.device("phone")?;
+    laptop.write("notes/new/a.md", "from the laptop\n")?;
+    laptop.sync()?;
+    phone.poll()?;
+    ensure!(
+        phone.read("notes/new/a.md").is_some(),
+        "the pull brought nothing"
+    );
+    ensure!(
+        phone.wrote_itself(&["notes/new/a.md", "notes/new"]),
+        "the pulled note was taken for an edit"
+    );
+    ensure!(
+        !phone.wrote_itself(&["notes/new/a.md", "notes/other.md"]),
+        "an edit next to the pulled note was missed"
+    );
+    phone.write("notes/new/a.md", "edited on the phone right after\n")?;
+    ensure!(
+        !phone.wrote_itself(&["notes/new/a.md"]),
+        "an edit of the pulled note was missed"
+    );
+    Ok(())
+}
+
+/// The event stream: the laptop records its commit and push, the phone
+/// the pull that brought the note, and a follower reads across a rotation.
+fn events() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    let mut phone = sandbox.device("phone")?;
+    laptop.write("notes/a.md", "from the laptop\n")?;
+    laptop.sync()?;
+    phone.poll()?;
+
+    let recorded = EventLog::new(laptop.config())?.recent(10);
+    let commit = laptop.head()?;
+    ensure!(
+        recorded.iter().any(|record| matches!(
+            &record.event,
+            Event::CommitCreated { commit: id, files, amended: false }
+                if *id == commit && *files == ["notes/a.md"]
+        )),
+        "the commit was not recorded: {recorded:?}"
+    );
+    ensure!(
+        recorded
+            .iter()
+            .any(|record| matches!(&record.event, Event::PushOk { branch } if branch == "main")),
+        "the push was not recorded: {recorded:?}"
+    );
+    let pulled = EventLog::new(phone.config())?.recent(10);
+    ensure!(
+        pulled.iter().any(|record| matches!(
+            &record.event,
+            Event::PullMerged { commit: id, files } if *id == commit && *files == ["notes/a.md"]
+        )),
+        "the pull was not recorded: {pulled:?}"
+    );
+    ensure!(
+        pulled.iter().all(|record| record.time().is_some()),
+        "an event has no time"
+    );
+
+    // One event ends the full file, the next starts the rotated one, both
+    // before the follower looks again.
+    let log = EventLog::new(laptop.config())?;
+    let mut stream = fs::read_to_string(log.path())?;
+    stream.push_str(&" ".repeat(MAX_BYTES as usize - stream.len() - 2));
+    stream.push('\n');
+    fs::write(log.path(), stream)?;
+    let (sender, followed) = mpsc::channel();
+    let follower = EventLog::new(laptop.config())?;
+    thread::spawn(move || follower.follow(|record| Ok(sender.send(record)?)));
+    thread::sleep(Duration::from_millis(200));
+    for branch in ["before", "after"] {
+        log.append(Event::PushOk {
+            branch: branch.to_string(),
+        })?;
+    }
+    let mut branches = Vec::new();
+    for _ in 0..2 {
+        match followed.recv_timeout(Duration::from_secs(5)) {
+            Ok(Record {
+                event: Event::PushOk { branch },
+                ..
+            }) => branches.push(branch),
+            other => bail!("the follower did not pass on the push: {other:?}"),
+        }
+    }
+    ensure!(
+        branches == ["before", "after"],
+        "the follower lost events across a rotation: {branches:?}"
+    );
+    Ok(())
+}
+
+/// `git.timeout_seconds` only gives up on a remote that went quiet: a
+/// clone or pull that keeps reporting progress runs past it, a silent one
+/// is killed.
+fn stalled_remote() -> Result<()> {
+    if cfg!(windows) {
+        // The slow git below is a shell script.
+        return Ok(());
+    }
+    let sandbox = Sandbox::new()?;
+    let slow_git = sandbox.root().join("slow-git");
+    fs::write(
+        &slow_git,
+        "#!/bin/sh\ncase \" $* \" in\n  *\" clone \"*|*\" pull \"*|*\" fetch \"*)\n    case \"$(cat \"$0.mode\")\" in\n      talk) for i in 1 2 3; do echo \"Receiving objects: $i\" >&2; sleep 1; done ;;\n      silent) sleep 30 ;;\n    esac ;;\nesac\nexec git \"$@\"\n",
+    )?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&slow_git, fs::Permissions::from_mode(0o755))?;
+    }
+    let mode = sandbox.root().join("slow-git.mode");
+    let slow = |config: &mut Config| {
+        config.git.executable = Some(slow_git.to_string_lossy().into_owned());
+        config.git.timeout_seconds = 2;
+    };
+
+    fs::write(&mode, "talk")?;
+    let started = std::time::Instant::now();
+    let mut phone = sandbox.device_with("phone", slow)?;
+    ensure!(
+        started.elapsed() >= Duration::from_secs(3),
+        "the slow clone was not slow"
+    );
+    phone.poll()?;
+
+    fs::write(&mode, "silent")?;
+    let stopped = |result: Result<()>| {
+        result.is_err_and(|err| format!("{err:#}").contains("the remote stopped responding"))
+    };
+    ensure!(
+        stopped(phone.poll()),
+        "a pull from a silent remote was not given up on"
+    );
+    ensure!(
+        stopped(sandbox.device_with("tablet", slow).map(|_| ())),
+        "a clone from a silent remote was not given up on"
+    );
+    Ok(())
+}
+
+/// `git.run_hooks`: a pre-commit hook found through `core.hooksPath`
+/// refuses the commit as a hook failure, and is skipped once it is off.
+fn hooks() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device_with("laptop", |config| config.git.run_hooks = true)?;
+    let hooks = sandbox.root().join("hooks");
+    fs::create_dir_all(&hooks)?;
+    let hook = hooks.join("pre-commit");
+    fs::write(
+        &hook,
+        "#!/bin/sh\nif git diff --cached | grep -q TODO; then echo 'TODO left in a note' >&2; exit 1; fi\n",
+    )?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+    }
+    git(
+        laptop.workdir(),
+        &["config", "core.hooksPath", &hooks.to_string_lossy()],
+    )?;
+    let head = laptop.head()?;
+    laptop.write("notes/a.md", "TODO\n")?;
+    match laptop.commit() {
+        Ok(_) => bail!("the pre-commit hook was not run"),
+        Err(err) => ensure!(
+            matches!(GitError::of(&err), GitError::Hook(_)),
+            "the refused commit failed with {err:#}"
+        ),
+    }
+    ensure!(laptop.head()? == head, "a refused commit was made");
+
+    laptop.reconfigure(|config| config.git.run_hooks = false)?;
+    ensure!(laptop.commit()?, "nothing was committed with hooks skipped");
+    ensure!(laptop.head()? != head, "the hook still ran");
+    Ok(())
+}
+
+/// `git.repo_config` is written into the vault's own config on setup, and
+/// a setting changed by hand afterwards is reported.
+fn repo_config() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let laptop = sandbox.device_with("laptop", |config| {
+        config
+            .git
+            .repo_config
+            .insert("user.name".to_string(), "Pinned Name".to_string());
+    })?;
+    for (key, value) in [
+        ("core.autocrlf", "false"),
+        ("pull.rebase", "true"),
+        ("user.name", "Pinned Name"),
+    ] {
+        let current = git(laptop.workdir(), &["config", "--local", "--get", key])?;
+        ensure!(current.trim() == value, "{key} is {current:?}, not {value}");
+    }
+    let repo = GitFacade::new(laptop.config())?;
+    ensure!(
+        repo.repo_config_drift().is_empty(),
+        "pinned settings reported as drifted"
+    );
+    git(laptop.workdir(), &["config", "core.autocrlf", "true"])?;
+    let drift = repo.repo_config_drift();
+    ensure!(
+        drift.len() == 1 && drift[0].key == "core.autocrlf",
+        "unexpected drift: {drift:?}"
+    );
+    Ok(())
+}
+
+/// `ignore.globs`: the last matching pattern wins, `!` re-includes, the
+/// builtin patterns hold regardless, and directories ignored as a whole
+/// are recognised as such.
+fn ignore_rules() -> Result<()> {
+    let root = Path::new("/vault");
+    let patterns = [
+        ".obsidian/**",
+        "!.obsidian/app.json",
+        "!.obsidian/plugins/**",
+        ".obsidian/plugins/*/data.json",
+        "!.git/config",
+        "\\!important.md",
+        "**/node_modules/**",
+    ]
+    .map(str::to_string);
+    let matcher = IgnoreMatcher::new(root, &patterns)?;
+    for (path, ignored) in [
+        (".obsidian/workspace.json", true),
+        (".obsidian/app.json", false),
+        (".obsidian/plugins/sync/main.js", false),
+        (".obsidian/plugins/sync/data.json", true),
+        (".git/config", true),
+        ("!important.md", true),
+        ("important.md", false),
+        ("notes/today.md", false),
+        ("plugins/x/node_modules/a/index.js", true),
+    ] {
+        ensure!(
+            matcher.should_ignore(root.join(path)) == ignored,
+            "{path} should {}be ignored",
+            if ignored { "" } else { "not " }
+        );
+    }
+    // Only directories nothing below can be re-included from are skipped
+    // whole.
+    for (dir, whole) in [
+        (".git", true),
+        ("plugins/x/node_modules", true),
+        (".obsidian", false),
+        (".obsidian/plugins", false),
+        ("notes", false),
+    ] {
+        ensure!(
+            matcher.ignores_dir(root.join(dir)) == whole,
+            "{dir} should {}be skipped whole",
+            if whole { "" } else { "not " }
+        );
+    }
+    let rules: Vec<&str> = matcher
+        .matching_rules(root.join(".obsidian/app.json"))
+        .into_iter()
+        .map(|rule| rule.pattern.as_str())
+        .collect();
+    ensure!(
+        rules == [".obsidian/**", "!.obsidian/app.json"],
+        "matching rules out of order: {rules:?}"
+    );
+    Ok(())
+}
+
+/// `ignore.max_file_size_mb` and `ignore.extensions_blocklist` keep files
+/// out of commits while the rest of the change goes through.
+fn blocked_files() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.ignore.max_file_size_mb = 1;
+        config.ignore.extensions_blocklist = vec![".MP4".to_string()];
+    })?;
+    laptop.write("notes/today.md", "hello\n")?;
+    laptop.write("media/clip.mp4", "not really a video\n")?;
+    laptop.write("media/scan.pdf", &"x".repeat(2 * 1024 * 1024))?;
+    ensure!(laptop.sync()?, "sync reported no changes");
+    ensure!(
+        sandbox.remote_file("notes/today.md").is_some(),
+        "the note did not reach the remote"
+    );
+    for blocked in ["media/clip.mp4", "media/scan.pdf"] {
+        ensure!(
+            sandbox.remote_file(blocked).is_none(),
+            "{blocked} was committed"
+        );
+    }
+    Ok(())
+}
+
+/// `security.secret_scan` holds back files that look like credentials, by
+/// name or content, and lists them for `status`.
+fn secret_scan() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    laptop.write("notes/today.md", "hello\n")?;
+    laptop.write(".env", "TOKEN=1\n")?;
+    laptop.write(".env.example", "TOKEN=\n")?;
+    laptop.write(
+        "notes/service.md",
+        "---\ntitle: Service\napi_key: 3f9a7c21d4e8b6a05f1c\n---\nSetup notes\n",
+    )?;
+    ensure!(laptop.sync()?, "sync reported no changes");
+    for (path, synced) in [
+        ("notes/today.md", true),
+        (".env.example", true),
+        (".env", false),
+        ("notes/service.md", false),
+    ] {
+        ensure!(
+            sandbox.remote_file(path).is_some() == synced,
+            "{path} should {}reach the remote",
+            if synced { "" } else { "not " }
+        );
+    }
+    let held = ControlInbox::new(laptop.config())?.held_secrets();
+    ensure!(
+        held == [".env", "notes/service.md"],
+        "held back files not recorded: {held:?}"
+    );
+
+    laptop.write(
+        "notes/service.md",
+        "---\ntitle: Service\napi_key: see the password manager\n---\nSetup notes\n",
+    )?;
+    laptop.sync()?;
+    ensure!(
+        sandbox.remote_file("notes/service.md").is_some(),
+        "the cleaned up note was not synced"
+    );
+    let held = ControlInbox::new(laptop.config())?.held_secrets();
+    ensure!(held == [".env"], "the record was not updated: {held:?}");
+    Ok(())
+}
+
+/// Lines tagged `#private` and listed frontmatter keys stay on the laptop
+/// while the rest of the note syncs, and survive a pull of the phone's edit.
+fn redaction() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.security.redaction.enabled = true;
+        config.security.redaction.frontmatter_keys = vec!["phone".to_string()];
+    })?;
+    let note = "---\ntitle: Contacts\nphone:\n  - 555 0100\n---\nAlice\nBob's door code 4821 #private\nCarol\n";
+    laptop.write("notes/contacts.md", note)?;
+    ensure!(laptop.sync()?, "sync reported no changes");
+    let published = "---\ntitle: Contacts\n---\nAlice\nCarol\n";
+    ensure!(
+        sandbox.remote_file("notes/contacts.md").as_deref() == Some(published),
+        "private lines reached the remote: {:?}",
+        sandbox.remote_file("notes/contacts.md")
+    );
+    ensure!(
+        laptop.read("notes/contacts.md").as_deref() == Some(note),
+        "the laptop's copy lost its private lines"
+    );
+
+    let mut phone = sandbox.device("phone")?;
+    phone.write(
+        "notes/contacts.md",
+        "---\ntitle: Contacts\n---\nAlice\nCarol\nDave\n",
+    )?;
+    ensure!(phone.sync()?, "the phone's edit was not synced");
+    laptop.write("notes/today.md", "hello\n")?;
+    laptop.sync()?;
+    let merged = "---\ntitle: Contacts\nphone:\n  - 555 0100\n---\nAlice\nBob's door code 4821 #private\nCarol\nDave\n";
+    ensure!(
+        laptop.read("notes/contacts.md").as_deref() == Some(merged),
+        "the pull dropped the private lines: {:?}",
+        laptop.read("notes/contacts.md")
+    );
+    ensure!(
+        !laptop.sync()?
+            && sandbox.remote_file("notes/contacts.md").as_deref()
+                == Some("---\ntitle: Contacts\n---\nAlice\nCarol\nDave\n"),
+        "the restored lines were committed"
+    );
+    Ok(())
+}
+
+/// `export-setup` leaves every secret written into the config out of the
+/// bundle and its `ogx1:` code, and keeps references to secrets.
+fn setup_secrets() -> Result<()> {
+    let mut config =
+        Config::parse("repo_url: https://example.invalid/vault.git\nworkdir: vault\n")?;
+    let secret = "plaintext-secret";
+    config.sync.pull_request.token = Some(format!("{secret}-token"));
+    config.remote.s3.access_key_id = Some(format!("{secret}-s3-key-id"));
+    config.remote.s3.secret_access_key = Some(format!("{secret}-s3-key"));
+    config.remote.webdav.password = Some(format!("{secret}-webdav"));
+    config.mqtt.password = Some(format!("{secret}-mqtt"));
+    config.alerts.email.password = Some(format!("{secret}-smtp"));
+    config.alerts.telegram.bot_token = Some(format!("{secret}-bot"));
+    config.alerts.discord.webhook_url =
+        Some(format!("https://discord.com/api/webhooks/1/{secret}"));
+    config.digest.webhook_url = Some(format!("https://hooks.slack.com/services/{secret}"));
+    let (bundle, notes) = SetupBundle::export(&config)?;
+    for contents in [
+        bundle.to_file_contents()?,
+        SetupBundle::read(&bundle.to_code()?)?.to_file_contents()?,
+    ] {
+        ensure!(
+            !contents.contains(secret),
+            "a secret went into the bundle:\n{contents}"
+        );
+    }
+    ensure!(
+        notes.stripped_secrets
+            == [
+                "sync.pull_request.token",
+                "remote.s3.access_key_id",
+                "remote.s3.secret_access_key",
+                "remote.webdav.password",
+                "mqtt.password",
+                "alerts.email.password",
+                "alerts.telegram.bot_token",
+                "alerts.discord.webhook_url",
+                "digest.webhook_url"
+            ],
+        "the left out secrets were not all named: {:?}",
+        notes.stripped_secrets
+    );
+
+    config.sync.pull_request.token = Some("!env:GITHUB_TOKEN".to_string());
+    let (bundle, notes) = SetupBundle::export(&config)?;
+    ensure!(
+        bundle.to_file_contents()?.contains("!env:GITHUB_TOKEN")
+            && !notes
+                .stripped_secrets
+                .iter()
+                .any(|key| key == "sync.pull_request.token"),
+        "a reference to a secret was left out"
+    );
+    Ok(())
+}
+
+/// `obsyncgit export` writes the vault as of an older commit, without
+/// files added since.
+fn export() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    laptop.write("todo.md", "- milk\n")?;
+    laptop.sync()?;
+    let first = laptop.head()?;
+    laptop.write("later.md", "added after the export point\n")?;
+    laptop.sync()?;
+
+    let git = GitFacade::new(laptop.config())?;
+    let zip = sandbox.root.join("snapshot.zip");
+    git.archive(&git.resolve_commit(Some(&first))?, "zip", "vault/", &zip)?;
+    let archive = fs::read(&zip)?;
+    let contains = |name: &str| {
+        archive
+            .windows(name.len())
+            .any(|window| window == name.as_bytes())
+    };
+    ensure!(
+        archive.starts_with(b"PK"),
+        "the export is not a zip archive"
+    );
+    ensure!(
+        contains("vault/todo.md") && !contains("later.md"),
+        "the export does not match the requested commit"
+    );
+
+    let tarball = sandbox.root.join("snapshot.tar.gz");
+    git.archive(&git.resolve_commit(None)?, "tar.gz", "vault/", &tarball)?;
+    ensure!(
+        fs::read(&tarball)?.starts_with(&[0x1f, 0x8b]),
+        "the export is not a gzip archive"
+    );
+    Ok(())
+}
+
+/// `obsyncgit import` brings in a folder with newer files winning, then a
+/// zip of older versions kept beside the vault's, each as one commit.
+fn import() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    laptop.write("todo.md", "- milk\n")?;
+    laptop.backdate("todo.md", Duration::from_secs(3600))?;
+    laptop.sync()?;
+
+    let folder = sandbox.root.join("phone-export");
+    fs::create_dir_all(folder.join("ideas"))?;
+    fs::write(folder.join("todo.md"), "- milk\n- eggs\n")?;
+    fs::write(folder.join("ideas/plan.md"), "plan\n")?;
+    fs::write(folder.join(".DS_Store"), "")?;
+    let git = GitFacade::new(laptop.config())?;
+    let report = import::import(&git, laptop.config(), &folder, OnCollision::Newer, false)?;
+    ensure!(
+        report.added == ["ideas/plan.md"] && report.updated == ["todo.md"],
+        "unexpected folder import: {report:?}"
+    );
+    ensure!(
+        laptop.status()?.lines().count() == 1 && report.commit.is_some(),
+        "the import was not committed: {}",
+        laptop.status()?
+    );
+
+    let archive = sandbox.root.join("old.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive)?);
+    let options = zip::write::SimpleFileOptions::default().last_modified_time(
+        zip::DateTime::from_date_and_time(2020, 1, 1, 12, 0, 0)
+            .map_err(|err| anyhow!("{err:?}"))?,
+    );
+    zip.start_file("vault/todo.md", options)?;
+    std::io::Write::write_all(&mut zip, b"- bread\n")?;
+    zip.start_file("vault/ideas/plan.md", options)?;
+    std::io::Write::write_all(&mut zip, b"plan\n")?;
+    zip.finish()?;
+    let report = import::import(&git, laptop.config(), &archive, OnCollision::Newer, true)?;
+    ensure!(
+        report.skipped == ["todo.md"] && report.unchanged == 1,
+        "an older file replaced a newer one: {report:?}"
+    );
+    let report = import::import(
+        &git,
+        laptop.config(),
+        &archive,
+        OnCollision::KeepBoth,
+        false,
+    )?;
+    ensure!(
+        report.copies == [("todo.md".to_string(), "todo (imported).md".to_string())]
+            && laptop.read("todo (imported).md").as_deref() == Some("- bread\n")
+            && laptop.read("todo.md").as_deref() == Some("- milk\n- eggs\n"),
+        "keep-both did not save the imported version beside the vault's: {report:?}"
+    );
+    ensure!(laptop.sync()?, "the import commits were not pushed");
+    ensure!(
+        sandbox.remote_file("todo (imported).md").is_some(),
+        "the imported copy did not reach the remote"
+    );
+    Ok(())
+}
+
+/// `replicas` get the vault's files after a sync and lose the ones it
+/// deletes, leaving files they did not put there alone; a replica on a
+/// missing drive is skipped.
+fn replicas() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let usb = sandbox.root.join("usb/vault");
+    let unmounted = sandbox.root.join("not-mounted/vault");
+    fs::create_dir_all(sandbox.root.join("usb"))?;
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.replicas = vec![
+            usb.to_string_lossy().into_owned(),
+            unmounted.to_string_lossy().into_owned(),
+        ];
+    })?;
+    laptop.write("todo.md", "- milk\n")?;
+    laptop.write("notes/idea.md", "idea\n")?;
+    laptop.sync()?;
+    laptop.mirror_replicas();
+    ensure!(
+        fs::read_to_string(usb.join("notes/idea.md"))? == "idea\n"
+            && fs::read_to_string(usb.join("todo.md"))? == "- milk\n",
+        "the replica did not get the vault's files"
+    );
+    ensure!(
+        !unmounted.exists(),
+        "a replica was created on a missing drive"
+    );
+    fs::write(usb.join("mine.txt"), "not from the vault\n")?;
+
+    laptop.write("todo.md", "- eggs\n")?;
+    fs::remove_file(laptop.workdir().join("notes/idea.md"))?;
+    laptop.sync()?;
+    laptop.mirror_replicas();
+    ensure!(
+        fs::read_to_string(usb.join("todo.md"))? == "- eggs\n",
+        "the replica did not get the edit"
+    );
+    ensure!(
+        !usb.join("notes").exists(),
+        "the deleted note stayed in the replica"
+    );
+    ensure!(
+        usb.join("mine.txt").exists(),
+        "a file the vault never had was deleted from the replica"
+    );
+
+    fs::write(usb.join("todo.md"), "- edited on the stick\n")?;
+    fs::remove_file(laptop.workdir().join("todo.md"))?;
+    laptop.write("other.md", "x\n")?;
+    laptop.sync()?;
+    laptop.mirror_replicas();
+    ensure!(
+        usb.join("todo.md").exists() && usb.join("other.md").exists(),
+        "a file changed in the replica was deleted"
+    );
+    Ok(())
+}
+
+/// Conflict copies made by Syncthing or Dropbox stay out of commits until
+/// one is chosen, and `sync.conflict_copies: newest` keeps the newer side.
+fn conflict_copies() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    laptop.write("notes/plan.md", "plan\n")?;
+    laptop.sync()?;
+    let syncthing = "notes/plan.sync-conflict-20240501-181500-ABCDEF1.md";
+    let dropbox = "todo (Bob's conflicted copy 2024-05-01).md";
+    laptop.write(syncthing, "plan from the phone\n")?;
+    laptop.write(dropbox, "- milk\n")?;
+    laptop.write("notes/other.md", "x\n")?;
+    laptop.sync()?;
+    ensure!(
+        sandbox.remote_file("notes/other.md").is_some()
+            && sandbox.remote_file(syncthing).is_none()
+            && sandbox.remote_file(dropbox).is_none(),
+        "conflict copies were committed"
+    );
+    let copies = conflict_copies::find(&[syncthing.to_string(), dropbox.to_string()]);
+    let originals: Vec<&str> = copies.iter().map(|copy| copy.original.as_str()).collect();
+    ensure!(
+        originals == ["notes/plan.md", "todo.md"],
+        "conflict copies not matched to their notes: {originals:?}"
+    );
+
+    conflict_copies::resolve(laptop.workdir(), &copies[0], Resolution::Theirs)?;
+    conflict_copies::resolve(laptop.workdir(), &copies[1], Resolution::Mine)?;
+    laptop.sync()?;
+    ensure!(
+        sandbox.remote_file("notes/plan.md").as_deref() == Some("plan from the phone\n")
+            && laptop.read(syncthing).is_none()
+            && laptop.read(dropbox).is_none(),
+        "choosing a version did not settle the copies"
+    );
+
+    laptop.reconfigure(|config| config.sync.conflict_copies = ConflictCopyPolicy::Newest)?;
+    laptop.write(syncthing, "stale\n")?;
+    laptop.backdate(syncthing, Duration::from_secs(3600))?;
+    laptop.write("notes/plan.md", "plan, edited\n")?;
+    laptop.sync()?;
+    ensure!(
+        laptop.read(syncthing).is_none()
+            && sandbox.remote_file("notes/plan.md").as_deref() == Some("plan, edited\n"),
+        "the newer note did not win over an older copy"
+    );
+    Ok(())
+}
+
+/// The digest counts the notes changed on any device since the period
+/// began, and a digest no channel takes keeps its period.
+fn digest() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut phone = sandbox.device("phone")?;
+    phone.write("seed.md", "seed\n")?;
+    phone.sync()?;
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.digest.enabled = true;
+        config.digest.desktop = false;
+    })?;
+    phone.write("a.md", "a\n")?;
+    phone.write("b.md", "b\n")?;
+    phone.sync()?;
+    laptop.write("c.md", "c\n")?;
+    laptop.sync()?;
+
+    let git = GitFacade::new(laptop.config())?;
+    let summary = digest::summarize(&git, laptop.config());
+    ensure!(
+        summary.notes_changed == 3,
+        "the digest counted {} notes changed instead of 3",
+        summary.notes_changed
+    );
+    ensure!(
+        digest::send(&git, laptop.config()).is_err(),
+        "a digest without a channel was reported as sent"
+    );
+    ensure!(
+        digest::load(laptop.config()).is_some_and(|tally| tally.sent_at.is_none()),
+        "an undelivered digest started a new period"
+    );
+    Ok(())
+}
+
+/// A finished day's last commit is tagged and pushed once, and another
+/// device picks the tag up instead of tagging the day again.
+fn snapshots() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    laptop.write("todo.md", "- milk\n")?;
+    laptop.sync()?;
+    laptop.write("todo.md", "- eggs\n")?;
+    laptop.sync()?;
+    let laptop_git = GitFacade::new(laptop.config())?;
+    let head = laptop_git.head_commit()?;
+
+    // Pretend the day of these commits is over.
+    let today = Local::now().date_naive();
+    let tomorrow = today.succ_opt().context("no tomorrow")?;
+    let created = snapshots::tag_finished_days(&laptop_git, tomorrow)?;
+    ensure!(
+        created == [snapshots::tag_name(today)],
+        "tagged {created:?} instead of today"
+    );
+    ensure!(
+        snapshots::commit(&laptop_git, today)? == head,
+        "the snapshot is not the day's last commit"
+    );
+
+    let mut phone = sandbox.device("phone")?;
+    phone.write("other.md", "x\n")?;
+    phone.sync()?;
+    let phone_git = GitFacade::new(phone.config())?;
+    let later = tomorrow.succ_opt().context("no day after tomorrow")?;
+    let created = snapshots::tag_finished_days(&phone_git, later)?;
+    ensure!(
+        snapshots::commit(&phone_git, today)? == head,
+        "the other device did not get the pushed snapshot"
+    );
+    ensure!(
+        created.is_empty(),
+        "the other device tagged {created:?} again"
+    );
+    Ok(())
+}
+
+/// `prune-history` drops attachment versions only old commits have, keeps
+/// the current one, and a device with a commit of its own follows the
+/// rewritten history without bringing the old one back or losing its
+/// stash.
+fn retention() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    // Two versions of a photo from years ago.
+    git(
+        sandbox.root(),
+        &["clone", "--quiet", path_str(&sandbox.remote)?, "old"],
+    )?;
+    let old = sandbox.root().join("old");
+    fs::create_dir_all(old.join("attachments"))?;
+    for version in ["v1", "v2"] {
+        fs::write(old.join("attachments/photo.png"), version)?;
+        git(&old, &["add", "-A"])?;
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=selftest",
+                "-c",
+                "user.email=selftest@invalid",
+            ])
+            .args(["commit", "--quiet", "-m", version])
+            .current_dir(&old)
+            .env("GIT_AUTHOR_DATE", "2020-01-01T12:00:00")
+            .env("GIT_COMMITTER_DATE", "2020-01-01T12:00:00")
+            .status()?;
+        ensure!(status.success(), "failed to commit {version}");
+    }
+    let stale = git(&old, &["rev-parse", "HEAD~1:attachments/photo.png"])?
+        .trim()
+        .to_string();
+    git(&old, &["push", "--quiet", "origin", "HEAD:main"])?;
+
+    let mut laptop = sandbox.device_with("laptop", |config| {
+        config.retention.paths = vec!["attachments/**".to_string()];
+    })?;
+    laptop.write("attachments/photo.png", "v3")?;
+    laptop.write("notes/today.md", "today\n")?;
+    laptop.sync()?;
+    let mut phone = sandbox.device("phone")?;
+    let commits = git(&sandbox.remote, &["rev-list", "--count", BRANCH])?;
+    phone.write("notes/today.md", "draft\n")?;
+    git(
+        &phone.workdir,
+        &[
+            "-c",
+            "user.name=selftest",
+            "-c",
+            "user.email=selftest@invalid",
+            "stash",
+            "push",
+            "--quiet",
+            "-m",
+            "draft",
+        ],
+    )?;
+    let old_tip = git(&phone.workdir, &["rev-parse", "HEAD"])?
+        .trim()
+        .to_string();
+
+    let git_laptop = GitFacade::new(laptop.config())?;
+    let pruned = retention::prune(&git_laptop, laptop.config(), |plan| {
+        Ok(plan.commits == 2 && plan.blobs.len() == 2)
+    })?;
+    ensure!(pruned.is_some(), "the prune did not plan to drop v1 and v2");
+    ensure!(
+        git(&sandbox.remote, &["rev-list", "--count", BRANCH])? == commits
+            && git(
+                &sandbox.remote,
+                &["rev-list", "--count", BRANCH, "--", "attachments/photo.png"]
+            )?
+            .trim()
+                == "1"
+            && sandbox.remote_file("attachments/photo.png").as_deref() == Some("v3"),
+        "the pruned history lost commits or kept old versions"
+    );
+    ensure!(
+        git(&laptop.workdir, &["cat-file", "-e", &stale]).is_err(),
+        "the pruning device kept the old version"
+    );
+
+    phone.write("phone.md", "phone\n")?;
+    phone.sync()?;
+    ensure!(
+        sandbox.remote_file("phone.md").is_some()
+            && git(
+                &sandbox.remote,
+                &["rev-list", "--count", BRANCH, "--", "attachments/photo.png"]
+            )?
+            .trim()
+                == "1",
+        "the other device brought the old history back"
+    );
+    ensure!(
+        !git(
+            &phone.workdir,
+            &["log", "--walk-reflogs", "--format=%H", "@{upstream}"]
+        )?
+        .contains(&old_tip),
+        "the other device's tracking branch still remembers the old history"
+    );
+    ensure!(
+        git(&phone.workdir, &["stash", "list"])?.contains("draft"),
+        "following the rewritten history dropped the other device's stash"
+    );
+    Ok(())
+}
+
+/// In a shared vault each member's commits carry their own name, a new
+/// note at the top goes into the member's folder, and a protected folder
+/// edited on a device that does not curate it stays as shared.
+fn shared_vault() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let mut ben = sandbox.device("ben")?;
+    ben.write("shared/recipes/soup.md", "leeks\n")?;
+    ben.sync()?;
+
+    let mut anna = sandbox.device_with("anna", |config| {
+        config.shared_vault.enabled = true;
+        config.shared_vault.user_folder = Some("people/anna".to_string());
+        config.shared_vault.protected_paths = vec!["shared/recipes/**".to_string()];
+    })?;
+    anna.write("shared/recipes/soup.md", "potatoes\n")?;
+    anna.write("idea.md", "a garden\n")?;
+    anna.write(".gitignore", "*.tmp\n")?;
+    ensure!(anna.sync()?, "sync reported no changes");
+    ensure!(
+        sandbox.remote_file("people/anna/idea.md").as_deref() == Some("a garden\n")
+            && sandbox.remote_file("idea.md").is_none()
+            && anna.read("idea.md").is_none(),
+        "the new note was not moved into the member's folder"
+    );
+    ensure!(
+        sandbox.remote_file(".gitignore").is_some(),
+        "a dot file at the top of the vault was moved or held back"
+    );
+    ensure!(
+        sandbox.remote_file("shared/recipes/soup.md").as_deref() == Some("leeks\n")
+            && anna.read("shared/recipes/soup.md").as_deref() == Some("potatoes\n"),
+        "the protected note was committed"
+    );
+    let author = git(
+        &sandbox.remote,
+        &[
+            "log",
+            "-1",
+            "--format=%an",
+            BRANCH,
+            "--",
+            "people/anna/idea.md",
+        ],
+    )?;
+    ensure!(
+        author.trim() == "selftest anna",
+        "the note was committed as {author:?}"
+    );
+
+    ben.write("shared/recipes/soup.md", "leeks, thyme\n")?;
+    ben.sync()?;
+    ensure!(
+        sandbox.remote_file("shared/recipes/soup.md").as_deref() == Some("leeks, thyme\n"),
+        "the curating member could not change the protected note"
+    );
+    Ok(())
+}
+
+/// A note edited on one device shows up as locked on the other once both
+/// shared their locks, and a device does not see its own.
+fn soft_locks() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let member = |name: &'static str| {
+        move |config: &mut Config| {
+            config.sync.device_name = Some(name.to_string());
+            config.shared_vault.enabled = true;
+            config.shared_vault.soft_locks = true;
+        }
+    };
+    let mut anna = sandbox.device_with("anna", member("anna"))?;
+    let mut ben = sandbox.device_with("ben", member("ben"))?;
+    anna.write("plans.md", "trip\n")?;
+    anna.sync()?;
+    anna.share_locks();
+    ben.share_locks();
+
+    let git_ben = GitFacade::new(ben.config())?;
+    let held = locks::current(&git_ben, ben.config())?;
+    ensure!(
+        held.len() == 1
+            && held[0].path == "plans.md"
+            && held[0].holder() == "selftest anna on anna",
+        "the other device does not see the lock: {held:?}"
+    );
+    let git_anna = GitFacade::new(anna.config())?;
+    ensure!(
+        locks::current(&git_anna, anna.config())?.is_empty(),
+        "a device sees its own locks as someone else's"
+    );
+    Ok(())
+}
+
+/// Each device notes its commits in `refs/notes/obsyncgit` and sees the
+/// other's notes after a sync, while the commit messages stay as they were.
+fn sync_notes() -> Result<()> {
+    let sandbox = Sandbox::new()?;
+    let noting = |name: &'static str| {
+        move |config: &mut Config| {
+            config.sync.device_name = Some(name.to_string());
+            config.commit.notes = true;
+        }
+    };
+    let mut laptop = sandbox.device_with("laptop", noting("laptop"))?;
+    let mut phone = sandbox.device_with("phone", noting("phone"))?;
+    laptop.write("a.md", "a\n")?;
+    laptop.write("b.md", "b\n")?;
+    laptop.sync()?;
+    laptop.share_notes();
+    phone.write("c.md", "c\n")?;
+    phone.sync()?;
+    phone.share_notes();
+    laptop.share_notes();
+
+    let git_phone = GitFacade::new(phone.config())?;
+    let noted: Vec<(String, usize)> = sync_notes::history(&git_phone, 10, None)?
+        .into_iter()
+        .filter_map(|entry| entry.note)
+        .map(|note| (note.device, note.files))
+        .collect();
+    ensure!(
+        noted == [("phone".to_string(), 1), ("laptop".to_string(), 2)],
+        "the phone did not merge the laptop's notes: {noted:?}"
+    );
+    let tip = git(&sandbox.remote, &["rev-parse", BRANCH])?;
+    let note = git(
+        &laptop.workdir,
+        &["notes", "--ref=obsyncgit", "show", tip.trim()],
+    )?;
+    ensure!(
+        note.contains("device: phone"),
+        "the laptop did not get the phone's note: {note:?}"
+    );
+    let message = git(&sandbox.remote, &["log", "-1", "--format=%B", BRANCH])?;
+    ensure!(
+        !message.contains("phone") && !message.contains("X-Obsync"),
+        "the metadata went into the commit message: {message:?}"
+    );
+    Ok(())
+}
+
+/// Every bundled translation parses, only has messages English has too, and
+/// picks the right plural form.
+fn locales() -> Result<()> {
+    let english: Vec<&str> = message_ids(i18n::LOCALES[0].1)?;
+    for (code, source) in &i18n::LOCALES[1..] {
+        for id in message_ids(source).with_context(|| format!("{code} locale"))? {
+            ensure!(
+                english.contains(&id),
+                "{code} has message {id} that English lacks"
+            );
+        }
+    }
+
+    let russian = Localizer::for_language("ru_RU.UTF-8");
+    ensure!(
+        russian.language() == "ru",
+        "ru_RU.UTF-8 did not pick Russian"
+    );
+    let mut args = FluentArgs::new();
+    args.set("count", 3);
+    let text = russian.format("cli-conflicts-resolved", Some(&args));
+    ensure!(
+        text.starts_with("Разрешено 3 конфликта"),
+        "wrong plural: {text}"
+    );
+    let fallback = Localizer::for_language("xx");
+    ensure!(
+        fallback.format("cli-conflicts-none", None) == "No conflicts.",
+        "unknown language did not fall back to English"
+    );
+    Ok(())
+}
+
+fn message_ids(source: &str) -> Result<Vec<&str>> {
+    let resource = fluent_syntax::parser::parse(source)
+        .map_err(|(_, errors)| anyhow!("invalid Fluent syntax: {errors:?}"))?;
+    Ok(resource
+        .body
+        .into_iter()
+        .filter_map(|entry| match entry {
+            fluent_syntax::ast::Entry::Message(message) => Some(message.id.name),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Failure modes users report, for `obsyncgit debug simulate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Simulation {
+    /// Both devices committed different files since their last sync.
+    Diverged,
+    /// Both devices changed the same line of a note.
+    Conflict,
+    /// The remote branch was rewritten by a force-push.
+    ForcePush,
+    /// A crashed git process left `.git/index.lock` behind.
+    IndexLock,
+}
+
+impl std::str::FromStr for Simulation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().replace('_', "-").to_ascii_lowercase().as_str() {
+            "diverged" => Ok(Self::Diverged),
+            "conflict" => Ok(Self::Conflict),
+            "force-push" => Ok(Self::ForcePush),
+            "index-lock" => Ok(Self::IndexLock),
+            other => anyhow::bail!(
+                "unknown scenario '{other}' (expected diverged, conflict, force-push or index-lock)"
+            ),
+        }
+    }
+}
+
+/// What happened when the daemon's sync cycle ran on the prepared state.
+pub struct SimulationOutcome {
+    pub sandbox: Sandbox,
+    pub device: Device,
+    /// Result of the recovering sync cycle.
+    pub recovery: Result<bool>,
+}
+
+/// Puts a `phone` device into the requested state and runs one sync cycle
+/// on it, the way the daemon would.
+pub fn simulate(scenario: Simulation) -> Result<SimulationOutcome> {
+    let sandbox = Sandbox::new()?;
+    let mut laptop = sandbox.device("laptop")?;
+    let mut phone = sandbox.device("phone")?;
+    laptop.write("shared.md", "base\n")?;
+    laptop.sync()?;
+    phone.write("phone.md", "first\n")?;
+    phone.sync()?;
+
+    match scenario {
+        Simulation::Diverged => {
+            laptop.write("laptop.md", "from laptop\n")?;
+            laptop.sync()?;
+            phone.write("phone.md", "from phone\n")?;
+        }
+        Simulation::Conflict => {
+            laptop.write("shared.md", "laptop version\n")?;
+            laptop.sync()?;
+            phone.write("shared.md", "phone version\n")?;
+        }
+        Simulation::ForcePush => {
+            sandbox.force_push("rewritten.md", "from a force-push\n")?;
+            phone.write("phone.md", "after the rewrite\n")?;
+        }
+        Simulation::IndexLock => {
+            let lock = phone.workdir().join(".git/index.lock");
+            fs::write(&lock, "")?;
+            fs::File::options()
+                .write(true)
+                .open(&lock)?
+                .set_modified(SystemTime::now() - Duration::from_secs(300))?;
+            phone.write("phone.md", "with a stale lock\n")?;
+        }
+    }
+
+    let recovery = phone.sync();
+    Ok(SimulationOutcome {
+        sandbox,
+        device: phone,
+        recovery,
+    })
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "user.name=selftest",
+            "-c",
+            "user.email=selftest@invalid",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .with_context(|| format!("non UTF-8 path {}", path.display()))
+}
@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// File in the state directory holding the most recent traced cycles.
+pub const TRACE_FILE: &str = "sync-trace.json";
+/// Longest stdout/stderr kept per command.
+const MAX_OUTPUT_BYTES: usize = 4096;
+
+/// One daemon sync cycle (commit, poll or recovery probe) and the git
+/// commands it ran.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceCycle {
+    pub started_at: String,
+    pub kind: String,
+    pub millis: u64,
+    pub outcome: String,
+    pub commands: Vec<TraceCommand>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceCommand {
+    pub args: String,
+    pub millis: u64,
+    pub code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Ring buffer of the last N cycles, persisted after each cycle so that
+/// `obsyncgit status --trace` can read it from another process.
+#[derive(Debug)]
+pub struct SyncTrace {
+    path: Utf8PathBuf,
+    capacity: usize,
+    state: Mutex<TraceState>,
+}
+
+#[derive(Debug, Default)]
+struct TraceState {
+    cycles: VecDeque<TraceCycle>,
+    current: Option<TraceCycle>,
+}
+
+impl SyncTrace {
+    pub fn new(state_dir: &Utf8Path, capacity: usize) -> Self {
+        Self {
+            path: state_dir.join(TRACE_FILE),
+            capacity: capacity.max(1),
+            state: Mutex::new(TraceState::default()),
+        }
+    }
+
+    pub fn begin_cycle(&self, kind: &str) {
+        let mut state = self.lock();
+        state.current = Some(TraceCycle {
+            started_at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            kind: kind.to_string(),
+            millis: 0,
+            outcome: String::new(),
+            commands: Vec::new(),
+        });
+    }
+
+    /// Records a git command; ignored outside a cycle.
+    pub fn record(&self, args: &str, elapsed: Duration, code: i32, stdout: &str, stderr: &str) {
+        let mut state = self.lock();
+        if let Some(cycle) = state.current.as_mut() {
+            cycle.commands.push(TraceCommand {
+                args: args.to_string(),
+                millis: elapsed.as_millis() as u64,
+                code,
+                stdout: truncate(stdout),
+                stderr: truncate(stderr),
+            });
+        }
+    }
+
+    pub fn end_cycle(&self, elapsed: Duration, outcome: &str) {
+        let mut state = self.lock();
+        let Some(mut cycle) = state.current.take() else {
+            return;
+        };
+        cycle.millis = elapsed.as_millis() as u64;
+        cycle.outcome = outcome.to_string();
+        if state.cycles.len() == self.capacity {
+            state.cycles.pop_front();
+        }
+        state.cycles.push_back(cycle);
+        if let Err(err) = self.save(&state.cycles) {
+            warn!(?err, "failed to write sync trace");
+        }
+    }
+
+    fn save(&self, cycles: &VecDeque<TraceCycle>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("failed to create {parent}"))?;
+        }
+        let rendered = serde_json::to_string_pretty(cycles)?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, rendered).with_context(|| format!("failed to write {tmp}"))?;
+        fs::rename(&tmp, &self.path).with_context(|| format!("failed to replace {}", self.path))?;
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TraceState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Reads the cycles persisted by a tracing daemon, oldest first.
+pub fn load(state_dir: &Utf8Path) -> Result<Vec<TraceCycle>> {
+    let path = state_dir.join(TRACE_FILE);
+    let contents = fs::read_to_string(&path).with_context(|| {
+        format!("no sync trace at {path}; start the daemon with `run --trace-sync`")
+    })?;
+    serde_json::from_str(&contents).with_context(|| format!("failed to parse {path}"))
+}
+
+fn truncate(output: &str) -> String {
+    let output = output.trim_end();
+    if output.len() <= MAX_OUTPUT_BYTES {
+        return output.to_string();
+    }
+    let mut end = MAX_OUTPUT_BYTES;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}… ({} bytes omitted)", &output[..end], output.len() - end)
+}
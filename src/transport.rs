@@ -0,0 +1,432 @@
+//! Remotes without a git server (`remote.kind: s3` or `webdav`). The
+//! repository is kept as a single `git bundle` object at `repo_url`; a copy
+//! of it in the state directory serves as the git remote, so fetches and
+//! pulls work unchanged, and a push uploads a new bundle in its place.
+//! Uploads only replace the version this device last downloaded, so two
+//! devices pushing at once get a rejected push and pull first, as with git.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{ETAG, IF_MATCH, IF_NONE_MATCH};
+use reqwest::{Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::config::{Config, RemoteKind, S3Options, WebdavOptions};
+use crate::git_error::GitError;
+use crate::instance;
+
+const USER_AGENT: &str = concat!("obsyncgit/", env!("CARGO_PKG_VERSION"));
+
+/// The bundle object at `repo_url` and its local copy.
+#[derive(Debug)]
+pub struct BundleRemote {
+    store: Store,
+    /// The local copy, which git knows as the remote's URL.
+    cache: PathBuf,
+    timeout: Option<Duration>,
+    /// Built on first use: a blocking client must not be created on an
+    /// async runtime's thread.
+    http: OnceLock<Client>,
+}
+
+#[derive(Debug)]
+enum Store {
+    S3(S3Store),
+    Webdav { url: Url, options: WebdavOptions },
+}
+
+#[derive(Debug)]
+struct S3Store {
+    /// Path-style object URL: `<endpoint>/<bucket>/<key>`.
+    url: Url,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl BundleRemote {
+    /// `None` for a plain git remote.
+    pub fn new(config: &Config) -> Result<Option<Self>> {
+        let store = match config.remote.kind {
+            RemoteKind::Git => return Ok(None),
+            RemoteKind::S3 => Store::S3(S3Store::new(&config.repo_url, &config.remote.s3)?),
+            RemoteKind::Webdav => {
+                let url = Url::parse(&config.repo_url)
+                    .with_context(|| format!("repo_url {} is not a URL", config.repo_url))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    bail!("repo_url must be an http:// or https:// URL for remote.kind webdav");
+                }
+                Store::Webdav {
+                    url,
+                    options: config.remote.webdav.clone(),
+                }
+            }
+        };
+        let cache = config
+            .state_dir()?
+            .join("remotes")
+            .join(format!("vault-{}.bundle", instance::vault_key(config)))
+            .into_std_path_buf();
+        let timeout = Some(config.git.timeout_seconds)
+            .filter(|seconds| *seconds > 0)
+            .map(Duration::from_secs);
+        Ok(Some(Self {
+            store,
+            cache,
+            timeout,
+            http: OnceLock::new(),
+        }))
+    }
+
+    /// The local copy of the bundle; missing while the remote has none.
+    pub fn cache(&self) -> &Path {
+        &self.cache
+    }
+
+    /// ETag of the version in [`Self::cache`].
+    fn cached_etag(&self) -> Option<String> {
+        if !self.cache.exists() {
+            return None;
+        }
+        fs::read_to_string(self.cache.with_extension("etag"))
+            .ok()
+            .filter(|etag| !etag.is_empty())
+    }
+
+    fn set_cached_etag(&self, etag: Option<&str>) {
+        let path = self.cache.with_extension("etag");
+        let result = match etag {
+            Some(etag) => fs::write(&path, etag),
+            None => fs::remove_file(&path).or_else(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(err),
+            }),
+        };
+        if let Err(err) = result {
+            debug!(?err, path = %path.display(), "failed to record the bundle's ETag");
+        }
+    }
+
+    /// Brings the local copy up to date with the remote, downloading only
+    /// when the bundle changed.
+    pub fn download(&self) -> Result<()> {
+        let mut request = self.request(Method::GET)?;
+        if let Some(etag) = self.cached_etag() {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        let response = self.send(request)?;
+        match response.status() {
+            StatusCode::NOT_MODIFIED => {
+                debug!("remote bundle unchanged");
+                Ok(())
+            }
+            StatusCode::NOT_FOUND => {
+                debug!("the remote has no bundle yet");
+                if self.cache.exists() {
+                    fs::remove_file(&self.cache)
+                        .with_context(|| format!("failed to remove {}", self.cache.display()))?;
+                }
+                self.set_cached_etag(None);
+                Ok(())
+            }
+            status if status.is_success() => {
+                let etag = header(&response, ETAG);
+                if let Some(parent) = self.cache.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("failed to create {}", parent.display()))?;
+                }
+                let tmp = self.cache.with_extension("bundle.part");
+                let mut file = fs::File::create(&tmp)
+                    .with_context(|| format!("failed to create {}", tmp.display()))?;
+                let mut response = response;
+                response.copy_to(&mut file).map_err(|err| {
+                    GitError::Network(format!("failed to download the bundle: {err}"))
+                })?;
+                drop(file);
+                fs::rename(&tmp, &self.cache)
+                    .with_context(|| format!("failed to replace {}", self.cache.display()))?;
+                self.set_cached_etag(etag.as_deref());
+                info!(url = %self.store.url(), "downloaded the remote bundle");
+                Ok(())
+            }
+            _ => Err(self.failure("download", response)),
+        }
+    }
+
+    /// Uploads `bundle` in place of the version last downloaded and makes
+    /// it the local copy. Fails with [`GitError::NonFastForward`] when
+    /// another device replaced the remote bundle in the meantime.
+    pub fn upload(&self, bundle: &Path) -> Result<()> {
+        let body = fs::File::open(bundle)
+            .with_context(|| format!("failed to open {}", bundle.display()))?;
+        let length = body.metadata()?.len();
+        let mut request = self
+            .request(Method::PUT)?
+            .header(reqwest::header::CONTENT_LENGTH, length)
+            .body(body);
+        request = match self.cached_etag() {
+            Some(etag) => request.header(IF_MATCH, etag),
+            None if self.cache.exists() => request,
+            None => request.header(IF_NONE_MATCH, "*"),
+        };
+        let response = self.send(request)?;
+        let status = response.status();
+        if status == StatusCode::PRECONDITION_FAILED {
+            return Err(GitError::NonFastForward(format!(
+                "! [rejected] (fetch first): {} was replaced by another device since it was fetched",
+                self.store.url()
+            ))
+            .into());
+        }
+        if !status.is_success() {
+            return Err(self.failure("upload", response));
+        }
+        let etag = header(&response, ETAG);
+        fs::rename(bundle, &self.cache)
+            .or_else(|_| fs::copy(bundle, &self.cache).map(|_| ()))
+            .with_context(|| format!("failed to replace {}", self.cache.display()))?;
+        // Without an ETag the next download fetches the bundle again.
+        self.set_cached_etag(etag.as_deref());
+        info!(url = %self.store.url(), "uploaded the bundle");
+        Ok(())
+    }
+
+    fn client(&self) -> Result<&Client> {
+        if let Some(client) = self.http.get() {
+            return Ok(client);
+        }
+        let mut builder = Client::builder().user_agent(USER_AGENT);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let client = builder.build().context("failed to build HTTP client")?;
+        Ok(self.http.get_or_init(|| client))
+    }
+
+    fn request(&self, method: Method) -> Result<RequestBuilder> {
+        let client = self.client()?;
+        Ok(match &self.store {
+            Store::Webdav { url, options } => {
+                let request = client.request(method, url.clone());
+                match &options.username {
+                    Some(username) => request.basic_auth(username, options.password.as_ref()),
+                    None => request,
+                }
+            }
+            Store::S3(store) => {
+                store.sign(client.request(method.clone(), store.url.clone()), &method)
+            }
+        })
+    }
+
+    fn send(&self, request: RequestBuilder) -> Result<Response> {
+        request.send().map_err(|err| {
+            GitError::Network(format!(
+                "failed to connect to {}: {}",
+                self.store.url(),
+                error_chain(&err)
+            ))
+            .into()
+        })
+    }
+
+    fn failure(&self, action: &str, response: Response) -> anyhow::Error {
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        let message = format!(
+            "failed to {action} {}: the server answered {status} {}",
+            self.store.url(),
+            body.trim().lines().next().unwrap_or_default()
+        );
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => GitError::Auth(message),
+            StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => GitError::Network(message),
+            _ => GitError::Unknown(message),
+        }
+        .into()
+    }
+}
+
+impl Store {
+    fn url(&self) -> &Url {
+        match self {
+            Store::S3(store) => &store.url,
+            Store::Webdav { url, .. } => url,
+        }
+    }
+}
+
+impl S3Store {
+    fn new(repo_url: &str, options: &S3Options) -> Result<Self> {
+        let Some((bucket, key)) = repo_url
+            .strip_prefix("s3://")
+            .and_then(|rest| rest.split_once('/'))
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        else {
+            bail!("repo_url must look like s3://bucket/path/vault.bundle for remote.kind s3");
+        };
+        let region = options
+            .region
+            .clone()
+            .unwrap_or_else(|| "us-east-1".to_string());
+        let endpoint = options
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com"));
+        let mut url = Url::parse(&endpoint)
+            .with_context(|| format!("remote.s3.endpoint {endpoint} is not a URL"))?;
+        if url.cannot_be_a_base() {
+            bail!("remote.s3.endpoint {endpoint} cannot hold a path");
+        }
+        // Signatures cover the path encoded as S3 does it, which escapes
+        // more than URLs need to.
+        let mut path = url.path().trim_end_matches('/').to_string();
+        for segment in std::iter::once(bucket).chain(key.split('/')) {
+            path.push('/');
+            path.push_str(&uri_encode(segment));
+        }
+        url.set_path(&path);
+        let access_key_id = options
+            .access_key_id
+            .clone()
+            .or_else(|| std::env::var("AWS_ACCESS_KEY_ID").ok())
+            .context("set remote.s3.access_key_id or AWS_ACCESS_KEY_ID")?;
+        let secret_access_key = options
+            .secret_access_key
+            .clone()
+            .or_else(|| std::env::var("AWS_SECRET_ACCESS_KEY").ok())
+            .context("set remote.s3.secret_access_key or AWS_SECRET_ACCESS_KEY")?;
+        Ok(Self {
+            url,
+            region,
+            access_key_id,
+            secret_access_key,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+
+    /// Adds an AWS Signature Version 4 to `request`. The body is left
+    /// unsigned, which S3 allows over HTTPS.
+    fn sign(&self, request: RequestBuilder, method: &Method) -> RequestBuilder {
+        const PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+        let now = Utc::now();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let host = match self.url.port() {
+            Some(port) => format!("{}:{port}", self.url.host_str().unwrap_or_default()),
+            None => self.url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", PAYLOAD.to_string()),
+            ("x-amz-date", timestamp.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+            .collect();
+        let canonical_request = format!(
+            "{method}\n{}\n\n{canonical_headers}\n{signed_headers}\n{PAYLOAD}",
+            self.url.path()
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut key = hmac(
+            format!("AWS4{}", self.secret_access_key).as_bytes(),
+            date.as_bytes(),
+        );
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key_id
+        );
+
+        let mut request = request.header("authorization", authorization);
+        // `host` is set by the client from the URL.
+        for (name, value) in headers.into_iter().skip(1) {
+            request = request.header(name, value);
+        }
+        request
+    }
+}
+
+/// HMAC-SHA256 (RFC 2104).
+fn hmac(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK: usize = 64;
+    let mut block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+fn uri_encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn header(response: &Response, name: reqwest::header::HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// `err` with its causes, which name what actually failed (DNS, TLS,
+/// refused connection).
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
@@ -0,0 +1,461 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use camino::Utf8PathBuf;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, List, ListItem, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::config::Config;
+use crate::control::Control;
+use crate::git::{CloneProgress, GitFacade};
+use crate::shutdown::Shutdown;
+
+/// Log lines kept for the log pane.
+const LOG_CAPACITY: usize = 500;
+/// How often the pending-change view re-reads `git status`.
+const STATUS_REFRESH: Duration = Duration::from_secs(2);
+/// Longest wait for a key before the screen is redrawn.
+const FRAME: Duration = Duration::from_millis(250);
+
+/// Destination for the daemon's log while the terminal is taken over by the
+/// TUI. Install it as the `tracing` writer; the monitor shows the tail.
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl LogBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tail(&self, count: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap_or_else(|err| err.into_inner());
+        let skip = lines.len().saturating_sub(count);
+        lines.iter().skip(skip).cloned().collect()
+    }
+
+    fn push(&self, text: &str) {
+        let text = strip_ansi(text);
+        let mut lines = self.lines.lock().unwrap_or_else(|err| err.into_inner());
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            if lines.len() == LOG_CAPACITY {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBuffer {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogWriter {
+            buffer: self.clone(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// Collects one formatted event and appends it to the [`LogBuffer`] on drop.
+pub struct LogWriter {
+    buffer: LogBuffer,
+    pending: Vec<u8>,
+}
+
+impl io::Write for LogWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        self.buffer.push(&String::from_utf8_lossy(&self.pending));
+    }
+}
+
+/// Drops the colour codes the log formatter adds; they would be drawn as
+/// text inside the log pane.
+fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\u{1b}' {
+            // CSI sequences: ESC [ parameters final-byte
+            if chars.next() == Some('[') {
+                for ch in chars.by_ref() {
+                    if ch.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        plain.push(ch);
+    }
+    plain
+}
+
+/// Answers collected by the first-run form.
+#[derive(Debug, Clone, Default)]
+pub struct SetupAnswers {
+    pub repo_url: String,
+    pub workdir: Utf8PathBuf,
+    pub branch: String,
+    pub author_name: Option<String>,
+    pub author_email: Option<String>,
+}
+
+struct Field {
+    label: &'static str,
+    hint: &'static str,
+    value: String,
+    required: bool,
+}
+
+/// Asks for the settings a new configuration cannot do without, starting
+/// from `defaults`. Returns `None` when the user backs out with Esc.
+pub fn first_run(defaults: &Config) -> Result<Option<SetupAnswers>> {
+    let mut fields = [
+        Field {
+            label: "Remote URL",
+            hint: "git@github.com:you/vault.git or an HTTPS URL",
+            value: String::new(),
+            required: true,
+        },
+        Field {
+            label: "Vault folder",
+            hint: "cloned here on first start when it does not exist",
+            value: defaults.workdir.to_string(),
+            required: true,
+        },
+        Field {
+            label: "Branch",
+            hint: "branch every device syncs",
+            value: defaults.branch.clone(),
+            required: true,
+        },
+        Field {
+            label: "Author name",
+            hint: "optional; defaults to git's user.name",
+            value: String::new(),
+            required: false,
+        },
+        Field {
+            label: "Author email",
+            hint: "optional; defaults to git's user.email",
+            value: String::new(),
+            required: false,
+        },
+    ];
+    let mut selected = 0;
+    let mut error: Option<String> = None;
+
+    let mut terminal = ratatui::init();
+    let result = loop {
+        if let Err(err) = terminal.draw(|frame| draw_setup(frame, &fields, selected, &error)) {
+            break Err(err).context("failed to draw the setup screen");
+        }
+        let key = match next_key(FRAME) {
+            Ok(Some(key)) => key,
+            Ok(None) => continue,
+            Err(err) => break Err(err),
+        };
+        match key.code {
+            KeyCode::Esc => break Ok(None),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break Ok(None),
+            KeyCode::Up | KeyCode::BackTab => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Tab => selected = (selected + 1).min(fields.len() - 1),
+            KeyCode::Backspace => {
+                fields[selected].value.pop();
+            }
+            KeyCode::Char(ch) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                fields[selected].value.push(ch);
+            }
+            KeyCode::Enter if selected + 1 < fields.len() => selected += 1,
+            KeyCode::Enter => {
+                if let Some(missing) = fields
+                    .iter()
+                    .position(|field| field.required && field.value.trim().is_empty())
+                {
+                    error = Some(format!("{} is required", fields[missing].label));
+                    selected = missing;
+                    continue;
+                }
+                let value = |index: usize| fields[index].value.trim().to_string();
+                let optional = |index: usize| Some(value(index)).filter(|text| !text.is_empty());
+                break Ok(Some(SetupAnswers {
+                    repo_url: value(0),
+                    workdir: Utf8PathBuf::from(value(1)),
+                    branch: value(2),
+                    author_name: optional(3),
+                    author_email: optional(4),
+                }));
+            }
+            _ => {}
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+fn draw_setup(frame: &mut Frame, fields: &[Field], selected: usize, error: &Option<String>) {
+    let [title, form, footer] = Layout::vertical([
+        Constraint::Length(2),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("ObsyncGit setup", Style::new().add_modifier(Modifier::BOLD)),
+            Span::raw("  no configuration found, let's create one"),
+        ])),
+        title,
+    );
+
+    let rows = Layout::vertical(fields.iter().map(|_| Constraint::Length(3))).split(form);
+    for (index, (field, area)) in fields.iter().zip(rows.iter()).enumerate() {
+        let style = if index == selected {
+            Style::new().fg(Color::Yellow)
+        } else {
+            Style::new()
+        };
+        let mut text = field.value.clone();
+        if index == selected {
+            text.push('▏');
+        }
+        let block = Block::bordered()
+            .title(format!(" {} ", field.label))
+            .title_bottom(Line::from(format!(" {} ", field.hint)).right_aligned())
+            .border_style(style);
+        frame.render_widget(Paragraph::new(text).block(block), *area);
+    }
+
+    let footer_text = match error {
+        Some(error) => Line::styled(error.as_str(), Style::new().fg(Color::Red)),
+        None => Line::raw("Tab/↓ next field · Enter on the last field saves · Esc cancels"),
+    };
+    frame.render_widget(Paragraph::new(footer_text), footer);
+}
+
+/// Live view of a daemon running in this process: pending changes, the log
+/// tail and keys to sync now or pause. Returns when the user quits or the
+/// daemon stops.
+pub fn monitor(
+    config: &Config,
+    control: &Control,
+    shutdown: &Shutdown,
+    logs: &LogBuffer,
+) -> Result<()> {
+    let git = GitFacade::observer(config)?;
+    let mut terminal = ratatui::init();
+    let result = run_monitor(&mut terminal, config, control, shutdown, logs, &git);
+    ratatui::restore();
+    result
+}
+
+#[derive(Debug, Default)]
+struct VaultStatus {
+    branch: String,
+    pending: Vec<String>,
+    error: Option<String>,
+}
+
+impl VaultStatus {
+    fn read(git: &GitFacade) -> Self {
+        let pending = git.list_pending_paths();
+        let branch = git.branch_status();
+        match (pending, branch) {
+            (Ok(pending), Ok(branch)) => Self {
+                branch,
+                pending,
+                error: None,
+            },
+            (Err(err), _) | (_, Err(err)) => Self {
+                error: Some(format!("{err:#}")),
+                ..Self::default()
+            },
+        }
+    }
+}
+
+fn run_monitor(
+    terminal: &mut DefaultTerminal,
+    config: &Config,
+    control: &Control,
+    shutdown: &Shutdown,
+    logs: &LogBuffer,
+    git: &GitFacade,
+) -> Result<()> {
+    let mut status = VaultStatus::default();
+    let mut refreshed_at: Option<Instant> = None;
+    while !shutdown.is_triggered() {
+        if refreshed_at.is_none_or(|at| at.elapsed() >= STATUS_REFRESH) {
+            status = VaultStatus::read(git);
+            refreshed_at = Some(Instant::now());
+        }
+        terminal
+            .draw(|frame| draw_monitor(frame, config, control, &status, logs))
+            .context("failed to draw the monitor")?;
+        let Some(key) = next_key(FRAME)? else {
+            continue;
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Char('s') => {
+                control.request_sync();
+                refreshed_at = None;
+            }
+            KeyCode::Char('p') => control.set_paused(!control.is_paused()),
+            KeyCode::Char('r') => refreshed_at = None,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn draw_monitor(
+    frame: &mut Frame,
+    config: &Config,
+    control: &Control,
+    status: &VaultStatus,
+    logs: &LogBuffer,
+) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [pending_area, log_area] =
+        Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(body);
+
+    let cloning = control.clone_progress();
+    let state = if cloning.is_some() {
+        Span::styled("cloning", Style::new().fg(Color::Cyan))
+    } else if control.is_paused() {
+        Span::styled("paused", Style::new().fg(Color::Yellow))
+    } else {
+        Span::styled("syncing", Style::new().fg(Color::Green))
+    };
+    let header_lines = vec![
+        Line::from(vec![
+            Span::styled("Vault   ", Style::new().add_modifier(Modifier::DIM)),
+            Span::raw(config.workdir.as_str()),
+            Span::raw("  ["),
+            state,
+            Span::raw("]"),
+        ]),
+        Line::from(vec![
+            Span::styled("Remote  ", Style::new().add_modifier(Modifier::DIM)),
+            Span::raw(format!("{} ({})", config.remote.name, config.repo_url)),
+        ]),
+        Line::from(vec![
+            Span::styled("Branch  ", Style::new().add_modifier(Modifier::DIM)),
+            Span::raw(status.branch.as_str()),
+        ]),
+    ];
+    frame.render_widget(Paragraph::new(header_lines), header);
+
+    match &cloning {
+        Some(progress) => draw_clone(frame, pending_area, progress),
+        None => draw_pending(frame, pending_area, status),
+    }
+
+    let visible = log_area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = logs.tail(visible).into_iter().map(Line::from).collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Log ")),
+        log_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new("s sync now · p pause/resume · r refresh · q quit")
+            .style(Style::new().add_modifier(Modifier::DIM)),
+        footer,
+    );
+}
+
+/// Shown instead of the pending changes while the vault is first cloned.
+fn draw_clone(frame: &mut Frame, area: Rect, progress: &CloneProgress) {
+    let block = Block::bordered().title(" Cloning the vault ");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let [phase_area, gauge_area, detail_area] =
+        Layout::vertical([Constraint::Length(1); 3]).areas(inner);
+    frame.render_widget(Paragraph::new(progress.phase.as_str()), phase_area);
+    frame.render_widget(
+        Gauge::default()
+            .gauge_style(Style::new().fg(Color::Green))
+            .percent(u16::from(progress.percent.min(100))),
+        gauge_area,
+    );
+    let mut detail = format!("{}/{} objects", progress.done, progress.total);
+    if let Some(received) = &progress.transferred {
+        detail.push_str(&format!(", {received}"));
+    }
+    if let Some(rate) = &progress.rate {
+        detail.push_str(&format!(" at {rate}"));
+    }
+    frame.render_widget(
+        Paragraph::new(detail).style(Style::new().add_modifier(Modifier::DIM)),
+        detail_area,
+    );
+}
+
+fn draw_pending(frame: &mut Frame, area: Rect, status: &VaultStatus) {
+    let title = format!(" Pending changes ({}) ", status.pending.len());
+    let block = Block::bordered().title(title);
+    if let Some(error) = &status.error {
+        frame.render_widget(
+            Paragraph::new(error.as_str())
+                .style(Style::new().fg(Color::Red))
+                .wrap(Wrap { trim: true })
+                .block(block),
+            area,
+        );
+        return;
+    }
+    if status.pending.is_empty() {
+        frame.render_widget(
+            Paragraph::new("Everything is committed.").block(block),
+            area,
+        );
+        return;
+    }
+    let items: Vec<ListItem> = status
+        .pending
+        .iter()
+        .map(|path| ListItem::new(path.as_str()))
+        .collect();
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+/// The next key press, or `None` when none arrived within `timeout`.
+fn next_key(timeout: Duration) -> Result<Option<KeyEvent>> {
+    if !event::poll(timeout).context("failed to read terminal input")? {
+        return Ok(None);
+    }
+    match event::read().context("failed to read terminal input")? {
+        Event::Key(key) if key.kind == KeyEventKind::Press => Ok(Some(key)),
+        _ => Ok(None),
+    }
+}
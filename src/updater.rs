@@ -1,14 +1,25 @@
-use std::process::Command;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use camino::{Utf8Path, Utf8PathBuf};
 use tracing::{debug, info, warn};
 
-use crate::config::SelfUpdateConfig;
+use reqwest::blocking::Client;
+use self_update::update::Release;
+use semver::Version;
+
+use crate::clock::{Deadline, SystemClock};
+use crate::config::{Config, NetworkConfig, SelfUpdateConfig, UpdateChannel, UpdateShell};
+use crate::network;
+use crate::shutdown::Shutdown;
+use crate::verify;
 
 const REPO_OWNER: &str = "GezzyDax";
 const REPO_NAME: &str = "ObsyncGit";
@@ -17,11 +28,18 @@ const BIN_NAME: &str = match option_env!("CARGO_BIN_NAME") {
     None => "obsyncgit",
 };
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+const SIGNATURE_ASSET: &str = "SHA256SUMS.minisig";
+const USER_AGENT: &str = concat!("obsyncgit/", env!("CARGO_PKG_VERSION"));
+/// Release signing key baked in by the release workflow.
+const EMBEDDED_PUBLIC_KEY: Option<&str> = option_env!("OBSYNCGIT_MINISIGN_PUBLIC_KEY");
 
 #[derive(Clone, Debug)]
 pub struct SelfUpdateManager {
     config: SelfUpdateConfig,
     config_path: Utf8PathBuf,
+    install_path: Option<PathBuf>,
+    network: NetworkConfig,
 }
 
 impl SelfUpdateManager {
@@ -29,105 +47,451 @@ impl SelfUpdateManager {
         Self {
             config: config.clone(),
             config_path: config_path.to_owned(),
+            install_path: None,
+            network: NetworkConfig::default(),
         }
     }
 
+    /// Downloads releases through `network.proxy`.
+    pub fn with_network(mut self, network: &NetworkConfig) -> Self {
+        self.network = network.clone();
+        self
+    }
+
+    /// Installs updates to the `obsyncgit` binary at `path` instead of
+    /// replacing the running executable, for callers such as the desktop
+    /// helper that are not the daemon binary themselves.
+    pub fn installing_to(mut self, path: PathBuf) -> Self {
+        self.install_path = Some(path);
+        self
+    }
+
     pub fn spawn_if_enabled(
-        config: &SelfUpdateConfig,
+        config: &Config,
         config_path: &Utf8Path,
-        shutdown: Arc<AtomicBool>,
+        shutdown: Shutdown,
+        restart: Arc<AtomicBool>,
     ) -> Option<thread::JoinHandle<()>> {
-        if !config.enabled {
+        if !config.self_update.enabled {
             return None;
         }
-        Some(Self::new(config, config_path).spawn(shutdown))
+        Some(
+            Self::new(&config.self_update, config_path)
+                .with_network(&config.network)
+                .spawn(shutdown, restart),
+        )
     }
 
-    pub fn spawn(self, shutdown: Arc<AtomicBool>) -> thread::JoinHandle<()> {
+    /// Runs periodic update checks on a background thread. When a new binary
+    /// has been installed and `restart_after_update` is set, `restart` is
+    /// raised together with `shutdown` so the daemon winds down after its
+    /// current cycle and the caller can re-exec via [`restart_daemon`].
+    pub fn spawn(self, shutdown: Shutdown, restart: Arc<AtomicBool>) -> thread::JoinHandle<()> {
         let interval_hours = self.config.interval_hours.unwrap_or(24).max(1);
         let sleep_interval = Duration::from_secs(interval_hours * 3600);
         thread::Builder::new()
             .name("obsyncgit-self-update".to_string())
             .spawn(move || {
                 debug!(path = %self.config_path, "self-update worker started");
-                if let Err(err) = self.check_now(false) {
-                    warn!(?err, "initial self-update check failed");
+                let request_restart = |updated: bool| {
+                    if updated && self.config.restart_after_update {
+                        info!("restarting daemon to run the updated binary");
+                        restart.store(true, Ordering::SeqCst);
+                        shutdown.trigger();
+                        return true;
+                    }
+                    false
+                };
+                match self.check_now(false) {
+                    Ok(updated) => {
+                        if request_restart(updated) {
+                            return;
+                        }
+                    }
+                    Err(err) => warn!(?err, "initial self-update check failed"),
                 }
                 loop {
                     if sleep_interval == Duration::from_secs(0) {
                         break;
                     }
-                    let target = Instant::now() + sleep_interval;
-                    while Instant::now() < target {
-                        if shutdown.load(Ordering::SeqCst) {
+                    // Counts time asleep too; an `Instant` would put the
+                    // check off by every suspend in between.
+                    let next_check = Deadline::after(&SystemClock, sleep_interval);
+                    while !next_check.is_due(&SystemClock) {
+                        if shutdown.is_triggered() {
                             debug!("self-update worker stopping");
                             return;
                         }
-                        let now = Instant::now();
-                        if now >= target {
-                            break;
-                        }
-                        let remaining = target - now;
-                        thread::sleep(remaining.min(Duration::from_secs(60)));
+                        thread::sleep(
+                            next_check
+                                .remaining(&SystemClock)
+                                .min(Duration::from_secs(60)),
+                        );
                     }
-                    if shutdown.load(Ordering::SeqCst) {
+                    if shutdown.is_triggered() {
                         debug!("self-update worker stopping");
                         return;
                     }
-                    if let Err(err) = self.check_now(false) {
-                        warn!(?err, "scheduled self-update check failed");
+                    match self.check_now(false) {
+                        Ok(updated) => {
+                            if request_restart(updated) {
+                                return;
+                            }
+                        }
+                        Err(err) => warn!(?err, "scheduled self-update check failed"),
                     }
                 }
             })
             .expect("self-update worker thread")
     }
 
-    pub fn check_now(&self, force: bool) -> Result<()> {
+    /// Returns whether a new binary was installed. Custom update commands are
+    /// opaque, so they never report an update.
+    pub fn check_now(&self, force: bool) -> Result<bool> {
         if force {
             debug!("forced self-update check requested");
         }
-        if let Some(cmd) = &self.config.command {
-            run_custom_command(cmd, force)
+        if self.config.command.is_some() || self.config.command_args.is_some() {
+            run_custom_command(&self.config).map(|()| false)
         } else {
             self.run_default_updater()
         }
     }
 
-    fn run_default_updater(&self) -> Result<()> {
-        let status = self_update::backends::github::Update::configure()
+    fn run_default_updater(&self) -> Result<bool> {
+        let Some(release) = self.select_release()? else {
+            debug!(
+                version = CURRENT_VERSION,
+                channel = ?self.config.channel,
+                "obsyncgit already up to date"
+            );
+            return Ok(false);
+        };
+
+        let target = self_update::get_target();
+        let asset = release
+            .asset_for(target, None)
+            .with_context(|| format!("release {} has no asset for {target}", release.version))?;
+        let http = http_client(&self.network)?;
+        let tmp_dir = self_update::TempDir::new().context("failed to create download directory")?;
+        let archive_path = tmp_dir.path().join(&asset.name);
+        let archive = fs::File::create(&archive_path)
+            .with_context(|| format!("failed to create {}", archive_path.display()))?;
+        download_release_file(&http, &release, &asset.name, archive)?;
+        self.verify_download(&http, &release, &asset.name, &archive_path)?;
+
+        let bin_name = format!("{BIN_NAME}{}", std::env::consts::EXE_SUFFIX);
+        self_update::Extract::from_source(&archive_path)
+            .extract_file(tmp_dir.path(), &bin_name)
+            .context("failed to extract release archive")?;
+        let extracted = tmp_dir.path().join(&bin_name);
+        match &self.install_path {
+            Some(path) => install_binary(&extracted, path)?,
+            None => self_update::self_replace::self_replace(&extracted)
+                .context("failed to replace running binary")?,
+        }
+
+        info!(version = %release.version, "obsyncgit updated to new version");
+        debug!(path = %self.config_path, "self-update check complete");
+        Ok(true)
+    }
+
+    /// Checks the downloaded archive against the release's `SHA256SUMS`, and
+    /// that file against its minisign signature. Without a signing key the
+    /// update is refused unless `allow_unsigned` is set.
+    fn verify_download(
+        &self,
+        http: &Client,
+        release: &Release,
+        asset_name: &str,
+        archive: &Path,
+    ) -> Result<()> {
+        if !release
+            .assets
+            .iter()
+            .any(|asset| asset.name == CHECKSUMS_ASSET)
+        {
+            bail!(
+                "release {} does not publish {CHECKSUMS_ASSET}; refusing to install an unverified binary",
+                release.version
+            );
+        }
+        let mut sums = Vec::new();
+        download_release_file(http, release, CHECKSUMS_ASSET, &mut sums)?;
+
+        match self.public_key() {
+            Some(key) => {
+                let mut signature = Vec::new();
+                download_release_file(http, release, SIGNATURE_ASSET, &mut signature)
+                    .context("release signature is missing")?;
+                verify::verify_minisign(&key, &sums, &String::from_utf8_lossy(&signature))
+                    .context("release signature verification failed")?;
+                debug!("release signature verified");
+            }
+            None if self.config.allow_unsigned => {
+                warn!("no release signing key configured; verifying checksums without a signature")
+            }
+            None => bail!(
+                "no release signing key is known, so release {} cannot be verified; set self_update.public_key, or self_update.allow_unsigned to trust the checksums alone",
+                release.version
+            ),
+        }
+
+        let sums = String::from_utf8(sums).context("checksum file is not valid UTF-8")?;
+        let expected = verify::expected_digest(&sums, asset_name)?;
+        let actual = verify::sha256_file(archive)?;
+        if expected != actual {
+            bail!("checksum mismatch for {asset_name}: expected {expected}, got {actual}");
+        }
+        debug!(asset = asset_name, "release checksum verified");
+        Ok(())
+    }
+
+    fn public_key(&self) -> Option<String> {
+        self.config
+            .public_key
+            .clone()
+            .filter(|key| !key.trim().is_empty())
+            .or_else(|| {
+                EMBEDDED_PUBLIC_KEY
+                    .filter(|key| !key.trim().is_empty())
+                    .map(str::to_string)
+            })
+    }
+
+    /// Picks the release to install: the pinned version if one is set,
+    /// otherwise the newest release on the configured channel that is newer
+    /// than the running binary.
+    fn select_release(&self) -> Result<Option<Release>> {
+        let releases = self_update::backends::github::ReleaseList::configure()
             .repo_owner(REPO_OWNER)
             .repo_name(REPO_NAME)
-            .bin_name(BIN_NAME)
-            .current_version(CURRENT_VERSION)
+            .with_target(self_update::get_target())
             .build()
-            .context("failed to configure GitHub self-update")?
-            .update()
-            .context("failed to execute GitHub self-update")?;
+            .context("failed to configure GitHub release listing")?
+            .fetch()
+            .context("failed to fetch GitHub releases")?;
+        let current = Version::parse(CURRENT_VERSION).context("invalid current version")?;
 
-        match status {
-            self_update::Status::Updated(version) => {
-                info!(%version, "obsyncgit updated to new version");
+        if let Some(pin) = &self.config.pin_version {
+            let pin = pin.trim().trim_start_matches('v');
+            let pinned =
+                Version::parse(pin).with_context(|| format!("invalid pinned version '{pin}'"))?;
+            if pinned == current {
+                return Ok(None);
             }
-            self_update::Status::UpToDate(version) => {
-                debug!(%version, "obsyncgit already up to date");
+            let release = releases
+                .into_iter()
+                .find(|release| release.version == pin)
+                .with_context(|| {
+                    format!("pinned version {pin} has no release for this platform")
+                })?;
+            return Ok(Some(release));
+        }
+
+        Ok(releases
+            .into_iter()
+            .filter_map(|release| {
+                let version = Version::parse(&release.version).ok()?;
+                (version > current && self.accepts(&version)).then_some((version, release))
+            })
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, release)| release))
+    }
+
+    fn accepts(&self, version: &Version) -> bool {
+        if version.pre.is_empty() || self.config.allow_prerelease {
+            return true;
+        }
+        match self.config.channel {
+            UpdateChannel::Stable => false,
+            UpdateChannel::Beta => {
+                let pre = version.pre.as_str().to_ascii_lowercase();
+                pre.starts_with("beta") || pre.starts_with("rc")
             }
         }
-        debug!(path = %self.config_path, "self-update check complete");
-        Ok(())
     }
 }
 
-fn run_custom_command(command: &str, _force: bool) -> Result<()> {
-    info!(%command, "running custom self-update command");
-    let status = Command::new("sh")
-        .arg("-c")
-        .arg(command)
-        .status()
-        .with_context(|| format!("failed to spawn self-update command: {command}"))?;
-    if status.success() {
-        info!("custom self-update command finished successfully");
+/// Replaces the current process with a fresh run of `exe`, started with
+/// the same arguments, so the configuration file and flags such as
+/// `--trace-sync` carry over. On Unix this `exec`s in place; elsewhere the
+/// new daemon is spawned and the caller is expected to exit.
+pub fn restart_daemon(exe: &Path) -> Result<()> {
+    let mut cmd = Command::new(exe);
+    // The vault is released before the relaunch, so there is nothing to
+    // take over.
+    cmd.args(
+        std::env::args_os()
+            .skip(1)
+            .filter(|arg| arg != "--takeover"),
+    );
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        let err = cmd.exec();
+        Err(err).with_context(|| format!("failed to exec {}", exe.display()))
+    }
+
+    #[cfg(not(unix))]
+    {
+        cmd.spawn()
+            .with_context(|| format!("failed to spawn {}", exe.display()))?;
         Ok(())
-    } else {
-        Err(anyhow!("self-update command exited with status {}", status))
+    }
+}
+
+/// Puts `source` in place of the binary at `target`. The new file is staged
+/// next to the target first so the swap is a rename.
+fn install_binary(source: &Path, target: &Path) -> Result<()> {
+    let staged = target.with_extension("update");
+    fs::copy(source, &staged)
+        .with_context(|| format!("failed to stage update at {}", staged.display()))?;
+    // Windows refuses to overwrite an executable that is running, but lets
+    // it be renamed out of the way.
+    #[cfg(windows)]
+    if target.exists() {
+        let old = target.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(target, &old)
+            .with_context(|| format!("failed to move {} aside", target.display()))?;
+    }
+    fs::rename(&staged, target)
+        .with_context(|| format!("failed to install update to {}", target.display()))?;
+    Ok(())
+}
+
+fn download_release_file<W: Write>(
+    http: &Client,
+    release: &Release,
+    name: &str,
+    mut dest: W,
+) -> Result<()> {
+    let url = format!(
+        "https://github.com/{REPO_OWNER}/{REPO_NAME}/releases/download/v{}/{name}",
+        release.version
+    );
+    debug!(%url, "downloading release file");
+    let mut response = http
+        .get(&url)
+        .header(reqwest::header::ACCEPT, "application/octet-stream")
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("failed to download {url}"))?;
+    response
+        .copy_to(&mut dest)
+        .with_context(|| format!("failed to download {url}"))?;
+    Ok(())
+}
+
+/// The client release files are downloaded with; it goes through the
+/// configured proxy, SOCKS included.
+fn http_client(network: &NetworkConfig) -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(30));
+    if let Some(proxy) = network::http_proxy(network)? {
+        builder = builder.proxy(proxy);
+    }
+    builder.build().context("failed to build HTTP client")
+}
+
+fn run_custom_command(config: &SelfUpdateConfig) -> Result<()> {
+    let mut cmd = match (&config.command_args, &config.command) {
+        (Some(argv), _) if !argv.is_empty() => {
+            let mut cmd = Command::new(&argv[0]);
+            cmd.args(&argv[1..]);
+            cmd
+        }
+        (_, Some(command)) => shell_command(config.shell.unwrap_or_default(), command),
+        _ => return Err(anyhow!("no custom self-update command configured")),
+    };
+    let timeout = Duration::from_secs(config.command_timeout_seconds.max(1));
+    info!(command = ?cmd, "running custom self-update command");
+
+    // In its own process group so a timeout also takes down whatever the
+    // shell started.
+    let mut child = crate::git::spawn_group(cmd.stdin(Stdio::null()))
+        .with_context(|| format!("failed to spawn self-update command: {cmd:?}"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .map(|pipe| thread::spawn(move || log_lines(pipe, false)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|pipe| thread::spawn(move || log_lines(pipe, true)));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child
+            .try_wait()
+            .context("failed to wait for self-update command")?
+        {
+            break Some(status);
+        }
+        if Instant::now() >= deadline {
+            crate::git::kill_tree(&mut child);
+            break None;
+        }
+        thread::sleep(Duration::from_millis(200));
+    };
+    // After a kill a surviving grandchild may still hold the pipes open;
+    // the readers finish on their own once it goes away.
+    if status.is_some() {
+        for reader in [stdout, stderr].into_iter().flatten() {
+            let _ = reader.join();
+        }
+    }
+
+    match status {
+        Some(status) if status.success() => {
+            info!("custom self-update command finished successfully");
+            Ok(())
+        }
+        Some(status) => Err(anyhow!("self-update command exited with status {}", status)),
+        None => Err(anyhow!(
+            "self-update command timed out after {}",
+            humantime::format_duration(timeout)
+        )),
+    }
+}
+
+pub(crate) fn shell_command(shell: UpdateShell, command: &str) -> Command {
+    match shell {
+        UpdateShell::Sh => {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(command);
+            cmd
+        }
+        UpdateShell::Cmd => {
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C");
+            // cmd.exe parses its own command line, so the MSVCRT quoting
+            // `arg` applies would mangle commands that contain quotes.
+            #[cfg(windows)]
+            std::os::windows::process::CommandExt::raw_arg(&mut cmd, command);
+            #[cfg(not(windows))]
+            cmd.arg(command);
+            cmd
+        }
+        UpdateShell::Powershell => {
+            let mut cmd = Command::new("powershell");
+            cmd.args(["-NoProfile", "-NonInteractive", "-Command", command]);
+            cmd
+        }
+    }
+}
+
+fn log_lines(pipe: impl Read, is_stderr: bool) {
+    for line in BufReader::new(pipe).lines() {
+        let Ok(line) = line else { break };
+        if is_stderr {
+            warn!(stderr = %line, "self-update command output");
+        } else {
+            info!(stdout = %line, "self-update command output");
+        }
     }
 }
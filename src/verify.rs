@@ -0,0 +1,114 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+/// Minisign algorithm tag for signatures over the raw message (`minisign -S -l`).
+const ALG_ED25519: &[u8; 2] = b"Ed";
+/// Minisign algorithm tag for BLAKE2b-prehashed signatures (the default mode).
+const ALG_ED25519_PREHASHED: &[u8; 2] = b"ED";
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Looks up the digest for `file_name` in a `sha256sum`-style listing.
+pub fn expected_digest(sums: &str, file_name: &str) -> Result<String> {
+    sums.lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, name)| name.trim().trim_start_matches('*') == file_name)
+        .map(|(digest, _)| digest.trim().to_ascii_lowercase())
+        .ok_or_else(|| anyhow!("SHA256SUMS has no entry for {file_name}"))
+}
+
+/// Verifies a minisign signature file against `message`.
+///
+/// `public_key` accepts either the full `minisign.pub` contents or just its
+/// base64 key line. Only non-prehashed (`minisign -S -l`) signatures are
+/// supported.
+pub fn verify_minisign(public_key: &str, message: &[u8], signature: &str) -> Result<()> {
+    let key = decode_last_line(public_key).context("invalid minisign public key")?;
+    if key.len() != 42 || &key[..2] != ALG_ED25519 {
+        bail!("unsupported minisign public key format");
+    }
+    let key_id = &key[2..10];
+    let verifying_key =
+        VerifyingKey::from_bytes(key[10..42].try_into()?).context("invalid minisign public key")?;
+
+    let mut lines = signature.lines().filter(|line| !line.trim().is_empty());
+    let _untrusted = lines.next();
+    let sig_line = lines.next().context("signature file is truncated")?;
+    let trusted = lines
+        .next()
+        .and_then(|line| line.strip_prefix("trusted comment: "))
+        .context("signature file is missing its trusted comment")?;
+    let global_line = lines
+        .next()
+        .context("signature file is missing its global signature")?;
+
+    let sig = STANDARD
+        .decode(sig_line.trim())
+        .context("invalid signature encoding")?;
+    if sig.len() != 74 {
+        bail!("unexpected minisign signature length");
+    }
+    match &sig[..2] {
+        alg if alg == ALG_ED25519 => {}
+        alg if alg == ALG_ED25519_PREHASHED => {
+            bail!("prehashed minisign signatures are not supported; sign with `minisign -S -l`")
+        }
+        _ => bail!("unknown minisign signature algorithm"),
+    }
+    if &sig[2..10] != key_id {
+        bail!("signature was made with a different key");
+    }
+    let signature_bytes: [u8; 64] = sig[10..74].try_into()?;
+    verifying_key
+        .verify_strict(message, &Signature::from_bytes(&signature_bytes))
+        .map_err(|_| anyhow!("signature does not match the signed file"))?;
+
+    let global = STANDARD
+        .decode(global_line.trim())
+        .context("invalid global signature encoding")?;
+    let global: [u8; 64] = global
+        .as_slice()
+        .try_into()
+        .map_err(|_| anyhow!("unexpected global signature length"))?;
+    let mut signed_comment = signature_bytes.to_vec();
+    signed_comment.extend_from_slice(trusted.as_bytes());
+    verifying_key
+        .verify_strict(&signed_comment, &Signature::from_bytes(&global))
+        .map_err(|_| anyhow!("trusted comment signature is invalid"))?;
+    Ok(())
+}
+
+fn decode_last_line(text: &str) -> Result<Vec<u8>> {
+    let line = text
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+        .context("empty key")?;
+    Ok(STANDARD.decode(line)?)
+}
@@ -0,0 +1,529 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result, bail};
+use notify::{
+    Config as NotifyConfig, ErrorKind, Event, PollWatcher, RecommendedWatcher, RecursiveMode,
+    Watcher,
+};
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{debug, error, info, warn};
+
+use crate::config::WatcherBackend;
+use crate::ignore::IgnoreMatcher;
+use crate::shutdown::Shutdown;
+
+/// Longest wait between attempts to recreate a failed watcher.
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(300);
+/// `ENOSPC`, returned by `inotify_add_watch` when the watch limit is reached.
+const ENOSPC: i32 = 28;
+/// Below this many spare watches selective watching is not worth it.
+const MIN_SELECTIVE_WATCHES: usize = 16;
+/// Slowest scan of the directories that are not watched natively.
+const SELECTIVE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// Relevant paths changed.
+    Changed(Vec<PathBuf>),
+    Rescan,
+    Error(notify::Error),
+}
+
+/// How the supervisor currently watches the vault.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Native,
+    /// Native watches on the most recently active directories only, plus a
+    /// slow poll of the whole vault.
+    Selective,
+    Poll,
+}
+
+/// Owns the filesystem watcher and recreates it with backoff when the
+/// backend fails. When the OS watch budget is too small for the vault it
+/// watches selectively or polls instead.
+pub struct WatcherSupervisor {
+    root: PathBuf,
+    ignore: Arc<RwLock<IgnoreMatcher>>,
+    tx: UnboundedSender<WatchEvent>,
+    shutdown: Shutdown,
+    interval: Duration,
+    backend: WatcherBackend,
+    mode: Mode,
+    active: Option<Active>,
+    failures: u32,
+    restart_at: Option<Instant>,
+    /// Set while the vault is unavailable; no restarts are attempted.
+    suspended: bool,
+}
+
+enum Active {
+    /// Held only to keep the watcher alive.
+    Single {
+        _watcher: Box<dyn Watcher + Send>,
+    },
+    Selective(SelectiveWatcher),
+}
+
+impl WatcherSupervisor {
+    pub fn new(
+        root: PathBuf,
+        ignore: Arc<RwLock<IgnoreMatcher>>,
+        tx: UnboundedSender<WatchEvent>,
+        shutdown: Shutdown,
+        interval: Duration,
+        backend: WatcherBackend,
+    ) -> Self {
+        let mode = match backend {
+            WatcherBackend::Auto | WatcherBackend::Native => Mode::Native,
+            WatcherBackend::Selective => Mode::Selective,
+            WatcherBackend::Poll => Mode::Poll,
+        };
+        Self {
+            root,
+            ignore,
+            tx,
+            shutdown,
+            interval,
+            backend,
+            mode,
+            active: None,
+            failures: 0,
+            restart_at: None,
+            suspended: false,
+        }
+    }
+
+    /// Starts watching. Only a watch limit that cannot be worked around is
+    /// fatal; see [`WatcherBackend`].
+    pub fn start(&mut self) -> Result<()> {
+        if self.backend == WatcherBackend::Auto {
+            self.mode = self.mode_for_budget();
+        }
+        match self.create() {
+            Ok(active) => {
+                self.active = Some(active);
+                Ok(())
+            }
+            Err(err) if is_watch_limit(&err) => self.degrade(err),
+            Err(err) => Err(err).with_context(|| {
+                format!(
+                    "failed to start filesystem watcher on {}",
+                    self.root.display()
+                )
+            }),
+        }
+    }
+
+    /// When the next restart attempt is due, if the watcher is down.
+    pub fn restart_at(&self) -> Option<Instant> {
+        self.restart_at
+    }
+
+    /// Stops watching while the vault is unavailable.
+    pub fn suspend(&mut self) {
+        self.suspended = true;
+        self.active = None;
+        self.restart_at = None;
+    }
+
+    /// Starts watching again after [`Self::suspend`].
+    pub fn resume(&mut self) {
+        self.suspended = false;
+        self.failures = 0;
+        self.restart();
+    }
+
+    /// Lets selective watching follow where the user is working.
+    pub fn note_activity(&mut self, paths: &[PathBuf]) {
+        if let Some(Active::Selective(selective)) = self.active.as_mut() {
+            for path in paths {
+                selective.promote(path);
+            }
+        }
+    }
+
+    /// Handles an error the backend reported. One about a single path, such
+    /// as a folder deleted while it was being read, is only logged; the
+    /// watch limit or a failing backend tears the watcher down and schedules
+    /// a restart.
+    pub fn on_error(&mut self, err: notify::Error) {
+        if self.suspended {
+            return;
+        }
+        if !is_watch_limit(&err) && !self.is_backend_failure(&err) {
+            warn!(error = %err, paths = ?err.paths, "filesystem watcher could not follow a path");
+            return;
+        }
+        if is_watch_limit(&err) && self.mode != Mode::Poll {
+            self.active = None;
+            if let Err(err) = self.degrade(err) {
+                error!("{err:#}");
+                self.schedule_restart();
+            }
+            return;
+        }
+        warn!(error = %err, "filesystem watcher failed, restarting it");
+        self.active = None;
+        self.schedule_restart();
+    }
+
+    /// Recreates the watcher. Returns true on success, after which the
+    /// caller should rescan for changes missed in the meantime.
+    pub fn restart(&mut self) -> bool {
+        self.restart_at = None;
+        match self.create() {
+            Ok(active) => {
+                info!(mode = ?self.mode, "filesystem watcher restarted");
+                self.active = Some(active);
+                self.failures = 0;
+                true
+            }
+            Err(err) if is_watch_limit(&err) && self.mode != Mode::Poll => {
+                match self.degrade(err) {
+                    Ok(()) => {
+                        self.failures = 0;
+                        true
+                    }
+                    Err(err) => {
+                        error!("{err:#}");
+                        self.schedule_restart();
+                        false
+                    }
+                }
+            }
+            Err(err) => {
+                warn!(?err, "failed to restart filesystem watcher");
+                self.schedule_restart();
+                false
+            }
+        }
+    }
+
+    /// Whether `err` is about the backend rather than one path below the
+    /// vault: notify reports a broken event stream without paths, and one
+    /// naming the vault itself leaves nothing watched.
+    fn is_backend_failure(&self, err: &notify::Error) -> bool {
+        err.paths.is_empty() || err.paths.contains(&self.root)
+    }
+
+    fn schedule_restart(&mut self) {
+        let delay = Duration::from_secs(1u64 << self.failures.min(9)).min(MAX_RESTART_DELAY);
+        self.failures = self.failures.saturating_add(1);
+        self.restart_at = Some(Instant::now() + delay);
+    }
+
+    /// Picks native watching when the vault fits into the spare watch budget.
+    fn mode_for_budget(&self) -> Mode {
+        let Some(budget) = WatchBudget::current() else {
+            return Mode::Native;
+        };
+        let needed = count_directories(&self.root);
+        let available = budget.available();
+        if needed <= available {
+            return Mode::Native;
+        }
+        let mode = if available >= MIN_SELECTIVE_WATCHES {
+            Mode::Selective
+        } else {
+            Mode::Poll
+        };
+        warn!(
+            directories = needed,
+            available,
+            ?mode,
+            "vault has more directories than spare inotify watches. {RAISE_LIMIT_ADVICE}"
+        );
+        mode
+    }
+
+    /// Steps down from native to selective watching to polling after the
+    /// watch limit was hit.
+    fn degrade(&mut self, err: notify::Error) -> Result<()> {
+        let advice = watch_limit_advice();
+        if self.backend != WatcherBackend::Auto {
+            bail!("{err}. {advice}");
+        }
+        self.mode = match self.mode {
+            Mode::Native => match WatchBudget::current() {
+                Some(budget) if budget.available() >= MIN_SELECTIVE_WATCHES => Mode::Selective,
+                _ => Mode::Poll,
+            },
+            Mode::Selective | Mode::Poll => Mode::Poll,
+        };
+        warn!(mode = ?self.mode, "{err}. {advice} Switching to a cheaper watch strategy.");
+        self.active = Some(self.create()?);
+        Ok(())
+    }
+
+    fn create(&self) -> notify::Result<Active> {
+        let config = NotifyConfig::default().with_poll_interval(self.interval);
+        match self.mode {
+            Mode::Native => {
+                let mut watcher = RecommendedWatcher::new(self.handler(), config)?;
+                watcher.watch(&self.root, RecursiveMode::Recursive)?;
+                Ok(Active::Single {
+                    _watcher: Box::new(watcher),
+                })
+            }
+            Mode::Poll => {
+                let mut watcher = PollWatcher::new(self.handler(), config)?;
+                watcher.watch(&self.root, RecursiveMode::Recursive)?;
+                Ok(Active::Single {
+                    _watcher: Box::new(watcher),
+                })
+            }
+            Mode::Selective => {
+                let budget = WatchBudget::current()
+                    .map(|budget| budget.available())
+                    .unwrap_or_default()
+                    .max(MIN_SELECTIVE_WATCHES);
+                SelectiveWatcher::new(self, budget).map(Active::Selective)
+            }
+        }
+    }
+
+    fn handler(&self) -> impl Fn(notify::Result<Event>) + Send + 'static {
+        let ignore = self.ignore.clone();
+        let tx = self.tx.clone();
+        let shutdown = self.shutdown.clone();
+        // Paths already warned about, so a file being edited is reported once.
+        let mangled = Mutex::new(HashSet::new());
+        move |res: notify::Result<Event>| {
+            if shutdown.is_triggered() {
+                return;
+            }
+            match res {
+                Ok(event) => {
+                    let ignore = ignore.read().unwrap_or_else(|err| err.into_inner());
+                    let relevant: Vec<PathBuf> = event
+                        .paths
+                        .iter()
+                        .filter(|path| !ignore.should_ignore(path))
+                        .filter(|path| {
+                            if path.to_str().is_some() {
+                                return true;
+                            }
+                            let mut reported =
+                                mangled.lock().unwrap_or_else(|err| err.into_inner());
+                            if reported.insert((*path).clone()) {
+                                warn!(
+                                    path = %path.display(),
+                                    "file name is not valid UTF-8 and will not be synced; rename it"
+                                );
+                            }
+                            false
+                        })
+                        .cloned()
+                        .collect();
+                    if !relevant.is_empty() {
+                        let _ = tx.send(WatchEvent::Changed(relevant));
+                    }
+                    if event.need_rescan() {
+                        let _ = tx.send(WatchEvent::Rescan);
+                    }
+                }
+                Err(err) => {
+                    let _ = tx.send(WatchEvent::Error(err));
+                }
+            }
+        }
+    }
+}
+
+/// Native non-recursive watches on up to `budget` directories, most recently
+/// active first, backed by a slow poll of the whole vault that notices
+/// activity elsewhere.
+struct SelectiveWatcher {
+    root: PathBuf,
+    ignore: Arc<RwLock<IgnoreMatcher>>,
+    native: RecommendedWatcher,
+    _poller: PollWatcher,
+    /// Natively watched directories, least recently active first.
+    watched: VecDeque<PathBuf>,
+    budget: usize,
+}
+
+impl SelectiveWatcher {
+    fn new(supervisor: &WatcherSupervisor, budget: usize) -> notify::Result<Self> {
+        let poll_config = NotifyConfig::default()
+            .with_poll_interval(supervisor.interval.max(SELECTIVE_POLL_INTERVAL));
+        let mut poller = PollWatcher::new(supervisor.handler(), poll_config)?;
+        poller.watch(&supervisor.root, RecursiveMode::Recursive)?;
+        let native = RecommendedWatcher::new(supervisor.handler(), NotifyConfig::default())?;
+
+        let mut selective = Self {
+            root: supervisor.root.clone(),
+            ignore: supervisor.ignore.clone(),
+            native,
+            _poller: poller,
+            watched: VecDeque::new(),
+            budget,
+        };
+        let mut directories = selective.directories();
+        directories.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+        // Oldest first, so the most recent end up at the back of the queue.
+        for (dir, _) in directories.into_iter().take(budget).rev() {
+            selective.watch(dir)?;
+        }
+        info!(
+            watched = selective.watched.len(),
+            budget, "watching the most recently active directories natively"
+        );
+        Ok(selective)
+    }
+
+    fn promote(&mut self, path: &Path) {
+        let dir = if path.is_dir() {
+            path.to_path_buf()
+        } else {
+            match path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => return,
+            }
+        };
+        if !dir.starts_with(&self.root) || self.is_ignored(&dir) {
+            return;
+        }
+        if let Some(index) = self.watched.iter().position(|watched| *watched == dir) {
+            if let Some(dir) = self.watched.remove(index) {
+                self.watched.push_back(dir);
+            }
+            return;
+        }
+        if self.watched.len() >= self.budget
+            && let Some(oldest) = self.watched.pop_front()
+        {
+            let _ = self.native.unwatch(&oldest);
+        }
+        if let Err(err) = self.watch(dir) {
+            debug!(?err, "failed to watch active directory natively");
+        }
+    }
+
+    fn watch(&mut self, dir: PathBuf) -> notify::Result<()> {
+        self.native.watch(&dir, RecursiveMode::NonRecursive)?;
+        self.watched.push_back(dir);
+        Ok(())
+    }
+
+    /// Every directory of the vault outside ignored paths, with its mtime.
+    fn directories(&self) -> Vec<(PathBuf, SystemTime)> {
+        let mut found = Vec::new();
+        let mut pending = vec![self.root.clone()];
+        while let Some(dir) = pending.pop() {
+            let modified = std::fs::metadata(&dir)
+                .and_then(|meta| meta.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            if let Ok(entries) = std::fs::read_dir(&dir) {
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if entry.file_type().is_ok_and(|kind| kind.is_dir()) && !self.is_ignored(&path)
+                    {
+                        pending.push(path);
+                    }
+                }
+            }
+            found.push((dir, modified));
+        }
+        found
+    }
+
+    fn is_ignored(&self, dir: &Path) -> bool {
+        let ignore = self.ignore.read().unwrap_or_else(|err| err.into_inner());
+        ignore.ignores_dir(dir) || ignore.should_ignore(dir)
+    }
+}
+
+/// Inotify watches in use by the current user (Linux only).
+#[derive(Debug, Clone, Copy)]
+pub struct WatchBudget {
+    pub limit: usize,
+    pub used: usize,
+}
+
+impl WatchBudget {
+    #[cfg(target_os = "linux")]
+    pub fn current() -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+
+        let limit = std::fs::read_to_string("/proc/sys/fs/inotify/max_user_watches")
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        let uid = std::fs::metadata("/proc/self").ok()?.uid();
+        let mut used = 0;
+        for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+            let is_pid = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.bytes().all(|byte| byte.is_ascii_digit()));
+            if !is_pid || !entry.metadata().is_ok_and(|meta| meta.uid() == uid) {
+                continue;
+            }
+            let Ok(fds) = std::fs::read_dir(entry.path().join("fdinfo")) else {
+                continue;
+            };
+            for fd in fds.flatten() {
+                if let Ok(info) = std::fs::read_to_string(fd.path()) {
+                    used += info
+                        .lines()
+                        .filter(|line| line.starts_with("inotify wd:"))
+                        .count();
+                }
+            }
+        }
+        Some(Self { limit, used })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn current() -> Option<Self> {
+        None
+    }
+
+    /// Spare watches, keeping a quarter back for other applications.
+    pub fn available(&self) -> usize {
+        self.limit.saturating_sub(self.used) * 3 / 4
+    }
+}
+
+/// Directories a recursive native watch of `root` needs, `.git` included.
+pub fn count_directories(root: &Path) -> usize {
+    let mut count = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        count += 1;
+        if let Ok(entries) = std::fs::read_dir(&dir) {
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                    pending.push(entry.path());
+                }
+            }
+        }
+    }
+    count
+}
+
+pub const RAISE_LIMIT_ADVICE: &str = "Raise the limit with `sudo sysctl fs.inotify.max_user_watches=524288` and persist it in /etc/sysctl.d/.";
+
+fn is_watch_limit(err: &notify::Error) -> bool {
+    match &err.kind {
+        ErrorKind::MaxFilesWatch => true,
+        ErrorKind::Io(io) => io.raw_os_error() == Some(ENOSPC),
+        _ => false,
+    }
+}
+
+fn watch_limit_advice() -> String {
+    match std::fs::read_to_string("/proc/sys/fs/inotify/max_user_watches") {
+        Ok(limit) => format!(
+            "The inotify watch limit (fs.inotify.max_user_watches = {}) is exhausted. {RAISE_LIMIT_ADVICE}",
+            limit.trim()
+        ),
+        Err(_) => "The operating system's file watch limit is exhausted.".to_string(),
+    }
+}
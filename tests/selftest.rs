@@ -0,0 +1,20 @@
+//! Runs the `obsyncgit selftest` scenarios as part of `cargo test`. They go
+//! through the real binary because the redaction scenario has git invoke it
+//! as a filter.
+
+use std::process::Command;
+
+#[test]
+fn scenarios_pass() {
+    let output = Command::new(env!("CARGO_BIN_EXE_obsyncgit"))
+        .arg("selftest")
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("failed to run obsyncgit selftest");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "selftest failed:\n{stdout}\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}